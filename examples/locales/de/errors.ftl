@@ -0,0 +1,20 @@
+error-unauthorized = Nicht autorisiert
+error-forbidden = Verboten
+error-not-found = Nicht gefunden
+error-method-not-allowed = Methode nicht erlaubt
+error-too-many-requests = Zu viele Anfragen
+error-origin-not-allowed = Herkunft nicht erlaubt
+error-preflight-rejected = CORS-Preflight abgelehnt
+error-invalid-csrf-token = Ungültiges CSRF-Token
+error-unknown-api-version = Unbekannte API-Version: { $version }
+error-api-retired = API { $version } wurde eingestellt, verwenden Sie { $successor }
+error-api-retired-no-successor = API { $version } wurde eingestellt, verwenden Sie eine neuere Version
+error-user-not-found = Benutzer nicht gefunden
+error-invalid-user-record = Ungültiger Benutzerdatensatz
+error-invalid-job-id = Ungültige Job-ID
+error-job-not-found = Job nicht gefunden
+error-invalid-export-id = Ungültige Export-ID
+error-invalid-export-file-name = Ungültiger Exportdateiname
+error-export-not-found = Export nicht gefunden
+error-export-gone = Der Export ist nicht mehr verfügbar
+error-invalid-download-link = Ungültiger Download-Link
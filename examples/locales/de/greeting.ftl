@@ -0,0 +1 @@
+greeting = Hallo, { $name }! Willkommen beim konfigurierbaren MCP-Server.
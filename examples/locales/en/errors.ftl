@@ -0,0 +1,21 @@
+# Example 20: error messages of the API
+error-unauthorized = Unauthorized
+error-forbidden = Forbidden
+error-not-found = Not Found
+error-method-not-allowed = Method Not Allowed
+error-too-many-requests = Too Many Requests
+error-origin-not-allowed = Origin not allowed
+error-preflight-rejected = CORS preflight rejected
+error-invalid-csrf-token = Invalid CSRF token
+error-unknown-api-version = Unknown API version: { $version }
+error-api-retired = API { $version } has been retired, use { $successor }
+error-api-retired-no-successor = API { $version } has been retired, use a newer version
+error-user-not-found = User not found
+error-invalid-user-record = Invalid user record
+error-invalid-job-id = Invalid job ID
+error-job-not-found = Job not found
+error-invalid-export-id = Invalid export ID
+error-invalid-export-file-name = Invalid export file name
+error-export-not-found = Export not found
+error-export-gone = Export is no longer available
+error-invalid-download-link = Invalid download link
@@ -0,0 +1,2 @@
+# Example 06: the greeting tool
+greeting = Hello, { $name }! Welcome to the configurable MCP server.
@@ -0,0 +1,20 @@
+error-unauthorized = No autorizado
+error-forbidden = Prohibido
+error-not-found = No encontrado
+error-method-not-allowed = Método no permitido
+error-too-many-requests = Demasiadas solicitudes
+error-origin-not-allowed = Origen no permitido
+error-preflight-rejected = Solicitud CORS previa rechazada
+error-invalid-csrf-token = Token CSRF no válido
+error-unknown-api-version = Versión de la API desconocida: { $version }
+error-api-retired = La API { $version } se ha retirado, usa { $successor }
+error-api-retired-no-successor = La API { $version } se ha retirado, usa una versión más reciente
+error-user-not-found = Usuario no encontrado
+error-invalid-user-record = Registro de usuario no válido
+error-invalid-job-id = ID de trabajo no válido
+error-job-not-found = Trabajo no encontrado
+error-invalid-export-id = ID de exportación no válido
+error-invalid-export-file-name = Nombre de archivo de exportación no válido
+error-export-not-found = Exportación no encontrada
+error-export-gone = La exportación ya no está disponible
+error-invalid-download-link = Enlace de descarga no válido
@@ -0,0 +1 @@
+greeting = ¡Hola, { $name }! Bienvenido al servidor MCP configurable.
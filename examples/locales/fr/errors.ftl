@@ -0,0 +1,20 @@
+error-unauthorized = Non autorisé
+error-forbidden = Interdit
+error-not-found = Introuvable
+error-method-not-allowed = Méthode non autorisée
+error-too-many-requests = Trop de requêtes
+error-origin-not-allowed = Origine non autorisée
+error-preflight-rejected = Requête CORS préliminaire refusée
+error-invalid-csrf-token = Jeton CSRF invalide
+error-unknown-api-version = Version d’API inconnue : { $version }
+error-api-retired = L’API { $version } a été retirée, utilisez { $successor }
+error-api-retired-no-successor = L’API { $version } a été retirée, utilisez une version plus récente
+error-user-not-found = Utilisateur introuvable
+error-invalid-user-record = Enregistrement utilisateur invalide
+error-invalid-job-id = ID de tâche invalide
+error-job-not-found = Tâche introuvable
+error-invalid-export-id = ID d’export invalide
+error-invalid-export-file-name = Nom de fichier d’export invalide
+error-export-not-found = Export introuvable
+error-export-gone = L’export n’est plus disponible
+error-invalid-download-link = Lien de téléchargement invalide
@@ -0,0 +1 @@
+greeting = Bonjour, { $name } ! Bienvenue sur le serveur MCP configurable.
@@ -0,0 +1,332 @@
+//! The `#[mcp_tool]` attribute of `mcp_rust_examples`.
+//!
+//! Put on a request struct, it keeps the struct as written and implements
+//! `mcp_rust_examples::schema::TypedTool` for it: the tool's name,
+//! description and annotations, and an input schema built from the fields.
+//! Each field's schema comes from its type's `JsonSchema` impl, refined by an
+//! optional `#[tool(...)]` attribute on the field. See the `schema` module of
+//! `mcp_rust_examples` for the full list of options.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, ExprLit, Fields, ItemStruct, Lit, LitStr, Meta, MetaNameValue, Token};
+
+/// Derives a `TypedTool` impl for a request struct.
+///
+/// ```ignore
+/// #[mcp_tool(name = "read_file", description = "Read a text file", read_only)]
+/// #[derive(Deserialize)]
+/// pub struct ReadFileRequest {
+///     #[tool(description = "Path to the file to read")]
+///     pub file_path: String,
+///     #[tool(description = "Byte offset to resume from", default = 0)]
+///     pub offset: Option<usize>,
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn mcp_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(attr with Punctuated::<Meta, Token![,]>::parse_terminated);
+    let item = syn::parse_macro_input!(item as ItemStruct);
+    expand(args, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// Flags that pick the annotations, in the order they are applied
+const BASE_ANNOTATIONS: &[&str] = &["read_only", "additive", "destructive"];
+const EXTRA_ANNOTATIONS: &[&str] = &["idempotent", "open_world"];
+
+// Field options and the JSON Schema keywords they set
+const KEYWORDS: &[(&str, &str)] = &[
+    ("description", "description"),
+    ("default", "default"),
+    ("minimum", "minimum"),
+    ("maximum", "maximum"),
+    ("exclusive_minimum", "exclusiveMinimum"),
+    ("exclusive_maximum", "exclusiveMaximum"),
+    ("min_items", "minItems"),
+    ("max_items", "maxItems"),
+    ("min_length", "minLength"),
+    ("max_length", "maxLength"),
+    ("pattern", "pattern"),
+    ("enum_values", "enum"),
+];
+
+fn expand(args: Punctuated<Meta, Token![,]>, mut item: ItemStruct) -> syn::Result<TokenStream2> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "#[mcp_tool] does not support generic structs",
+        ));
+    }
+    reject_serde(
+        &item.attrs,
+        &["rename_all", "deny_unknown_fields"],
+        "struct",
+    )?;
+
+    let mut name = None;
+    let mut description = doc_comment(&item.attrs);
+    let mut base = None;
+    let mut extras = Vec::new();
+    for arg in args {
+        match &arg {
+            Meta::NameValue(MetaNameValue { path, value, .. }) if path.is_ident("name") => {
+                name = Some(string_literal(value)?);
+            }
+            Meta::NameValue(MetaNameValue { path, value, .. }) if path.is_ident("description") => {
+                description = Some(string_literal(value)?);
+            }
+            Meta::Path(path) => {
+                let flag = path
+                    .get_ident()
+                    .map(|ident| ident.to_string())
+                    .unwrap_or_default();
+                if BASE_ANNOTATIONS.contains(&flag.as_str()) {
+                    if base.replace(flag).is_some() {
+                        return Err(syn::Error::new_spanned(
+                            path,
+                            "use only one of read_only, additive and destructive",
+                        ));
+                    }
+                } else if EXTRA_ANNOTATIONS.contains(&flag.as_str()) {
+                    extras.push(flag);
+                } else {
+                    return Err(syn::Error::new_spanned(path, "unknown #[mcp_tool] flag"));
+                }
+            }
+            _ => return Err(syn::Error::new_spanned(arg, "unknown #[mcp_tool] argument")),
+        }
+    }
+    let name = name.ok_or_else(|| {
+        syn::Error::new(Span::call_site(), "#[mcp_tool] needs name = \"tool_name\"")
+    })?;
+    let description = description.unwrap_or_default();
+
+    let Fields::Named(fields) = &mut item.fields else {
+        return Err(syn::Error::new_spanned(
+            &item.fields,
+            "#[mcp_tool] needs a struct with named fields",
+        ));
+    };
+    let mut properties = Vec::new();
+    for field in fields.named.iter_mut() {
+        let options = take_tool_attributes(&mut field.attrs)?;
+        if let Some(property) = property(field, options)? {
+            properties.push(property);
+        }
+    }
+
+    let annotations = match (base, extras.is_empty()) {
+        (None, true) => quote!(::core::option::Option::None),
+        (base, _) => {
+            let base = syn::Ident::new(base.as_deref().unwrap_or("additive"), Span::call_site());
+            let extras = extras
+                .iter()
+                .map(|extra| syn::Ident::new(extra, Span::call_site()));
+            quote!(::core::option::Option::Some(
+                ::mcp_rust_examples::protocol::ToolAnnotations::#base()#(.#extras())*
+            ))
+        }
+    };
+
+    let ident = &item.ident;
+    Ok(quote! {
+        #item
+
+        impl ::mcp_rust_examples::schema::TypedTool for #ident {
+            const NAME: &'static str = #name;
+
+            fn tool() -> ::mcp_rust_examples::protocol::Tool {
+                #[allow(unused_mut)]
+                let mut properties = ::mcp_rust_examples::schema::__private::Map::new();
+                #[allow(unused_mut)]
+                let mut required = ::std::vec::Vec::<&str>::new();
+                #(#properties)*
+                ::mcp_rust_examples::protocol::Tool {
+                    name: #name.to_string(),
+                    description: #description.to_string(),
+                    input_schema: ::mcp_rust_examples::schema::object_schema(properties, required),
+                    annotations: #annotations,
+                }
+            }
+        }
+    })
+}
+
+// What `#[tool(...)]` on a field asked for
+#[derive(Default)]
+struct FieldOptions {
+    keywords: Vec<(String, Expr)>,
+    required: bool,
+}
+
+fn take_tool_attributes(attrs: &mut Vec<Attribute>) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+    let mut kept = Vec::new();
+    for attr in attrs.drain(..) {
+        if !attr.path().is_ident("tool") {
+            kept.push(attr);
+            continue;
+        }
+        let metas = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        for meta in metas {
+            match meta {
+                Meta::Path(path) if path.is_ident("required") => options.required = true,
+                Meta::NameValue(MetaNameValue { path, value, .. }) => {
+                    let option = path
+                        .get_ident()
+                        .map(|ident| ident.to_string())
+                        .unwrap_or_default();
+                    let Some((_, keyword)) = KEYWORDS.iter().find(|(name, _)| *name == option)
+                    else {
+                        return Err(syn::Error::new_spanned(path, "unknown #[tool] option"));
+                    };
+                    options.keywords.push((keyword.to_string(), value));
+                }
+                meta => return Err(syn::Error::new_spanned(meta, "unknown #[tool] option")),
+            }
+        }
+    }
+    *attrs = kept;
+    Ok(options)
+}
+
+// The statements adding one field to `properties` and `required`, or `None`
+// for fields serde skips
+fn property(field: &syn::Field, options: FieldOptions) -> syn::Result<Option<TokenStream2>> {
+    reject_serde(&field.attrs, &["flatten"], "field")?;
+    let serde = serde_options(&field.attrs)?;
+    if serde.skip {
+        return Ok(None);
+    }
+    let name = match serde.rename {
+        Some(rename) => rename,
+        None => field
+            .ident
+            .as_ref()
+            .map(|ident| ident.to_string().trim_start_matches("r#").to_string())
+            .unwrap_or_default(),
+    };
+
+    let ty = &field.ty;
+    let mut keywords = options.keywords;
+    if !keywords.iter().any(|(keyword, _)| keyword == "description") {
+        if let Some(doc) = doc_comment(&field.attrs) {
+            keywords.push(("description".to_string(), string_expr(&doc)));
+        }
+    }
+    let inserts = keywords.iter().map(|(keyword, value)| {
+        quote! {
+            schema.insert(#keyword.to_string(), ::mcp_rust_examples::schema::__private::json!(#value));
+        }
+    });
+    let required = if options.required {
+        quote!(true)
+    } else if serde.default {
+        quote!(false)
+    } else {
+        quote!(!<#ty as ::mcp_rust_examples::schema::JsonSchema>::OPTIONAL)
+    };
+
+    Ok(Some(quote! {
+        {
+            let mut schema = <#ty as ::mcp_rust_examples::schema::JsonSchema>::json_schema();
+            if let ::mcp_rust_examples::schema::__private::Value::Object(schema) = &mut schema {
+                #(#inserts)*
+            }
+            properties.insert(#name.to_string(), schema);
+            if #required {
+                required.push(#name);
+            }
+        }
+    }))
+}
+
+#[derive(Default)]
+struct SerdeOptions {
+    rename: Option<String>,
+    default: bool,
+    skip: bool,
+}
+
+// The serde field attributes that change what the arguments look like
+fn serde_options(attrs: &[Attribute]) -> syn::Result<SerdeOptions> {
+    let mut options = SerdeOptions::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("default") {
+                options.default = true;
+                if meta.input.peek(Token![=]) {
+                    meta.value()?.parse::<Expr>()?;
+                }
+            } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                options.skip = true;
+            } else if meta.input.peek(Token![=]) {
+                meta.value()?.parse::<Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                let _ = meta.parse_nested_meta(|_| Ok(()));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+// Serde attributes the schema would silently get wrong
+fn reject_serde(attrs: &[Attribute], unsupported: &[&str], on: &str) -> syn::Result<()> {
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        let metas = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        for meta in metas {
+            if unsupported.iter().any(|name| meta.path().is_ident(name)) {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    format!("#[mcp_tool] does not support this serde {} attribute", on),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// `///` comments, joined into one line
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(MetaNameValue {
+                value:
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(text),
+                        ..
+                    }),
+                ..
+            }) => Some(text.value().trim().to_string()),
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect();
+    (!lines.is_empty()).then(|| lines.join(" "))
+}
+
+fn string_literal(value: &Expr) -> syn::Result<String> {
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(text),
+            ..
+        }) => Ok(text.value()),
+        _ => Err(syn::Error::new_spanned(value, "expected a string literal")),
+    }
+}
+
+fn string_expr(text: &str) -> Expr {
+    Expr::Lit(ExprLit {
+        attrs: Vec::new(),
+        lit: Lit::Str(LitStr::new(text, Span::call_site())),
+    })
+}
@@ -0,0 +1,217 @@
+//! Aborting requests the client no longer wants.
+//!
+//! A client that gives up on a request sends `notifications/cancelled` with
+//! the request's id (LSP-style clients send `$/cancelRequest` instead). The
+//! transports keep an [`InFlight`] table per session, cancel the matching
+//! [`CancellationToken`], and drop the handler's future, so an awaited HTTP
+//! call or database query stops where it is. No response is sent for a
+//! cancelled request.
+//!
+//! Dropping the future is all most tools need. Work that outlives it, such
+//! as a task handed to a queue, can watch the token of the request being
+//! handled through [`current`]:
+//!
+//! ```no_run
+//! # async fn download() -> Result<String, String> { Ok(String::new()) }
+//! # async fn example() -> Result<String, String> {
+//! use mcp_rust_examples::cancellation;
+//!
+//! cancellation::until_cancelled(download())
+//!     .await
+//!     .ok_or("Download cancelled")?
+//! # }
+//! ```
+
+use crate::protocol::{JsonRpcRequest, RequestId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+pub use tokio_util::sync::CancellationToken;
+
+tokio::task_local! {
+    static TOKEN: CancellationToken;
+}
+
+/// The requests of one session that are being handled, by id.
+#[derive(Debug, Clone, Default)]
+pub struct InFlight {
+    requests: Arc<Mutex<HashMap<RequestId, CancellationToken>>>,
+}
+
+impl InFlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles `message` with `handler`, unless it is a request that gets
+    /// cancelled first; then the handler is dropped and this returns `None`.
+    /// The request can be cancelled from the moment this is called, before
+    /// the returned future is first polled.
+    pub fn track<'a, F: Future + 'a>(
+        &'a self,
+        message: &str,
+        handler: F,
+    ) -> impl Future<Output = Option<F::Output>> + 'a {
+        let tracked = request_id(message).map(|id| {
+            let token = CancellationToken::new();
+            self.requests
+                .lock()
+                .unwrap()
+                .insert(id.clone(), token.clone());
+            let untrack = Untrack {
+                requests: &self.requests,
+                id,
+            };
+            (token, untrack)
+        });
+        async move {
+            match tracked {
+                Some((token, _untrack)) => scope(token, handler).await,
+                None => Some(handler.await),
+            }
+        }
+    }
+
+    /// Cancels the request `message` asks to cancel, if it is a cancellation
+    /// notification, and returns that request's id. Requests that already
+    /// finished are not an error: the notification may cross the response.
+    pub fn cancel(&self, message: &str) -> Option<RequestId> {
+        let (id, reason) = cancelled_request(message)?;
+        match self.requests.lock().unwrap().get(&id) {
+            Some(token) => {
+                tracing::info!(?id, reason = reason.as_deref(), "Request cancelled");
+                token.cancel();
+            }
+            None => tracing::debug!(?id, "Cancellation for a request not in flight"),
+        }
+        Some(id)
+    }
+
+    /// How many requests are being handled.
+    pub fn len(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Removes a finished or dropped request from the table. Clients must not
+// reuse the id of a request that is still running.
+struct Untrack<'a> {
+    requests: &'a Mutex<HashMap<RequestId, CancellationToken>>,
+    id: RequestId,
+}
+
+impl Drop for Untrack<'_> {
+    fn drop(&mut self) {
+        self.requests.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Runs `future` with `token` as the [`current`] token, and drops it as soon
+/// as the token is cancelled.
+pub async fn scope<F: Future>(token: CancellationToken, future: F) -> Option<F::Output> {
+    let cancelled = token.clone();
+    TOKEN
+        .scope(token, async move {
+            tokio::select! {
+                output = future => Some(output),
+                _ = cancelled.cancelled() => None,
+            }
+        })
+        .await
+}
+
+/// The token of the request being handled. Outside a request it is a fresh
+/// token that is never cancelled.
+pub fn current() -> CancellationToken {
+    TOKEN.try_with(Clone::clone).unwrap_or_default()
+}
+
+/// Runs `future` unless the current request is cancelled first.
+pub async fn until_cancelled<F: Future>(future: F) -> Option<F::Output> {
+    current().run_until_cancelled(future).await
+}
+
+/// The id of the request `message` asks to cancel, and the reason given.
+pub fn cancelled_request(message: &str) -> Option<(RequestId, Option<String>)> {
+    let notification: JsonRpcRequest = serde_json::from_str(message).ok()?;
+    if notification.id.is_some() {
+        return None;
+    }
+    let id = match notification.method.as_str() {
+        "notifications/cancelled" => "requestId",
+        "$/cancelRequest" => "id",
+        _ => return None,
+    };
+    let id = serde_json::from_value(notification.params.get(id)?.clone()).ok()?;
+    let reason = notification.params["reason"].as_str().map(str::to_string);
+    Some((id, reason))
+}
+
+// The id of `message` if it is a request; notifications and responses have
+// nothing to cancel
+pub(crate) fn request_id(message: &str) -> Option<RequestId> {
+    serde_json::from_str::<JsonRpcRequest>(message).ok()?.id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reads_cancellation_notifications() {
+        let cancelled = r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":"a","reason":"user"}}"#;
+        assert_eq!(
+            cancelled_request(cancelled),
+            Some((RequestId::String("a".to_string()), Some("user".to_string())))
+        );
+        let lsp = r#"{"jsonrpc":"2.0","method":"$/cancelRequest","params":{"id":7}}"#;
+        assert_eq!(cancelled_request(lsp), Some((RequestId::Number(7), None)));
+
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{}}"#;
+        assert_eq!(cancelled_request(request), None);
+        assert_eq!(request_id(request), Some(RequestId::Number(1)));
+        assert_eq!(request_id(lsp), None);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_drops_the_handler() {
+        let in_flight = InFlight::new();
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{}}"#;
+        let (started, mut running) = tokio::sync::mpsc::channel(1);
+
+        let handled = tokio::spawn({
+            let in_flight = in_flight.clone();
+            async move {
+                let handler = async move {
+                    started.send(()).await.unwrap();
+                    // What the handler sees, if it looks
+                    let token = current();
+                    token.cancelled().await;
+                    std::future::pending::<()>().await
+                };
+                in_flight.track(request, handler).await
+            }
+        });
+        running.recv().await.unwrap();
+        assert_eq!(in_flight.len(), 1);
+
+        let cancel =
+            r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":1}}"#;
+        assert_eq!(in_flight.cancel(cancel), Some(RequestId::Number(1)));
+        let output = tokio::time::timeout(Duration::from_secs(5), handled)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(output, None);
+        assert!(in_flight.is_empty());
+
+        // Outside a request nothing is ever cancelled
+        assert!(!current().is_cancelled());
+        assert_eq!(until_cancelled(async { 42 }).await, Some(42));
+    }
+}
@@ -0,0 +1,287 @@
+//! What the connected client can do, so servers can adapt to it.
+//!
+//! Clients declare optional features in `initialize`: whether they answer
+//! `sampling/createMessage` and `elicitation/create`, and whether they expose
+//! roots. The protocol version they speak also limits the content blocks
+//! they understand; audio arrived in 2025-03-26 and resource links in
+//! 2025-06-18. A server that checks first can skip a sampling request, fall
+//! back from elicitation, or turn an audio block into a text note, instead of
+//! failing on a minimal client.
+//!
+//! The transports record each session's [`ClientCapabilities`] and make them
+//! available to the code handling its requests through [`current`]:
+//!
+//! ```no_run
+//! # use mcp_rust_examples::capabilities::{self, ClientFeature};
+//! if capabilities::client_supports(ClientFeature::Sampling) {
+//!     // ask the client's model
+//! } else {
+//!     // use a canned summary
+//! }
+//! ```
+//!
+//! Clients may also ask for an [`OutputFormat`] for tool results, and list
+//! the [`Compression`] algorithms they accept, as experimental capabilities.
+//!
+//! Outside a session, e.g. when an example calls its own tools, nothing is
+//! known and every helper assumes full support: the caller wired up whatever
+//! it uses.
+
+use crate::compression::{self, Compression};
+use crate::output_format::OutputFormat;
+use crate::protocol::PROTOCOL_VERSION;
+use serde_json::Value;
+use std::future::Future;
+use std::sync::Arc;
+
+/// Protocol versions this crate can speak, oldest first.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", PROTOCOL_VERSION];
+
+tokio::task_local! {
+    static CLIENT: Arc<ClientCapabilities>;
+}
+
+/// Optional features a client declares in `initialize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientFeature {
+    /// Answers `sampling/createMessage`.
+    Sampling,
+    /// Answers `elicitation/create`.
+    Elicitation,
+    /// Answers `roots/list`.
+    Roots,
+    /// Sends `notifications/roots/list_changed`.
+    RootsListChanged,
+}
+
+/// The protocol version to answer `initialize` with: the client's if this
+/// crate speaks it, otherwise the newest one, which the client may reject.
+pub fn negotiate_version(requested: Option<&str>) -> &'static str {
+    SUPPORTED_PROTOCOL_VERSIONS
+        .iter()
+        .find(|&&version| Some(version) == requested)
+        .copied()
+        .unwrap_or(PROTOCOL_VERSION)
+}
+
+/// What one client said about itself in `initialize`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCapabilities {
+    /// The negotiated version, see [`negotiate_version`].
+    pub protocol_version: String,
+    pub client_name: Option<String>,
+    pub client_version: Option<String>,
+    pub sampling: bool,
+    pub elicitation: bool,
+    pub roots: bool,
+    pub roots_list_changed: bool,
+    /// How the client wants tool results written, from the experimental
+    /// `outputFormat` capability.
+    pub output_format: Option<OutputFormat>,
+    /// The algorithms the client accepts for large messages, preferred
+    /// first, from the experimental `compression` capability.
+    pub compression: Vec<Compression>,
+}
+
+impl ClientCapabilities {
+    /// Reads the `params` of an `initialize` request. Anything missing or
+    /// malformed counts as unsupported.
+    pub fn from_initialize(params: &Value) -> Self {
+        let capabilities = &params["capabilities"];
+        let declared = |name: &str| capabilities.get(name).is_some_and(Value::is_object);
+        let info = |field: &str| params["clientInfo"][field].as_str().map(str::to_string);
+        Self {
+            protocol_version: negotiate_version(params["protocolVersion"].as_str()).to_string(),
+            client_name: info("name"),
+            client_version: info("version"),
+            sampling: declared("sampling"),
+            elicitation: declared("elicitation"),
+            roots: declared("roots"),
+            roots_list_changed: capabilities["roots"]["listChanged"] == true,
+            output_format: capabilities["experimental"]["outputFormat"]
+                .as_str()
+                .and_then(|format| format.parse().ok()),
+            compression: compression::accepted(capabilities),
+        }
+    }
+
+    /// The client's capabilities if `message` is its `initialize` request.
+    pub fn from_message(message: &str) -> Option<Self> {
+        let message: Value = serde_json::from_str(message).ok()?;
+        (message["method"] == "initialize").then(|| Self::from_initialize(&message["params"]))
+    }
+
+    pub fn supports(&self, feature: ClientFeature) -> bool {
+        match feature {
+            ClientFeature::Sampling => self.sampling,
+            ClientFeature::Elicitation => self.elicitation,
+            ClientFeature::Roots => self.roots,
+            ClientFeature::RootsListChanged => self.roots_list_changed,
+        }
+    }
+
+    /// Whether the negotiated protocol version has content blocks of this
+    /// `type`, e.g. `"audio"`. Unknown types are never supported.
+    pub fn supports_content(&self, content_type: &str) -> bool {
+        let since = match content_type {
+            "text" | "image" | "resource" => "2024-11-05",
+            "audio" => "2025-03-26",
+            "resource_link" => "2025-06-18",
+            _ => return false,
+        };
+        // Versions are dates, so they compare as strings
+        self.protocol_version.as_str() >= since
+    }
+
+    /// Replaces the content blocks this client cannot show with text saying
+    /// what was left out, so the rest of a result still gets through.
+    pub fn degrade_content(&self, content: Vec<Value>) -> Vec<Value> {
+        content
+            .into_iter()
+            .map(|block| {
+                let content_type = block["type"].as_str().unwrap_or_default();
+                if self.supports_content(content_type) {
+                    return block;
+                }
+                let text = match content_type {
+                    "resource_link" => format!(
+                        "{}: {}",
+                        block["name"].as_str().unwrap_or("Resource"),
+                        block["uri"].as_str().unwrap_or_default()
+                    ),
+                    "audio" => format!(
+                        "[{} audio omitted: not supported by this client]",
+                        block["mimeType"].as_str().unwrap_or("unknown")
+                    ),
+                    other => format!("[{} content omitted: not supported by this client]", other),
+                };
+                tracing::debug!(content_type, "Degraded a content block to text");
+                serde_json::json!({ "type": "text", "text": text })
+            })
+            .collect()
+    }
+}
+
+/// Runs `future` with `client` as the [`current`] client. Transports wrap
+/// each request of a session in this.
+pub async fn scope<F: Future>(client: Option<Arc<ClientCapabilities>>, future: F) -> F::Output {
+    match client {
+        Some(client) => CLIENT.scope(client, future).await,
+        None => future.await,
+    }
+}
+
+/// The client whose request is being handled, if it has initialized.
+pub fn current() -> Option<Arc<ClientCapabilities>> {
+    CLIENT.try_with(Arc::clone).ok()
+}
+
+/// Whether the current client supports `feature`. True outside a session.
+pub fn client_supports(feature: ClientFeature) -> bool {
+    current().is_none_or(|client| client.supports(feature))
+}
+
+/// [`ClientCapabilities::degrade_content`] for the current client. Content
+/// passes unchanged outside a session.
+pub fn degrade_content(content: Vec<Value>) -> Vec<Value> {
+    match current() {
+        Some(client) => client.degrade_content(content),
+        None => content,
+    }
+}
+
+/// The token a request asked progress to be reported under. Requests
+/// without one, e.g. from clients that show no progress, must not get
+/// `notifications/progress`.
+pub fn progress_token(params: &Value) -> Option<&Value> {
+    params
+        .get("_meta")
+        .and_then(|meta| meta.get("progressToken"))
+        .filter(|token| token.is_string() || token.is_number())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_reads_initialize_params() {
+        let client = ClientCapabilities::from_message(
+            &json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {
+                    "protocolVersion": "2025-03-26",
+                    "capabilities": {
+                        "sampling": {},
+                        "roots": { "listChanged": true },
+                        "experimental": { "outputFormat": "pretty", "compression": ["gzip"] }
+                    },
+                    "clientInfo": { "name": "inspector", "version": "1.2" }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(client.protocol_version, "2025-03-26");
+        assert_eq!(client.client_name.as_deref(), Some("inspector"));
+        assert!(client.supports(ClientFeature::Sampling));
+        assert!(client.supports(ClientFeature::RootsListChanged));
+        assert!(!client.supports(ClientFeature::Elicitation));
+        assert_eq!(client.output_format, Some(OutputFormat::Pretty));
+        assert_eq!(client.compression, [Compression::Gzip]);
+
+        // A minimal client declares nothing and gets the newest version
+        let minimal =
+            ClientCapabilities::from_initialize(&json!({ "protocolVersion": "1999-01-01" }));
+        assert_eq!(minimal.protocol_version, PROTOCOL_VERSION);
+        assert!(!minimal.supports(ClientFeature::Sampling));
+        assert!(!minimal.supports(ClientFeature::Roots));
+        assert_eq!(minimal.output_format, None);
+        assert!(minimal.compression.is_empty());
+
+        assert!(ClientCapabilities::from_message(r#"{"method":"ping"}"#).is_none());
+    }
+
+    #[test]
+    fn test_degrades_content_for_older_versions() {
+        let old = ClientCapabilities::from_initialize(&json!({ "protocolVersion": "2024-11-05" }));
+        let content = vec![
+            json!({ "type": "text", "text": "summary" }),
+            json!({ "type": "audio", "data": "AAAA", "mimeType": "audio/wav" }),
+            json!({ "type": "resource_link", "uri": "file:///report.pdf", "name": "report" }),
+        ];
+        assert_eq!(
+            old.degrade_content(content.clone()),
+            [
+                json!({ "type": "text", "text": "summary" }),
+                json!({ "type": "text", "text": "[audio/wav audio omitted: not supported by this client]" }),
+                json!({ "type": "text", "text": "report: file:///report.pdf" }),
+            ]
+        );
+
+        let current =
+            ClientCapabilities::from_initialize(&json!({ "protocolVersion": PROTOCOL_VERSION }));
+        assert_eq!(current.degrade_content(content.clone()), content);
+    }
+
+    #[tokio::test]
+    async fn test_current_client_is_scoped() {
+        assert!(current().is_none());
+        assert!(client_supports(ClientFeature::Elicitation));
+
+        let minimal = Arc::new(ClientCapabilities::from_initialize(&Value::Null));
+        let supported = scope(Some(minimal), async {
+            client_supports(ClientFeature::Elicitation)
+        })
+        .await;
+        assert!(!supported);
+        assert!(current().is_none());
+
+        let params = json!({ "name": "slow", "_meta": { "progressToken": "abc" } });
+        assert_eq!(progress_token(&params), Some(&json!("abc")));
+        assert_eq!(progress_token(&json!({ "name": "slow" })), None);
+    }
+}
@@ -0,0 +1,453 @@
+//! Fault injection for testing how clients cope with a flaky server.
+//!
+//! [`Chaos`] is a [`Middleware`] for [`McpServer`] that applies a
+//! [`ChaosPolicy`]: it delays tool calls, fails a share of them with
+//! [`McpError::Unavailable`] before they reach the tool, and drops a share of
+//! the notifications the server sends. Agents built on these examples can so
+//! be tested against slow calls, transient errors and missed updates without
+//! breaking a real dependency.
+//!
+//! Chaos is opt-in. A server only has it when started with [`CHAOS_ENV`] set,
+//! either to `on` for no faults yet or to a policy as JSON:
+//!
+//! ```text
+//! MCP_CHAOS='{"latency_ms": 200, "error_rate": 0.1, "tools": ["search"]}'
+//! ```
+//!
+//! Only then does the server offer the `set_chaos_policy` admin tool, which
+//! replaces the policy at runtime and reports how many faults were injected
+//! so far. Calling it with no arguments turns the faults off again.
+//!
+//! [`McpServer`]: crate::server::McpServer
+
+use crate::error::McpError;
+use crate::middleware::Middleware;
+use crate::protocol::{CallToolResult, JsonRpcRequest, JsonRpcResponse, Tool, ToolAnnotations};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+pub const TOOL_NAME: &str = "set_chaos_policy";
+
+/// Enables chaos: `on`, or the initial [`ChaosPolicy`] as JSON.
+pub const CHAOS_ENV: &str = "MCP_CHAOS";
+
+/// The longest delay a policy may add to a call, jitter included.
+pub const MAX_LATENCY_MS: u64 = 60_000;
+
+/// The faults to inject. The default injects none.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChaosPolicy {
+    /// Added to every affected tool call.
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// A random delay of up to this much on top of `latency_ms`.
+    #[serde(default)]
+    pub jitter_ms: u64,
+    /// Share of affected tool calls that fail, from 0 to 1.
+    #[serde(default)]
+    pub error_rate: f64,
+    /// Share of notifications that are never sent, from 0 to 1.
+    #[serde(default)]
+    pub notification_drop_rate: f64,
+    /// The tools affected; every tool when empty.
+    #[serde(default)]
+    pub tools: Vec<String>,
+}
+
+impl ChaosPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, rate) in [
+            ("error_rate", self.error_rate),
+            ("notification_drop_rate", self.notification_drop_rate),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(format!("{} must be between 0 and 1", name));
+            }
+        }
+        if self.latency_ms.saturating_add(self.jitter_ms) > MAX_LATENCY_MS {
+            return Err(format!(
+                "latency_ms and jitter_ms may add up to at most {}",
+                MAX_LATENCY_MS
+            ));
+        }
+        Ok(())
+    }
+
+    fn affects(&self, tool: &str) -> bool {
+        self.tools.is_empty() || self.tools.iter().any(|t| t == tool)
+    }
+
+    fn delay(&self) -> Duration {
+        let jitter = match self.jitter_ms {
+            0 => 0,
+            jitter => rand::random::<u64>() % (jitter + 1),
+        };
+        Duration::from_millis(self.latency_ms + jitter)
+    }
+}
+
+/// Counts of the faults injected since the server started.
+#[derive(Debug, Default)]
+struct Injected {
+    delayed_calls: AtomicU64,
+    failed_calls: AtomicU64,
+    dropped_notifications: AtomicU64,
+}
+
+/// Injects the faults of its policy into a server's tool calls and
+/// notifications.
+#[derive(Debug, Default)]
+pub struct Chaos {
+    policy: RwLock<ChaosPolicy>,
+    injected: Injected,
+}
+
+impl Chaos {
+    pub fn new(policy: ChaosPolicy) -> Self {
+        Self {
+            policy: RwLock::new(policy),
+            injected: Injected::default(),
+        }
+    }
+
+    /// Chaos as configured by [`CHAOS_ENV`]; `None` when it is unset. An
+    /// invalid policy is reported and replaced by one without faults, so the
+    /// tool is still there to set a better one.
+    pub fn from_env() -> Option<Arc<Self>> {
+        let value = std::env::var(CHAOS_ENV).ok()?;
+        let value = value.trim();
+        if value.is_empty() || matches!(value, "0" | "off" | "false") {
+            return None;
+        }
+        let policy = if matches!(value, "1" | "on" | "true") {
+            ChaosPolicy::default()
+        } else {
+            match serde_json::from_str::<ChaosPolicy>(value)
+                .map_err(|e| e.to_string())
+                .and_then(|policy| policy.validate().map(|()| policy))
+            {
+                Ok(policy) => policy,
+                Err(e) => {
+                    tracing::warn!("Ignoring invalid {}: {}", CHAOS_ENV, e);
+                    ChaosPolicy::default()
+                }
+            }
+        };
+        tracing::warn!(?policy, "Chaos enabled: faults will be injected");
+        Some(Arc::new(Self::new(policy)))
+    }
+
+    pub fn policy(&self) -> ChaosPolicy {
+        self.policy.read().unwrap().clone()
+    }
+
+    /// Replaces the policy, unless it is invalid.
+    pub fn set_policy(&self, policy: ChaosPolicy) -> Result<(), String> {
+        policy.validate()?;
+        tracing::warn!(?policy, "Chaos policy changed");
+        *self.policy.write().unwrap() = policy;
+        Ok(())
+    }
+
+    /// Whether to drop the next notification, counting it if so.
+    pub fn drops_notification(&self) -> bool {
+        let rate = self.policy.read().unwrap().notification_drop_rate;
+        let dropped = rate > 0.0 && rand::random::<f64>() < rate;
+        if dropped {
+            self.injected
+                .dropped_notifications
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        dropped
+    }
+
+    /// The `set_chaos_policy` result: the policy in force and the faults
+    /// injected so far.
+    pub fn status(&self) -> Value {
+        let count = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        json!({
+            "policy": self.policy(),
+            "injected": {
+                "delayed_calls": count(&self.injected.delayed_calls),
+                "failed_calls": count(&self.injected.failed_calls),
+                "dropped_notifications": count(&self.injected.dropped_notifications),
+            },
+        })
+    }
+
+    fn set_policy_tool(&self, arguments: &Value) -> Result<Value, McpError> {
+        let policy = match arguments {
+            Value::Null => ChaosPolicy::default(),
+            arguments => serde_json::from_value(arguments.clone())
+                .map_err(|e| McpError::InvalidParams(format!("Invalid chaos policy: {}", e)))?,
+        };
+        self.set_policy(policy).map_err(McpError::InvalidParams)?;
+        Ok(self.status())
+    }
+
+    // Delays the call, then decides whether it fails
+    async fn inject(&self, tool: &str) -> Option<McpError> {
+        let (delay, error_rate) = {
+            let policy = self.policy.read().unwrap();
+            if !policy.affects(tool) {
+                return None;
+            }
+            (policy.delay(), policy.error_rate)
+        };
+
+        if !delay.is_zero() {
+            self.injected.delayed_calls.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(delay).await;
+        }
+        if error_rate > 0.0 && rand::random::<f64>() < error_rate {
+            self.injected.failed_calls.fetch_add(1, Ordering::Relaxed);
+            tracing::info!(tool, "Chaos failed a tool call");
+            return Some(McpError::Unavailable(format!(
+                "Injected fault: {} is unavailable",
+                tool
+            )));
+        }
+        None
+    }
+}
+
+/// The `set_chaos_policy` tool's description.
+pub fn tool() -> Tool {
+    Tool {
+        name: TOOL_NAME.to_string(),
+        description: "Admin: inject faults into this server's tool calls and notifications \
+                      for resilience testing. Call without arguments to stop injecting faults"
+            .to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "latency_ms": {
+                    "type": "integer",
+                    "description": "Delay added to every affected tool call",
+                    "minimum": 0,
+                    "maximum": MAX_LATENCY_MS
+                },
+                "jitter_ms": {
+                    "type": "integer",
+                    "description": "Random extra delay of up to this many milliseconds",
+                    "minimum": 0,
+                    "maximum": MAX_LATENCY_MS
+                },
+                "error_rate": {
+                    "type": "number",
+                    "description": "Share of affected tool calls that fail as unavailable",
+                    "minimum": 0,
+                    "maximum": 1
+                },
+                "notification_drop_rate": {
+                    "type": "number",
+                    "description": "Share of notifications that are never sent",
+                    "minimum": 0,
+                    "maximum": 1
+                },
+                "tools": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Only affect these tools; all tools when empty"
+                }
+            },
+            "additionalProperties": false
+        }),
+        annotations: Some(ToolAnnotations::destructive().idempotent()),
+    }
+}
+
+// The tool a `tools/call` request is for
+fn called_tool(request: &JsonRpcRequest) -> Option<&str> {
+    (request.method == "tools/call")
+        .then(|| request.params["name"].as_str())
+        .flatten()
+}
+
+fn call_result(result: Result<Value, McpError>) -> Value {
+    serde_json::to_value(CallToolResult::from_result(result)).unwrap_or(Value::Null)
+}
+
+impl Middleware<JsonRpcRequest, JsonRpcResponse> for Chaos {
+    // Answers set_chaos_policy itself, and delays or fails other tool calls
+    fn on_request<'a>(
+        &'a self,
+        request: &'a mut JsonRpcRequest,
+    ) -> BoxFuture<'a, Option<JsonRpcResponse>> {
+        Box::pin(async move {
+            let tool = called_tool(request)?;
+            if tool == TOOL_NAME {
+                let result = call_result(self.set_policy_tool(&request.params["arguments"]));
+                return Some(JsonRpcResponse::success(request.id.clone(), result));
+            }
+
+            let error = self.inject(tool).await?;
+            Some(JsonRpcResponse::success(
+                request.id.clone(),
+                call_result(Err(error)),
+            ))
+        })
+    }
+
+    // Lists set_chaos_policy on the last page of tools/list
+    fn on_response<'a>(
+        &'a self,
+        request: &'a JsonRpcRequest,
+        response: &'a mut JsonRpcResponse,
+        _elapsed: Duration,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let Some(result) = response.result.as_mut() else {
+                return;
+            };
+            if request.method != "tools/list" || result.get("nextCursor").is_some() {
+                return;
+            }
+            if let Some(tools) = result["tools"].as_array_mut() {
+                if !tools.iter().any(|tool| tool["name"] == TOOL_NAME) {
+                    tools.push(json!(tool()));
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{McpServer, ToolHandler, ToolRouter};
+    use std::time::Instant;
+
+    struct Echo;
+
+    impl ToolHandler for Echo {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "echo".to_string(),
+                description: "Echoes its arguments".to_string(),
+                input_schema: json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async move { Ok(arguments) })
+        }
+    }
+
+    fn call(name: &str, arguments: Value) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": arguments }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_policy_validation() {
+        assert!(ChaosPolicy::default().validate().is_ok());
+        let policy = ChaosPolicy {
+            error_rate: 1.5,
+            ..ChaosPolicy::default()
+        };
+        assert!(policy.validate().is_err());
+        let policy = ChaosPolicy {
+            latency_ms: MAX_LATENCY_MS,
+            jitter_ms: 1,
+            ..ChaosPolicy::default()
+        };
+        assert!(policy.validate().is_err());
+
+        let policy = ChaosPolicy {
+            latency_ms: 100,
+            jitter_ms: 50,
+            ..ChaosPolicy::default()
+        };
+        for _ in 0..20 {
+            let delay = policy.delay();
+            assert!((100..=150).contains(&(delay.as_millis() as u64)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_injects_faults_into_tool_calls() {
+        let chaos = Arc::new(Chaos::default());
+        let server = McpServer::new(Arc::new(ToolRouter::new("flaky").with_handler(Echo)))
+            .with_chaos(chaos.clone());
+
+        // No faults until a policy asks for them
+        let response = server.handle_message(&call("echo", json!({}))).await;
+        assert_eq!(response.unwrap().result.unwrap()["isError"], false);
+
+        let policy = json!({ "latency_ms": 30, "error_rate": 1.0, "tools": ["echo"] });
+        let response = server.handle_message(&call(TOOL_NAME, policy)).await;
+        let result: CallToolResult =
+            serde_json::from_value(response.unwrap().result.unwrap()).unwrap();
+        let status: Value = serde_json::from_str(&result.into_result().unwrap()).unwrap();
+        assert_eq!(status["policy"]["error_rate"], 1.0);
+
+        let started = Instant::now();
+        let response = server.handle_message(&call("echo", json!({}))).await;
+        assert!(started.elapsed() >= Duration::from_millis(30));
+        let result = response.unwrap().result.unwrap();
+        assert_eq!(result["isError"], true);
+        assert_eq!(result["_meta"]["error"]["kind"], "unavailable");
+
+        // Tools outside the policy are left alone
+        let response = server
+            .handle_message(&call("get_tool_stats", json!({})))
+            .await;
+        assert_eq!(response.unwrap().result.unwrap()["isError"], false);
+
+        let response = server
+            .handle_message(&call(TOOL_NAME, json!({ "error_rate": 2 })))
+            .await;
+        assert_eq!(response.unwrap().result.unwrap()["isError"], true);
+        assert_eq!(chaos.policy().error_rate, 1.0);
+
+        // No arguments turns the faults off and reports what was injected
+        let response = server.handle_message(&call(TOOL_NAME, Value::Null)).await;
+        let result: CallToolResult =
+            serde_json::from_value(response.unwrap().result.unwrap()).unwrap();
+        let status: Value = serde_json::from_str(&result.into_result().unwrap()).unwrap();
+        assert_eq!(status["injected"]["failed_calls"], 1);
+        assert_eq!(status["injected"]["delayed_calls"], 1);
+        assert_eq!(chaos.policy(), ChaosPolicy::default());
+
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#)
+            .await;
+        let tools = response.unwrap().result.unwrap()["tools"].clone();
+        assert!(tools
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|t| t["name"] == TOOL_NAME));
+    }
+
+    #[test]
+    fn test_drops_notifications() {
+        let chaos = Chaos::default();
+        assert!(!chaos.drops_notification());
+
+        chaos
+            .set_policy(ChaosPolicy {
+                notification_drop_rate: 1.0,
+                ..ChaosPolicy::default()
+            })
+            .unwrap();
+        let server = McpServer::new(Arc::new(ToolRouter::new("flaky"))).with_chaos(Arc::new(chaos));
+        let notification =
+            JsonRpcRequest::notification("notifications/tools/list_changed", Value::Null);
+        assert!(!server.delivers_notification(&notification));
+        assert!(McpServer::new(Arc::new(ToolRouter::new("steady")))
+            .delivers_notification(&notification));
+    }
+}
@@ -0,0 +1,228 @@
+//! Progress checkpoints that survive a restart.
+//!
+//! Servers that hand work to background tasks keep their progress in
+//! memory: which messages a consumer still has to acknowledge, which tasks
+//! finished, which notifications are still to be delivered. A crash loses
+//! all of it, so after a restart work is either done again or silently
+//! dropped. A [`CheckpointStore`] is a small key-value store in a SQLite
+//! file where they record that progress as they go and read it back on
+//! startup:
+//!
+//! ```
+//! # use mcp_rust_examples::checkpoint::{CheckpointError, CheckpointStore};
+//! # async fn demo() -> Result<(), CheckpointError> {
+//! # let dir = tempfile::tempdir()?;
+//! # let path = dir.path().join("checkpoints.db");
+//! let store = CheckpointStore::open(path).await?;
+//! store.put("streaming", "next_message_id", &42_u64).await?;
+//! let next: Option<u64> = store.get("streaming", "next_message_id").await?;
+//! assert_eq!(next, Some(42));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Values are stored as JSON under a namespace, one per server or component,
+//! so several servers can share one file. Every write is committed before
+//! it returns; the file uses SQLite's write-ahead log, so the last
+//! checkpoint survives a crash of the process.
+//!
+//! The examples that checkpoint open the file named by
+//! [`CHECKPOINT_PATH_ENV`] with [`CheckpointStore::from_env`], and keep
+//! their progress in memory only when it is unset.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use sqlx::SqlitePool;
+use std::path::Path;
+
+/// The SQLite file checkpoints are kept in, e.g. `data/checkpoints.db`.
+pub const CHECKPOINT_PATH_ENV: &str = "MCP_CHECKPOINT_PATH";
+
+/// Errors raised while reading or writing checkpoints.
+#[derive(Debug, thiserror::Error)]
+pub enum CheckpointError {
+    #[error("checkpoint store failed: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("invalid checkpoint data: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("failed to create checkpoint directory: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS checkpoints (
+    namespace TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (namespace, key)
+)";
+
+/// JSON values kept by namespace and key in a SQLite file. Clones share
+/// the same connections.
+#[derive(Debug, Clone)]
+pub struct CheckpointStore {
+    pool: SqlitePool,
+}
+
+impl CheckpointStore {
+    /// Opens the store at `path`, creating the file and its directory if
+    /// needed.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, CheckpointError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal);
+        Self::connect(SqlitePoolOptions::new().max_connections(4), options).await
+    }
+
+    /// A store that lasts as long as the process, for tests.
+    pub async fn in_memory() -> Result<Self, CheckpointError> {
+        // Every connection to :memory: is a database of its own
+        let options = SqliteConnectOptions::new().in_memory(true);
+        Self::connect(SqlitePoolOptions::new().max_connections(1), options).await
+    }
+
+    /// Opens the store named by [`CHECKPOINT_PATH_ENV`], or `None` when it
+    /// is unset.
+    pub async fn from_env() -> Result<Option<Self>, CheckpointError> {
+        match std::env::var(CHECKPOINT_PATH_ENV) {
+            Ok(path) if !path.trim().is_empty() => Ok(Some(Self::open(path.trim()).await?)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn connect(
+        pool: SqlitePoolOptions,
+        options: SqliteConnectOptions,
+    ) -> Result<Self, CheckpointError> {
+        // Idle connections are never closed, or an in-memory store would
+        // vanish with its only connection
+        let pool = pool
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?;
+        sqlx::query(CREATE_TABLE_SQL).execute(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    /// The value under `key`, if there is one.
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<T>, CheckpointError> {
+        let value: Option<(String,)> =
+            sqlx::query_as("SELECT value FROM checkpoints WHERE namespace = ? AND key = ?")
+                .bind(namespace)
+                .bind(key)
+                .fetch_optional(&self.pool)
+                .await?;
+        match value {
+            Some((value,)) => Ok(Some(serde_json::from_str(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores `value` under `key`, replacing what was there.
+    pub async fn put<T: Serialize + ?Sized>(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: &T,
+    ) -> Result<(), CheckpointError> {
+        let value = serde_json::to_string(value)?;
+        sqlx::query(
+            "INSERT INTO checkpoints (namespace, key, value) VALUES (?, ?, ?)
+             ON CONFLICT (namespace, key)
+             DO UPDATE SET value = excluded.value, updated_at = datetime('now')",
+        )
+        .bind(namespace)
+        .bind(key)
+        .bind(value)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes the value under `key`; false if there was none.
+    pub async fn remove(&self, namespace: &str, key: &str) -> Result<bool, CheckpointError> {
+        let result = sqlx::query("DELETE FROM checkpoints WHERE namespace = ? AND key = ?")
+            .bind(namespace)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Every key and value in `namespace`, in the order the keys were first
+    /// stored.
+    pub async fn list<T: DeserializeOwned>(
+        &self,
+        namespace: &str,
+    ) -> Result<Vec<(String, T)>, CheckpointError> {
+        let rows: Vec<(String, String)> =
+            sqlx::query_as("SELECT key, value FROM checkpoints WHERE namespace = ? ORDER BY rowid")
+                .bind(namespace)
+                .fetch_all(&self.pool)
+                .await?;
+        rows.into_iter()
+            .map(|(key, value)| Ok((key, serde_json::from_str(&value)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_values_are_kept_by_namespace() {
+        let store = CheckpointStore::in_memory().await.unwrap();
+        store.put("queue", "b", &2).await.unwrap();
+        store.put("queue", "a", &1).await.unwrap();
+        store.put("other", "a", "text").await.unwrap();
+        store.put("queue", "b", &3).await.unwrap();
+
+        assert_eq!(store.get::<i32>("queue", "a").await.unwrap(), Some(1));
+        assert_eq!(
+            store.get::<String>("other", "a").await.unwrap().as_deref(),
+            Some("text")
+        );
+        assert_eq!(store.get::<i32>("queue", "c").await.unwrap(), None);
+        // Replacing a value keeps its place in the list
+        let entries = store.list::<i32>("queue").await.unwrap();
+        assert_eq!(entries, [("b".to_string(), 3), ("a".to_string(), 1)]);
+
+        assert!(store.remove("queue", "b").await.unwrap());
+        assert!(!store.remove("queue", "b").await.unwrap());
+        assert!(matches!(
+            store.get::<i32>("other", "a").await,
+            Err(CheckpointError::Json(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_checkpoints_survive_reopening_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/checkpoints.db");
+        let store = CheckpointStore::open(&path).await.unwrap();
+        store
+            .put("streaming", "next_message_id", &7_u64)
+            .await
+            .unwrap();
+        drop(store);
+
+        let store = CheckpointStore::open(&path).await.unwrap();
+        let next: Option<u64> = store.get("streaming", "next_message_id").await.unwrap();
+        assert_eq!(next, Some(7));
+    }
+}
@@ -0,0 +1,489 @@
+//! Typed Rust client bindings generated from a server's tools.
+//!
+//! [`generate_client`] turns the tools a server lists into Rust source: an
+//! argument struct per tool, built from its input schema, and a client with
+//! one async method per tool that calls it through any [`ToolServer`], e.g.
+//! a server in the same process or example 04's `ProcessServer`:
+//!
+//! ```
+//! # use mcp_rust_examples::codegen::generate_client;
+//! # use mcp_rust_examples::protocol::Tool;
+//! let tool = Tool {
+//!     name: "greet".to_string(),
+//!     description: "Greets someone".to_string(),
+//!     input_schema: serde_json::json!({
+//!         "type": "object",
+//!         "properties": { "name": { "type": "string" } },
+//!         "required": ["name"]
+//!     }),
+//!     annotations: None,
+//! };
+//! let source = generate_client("hello_world", &[tool]);
+//! assert!(source.contains("pub struct GreetArgs"));
+//! assert!(source.contains("pub async fn greet(&self, args: &GreetArgs)"));
+//! ```
+//!
+//! Schema types map onto Rust as `string` to `String`, `integer` to `i64`,
+//! `number` to `f64`, `boolean` to `bool` and `array` to `Vec`. Objects with
+//! properties become structs of their own, string `enum`s become enums, and
+//! a `oneOf` or `anyOf` of different types becomes an untagged enum. Optional
+//! properties are `Option`s left out when `None`; anything the generator
+//! cannot type is a `serde_json::Value`.
+//!
+//! [`ToolServer`]: crate::tools::ToolServer
+
+use crate::protocol::Tool;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Keywords that need a raw identifier as a field or method name.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// Keywords that cannot be raw identifiers, and the client's own methods.
+const RESERVED: &[&str] = &["crate", "self", "super", "Self", "new", "call", "server"];
+
+/// Rust source for a client of `server_name` with a method for each of
+/// `tools`.
+pub fn generate_client(server_name: &str, tools: &[Tool]) -> String {
+    let client = format!("{}Client", pascal_case(server_name));
+    let mut generator = Generator::default();
+    generator.names.insert(client.clone());
+
+    let mut methods = String::new();
+    let mut method_names = HashSet::new();
+    for tool in tools {
+        let mut method = field_name(&tool.name);
+        while !method_names.insert(method.clone()) {
+            method.push('_');
+        }
+        methods.push('\n');
+        doc_comment(&mut methods, "    ", &tool.description);
+        let has_arguments = tool.input_schema["properties"]
+            .as_object()
+            .is_some_and(|properties| !properties.is_empty());
+        if has_arguments {
+            let prefix = pascal_case(&tool.name);
+            let args = generator.object(&tool.input_schema, &format!("{}Args", prefix), &prefix);
+            let _ = writeln!(
+                methods,
+                "    pub async fn {}(&self, args: &{}) -> Result<Value, McpError> {{\n        \
+                 self.call({:?}, args).await\n    }}",
+                method, args, tool.name
+            );
+        } else {
+            let _ = writeln!(
+                methods,
+                "    pub async fn {}(&self) -> Result<Value, McpError> {{\n        \
+                 self.call({:?}, &serde_json::json!({{}})).await\n    }}",
+                method, tool.name
+            );
+        }
+    }
+
+    let mut source = format!(
+        "// Typed client for the `{server}` MCP server, generated from its tools/list\n\
+         // by `example_04_simple_client --generate-bindings`. Regenerate it when\n\
+         // the server's tools change rather than editing it.\n\
+         \n\
+         use mcp_rust_examples::error::McpError;\n\
+         use mcp_rust_examples::tools::ToolServer;\n\
+         use serde::Serialize;\n\
+         use serde_json::Value;\n\
+         use std::sync::Arc;\n",
+        server = server_name
+    );
+    for item in &generator.items {
+        source.push('\n');
+        source.push_str(item);
+    }
+    let _ = write!(
+        source,
+        "\n/// Calls the tools of `{server}` with typed arguments.\n\
+         #[derive(Clone)]\n\
+         pub struct {client} {{\n    server: Arc<dyn ToolServer>,\n}}\n\
+         \n\
+         impl {client} {{\n    \
+             pub fn new(server: Arc<dyn ToolServer>) -> Self {{\n        Self {{ server }}\n    }}\n\
+         \n    \
+             pub fn server(&self) -> &Arc<dyn ToolServer> {{\n        &self.server\n    }}\n\
+         \n    \
+             async fn call(&self, tool: &str, args: &impl Serialize) -> Result<Value, McpError> {{\n        \
+                 let arguments = serde_json::to_value(args).map_err(|e| {{\n            \
+                     McpError::InvalidParams(format!(\"Failed to serialize arguments: {{}}\", e))\n        \
+                 }})?;\n        \
+                 self.server.invoke_tool(tool, arguments).await\n    \
+             }}\n\
+         {methods}}}\n",
+        server = server_name,
+        client = client,
+        methods = methods
+    );
+    source
+}
+
+/// Collects the types generated for the tools' schemas.
+#[derive(Default)]
+struct Generator {
+    items: Vec<String>,
+    names: HashSet<String>,
+}
+
+impl Generator {
+    /// `name`, or `name` with a number appended if it is taken.
+    fn unique(&mut self, name: &str) -> String {
+        let mut candidate = name.to_string();
+        let mut suffix = 2;
+        while !self.names.insert(candidate.clone()) {
+            candidate = format!("{}{}", name, suffix);
+            suffix += 1;
+        }
+        candidate
+    }
+
+    /// The Rust type of values matching `schema`. `name` is used for the
+    /// struct or enum generated when one is needed.
+    fn rust_type(&mut self, schema: &Value, name: &str) -> String {
+        if let Some(variants) = schema.get("oneOf").or_else(|| schema.get("anyOf")) {
+            return self.one_of(variants, name);
+        }
+        if let Some(values) = schema.get("enum") {
+            return self.string_enum(values, name);
+        }
+        match &schema["type"] {
+            Value::String(kind) => self.typed(kind, schema, name),
+            // e.g. ["string", "null"]
+            Value::Array(kinds) => {
+                let kinds: Vec<&str> = kinds
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .filter(|kind| *kind != "null")
+                    .collect();
+                match kinds.as_slice() {
+                    [kind] => format!("Option<{}>", self.typed(kind, schema, name)),
+                    _ => "Value".to_string(),
+                }
+            }
+            _ => "Value".to_string(),
+        }
+    }
+
+    fn typed(&mut self, kind: &str, schema: &Value, name: &str) -> String {
+        match kind {
+            "string" => "String".to_string(),
+            "integer" => "i64".to_string(),
+            "number" => "f64".to_string(),
+            "boolean" => "bool".to_string(),
+            "array" => format!(
+                "Vec<{}>",
+                self.rust_type(&schema["items"], &format!("{}Item", name))
+            ),
+            "object"
+                if schema["properties"]
+                    .as_object()
+                    .is_some_and(|p| !p.is_empty()) =>
+            {
+                self.object(schema, name, name)
+            }
+            _ => "Value".to_string(),
+        }
+    }
+
+    /// A struct for an object schema; nested types are named after `prefix`
+    /// and the property.
+    fn object(&mut self, schema: &Value, name: &str, prefix: &str) -> String {
+        let name = self.unique(name);
+        let required: HashSet<&str> = schema["required"]
+            .as_array()
+            .map(|required| required.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let empty = serde_json::Map::new();
+        let properties = schema["properties"].as_object().unwrap_or(&empty);
+
+        let mut fields = String::new();
+        let mut field_names = HashSet::new();
+        let mut all_optional = true;
+        for (property, property_schema) in properties {
+            let mut field = field_name(property);
+            while !field_names.insert(field.clone()) {
+                field.push('_');
+            }
+            let mut ty = self.rust_type(
+                property_schema,
+                &format!("{}{}", prefix, pascal_case(property)),
+            );
+            let optional = !required.contains(property.as_str());
+            if optional && !ty.starts_with("Option<") {
+                ty = format!("Option<{}>", ty);
+            }
+            all_optional &= optional;
+
+            if let Some(description) = property_schema["description"].as_str() {
+                doc_comment(&mut fields, "    ", description);
+            }
+            if field.trim_start_matches("r#") != property {
+                let _ = writeln!(fields, "    #[serde(rename = {:?})]", property);
+            }
+            if optional {
+                let _ = writeln!(
+                    fields,
+                    "    #[serde(skip_serializing_if = \"Option::is_none\")]"
+                );
+            }
+            let _ = writeln!(fields, "    pub {}: {},", field, ty);
+        }
+
+        let derives = if all_optional {
+            "Debug, Clone, Default, PartialEq, Serialize"
+        } else {
+            "Debug, Clone, PartialEq, Serialize"
+        };
+        let mut item = String::new();
+        if let Some(description) = schema["description"].as_str() {
+            doc_comment(&mut item, "", description);
+        }
+        let _ = write!(
+            item,
+            "#[derive({})]\npub struct {} {{\n{}}}\n",
+            derives, name, fields
+        );
+        self.items.push(item);
+        name
+    }
+
+    /// An enum for string `values`, or `String` when they don't make
+    /// distinct variant names.
+    fn string_enum(&mut self, values: &Value, name: &str) -> String {
+        let values: Option<Vec<&str>> = values
+            .as_array()
+            .map(|values| values.iter().map(Value::as_str).collect())
+            .unwrap_or_default();
+        let Some(values) = values.filter(|values| !values.is_empty()) else {
+            return "Value".to_string();
+        };
+        let variants: Vec<String> = values.iter().map(|value| pascal_case(value)).collect();
+        let distinct: HashSet<&String> = variants.iter().collect();
+        if distinct.len() != variants.len() {
+            return "String".to_string();
+        }
+
+        let name = self.unique(name);
+        let mut item = format!(
+            "#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]\npub enum {} {{\n",
+            name
+        );
+        for (value, variant) in values.iter().zip(&variants) {
+            let _ = writeln!(item, "    #[serde(rename = {:?})]\n    {},", value, variant);
+        }
+        item.push_str("}\n");
+        self.items.push(item);
+        name
+    }
+
+    /// An untagged enum with a variant per alternative, or `Value` when two
+    /// alternatives have the same type.
+    fn one_of(&mut self, alternatives: &Value, name: &str) -> String {
+        let Some(alternatives) = alternatives.as_array().filter(|a| !a.is_empty()) else {
+            return "Value".to_string();
+        };
+        let kinds: Vec<&str> = alternatives
+            .iter()
+            .map(|alternative| alternative["type"].as_str().unwrap_or_default())
+            .collect();
+        let distinct: HashSet<&&str> = kinds.iter().collect();
+        if kinds.contains(&"") || distinct.len() != kinds.len() {
+            return "Value".to_string();
+        }
+
+        let name = self.unique(name);
+        let mut variants = String::new();
+        for (alternative, kind) in alternatives.iter().zip(kinds) {
+            let variant = pascal_case(kind);
+            let ty = self.rust_type(alternative, &format!("{}{}", name, variant));
+            let _ = writeln!(variants, "    {}({}),", variant, ty);
+        }
+        self.items.push(format!(
+            "#[derive(Debug, Clone, PartialEq, Serialize)]\n#[serde(untagged)]\npub enum {} {{\n{}}}\n",
+            name, variants
+        ));
+        name
+    }
+}
+
+fn doc_comment(out: &mut String, indent: &str, text: &str) {
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            let _ = writeln!(out, "{}///", indent);
+        } else {
+            let _ = writeln!(out, "{}/// {}", indent, line);
+        }
+    }
+}
+
+/// The words of a name: split at anything but letters and digits, and
+/// where a lowercase letter or digit is followed by an uppercase one.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if !c.is_alphanumeric() {
+            words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+            previous_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lower {
+            words.push(std::mem::take(&mut word));
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        word.extend(c.to_lowercase());
+    }
+    words.extend((!word.is_empty()).then_some(word));
+    words
+}
+
+fn pascal_case(name: &str) -> String {
+    let mut pascal: String = words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect();
+    if pascal.is_empty() || pascal.starts_with(|c: char| c.is_ascii_digit()) {
+        pascal.insert(0, 'V');
+    }
+    pascal
+}
+
+/// A snake_case identifier for a property or tool name.
+fn field_name(name: &str) -> String {
+    let mut snake = words(name).join("_");
+    if snake.is_empty() || snake.starts_with(|c: char| c.is_ascii_digit()) {
+        snake.insert(0, '_');
+    }
+    if KEYWORDS.contains(&snake.as_str()) {
+        format!("r#{}", snake)
+    } else if RESERVED.contains(&snake.as_str()) {
+        format!("{}_", snake)
+    } else {
+        snake
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(name: &str, input_schema: Value) -> Tool {
+        Tool {
+            name: name.to_string(),
+            description: format!("The {} tool", name),
+            input_schema,
+            annotations: None,
+        }
+    }
+
+    #[test]
+    fn test_names_become_identifiers() {
+        assert_eq!(pascal_case("file_operations"), "FileOperations");
+        assert_eq!(pascal_case("half-even"), "HalfEven");
+        assert_eq!(pascal_case("mimeType"), "MimeType");
+        assert_eq!(pascal_case("2fa"), "V2fa");
+        assert_eq!(field_name("mimeType"), "mime_type");
+        assert_eq!(field_name("type"), "r#type");
+        assert_eq!(field_name("self"), "self_");
+        assert_eq!(field_name("new"), "new_");
+        assert_eq!(field_name("max-items"), "max_items");
+    }
+
+    #[test]
+    fn test_schemas_become_typed_structs() {
+        let tools = [
+            tool(
+                "calculator",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "operation": { "type": "string", "enum": ["add", "divide"] },
+                        "a": {
+                            "description": "First number, or a variable",
+                            "oneOf": [{ "type": "number" }, { "type": "string" }]
+                        },
+                        "store_as": { "type": "string" },
+                        "type": { "type": ["integer", "null"] },
+                        "points": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": { "x": { "type": "number" } },
+                                "required": ["x"]
+                            }
+                        },
+                        "mimeType": { "type": "string" }
+                    },
+                    "required": ["operation", "a"]
+                }),
+            ),
+            tool("list_variables", serde_json::json!({ "type": "object" })),
+        ];
+        let source = generate_client("calculator", &tools);
+        syn::parse_file(&source).expect("generated code parses");
+
+        for expected in [
+            "pub struct CalculatorClient",
+            "pub struct CalculatorArgs {",
+            "pub operation: CalculatorOperation,",
+            "    #[serde(rename = \"divide\")]\n    Divide,",
+            "    /// First number, or a variable\n    pub a: CalculatorA,",
+            "#[serde(untagged)]\npub enum CalculatorA {\n    Number(f64),\n    String(String),\n}",
+            "pub store_as: Option<String>,",
+            "pub r#type: Option<i64>,",
+            "pub points: Option<Vec<CalculatorPointsItem>>,",
+            "pub struct CalculatorPointsItem {\n    pub x: f64,\n}",
+            "#[serde(rename = \"mimeType\")]",
+            "pub async fn calculator(&self, args: &CalculatorArgs)",
+            "pub async fn list_variables(&self) -> Result<Value, McpError>",
+        ] {
+            assert!(
+                source.contains(expected),
+                "missing {:?} in\n{}",
+                expected,
+                source
+            );
+        }
+        // Required fields and enums have no sensible default
+        assert!(!source.contains("Default, PartialEq, Serialize)]\npub struct CalculatorArgs"));
+    }
+
+    #[test]
+    fn test_clashing_names_are_made_unique() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "mode": { "type": "string", "enum": ["a", "b"] },
+                "Mode": { "type": "string", "enum": ["c"] },
+                "casing": { "type": "string", "enum": ["x", "X"] }
+            }
+        });
+        let source = generate_client("modes", &[tool("set", schema.clone()), tool("set", schema)]);
+        syn::parse_file(&source).expect("generated code parses");
+
+        assert!(source.contains("pub struct SetArgs2"));
+        assert!(source.contains("pub enum SetMode2"));
+        assert!(source.contains("pub mode_: Option<"));
+        assert!(source.contains("pub casing: Option<String>,"));
+        assert!(source.contains("pub async fn set_(&self, args: &SetArgs2)"));
+        assert!(source.contains("#[derive(Debug, Clone, Default, PartialEq, Serialize)]"));
+    }
+}
@@ -0,0 +1,359 @@
+//! Compression of large JSON-RPC messages on network transports.
+//!
+//! Documents, exports and query results can run to megabytes of JSON, which
+//! compresses well. Small messages do not gain enough to be worth the CPU,
+//! so only messages of at least [`CompressionPolicy::min_bytes`] are
+//! compressed, and only when that makes them smaller.
+//!
+//! A client lists the algorithms it accepts in `initialize`, preferred first,
+//! as an experimental capability:
+//!
+//! ```json
+//! "capabilities": { "experimental": { "compression": ["zstd", "gzip"] } }
+//! ```
+//!
+//! The server picks the first one it offers and answers with the algorithm
+//! and threshold the connection got, or leaves `compression` out when they
+//! share none:
+//!
+//! ```json
+//! "capabilities": { "experimental": { "compression": { "algorithm": "zstd", "minBytes": 1024 } } }
+//! ```
+//!
+//! From then on either side may compress what it sends. Over WebSocket a
+//! compressed message is a binary message; over streamable HTTP it is a body
+//! with a `Content-Encoding` header. Event stream notifications stay plain
+//! text, as Server-Sent Events require, and stdio is left alone.
+//!
+//! ```
+//! # use mcp_rust_examples::compression::{self, Compression, CompressionPolicy};
+//! let accepted = [Compression::Gzip];
+//! let compressor = CompressionPolicy::default().negotiate(&accepted).unwrap();
+//! let message = format!(r#"{{"text":"{}"}}"#, "word ".repeat(1000));
+//! let compressed = compressor.compress(message.as_bytes()).unwrap();
+//! assert!(compressed.len() < message.len());
+//! assert_eq!(compression::decompress(&compressed, 1 << 20).unwrap(), message.as_bytes());
+//! // Small messages are sent as they are
+//! assert_eq!(compressor.compress(b"{}"), None);
+//! ```
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// The algorithms a server offers, e.g. `zstd,gzip`, or `off`.
+pub const COMPRESSION_ENV: &str = "MCP_COMPRESSION";
+
+/// The smallest message a server compresses, in bytes.
+pub const COMPRESSION_MIN_BYTES_ENV: &str = "MCP_COMPRESSION_MIN_BYTES";
+
+/// The smallest message compressed unless configured otherwise.
+pub const DEFAULT_MIN_BYTES: usize = 1024;
+
+// Favours speed: messages are compressed once and read once
+const ZSTD_LEVEL: i32 = 3;
+
+/// A name that is not a [`Compression`].
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("unknown compression '{0}'; expected gzip or zstd")]
+pub struct UnknownCompression(pub String);
+
+/// Why a compressed message could not be read.
+#[derive(Debug, thiserror::Error)]
+pub enum CompressionError {
+    #[error("message is not gzip or zstd compressed")]
+    UnknownFormat,
+
+    #[error("corrupt compressed message: {0}")]
+    Corrupt(#[from] std::io::Error),
+
+    #[error("decompressed message is larger than {0} bytes")]
+    TooLarge(usize),
+}
+
+/// A compression algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Every algorithm, in the order servers prefer them.
+    pub const ALL: [Compression; 2] = [Self::Zstd, Self::Gzip];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// The algorithm `bytes` were compressed with, told by their magic
+    /// number.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    pub fn compress(&self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::fast());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            Self::Zstd => zstd::encode_all(bytes, ZSTD_LEVEL),
+        }
+    }
+
+    /// Decompresses `bytes`, refusing to produce more than `limit` bytes so a
+    /// small message cannot expand without end.
+    pub fn decompress(&self, bytes: &[u8], limit: usize) -> Result<Vec<u8>, CompressionError> {
+        let reader: Box<dyn Read + '_> = match self {
+            Self::Gzip => Box::new(GzDecoder::new(bytes)),
+            Self::Zstd => Box::new(zstd::Decoder::new(bytes)?),
+        };
+        let mut output = Vec::new();
+        reader.take(limit as u64 + 1).read_to_end(&mut output)?;
+        if output.len() > limit {
+            return Err(CompressionError::TooLarge(limit));
+        }
+        Ok(output)
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Compression {
+    type Err = UnknownCompression;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "gz" => Ok(Self::Gzip),
+            "zstd" | "zstandard" => Ok(Self::Zstd),
+            _ => Err(UnknownCompression(s.to_string())),
+        }
+    }
+}
+
+/// Decompresses a message with whichever algorithm it was compressed with.
+pub fn decompress(bytes: &[u8], limit: usize) -> Result<Vec<u8>, CompressionError> {
+    Compression::detect(bytes)
+        .ok_or(CompressionError::UnknownFormat)?
+        .decompress(bytes, limit)
+}
+
+/// The algorithms a client accepts, from the experimental `compression`
+/// capability of its `initialize` request. Names this crate does not know are
+/// skipped.
+pub fn accepted(capabilities: &Value) -> Vec<Compression> {
+    capabilities["experimental"]["compression"]
+        .as_array()
+        .map_or(&[][..], Vec::as_slice)
+        .iter()
+        .filter_map(|name| name.as_str()?.parse().ok())
+        .collect()
+}
+
+/// What a server compresses with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    /// The algorithms offered; none turns compression off.
+    pub algorithms: Vec<Compression>,
+    pub min_bytes: usize,
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self {
+            algorithms: Compression::ALL.to_vec(),
+            min_bytes: DEFAULT_MIN_BYTES,
+        }
+    }
+}
+
+impl CompressionPolicy {
+    pub fn disabled() -> Self {
+        Self {
+            algorithms: Vec::new(),
+            ..Self::default()
+        }
+    }
+
+    /// The default policy, with what [`COMPRESSION_ENV`] and
+    /// [`COMPRESSION_MIN_BYTES_ENV`] say instead. Invalid values are ignored.
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+        if let Ok(value) = std::env::var(COMPRESSION_ENV) {
+            let value = value.trim();
+            if matches!(value, "off" | "none" | "false" | "0") {
+                policy.algorithms.clear();
+            } else {
+                let algorithms: Result<Vec<_>, _> = value.split(',').map(str::parse).collect();
+                match algorithms {
+                    Ok(algorithms) => policy.algorithms = algorithms,
+                    Err(e) => tracing::warn!("Ignoring invalid {}: {}", COMPRESSION_ENV, e),
+                }
+            }
+        }
+        if let Ok(value) = std::env::var(COMPRESSION_MIN_BYTES_ENV) {
+            match value.trim().parse() {
+                Ok(min_bytes) => policy.min_bytes = min_bytes,
+                Err(_) => tracing::warn!(
+                    "Ignoring invalid {}: '{}'",
+                    COMPRESSION_MIN_BYTES_ENV,
+                    value
+                ),
+            }
+        }
+        policy
+    }
+
+    /// The compression for a client that accepts `accepted`: its most
+    /// preferred algorithm that this policy offers.
+    pub fn negotiate(&self, accepted: &[Compression]) -> Option<Compressor> {
+        let algorithm = accepted
+            .iter()
+            .find(|algorithm| self.algorithms.contains(algorithm))?;
+        Some(Compressor {
+            algorithm: *algorithm,
+            min_bytes: self.min_bytes,
+        })
+    }
+}
+
+/// The compression one connection agreed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compressor {
+    pub algorithm: Compression,
+    pub min_bytes: usize,
+}
+
+impl Compressor {
+    /// `message` compressed, or `None` when it is too small to bother or
+    /// compressing would not make it smaller.
+    pub fn compress(&self, message: &[u8]) -> Option<Vec<u8>> {
+        if message.len() < self.min_bytes {
+            return None;
+        }
+        match self.algorithm.compress(message) {
+            Ok(compressed) if compressed.len() < message.len() => Some(compressed),
+            Ok(_) => None,
+            Err(e) => {
+                tracing::warn!(algorithm = %self.algorithm, error = %e, "Compression failed");
+                None
+            }
+        }
+    }
+
+    /// The experimental `compression` capability a server answers
+    /// `initialize` with.
+    pub fn to_capability(&self) -> Value {
+        serde_json::json!({ "algorithm": self.algorithm, "minBytes": self.min_bytes })
+    }
+
+    /// Reads the capability written by [`to_capability`](Self::to_capability).
+    pub fn from_capability(capability: &Value) -> Option<Self> {
+        Some(Self {
+            algorithm: capability["algorithm"].as_str()?.parse().ok()?,
+            min_bytes: capability["minBytes"]
+                .as_u64()
+                .map_or(DEFAULT_MIN_BYTES, |min_bytes| min_bytes as usize),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trips_and_limits() {
+        let message = "{\"rows\":[1,2,3]}".repeat(200);
+        for algorithm in Compression::ALL {
+            let compressed = algorithm.compress(message.as_bytes()).unwrap();
+            assert_eq!(Compression::detect(&compressed), Some(algorithm));
+            assert_eq!(
+                decompress(&compressed, message.len()).unwrap(),
+                message.as_bytes()
+            );
+            let error = decompress(&compressed, 100).unwrap_err();
+            assert!(
+                matches!(error, CompressionError::TooLarge(100)),
+                "{}",
+                error
+            );
+        }
+        assert!(matches!(
+            decompress(b"{}", 100),
+            Err(CompressionError::UnknownFormat)
+        ));
+        assert!(matches!(
+            decompress(&[0x1f, 0x8b, 0, 0], 100),
+            Err(CompressionError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn test_negotiates_the_clients_preference() {
+        let initialize = json!({ "experimental": { "compression": ["brotli", "gzip", "zstd"] } });
+        let accepted = accepted(&initialize);
+        assert_eq!(accepted, [Compression::Gzip, Compression::Zstd]);
+
+        let compressor = CompressionPolicy::default().negotiate(&accepted).unwrap();
+        assert_eq!(compressor.algorithm, Compression::Gzip);
+        assert_eq!(
+            Compressor::from_capability(&compressor.to_capability()),
+            Some(compressor)
+        );
+
+        let zstd_only = CompressionPolicy {
+            algorithms: vec![Compression::Zstd],
+            min_bytes: 10,
+        };
+        assert_eq!(
+            zstd_only.negotiate(&accepted).map(|c| c.algorithm),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(CompressionPolicy::disabled().negotiate(&accepted), None);
+        assert_eq!(CompressionPolicy::default().negotiate(&[]), None);
+    }
+
+    #[test]
+    fn test_skips_small_and_incompressible_messages() {
+        let compressor = Compressor {
+            algorithm: Compression::Zstd,
+            min_bytes: 64,
+        };
+        assert_eq!(compressor.compress(&[b'a'; 63]), None);
+        assert!(compressor.compress(&[b'a'; 64]).is_some());
+
+        // Random bytes grow when compressed
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        assert_eq!(compressor.compress(&noise), None);
+    }
+}
@@ -0,0 +1,568 @@
+//! Asking the user for input in the middle of a tool call.
+//!
+//! MCP elicitation lets a server pause a tool call and send the client an
+//! `elicitation/create` request: a message plus a flat JSON schema describing
+//! the answer it needs. The client shows a form and replies with one of three
+//! actions:
+//!
+//! ```json
+//! { "action": "accept", "content": { "overwrite": true } }
+//! { "action": "decline" }
+//! { "action": "cancel" }
+//! ```
+//!
+//! Servers go through [`Elicitation::ask`], which adds a timeout and checks
+//! accepted content against the schema, so a tool only has to handle an
+//! [`ElicitationOutcome`]. How the request reaches the user is up to the
+//! [`Elicitor`]; [`ChannelElicitor`] hands requests to whatever task talks to
+//! the client, and [`TransportElicitor`] sends them to the client of the
+//! request being handled. For the latter, the transport opens a [`channel`]
+//! per connection, runs each request in its [`scope`], writes out the
+//! requests the receiver yields and passes the client's responses to
+//! [`ElicitationSink::resolve`].
+
+use crate::capabilities::{self, ClientFeature};
+use crate::protocol::{JsonRpcRequest, JsonRpcResponse, RequestId};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// The method a server calls to ask the user for input.
+pub const CREATE_METHOD: &str = "elicitation/create";
+
+tokio::task_local! {
+    static SINK: ElicitationSink;
+}
+
+/// Errors raised when the client cannot be asked, or answers with something
+/// that does not match the request.
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ElicitationError {
+    #[error("client does not support elicitation")]
+    Unsupported,
+
+    #[error("client disconnected before answering")]
+    Disconnected,
+
+    #[error("invalid elicitation response: {0}")]
+    InvalidResponse(String),
+}
+
+/// The shape of the answer a server asks for. MCP restricts it to an object
+/// whose properties are primitives: strings (optionally an enum), numbers,
+/// integers and booleans.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ElicitationSchema {
+    #[serde(rename = "type")]
+    pub schema_type: String,
+    pub properties: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+}
+
+impl Default for ElicitationSchema {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ElicitationSchema {
+    pub fn new() -> Self {
+        Self {
+            schema_type: "object".to_string(),
+            properties: Map::new(),
+            required: Vec::new(),
+        }
+    }
+
+    pub fn boolean(self, name: &str, description: &str, required: bool) -> Self {
+        self.property(
+            name,
+            serde_json::json!({ "type": "boolean", "description": description }),
+            required,
+        )
+    }
+
+    pub fn string(self, name: &str, description: &str, required: bool) -> Self {
+        self.property(
+            name,
+            serde_json::json!({ "type": "string", "description": description }),
+            required,
+        )
+    }
+
+    pub fn integer(self, name: &str, description: &str, required: bool) -> Self {
+        self.property(
+            name,
+            serde_json::json!({ "type": "integer", "description": description }),
+            required,
+        )
+    }
+
+    /// A string that must be one of `options`.
+    pub fn choice(self, name: &str, description: &str, options: &[&str], required: bool) -> Self {
+        self.property(
+            name,
+            serde_json::json!({ "type": "string", "description": description, "enum": options }),
+            required,
+        )
+    }
+
+    fn property(mut self, name: &str, schema: Value, required: bool) -> Self {
+        self.properties.insert(name.to_string(), schema);
+        if required {
+            self.required.push(name.to_string());
+        }
+        self
+    }
+
+    /// Checks accepted content: required fields are present, no unknown
+    /// fields are sent, and every value has the declared type.
+    pub fn validate(&self, content: &Map<String, Value>) -> Result<(), ElicitationError> {
+        let invalid = |message: String| Err(ElicitationError::InvalidResponse(message));
+
+        if let Some(missing) = self
+            .required
+            .iter()
+            .find(|name| !content.contains_key(*name))
+        {
+            return invalid(format!("missing required field '{}'", missing));
+        }
+
+        for (name, value) in content {
+            let Some(schema) = self.properties.get(name) else {
+                return invalid(format!("unexpected field '{}'", name));
+            };
+
+            let type_matches = match schema["type"].as_str() {
+                Some("boolean") => value.is_boolean(),
+                Some("string") => value.is_string(),
+                Some("integer") => value.is_i64() || value.is_u64(),
+                Some("number") => value.is_number(),
+                _ => false,
+            };
+            if !type_matches {
+                return invalid(format!("field '{}' has the wrong type", name));
+            }
+
+            if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+                if !options.contains(value) {
+                    return invalid(format!("field '{}' is not one of the options", name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The `elicitation/create` request sent to the client.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ElicitationRequest {
+    pub message: String,
+    #[serde(rename = "requestedSchema")]
+    pub requested_schema: ElicitationSchema,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ElicitationAction {
+    /// The user submitted the form.
+    Accept,
+    /// The user explicitly refused to answer.
+    Decline,
+    /// The user dismissed the prompt without choosing.
+    Cancel,
+}
+
+/// The client's answer to an [`ElicitationRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ElicitationResponse {
+    pub action: ElicitationAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<Map<String, Value>>,
+}
+
+impl ElicitationResponse {
+    /// Accepts with the given content, which should be a JSON object.
+    pub fn accept(content: Value) -> Self {
+        Self {
+            action: ElicitationAction::Accept,
+            content: match content {
+                Value::Object(map) => Some(map),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn decline() -> Self {
+        Self {
+            action: ElicitationAction::Decline,
+            content: None,
+        }
+    }
+
+    pub fn cancel() -> Self {
+        Self {
+            action: ElicitationAction::Cancel,
+            content: None,
+        }
+    }
+}
+
+/// What a tool gets back from [`Elicitation::ask`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElicitationOutcome {
+    /// The user answered; the content matches the schema.
+    Accepted(Map<String, Value>),
+    Declined,
+    Cancelled,
+    /// No answer arrived within the timeout.
+    TimedOut,
+}
+
+/// Delivers elicitation requests to the client and waits for the answer.
+pub trait Elicitor: Send + Sync {
+    fn elicit(
+        &self,
+        request: ElicitationRequest,
+    ) -> BoxFuture<'_, Result<ElicitationResponse, ElicitationError>>;
+}
+
+/// An elicitation request waiting for the client's answer.
+#[derive(Debug)]
+pub struct PendingElicitation {
+    pub request: ElicitationRequest,
+    reply: oneshot::Sender<ElicitationResponse>,
+}
+
+impl PendingElicitation {
+    /// Sends the answer back to the waiting tool call. Answers that arrive
+    /// after the tool gave up are dropped.
+    pub fn respond(self, response: ElicitationResponse) {
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Forwards elicitation requests over a channel to the task that talks to
+/// the client, e.g. the one writing to stdout or a WebSocket.
+#[derive(Debug, Clone)]
+pub struct ChannelElicitor {
+    sender: mpsc::UnboundedSender<PendingElicitation>,
+}
+
+impl ChannelElicitor {
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<PendingElicitation>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { sender }, receiver)
+    }
+}
+
+impl Elicitor for ChannelElicitor {
+    fn elicit(
+        &self,
+        request: ElicitationRequest,
+    ) -> BoxFuture<'_, Result<ElicitationResponse, ElicitationError>> {
+        Box::pin(async move {
+            let (reply, answer) = oneshot::channel();
+            self.sender
+                .send(PendingElicitation { request, reply })
+                .map_err(|_| ElicitationError::Disconnected)?;
+            answer.await.map_err(|_| ElicitationError::Disconnected)
+        })
+    }
+}
+
+/// Where the elicitation requests of a connection's tool calls go, and the
+/// calls waiting for the client to answer them.
+#[derive(Debug, Clone)]
+pub struct ElicitationSink {
+    requests: mpsc::UnboundedSender<JsonRpcRequest>,
+    waiting: Arc<Mutex<HashMap<RequestId, oneshot::Sender<JsonRpcResponse>>>>,
+    sent: Arc<AtomicU64>,
+}
+
+impl ElicitationSink {
+    /// Passes a response from the client to the tool call waiting for it.
+    /// Returns false when `message` answers no elicitation request, e.g.
+    /// because it is not a response or the call already gave up.
+    pub fn resolve(&self, message: &str) -> bool {
+        // A request from the client may reuse one of our ids
+        let response = serde_json::from_str::<Value>(message)
+            .ok()
+            .filter(|value| value.get("method").is_none())
+            .and_then(|value| serde_json::from_value::<JsonRpcResponse>(value).ok());
+        let Some(response) = response else {
+            return false;
+        };
+        let Some(id) = &response.id else {
+            return false;
+        };
+        let Some(reply) = self.waiting.lock().unwrap().remove(id) else {
+            return false;
+        };
+        let _ = reply.send(response);
+        true
+    }
+}
+
+/// A sink for one connection, and the `elicitation/create` requests sent to
+/// it in order.
+pub fn channel() -> (ElicitationSink, mpsc::UnboundedReceiver<JsonRpcRequest>) {
+    let (requests, receiver) = mpsc::unbounded_channel();
+    let sink = ElicitationSink {
+        requests,
+        waiting: Arc::new(Mutex::new(HashMap::new())),
+        sent: Arc::new(AtomicU64::new(0)),
+    };
+    (sink, receiver)
+}
+
+/// Runs `future` with its elicitation requests going to `sink`. Transports
+/// wrap each request of a connection in this.
+pub async fn scope<F: Future>(sink: ElicitationSink, future: F) -> F::Output {
+    SINK.scope(sink, future).await
+}
+
+// Forgets a request whose tool call stopped waiting, e.g. on a timeout, so
+// a late answer is not mistaken for a live one
+struct Waiting<'a> {
+    sink: &'a ElicitationSink,
+    id: RequestId,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.sink.waiting.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Sends elicitation requests to the client of the request being handled,
+/// over the transport it came in on. Outside a transport's [`scope`] there is
+/// nobody to ask, and [`Elicitation::ask`] fails with
+/// [`ElicitationError::Unsupported`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransportElicitor;
+
+impl Elicitor for TransportElicitor {
+    fn elicit(
+        &self,
+        request: ElicitationRequest,
+    ) -> BoxFuture<'_, Result<ElicitationResponse, ElicitationError>> {
+        Box::pin(async move {
+            let sink = SINK
+                .try_with(ElicitationSink::clone)
+                .map_err(|_| ElicitationError::Unsupported)?;
+            let sent = sink.sent.fetch_add(1, Ordering::Relaxed) + 1;
+            let id = RequestId::String(format!("elicitation-{}", sent));
+
+            let (reply, answer) = oneshot::channel();
+            sink.waiting.lock().unwrap().insert(id.clone(), reply);
+            let _waiting = Waiting {
+                sink: &sink,
+                id: id.clone(),
+            };
+            sink.requests
+                .send(JsonRpcRequest::new(
+                    id,
+                    CREATE_METHOD,
+                    serde_json::json!(request),
+                ))
+                .map_err(|_| ElicitationError::Disconnected)?;
+
+            let response = answer.await.map_err(|_| ElicitationError::Disconnected)?;
+            if let Some(error) = response.error {
+                return Err(ElicitationError::InvalidResponse(error.message));
+            }
+            let result = response.result.ok_or_else(|| {
+                ElicitationError::InvalidResponse("response without a result".to_string())
+            })?;
+            serde_json::from_value(result)
+                .map_err(|e| ElicitationError::InvalidResponse(e.to_string()))
+        })
+    }
+}
+
+/// Server-side handle for asking the user questions during tool calls.
+#[derive(Clone)]
+pub struct Elicitation {
+    elicitor: Arc<dyn Elicitor>,
+    timeout: Duration,
+}
+
+impl Elicitation {
+    pub fn new(elicitor: Arc<dyn Elicitor>, timeout: Duration) -> Self {
+        Self { elicitor, timeout }
+    }
+
+    /// Asks the user and waits up to the timeout for an answer. Fails with
+    /// [`ElicitationError::Unsupported`] without asking when the current
+    /// client did not declare elicitation, so the caller can fall back.
+    pub async fn ask(
+        &self,
+        message: impl Into<String>,
+        schema: ElicitationSchema,
+    ) -> Result<ElicitationOutcome, ElicitationError> {
+        if !capabilities::client_supports(ClientFeature::Elicitation) {
+            return Err(ElicitationError::Unsupported);
+        }
+        let request = ElicitationRequest {
+            message: message.into(),
+            requested_schema: schema.clone(),
+        };
+
+        let response = match tokio::time::timeout(self.timeout, self.elicitor.elicit(request)).await
+        {
+            Ok(response) => response?,
+            Err(_) => return Ok(ElicitationOutcome::TimedOut),
+        };
+
+        match response.action {
+            ElicitationAction::Accept => {
+                let content = response.content.ok_or_else(|| {
+                    ElicitationError::InvalidResponse("accept without content".to_string())
+                })?;
+                schema.validate(&content)?;
+                Ok(ElicitationOutcome::Accepted(content))
+            }
+            ElicitationAction::Decline => Ok(ElicitationOutcome::Declined),
+            ElicitationAction::Cancel => Ok(ElicitationOutcome::Cancelled),
+        }
+    }
+}
+
+impl std::fmt::Debug for Elicitation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Elicitation")
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn confirm_schema() -> ElicitationSchema {
+        ElicitationSchema::new()
+            .boolean("confirm", "Go ahead?", true)
+            .choice("mode", "How", &["fast", "safe"], false)
+    }
+
+    #[test]
+    fn test_schema_serializes_to_mcp_shape() {
+        let request = ElicitationRequest {
+            message: "Proceed?".to_string(),
+            requested_schema: confirm_schema(),
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["requestedSchema"]["type"], "object");
+        assert_eq!(
+            json["requestedSchema"]["properties"]["mode"]["enum"],
+            serde_json::json!(["fast", "safe"])
+        );
+        assert_eq!(
+            json["requestedSchema"]["required"],
+            serde_json::json!(["confirm"])
+        );
+
+        let response: ElicitationResponse =
+            serde_json::from_value(serde_json::json!({ "action": "decline" })).unwrap();
+        assert_eq!(response, ElicitationResponse::decline());
+    }
+
+    #[test]
+    fn test_schema_validation() {
+        let schema = confirm_schema();
+        let content = |value: Value| value.as_object().unwrap().clone();
+
+        assert!(schema
+            .validate(&content(
+                serde_json::json!({ "confirm": true, "mode": "safe" })
+            ))
+            .is_ok());
+        for bad in [
+            serde_json::json!({}),
+            serde_json::json!({ "confirm": "yes" }),
+            serde_json::json!({ "confirm": true, "mode": "reckless" }),
+            serde_json::json!({ "confirm": true, "extra": 1 }),
+        ] {
+            assert!(matches!(
+                schema.validate(&content(bad)),
+                Err(ElicitationError::InvalidResponse(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ask_over_channel() {
+        let (elicitor, mut requests) = ChannelElicitor::channel();
+        let elicitation = Elicitation::new(Arc::new(elicitor), Duration::from_secs(5));
+
+        tokio::spawn(async move {
+            let pending = requests.recv().await.unwrap();
+            assert_eq!(pending.request.message, "Proceed?");
+            pending.respond(ElicitationResponse::accept(
+                serde_json::json!({ "confirm": true }),
+            ));
+
+            let pending = requests.recv().await.unwrap();
+            pending.respond(ElicitationResponse::cancel());
+
+            // Drop the third request unanswered
+            let _ = requests.recv().await.unwrap();
+        });
+
+        let outcome = elicitation.ask("Proceed?", confirm_schema()).await.unwrap();
+        assert_eq!(
+            outcome,
+            ElicitationOutcome::Accepted(
+                serde_json::json!({ "confirm": true })
+                    .as_object()
+                    .unwrap()
+                    .clone()
+            )
+        );
+        assert_eq!(
+            elicitation.ask("Again?", confirm_schema()).await,
+            Ok(ElicitationOutcome::Cancelled)
+        );
+        assert_eq!(
+            elicitation.ask("Once more?", confirm_schema()).await,
+            Err(ElicitationError::Disconnected)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ask_times_out() {
+        // Keep the receiver alive so the request is delivered but never answered
+        let (elicitor, _requests) = ChannelElicitor::channel();
+        let elicitation = Elicitation::new(Arc::new(elicitor), Duration::from_millis(20));
+
+        assert_eq!(
+            elicitation.ask("Anyone there?", confirm_schema()).await,
+            Ok(ElicitationOutcome::TimedOut)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_does_not_ask_clients_without_elicitation() {
+        let (elicitor, mut requests) = ChannelElicitor::channel();
+        let elicitation = Elicitation::new(Arc::new(elicitor), Duration::from_secs(1));
+
+        let client = capabilities::ClientCapabilities::from_initialize(&Value::Null);
+        let result = capabilities::scope(
+            Some(Arc::new(client)),
+            elicitation.ask("Proceed?", confirm_schema()),
+        )
+        .await;
+        assert_eq!(result, Err(ElicitationError::Unsupported));
+        assert!(requests.try_recv().is_err());
+    }
+}
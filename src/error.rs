@@ -0,0 +1,211 @@
+//! Structured errors for tool calls.
+//!
+//! Tools used to fail with a bare `String`, so a client could not tell a
+//! mistyped argument from a database outage. [`McpError`] keeps the kind of
+//! failure and maps it to a JSON-RPC error code.
+//!
+//! A failed `tools/call` is still answered with an `isError` result, whose
+//! text is the message so the model can read it and try again; the code goes
+//! in the result's `_meta`:
+//!
+//! ```json
+//! {"content":[{"type":"text","text":"User 7 not found"}],"isError":true,
+//!  "_meta":{"error":{"code":-32002,"kind":"not_found"}}}
+//! ```
+//!
+//! Failures outside a tool call become a [`JsonRpcError`] with the same code.
+//! Helpers that still return `String` errors convert with `?`; those become
+//! [`McpError::Internal`].
+
+use crate::protocol::{error_codes, JsonRpcError};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum McpError {
+    /// The arguments are missing, malformed or out of range.
+    #[error("{0}")]
+    InvalidParams(String),
+    /// What the call refers to does not exist.
+    #[error("{0}")]
+    NotFound(String),
+    /// The call is not allowed, e.g. a path outside the allowed directories.
+    #[error("{0}")]
+    PermissionDenied(String),
+    /// Too many calls; the client should wait `retry_after` if given.
+    #[error("{message}")]
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// The call clashes with the current state, e.g. a duplicate key.
+    #[error("{0}")]
+    Conflict(String),
+    /// Something the tool depends on is down or timed out.
+    #[error("{0}")]
+    Unavailable(String),
+    /// Anything else.
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl McpError {
+    /// The JSON-RPC error code for this kind of error.
+    pub fn code(&self) -> i64 {
+        match self {
+            McpError::InvalidParams(_) => error_codes::INVALID_PARAMS,
+            McpError::NotFound(_) => error_codes::NOT_FOUND,
+            McpError::PermissionDenied(_) => error_codes::PERMISSION_DENIED,
+            McpError::RateLimited { .. } => error_codes::RATE_LIMITED,
+            McpError::Conflict(_) => error_codes::CONFLICT,
+            McpError::Unavailable(_) => error_codes::UNAVAILABLE,
+            McpError::Internal(_) => error_codes::INTERNAL_ERROR,
+        }
+    }
+
+    /// A stable name for the kind, e.g. `"not_found"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            McpError::InvalidParams(_) => "invalid_params",
+            McpError::NotFound(_) => "not_found",
+            McpError::PermissionDenied(_) => "permission_denied",
+            McpError::RateLimited { .. } => "rate_limited",
+            McpError::Conflict(_) => "conflict",
+            McpError::Unavailable(_) => "unavailable",
+            McpError::Internal(_) => "internal",
+        }
+    }
+
+    /// Rebuilds an error from its code, e.g. one reported by another server.
+    /// Unknown codes become [`McpError::Internal`].
+    pub fn from_code(code: i64, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match code {
+            error_codes::INVALID_PARAMS => McpError::InvalidParams(message),
+            error_codes::NOT_FOUND => McpError::NotFound(message),
+            error_codes::PERMISSION_DENIED => McpError::PermissionDenied(message),
+            error_codes::RATE_LIMITED => McpError::RateLimited {
+                message,
+                retry_after: None,
+            },
+            error_codes::CONFLICT => McpError::Conflict(message),
+            error_codes::UNAVAILABLE => McpError::Unavailable(message),
+            _ => McpError::Internal(message),
+        }
+    }
+
+    /// The `_meta` of an `isError` result reporting this error.
+    pub fn meta(&self) -> Value {
+        let mut error = json!({ "code": self.code(), "kind": self.kind() });
+        if let McpError::RateLimited {
+            retry_after: Some(retry_after),
+            ..
+        } = self
+        {
+            error["retryAfterSeconds"] = json!(retry_after.as_secs());
+        }
+        json!({ "error": error })
+    }
+
+    /// Reads an error back from an `isError` result's text and `_meta`.
+    pub fn from_meta(message: impl Into<String>, meta: Option<&Value>) -> Self {
+        let error = meta.and_then(|meta| meta.get("error"));
+        let code = error
+            .and_then(|error| error["code"].as_i64())
+            .unwrap_or(error_codes::INTERNAL_ERROR);
+        match McpError::from_code(code, message) {
+            McpError::RateLimited { message, .. } => McpError::RateLimited {
+                message,
+                retry_after: error
+                    .and_then(|error| error["retryAfterSeconds"].as_u64())
+                    .map(Duration::from_secs),
+            },
+            other => other,
+        }
+    }
+
+    pub fn to_json_rpc(&self) -> JsonRpcError {
+        let mut error = JsonRpcError::new(self.code(), self.to_string());
+        if let McpError::RateLimited {
+            retry_after: Some(retry_after),
+            ..
+        } = self
+        {
+            error.data = Some(json!({ "retryAfterSeconds": retry_after.as_secs() }));
+        }
+        error
+    }
+}
+
+impl From<String> for McpError {
+    fn from(message: String) -> Self {
+        McpError::Internal(message)
+    }
+}
+
+impl From<&str> for McpError {
+    fn from(message: &str) -> Self {
+        McpError::Internal(message.to_string())
+    }
+}
+
+impl From<McpError> for JsonRpcError {
+    fn from(error: McpError) -> Self {
+        error.to_json_rpc()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_round_trip() {
+        let errors = [
+            McpError::InvalidParams("bad".to_string()),
+            McpError::NotFound("missing".to_string()),
+            McpError::PermissionDenied("no".to_string()),
+            McpError::RateLimited {
+                message: "slow down".to_string(),
+                retry_after: Some(Duration::from_secs(3)),
+            },
+            McpError::Conflict("taken".to_string()),
+            McpError::Unavailable("down".to_string()),
+            McpError::Internal("boom".to_string()),
+        ];
+        for error in errors {
+            let meta = error.meta();
+            assert_eq!(McpError::from_meta(error.to_string(), Some(&meta)), error);
+        }
+
+        // Without a code, an error is internal
+        assert_eq!(
+            McpError::from_meta("boom", None),
+            McpError::Internal("boom".to_string())
+        );
+        assert_eq!(
+            McpError::from("boom"),
+            McpError::Internal("boom".to_string())
+        );
+    }
+
+    #[test]
+    fn test_json_rpc_error() {
+        let error = McpError::RateLimited {
+            message: "Too many calls".to_string(),
+            retry_after: Some(Duration::from_secs(30)),
+        };
+        assert_eq!(
+            serde_json::to_value(error.to_json_rpc()).unwrap(),
+            json!({
+                "code": error_codes::RATE_LIMITED,
+                "message": "Too many calls",
+                "data": { "retryAfterSeconds": 30 }
+            })
+        );
+
+        let error: JsonRpcError = McpError::InvalidParams("Missing name".to_string()).into();
+        assert_eq!(error.code, -32602);
+        assert_eq!(error.message, "Missing name");
+    }
+}
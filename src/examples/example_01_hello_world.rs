@@ -4,9 +4,16 @@
 // It demonstrates the basic structure and initialization process
 // for an MCP server using the official rust-sdk.
 
+use futures::future::BoxFuture;
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::logging::ToolCallLog;
+use mcp_rust_examples::protocol::{Tool, ToolAnnotations};
+use mcp_rust_examples::server::McpServer;
+use mcp_rust_examples::tools::ToolServer;
+use mcp_rust_examples::transport::StdioTransport;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tokio::io::{stdin, stdout};
+use std::sync::Arc;
 
 // Step 1: Define the request structure for our greeting tool.
 // This struct represents the data that clients will send when calling our tool.
@@ -25,14 +32,6 @@ pub struct GreetingResponse {
     pub message: String,
 }
 
-// Step 3: Define tool metadata structure
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Tool {
-    pub name: String,
-    pub description: String,
-    pub input_schema: Value,
-}
-
 // Step 4: Create our MCP server handler struct.
 // This struct will handle MCP protocol messages.
 pub struct HelloWorldServer;
@@ -65,16 +64,26 @@ impl HelloWorldServer {
                 },
                 "required": ["name"]
             }),
+            annotations: Some(ToolAnnotations::read_only()),
         }]
     }
 
-    // Handle tool call requests - this is where the actual tool logic executes
-    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        ToolCallLog::run(name, arguments, |arguments| {
+            self.dispatch_tool(name, arguments)
+        })
+    }
+
+    // Handle tool call requests - this is where the actual tool logic executes.
+    // Errors say what went wrong, so bad arguments are reported as
+    // `InvalidParams` and an unknown tool as `NotFound`
+    fn dispatch_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
         match name {
             "greeting" => {
                 // Step 5: Parse the incoming request parameters
-                let request: GreetingRequest = serde_json::from_value(arguments)
-                    .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+                let request: GreetingRequest = serde_json::from_value(arguments).map_err(|e| {
+                    McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                })?;
 
                 // Step 6: Execute the tool logic (create a greeting)
                 let response = GreetingResponse {
@@ -83,74 +92,39 @@ impl HelloWorldServer {
 
                 // Step 7: Return the response as JSON
                 serde_json::to_value(response)
-                    .map_err(|e| format!("Failed to serialize response: {}", e))
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
             }
-            _ => Err(format!("Unknown tool: {}", name)),
+            _ => Err(McpError::NotFound(format!("Unknown tool: {}", name))),
         }
     }
+}
 
-    // Simple JSON-RPC message handler for demonstration
-    pub fn handle_message(&self, message: Value) -> Result<Value, String> {
-        let method = message
-            .get("method")
-            .and_then(|m| m.as_str())
-            .ok_or("Missing method")?;
-
-        match method {
-            "tools/list" => {
-                let tools = self.list_tools();
-                Ok(serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": message.get("id"),
-                    "result": {
-                        "tools": tools
-                    }
-                }))
-            }
-            "tools/call" => {
-                let params = message.get("params").ok_or("Missing params")?;
-
-                let tool_name = params
-                    .get("name")
-                    .and_then(|n| n.as_str())
-                    .ok_or("Missing tool name")?;
-
-                let arguments = params
-                    .get("arguments")
-                    .unwrap_or(&Value::Object(serde_json::Map::new()))
-                    .clone();
-
-                match self.call_tool(tool_name, arguments) {
-                    Ok(result) => Ok(serde_json::json!({
-                        "jsonrpc": "2.0",
-                        "id": message.get("id"),
-                        "result": {
-                            "content": [{
-                                "type": "text",
-                                "text": serde_json::to_string(&result).unwrap_or_default()
-                            }]
-                        }
-                    })),
-                    Err(error) => Ok(serde_json::json!({
-                        "jsonrpc": "2.0",
-                        "id": message.get("id"),
-                        "error": {
-                            "code": -32000,
-                            "message": error
-                        }
-                    })),
-                }
-            }
-            _ => Err(format!("Unknown method: {}", method)),
-        }
+// Step 8: Expose the server through the shared MCP runtime. `McpServer`
+// handles the JSON-RPC side (initialize, tools/list, tools/call) for any
+// `ToolServer`, so this example only has to provide its tools.
+impl ToolServer for HelloWorldServer {
+    fn server_name(&self) -> &str {
+        "hello_world"
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.list_tools()
+    }
+
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(async move { self.call_tool(name, arguments) })
     }
 }
 
-// Step 8: Main function to start the MCP server
+// Step 9: Main function to start the MCP server
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logging to help with debugging
-    tracing_subscriber::fmt::init();
+    let _telemetry = mcp_rust_examples::logging::init("error");
 
     eprintln!("🚀 Starting Hello World MCP Server");
     eprintln!("📝 Available tools: greeting");
@@ -158,51 +132,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("📋 Example: {{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/list\"}}");
     eprintln!();
 
-    // Create our server handler instance
-    let server = HelloWorldServer::new();
-
-    // Simple message loop for demonstration
-    // In a real implementation, this would be handled by the rmcp crate
-    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-
-    let stdin = stdin();
-    let mut stdout = stdout();
-    let mut reader = BufReader::new(stdin);
-    let mut line = String::new();
-
-    loop {
-        line.clear();
-        match reader.read_line(&mut line).await {
-            Ok(0) => break, // EOF
-            Ok(_) => {
-                let trimmed = line.trim();
-                if trimmed.is_empty() {
-                    continue;
-                }
-
-                match serde_json::from_str::<Value>(trimmed) {
-                    Ok(message) => match server.handle_message(message) {
-                        Ok(response) => {
-                            let response_str = serde_json::to_string(&response)?;
-                            stdout.write_all(response_str.as_bytes()).await?;
-                            stdout.write_all(b"\n").await?;
-                            stdout.flush().await?;
-                        }
-                        Err(e) => {
-                            eprintln!("Error handling message: {}", e);
-                        }
-                    },
-                    Err(e) => {
-                        eprintln!("Failed to parse JSON: {}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("Error reading input: {}", e);
-                break;
-            }
-        }
-    }
+    // Create our server handler instance and wrap it in the MCP runtime
+    let server = McpServer::new(Arc::new(HelloWorldServer::new()));
+
+    // Serve newline-delimited JSON-RPC on stdin/stdout until the client
+    // closes stdin
+    StdioTransport::new().serve(&server).await?;
 
     eprintln!("👋 Hello World server shutting down");
     Ok(())
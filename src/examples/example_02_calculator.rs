@@ -2,22 +2,108 @@
 //
 // This example builds upon the hello world server by adding a calculator tool
 // that demonstrates parameter validation, error handling, and multiple operations.
-
+// A set of financial tools (compound interest, amortization, NPV/IRR and
+// currency rounding) shows how the same patterns extend to a real domain.
+// Variables make the calculator multi-turn: a result stored with store_as or
+// set_variable can be used as an operand by later calls on the same connection.
+
+use futures::future::BoxFuture;
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::logging::ToolCallLog;
+use mcp_rust_examples::protocol::Tool;
+use mcp_rust_examples::schema::{mcp_tool, JsonSchema, TypedTool};
+use mcp_rust_examples::server::McpServer;
+use mcp_rust_examples::session;
+use mcp_rust_examples::tools::ToolServer;
+use mcp_rust_examples::transport::StdioTransport;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tokio::io::{stdin, stdout};
-
-// Define the calculator request structure with multiple parameters
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Define the calculator request structure with multiple parameters. The
+// tool's input schema is generated from it by #[mcp_tool].
+#[mcp_tool(
+    name = "calculator",
+    description = "Perform basic arithmetic operations (add, subtract, multiply, divide) \
+                   on numbers or variables set earlier in the session"
+)]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CalculatorRequest {
     // The mathematical operation to perform
+    #[tool(
+        description = "The operation to perform",
+        enum_values = ["add", "subtract", "multiply", "divide"]
+    )]
     pub operation: String,
     // First number in the calculation
-    pub a: f64,
+    #[tool(description = "First number, or the name of a variable")]
+    pub a: Operand,
     // Second number in the calculation
-    pub b: f64,
+    #[tool(description = "Second number, or the name of a variable")]
+    pub b: Operand,
+    // Keep the result for later calls
+    #[tool(description = "Store the result in this variable")]
+    pub store_as: Option<String>,
+}
+
+// A number, or the name of a variable holding one
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Operand {
+    Number(f64),
+    Variable(String),
 }
 
+impl JsonSchema for Operand {
+    fn json_schema() -> Value {
+        serde_json::json!({
+            "oneOf": [
+                { "type": "number" },
+                { "type": "string", "pattern": VARIABLE_NAME_PATTERN }
+            ]
+        })
+    }
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Number(value) => write!(f, "{}", value),
+            Operand::Variable(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+// Variables live in the connection's session, one key per variable
+const VARIABLE_KEY_PREFIX: &str = "calculator.variable.";
+const VARIABLE_NAME_PATTERN: &str = "^[A-Za-z_][A-Za-z0-9_]{0,63}$";
+
+#[mcp_tool(
+    name = "set_variable",
+    description = "Set a variable for later calculations in this session",
+    destructive,
+    idempotent
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetVariableRequest {
+    #[tool(description = "Variable name", pattern = VARIABLE_NAME_PATTERN)]
+    pub name: String,
+    pub value: f64,
+    // Forget the variable after this long; it lasts the session otherwise
+    #[tool(description = "Seconds until the variable is forgotten", minimum = 1)]
+    pub ttl_seconds: Option<u64>,
+}
+
+#[mcp_tool(
+    name = "list_variables",
+    description = "List the variables set in this session",
+    read_only
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListVariablesRequest {}
+
 // Define the calculator response structure
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CalculatorResponse {
@@ -25,6 +111,184 @@ pub struct CalculatorResponse {
     pub result: f64,
     // The operation that was performed (for confirmation)
     pub operation_performed: String,
+    // The variable the result was stored in, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_as: Option<String>,
+}
+
+// How monetary amounts are rounded to a number of decimal places
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    // Ties round away from zero (2.665 -> 2.67)
+    #[default]
+    HalfUp,
+    // Ties round to the even digit, a.k.a. banker's rounding (2.665 -> 2.66)
+    HalfEven,
+    // Toward zero
+    Down,
+    // Away from zero
+    Up,
+    // Toward positive infinity
+    Ceiling,
+    // Toward negative infinity
+    Floor,
+}
+
+impl JsonSchema for RoundingMode {
+    fn json_schema() -> Value {
+        serde_json::json!({
+            "type": "string",
+            "description": "How amounts are rounded to cents",
+            "enum": ["half_up", "half_even", "down", "up", "ceiling", "floor"],
+            "default": "half_up"
+        })
+    }
+}
+
+impl RoundingMode {
+    pub fn round(self, amount: f64, decimals: u32) -> f64 {
+        let factor = 10f64.powi(decimals as i32);
+        // Remove binary noise first so 2.675 is treated as an exact tie
+        let scaled = (amount * factor * 1e9).round() / 1e9;
+        let rounded = match self {
+            RoundingMode::HalfUp => scaled.round(),
+            RoundingMode::HalfEven => scaled.round_ties_even(),
+            RoundingMode::Down => scaled.trunc(),
+            RoundingMode::Up => {
+                if scaled >= 0.0 {
+                    scaled.ceil()
+                } else {
+                    scaled.floor()
+                }
+            }
+            RoundingMode::Ceiling => scaled.ceil(),
+            RoundingMode::Floor => scaled.floor(),
+        };
+        rounded / factor
+    }
+}
+
+// Financial tool requests
+#[mcp_tool(
+    name = "compound_interest",
+    description = "Future value of a principal with compound interest",
+    read_only
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CompoundInterestRequest {
+    #[tool(description = "Initial amount")]
+    pub principal: f64,
+    // Nominal annual rate in percent, e.g. 5.0 for 5%
+    #[tool(description = "Nominal annual interest rate in percent")]
+    pub annual_rate_percent: f64,
+    #[tool(minimum = 0)]
+    pub years: f64,
+    // Defaults to monthly compounding
+    #[tool(minimum = 1, default = 12)]
+    pub compounds_per_year: Option<u32>,
+    pub rounding: Option<RoundingMode>,
+}
+
+#[mcp_tool(
+    name = "amortization_schedule",
+    description = "Monthly payment and full payment schedule for a fixed-rate loan",
+    read_only
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AmortizationRequest {
+    #[tool(exclusive_minimum = 0)]
+    pub principal: f64,
+    #[tool(minimum = 0)]
+    pub annual_rate_percent: f64,
+    #[tool(minimum = 1, maximum = 1200)]
+    pub term_months: u32,
+    pub rounding: Option<RoundingMode>,
+}
+
+#[mcp_tool(
+    name = "npv",
+    description = "Net present value of periodic cash flows",
+    read_only
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NpvRequest {
+    // Cash flow per period, starting with the initial investment at period 0
+    #[tool(
+        description = "Cash flow per period, starting at period 0",
+        min_items = 1
+    )]
+    pub cash_flows: Vec<f64>,
+    #[tool(description = "Discount rate per period in percent")]
+    pub rate_percent: f64,
+}
+
+#[mcp_tool(
+    name = "irr",
+    description = "Internal rate of return of periodic cash flows",
+    read_only
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IrrRequest {
+    #[tool(
+        description = "Cash flow per period, starting at period 0",
+        min_items = 2
+    )]
+    pub cash_flows: Vec<f64>,
+}
+
+#[mcp_tool(
+    name = "round_currency",
+    description = "Round an amount using a currency rounding mode",
+    read_only
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RoundCurrencyRequest {
+    pub amount: f64,
+    #[tool(minimum = 0, maximum = 8, default = 2)]
+    pub decimals: Option<u32>,
+    pub mode: Option<RoundingMode>,
+}
+
+// Financial tool responses
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CompoundInterestResponse {
+    pub future_value: f64,
+    pub interest_earned: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AmortizationPayment {
+    pub period: u32,
+    pub payment: f64,
+    pub principal: f64,
+    pub interest: f64,
+    pub balance: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AmortizationSchedule {
+    pub monthly_payment: f64,
+    pub total_paid: f64,
+    pub total_interest: f64,
+    pub schedule: Vec<AmortizationPayment>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NpvResponse {
+    pub npv: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IrrResponse {
+    pub irr_percent: f64,
+    pub iterations: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RoundCurrencyResponse {
+    pub rounded: f64,
+    pub mode: RoundingMode,
 }
 
 // Custom error type for calculator-specific errors
@@ -32,6 +296,9 @@ pub struct CalculatorResponse {
 pub enum CalculatorError {
     DivisionByZero,
     UnsupportedOperation(String),
+    InvalidInput(String),
+    NoConvergence,
+    UnknownVariable(String),
 }
 
 impl std::fmt::Display for CalculatorError {
@@ -39,18 +306,28 @@ impl std::fmt::Display for CalculatorError {
         match self {
             CalculatorError::DivisionByZero => write!(f, "Division by zero is not allowed"),
             CalculatorError::UnsupportedOperation(op) => write!(f, "Unsupported operation: {}", op),
+            CalculatorError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            CalculatorError::NoConvergence => {
+                write!(f, "IRR did not converge for these cash flows")
+            }
+            CalculatorError::UnknownVariable(name) => {
+                write!(
+                    f,
+                    "Unknown variable: {} (set it with set_variable first)",
+                    name
+                )
+            }
         }
     }
 }
 
 impl std::error::Error for CalculatorError {}
 
-// Tool metadata structure
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Tool {
-    pub name: String,
-    pub description: String,
-    pub input_schema: Value,
+// Every calculator error comes from the numbers the caller passed in
+impl From<CalculatorError> for McpError {
+    fn from(error: CalculatorError) -> Self {
+        McpError::InvalidParams(error.to_string())
+    }
 }
 
 // The calculator server handler
@@ -70,16 +347,18 @@ impl CalculatorServer {
     // Private method to perform the actual calculation
     // This demonstrates separation of concerns and clean code principles
     fn perform_calculation(&self, request: &CalculatorRequest) -> Result<f64, CalculatorError> {
+        let a = resolve(&request.a)?;
+        let b = resolve(&request.b)?;
         match request.operation.as_str() {
-            "add" => Ok(request.a + request.b),
-            "subtract" => Ok(request.a - request.b),
-            "multiply" => Ok(request.a * request.b),
+            "add" => Ok(a + b),
+            "subtract" => Ok(a - b),
+            "multiply" => Ok(a * b),
             "divide" => {
                 // Validate that we're not dividing by zero
-                if request.b == 0.0 {
+                if b == 0.0 {
                     Err(CalculatorError::DivisionByZero)
                 } else {
-                    Ok(request.a / request.b)
+                    Ok(a / b)
                 }
             }
             _ => Err(CalculatorError::UnsupportedOperation(
@@ -88,44 +367,230 @@ impl CalculatorServer {
         }
     }
 
+    // FV = P * (1 + r/n)^(n*t)
+    fn compound_interest(
+        &self,
+        request: &CompoundInterestRequest,
+    ) -> Result<CompoundInterestResponse, CalculatorError> {
+        let compounds = request.compounds_per_year.unwrap_or(12);
+        if compounds == 0 {
+            return Err(CalculatorError::InvalidInput(
+                "compounds_per_year must be at least 1".to_string(),
+            ));
+        }
+        if request.years < 0.0 {
+            return Err(CalculatorError::InvalidInput(
+                "years cannot be negative".to_string(),
+            ));
+        }
+
+        let rate = request.annual_rate_percent / 100.0 / compounds as f64;
+        let future_value = request.principal * (1.0 + rate).powf(compounds as f64 * request.years);
+
+        let rounding = request.rounding.unwrap_or_default();
+        let future_value = rounding.round(future_value, 2);
+        Ok(CompoundInterestResponse {
+            future_value,
+            interest_earned: rounding.round(future_value - request.principal, 2),
+        })
+    }
+
+    // Fixed-payment loan schedule. Every amount is rounded to cents as it would
+    // be on a statement, and the final payment absorbs the rounding drift so
+    // the balance ends at exactly zero.
+    fn amortization_schedule(
+        &self,
+        request: &AmortizationRequest,
+    ) -> Result<AmortizationSchedule, CalculatorError> {
+        if request.term_months == 0 || request.term_months > 1200 {
+            return Err(CalculatorError::InvalidInput(
+                "term_months must be between 1 and 1200".to_string(),
+            ));
+        }
+        if request.principal <= 0.0 {
+            return Err(CalculatorError::InvalidInput(
+                "principal must be positive".to_string(),
+            ));
+        }
+
+        let rounding = request.rounding.unwrap_or_default();
+        let rate = request.annual_rate_percent / 100.0 / 12.0;
+        let months = request.term_months as f64;
+        let payment = if rate == 0.0 {
+            request.principal / months
+        } else {
+            request.principal * rate / (1.0 - (1.0 + rate).powf(-months))
+        };
+        let payment = rounding.round(payment, 2);
+
+        let mut balance = request.principal;
+        let mut schedule = Vec::with_capacity(request.term_months as usize);
+        for period in 1..=request.term_months {
+            let interest = rounding.round(balance * rate, 2);
+            let principal = if period == request.term_months {
+                balance
+            } else {
+                rounding.round(payment - interest, 2)
+            };
+            balance = rounding.round(balance - principal, 2);
+
+            schedule.push(AmortizationPayment {
+                period,
+                payment: rounding.round(principal + interest, 2),
+                principal,
+                interest,
+                balance,
+            });
+        }
+
+        let total_paid = rounding.round(schedule.iter().map(|p| p.payment).sum(), 2);
+        Ok(AmortizationSchedule {
+            monthly_payment: payment,
+            total_paid,
+            total_interest: rounding.round(total_paid - request.principal, 2),
+            schedule,
+        })
+    }
+
+    // NPV = sum of CF_t / (1 + r)^t, with the first cash flow at t = 0
+    fn net_present_value(cash_flows: &[f64], rate: f64) -> f64 {
+        cash_flows
+            .iter()
+            .enumerate()
+            .map(|(t, cf)| cf / (1.0 + rate).powi(t as i32))
+            .sum()
+    }
+
+    fn npv(&self, request: &NpvRequest) -> Result<NpvResponse, CalculatorError> {
+        let rate_percent = request.rate_percent;
+        if rate_percent <= -100.0 {
+            return Err(CalculatorError::InvalidInput(
+                "rate_percent must be greater than -100".to_string(),
+            ));
+        }
+        if request.cash_flows.is_empty() {
+            return Err(CalculatorError::InvalidInput(
+                "cash_flows cannot be empty".to_string(),
+            ));
+        }
+
+        Ok(NpvResponse {
+            npv: Self::net_present_value(&request.cash_flows, rate_percent / 100.0),
+        })
+    }
+
+    // Newton's method from a 10% guess, falling back to bisection when Newton
+    // leaves the valid range or stalls
+    fn irr(&self, request: &IrrRequest) -> Result<IrrResponse, CalculatorError> {
+        let flows = &request.cash_flows;
+        if !flows.iter().any(|cf| *cf > 0.0) || !flows.iter().any(|cf| *cf < 0.0) {
+            return Err(CalculatorError::InvalidInput(
+                "cash_flows need at least one positive and one negative value".to_string(),
+            ));
+        }
+
+        const TOLERANCE: f64 = 1e-10;
+        const MAX_ITERATIONS: u32 = 100;
+
+        let mut rate = 0.1;
+        for iteration in 1..=MAX_ITERATIONS {
+            let npv = Self::net_present_value(flows, rate);
+            let derivative: f64 = flows
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(t, cf)| -(t as f64) * cf / (1.0 + rate).powi(t as i32 + 1))
+                .sum();
+            if derivative == 0.0 {
+                break;
+            }
+
+            let next = rate - npv / derivative;
+            if next <= -1.0 || !next.is_finite() {
+                break;
+            }
+            if (next - rate).abs() < TOLERANCE {
+                return Ok(IrrResponse {
+                    irr_percent: next * 100.0,
+                    iterations: iteration,
+                });
+            }
+            rate = next;
+        }
+
+        let (mut low, mut high) = (-0.9999, 10.0);
+        let mut npv_low = Self::net_present_value(flows, low);
+        if npv_low.signum() == Self::net_present_value(flows, high).signum() {
+            return Err(CalculatorError::NoConvergence);
+        }
+        for iteration in 1..=MAX_ITERATIONS * 2 {
+            let mid = (low + high) / 2.0;
+            let npv_mid = Self::net_present_value(flows, mid);
+            if (high - low) / 2.0 < TOLERANCE {
+                return Ok(IrrResponse {
+                    irr_percent: mid * 100.0,
+                    iterations: iteration,
+                });
+            }
+            if npv_mid.signum() == npv_low.signum() {
+                low = mid;
+                npv_low = npv_mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        Err(CalculatorError::NoConvergence)
+    }
+
+    fn round_currency(
+        &self,
+        request: &RoundCurrencyRequest,
+    ) -> Result<RoundCurrencyResponse, CalculatorError> {
+        let decimals = request.decimals.unwrap_or(2);
+        if decimals > 8 {
+            return Err(CalculatorError::InvalidInput(
+                "decimals must be 8 or fewer".to_string(),
+            ));
+        }
+
+        let mode = request.mode.unwrap_or_default();
+        Ok(RoundCurrencyResponse {
+            rounded: mode.round(request.amount, decimals),
+            mode,
+        })
+    }
+
     pub fn list_tools(&self) -> Vec<Tool> {
-        vec![Tool {
-            name: "calculator".to_string(),
-            description: "Perform basic arithmetic operations (add, subtract, multiply, divide)"
-                .to_string(),
-            input_schema: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "operation": {
-                        "type": "string",
-                        "description": "The operation to perform",
-                        "enum": ["add", "subtract", "multiply", "divide"]
-                    },
-                    "a": {
-                        "type": "number",
-                        "description": "First number"
-                    },
-                    "b": {
-                        "type": "number",
-                        "description": "Second number"
-                    }
-                },
-                "required": ["operation", "a", "b"]
-            }),
-        }]
+        vec![
+            CalculatorRequest::tool(),
+            SetVariableRequest::tool(),
+            ListVariablesRequest::tool(),
+            CompoundInterestRequest::tool(),
+            AmortizationRequest::tool(),
+            NpvRequest::tool(),
+            IrrRequest::tool(),
+            RoundCurrencyRequest::tool(),
+        ]
     }
 
-    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        ToolCallLog::run(name, arguments, |arguments| {
+            self.dispatch_tool(name, arguments)
+        })
+    }
+
+    fn dispatch_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
         match name {
             "calculator" => {
                 // Parse the request
-                let request: CalculatorRequest = serde_json::from_value(arguments)
-                    .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+                let request = CalculatorRequest::parse(arguments)?;
 
                 // Perform the calculation
-                let result = self
-                    .perform_calculation(&request)
-                    .map_err(|e| e.to_string())?;
+                let result = self.perform_calculation(&request)?;
+                if let Some(name) = &request.store_as {
+                    store_variable(name, result, None)?;
+                }
 
                 // Create the response
                 let response = CalculatorResponse {
@@ -134,125 +599,144 @@ impl CalculatorServer {
                         "{} {} {}",
                         request.a, request.operation, request.b
                     ),
+                    stored_as: request.store_as,
                 };
 
                 serde_json::to_value(response)
-                    .map_err(|e| format!("Failed to serialize response: {}", e))
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
             }
-            _ => Err(format!("Unknown tool: {}", name)),
-        }
-    }
-
-    // Simple JSON-RPC message handler
-    pub fn handle_message(&self, message: Value) -> Result<Value, String> {
-        let method = message
-            .get("method")
-            .and_then(|m| m.as_str())
-            .ok_or("Missing method")?;
-
-        match method {
-            "tools/list" => {
-                let tools = self.list_tools();
-                Ok(serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "id": message.get("id"),
-                    "result": {
-                        "tools": tools
-                    }
-                }))
+            "set_variable" => {
+                let request = SetVariableRequest::parse(arguments)?;
+                let ttl = request.ttl_seconds.map(Duration::from_secs);
+                store_variable(&request.name, request.value, ttl)?;
+                Ok(serde_json::json!({ "name": request.name, "value": request.value }))
             }
-            "tools/call" => {
-                let params = message.get("params").ok_or("Missing params")?;
-
-                let tool_name = params
-                    .get("name")
-                    .and_then(|n| n.as_str())
-                    .ok_or("Missing tool name")?;
-
-                let arguments = params
-                    .get("arguments")
-                    .unwrap_or(&Value::Object(serde_json::Map::new()))
-                    .clone();
-
-                match self.call_tool(tool_name, arguments) {
-                    Ok(result) => Ok(serde_json::json!({
-                        "jsonrpc": "2.0",
-                        "id": message.get("id"),
-                        "result": {
-                            "content": [{
-                                "type": "text",
-                                "text": serde_json::to_string(&result).unwrap_or_default()
-                            }]
-                        }
-                    })),
-                    Err(error) => Ok(serde_json::json!({
-                        "jsonrpc": "2.0",
-                        "id": message.get("id"),
-                        "error": {
-                            "code": -32000,
-                            "message": error
-                        }
-                    })),
-                }
+            "list_variables" => {
+                ListVariablesRequest::parse(arguments)?;
+                Ok(serde_json::json!({ "variables": variables() }))
+            }
+            "compound_interest" => {
+                let request = CompoundInterestRequest::parse(arguments)?;
+                to_result(self.compound_interest(&request))
+            }
+            "amortization_schedule" => {
+                let request = AmortizationRequest::parse(arguments)?;
+                to_result(self.amortization_schedule(&request))
+            }
+            "npv" => {
+                let request = NpvRequest::parse(arguments)?;
+                to_result(self.npv(&request))
             }
-            _ => Err(format!("Unknown method: {}", method)),
+            "irr" => {
+                let request = IrrRequest::parse(arguments)?;
+                to_result(self.irr(&request))
+            }
+            "round_currency" => {
+                let request = RoundCurrencyRequest::parse(arguments)?;
+                to_result(self.round_currency(&request))
+            }
+            _ => Err(McpError::NotFound(format!("Unknown tool: {}", name))),
         }
     }
 }
 
+// Served over JSON-RPC by the shared MCP runtime
+impl ToolServer for CalculatorServer {
+    fn server_name(&self) -> &str {
+        "calculator"
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.list_tools()
+    }
+
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(async move { self.call_tool(name, arguments) })
+    }
+}
+
+// The value of an operand, looking variables up in the session
+fn resolve(operand: &Operand) -> Result<f64, CalculatorError> {
+    match operand {
+        Operand::Number(value) => Ok(*value),
+        Operand::Variable(name) => session::current()
+            .get::<f64>(&format!("{}{}", VARIABLE_KEY_PREFIX, name))
+            .ok_or_else(|| CalculatorError::UnknownVariable(name.clone())),
+    }
+}
+
+// Fails with the session's limit error, rather than a calculator error, when
+// the session has no room for another variable
+fn store_variable(name: &str, value: f64, ttl: Option<Duration>) -> Result<(), McpError> {
+    let mut chars = name.chars();
+    let valid = name.len() <= 64
+        && chars
+            .next()
+            .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric());
+    if !valid {
+        return Err(CalculatorError::InvalidInput(format!(
+            "Variable names must match {}",
+            VARIABLE_NAME_PATTERN
+        ))
+        .into());
+    }
+    if !value.is_finite() {
+        return Err(CalculatorError::InvalidInput(format!(
+            "{} cannot be stored in a variable",
+            value
+        ))
+        .into());
+    }
+
+    let session = session::current();
+    let key = format!("{}{}", VARIABLE_KEY_PREFIX, name);
+    match ttl {
+        Some(ttl) => session.set_with_ttl(key, value, ttl)?,
+        None => session.set(key, value)?,
+    }
+    Ok(())
+}
+
+// The session's variables by name
+fn variables() -> BTreeMap<String, f64> {
+    let session = session::current();
+    session
+        .keys()
+        .iter()
+        .filter_map(|key| {
+            let name = key.strip_prefix(VARIABLE_KEY_PREFIX)?;
+            Some((name.to_string(), session.get::<f64>(key)?))
+        })
+        .collect()
+}
+
+// Shared by the financial tools
+fn to_result<T: Serialize>(result: Result<T, CalculatorError>) -> Result<Value, McpError> {
+    let response = result?;
+    serde_json::to_value(response)
+        .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
+    let _telemetry = mcp_rust_examples::logging::init("error");
 
     eprintln!("🧮 Starting Calculator MCP Server");
-    eprintln!("📝 Available tools: calculator");
+    eprintln!("📝 Available tools: calculator, set_variable, list_variables, compound_interest, amortization_schedule, npv, irr, round_currency");
     eprintln!("💡 Send JSON-RPC messages via stdin");
     eprintln!("📋 Example: {{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/call\",\"params\":{{\"name\":\"calculator\",\"arguments\":{{\"operation\":\"add\",\"a\":5,\"b\":3}}}}}}");
     eprintln!();
 
-    let server = CalculatorServer::new();
-
-    // Message loop for JSON-RPC communication
-    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    let server = McpServer::new(Arc::new(CalculatorServer::new()));
 
-    let stdin = stdin();
-    let mut stdout = stdout();
-    let mut reader = BufReader::new(stdin);
-    let mut line = String::new();
-
-    loop {
-        line.clear();
-        match reader.read_line(&mut line).await {
-            Ok(0) => break, // EOF
-            Ok(_) => {
-                let trimmed = line.trim();
-                if trimmed.is_empty() {
-                    continue;
-                }
-
-                match serde_json::from_str::<Value>(trimmed) {
-                    Ok(message) => match server.handle_message(message) {
-                        Ok(response) => {
-                            let response_str = serde_json::to_string(&response)?;
-                            stdout.write_all(response_str.as_bytes()).await?;
-                            stdout.write_all(b"\n").await?;
-                            stdout.flush().await?;
-                        }
-                        Err(e) => {
-                            eprintln!("Error handling message: {}", e);
-                        }
-                    },
-                    Err(e) => {
-                        eprintln!("Failed to parse JSON: {}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("Error reading input: {}", e);
-                break;
-            }
-        }
-    }
+    // Serve newline-delimited JSON-RPC on stdin/stdout until the client
+    // closes stdin
+    StdioTransport::new().serve(&server).await?;
 
     eprintln!("🧮 Calculator server shutting down");
     Ok(())
@@ -285,8 +769,18 @@ mod tests {
         });
 
         let result = server.call_tool("calculator", div_zero_args);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Division by zero"));
+        assert_eq!(
+            result,
+            Err(McpError::InvalidParams(
+                "Division by zero is not allowed".to_string()
+            ))
+        );
+
+        // Arguments of the wrong type are invalid too, and unknown tools are not found
+        let result = server.call_tool("calculator", serde_json::json!({ "a": "five" }));
+        assert!(matches!(result, Err(McpError::InvalidParams(_))));
+        let result = server.call_tool("square_root", serde_json::json!({}));
+        assert!(matches!(result, Err(McpError::NotFound(_))));
     }
 
     #[test]
@@ -294,7 +788,146 @@ mod tests {
         let server = CalculatorServer::new();
         let tools = server.list_tools();
 
-        assert_eq!(tools.len(), 1);
+        assert_eq!(tools.len(), 8);
         assert_eq!(tools[0].name, "calculator");
+        for name in [
+            "set_variable",
+            "list_variables",
+            "compound_interest",
+            "amortization_schedule",
+            "npv",
+            "irr",
+            "round_currency",
+        ] {
+            assert!(tools.iter().any(|t| t.name == name));
+        }
+
+        // Schemas are generated from the request structs
+        let schema = &tools[4].input_schema;
+        assert_eq!(
+            schema["required"],
+            serde_json::json!(["principal", "annual_rate_percent", "term_months"])
+        );
+        assert_eq!(schema["properties"]["term_months"]["maximum"], 1200);
+        assert_eq!(schema["properties"]["rounding"]["default"], "half_up");
+        assert_eq!(
+            tools[0].input_schema["properties"]["operation"]["enum"][3],
+            "divide"
+        );
+    }
+
+    #[test]
+    fn test_financial_tools() {
+        let server = CalculatorServer::new();
+
+        // $1,000 at 5% compounded annually for 10 years
+        let result = server
+            .call_tool(
+                "compound_interest",
+                serde_json::json!({
+                    "principal": 1000.0,
+                    "annual_rate_percent": 5.0,
+                    "years": 10.0,
+                    "compounds_per_year": 1
+                }),
+            )
+            .unwrap();
+        assert_eq!(result["future_value"], 1628.89);
+
+        // 30-year $100,000 mortgage at 6%
+        let result = server
+            .call_tool(
+                "amortization_schedule",
+                serde_json::json!({
+                    "principal": 100000.0,
+                    "annual_rate_percent": 6.0,
+                    "term_months": 360
+                }),
+            )
+            .unwrap();
+        let schedule: AmortizationSchedule = serde_json::from_value(result).unwrap();
+        assert_eq!(schedule.monthly_payment, 599.55);
+        assert_eq!(schedule.schedule.len(), 360);
+        assert_eq!(schedule.schedule.last().unwrap().balance, 0.0);
+
+        let cash_flows = serde_json::json!([-1000.0, 300.0, 400.0, 500.0]);
+        let result = server
+            .call_tool(
+                "irr",
+                serde_json::json!({ "cash_flows": cash_flows.clone() }),
+            )
+            .unwrap();
+        let irr = result["irr_percent"].as_f64().unwrap();
+        assert!((irr - 8.896).abs() < 0.001);
+
+        // Discounting at the IRR gives an NPV of zero
+        let result = server
+            .call_tool(
+                "npv",
+                serde_json::json!({ "cash_flows": cash_flows, "rate_percent": irr }),
+            )
+            .unwrap();
+        assert!(result["npv"].as_f64().unwrap().abs() < 1e-6);
+
+        let result = server.call_tool("irr", serde_json::json!({ "cash_flows": [100.0, 200.0] }));
+        assert!(result.unwrap_err().to_string().contains("Invalid input"));
+    }
+
+    #[tokio::test]
+    async fn test_variables_last_for_the_session() {
+        let server = CalculatorServer::new();
+        let store = Arc::new(session::SessionStore::new());
+
+        session::scope(store.clone(), async {
+            let args = serde_json::json!({ "operation": "multiply", "a": 6, "b": 7, "store_as": "answer" });
+            let result = server.call_tool("calculator", args).unwrap();
+            assert_eq!(result["stored_as"], "answer");
+
+            let args = serde_json::json!({ "name": "rate", "value": 0.5 });
+            server.call_tool("set_variable", args).unwrap();
+            let args = serde_json::json!({ "operation": "multiply", "a": "answer", "b": "rate" });
+            let result = server.call_tool("calculator", args).unwrap();
+            let response: CalculatorResponse = serde_json::from_value(result).unwrap();
+            assert_eq!(response.result, 21.0);
+            assert_eq!(response.operation_performed, "answer multiply rate");
+
+            let result = server
+                .call_tool("list_variables", serde_json::json!({}))
+                .unwrap();
+            assert_eq!(
+                result["variables"],
+                serde_json::json!({ "answer": 42.0, "rate": 0.5 })
+            );
+
+            let args = serde_json::json!({ "name": "2fast", "value": 1 });
+            let result = server.call_tool("set_variable", args);
+            assert!(matches!(result, Err(McpError::InvalidParams(_))));
+        })
+        .await;
+
+        // Another session has variables of its own
+        session::scope(Arc::new(session::SessionStore::new()), async {
+            let args = serde_json::json!({ "operation": "add", "a": "answer", "b": 1 });
+            let result = server.call_tool("calculator", args);
+            assert_eq!(
+                result,
+                Err(McpError::InvalidParams(
+                    "Unknown variable: answer (set it with set_variable first)".to_string()
+                ))
+            );
+        })
+        .await;
+        assert_eq!(store.keys().len(), 2);
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        assert_eq!(RoundingMode::HalfUp.round(2.665, 2), 2.67);
+        assert_eq!(RoundingMode::HalfEven.round(2.665, 2), 2.66);
+        assert_eq!(RoundingMode::HalfEven.round(2.675, 2), 2.68);
+        assert_eq!(RoundingMode::Down.round(-1.239, 2), -1.23);
+        assert_eq!(RoundingMode::Up.round(-1.231, 2), -1.24);
+        assert_eq!(RoundingMode::Ceiling.round(-1.239, 2), -1.23);
+        assert_eq!(RoundingMode::Floor.round(1.231, 2), 1.23);
     }
 }
@@ -4,8 +4,18 @@
 // for text processing operations. It shows how to organize multiple tools
 // within a MCP server.
 
+use futures::future::BoxFuture;
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::logging::ToolCallLog;
+use mcp_rust_examples::protocol::{Tool, ToolAnnotations};
+use mcp_rust_examples::server::McpServer;
+use mcp_rust_examples::tools::ToolServer;
+use mcp_rust_examples::transport::{self, StdioTransport};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
 
 // Request structures for different text operations
 #[derive(Serialize, Deserialize, Debug)]
@@ -19,6 +29,20 @@ pub struct TextAnalysisRequest {
     pub text: String,
 }
 
+// A caller-supplied pattern for PII the built-in detectors don't know about,
+// e.g. employee IDs. Matches are replaced with `[NAME]`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomPiiPattern {
+    pub name: String,
+    pub pattern: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RedactPiiRequest {
+    pub text: String,
+    pub custom_patterns: Option<Vec<CustomPiiPattern>>,
+}
+
 // Response structures
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TextResponse {
@@ -35,12 +59,23 @@ pub struct TextAnalysisResponse {
     pub has_numbers: bool,
 }
 
-// Tool metadata structure
+// One piece of PII found in the input. The report never repeats the value
+// itself; `hint` keeps just enough to recognise it (e.g. a card's last digits).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PiiFinding {
+    pub kind: String,
+    // Byte offsets into the original text
+    pub start: usize,
+    pub end: usize,
+    pub hint: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Tool {
-    pub name: String,
-    pub description: String,
-    pub input_schema: Value,
+pub struct RedactPiiResponse {
+    pub redacted_text: String,
+    pub findings: Vec<PiiFinding>,
+    // Number of findings per kind
+    pub summary: BTreeMap<String, usize>,
 }
 
 // The text processing server with multiple related tools
@@ -58,14 +93,17 @@ impl TextProcessorServer {
     }
 
     // Helper method for text transformation operations
-    fn transform_text(&self, text: &str, operation: &str) -> Result<String, String> {
+    fn transform_text(&self, text: &str, operation: &str) -> Result<String, McpError> {
         match operation {
             "uppercase" => Ok(text.to_uppercase()),
             "lowercase" => Ok(text.to_lowercase()),
             "reverse" => Ok(text.chars().rev().collect()),
             "capitalize" => Ok(self.capitalize_words(text)),
             "trim" => Ok(text.trim().to_string()),
-            _ => Err(format!("Unsupported transformation: {}", operation)),
+            _ => Err(McpError::InvalidParams(format!(
+                "Unsupported transformation: {}",
+                operation
+            ))),
         }
     }
 
@@ -95,6 +133,84 @@ impl TextProcessorServer {
         }
     }
 
+    // Helper method for PII detection. Detectors run in priority order (card
+    // numbers before phone numbers, which they would otherwise resemble) and a
+    // match overlapping an earlier finding is ignored.
+    fn detect_pii(
+        &self,
+        text: &str,
+        custom_patterns: &[CustomPiiPattern],
+    ) -> Result<Vec<PiiFinding>, McpError> {
+        let mut detectors: Vec<(String, &Regex)> = vec![
+            ("CREDIT_CARD".to_string(), credit_card_regex()),
+            ("EMAIL".to_string(), email_regex()),
+            ("PHONE".to_string(), phone_regex()),
+        ];
+
+        let custom = custom_patterns
+            .iter()
+            .map(|custom| {
+                Regex::new(&custom.pattern)
+                    .map(|regex| (custom.name.to_uppercase(), regex))
+                    .map_err(|e| {
+                        McpError::InvalidParams(format!("Invalid pattern '{}': {}", custom.name, e))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        detectors.extend(custom.iter().map(|(name, regex)| (name.clone(), regex)));
+
+        let mut findings: Vec<PiiFinding> = Vec::new();
+        for (kind, regex) in detectors {
+            for found in regex.find_iter(text) {
+                if found.as_str().is_empty() {
+                    continue;
+                }
+                if kind == "CREDIT_CARD" && !luhn_valid(found.as_str()) {
+                    continue;
+                }
+                let overlaps = findings
+                    .iter()
+                    .any(|f| found.start() < f.end && f.start < found.end());
+                if overlaps {
+                    continue;
+                }
+
+                findings.push(PiiFinding {
+                    hint: pii_hint(&kind, found.as_str()),
+                    kind: kind.clone(),
+                    start: found.start(),
+                    end: found.end(),
+                });
+            }
+        }
+
+        findings.sort_by_key(|f| f.start);
+        Ok(findings)
+    }
+
+    // Helper method that replaces each finding with a `[KIND]` placeholder
+    fn redact_pii(&self, request: &RedactPiiRequest) -> Result<RedactPiiResponse, McpError> {
+        let custom_patterns = request.custom_patterns.as_deref().unwrap_or_default();
+        let findings = self.detect_pii(&request.text, custom_patterns)?;
+
+        let mut redacted_text = String::with_capacity(request.text.len());
+        let mut summary = BTreeMap::new();
+        let mut last = 0;
+        for finding in &findings {
+            redacted_text.push_str(&request.text[last..finding.start]);
+            redacted_text.push_str(&format!("[{}]", finding.kind));
+            last = finding.end;
+            *summary.entry(finding.kind.clone()).or_insert(0) += 1;
+        }
+        redacted_text.push_str(&request.text[last..]);
+
+        Ok(RedactPiiResponse {
+            redacted_text,
+            findings,
+            summary,
+        })
+    }
+
     pub fn list_tools(&self) -> Vec<Tool> {
         vec![
             // Text transformation tool
@@ -116,6 +232,7 @@ impl TextProcessorServer {
                     },
                     "required": ["text", "operation"]
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
             // Text analysis tool
             Tool {
@@ -131,46 +248,193 @@ impl TextProcessorServer {
                     },
                     "required": ["text"]
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            // PII redaction tool
+            Tool {
+                name: "redact_pii".to_string(),
+                description: "Detect and redact emails, phone numbers, credit card numbers and custom patterns"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "text": {
+                            "type": "string",
+                            "description": "The text to sanitize"
+                        },
+                        "custom_patterns": {
+                            "type": "array",
+                            "description": "Additional regular expressions to redact",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "name": {
+                                        "type": "string",
+                                        "description": "Label used in the placeholder, e.g. EMPLOYEE_ID"
+                                    },
+                                    "pattern": {
+                                        "type": "string",
+                                        "description": "Regular expression to match"
+                                    }
+                                },
+                                "required": ["name", "pattern"]
+                            }
+                        }
+                    },
+                    "required": ["text"]
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
         ]
     }
 
-    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        ToolCallLog::run(name, arguments, |arguments| {
+            self.dispatch_tool(name, arguments)
+        })
+    }
+
+    fn dispatch_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
         match name {
             "transform_text" => {
-                let request: TextTransformRequest = serde_json::from_value(arguments)
-                    .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+                let request: TextTransformRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
 
                 let result = self.transform_text(&request.text, &request.operation)?;
 
                 let response = TextResponse { result };
                 serde_json::to_value(response)
-                    .map_err(|e| format!("Failed to serialize response: {}", e))
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
             }
             "analyze_text" => {
-                let request: TextAnalysisRequest = serde_json::from_value(arguments)
-                    .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+                let request: TextAnalysisRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
 
                 let response = self.analyze_text(&request.text);
                 serde_json::to_value(response)
-                    .map_err(|e| format!("Failed to serialize response: {}", e))
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
+            }
+            "redact_pii" => {
+                let request: RedactPiiRequest = serde_json::from_value(arguments).map_err(|e| {
+                    McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                })?;
+
+                let response = self.redact_pii(&request)?;
+                serde_json::to_value(response)
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
+            }
+            _ => Err(McpError::NotFound(format!("Unknown tool: {}", name))),
+        }
+    }
+}
+
+// Built-in PII patterns, compiled once
+fn email_regex() -> &'static Regex {
+    static EMAIL: OnceLock<Regex> = OnceLock::new();
+    EMAIL.get_or_init(|| Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap())
+}
+
+fn phone_regex() -> &'static Regex {
+    static PHONE: OnceLock<Regex> = OnceLock::new();
+    PHONE.get_or_init(|| {
+        Regex::new(r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{3}\)|\b\d{3})[\s.-]?\d{3}[\s.-]?\d{4}\b").unwrap()
+    })
+}
+
+fn credit_card_regex() -> &'static Regex {
+    static CREDIT_CARD: OnceLock<Regex> = OnceLock::new();
+    CREDIT_CARD.get_or_init(|| Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap())
+}
+
+// Luhn checksum, which every real card number satisfies. This filters out
+// order numbers and other long digit runs.
+fn luhn_valid(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if i % 2 == 1 {
+                let doubled = d * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                d
             }
-            _ => Err(format!("Unknown tool: {}", name)),
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+fn pii_hint(kind: &str, value: &str) -> String {
+    match kind {
+        "CREDIT_CARD" => {
+            let digits: String = value.chars().filter(|c| c.is_ascii_digit()).collect();
+            format!("****{}", &digits[digits.len() - 4..])
         }
+        "EMAIL" => match value.split_once('@') {
+            Some((_, domain)) => format!("***@{}", domain),
+            None => "***".to_string(),
+        },
+        "PHONE" => {
+            let digits: String = value.chars().filter(|c| c.is_ascii_digit()).collect();
+            format!("***{}", &digits[digits.len().saturating_sub(2)..])
+        }
+        _ => format!("{} chars", value.chars().count()),
+    }
+}
+
+// Served over stdio with --stdio, and to clients that work with several servers
+impl ToolServer for TextProcessorServer {
+    fn server_name(&self) -> &str {
+        "text_processor"
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.list_tools()
+    }
+
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(async move { self.call_tool(name, arguments) })
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
+    let _telemetry = mcp_rust_examples::logging::init("error");
 
     eprintln!("📝 Starting Text Processor MCP Server");
-    eprintln!("🛠️  Available tools: transform_text, analyze_text");
-    eprintln!("💡 Send JSON-RPC messages via stdin");
+    eprintln!("🛠️  Available tools: transform_text, analyze_text, redact_pii");
+    eprintln!("💡 Run with --stdio to serve JSON-RPC messages on stdin");
 
-    // Simple demo mode for testing
     let server = TextProcessorServer::new();
 
+    // With --stdio, serve the tools to an MCP client instead of running the demo
+    if transport::stdio_requested() {
+        StdioTransport::new()
+            .serve(&McpServer::new(Arc::new(server)))
+            .await?;
+        return Ok(());
+    }
+
+    // Simple demo mode for testing
+
     // Demo usage
     eprintln!("\n🧪 Running demo transformations:");
 
@@ -206,6 +470,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Err(e) => eprintln!("❌ Analysis failed: {}", e),
     }
 
+    let pii_args = serde_json::json!({
+        "text": "Contact jane.doe@example.com or (555) 123-4567, card 4111 1111 1111 1111, badge EMP-00421",
+        "custom_patterns": [{ "name": "employee_id", "pattern": "EMP-\\d{5}" }]
+    });
+
+    match server.call_tool("redact_pii", pii_args) {
+        Ok(result) => {
+            let response: RedactPiiResponse = serde_json::from_value(result).unwrap();
+            eprintln!("✅ Redacted: '{}'", response.redacted_text);
+            for finding in &response.findings {
+                eprintln!(
+                    "   - {} at {}..{} ({})",
+                    finding.kind, finding.start, finding.end, finding.hint
+                );
+            }
+        }
+        Err(e) => eprintln!("❌ Redaction failed: {}", e),
+    }
+
     eprintln!("\n🎉 Text processor demo completed");
     Ok(())
 }
@@ -262,8 +545,46 @@ mod tests {
         let server = TextProcessorServer::new();
         let tools = server.list_tools();
 
-        assert_eq!(tools.len(), 2);
+        assert_eq!(tools.len(), 3);
         assert!(tools.iter().any(|t| t.name == "transform_text"));
         assert!(tools.iter().any(|t| t.name == "analyze_text"));
+        assert!(tools.iter().any(|t| t.name == "redact_pii"));
+    }
+
+    #[test]
+    fn test_pii_redaction() {
+        let server = TextProcessorServer::new();
+
+        let args = serde_json::json!({
+            "text": "Mail bob@corp.io, call +1 555-123-4567. Card 4111-1111-1111-1111, order 1234567890123. ID EMP-00042",
+            "custom_patterns": [{ "name": "employee_id", "pattern": "EMP-\\d{5}" }]
+        });
+
+        let result = server.call_tool("redact_pii", args).unwrap();
+        let response: RedactPiiResponse = serde_json::from_value(result).unwrap();
+
+        assert_eq!(
+            response.redacted_text,
+            "Mail [EMAIL], call [PHONE]. Card [CREDIT_CARD], order 1234567890123. ID [EMPLOYEE_ID]"
+        );
+        assert_eq!(response.findings.len(), 4);
+        assert_eq!(response.summary.get("CREDIT_CARD"), Some(&1));
+
+        let card = response
+            .findings
+            .iter()
+            .find(|f| f.kind == "CREDIT_CARD")
+            .unwrap();
+        assert_eq!(card.hint, "****1111");
+
+        // Invalid custom patterns are reported rather than ignored
+        let args = serde_json::json!({
+            "text": "anything",
+            "custom_patterns": [{ "name": "broken", "pattern": "(" }]
+        });
+        assert!(matches!(
+            server.call_tool("redact_pii", args),
+            Err(McpError::InvalidParams(_))
+        ));
     }
 }
@@ -3,14 +3,114 @@
 // This example demonstrates how to build an MCP client that can connect to
 // and interact with MCP servers. It shows the client-side perspective of
 // the MCP protocol.
+//
+// Servers can also send requests to the client. The client answers
+// `sampling/createMessage` by handing the conversation to a SamplingHandler,
+// which may call an LLM backend or, in tests, return canned replies.
+//
+// Results of the tools the client knows (users, system metrics, directory
+// listings) can be read into typed structs. The client checks those results
+// against the schema it expects and reports drift when a server's answers
+// no longer match, before a missing field turns into a parse error.
+//
+// With --scenario <file> the client runs a scripted sequence of tool calls
+// from YAML instead of the demo, checking each result against what the
+// scenario expects (see examples/scenarios/). A scenario can start any of
+// the example servers over stdio, which makes it a repeatable acceptance
+// test for that server.
+//
+// With --generate-bindings <server command...> the client starts a server,
+// lists its tools and prints a typed Rust client for them (see
+// mcp_rust_examples::codegen), so callers build argument structs instead of
+// `json!` values. A JSON file holding a tools/list result works in place of
+// the command, e.g. for a server that is not at hand.
 
+use futures::future::BoxFuture;
+use mcp_rust_examples::codegen;
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::protocol::{
+    error_codes, CallToolResult, JsonRpcError, JsonRpcRequest, JsonRpcResponse, ListToolsResult,
+    RequestId, Tool, ToolAnnotations, PROTOCOL_VERSION,
+};
+use mcp_rust_examples::sampling::{
+    CreateMessageRequest, CreateMessageResult, CREATE_MESSAGE_METHOD,
+};
+use mcp_rust_examples::schema::{self, SchemaViolation};
+use mcp_rust_examples::tools::ToolServer;
+use mcp_rust_examples::yaml;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+
+// Produces the completions servers ask for with sampling/createMessage
+pub trait SamplingHandler: Send + Sync {
+    fn create_message(
+        &self,
+        request: CreateMessageRequest,
+    ) -> BoxFuture<'_, Result<CreateMessageResult, String>>;
+}
+
+// Answers sampling requests with prepared replies, in order, and remembers
+// what it was asked
+pub struct CannedSampler {
+    model: String,
+    replies: Mutex<VecDeque<String>>,
+    received: Mutex<Vec<CreateMessageRequest>>,
+}
+
+impl CannedSampler {
+    pub fn new(model: &str, replies: &[&str]) -> Self {
+        Self {
+            model: model.to_string(),
+            replies: Mutex::new(replies.iter().map(|reply| reply.to_string()).collect()),
+            received: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn received(&self) -> Vec<CreateMessageRequest> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+impl SamplingHandler for CannedSampler {
+    fn create_message(
+        &self,
+        request: CreateMessageRequest,
+    ) -> BoxFuture<'_, Result<CreateMessageResult, String>> {
+        Box::pin(async move {
+            self.received.lock().unwrap().push(request);
+            let reply = self
+                .replies
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or("No canned reply left")?;
+            Ok(CreateMessageResult::text(&self.model, reply))
+        })
+    }
+}
 
 // Structure to represent an MCP client application
 pub struct SimpleMcpClient {
     // This simulates a connection to an MCP server
     server_url: String,
+    // Servers attached in-process; their tools take precedence over the
+    // simulated ones
+    servers: Vec<Arc<dyn ToolServer>>,
+    // Answers sampling requests; without one the client does not offer sampling
+    sampling: Option<Arc<dyn SamplingHandler>>,
+    // Schemas results of known tools are checked against, by tool name
+    response_schemas: HashMap<String, Value>,
+    // Results that did not match their schema
+    drift: Mutex<Vec<SchemaDrift>>,
 }
 
 // Structures for client-server communication
@@ -19,6 +119,15 @@ pub struct ToolInfo {
     pub name: String,
     pub description: String,
     pub input_schema: Value,
+    #[serde(default)]
+    pub annotations: Option<ToolAnnotations>,
+}
+
+// A tool found during discovery, along with the server that provides it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DiscoveredTool {
+    pub server: String,
+    pub tool: ToolInfo,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -34,12 +143,391 @@ pub struct ToolCallResponse {
     pub error: Option<String>,
 }
 
+// The result of a known tool, with the schema the client expects it to
+// follow. The structs mirror what the example servers return.
+pub trait KnownResponse: DeserializeOwned {
+    // The tools whose results have this shape
+    const TOOLS: &'static [&'static str];
+
+    fn output_schema() -> Value;
+}
+
+// A user of the database server (example 09)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct User {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+    pub age: Option<i32>,
+    pub version: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl KnownResponse for User {
+    const TOOLS: &'static [&'static str] = &["create_user", "get_user", "update_user"];
+
+    fn output_schema() -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer", "minimum": 1 },
+                "name": { "type": "string", "minLength": 1 },
+                "email": { "type": "string", "pattern": "^[^@\\s]+@[^@\\s]+$" },
+                "age": { "type": ["integer", "null"], "minimum": 0 },
+                "version": { "type": "integer", "minimum": 1 },
+                "created_at": { "type": "string" },
+                "updated_at": { "type": "string" }
+            },
+            "required": ["id", "name", "email", "version", "created_at", "updated_at"],
+            "additionalProperties": false
+        })
+    }
+}
+
+// A metrics sample of the monitoring server (example 11)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SystemMetrics {
+    pub timestamp: u64,
+    pub cpu_usage_percent: f64,
+    // Not sent by servers that predate real metrics collection
+    #[serde(default)]
+    pub cpu_per_core_percent: Vec<f64>,
+    pub memory_usage_percent: f64,
+    #[serde(default)]
+    pub process_rss_bytes: u64,
+    pub disk_usage_percent: f64,
+    pub network_bytes_sent: u64,
+    pub network_bytes_received: u64,
+    pub active_connections: u32,
+    pub uptime_seconds: u64,
+}
+
+impl KnownResponse for SystemMetrics {
+    const TOOLS: &'static [&'static str] = &["get_current_metrics"];
+
+    fn output_schema() -> Value {
+        let percent = serde_json::json!({ "type": "number", "minimum": 0, "maximum": 100 });
+        let count = serde_json::json!({ "type": "integer", "minimum": 0 });
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "timestamp": count,
+                "cpu_usage_percent": percent,
+                "cpu_per_core_percent": { "type": "array", "items": percent },
+                "memory_usage_percent": percent,
+                "process_rss_bytes": count,
+                "disk_usage_percent": percent,
+                "network_bytes_sent": count,
+                "network_bytes_received": count,
+                "active_connections": count,
+                "uptime_seconds": count
+            },
+            "required": [
+                "timestamp", "cpu_usage_percent", "memory_usage_percent", "disk_usage_percent",
+                "network_bytes_sent", "network_bytes_received", "active_connections",
+                "uptime_seconds"
+            ],
+            "additionalProperties": false
+        })
+    }
+}
+
+// A file or directory of the file operations server (example 07)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileInfo {
+    pub name: String,
+    pub path: String,
+    pub file_type: String,
+    pub size: u64,
+    pub modified: String,
+    pub readable: bool,
+    pub writable: bool,
+    // Directories of recursive listings: the bytes in the files below them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_size: Option<u64>,
+    // Directories of tree listings: their entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<FileInfo>>,
+}
+
+impl KnownResponse for FileInfo {
+    const TOOLS: &'static [&'static str] = &["get_file_info"];
+
+    fn output_schema() -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "path": { "type": "string" },
+                "file_type": { "type": "string", "enum": ["file", "directory", "other"] },
+                "size": { "type": "integer", "minimum": 0 },
+                "modified": { "type": "string" },
+                "readable": { "type": "boolean" },
+                "writable": { "type": "boolean" },
+                "total_size": { "type": "integer", "minimum": 0 },
+                "children": { "type": "array", "items": { "type": "object" } }
+            },
+            "required": ["name", "path", "file_type", "size", "modified", "readable", "writable"],
+            "additionalProperties": false
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DirectoryListing {
+    pub path: String,
+    pub files: Vec<FileInfo>,
+    pub total_count: usize,
+    pub file_count: usize,
+    pub directory_count: usize,
+    pub total_size: u64,
+    pub truncated: bool,
+}
+
+impl KnownResponse for DirectoryListing {
+    const TOOLS: &'static [&'static str] = &["list_directory"];
+
+    fn output_schema() -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "files": { "type": "array", "items": FileInfo::output_schema() },
+                "total_count": { "type": "integer", "minimum": 0 },
+                "file_count": { "type": "integer", "minimum": 0 },
+                "directory_count": { "type": "integer", "minimum": 0 },
+                "total_size": { "type": "integer", "minimum": 0 },
+                "truncated": { "type": "boolean" }
+            },
+            "required": [
+                "path", "files", "total_count", "file_count", "directory_count", "total_size",
+                "truncated"
+            ],
+            "additionalProperties": false
+        })
+    }
+}
+
+// A result that did not match the schema the client expects for its tool
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SchemaDrift {
+    pub tool: String,
+    pub server: String,
+    pub violations: Vec<SchemaViolation>,
+}
+
+// Why a typed call produced no value
+#[derive(Debug)]
+pub enum TypedCallError {
+    // The tool reported an error
+    Tool(String),
+    // The result does not have the expected type
+    Shape(String),
+}
+
+impl std::fmt::Display for TypedCallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypedCallError::Tool(msg) => write!(f, "Tool failed: {}", msg),
+            TypedCallError::Shape(msg) => write!(f, "Unexpected result: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TypedCallError {}
+
 impl SimpleMcpClient {
     // Constructor to create a new MCP client instance
     pub fn new(server_url: &str) -> Self {
         Self {
             server_url: server_url.to_string(),
+            servers: Vec::new(),
+            sampling: None,
+            response_schemas: HashMap::new(),
+            drift: Mutex::new(Vec::new()),
+        }
+    }
+
+    // Check results of the tools that return `T` against its schema
+    pub fn expect_response<T: KnownResponse>(mut self) -> Self {
+        for tool in T::TOOLS {
+            self.response_schemas
+                .insert(tool.to_string(), T::output_schema());
+        }
+        self
+    }
+
+    // Check results of every tool the client knows
+    pub fn expect_known_responses(self) -> Self {
+        self.expect_response::<User>()
+            .expect_response::<SystemMetrics>()
+            .expect_response::<FileInfo>()
+            .expect_response::<DirectoryListing>()
+    }
+
+    // Every result so far that did not match its schema
+    pub fn drift(&self) -> Vec<SchemaDrift> {
+        self.drift.lock().unwrap().clone()
+    }
+
+    // Record where a successful result differs from what the client expects
+    fn check_response(&self, tool: &str, server: &str, result: &Value) {
+        let Some(schema) = self.response_schemas.get(tool) else {
+            return;
+        };
+        let violations = schema::validate(schema, result);
+        if violations.is_empty() {
+            return;
+        }
+
+        eprintln!(
+            "⚠️  {} ({}) no longer returns what the client expects:",
+            tool, server
+        );
+        for violation in &violations {
+            eprintln!("     - {}", violation);
+        }
+        self.drift.lock().unwrap().push(SchemaDrift {
+            tool: tool.to_string(),
+            server: server.to_string(),
+            violations,
+        });
+    }
+
+    // Call a tool and read its result as `T`. Drift that still parses, such
+    // as a new field, is only reported.
+    pub async fn call_typed<T: KnownResponse>(
+        &self,
+        tool_name: &str,
+        arguments: Value,
+    ) -> Result<T, TypedCallError> {
+        let response = self
+            .call_tool(ToolCallRequest {
+                tool_name: tool_name.to_string(),
+                arguments,
+            })
+            .await
+            .map_err(TypedCallError::Tool)?;
+        if !response.success {
+            return Err(TypedCallError::Tool(response.error.unwrap_or_default()));
+        }
+        serde_json::from_value(response.result.unwrap_or_default())
+            .map_err(|e| TypedCallError::Shape(e.to_string()))
+    }
+
+    // Let servers request completions through `handler`
+    pub fn with_sampling(mut self, handler: Arc<dyn SamplingHandler>) -> Self {
+        self.sampling = Some(handler);
+        self
+    }
+
+    // The capabilities sent in `initialize`
+    pub fn capabilities(&self) -> Value {
+        let mut capabilities = serde_json::json!({});
+        if self.sampling.is_some() {
+            capabilities["sampling"] = serde_json::json!({});
         }
+        capabilities
+    }
+
+    // Answer a request sent by a server. Notifications get no response.
+    pub async fn handle_server_request(&self, request: JsonRpcRequest) -> Option<JsonRpcResponse> {
+        let id = request.id.clone()?;
+        let result = match request.method.as_str() {
+            "ping" => Ok(serde_json::json!({})),
+            CREATE_MESSAGE_METHOD => self.create_message(request.params).await,
+            method => Err(JsonRpcError::method_not_found(method)),
+        };
+
+        Some(match result {
+            Ok(result) => JsonRpcResponse::success(Some(id), result),
+            Err(error) => JsonRpcResponse::failure(Some(id), error),
+        })
+    }
+
+    async fn create_message(&self, params: Value) -> Result<Value, JsonRpcError> {
+        // A client without the sampling capability does not know the method
+        let Some(handler) = &self.sampling else {
+            return Err(JsonRpcError::method_not_found(CREATE_MESSAGE_METHOD));
+        };
+        let request: CreateMessageRequest =
+            serde_json::from_value(params).map_err(JsonRpcError::invalid_params)?;
+        request.validate().map_err(JsonRpcError::invalid_params)?;
+
+        eprintln!(
+            "🧠 Server asked for a completion ({} messages, up to {} tokens)",
+            request.messages.len(),
+            request.max_tokens
+        );
+        let result = handler
+            .create_message(request)
+            .await
+            .map_err(|e| JsonRpcError::new(error_codes::INTERNAL_ERROR, e))?;
+        serde_json::to_value(result)
+            .map_err(|e| JsonRpcError::new(error_codes::INTERNAL_ERROR, e.to_string()))
+    }
+
+    // Attach a server so its tools can be discovered and called
+    pub fn attach_server(&mut self, server: Arc<dyn ToolServer>) {
+        eprintln!("🔌 Attached server: {}", server.server_name());
+        self.servers.push(server);
+    }
+
+    // Discover tools across all attached servers
+    pub async fn discover_tools(&self) -> Result<Vec<DiscoveredTool>, String> {
+        eprintln!(
+            "🔍 Discovering tools across {} servers...",
+            self.servers.len()
+        );
+
+        let mut discovered: Vec<DiscoveredTool> = Vec::new();
+        for server in &self.servers {
+            for tool in server.tool_descriptors() {
+                // The first server to offer a tool name handles calls to it
+                if let Some(existing) = discovered.iter().find(|d| d.tool.name == tool.name) {
+                    eprintln!(
+                        "⚠️  {} also offers '{}', keeping {}",
+                        server.server_name(),
+                        tool.name,
+                        existing.server
+                    );
+                    continue;
+                }
+
+                discovered.push(DiscoveredTool {
+                    server: server.server_name().to_string(),
+                    tool: ToolInfo {
+                        name: tool.name,
+                        description: tool.description,
+                        input_schema: tool.input_schema,
+                        annotations: tool.annotations,
+                    },
+                });
+            }
+        }
+
+        eprintln!("📋 Found {} tools", discovered.len());
+        for entry in &discovered {
+            // Tools without annotations get the cautious MCP defaults
+            let confirm = match &entry.tool.annotations {
+                Some(annotations) if !annotations.needs_confirmation() => "",
+                _ => " ⚠️  asks for confirmation",
+            };
+            eprintln!("  - {} ({}){}", entry.tool.name, entry.server, confirm);
+        }
+
+        Ok(discovered)
+    }
+
+    // Find the attached server that provides a tool
+    fn server_for_tool(&self, tool_name: &str) -> Option<&Arc<dyn ToolServer>> {
+        self.servers.iter().find(|server| {
+            server
+                .tool_descriptors()
+                .iter()
+                .any(|tool| tool.name == tool_name)
+        })
     }
 
     // Simulate connecting to an MCP server
@@ -73,6 +561,7 @@ impl SimpleMcpClient {
                     },
                     "required": ["name"]
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
             ToolInfo {
                 name: "calculator".to_string(),
@@ -86,6 +575,7 @@ impl SimpleMcpClient {
                     },
                     "required": ["operation", "a", "b"]
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
             ToolInfo {
                 name: "text_transform".to_string(),
@@ -98,6 +588,7 @@ impl SimpleMcpClient {
                     },
                     "required": ["text", "operation"]
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
         ];
 
@@ -113,6 +604,30 @@ impl SimpleMcpClient {
     pub async fn call_tool(&self, request: ToolCallRequest) -> Result<ToolCallResponse, String> {
         eprintln!("🔧 Calling tool: {}", request.tool_name);
 
+        // Route to an attached server when one provides the tool
+        if let Some(server) = self.server_for_tool(&request.tool_name) {
+            return Ok(
+                match server
+                    .invoke_tool(&request.tool_name, request.arguments)
+                    .await
+                {
+                    Ok(result) => {
+                        self.check_response(&request.tool_name, server.server_name(), &result);
+                        ToolCallResponse {
+                            success: true,
+                            result: Some(result),
+                            error: None,
+                        }
+                    }
+                    Err(e) => ToolCallResponse {
+                        success: false,
+                        result: None,
+                        error: Some(e.to_string()),
+                    },
+                },
+            );
+        }
+
         // Simulate network delay
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 
@@ -313,18 +828,629 @@ impl SimpleMcpClient {
             }
         }
 
+        // Step 4: Answer a server that wants to borrow the client's model
+        self.demonstrate_sampling().await;
+
         eprintln!("\n🎉 Client demonstration completed successfully!");
         Ok(())
     }
+
+    // Play the server's side of a sampling/createMessage exchange
+    async fn demonstrate_sampling(&self) {
+        eprintln!("\n🧠 Testing server-initiated sampling:");
+
+        let mut params = CreateMessageRequest::new(
+            "Summarize in one sentence: MCP standardizes how applications give context to LLMs.",
+            100,
+        );
+        params.system_prompt = Some("You write short summaries.".to_string());
+        let request = JsonRpcRequest::new(
+            RequestId::String("sampling-1".to_string()),
+            CREATE_MESSAGE_METHOD,
+            serde_json::to_value(params).unwrap_or_default(),
+        );
+
+        match self.handle_server_request(request).await {
+            Some(JsonRpcResponse {
+                result: Some(result),
+                ..
+            }) => eprintln!("✅ Sampling result: {}", result),
+            Some(JsonRpcResponse {
+                error: Some(error), ..
+            }) => eprintln!("❌ Sampling refused: {}", error.message),
+            _ => eprintln!("⚠️  Unexpected sampling response"),
+        }
+    }
+}
+
+// A server running as a child process, reached over its stdin and stdout
+// the way desktop MCP clients reach local servers
+pub struct ProcessServer {
+    name: String,
+    tools: Vec<Tool>,
+    io: tokio::sync::Mutex<ProcessIo>,
+    next_id: AtomicI64,
+}
+
+struct ProcessIo {
+    // Killed when the server is dropped
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+// How long a process server gets to answer one request
+const PROCESS_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+impl ProcessServer {
+    // Start `command` (program and arguments), initialize the session and
+    // fetch its tools
+    pub async fn spawn(command: &[String]) -> Result<Self, String> {
+        let (program, args) = command.split_first().ok_or("The server command is empty")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+        let stdin = child.stdin.take().ok_or("The server has no stdin")?;
+        let stdout = child.stdout.take().ok_or("The server has no stdout")?;
+
+        let mut server = Self {
+            name: program.clone(),
+            tools: Vec::new(),
+            io: tokio::sync::Mutex::new(ProcessIo {
+                _child: child,
+                stdin,
+                stdout: BufReader::new(stdout).lines(),
+            }),
+            next_id: AtomicI64::new(1),
+        };
+
+        let initialized = server
+            .request(
+                "initialize",
+                serde_json::json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": "simple-mcp-client", "version": "0.1.0" }
+                }),
+            )
+            .await?;
+        if let Some(name) = initialized["serverInfo"]["name"].as_str() {
+            server.name = name.to_string();
+        }
+        server
+            .send(&JsonRpcRequest::notification(
+                "notifications/initialized",
+                Value::Null,
+            ))
+            .await?;
+
+        let listed: ListToolsResult =
+            serde_json::from_value(server.request("tools/list", Value::Null).await?)
+                .map_err(|e| format!("Invalid tools/list result: {}", e))?;
+        server.tools = listed.tools;
+        Ok(server)
+    }
+
+    async fn send(&self, message: &impl Serialize) -> Result<(), String> {
+        let mut line =
+            serde_json::to_string(message).map_err(|e| format!("Invalid message: {}", e))?;
+        line.push('\n');
+        let mut io = self.io.lock().await;
+        io.stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write to the server: {}", e))
+    }
+
+    // Send a request and wait for its response, answering pings and skipping
+    // notifications the server sends meanwhile
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = RequestId::Number(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.send(&JsonRpcRequest::new(id.clone(), method, params))
+            .await?;
+
+        let mut io = self.io.lock().await;
+        let response = tokio::time::timeout(PROCESS_REQUEST_TIMEOUT, async {
+            loop {
+                let line = io
+                    .stdout
+                    .next_line()
+                    .await
+                    .map_err(|e| format!("Failed to read from the server: {}", e))?
+                    .ok_or("The server closed its output")?;
+                let message: Value = match serde_json::from_str(&line) {
+                    Ok(message) => message,
+                    Err(_) => continue,
+                };
+                if message.get("method").is_some() {
+                    if let Ok(request) = serde_json::from_value::<JsonRpcRequest>(message) {
+                        if let Some(ping) = request.id.filter(|_| request.method == "ping") {
+                            let pong = JsonRpcResponse::success(Some(ping), serde_json::json!({}));
+                            let mut pong = serde_json::to_string(&pong).unwrap_or_default();
+                            pong.push('\n');
+                            let _ = io.stdin.write_all(pong.as_bytes()).await;
+                        }
+                    }
+                    continue;
+                }
+                let response: JsonRpcResponse = serde_json::from_value(message)
+                    .map_err(|e| format!("Invalid response: {}", e))?;
+                if response.id.as_ref() == Some(&id) {
+                    return Ok::<_, String>(response);
+                }
+            }
+        })
+        .await
+        .map_err(|_| format!("No response to {} from the server", method))??;
+
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(format!("{} (code {})", error.message, error.code)),
+            (Some(result), None) => Ok(result),
+            (None, None) => Ok(Value::Null),
+        }
+    }
+}
+
+impl ToolServer for ProcessServer {
+    fn server_name(&self) -> &str {
+        &self.name
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.tools.clone()
+    }
+
+    // Results come back as text; JSON text is turned back into a value
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(async move {
+            let result = self
+                .request(
+                    "tools/call",
+                    serde_json::json!({ "name": name, "arguments": arguments }),
+                )
+                .await
+                .map_err(McpError::Unavailable)?;
+            let result: CallToolResult = serde_json::from_value(result)
+                .map_err(|e| McpError::Internal(format!("Invalid tools/call result: {}", e)))?;
+            // A failed call keeps the error code the server put in `_meta`
+            let text = result.into_result()?;
+            Ok(serde_json::from_str(&text).unwrap_or(Value::String(text)))
+        })
+    }
+}
+
+// A scripted sequence of tool calls and what each should return
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    // Command that starts the server to test over stdio, e.g.
+    // [cargo, run, --quiet, --bin, example_02_calculator]. Without one the
+    // scenario runs against the client's simulated tools.
+    #[serde(default)]
+    pub server: Option<Vec<String>>,
+    pub steps: Vec<ScenarioStep>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioStep {
+    pub call: String,
+    // Strings may refer to values saved by earlier steps as {{name}}
+    #[serde(default)]
+    pub arguments: Value,
+    #[serde(default)]
+    pub expect: Expectation,
+    // Values of the result to keep for later steps: name -> path
+    #[serde(default)]
+    pub save: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    // Whether the call should succeed; by default it should, unless an
+    // error is expected
+    #[serde(default)]
+    pub success: Option<bool>,
+    // The call fails with an error containing this text
+    #[serde(default)]
+    pub error: Option<String>,
+    // The result contains these values: objects may have more fields than
+    // listed, arrays and everything else must match exactly
+    #[serde(default)]
+    pub result: Option<Value>,
+    #[serde(default, rename = "assert")]
+    pub assertions: Vec<Assertion>,
+}
+
+// A check on one value of the result, found by a dotted path such as
+// `schedule.0.balance`; an empty path is the whole result
+#[derive(Deserialize, Debug, Clone)]
+pub struct Assertion {
+    #[serde(default)]
+    pub path: String,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    Equals(Value),
+    // A substring of a string, or an element of an array
+    Contains(Value),
+    Exists(bool),
+    GreaterThan(f64),
+    LessThan(f64),
+    Length(usize),
+}
+
+impl Check {
+    fn verify(&self, actual: Option<&Value>) -> Result<(), String> {
+        if let Check::Exists(expected) = self {
+            return match (actual, expected) {
+                (Some(_), true) | (None, false) => Ok(()),
+                (None, true) => Err("is missing".to_string()),
+                (Some(value), false) => Err(format!("should be missing but is {}", value)),
+            };
+        }
+        let actual = actual.ok_or("is missing")?;
+        let number = || actual.as_f64().ok_or(format!("{} is not a number", actual));
+        let passed = match self {
+            Check::Equals(expected) => contains(expected, actual) && contains(actual, expected),
+            Check::Contains(expected) => match (actual, expected) {
+                (Value::String(text), Value::String(part)) => text.contains(part.as_str()),
+                (Value::Array(items), _) => items.iter().any(|item| contains(expected, item)),
+                _ => false,
+            },
+            Check::GreaterThan(bound) => number()? > *bound,
+            Check::LessThan(bound) => number()? < *bound,
+            Check::Length(length) => match actual {
+                Value::String(text) => text.chars().count() == *length,
+                Value::Array(items) => items.len() == *length,
+                Value::Object(fields) => fields.len() == *length,
+                _ => return Err(format!("{} has no length", actual)),
+            },
+            Check::Exists(_) => unreachable!(),
+        };
+        match passed {
+            true => Ok(()),
+            false => Err(format!("{} does not satisfy {:?}", actual, self)),
+        }
+    }
+}
+
+// Whether `actual` has everything `expected` has. Numbers compare by value,
+// so 5 matches 5.0.
+fn contains(expected: &Value, actual: &Value) -> bool {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => expected
+            .iter()
+            .all(|(key, value)| actual.get(key).is_some_and(|field| contains(value, field))),
+        (Value::Array(expected), Value::Array(actual)) => {
+            expected.len() == actual.len()
+                && expected.iter().zip(actual).all(|(e, a)| contains(e, a))
+        }
+        (Value::Number(expected), Value::Number(actual)) => expected.as_f64() == actual.as_f64(),
+        _ => expected == actual,
+    }
+}
+
+// The value at a dotted path; numeric segments index arrays
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |value, segment| match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => value.get(segment),
+        })
+}
+
+// Replace {{name}} in the strings of `value` with saved values. A string
+// that is only a placeholder takes the saved value's type.
+fn substitute(value: &Value, saved: &BTreeMap<String, Value>) -> Result<Value, String> {
+    match value {
+        Value::String(text) => substitute_text(text, saved),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| substitute(item, saved))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(key, field)| Ok((key.clone(), substitute(field, saved)?)))
+            .collect::<Result<Map<_, _>, String>>()
+            .map(Value::Object),
+        other => Ok(other.clone()),
+    }
+}
+
+fn substitute_text(text: &str, saved: &BTreeMap<String, Value>) -> Result<Value, String> {
+    let saved_value = |name: &str| {
+        saved
+            .get(name.trim())
+            .ok_or_else(|| format!("No saved value named '{}'", name.trim()))
+    };
+    if let Some(name) = text.strip_prefix("{{").and_then(|t| t.strip_suffix("}}")) {
+        if !name.contains("{{") && !name.contains("}}") {
+            return saved_value(name).cloned();
+        }
+    }
+
+    let mut substituted = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start..].find("}}") else {
+            break;
+        };
+        substituted.push_str(&rest[..start]);
+        match saved_value(&rest[start + 2..start + length])? {
+            Value::String(value) => substituted.push_str(value),
+            value => substituted.push_str(&value.to_string()),
+        }
+        rest = &rest[start + length + 2..];
+    }
+    substituted.push_str(rest);
+    Ok(Value::String(substituted))
+}
+
+// What happened in one step; it passed if nothing failed
+#[derive(Debug)]
+pub struct StepReport {
+    pub call: String,
+    pub failures: Vec<String>,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug)]
+pub struct ScenarioReport {
+    pub name: String,
+    pub steps: Vec<StepReport>,
+}
+
+impl ScenarioReport {
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|step| step.failures.is_empty())
+    }
+}
+
+impl Scenario {
+    pub fn from_yaml(text: &str) -> Result<Self, String> {
+        yaml::from_str(text).map_err(|e| format!("Invalid scenario: {}", e))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_yaml(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Run against the scenario's own server, or the simulated tools
+    pub async fn run(&self) -> Result<ScenarioReport, String> {
+        let mut client = SimpleMcpClient::new(&format!("scenario://{}", self.name));
+        if let Some(command) = &self.server {
+            client.attach_server(Arc::new(ProcessServer::spawn(command).await?));
+        }
+        Ok(client.run_scenario(self).await)
+    }
+}
+
+impl SimpleMcpClient {
+    // Run every step, even after a failure, so one report shows all of them
+    pub async fn run_scenario(&self, scenario: &Scenario) -> ScenarioReport {
+        eprintln!("🎬 Scenario: {}", scenario.name);
+        if let Some(description) = &scenario.description {
+            eprintln!("   {}", description.trim());
+        }
+
+        let mut saved = BTreeMap::new();
+        let mut steps = Vec::new();
+        for (number, step) in scenario.steps.iter().enumerate() {
+            let started = Instant::now();
+            let failures = self.run_step(step, &mut saved).await;
+            let report = StepReport {
+                call: step.call.clone(),
+                failures,
+                elapsed: started.elapsed(),
+            };
+
+            let mark = if report.failures.is_empty() {
+                "✅"
+            } else {
+                "❌"
+            };
+            eprintln!(
+                "  {} {}. {} ({} ms)",
+                mark,
+                number + 1,
+                report.call,
+                report.elapsed.as_millis()
+            );
+            for failure in &report.failures {
+                eprintln!("       {}", failure);
+            }
+            steps.push(report);
+        }
+
+        ScenarioReport {
+            name: scenario.name.clone(),
+            steps,
+        }
+    }
+
+    async fn run_step(
+        &self,
+        step: &ScenarioStep,
+        saved: &mut BTreeMap<String, Value>,
+    ) -> Vec<String> {
+        let arguments = match substitute(&step.arguments, saved) {
+            Ok(arguments) => arguments,
+            Err(e) => return vec![e],
+        };
+        let response = match self
+            .call_tool(ToolCallRequest {
+                tool_name: step.call.clone(),
+                arguments,
+            })
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => return vec![e],
+        };
+
+        let expect = &step.expect;
+        let mut failures = Vec::new();
+        let error = response.error.unwrap_or_default();
+        let should_succeed = expect.success.unwrap_or(expect.error.is_none());
+        if response.success != should_succeed {
+            failures.push(match response.success {
+                true => "expected the call to fail, but it succeeded".to_string(),
+                false => format!("call failed: {}", error),
+            });
+        }
+        if let Some(expected) = &expect.error {
+            if !response.success && !error.contains(expected.as_str()) {
+                failures.push(format!("error '{}' does not contain '{}'", error, expected));
+            }
+        }
+
+        let result = response.result.unwrap_or_default();
+        if let Some(expected) = &expect.result {
+            if !contains(expected, &result) {
+                failures.push(format!("result {} does not match {}", result, expected));
+            }
+        }
+        for assertion in &expect.assertions {
+            if let Err(e) = assertion.check.verify(lookup(&result, &assertion.path)) {
+                let path = if assertion.path.is_empty() {
+                    "result"
+                } else {
+                    &assertion.path
+                };
+                failures.push(format!("{}: {}", path, e));
+            }
+        }
+        for (name, path) in &step.save {
+            match lookup(&result, path) {
+                Some(value) => {
+                    saved.insert(name.clone(), value.clone());
+                }
+                None => failures.push(format!("nothing at '{}' to save as '{}'", path, name)),
+            }
+        }
+        failures
+    }
+}
+
+// The files named by --scenario; the flag may be repeated
+fn scenario_paths() -> Vec<PathBuf> {
+    let args: Vec<String> = std::env::args().collect();
+    args.windows(2)
+        .filter(|pair| pair[0] == "--scenario")
+        .map(|pair| PathBuf::from(&pair[1]))
+        .collect()
+}
+
+// The server command (or tools/list JSON file) after --generate-bindings
+fn bindings_command() -> Option<Vec<String>> {
+    let args: Vec<String> = std::env::args().collect();
+    let start = args.iter().position(|arg| arg == "--generate-bindings")?;
+    Some(args[start + 1..].to_vec())
+}
+
+// Tools from a saved tools/list result, a bare array of tools or the whole
+// JSON-RPC response
+fn tools_from_json(text: &str) -> Result<Vec<Tool>, String> {
+    let value: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let tools = if value.is_array() {
+        value
+    } else if !value["result"]["tools"].is_null() {
+        value["result"]["tools"].clone()
+    } else {
+        value["tools"].clone()
+    };
+    serde_json::from_value(tools).map_err(|e| format!("No tools/list result: {}", e))
+}
+
+// Print a typed client for the server's tools to stdout
+async fn generate_bindings(command: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (name, tools) = match command {
+        [path] if path.ends_with(".json") && Path::new(path).is_file() => {
+            let name = Path::new(path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            (name, tools_from_json(&std::fs::read_to_string(path)?)?)
+        }
+        [] => return Err("Usage: --generate-bindings <server command...> | <tools.json>".into()),
+        _ => {
+            let server = ProcessServer::spawn(command).await?;
+            (server.name.clone(), server.tool_descriptors())
+        }
+    };
+    eprintln!(
+        "🧬 Generating bindings for {} tools of {}",
+        tools.len(),
+        name
+    );
+    print!("{}", codegen::generate_client(&name, &tools));
+    Ok(())
+}
+
+// Run each scenario file and fail if any step of any scenario failed
+async fn run_scenarios(paths: &[PathBuf]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut failed = Vec::new();
+    for path in paths {
+        let report = Scenario::from_file(path)?.run().await?;
+        if !report.passed() {
+            failed.push(report.name);
+        }
+        eprintln!();
+    }
+
+    if failed.is_empty() {
+        eprintln!("🎉 All {} scenarios passed", paths.len());
+        Ok(())
+    } else {
+        Err(format!("Scenarios failed: {}", failed.join(", ")).into())
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logging for better debugging
-    tracing_subscriber::fmt::init();
+    let _telemetry = mcp_rust_examples::logging::init("error");
 
-    // Create a client instance
-    let client = SimpleMcpClient::new("ws://localhost:8080");
+    if let Some(command) = bindings_command() {
+        return generate_bindings(&command).await;
+    }
+
+    let scenarios = scenario_paths();
+    if !scenarios.is_empty() {
+        return run_scenarios(&scenarios).await;
+    }
+
+    // Create a client instance. A real client would route sampling requests
+    // to its LLM; the demo answers with a prepared summary.
+    let sampler = CannedSampler::new(
+        "demo-model",
+        &["MCP is a common protocol for connecting LLM apps to data sources."],
+    );
+    let client = SimpleMcpClient::new("ws://localhost:8080")
+        .with_sampling(Arc::new(sampler))
+        .expect_known_responses();
 
     // Run the demonstration
     client.demonstrate_client_workflow().await?;
@@ -391,4 +1517,237 @@ mod tests {
         assert!(!response.success);
         assert!(response.error.is_some());
     }
+
+    #[tokio::test]
+    async fn test_sampling_requests() {
+        let request = |params: Value| {
+            JsonRpcRequest::new(RequestId::Number(1), CREATE_MESSAGE_METHOD, params)
+        };
+        let params = serde_json::to_value(CreateMessageRequest::new("Say hi", 20)).unwrap();
+
+        // Without a handler the client does not offer sampling
+        let client = SimpleMcpClient::new("test://server");
+        assert!(client.capabilities().get("sampling").is_none());
+        let response = client
+            .handle_server_request(request(params.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.error.unwrap().code, error_codes::METHOD_NOT_FOUND);
+
+        let sampler = Arc::new(CannedSampler::new("test-model", &["Hi!"]));
+        let client = SimpleMcpClient::new("test://server").with_sampling(sampler.clone());
+        assert!(client.capabilities().get("sampling").is_some());
+
+        let result = client
+            .handle_server_request(request(params.clone()))
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        let result: CreateMessageResult = serde_json::from_value(result).unwrap();
+        assert_eq!(result.model, "test-model");
+        assert_eq!(result.content.as_text(), Some("Hi!"));
+        assert_eq!(
+            sampler.received()[0].messages[0].content.as_text(),
+            Some("Say hi")
+        );
+
+        // Invalid requests never reach the handler; handler failures are errors
+        let response = client
+            .handle_server_request(request(
+                serde_json::json!({ "messages": [], "maxTokens": 5 }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.error.unwrap().code, error_codes::INVALID_PARAMS);
+        assert_eq!(sampler.received().len(), 1);
+
+        let response = client.handle_server_request(request(params)).await.unwrap();
+        assert_eq!(response.error.unwrap().code, error_codes::INTERNAL_ERROR);
+    }
+
+    // A monitoring server whose metrics changed shape since the client was
+    // written
+    struct DriftedMonitoring;
+
+    impl ToolServer for DriftedMonitoring {
+        fn server_name(&self) -> &str {
+            "monitoring"
+        }
+
+        fn tool_descriptors(&self) -> Vec<Tool> {
+            vec![Tool {
+                name: "get_current_metrics".to_string(),
+                description: "Current metrics".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }]
+        }
+
+        fn invoke_tool<'a>(
+            &'a self,
+            _name: &'a str,
+            _arguments: Value,
+        ) -> BoxFuture<'a, Result<Value, McpError>> {
+            Box::pin(async {
+                Ok(serde_json::json!({
+                    "timestamp": 1700000000,
+                    "cpu_usage_percent": 0.42,
+                    "memory_usage_percent": 150.0,
+                    "disk_usage_percent": 35.0,
+                    "network_bytes_sent": 10,
+                    "network_bytes_received": 20,
+                    "active_connections": 3,
+                    "uptime": 60,
+                    "load_average": [0.5, 0.4, 0.3]
+                }))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_response_drift() {
+        let mut client = SimpleMcpClient::new("test://server").expect_known_responses();
+        client.attach_server(Arc::new(DriftedMonitoring));
+
+        // uptime_seconds was renamed, so the typed call fails to parse
+        let error = client
+            .call_typed::<SystemMetrics>("get_current_metrics", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, TypedCallError::Shape(_)));
+
+        let drift = client.drift();
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].server, "monitoring");
+        let violations: Vec<String> = drift[0].violations.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            violations,
+            [
+                "uptime_seconds: required field is missing",
+                "load_average: field is not in the schema",
+                "memory_usage_percent: 150 is not at most 100",
+                "uptime: field is not in the schema",
+            ]
+        );
+
+        // Tools the client knows nothing about are not checked
+        let client = SimpleMcpClient::new("test://server").expect_response::<User>();
+        let response = client
+            .call_tool(ToolCallRequest {
+                tool_name: "greeting".to_string(),
+                arguments: serde_json::json!({ "name": "Ada" }),
+            })
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert!(client.drift().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scenario_file() {
+        let scenario =
+            Scenario::from_yaml(include_str!("../../examples/scenarios/client_tools.yaml"))
+                .unwrap();
+        assert!(scenario.server.is_none());
+
+        let report = scenario.run().await.unwrap();
+        assert_eq!(report.steps.len(), 5);
+        assert!(report.passed(), "{:?}", report.steps);
+    }
+
+    #[tokio::test]
+    async fn test_scenario_failures() {
+        let scenario = Scenario::from_yaml(
+            r#"
+name: Wrong expectations
+steps:
+  - call: calculator
+    arguments: { operation: add, a: 1, b: 2 }
+    expect:
+      result: { result: 4 }
+      assert:
+        - path: result
+          greater_than: 2
+        - path: missing
+          exists: true
+    save:
+      sum: result
+  - call: calculator
+    arguments: { operation: subtract, a: "{{sum}}", b: "{{nothing}}" }
+  - call: calculator
+    arguments: { operation: add, a: 1, b: 1 }
+    expect:
+      error: overflow
+"#,
+        )
+        .unwrap();
+        let client = SimpleMcpClient::new("test://server");
+        let report = client.run_scenario(&scenario).await;
+
+        assert!(!report.passed());
+        let failures: Vec<&Vec<String>> = report.steps.iter().map(|s| &s.failures).collect();
+        assert_eq!(failures[0].len(), 2);
+        assert!(failures[0][0].contains("does not match"));
+        assert_eq!(failures[0][1], "missing: is missing");
+        assert_eq!(
+            failures[1],
+            &vec!["No saved value named 'nothing'".to_string()]
+        );
+        assert_eq!(failures[2].len(), 1);
+        assert!(failures[2][0].contains("expected the call to fail"));
+
+        let unknown =
+            Scenario::from_yaml("name: x\nsteps:\n  - call: a\n    expect: { succes: true }");
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    fn test_paths_and_substitution() {
+        let result = serde_json::json!({ "schedule": [{ "balance": 0.0 }], "id": 7 });
+        assert_eq!(
+            lookup(&result, "schedule.0.balance"),
+            Some(&serde_json::json!(0.0))
+        );
+        assert_eq!(lookup(&result, ""), Some(&result));
+        assert_eq!(lookup(&result, "schedule.1"), None);
+
+        let saved = BTreeMap::from([("id".to_string(), serde_json::json!(7))]);
+        let arguments = serde_json::json!({ "id": "{{id}}", "label": "task {{ id }}!" });
+        assert_eq!(
+            substitute(&arguments, &saved).unwrap(),
+            serde_json::json!({ "id": 7, "label": "task 7!" })
+        );
+        assert!(contains(&serde_json::json!({ "id": 7.0 }), &result));
+        assert!(!contains(
+            &serde_json::json!([1]),
+            &serde_json::json!([1, 2])
+        ));
+    }
+
+    #[test]
+    fn test_bindings_from_saved_tools() {
+        let tool = serde_json::json!({
+            "name": "echo",
+            "description": "Echoes text",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "text": { "type": "string" } },
+                "required": ["text"]
+            }
+        });
+        let saved = [
+            serde_json::json!([tool]),
+            serde_json::json!({ "tools": [tool] }),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": { "tools": [tool] } }),
+        ];
+        for saved in saved {
+            let tools = tools_from_json(&saved.to_string()).unwrap();
+            assert_eq!(tools.len(), 1);
+            let source = codegen::generate_client("echo-server", &tools);
+            assert!(source.contains("pub async fn echo(&self, args: &EchoArgs)"));
+            assert!(source.contains("pub struct EchoServerClient"));
+        }
+        assert!(tools_from_json("{\"error\": {}}").is_err());
+    }
 }
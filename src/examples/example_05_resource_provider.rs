@@ -3,10 +3,53 @@
 // This example demonstrates how to implement MCP resources, which allow
 // servers to provide data and content that LLMs can access. Resources
 // are identified by URIs and can contain text or binary data.
+//
+// Documents are grouped into named collections so one provider can serve
+// isolated datasets to different users. Each collection has its own access
+// list keyed by the usernames and roles issued by the auth service (example
+// 13). Collection documents are addressed as `document://{collection}/{id}`;
+// the public `default` collection keeps the short `document://{id}` form.
+//
+// Clients can subscribe to a document with `resources/subscribe` and are sent
+// `notifications/resources/updated` whenever it is edited or its collection
+// is deleted. The notification says which lines of the content changed, or
+// only which bytes when that is too much to send; MCP_RESOURCE_DIFF and
+// MCP_RESOURCE_DIFF_MAX_BYTES set how much detail goes out.
+//
+// `export_collection` packs a collection into a JSON Lines archive, returned
+// base64-encoded as a blob resource, and `import_collection` turns such an
+// archive back into a collection. Together they back up a collection or move
+// it to another server.
 
+use base64::Engine;
+use futures::future::BoxFuture;
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::identity::Identity;
+use mcp_rust_examples::logging::ToolCallLog;
+use mcp_rust_examples::protocol::{
+    CompleteParams, CompletionReference, Resource, Tool, ToolAnnotations,
+};
+use mcp_rust_examples::resource_diff::{DiffConfig, ResourceUpdate};
+use mcp_rust_examples::server::{CompletionProvider, McpServer, ResourceProvider};
+use mcp_rust_examples::state::{StateCommand, StateError, StatefulServer};
+use mcp_rust_examples::tools::ToolServer;
+use mcp_rust_examples::transport::{self, StdioTransport};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+// The public collection holding the sample documents
+pub const DEFAULT_COLLECTION: &str = "default";
+
+// How many resource updates a slow subscriber may fall behind by
+const UPDATE_BUFFER: usize = 64;
+
+// Identifies collection archives and the layout of their lines
+const ARCHIVE_FORMAT: &str = "mcp-collection-archive";
+const ARCHIVE_VERSION: u32 = 1;
+const ARCHIVE_MIME_TYPE: &str = "application/x-ndjson";
 
 // Structure representing a simple document resource
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -19,13 +62,51 @@ pub struct Document {
     pub tags: Vec<String>,
 }
 
-// Structure representing an MCP resource
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Resource {
-    pub uri: String,
-    pub name: Option<String>,
-    pub description: Option<String>,
-    pub mime_type: Option<String>,
+// Who may read, write and manage a collection. Admins can do everything;
+// the owner manages the collection and decides who else gets access.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CollectionAcl {
+    pub owner: Option<String>,
+    pub public: bool,
+    pub readers: BTreeSet<String>,
+    pub writers: BTreeSet<String>,
+}
+
+impl CollectionAcl {
+    fn is_owner(&self, identity: &Identity) -> bool {
+        self.owner.as_deref() == Some(identity.username.as_str())
+    }
+
+    pub fn can_read(&self, identity: Option<&Identity>) -> bool {
+        self.public
+            || identity.is_some_and(|id| {
+                id.is_admin()
+                    || self.is_owner(id)
+                    || self.readers.contains(&id.username)
+                    || self.writers.contains(&id.username)
+            })
+    }
+
+    // Guests are read-only even when listed as writers
+    pub fn can_write(&self, identity: Option<&Identity>) -> bool {
+        identity.is_some_and(|id| {
+            id.is_admin()
+                || (!id.is_guest() && (self.is_owner(id) || self.writers.contains(&id.username)))
+        })
+    }
+
+    pub fn can_manage(&self, identity: Option<&Identity>) -> bool {
+        identity.is_some_and(|id| id.is_admin() || self.is_owner(id))
+    }
+}
+
+// A named, independently access-controlled set of documents
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Collection {
+    pub name: String,
+    pub description: String,
+    pub acl: CollectionAcl,
+    pub documents: HashMap<String, Document>,
 }
 
 // Request structure for document search
@@ -33,6 +114,8 @@ pub struct Resource {
 pub struct SearchRequest {
     pub query: String,
     pub limit: Option<usize>,
+    // Search a single collection instead of every readable one
+    pub collection: Option<String>,
 }
 
 // Response structure for document search
@@ -49,21 +132,219 @@ pub struct DocumentSummary {
     pub author: String,
     pub uri: String,
     pub tags: Vec<String>,
+    pub collection: String,
+}
+
+// Request structures for collection management
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateCollectionRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub public: Option<bool>,
 }
 
-// Structure for tool definitions
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Tool {
+pub struct CollectionNameRequest {
     pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLevel {
+    Read,
+    Write,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GrantAccessRequest {
+    pub collection: String,
+    pub username: String,
+    pub access: AccessLevel,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RevokeAccessRequest {
+    pub collection: String,
+    pub username: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddDocumentRequest {
+    pub collection: String,
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateDocumentRequest {
+    pub collection: Option<String>,
+    pub document_id: String,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportCollectionRequest {
+    // Base64-encoded archive from export_collection
+    pub archive: String,
+    // Import under another name (default: the name in the archive)
+    pub name: Option<String>,
+}
+
+// First line of a collection archive; each following line is a Document.
+// Access lists are not exported: they name users of the exporting server, so
+// whoever imports the archive owns the new collection.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ArchiveHeader {
+    pub format: String,
+    pub version: u32,
+    pub collection: String,
     pub description: String,
-    pub input_schema: Value,
+    pub exported_at: String,
+    pub document_count: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CollectionSummary {
+    pub name: String,
+    pub description: String,
+    pub owner: Option<String>,
+    pub public: bool,
+    pub document_count: usize,
+    pub can_write: bool,
+}
+
+// Builds the URI of a document, using the short form for the default collection
+pub fn document_uri(collection: &str, id: &str) -> String {
+    if collection == DEFAULT_COLLECTION {
+        format!("document://{}", id)
+    } else {
+        format!("document://{}/{}", collection, id)
+    }
+}
+
+// Splits a document URI into (collection, id)
+fn parse_document_uri(uri: &str) -> Option<(&str, &str)> {
+    let path = uri.strip_prefix("document://")?;
+    match path.split_once('/') {
+        Some((collection, id)) if !collection.is_empty() && !id.is_empty() => {
+            Some((collection, id))
+        }
+        Some(_) => None,
+        None if !path.is_empty() => Some((DEFAULT_COLLECTION, path)),
+        None => None,
+    }
+}
+
+// Collection names become part of URIs, so keep them simple
+fn validate_collection_name(name: &str) -> Result<(), McpError> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(McpError::InvalidParams(format!(
+            "Invalid collection name '{}': use 1-64 lowercase letters, digits, '-' or '_'",
+            name
+        )))
+    }
+}
+
+// Writes a collection as JSON Lines: the header, then its documents by id.
+// Search scans the documents directly, so they are all an import needs.
+fn encode_archive(collection: &Collection) -> Result<String, McpError> {
+    let header = ArchiveHeader {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        collection: collection.name.clone(),
+        description: collection.description.clone(),
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        document_count: collection.documents.len(),
+    };
+    let mut documents: Vec<&Document> = collection.documents.values().collect();
+    documents.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let to_line = |value: serde_json::Result<String>| {
+        value.map_err(|e| McpError::Internal(format!("Failed to write archive: {}", e)))
+    };
+    let mut archive = to_line(serde_json::to_string(&header))?;
+    archive.push('\n');
+    for document in documents {
+        archive.push_str(&to_line(serde_json::to_string(document))?);
+        archive.push('\n');
+    }
+    Ok(archive)
+}
+
+// Reads an archive written by encode_archive, rejecting truncated ones
+fn decode_archive(archive: &str) -> Result<(ArchiveHeader, Vec<Document>), McpError> {
+    let invalid = |reason: String| McpError::InvalidParams(format!("Invalid archive: {}", reason));
+    let mut lines = archive.lines().filter(|line| !line.trim().is_empty());
+
+    let header: ArchiveHeader = lines
+        .next()
+        .ok_or_else(|| invalid("it is empty".to_string()))
+        .and_then(|line| serde_json::from_str(line).map_err(|e| invalid(e.to_string())))?;
+    if header.format != ARCHIVE_FORMAT || header.version != ARCHIVE_VERSION {
+        return Err(invalid(format!(
+            "unsupported format {} version {}",
+            header.format, header.version
+        )));
+    }
+
+    let documents = lines
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| invalid(format!("document {}: {}", index + 1, e)))
+        })
+        .collect::<Result<Vec<Document>, _>>()?;
+    if documents.len() != header.document_count {
+        return Err(invalid(format!(
+            "expected {} documents, found {}",
+            header.document_count,
+            documents.len()
+        )));
+    }
+    Ok((header, documents))
+}
+
+// Simple relevance score: title matches count double, tag matches once
+fn relevance(doc: &Document, query_lower: &str) -> u32 {
+    let title = if doc.title.to_lowercase().contains(query_lower) {
+        2
+    } else {
+        0
+    };
+    let tags = if doc
+        .tags
+        .iter()
+        .any(|tag| tag.to_lowercase().contains(query_lower))
+    {
+        1
+    } else {
+        0
+    };
+    title + tags
 }
 
 // The resource provider server
 pub struct ResourceProviderServer {
-    // In-memory document storage for this example
+    // In-memory collection storage for this example
     // In a real application, this might be a database connection
-    documents: HashMap<String, Document>,
+    collections: RwLock<HashMap<String, Collection>>,
+    // URIs of subscribed documents. Each stdio session has its own server,
+    // so these are the connected client's subscriptions.
+    subscriptions: RwLock<HashSet<String>>,
+    // Announces changes to subscribed documents
+    updates: broadcast::Sender<ResourceUpdate>,
+    // How much of each change the announcements describe
+    diff: DiffConfig,
 }
 
 impl Default for ResourceProviderServer {
@@ -114,54 +395,193 @@ impl ResourceProviderServer {
             tags: vec!["JSON-RPC".to_string(), "Protocol".to_string(), "API".to_string()],
         });
 
-        Self { documents }
+        let default_collection = Collection {
+            name: DEFAULT_COLLECTION.to_string(),
+            description: "Public sample documents".to_string(),
+            acl: CollectionAcl {
+                public: true,
+                ..CollectionAcl::default()
+            },
+            documents,
+        };
+
+        let mut collections = HashMap::new();
+        collections.insert(DEFAULT_COLLECTION.to_string(), default_collection);
+
+        Self {
+            collections: RwLock::new(collections),
+            subscriptions: RwLock::new(HashSet::new()),
+            updates: broadcast::channel(UPDATE_BUFFER).0,
+            diff: DiffConfig::default(),
+        }
     }
 
-    // List all available resources
+    pub fn with_diff_config(mut self, diff: DiffConfig) -> Self {
+        self.diff = diff;
+        self
+    }
+
+    // List all resources available to anonymous clients
     pub fn list_resources(&self) -> Vec<Resource> {
-        self.documents
+        self.list_resources_as(None)
+    }
+
+    // List the resources in every collection the caller can read
+    pub fn list_resources_as(&self, identity: Option<&Identity>) -> Vec<Resource> {
+        let collections = self.collections.read().unwrap();
+        collections
             .values()
-            .map(|doc| Resource {
-                uri: format!("document://{}", doc.id),
-                name: Some(doc.title.clone()),
-                description: Some(format!(
-                    "Document by {} - Tags: {}",
-                    doc.author,
-                    doc.tags.join(", ")
-                )),
-                mime_type: Some("text/plain".to_string()),
+            .filter(|collection| collection.acl.can_read(identity))
+            .flat_map(|collection| {
+                collection.documents.values().map(|doc| Resource {
+                    uri: document_uri(&collection.name, &doc.id),
+                    name: Some(doc.title.clone()),
+                    description: Some(format!(
+                        "Document by {} - Tags: {}",
+                        doc.author,
+                        doc.tags.join(", ")
+                    )),
+                    mime_type: Some("text/plain".to_string()),
+                })
             })
             .collect()
     }
 
+    // Read a specific resource by URI as an anonymous client
+    pub fn read_resource(&self, uri: &str) -> Result<Value, McpError> {
+        self.read_resource_as(None, uri)
+    }
+
     // Read a specific resource by URI
-    pub fn read_resource(&self, uri: &str) -> Result<Value, String> {
-        // Parse the URI to extract the document ID
-        if let Some(doc_id) = uri.strip_prefix("document://") {
-            if let Some(document) = self.documents.get(doc_id) {
-                // Return the document content as a resource
-                Ok(serde_json::json!({
-                    "contents": [{
-                        "uri": uri,
-                        "mimeType": "text/plain",
-                        "text": document.content
-                    }]
-                }))
+    pub fn read_resource_as(
+        &self,
+        identity: Option<&Identity>,
+        uri: &str,
+    ) -> Result<Value, McpError> {
+        // Parse the URI to extract the collection and document ID
+        let (collection, doc_id) = parse_document_uri(uri)
+            .ok_or_else(|| McpError::InvalidParams(format!("Invalid document URI: {}", uri)))?;
+        let document = self.get_document(identity, collection, doc_id)?;
+
+        // Return the document content as a resource
+        Ok(serde_json::json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": "text/plain",
+                "text": document.content
+            }]
+        }))
+    }
+
+    // Subscribe an anonymous client to changes of a document
+    pub fn subscribe(&self, uri: &str) -> Result<(), McpError> {
+        self.subscribe_as(None, uri)
+    }
+
+    // Subscribe to changes of a document the caller can read
+    pub fn subscribe_as(&self, identity: Option<&Identity>, uri: &str) -> Result<(), McpError> {
+        let (collection, doc_id) = parse_document_uri(uri)
+            .ok_or_else(|| McpError::InvalidParams(format!("Invalid document URI: {}", uri)))?;
+        self.get_document(identity, collection, doc_id)?;
+
+        self.subscriptions.write().unwrap().insert(uri.to_string());
+        tracing::debug!(uri, "Subscribed to resource");
+        Ok(())
+    }
+
+    // Unsubscribing from a document that is not subscribed is not an error
+    pub fn unsubscribe(&self, uri: &str) {
+        self.subscriptions.write().unwrap().remove(uri);
+    }
+
+    // Receives an update for every subscribed document that changes
+    pub fn updates(&self) -> broadcast::Receiver<ResourceUpdate> {
+        self.updates.subscribe()
+    }
+
+    // Announces the updates to subscribed documents. Deleted documents are
+    // announced one last time and then unsubscribed.
+    fn notify_updated(&self, updates: impl IntoIterator<Item = ResourceUpdate>, deleted: bool) {
+        let mut subscriptions = self.subscriptions.write().unwrap();
+        for update in updates {
+            let subscribed = if deleted {
+                subscriptions.remove(&update.uri)
             } else {
-                Err(format!("Document not found: {}", doc_id))
+                subscriptions.contains(&update.uri)
+            };
+            let uri = update.uri.clone();
+            // Sending fails only when nobody is listening, which is fine
+            if subscribed && self.updates.send(update).is_ok() {
+                tracing::debug!(uri, "Announced resource update");
             }
-        } else {
-            Err(format!("Invalid document URI: {}", uri))
+        }
+    }
+
+    // Runs `f` on a collection the caller can read. Unreadable collections are
+    // reported as missing so their names don't leak to other tenants.
+    fn with_readable_collection<T>(
+        &self,
+        identity: Option<&Identity>,
+        name: &str,
+        f: impl FnOnce(&Collection) -> Result<T, McpError>,
+    ) -> Result<T, McpError> {
+        let collections = self.collections.read().unwrap();
+        match collections.get(name) {
+            Some(collection) if collection.acl.can_read(identity) => f(collection),
+            _ => Err(McpError::NotFound(format!(
+                "Collection not found: {}",
+                name
+            ))),
+        }
+    }
+
+    // Runs `f` on a collection the caller can see, after `allowed` approves
+    fn with_collection_mut<T>(
+        &self,
+        identity: Option<&Identity>,
+        name: &str,
+        allowed: impl FnOnce(&CollectionAcl, Option<&Identity>) -> bool,
+        f: impl FnOnce(&mut Collection) -> Result<T, McpError>,
+    ) -> Result<T, McpError> {
+        let mut collections = self.collections.write().unwrap();
+        match collections.get_mut(name) {
+            Some(collection) if collection.acl.can_read(identity) => {
+                if !allowed(&collection.acl, identity) {
+                    return Err(McpError::PermissionDenied(format!(
+                        "Permission denied on collection: {}",
+                        name
+                    )));
+                }
+                f(collection)
+            }
+            _ => Err(McpError::NotFound(format!(
+                "Collection not found: {}",
+                name
+            ))),
         }
     }
 
     // Helper method to search documents by query
-    fn search_documents(&self, query: &str, limit: Option<usize>) -> Vec<&Document> {
+    fn search_documents(
+        &self,
+        identity: Option<&Identity>,
+        collection: Option<&str>,
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<DocumentSummary>, McpError> {
+        if let Some(name) = collection {
+            // Fail early on collections the caller cannot read
+            self.with_readable_collection(identity, name, |_| Ok(()))?;
+        }
+
         let query_lower = query.to_lowercase();
-        let mut matches: Vec<&Document> = self
-            .documents
+        let collections = self.collections.read().unwrap();
+        let mut matches: Vec<(u32, DocumentSummary)> = collections
             .values()
-            .filter(|doc| {
+            .filter(|c| c.acl.can_read(identity))
+            .filter(|c| collection.is_none_or(|name| c.name == name))
+            .flat_map(|c| c.documents.values().map(move |doc| (c, doc)))
+            .filter(|(_, doc)| {
                 doc.title.to_lowercase().contains(&query_lower)
                     || doc.content.to_lowercase().contains(&query_lower)
                     || doc.author.to_lowercase().contains(&query_lower)
@@ -170,49 +590,342 @@ impl ResourceProviderServer {
                         .iter()
                         .any(|tag| tag.to_lowercase().contains(&query_lower))
             })
+            .map(|(c, doc)| {
+                (
+                    relevance(doc, &query_lower),
+                    DocumentSummary {
+                        id: doc.id.clone(),
+                        title: doc.title.clone(),
+                        author: doc.author.clone(),
+                        uri: document_uri(&c.name, &doc.id),
+                        tags: doc.tags.clone(),
+                        collection: c.name.clone(),
+                    },
+                )
+            })
             .collect();
 
-        // Sort by relevance (simple scoring based on title matches)
-        matches.sort_by(|a, b| {
-            let a_score = if a.title.to_lowercase().contains(&query_lower) {
-                2
-            } else {
-                0
-            } + if a
-                .tags
-                .iter()
-                .any(|tag| tag.to_lowercase().contains(&query_lower))
-            {
-                1
-            } else {
-                0
-            };
-            let b_score = if b.title.to_lowercase().contains(&query_lower) {
-                2
-            } else {
-                0
-            } + if b
-                .tags
-                .iter()
-                .any(|tag| tag.to_lowercase().contains(&query_lower))
-            {
-                1
-            } else {
-                0
-            };
-            b_score.cmp(&a_score)
+        // Sort by relevance, then by URI so results are stable
+        matches.sort_by(|(a_score, a), (b_score, b)| {
+            b_score.cmp(a_score).then_with(|| a.uri.cmp(&b.uri))
         });
 
-        if let Some(limit) = limit {
-            matches.into_iter().take(limit).collect()
-        } else {
-            matches
+        let matches = matches.into_iter().map(|(_, summary)| summary);
+        Ok(match limit {
+            Some(limit) => matches.take(limit).collect(),
+            None => matches.collect(),
+        })
+    }
+
+    // Get document by collection and ID
+    fn get_document(
+        &self,
+        identity: Option<&Identity>,
+        collection: &str,
+        id: &str,
+    ) -> Result<Document, McpError> {
+        self.with_readable_collection(identity, collection, |c| {
+            c.documents
+                .get(id)
+                .cloned()
+                .ok_or_else(|| McpError::NotFound(format!("Document not found: {}", id)))
+        })
+    }
+
+    fn list_collections(&self, identity: Option<&Identity>) -> Vec<CollectionSummary> {
+        let collections = self.collections.read().unwrap();
+        let mut summaries: Vec<CollectionSummary> = collections
+            .values()
+            .filter(|c| c.acl.can_read(identity))
+            .map(|c| CollectionSummary {
+                name: c.name.clone(),
+                description: c.description.clone(),
+                owner: c.acl.owner.clone(),
+                public: c.acl.public,
+                document_count: c.documents.len(),
+                can_write: c.acl.can_write(identity),
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.name.cmp(&b.name));
+        summaries
+    }
+
+    // Any signed-in, non-guest user can create a collection and owns it
+    fn create_collection(
+        &self,
+        identity: Option<&Identity>,
+        request: CreateCollectionRequest,
+    ) -> Result<CollectionSummary, McpError> {
+        let owner = identity.filter(|id| !id.is_guest()).ok_or_else(|| {
+            McpError::PermissionDenied(
+                "Creating a collection requires a signed-in, non-guest user".to_string(),
+            )
+        })?;
+        validate_collection_name(&request.name)?;
+
+        let mut collections = self.collections.write().unwrap();
+        if collections.contains_key(&request.name) {
+            return Err(McpError::Conflict(format!(
+                "Collection already exists: {}",
+                request.name
+            )));
         }
+
+        let collection = Collection {
+            name: request.name.clone(),
+            description: request.description.unwrap_or_default(),
+            acl: CollectionAcl {
+                owner: Some(owner.username.clone()),
+                public: request.public.unwrap_or(false),
+                ..CollectionAcl::default()
+            },
+            documents: HashMap::new(),
+        };
+        let summary = CollectionSummary {
+            name: collection.name.clone(),
+            description: collection.description.clone(),
+            owner: collection.acl.owner.clone(),
+            public: collection.acl.public,
+            document_count: 0,
+            can_write: true,
+        };
+        collections.insert(request.name, collection);
+        Ok(summary)
+    }
+
+    fn delete_collection(
+        &self,
+        identity: Option<&Identity>,
+        name: &str,
+    ) -> Result<Value, McpError> {
+        if name == DEFAULT_COLLECTION {
+            return Err(McpError::PermissionDenied(
+                "The default collection cannot be deleted".to_string(),
+            ));
+        }
+
+        let mut collections = self.collections.write().unwrap();
+        let removed = match collections.get(name) {
+            Some(c) if c.acl.can_manage(identity) => c.documents.len(),
+            Some(c) if c.acl.can_read(identity) => {
+                return Err(McpError::PermissionDenied(format!(
+                    "Permission denied on collection: {}",
+                    name
+                )))
+            }
+            _ => {
+                return Err(McpError::NotFound(format!(
+                    "Collection not found: {}",
+                    name
+                )))
+            }
+        };
+        let removed_uris: Vec<String> = collections
+            .remove(name)
+            .map(|c| {
+                c.documents
+                    .keys()
+                    .map(|id| document_uri(name, id))
+                    .collect()
+            })
+            .unwrap_or_default();
+        drop(collections);
+        let deleted = removed_uris
+            .into_iter()
+            .map(|uri| ResourceUpdate::new(uri).with_change(self.diff.deleted()));
+        self.notify_updated(deleted, true);
+
+        Ok(serde_json::json!({
+            "deleted": name,
+            "documents_removed": removed
+        }))
     }
 
-    // Get document by ID
-    fn get_document(&self, id: &str) -> Option<&Document> {
-        self.documents.get(id)
+    fn grant_access(
+        &self,
+        identity: Option<&Identity>,
+        request: GrantAccessRequest,
+    ) -> Result<Value, McpError> {
+        self.with_collection_mut(
+            identity,
+            &request.collection,
+            CollectionAcl::can_manage,
+            |c| {
+                // Write access implies read access, so a user is in one list only
+                c.acl.readers.remove(&request.username);
+                c.acl.writers.remove(&request.username);
+                match request.access {
+                    AccessLevel::Read => c.acl.readers.insert(request.username.clone()),
+                    AccessLevel::Write => c.acl.writers.insert(request.username.clone()),
+                };
+                Ok(serde_json::json!({
+                    "collection": c.name,
+                    "username": request.username,
+                    "access": request.access
+                }))
+            },
+        )
+    }
+
+    fn revoke_access(
+        &self,
+        identity: Option<&Identity>,
+        request: RevokeAccessRequest,
+    ) -> Result<Value, McpError> {
+        self.with_collection_mut(
+            identity,
+            &request.collection,
+            CollectionAcl::can_manage,
+            |c| {
+                let revoked = c.acl.readers.remove(&request.username)
+                    | c.acl.writers.remove(&request.username);
+                Ok(serde_json::json!({
+                    "collection": c.name,
+                    "username": request.username,
+                    "revoked": revoked
+                }))
+            },
+        )
+    }
+
+    fn add_document(
+        &self,
+        identity: Option<&Identity>,
+        request: AddDocumentRequest,
+    ) -> Result<DocumentSummary, McpError> {
+        let author = identity.map(|id| id.username.clone()).unwrap_or_default();
+
+        self.with_collection_mut(
+            identity,
+            &request.collection,
+            CollectionAcl::can_write,
+            |c| {
+                let document = Document {
+                    id: uuid::Uuid::new_v4().simple().to_string(),
+                    title: request.title,
+                    content: request.content,
+                    author,
+                    created_at: chrono::Utc::now().to_rfc3339(),
+                    tags: request.tags.unwrap_or_default(),
+                };
+                let summary = DocumentSummary {
+                    id: document.id.clone(),
+                    title: document.title.clone(),
+                    author: document.author.clone(),
+                    uri: document_uri(&c.name, &document.id),
+                    tags: document.tags.clone(),
+                    collection: c.name.clone(),
+                };
+                c.documents.insert(document.id.clone(), document);
+                Ok(summary)
+            },
+        )
+    }
+
+    // Edits a document in place; fields left out keep their value
+    fn update_document(
+        &self,
+        identity: Option<&Identity>,
+        request: UpdateDocumentRequest,
+    ) -> Result<DocumentSummary, McpError> {
+        let collection = request
+            .collection
+            .unwrap_or_else(|| DEFAULT_COLLECTION.to_string());
+
+        let (summary, change) =
+            self.with_collection_mut(identity, &collection, CollectionAcl::can_write, |c| {
+                let document = c.documents.get_mut(&request.document_id).ok_or_else(|| {
+                    McpError::NotFound(format!("Document not found: {}", request.document_id))
+                })?;
+                if let Some(title) = request.title {
+                    document.title = title;
+                }
+                // The resource's text is the content, so that is what is diffed
+                let change = match request.content {
+                    Some(content) => {
+                        let old = std::mem::replace(&mut document.content, content);
+                        self.diff.change(&old, &document.content)
+                    }
+                    None => self.diff.change(&document.content, &document.content),
+                };
+                if let Some(tags) = request.tags {
+                    document.tags = tags;
+                }
+                let summary = DocumentSummary {
+                    id: document.id.clone(),
+                    title: document.title.clone(),
+                    author: document.author.clone(),
+                    uri: document_uri(&c.name, &document.id),
+                    tags: document.tags.clone(),
+                    collection: c.name.clone(),
+                };
+                Ok((summary, change))
+            })?;
+        self.notify_updated(
+            [ResourceUpdate::new(summary.uri.clone()).with_change(change)],
+            false,
+        );
+        Ok(summary)
+    }
+
+    // Anyone who can read a collection can export it
+    fn export_collection(
+        &self,
+        identity: Option<&Identity>,
+        name: &str,
+    ) -> Result<Value, McpError> {
+        let (archive, document_count) = self.with_readable_collection(identity, name, |c| {
+            Ok((encode_archive(c)?, c.documents.len()))
+        })?;
+
+        Ok(serde_json::json!({
+            "collection": name,
+            "document_count": document_count,
+            "size_bytes": archive.len(),
+            "resource": {
+                "uri": format!("archive://{}.jsonl", name),
+                "mimeType": ARCHIVE_MIME_TYPE,
+                "blob": base64::engine::general_purpose::STANDARD.encode(archive)
+            }
+        }))
+    }
+
+    // Creates a private collection owned by the caller from an exported
+    // archive. Documents keep their ids, so their URIs only change collection.
+    fn import_collection(
+        &self,
+        identity: Option<&Identity>,
+        request: ImportCollectionRequest,
+    ) -> Result<CollectionSummary, McpError> {
+        let archive = base64::engine::general_purpose::STANDARD
+            .decode(request.archive.trim())
+            .map_err(|e| McpError::InvalidParams(format!("Archive is not base64: {}", e)))
+            .and_then(|bytes| {
+                String::from_utf8(bytes)
+                    .map_err(|e| McpError::InvalidParams(format!("Archive is not UTF-8: {}", e)))
+            })?;
+        let (header, documents) = decode_archive(&archive)?;
+
+        let summary = self.create_collection(
+            identity,
+            CreateCollectionRequest {
+                name: request.name.unwrap_or(header.collection),
+                description: Some(header.description),
+                public: Some(false),
+            },
+        )?;
+        let document_count =
+            self.with_collection_mut(identity, &summary.name, CollectionAcl::can_write, |c| {
+                c.documents = documents
+                    .into_iter()
+                    .map(|document| (document.id.clone(), document))
+                    .collect();
+                Ok(c.documents.len())
+            })?;
+        Ok(CollectionSummary {
+            document_count,
+            ..summary
+        })
     }
 
     // List available tools
@@ -231,10 +944,15 @@ impl ResourceProviderServer {
                         "limit": {
                             "type": "integer",
                             "description": "Maximum number of results to return (default: 10)"
+                        },
+                        "collection": {
+                            "type": "string",
+                            "description": "Only search this collection (default: all readable collections)"
                         }
                     },
                     "required": ["query"]
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
             Tool {
                 name: "get_document_details".to_string(),
@@ -245,67 +963,447 @@ impl ResourceProviderServer {
                         "document_id": {
                             "type": "string",
                             "description": "ID of the document to retrieve details for"
+                        },
+                        "collection": {
+                            "type": "string",
+                            "description": "Collection holding the document (default: \"default\")"
                         }
                     },
                     "required": ["document_id"]
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "list_collections".to_string(),
+                description: "List the document collections you can read".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {}
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "create_collection".to_string(),
+                description: "Create a private collection owned by the caller".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "pattern": "^[a-z0-9_-]{1,64}$"
+                        },
+                        "description": { "type": "string" },
+                        "public": {
+                            "type": "boolean",
+                            "description": "Allow anyone to read the collection",
+                            "default": false
+                        }
+                    },
+                    "required": ["name"]
+                }),
+                annotations: Some(ToolAnnotations::additive()),
+            },
+            Tool {
+                name: "delete_collection".to_string(),
+                description: "Delete a collection and all of its documents".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" }
+                    },
+                    "required": ["name"]
+                }),
+                annotations: Some(ToolAnnotations::destructive().idempotent()),
+            },
+            Tool {
+                name: "grant_collection_access".to_string(),
+                description: "Give a user read or write access to a collection".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "collection": { "type": "string" },
+                        "username": { "type": "string" },
+                        "access": { "type": "string", "enum": ["read", "write"] }
+                    },
+                    "required": ["collection", "username", "access"]
+                }),
+                annotations: Some(ToolAnnotations::additive().idempotent()),
+            },
+            Tool {
+                name: "revoke_collection_access".to_string(),
+                description: "Remove a user's access to a collection".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "collection": { "type": "string" },
+                        "username": { "type": "string" }
+                    },
+                    "required": ["collection", "username"]
+                }),
+                annotations: Some(ToolAnnotations::destructive().idempotent()),
+            },
+            Tool {
+                name: "add_document".to_string(),
+                description: "Add a document to a collection you can write to".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "collection": { "type": "string" },
+                        "title": { "type": "string" },
+                        "content": { "type": "string" },
+                        "tags": { "type": "array", "items": { "type": "string" } }
+                    },
+                    "required": ["collection", "title", "content"]
+                }),
+                annotations: Some(ToolAnnotations::additive()),
+            },
+            Tool {
+                name: "update_document".to_string(),
+                description:
+                    "Edit the title, content or tags of a document in a collection you can write to"
+                        .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "collection": { "type": "string", "default": DEFAULT_COLLECTION },
+                        "document_id": { "type": "string" },
+                        "title": { "type": "string" },
+                        "content": { "type": "string" },
+                        "tags": { "type": "array", "items": { "type": "string" } }
+                    },
+                    "required": ["document_id"]
+                }),
+                annotations: Some(ToolAnnotations::destructive().idempotent()),
+            },
+            Tool {
+                name: "export_collection".to_string(),
+                description:
+                    "Export a collection's documents and metadata as a base64-encoded JSON Lines archive"
+                        .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" }
+                    },
+                    "required": ["name"]
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "import_collection".to_string(),
+                description: "Create a private collection from an export_collection archive"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "archive": {
+                            "type": "string",
+                            "description": "The base64 blob returned by export_collection"
+                        },
+                        "name": {
+                            "type": "string",
+                            "pattern": "^[a-z0-9_-]{1,64}$",
+                            "description": "Name for the new collection (default: the exported name)"
+                        }
+                    },
+                    "required": ["archive"]
+                }),
+                annotations: Some(ToolAnnotations::additive()),
             },
         ]
     }
 
+    // Handle tool call requests from an anonymous client
+    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        self.call_tool_as(None, name, arguments)
+    }
+
+    // Handle tool call requests on behalf of a caller
+    pub fn call_tool_as(
+        &self,
+        identity: Option<&Identity>,
+        name: &str,
+        arguments: Value,
+    ) -> Result<Value, McpError> {
+        ToolCallLog::run(name, arguments, |arguments| {
+            self.dispatch_tool(identity, name, arguments)
+        })
+    }
+
     // Handle tool calls
-    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+    fn dispatch_tool(
+        &self,
+        identity: Option<&Identity>,
+        name: &str,
+        arguments: Value,
+    ) -> Result<Value, McpError> {
         match name {
             "search_documents" => {
-                let request: SearchRequest = serde_json::from_value(arguments)
-                    .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+                let request: SearchRequest = serde_json::from_value(arguments).map_err(|e| {
+                    McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                })?;
 
-                let matches = self.search_documents(&request.query, request.limit);
+                let matches = self.search_documents(
+                    identity,
+                    request.collection.as_deref(),
+                    &request.query,
+                    request.limit,
+                )?;
 
                 let response = SearchResponse {
                     total_count: matches.len(),
-                    matches: matches
-                        .into_iter()
-                        .map(|doc| DocumentSummary {
-                            id: doc.id.clone(),
-                            title: doc.title.clone(),
-                            author: doc.author.clone(),
-                            uri: format!("document://{}", doc.id),
-                            tags: doc.tags.clone(),
-                        })
-                        .collect(),
+                    matches,
                 };
 
                 serde_json::to_value(response)
-                    .map_err(|e| format!("Failed to serialize response: {}", e))
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
             }
             "get_document_details" => {
                 let document_id = arguments
                     .get("document_id")
                     .and_then(|id| id.as_str())
-                    .ok_or("Missing document_id parameter")?;
+                    .ok_or_else(|| {
+                        McpError::InvalidParams("Missing document_id parameter".to_string())
+                    })?;
+                let collection = arguments
+                    .get("collection")
+                    .and_then(|c| c.as_str())
+                    .unwrap_or(DEFAULT_COLLECTION);
 
-                if let Some(document) = self.get_document(document_id) {
-                    serde_json::to_value(document)
-                        .map_err(|e| format!("Failed to serialize document: {}", e))
-                } else {
-                    Err(format!("Document not found: {}", document_id))
-                }
+                let document = self.get_document(identity, collection, document_id)?;
+                serde_json::to_value(document)
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize document: {}", e)))
             }
-            _ => Err(format!("Unknown tool: {}", name)),
+            "list_collections" => {
+                let collections = self.list_collections(identity);
+                Ok(serde_json::json!({
+                    "count": collections.len(),
+                    "collections": collections
+                }))
+            }
+            "create_collection" => {
+                let request: CreateCollectionRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+
+                let summary = self.create_collection(identity, request)?;
+                serde_json::to_value(summary)
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
+            }
+            "delete_collection" => {
+                let request: CollectionNameRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+
+                self.delete_collection(identity, &request.name)
+            }
+            "grant_collection_access" => {
+                let request: GrantAccessRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+
+                self.grant_access(identity, request)
+            }
+            "revoke_collection_access" => {
+                let request: RevokeAccessRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+
+                self.revoke_access(identity, request)
+            }
+            "add_document" => {
+                let request: AddDocumentRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+
+                let summary = self.add_document(identity, request)?;
+                serde_json::to_value(summary)
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
+            }
+            "update_document" => {
+                let request: UpdateDocumentRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+
+                let summary = self.update_document(identity, request)?;
+                serde_json::to_value(summary)
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
+            }
+            "export_collection" => {
+                let request: CollectionNameRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+
+                self.export_collection(identity, &request.name)
+            }
+            "import_collection" => {
+                let request: ImportCollectionRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+
+                let summary = self.import_collection(identity, request)?;
+                serde_json::to_value(summary)
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
+            }
+            _ => Err(McpError::NotFound(format!("Unknown tool: {}", name))),
         }
     }
 }
 
+// Served over stdio with --stdio, and to clients that work with several servers
+impl ToolServer for ResourceProviderServer {
+    fn server_name(&self) -> &str {
+        "resource_provider"
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.list_tools()
+    }
+
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(async move { self.call_tool(name, arguments) })
+    }
+}
+
+// Suggests document ids and collection names for `document://` URIs, and
+// existing tags for the tag-based tool arguments. Completion requests carry no
+// identity, so only collections anonymous clients can read contribute.
+impl ResourceProviderServer {
+    pub fn complete_argument(&self, params: &CompleteParams) -> Vec<String> {
+        let collections = self.collections.read().unwrap();
+        let readable = || collections.values().filter(|c| c.acl.can_read(None));
+        let tags = || {
+            readable()
+                .flat_map(|c| c.documents.values())
+                .flat_map(|doc| doc.tags.iter().cloned())
+                .collect::<Vec<_>>()
+        };
+
+        let mut values = match (&params.reference, params.argument.name.as_str()) {
+            (CompletionReference::Resource { uri }, argument) if uri.starts_with("document://") => {
+                match argument {
+                    "id" => {
+                        let collection = params
+                            .context_argument("collection")
+                            .unwrap_or(DEFAULT_COLLECTION);
+                        readable()
+                            .filter(|c| c.name == collection)
+                            .flat_map(|c| c.documents.keys().cloned())
+                            .collect()
+                    }
+                    "collection" => readable().map(|c| c.name.clone()).collect(),
+                    _ => Vec::new(),
+                }
+            }
+            (CompletionReference::Tool { name }, argument) => match (name.as_str(), argument) {
+                ("add_document" | "update_document", "tags") | ("search_documents", "query") => {
+                    tags()
+                }
+                ("add_document" | "search_documents" | "update_document", "collection") => {
+                    readable().map(|c| c.name.clone()).collect()
+                }
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+
+        let prefix = params.argument.value.to_lowercase();
+        values.retain(|value| value.to_lowercase().starts_with(&prefix));
+        values.sort();
+        values.dedup();
+        values
+    }
+}
+
+impl CompletionProvider for ResourceProviderServer {
+    fn complete<'a>(
+        &'a self,
+        params: &'a CompleteParams,
+    ) -> BoxFuture<'a, Result<Vec<String>, String>> {
+        Box::pin(async move { Ok(self.complete_argument(params)) })
+    }
+}
+
+// Resources over MCP. Requests carry no identity, so clients see, read and
+// subscribe to what anonymous clients can.
+impl ResourceProvider for ResourceProviderServer {
+    fn list_resources(&self) -> BoxFuture<'_, Result<Vec<Resource>, McpError>> {
+        Box::pin(async move { Ok(ResourceProviderServer::list_resources(self)) })
+    }
+
+    fn read_resource<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(async move { ResourceProviderServer::read_resource(self, uri) })
+    }
+
+    fn subscribe(&self, uri: &str) -> Result<(), McpError> {
+        ResourceProviderServer::subscribe(self, uri)
+    }
+
+    fn unsubscribe(&self, uri: &str) -> Result<(), McpError> {
+        ResourceProviderServer::unsubscribe(self, uri);
+        Ok(())
+    }
+
+    fn updates(&self) -> broadcast::Receiver<ResourceUpdate> {
+        ResourceProviderServer::updates(self)
+    }
+}
+
+// Snapshots hold every collection with its access list, so a restored server
+// serves exactly the documents, to exactly the users, it did before
+impl StatefulServer for ResourceProviderServer {
+    const STATE_NAME: &'static str = "resource_provider";
+    // Version 2 groups documents into collections
+    const STATE_VERSION: u32 = 2;
+
+    async fn snapshot(&self) -> Result<Value, StateError> {
+        Ok(serde_json::to_value(&*self.collections.read().unwrap())?)
+    }
+
+    async fn restore(&mut self, state: Value) -> Result<(), StateError> {
+        *self.collections.get_mut().unwrap() = serde_json::from_value(state)?;
+        Ok(())
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
+    let _telemetry = mcp_rust_examples::logging::init("error");
 
     eprintln!("📚 Starting Resource Provider MCP Server");
     eprintln!("🗂️  Sample documents with search capabilities loaded");
     eprintln!();
 
-    let server = ResourceProviderServer::new();
+    let state_command = StateCommand::from_env()?;
+    let mut server = ResourceProviderServer::new().with_diff_config(DiffConfig::from_env());
+    state_command.restore(&mut server).await?;
+
+    // With --stdio, serve the tools to an MCP client instead of running the
+    // demo. State is dumped once the client disconnects.
+    if transport::stdio_requested() {
+        let server = Arc::new(server);
+        StdioTransport::new()
+            .serve(
+                &McpServer::new(server.clone())
+                    .with_completions(server.clone())
+                    .with_resources(server.clone()),
+            )
+            .await?;
+        state_command.dump(&*server).await?;
+        return Ok(());
+    }
 
     // Demonstrate resource functionality
     eprintln!("🧪 Demonstrating resource functionality:");
@@ -362,13 +1460,125 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Err(e) => eprintln!("❌ Read failed: {}", e),
     }
 
+    // Demonstrate isolated collections. In a deployment these identities come
+    // from tokens validated by the auth service (example 13).
+    if state_command.restore_from.is_none() {
+        demo_collections(&server);
+        demo_subscriptions(&server);
+    }
+
+    state_command.dump(&server).await?;
+
     eprintln!("\n🎉 Resource provider demonstration completed!");
     Ok(())
 }
 
+// Function: demo_collections
+//
+// Walks through a private collection: the owner creates it and adds a
+// document, another user cannot see it until granted read access.
+fn demo_collections(server: &ResourceProviderServer) {
+    eprintln!("\n🔐 Multi-tenant collections demonstration:");
+
+    let alice = Identity::new(uuid::Uuid::new_v4(), "alice", "User");
+    let bob = Identity::new(uuid::Uuid::new_v4(), "bob", "User");
+
+    let created = server
+        .call_tool_as(
+            Some(&alice),
+            "create_collection",
+            serde_json::json!({ "name": "finance", "description": "Q3 planning" }),
+        )
+        .and_then(|_| {
+            server.call_tool_as(
+                Some(&alice),
+                "add_document",
+                serde_json::json!({
+                    "collection": "finance",
+                    "title": "Q3 Budget",
+                    "content": "Cloud spend is capped at 40k per month.",
+                    "tags": ["budget"]
+                }),
+            )
+        });
+    let uri = match created {
+        Ok(summary) => {
+            let uri = summary["uri"].as_str().unwrap_or_default().to_string();
+            eprintln!("✅ alice created the 'finance' collection with {}", uri);
+            uri
+        }
+        Err(e) => {
+            eprintln!("❌ Collection setup failed: {}", e);
+            return;
+        }
+    };
+
+    match server.read_resource_as(Some(&bob), &uri) {
+        Ok(_) => eprintln!("❌ bob should not be able to read {}", uri),
+        Err(e) => eprintln!("✅ bob is isolated from alice's data: {}", e),
+    }
+
+    let granted = server.call_tool_as(
+        Some(&alice),
+        "grant_collection_access",
+        serde_json::json!({ "collection": "finance", "username": "bob", "access": "read" }),
+    );
+    if let Err(e) = granted {
+        eprintln!("❌ Grant failed: {}", e);
+        return;
+    }
+
+    match server.read_resource_as(Some(&bob), &uri) {
+        Ok(_) => eprintln!("✅ After alice granted read access, bob can read {}", uri),
+        Err(e) => eprintln!("❌ bob still cannot read: {}", e),
+    }
+    eprintln!(
+        "📋 Anonymous clients still see {} public resources",
+        server.list_resources().len()
+    );
+}
+
+// Function: demo_subscriptions
+//
+// A client subscribes to a public document and is told when an admin edits
+// it; edits to documents nobody subscribed to stay quiet.
+fn demo_subscriptions(server: &ResourceProviderServer) {
+    eprintln!("\n🔔 Resource subscription demonstration:");
+
+    let admin = Identity::new(uuid::Uuid::new_v4(), "root", "Admin");
+    let mut updates = server.updates();
+    if let Err(e) = server.subscribe("document://doc2") {
+        eprintln!("❌ Subscribe failed: {}", e);
+        return;
+    }
+    eprintln!("✅ Subscribed to document://doc2");
+
+    for document_id in ["doc1", "doc2"] {
+        let edited = server.call_tool_as(
+            Some(&admin),
+            "update_document",
+            serde_json::json!({
+                "document_id": document_id,
+                "content": "Rust is a systems programming language.\nIt guarantees memory safety.",
+                "tags": ["Rust", "Reviewed"]
+            }),
+        );
+        if let Err(e) = edited {
+            eprintln!("❌ Update of {} failed: {}", document_id, e);
+        }
+    }
+
+    while let Ok(update) = updates.try_recv() {
+        eprintln!("📨 notifications/resources/updated: {}", update.params());
+    }
+    server.unsubscribe("document://doc2");
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mcp_rust_examples::mock_transport::MockTransport;
+    use mcp_rust_examples::resource_diff::{DiffMode, ResourceChange};
 
     #[test]
     fn test_resource_listing() {
@@ -432,13 +1642,394 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_argument_completion() {
+        let server = ResourceProviderServer::new();
+        let complete = |reference: Value, argument: &str, value: &str| {
+            let params: CompleteParams = serde_json::from_value(serde_json::json!({
+                "ref": reference,
+                "argument": { "name": argument, "value": value }
+            }))
+            .unwrap();
+            server.complete_argument(&params)
+        };
+        let document = serde_json::json!({ "type": "ref/resource", "uri": "document://{id}" });
+        let add_document = serde_json::json!({ "type": "ref/tool", "name": "add_document" });
+
+        assert_eq!(
+            complete(document.clone(), "id", "doc"),
+            ["doc1", "doc2", "doc3", "doc4"]
+        );
+        assert_eq!(complete(document.clone(), "id", "doc3"), ["doc3"]);
+        assert_eq!(complete(document, "collection", ""), [DEFAULT_COLLECTION]);
+
+        // Tags match case-insensitively and are listed once
+        assert_eq!(complete(add_document.clone(), "tags", "r"), ["Rust"]);
+        assert_eq!(
+            complete(add_document.clone(), "tags", "p"),
+            ["Programming", "Protocol"]
+        );
+        assert!(complete(add_document, "title", "").is_empty());
+    }
+
     #[test]
     fn test_tool_listing() {
         let server = ResourceProviderServer::new();
         let tools = server.list_tools();
 
-        assert_eq!(tools.len(), 2);
+        assert_eq!(tools.len(), 11);
         assert!(tools.iter().any(|t| t.name == "search_documents"));
         assert!(tools.iter().any(|t| t.name == "get_document_details"));
+        assert!(tools.iter().any(|t| t.name == "create_collection"));
+        assert!(tools.iter().any(|t| t.name == "grant_collection_access"));
+    }
+
+    #[test]
+    fn test_collection_isolation() {
+        let server = ResourceProviderServer::new();
+        let alice = Identity::new(uuid::Uuid::new_v4(), "alice", "User");
+        let bob = Identity::new(uuid::Uuid::new_v4(), "bob", "User");
+        let admin = Identity::new(uuid::Uuid::new_v4(), "root", "Admin");
+
+        server
+            .call_tool_as(
+                Some(&alice),
+                "create_collection",
+                serde_json::json!({ "name": "research" }),
+            )
+            .unwrap();
+        let added = server
+            .call_tool_as(
+                Some(&alice),
+                "add_document",
+                serde_json::json!({
+                    "collection": "research",
+                    "title": "Secret Rust roadmap",
+                    "content": "Private plans"
+                }),
+            )
+            .unwrap();
+        let uri = added["uri"].as_str().unwrap().to_string();
+        assert!(uri.starts_with("document://research/"));
+
+        // Other tenants and anonymous clients can't see the collection at all
+        assert!(matches!(
+            server.read_resource_as(Some(&bob), &uri),
+            Err(McpError::NotFound(_))
+        ));
+        assert!(server.read_resource(&uri).is_err());
+        let search = server
+            .call_tool_as(
+                Some(&bob),
+                "search_documents",
+                serde_json::json!({ "query": "roadmap" }),
+            )
+            .unwrap();
+        assert_eq!(search["total_count"], 0);
+        assert_eq!(server.list_resources().len(), 4);
+
+        // Bob can't manage alice's collection
+        let grant = serde_json::json!({
+            "collection": "research",
+            "username": "bob",
+            "access": "read"
+        });
+        assert!(server
+            .call_tool_as(Some(&bob), "grant_collection_access", grant.clone())
+            .is_err());
+
+        // Read access lets bob read but not write
+        server
+            .call_tool_as(Some(&alice), "grant_collection_access", grant)
+            .unwrap();
+        assert!(server.read_resource_as(Some(&bob), &uri).is_ok());
+        let write = server.call_tool_as(
+            Some(&bob),
+            "add_document",
+            serde_json::json!({ "collection": "research", "title": "x", "content": "y" }),
+        );
+        assert!(matches!(write, Err(McpError::PermissionDenied(_))));
+
+        // Admins see everything, and the owner can delete the collection
+        assert_eq!(server.list_resources_as(Some(&admin)).len(), 5);
+        server
+            .call_tool_as(
+                Some(&alice),
+                "delete_collection",
+                serde_json::json!({ "name": "research" }),
+            )
+            .unwrap();
+        assert!(server.read_resource_as(Some(&admin), &uri).is_err());
+    }
+
+    #[test]
+    fn test_collection_archive_round_trip() {
+        let server = ResourceProviderServer::new();
+        let alice = Identity::new(uuid::Uuid::new_v4(), "alice", "User");
+        let bob = Identity::new(uuid::Uuid::new_v4(), "bob", "User");
+
+        let exported = server
+            .call_tool_as(
+                None,
+                "export_collection",
+                serde_json::json!({ "name": DEFAULT_COLLECTION }),
+            )
+            .unwrap();
+        assert_eq!(exported["document_count"], 4);
+        assert_eq!(exported["resource"]["mimeType"], ARCHIVE_MIME_TYPE);
+        let blob = exported["resource"]["blob"].as_str().unwrap().to_string();
+
+        // The archive is a header line followed by one line per document
+        let archive = base64::engine::general_purpose::STANDARD
+            .decode(&blob)
+            .unwrap();
+        let archive = String::from_utf8(archive).unwrap();
+        assert_eq!(archive.lines().count(), 5);
+        assert_eq!(exported["size_bytes"], archive.len());
+
+        // Importing under the same name clashes with the existing collection
+        let import = server.call_tool_as(
+            Some(&alice),
+            "import_collection",
+            serde_json::json!({ "archive": blob }),
+        );
+        assert!(matches!(import, Err(McpError::Conflict(_))));
+
+        // Imported collections are private to the importer and keep document ids
+        let imported = server
+            .call_tool_as(
+                Some(&alice),
+                "import_collection",
+                serde_json::json!({ "archive": blob, "name": "backup" }),
+            )
+            .unwrap();
+        assert_eq!(imported["document_count"], 4);
+        assert_eq!(imported["owner"], "alice");
+        let original = server.read_resource("document://doc1").unwrap();
+        let restored = server
+            .read_resource_as(Some(&alice), "document://backup/doc1")
+            .unwrap();
+        assert_eq!(
+            original["contents"][0]["text"],
+            restored["contents"][0]["text"]
+        );
+        assert!(server
+            .read_resource_as(Some(&bob), "document://backup/doc1")
+            .is_err());
+
+        // Exporting needs read access, importing a signed-in user
+        assert!(matches!(
+            server.call_tool_as(
+                Some(&bob),
+                "export_collection",
+                serde_json::json!({ "name": "backup" })
+            ),
+            Err(McpError::NotFound(_))
+        ));
+        assert!(matches!(
+            server.call_tool_as(
+                None,
+                "import_collection",
+                serde_json::json!({ "archive": blob, "name": "anonymous" })
+            ),
+            Err(McpError::PermissionDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_truncated_archive_rejected() {
+        let server = ResourceProviderServer::new();
+        let alice = Identity::new(uuid::Uuid::new_v4(), "alice", "User");
+        let collections = server.collections.read().unwrap();
+        let archive = encode_archive(&collections[DEFAULT_COLLECTION]).unwrap();
+        drop(collections);
+
+        let truncated: String = archive
+            .lines()
+            .take(3)
+            .map(|l| format!("{}\n", l))
+            .collect();
+        for archive in [truncated.as_str(), "not json\n", ""] {
+            let result = server.call_tool_as(
+                Some(&alice),
+                "import_collection",
+                serde_json::json!({
+                    "archive": base64::engine::general_purpose::STANDARD.encode(archive),
+                    "name": "partial"
+                }),
+            );
+            assert!(matches!(result, Err(McpError::InvalidParams(_))));
+        }
+        let result = server.call_tool_as(
+            Some(&alice),
+            "import_collection",
+            serde_json::json!({ "archive": "%%%", "name": "partial" }),
+        );
+        assert!(matches!(result, Err(McpError::InvalidParams(_))));
+        assert_eq!(server.list_collections(Some(&alice)).len(), 1);
+    }
+
+    #[test]
+    fn test_subscription_updates() {
+        let server = ResourceProviderServer::new();
+        let alice = Identity::new(uuid::Uuid::new_v4(), "alice", "User");
+        let admin = Identity::new(uuid::Uuid::new_v4(), "root", "Admin");
+        let mut updates = server.updates();
+
+        // Only documents the client can read can be subscribed to
+        assert!(server.subscribe("document://nonexistent").is_err());
+        server.subscribe("document://doc1").unwrap();
+
+        let edit = |document_id: &str| {
+            server.call_tool_as(
+                Some(&admin),
+                "update_document",
+                serde_json::json!({ "document_id": document_id, "content": "Edited" }),
+            )
+        };
+        edit("doc2").unwrap();
+        edit("doc1").unwrap();
+        let update = updates.try_recv().unwrap();
+        assert_eq!(update.uri, "document://doc1");
+        assert!(matches!(update.change, Some(ResourceChange::Lines { .. })));
+        assert!(updates.try_recv().is_err());
+        assert_eq!(
+            server.read_resource("document://doc1").unwrap()["contents"][0]["text"],
+            "Edited"
+        );
+
+        // Anonymous clients cannot edit, so nothing is announced
+        assert!(server
+            .call_tool(
+                "update_document",
+                serde_json::json!({ "document_id": "doc1", "content": "x" })
+            )
+            .is_err());
+        server.unsubscribe("document://doc1");
+        edit("doc1").unwrap();
+        assert!(updates.try_recv().is_err());
+
+        // Deleting a collection announces its subscribed documents once
+        server
+            .call_tool_as(
+                Some(&alice),
+                "create_collection",
+                serde_json::json!({ "name": "notes" }),
+            )
+            .unwrap();
+        let added = server
+            .call_tool_as(
+                Some(&alice),
+                "add_document",
+                serde_json::json!({ "collection": "notes", "title": "t", "content": "c" }),
+            )
+            .unwrap();
+        let uri = added["uri"].as_str().unwrap();
+        server.subscribe_as(Some(&alice), uri).unwrap();
+        server
+            .call_tool_as(
+                Some(&alice),
+                "delete_collection",
+                serde_json::json!({ "name": "notes" }),
+            )
+            .unwrap();
+        let update = updates.try_recv().unwrap();
+        assert_eq!(update.uri, uri);
+        assert_eq!(update.change, Some(ResourceChange::Deleted));
+        assert!(server.subscriptions.read().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_subscription_over_json_rpc() {
+        let server = Arc::new(ResourceProviderServer::new());
+        let admin = Identity::new(uuid::Uuid::new_v4(), "root", "Admin");
+        let (mut client, server_end) = MockTransport::new().pair();
+        server_end.spawn(Arc::new(
+            McpServer::new(server.clone()).with_resources(server.clone()),
+        ));
+
+        let response = client
+            .request(
+                "resources/subscribe",
+                serde_json::json!({ "uri": "document://doc3" }),
+            )
+            .await
+            .unwrap();
+        assert!(response.error.is_none());
+
+        server
+            .call_tool_as(
+                Some(&admin),
+                "update_document",
+                serde_json::json!({ "document_id": "doc3", "title": "Tokio in depth" }),
+            )
+            .unwrap();
+        let notification = client.recv().await.unwrap();
+        assert_eq!(notification["method"], "notifications/resources/updated");
+        assert_eq!(notification["params"]["uri"], "document://doc3");
+        // Only the title changed, so no lines of the content did
+        assert_eq!(notification["params"]["_meta"]["change"]["removed"], 0);
+    }
+
+    #[test]
+    fn test_updates_describe_the_change() {
+        let admin = Identity::new(uuid::Uuid::new_v4(), "root", "Admin");
+        let edit = |server: &ResourceProviderServer, content: &str| {
+            server
+                .call_tool_as(
+                    Some(&admin),
+                    "update_document",
+                    serde_json::json!({ "document_id": "doc4", "content": content }),
+                )
+                .unwrap();
+        };
+        let server = ResourceProviderServer::new();
+        let mut updates = server.updates();
+        edit(&server, "Line one\nLine two\nLine three\n");
+        server.subscribe("document://doc4").unwrap();
+
+        // A subscriber holding the old text can bring it up to date
+        edit(&server, "Line one\nLine 2\nLine three\n");
+        let change = updates.try_recv().unwrap().change.unwrap();
+        assert_eq!(
+            change.apply("Line one\nLine two\nLine three\n").as_deref(),
+            Some("Line one\nLine 2\nLine three\n")
+        );
+
+        // Past the size limit only the changed bytes are described
+        let server = server.with_diff_config(DiffConfig {
+            max_bytes: 32,
+            ..DiffConfig::default()
+        });
+        let long = format!("Line one\n{}\nLine three\n", "x".repeat(64));
+        edit(&server, &long);
+        assert_eq!(
+            updates.try_recv().unwrap().change,
+            Some(ResourceChange::Range {
+                start: 9,
+                old_end: 15,
+                new_end: 73
+            })
+        );
+
+        let server = server.with_diff_config(DiffConfig {
+            mode: DiffMode::Off,
+            ..DiffConfig::default()
+        });
+        edit(&server, "Short");
+        assert_eq!(updates.try_recv().unwrap().change, None);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_restore() {
+        let server = ResourceProviderServer::new();
+        let snapshot = server.snapshot().await.unwrap();
+
+        let mut restored = ResourceProviderServer::new();
+        restored.collections.get_mut().unwrap().clear();
+        restored.restore(snapshot).await.unwrap();
+
+        assert_eq!(restored.list_resources().len(), 4);
+        assert!(restored.read_resource("document://doc3").is_ok());
     }
 }
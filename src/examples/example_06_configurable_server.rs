@@ -3,11 +3,61 @@
 // This example demonstrates how to build a configurable MCP server that can be
 // customized through external configuration files, environment variables, and
 // command-line arguments. This is essential for real-world deployments.
-
+//
+// When served over stdio the configuration file is watched and reloaded while
+// the server runs. The tools live in a ToolRegistry, so enabling or disabling
+// a tool in the file tells connected clients that the tool list changed.
+//
+// The transport is part of the configuration too: besides stdio, the server
+// can listen for MCP clients over streamable HTTP, so it can be deployed on
+// another machine.
+//
+// A remote configuration source can be layered over the file: while serving,
+// the server polls an HTTP endpoint for a JSON document (etcd-style, only the
+// keys to change) and applies it through the same reload path. The endpoint's
+// host must be on the source's allowed-domain list.
+//
+// With --manifest <file> the built-in tools are replaced by the ones a
+// manifest describes (see examples/manifest_server.yaml), so a whole server
+// can be put together without writing code.
+//
+// Tools can also come from WebAssembly plugins: every .wasm file in
+// MCP_PLUGIN_DIR is loaded at startup and its tools are served next to the
+// built-in ones, each call sandboxed with fuel and memory limits.
+//
+// The greeting tool's messages come from Fluent catalogs in
+// examples/locales; a requested language the server lacks falls back to a
+// close one (es-MX to es) and then to English.
+
+use futures::future::BoxFuture;
+use mcp_rust_examples::diagnostics::DiagnosticsProvider;
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::http_transport::{self, HttpTransport};
+use mcp_rust_examples::i18n::{Catalog, FluentArgs};
+use mcp_rust_examples::logging::ToolCallLog;
+use mcp_rust_examples::manifest::ServerManifest;
+use mcp_rust_examples::plugin::{Plugin, PluginLimits};
+use mcp_rust_examples::protocol::{Tool, ToolAnnotations};
+use mcp_rust_examples::rate_limit::{RateLimitConfig, RateLimiter};
+use mcp_rust_examples::registry::ToolRegistry;
+use mcp_rust_examples::server::{McpServer, ToolHandler};
+use mcp_rust_examples::tools::ToolServer;
+use mcp_rust_examples::transport::{self, StdioTransport};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+// How often the configuration file is checked for changes
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Limits for fetching a remote configuration document
+const REMOTE_CONFIG_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REMOTE_CONFIG_BYTES: usize = 256 * 1024;
 
 // Configuration structure for our server
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -18,8 +68,81 @@ pub struct ServerConfig {
     pub timeout_seconds: u64,
     pub enabled_features: Vec<String>,
     pub tool_configs: HashMap<String, ToolConfig>,
+    // Older configuration files have no transport and keep using stdio
+    #[serde(default)]
+    pub transport: TransportConfig,
+    // Polled while serving and layered over the configuration file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_config: Option<RemoteConfigSource>,
+    // Token buckets for tool calls; unlimited when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limits: Option<RateLimitConfig>,
+}
+
+// An HTTP endpoint serving configuration as a JSON object
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RemoteConfigSource {
+    pub url: String,
+    #[serde(default = "default_remote_poll_seconds")]
+    pub poll_seconds: u64,
+    // Hosts, and their subdomains, the configuration may be fetched from
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+}
+
+fn default_remote_poll_seconds() -> u64 {
+    30
+}
+
+impl RemoteConfigSource {
+    // The URL to poll, if it is HTTP(S) on an allowed domain
+    pub fn validate_url(&self) -> Result<reqwest::Url, String> {
+        let url = reqwest::Url::parse(&self.url)
+            .map_err(|e| format!("Invalid remote config URL: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Unsupported URL scheme: {}", url.scheme()));
+        }
+        let host = url
+            .host_str()
+            .ok_or("Remote config URL must have a valid host")?
+            .trim_end_matches('.')
+            .to_lowercase();
+        let allowed = self
+            .allowed_domains
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)));
+        if !allowed {
+            return Err(format!("Domain '{}' is not in allowed list", host));
+        }
+        Ok(url)
+    }
 }
 
+// How MCP clients reach the server
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TransportConfig {
+    // Served with --stdio, to a client that starts the binary itself
+    #[default]
+    Stdio,
+    // Served over streamable HTTP on startup
+    Http {
+        bind_address: String,
+        #[serde(default = "default_http_path")]
+        path: String,
+        // Browser origins allowed besides localhost
+        #[serde(default)]
+        allowed_origins: Vec<String>,
+    },
+}
+
+fn default_http_path() -> String {
+    http_transport::DEFAULT_PATH.to_string()
+}
+
+// Where MCP_TRANSPORT=http listens unless MCP_HTTP_ADDRESS says otherwise
+const DEFAULT_HTTP_ADDRESS: &str = "127.0.0.1:8080";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ToolConfig {
     pub enabled: bool,
@@ -69,18 +192,13 @@ impl Default for ServerConfig {
             timeout_seconds: 30,
             enabled_features: vec!["logging".to_string(), "metrics".to_string()],
             tool_configs,
+            transport: TransportConfig::Stdio,
+            remote_config: None,
+            rate_limits: None,
         }
     }
 }
 
-// Tool structures
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Tool {
-    pub name: String,
-    pub description: String,
-    pub input_schema: Value,
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GreetingRequest {
     pub name: String,
@@ -104,40 +222,166 @@ pub struct StatusResponse {
 
 // Configurable MCP Server
 pub struct ConfigurableServer {
-    config: ServerConfig,
+    config: RwLock<ServerConfig>,
     start_time: std::time::Instant,
     request_count: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    // Shared with the MCP server's middleware and updated on reload
+    rate_limiter: Arc<RateLimiter>,
+    messages: Catalog,
+    // Plugin tools, published next to the built-in ones on every reload
+    plugins: Vec<Arc<Plugin>>,
+}
+
+// The greeting tool's messages, one catalog per language
+const DEFAULT_LANGUAGE: &str = "en";
+const GREETING_MESSAGES: &[(&str, &str)] = &[
+    ("en", include_str!("../../examples/locales/en/greeting.ftl")),
+    ("es", include_str!("../../examples/locales/es/greeting.ftl")),
+    ("fr", include_str!("../../examples/locales/fr/greeting.ftl")),
+    ("de", include_str!("../../examples/locales/de/greeting.ftl")),
+];
+
+fn greeting_catalog() -> Catalog {
+    let mut catalog = Catalog::new(DEFAULT_LANGUAGE).expect("default language is a valid locale");
+    for (locale, source) in GREETING_MESSAGES {
+        catalog
+            .add_messages(locale, source)
+            .expect("bundled greeting messages are valid Fluent");
+    }
+    catalog
 }
 
 impl ConfigurableServer {
     // Create server with configuration
     pub fn new(config: ServerConfig) -> Self {
         Self {
-            config,
+            rate_limiter: Arc::new(RateLimiter::new(config.rate_limits.clone())),
+            config: RwLock::new(config),
             start_time: std::time::Instant::now(),
             request_count: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            messages: greeting_catalog(),
+            plugins: Vec::new(),
         }
     }
 
+    // Serve the tools of `plugins` as well as the built-in ones
+    pub fn with_plugins(mut self, plugins: Vec<Arc<Plugin>>) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
+    // The plugin directory named by MCP_PLUGIN_DIR, if any
+    pub fn plugin_dir() -> Option<PathBuf> {
+        env::var("MCP_PLUGIN_DIR").ok().map(PathBuf::from)
+    }
+
     // Load configuration from multiple sources with priority:
     // 1. Command line arguments (highest priority)
     // 2. Environment variables
-    // 3. Configuration file
-    // 4. Default values (lowest priority)
+    // 3. Remote configuration source (applied once serving)
+    // 4. Configuration file
+    // 5. Default values (lowest priority)
     pub fn load_config() -> Result<ServerConfig, String> {
         // Start with default configuration
         let mut config = ServerConfig::default();
 
         // Try to load from config file if specified
-        if let Ok(config_path) = env::var("MCP_CONFIG_FILE") {
-            if let Ok(config_content) = std::fs::read_to_string(&config_path) {
-                if let Ok(file_config) = serde_json::from_str::<ServerConfig>(&config_content) {
-                    config = file_config;
-                    eprintln!("📋 Loaded configuration from: {}", config_path);
-                }
+        if let Some(config_path) = Self::config_path() {
+            if let Ok(file_config) = Self::read_config_file(&config_path) {
+                config = file_config;
+                eprintln!("📋 Loaded configuration from: {}", config_path.display());
             }
         }
+        Self::apply_overrides(&mut config);
+
+        eprintln!("⚙️  Configuration loaded:");
+        eprintln!("   Server: {} v{}", config.server_name, config.version);
+        eprintln!("   Max connections: {}", config.max_connections);
+        eprintln!("   Timeout: {}s", config.timeout_seconds);
+        eprintln!("   Features: {:?}", config.enabled_features);
+        eprintln!("   Transport: {:?}", config.transport);
+        if let Some(remote) = &config.remote_config {
+            eprintln!(
+                "   Remote config: {} every {}s",
+                remote.url, remote.poll_seconds
+            );
+        }
+        if let Some(limits) = &config.rate_limits {
+            eprintln!(
+                "   Rate limits: {} calls, {}/s refill ({} tool overrides)",
+                limits.default.capacity,
+                limits.default.refill_per_second,
+                limits.tools.len()
+            );
+        }
+
+        Ok(config)
+    }
+
+    // The configuration file named by MCP_CONFIG_FILE, if any
+    pub fn config_path() -> Option<PathBuf> {
+        Self::config_path_from_env("MCP_CONFIG_FILE")
+    }
+
+    // The configuration file named by the environment variable `var`, if any
+    pub fn config_path_from_env(var: &str) -> Option<PathBuf> {
+        env::var(var).ok().map(PathBuf::from)
+    }
+
+    // The manifest named by --manifest or MCP_MANIFEST, if any
+    pub fn manifest_path() -> Option<PathBuf> {
+        let args: Vec<String> = env::args().collect();
+        args.iter()
+            .position(|arg| arg == "--manifest")
+            .and_then(|i| args.get(i + 1))
+            .map(PathBuf::from)
+            .or_else(|| env::var("MCP_MANIFEST").ok().map(PathBuf::from))
+    }
+
+    // Reads a JSON configuration file. Any config type can be read this way;
+    // the gateway in example 19 loads its routes with it.
+    pub fn read_config_file<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    // Layers a remote document over `base`. Objects merge key by key, so the
+    // document only lists what it changes. It cannot replace the remote source
+    // itself, which would let one endpoint hand the server to another.
+    pub fn overlay_config(base: &ServerConfig, overlay: &Value) -> Result<ServerConfig, String> {
+        let Value::Object(overlay) = overlay else {
+            return Err("Remote configuration must be a JSON object".to_string());
+        };
+        let mut overlay = overlay.clone();
+        overlay.remove("remote_config");
+
+        let mut config = serde_json::to_value(base)
+            .map_err(|e| format!("Failed to serialize configuration: {}", e))?;
+        merge_json(&mut config, Value::Object(overlay));
+        let mut config: ServerConfig = serde_json::from_value(config)
+            .map_err(|e| format!("Invalid remote configuration: {}", e))?;
+        config.remote_config = base.remote_config.clone();
+        Ok(config)
+    }
+
+    // The configuration to run with: `base` from the file (or the defaults),
+    // the remote document over it, then environment and command line
+    pub fn compose_config(
+        base: &ServerConfig,
+        overlay: Option<&Value>,
+    ) -> Result<ServerConfig, String> {
+        let mut config = match overlay {
+            Some(overlay) => Self::overlay_config(base, overlay)?,
+            None => base.clone(),
+        };
+        Self::apply_overrides(&mut config);
+        Ok(config)
+    }
 
+    // Environment variables and command line arguments win over the file
+    fn apply_overrides(config: &mut ServerConfig) {
         // Override with environment variables
         if let Ok(server_name) = env::var("MCP_SERVER_NAME") {
             config.server_name = server_name;
@@ -155,6 +399,40 @@ impl ConfigurableServer {
             }
         }
 
+        if let Ok(transport) = env::var("MCP_TRANSPORT") {
+            let bind_address =
+                env::var("MCP_HTTP_ADDRESS").unwrap_or_else(|_| DEFAULT_HTTP_ADDRESS.to_string());
+            match transport.as_str() {
+                "stdio" => config.transport = TransportConfig::Stdio,
+                "http" => {
+                    config.transport = TransportConfig::Http {
+                        bind_address,
+                        path: default_http_path(),
+                        allowed_origins: Vec::new(),
+                    }
+                }
+                other => eprintln!("⚠️  Ignoring unknown MCP_TRANSPORT: {}", other),
+            }
+        }
+
+        if let Ok(url) = env::var("MCP_CONFIG_URL") {
+            let remote = config
+                .remote_config
+                .get_or_insert_with(|| RemoteConfigSource {
+                    url: String::new(),
+                    poll_seconds: default_remote_poll_seconds(),
+                    allowed_domains: Vec::new(),
+                });
+            remote.url = url;
+            if let Ok(domains) = env::var("MCP_CONFIG_ALLOWED_DOMAINS") {
+                remote.allowed_domains = domains
+                    .split(',')
+                    .map(|domain| domain.trim().to_lowercase())
+                    .filter(|domain| !domain.is_empty())
+                    .collect();
+            }
+        }
+
         // Override with command line arguments (simulated for demo)
         let args: Vec<String> = env::args().collect();
         for i in 0..args.len() {
@@ -170,21 +448,33 @@ impl ConfigurableServer {
                 _ => {}
             }
         }
+    }
 
-        eprintln!("⚙️  Configuration loaded:");
-        eprintln!("   Server: {} v{}", config.server_name, config.version);
-        eprintln!("   Max connections: {}", config.max_connections);
-        eprintln!("   Timeout: {}s", config.timeout_seconds);
-        eprintln!("   Features: {:?}", config.enabled_features);
+    // Swap in a new configuration. Calls already running finish with the old one.
+    pub fn reload_config(&self, config: ServerConfig) {
+        self.rate_limiter.set_config(config.rate_limits.clone());
+        *self.config.write().unwrap() = config;
+    }
 
-        Ok(config)
+    // Make `registry` offer exactly the tools the current configuration
+    // enables, plus the plugins' tools
+    pub fn publish_tools(self: &Arc<Self>, registry: &ToolRegistry) {
+        let tools = self.list_tools().into_iter().map(|tool| {
+            Box::new(ConfiguredTool {
+                tool,
+                server: self.clone(),
+            }) as Box<dyn ToolHandler>
+        });
+        let plugin_tools = self.plugins.iter().flat_map(|plugin| plugin.handlers());
+        registry.replace_all(tools.chain(plugin_tools));
     }
 
     // Get enabled tools based on configuration
     pub fn list_tools(&self) -> Vec<Tool> {
+        let config = self.config.read().unwrap();
         let mut tools = Vec::new();
 
-        for (tool_name, tool_config) in &self.config.tool_configs {
+        for (tool_name, tool_config) in &config.tool_configs {
             if !tool_config.enabled {
                 continue;
             }
@@ -204,13 +494,17 @@ impl ConfigurableServer {
                             },
                             "language": {
                                 "type": "string",
-                                "description": "Language for greeting (en, es, fr, de)",
-                                "enum": ["en", "es", "fr", "de"],
-                                "default": "en"
+                                "description": format!(
+                                    "Language for greeting ({}, or a regional variant such as es-MX); others fall back to {}",
+                                    self.messages.locales().join(", "),
+                                    DEFAULT_LANGUAGE
+                                ),
+                                "default": DEFAULT_LANGUAGE
                             }
                         },
                         "required": ["name"]
                     }),
+                    annotations: Some(ToolAnnotations::read_only()),
                 },
                 "echo" => Tool {
                     name: "echo".to_string(),
@@ -228,6 +522,7 @@ impl ConfigurableServer {
                         },
                         "required": ["message"]
                     }),
+                    annotations: Some(ToolAnnotations::read_only()),
                 },
                 "status" => Tool {
                     name: "status".to_string(),
@@ -240,6 +535,7 @@ impl ConfigurableServer {
                         "properties": {},
                         "additionalProperties": false
                     }),
+                    annotations: Some(ToolAnnotations::read_only()),
                 },
                 _ => continue,
             };
@@ -247,61 +543,60 @@ impl ConfigurableServer {
             tools.push(tool);
         }
 
+        // The map has no order; a stable list keeps reloads from looking like changes
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
         tools
     }
 
+    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        ToolCallLog::run(name, arguments, |arguments| {
+            self.dispatch_tool(name, arguments)
+        })
+    }
+
     // Handle tool calls with configuration support
-    pub fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+    fn dispatch_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        let config = self.config.read().unwrap();
+
         // Increment request counter
         self.request_count
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
         // Check if tool is enabled
-        if let Some(tool_config) = self.config.tool_configs.get(name) {
+        if let Some(tool_config) = config.tool_configs.get(name) {
             if !tool_config.enabled {
-                return Err(format!("Tool '{}' is disabled", name));
+                return Err(McpError::PermissionDenied(format!(
+                    "Tool '{}' is disabled",
+                    name
+                )));
             }
         } else {
-            return Err(format!("Unknown tool: {}", name));
+            return Err(McpError::NotFound(format!("Unknown tool: {}", name)));
         }
 
         match name {
             "greeting" => {
-                let request: GreetingRequest = serde_json::from_value(arguments)
-                    .map_err(|e| format!("Failed to parse arguments: {}", e))?;
-
-                let greeting = match request.language.as_deref().unwrap_or("en") {
-                    "es" => format!(
-                        "¡Hola, {}! Bienvenido al servidor MCP configurable.",
-                        request.name
-                    ),
-                    "fr" => format!(
-                        "Bonjour, {} ! Bienvenue sur le serveur MCP configurable.",
-                        request.name
-                    ),
-                    "de" => format!(
-                        "Hallo, {}! Willkommen beim konfigurierbaren MCP-Server.",
-                        request.name
-                    ),
-                    _ => format!(
-                        "Hello, {}! Welcome to the configurable MCP server.",
-                        request.name
-                    ),
-                };
+                let request: GreetingRequest = serde_json::from_value(arguments).map_err(|e| {
+                    McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                })?;
+
+                let requested = request.language.as_slice();
+                let messages = self.messages.localizer(requested);
+                let args = FluentArgs::from_iter([("name", request.name.as_str())]);
 
                 Ok(serde_json::json!({
-                    "message": greeting,
-                    "language": request.language.unwrap_or_else(|| "en".to_string()),
-                    "server": self.config.server_name
+                    "message": messages.message("greeting", Some(&args)),
+                    "language": messages.locale(),
+                    "server": config.server_name
                 }))
             }
             "echo" => {
-                let request: EchoRequest = serde_json::from_value(arguments)
-                    .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+                let request: EchoRequest = serde_json::from_value(arguments).map_err(|e| {
+                    McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                })?;
 
                 // Get prefix from tool configuration
-                let prefix = self
-                    .config
+                let prefix = config
                     .tool_configs
                     .get("echo")
                     .and_then(|tc| tc.parameters.get("prefix"))
@@ -321,25 +616,214 @@ impl ConfigurableServer {
                     .load(std::sync::atomic::Ordering::Relaxed);
 
                 let response = StatusResponse {
-                    server_name: self.config.server_name.clone(),
-                    version: self.config.version.clone(),
+                    server_name: config.server_name.clone(),
+                    version: config.version.clone(),
                     uptime_seconds: uptime,
                     active_connections: 1, // Simplified for demo
-                    enabled_features: self.config.enabled_features.clone(),
+                    enabled_features: config.enabled_features.clone(),
                     total_requests: request_count,
                 };
 
                 serde_json::to_value(response)
-                    .map_err(|e| format!("Failed to serialize status: {}", e))
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize status: {}", e)))
+            }
+            // Configured, but this build has no code for it
+            _ => Err(McpError::Internal(format!(
+                "Tool implementation not found: {}",
+                name
+            ))),
+        }
+    }
+}
+
+// One enabled tool as registered in the ToolRegistry. Calls go through the
+// server, so they always see the current configuration.
+struct ConfiguredTool {
+    tool: Tool,
+    server: Arc<ConfigurableServer>,
+}
+
+impl ToolHandler for ConfiguredTool {
+    fn tool(&self) -> Tool {
+        self.tool.clone()
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+        Box::pin(async move { self.server.call_tool(&self.tool.name, arguments) })
+    }
+}
+
+// Merges `overlay` into `base`: objects key by key, anything else replaces
+fn merge_json(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
             }
-            _ => Err(format!("Tool implementation not found: {}", name)),
         }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+// Fetches the remote configuration document. Redirects are not followed, so
+// the allowed-domain check on the URL covers every request.
+pub async fn fetch_remote_config(
+    client: &reqwest::Client,
+    url: reqwest::Url,
+) -> Result<Value, String> {
+    let response = client
+        .get(url.clone())
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{} answered {}", url, response.status()));
+    }
+    if response
+        .content_length()
+        .is_some_and(|length| length as usize > MAX_REMOTE_CONFIG_BYTES)
+    {
+        return Err(format!(
+            "{} sent more than {} bytes",
+            url, MAX_REMOTE_CONFIG_BYTES
+        ));
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", url, e))?;
+    if body.len() > MAX_REMOTE_CONFIG_BYTES {
+        return Err(format!(
+            "{} sent more than {} bytes",
+            url, MAX_REMOTE_CONFIG_BYTES
+        ));
+    }
+    serde_json::from_slice(&body).map_err(|e| format!("Failed to parse {}: {}", url, e))
+}
+
+// Waits for the next tick of `interval`, or forever without one
+async fn next_tick(interval: Option<&mut tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+// Polls the configuration file and the remote source, and reloads the server
+// whenever either changes. Invalid files and remote documents are logged and
+// the current configuration is kept. The remote source is the one configured
+// at startup; changing it takes a restart.
+pub fn spawn_config_reloader(
+    path: Option<PathBuf>,
+    remote: Option<RemoteConfigSource>,
+    server: Arc<ConfigurableServer>,
+    registry: Arc<ToolRegistry>,
+    poll_interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last_content = path.as_ref().and_then(|p| std::fs::read_to_string(p).ok());
+        let mut base: ServerConfig = path
+            .as_ref()
+            .and_then(|p| ConfigurableServer::read_config_file(p).ok())
+            .unwrap_or_default();
+        let mut overlay: Option<Value> = None;
+        let mut file_interval = tokio::time::interval(poll_interval);
+
+        let mut remote = remote.and_then(|source| {
+            let checked = source.validate_url().and_then(|url| {
+                reqwest::Client::builder()
+                    .timeout(REMOTE_CONFIG_TIMEOUT)
+                    .redirect(reqwest::redirect::Policy::none())
+                    .build()
+                    .map(|client| (client, url))
+                    .map_err(|e| e.to_string())
+            });
+            match checked {
+                Ok((client, url)) => {
+                    let every = Duration::from_secs(source.poll_seconds.max(1));
+                    Some((client, url, tokio::time::interval(every)))
+                }
+                Err(e) => {
+                    tracing::warn!(url = %source.url, error = %e, "Remote configuration disabled");
+                    None
+                }
+            }
+        });
+
+        loop {
+            let (next_base, next_overlay) = tokio::select! {
+                _ = file_interval.tick(), if path.is_some() => {
+                    let Some(path) = &path else { continue };
+                    let content = std::fs::read_to_string(path).ok();
+                    if content == last_content {
+                        continue;
+                    }
+                    last_content = content;
+                    match ConfigurableServer::read_config_file(path) {
+                        Ok(config) => (config, overlay.clone()),
+                        Err(e) => {
+                            tracing::warn!(error = %e, "Ignoring invalid configuration");
+                            continue;
+                        }
+                    }
+                }
+                _ = next_tick(remote.as_mut().map(|(_, _, interval)| interval)) => {
+                    let Some((client, url, _)) = &remote else { continue };
+                    match fetch_remote_config(client, url.clone()).await {
+                        Ok(document) if overlay.as_ref() == Some(&document) => continue,
+                        Ok(document) => (base.clone(), Some(document)),
+                        Err(e) => {
+                            tracing::warn!(error = %e, "Remote configuration unavailable");
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            match ConfigurableServer::compose_config(&next_base, next_overlay.as_ref()) {
+                Ok(config) => {
+                    server.reload_config(config);
+                    server.publish_tools(&registry);
+                    base = next_base;
+                    overlay = next_overlay;
+                    tracing::info!(remote = overlay.is_some(), "Configuration reloaded");
+                }
+                Err(e) => tracing::warn!(error = %e, "Ignoring invalid configuration"),
+            }
+        }
+    })
+}
+
+// Reported by the self_diagnostics tool
+impl DiagnosticsProvider for ConfigurableServer {
+    fn config_summary(&self) -> Value {
+        serde_json::to_value(&*self.config.read().unwrap()).unwrap_or(Value::Null)
+    }
+
+    fn feature_flags(&self) -> BTreeMap<String, bool> {
+        let config = self.config.read().unwrap();
+        let features = config
+            .enabled_features
+            .iter()
+            .map(|feature| (feature.clone(), true));
+        let tools = config
+            .tool_configs
+            .iter()
+            .map(|(name, tool)| (format!("tool.{}", name), tool.enabled));
+        features.chain(tools).collect()
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
+    let _telemetry = mcp_rust_examples::logging::init("error");
 
     eprintln!("⚙️  Starting Configurable MCP Server");
     eprintln!("=====================================");
@@ -348,7 +832,88 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = ConfigurableServer::load_config()?;
 
     // Create server with loaded configuration
-    let server = ConfigurableServer::new(config);
+    let version = config.version.clone();
+    let transport_config = config.transport.clone();
+    let registry = Arc::new(ToolRegistry::new(config.server_name.clone()));
+    let plugins = match ConfigurableServer::plugin_dir() {
+        Some(dir) => {
+            let plugins = Plugin::load_dir(&dir, PluginLimits::default())?;
+            for plugin in &plugins {
+                eprintln!(
+                    "🧩 Loaded plugin {} with {} tools",
+                    plugin.name(),
+                    plugin.tools().len()
+                );
+            }
+            plugins
+        }
+        None => Vec::new(),
+    };
+    let server = Arc::new(ConfigurableServer::new(config).with_plugins(plugins));
+    server.publish_tools(&registry);
+
+    // With --stdio, or an HTTP transport configured, serve the tools to MCP
+    // clients instead of running the demo. --stdio wins over the configuration.
+    let http = match transport_config {
+        _ if transport::stdio_requested() => None,
+        TransportConfig::Http {
+            bind_address,
+            path,
+            allowed_origins,
+        } => Some(
+            HttpTransport::new(bind_address.parse()?)
+                .with_path(path)
+                .with_allowed_origins(allowed_origins),
+        ),
+        TransportConfig::Stdio => None,
+    };
+
+    // A manifest brings its own tools, served over the configured transport
+    if let Some(path) = ConfigurableServer::manifest_path() {
+        let manifest = ServerManifest::load(&path)?;
+        eprintln!(
+            "📜 Serving {} tools from {}",
+            manifest.tools.len(),
+            path.display()
+        );
+        let mcp_server = Arc::new(
+            manifest
+                .build_server()?
+                .with_middleware(server.rate_limiter.clone()),
+        );
+        match http {
+            Some(http) => http.serve(mcp_server).await?,
+            None => {
+                StdioTransport::new().serve(&mcp_server).await?;
+            }
+        }
+        return Ok(());
+    }
+
+    if transport::stdio_requested() || http.is_some() {
+        let config_path = ConfigurableServer::config_path();
+        let remote_config = server.config.read().unwrap().remote_config.clone();
+        if config_path.is_some() || remote_config.is_some() {
+            spawn_config_reloader(
+                config_path,
+                remote_config,
+                server.clone(),
+                registry.clone(),
+                CONFIG_POLL_INTERVAL,
+            );
+        }
+        let mcp_server = McpServer::new(registry)
+            .with_version(version)
+            .with_middleware(server.rate_limiter.clone())
+            .with_diagnostics(server);
+        match http {
+            Some(http) => http.serve(Arc::new(mcp_server)).await?,
+            None => {
+                StdioTransport::new().serve(&mcp_server).await?;
+            }
+        }
+        return Ok(());
+    }
 
     // Demo configuration features
     eprintln!("\n🧪 Configuration Demo:");
@@ -407,10 +972,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Err(e) => eprintln!("  ❌ Status error: {}", e),
     }
 
+    // Reload a configuration with the echo tool turned off
+    eprintln!("\n🔄 Configuration reload:");
+    let mut changes = registry
+        .tool_list_changes()
+        .ok_or("registry does not announce changes")?;
+    let mut reloaded = server.config.read().unwrap().clone();
+    if let Some(echo) = reloaded.tool_configs.get_mut("echo") {
+        echo.enabled = false;
+    }
+    server.reload_config(reloaded);
+    server.publish_tools(&registry);
+    let tool_names: Vec<String> = registry
+        .tool_descriptors()
+        .into_iter()
+        .map(|tool| tool.name)
+        .collect();
+    eprintln!("  📋 Registered tools: {:?}", tool_names);
+    if changes.try_recv().is_ok() {
+        eprintln!("  📣 Clients would receive notifications/tools/list_changed");
+    }
+
     eprintln!("\n🎉 Configuration demo completed!");
     eprintln!("\n💡 Try setting environment variables:");
     eprintln!("   export MCP_SERVER_NAME=\"My Custom Server\"");
     eprintln!("   export MCP_MAX_CONNECTIONS=50");
+    eprintln!("   export MCP_CONFIG_FILE=config.json  # reloaded on change while serving");
+    eprintln!("   export MCP_PLUGIN_DIR=plugins  # serve the tools of every .wasm file there");
+    eprintln!(
+        "   export MCP_CONFIG_URL=https://config.example.com/mcp.json  # polled while serving"
+    );
+    eprintln!("   export MCP_CONFIG_ALLOWED_DOMAINS=config.example.com");
+    eprintln!(
+        "   export MCP_TRANSPORT=http  # serve on {}",
+        DEFAULT_HTTP_ADDRESS
+    );
     eprintln!("   cargo run --bin example_06_configurable_server");
 
     Ok(())
@@ -430,6 +1026,25 @@ mod tests {
         assert!(config.tool_configs.contains_key("status"));
     }
 
+    #[tokio::test]
+    async fn test_example_manifest() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/manifest_server.yaml");
+        let manifest = ServerManifest::load(&path).unwrap();
+        let router = manifest.tool_router().unwrap();
+
+        let names: Vec<String> = router
+            .tool_descriptors()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, ["greet", "read_example", "find_user", "fetch_json"]);
+        let greeting = router
+            .invoke_tool("greet", serde_json::json!({ "name": "Test" }))
+            .await
+            .unwrap();
+        assert!(greeting.as_str().unwrap().starts_with("Hello, Test!"));
+    }
+
     #[test]
     fn test_server_creation() {
         let config = ServerConfig::default();
@@ -466,6 +1081,18 @@ mod tests {
         let result = server.call_tool("greeting", args).unwrap();
         let message = result.get("message").unwrap().as_str().unwrap();
         assert!(message.contains("¡Hola, Test"));
+
+        // Regional variants fall back to their language, unknown languages
+        // to English
+        for (language, greeting, negotiated) in [
+            ("de-AT", "Hallo, Test!", "de"),
+            ("pt-BR", "Hello, Test!", "en"),
+        ] {
+            let args = serde_json::json!({ "name": "Test", "language": language });
+            let result = server.call_tool("greeting", args).unwrap();
+            assert!(result["message"].as_str().unwrap().starts_with(greeting));
+            assert_eq!(result["language"], negotiated);
+        }
     }
 
     #[test]
@@ -508,7 +1135,245 @@ mod tests {
 
         let args = serde_json::json!({"name": "Test"});
         let result = server.call_tool("greeting", args);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("disabled"));
+        assert_eq!(
+            result,
+            Err(McpError::PermissionDenied(
+                "Tool 'greeting' is disabled".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload_updates_registered_tools() {
+        let server = Arc::new(ConfigurableServer::new(ServerConfig::default()));
+        let registry = ToolRegistry::new("configurable");
+        server.publish_tools(&registry);
+        let mut changes = registry.tool_list_changes().unwrap();
+        assert_eq!(registry.tool_descriptors().len(), 3);
+
+        // A new prefix changes behaviour but not the tool list
+        let mut config = ServerConfig::default();
+        config
+            .tool_configs
+            .get_mut("echo")
+            .unwrap()
+            .parameters
+            .insert(
+                "prefix".to_string(),
+                Value::String("Reloaded: ".to_string()),
+            );
+        server.reload_config(config.clone());
+        server.publish_tools(&registry);
+        assert!(changes.try_recv().is_err());
+        let result = registry
+            .invoke_tool("echo", serde_json::json!({ "message": "hi" }))
+            .await
+            .unwrap();
+        assert_eq!(result["echo"], "Reloaded: hi");
+
+        config.tool_configs.get_mut("greeting").unwrap().enabled = false;
+        server.reload_config(config);
+        server.publish_tools(&registry);
+        assert!(changes.try_recv().is_ok());
+        assert!(!registry.contains("greeting"));
+        assert!(registry.contains("echo"));
+    }
+
+    #[tokio::test]
+    async fn test_plugin_tools_survive_reload() {
+        // Declares one tool, `shout`, whose calls always report an error
+        let tools = r#"[{"name":"shout","description":"Shouts","inputSchema":{"type":"object"}}]"#;
+        let error = r#"{"error":"too quiet"}"#;
+        let source = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{tools}")
+                (data (i32.const 512) "{error}")
+                (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+                (func (export "tools") (result i64) (i64.const {tools_len}))
+                (func (export "invoke") (param i32 i32 i32 i32) (result i64)
+                    (i64.const {error_at})))"#,
+            tools = tools.replace('"', "\\\""),
+            error = error.replace('"', "\\\""),
+            tools_len = tools.len(),
+            error_at = (512i64 << 32) | error.len() as i64,
+        );
+        let bytes = wat::parse_str(source).unwrap();
+        let plugin = Plugin::from_bytes("shout", &bytes, PluginLimits::default()).unwrap();
+        let server = Arc::new(
+            ConfigurableServer::new(ServerConfig::default()).with_plugins(vec![Arc::new(plugin)]),
+        );
+        let registry = ToolRegistry::new("configurable");
+        server.publish_tools(&registry);
+        assert_eq!(registry.tool_descriptors().len(), 4);
+
+        let mut config = ServerConfig::default();
+        config.tool_configs.get_mut("greeting").unwrap().enabled = false;
+        server.reload_config(config);
+        server.publish_tools(&registry);
+        assert!(!registry.contains("greeting"));
+        assert!(registry.contains("shout"));
+
+        let result = registry.invoke_tool("shout", serde_json::json!({})).await;
+        assert_eq!(result, Err(McpError::Internal("too quiet".to_string())));
+    }
+
+    #[test]
+    fn test_remote_config_overlay() {
+        let base = ServerConfig::default();
+        let overlay = serde_json::json!({
+            "server_name": "Remote Server",
+            "tool_configs": { "echo": { "enabled": false } },
+            "remote_config": { "url": "https://elsewhere.example/config" }
+        });
+        let config = ConfigurableServer::overlay_config(&base, &overlay).unwrap();
+
+        // Only the listed keys change, and the source cannot be redirected
+        assert_eq!(config.server_name, "Remote Server");
+        assert!(!config.tool_configs["echo"].enabled);
+        assert_eq!(
+            config.tool_configs["echo"].description_override,
+            base.tool_configs["echo"].description_override
+        );
+        assert!(config.tool_configs["greeting"].enabled);
+        assert_eq!(config.remote_config, None);
+
+        assert!(ConfigurableServer::overlay_config(&base, &serde_json::json!([1])).is_err());
+        let wrong_type = serde_json::json!({ "max_connections": "many" });
+        assert!(ConfigurableServer::overlay_config(&base, &wrong_type).is_err());
+    }
+
+    #[test]
+    fn test_remote_config_allowed_domains() {
+        let source = |url: &str| RemoteConfigSource {
+            url: url.to_string(),
+            poll_seconds: 30,
+            allowed_domains: vec!["config.example.com".to_string()],
+        };
+
+        assert!(source("https://config.example.com/mcp.json")
+            .validate_url()
+            .is_ok());
+        assert!(source("https://eu.config.example.com/mcp.json")
+            .validate_url()
+            .is_ok());
+        assert!(source("https://evilconfig.example.com/mcp.json")
+            .validate_url()
+            .unwrap_err()
+            .contains("not in allowed list"));
+        assert!(source("ftp://config.example.com/mcp.json")
+            .validate_url()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remote_config_reload() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Answers every request with the same configuration document
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let body = r#"{"tool_configs":{"echo":{"enabled":false}}}"#;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let server = Arc::new(ConfigurableServer::new(ServerConfig::default()));
+        let registry = Arc::new(ToolRegistry::new("configurable"));
+        server.publish_tools(&registry);
+        let mut changes = registry.tool_list_changes().unwrap();
+
+        let source = RemoteConfigSource {
+            url: format!("http://{}/config", addr),
+            poll_seconds: 1,
+            allowed_domains: vec!["127.0.0.1".to_string()],
+        };
+        let reloader = spawn_config_reloader(
+            None,
+            Some(source),
+            server.clone(),
+            registry.clone(),
+            CONFIG_POLL_INTERVAL,
+        );
+        tokio::time::timeout(Duration::from_secs(5), changes.recv())
+            .await
+            .expect("remote configuration was not applied")
+            .unwrap();
+        reloader.abort();
+
+        assert!(!registry.contains("echo"));
+        assert!(registry.contains("greeting"));
+        assert!(matches!(
+            server.call_tool("echo", serde_json::json!({ "message": "hi" })),
+            Err(McpError::PermissionDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_transport_configuration() {
+        // Files written before the transport setting still load
+        let mut config = serde_json::to_value(ServerConfig::default()).unwrap();
+        config.as_object_mut().unwrap().remove("transport");
+        let config: ServerConfig = serde_json::from_value(config).unwrap();
+        assert_eq!(config.transport, TransportConfig::Stdio);
+
+        let transport: TransportConfig = serde_json::from_value(serde_json::json!({
+            "type": "http",
+            "bind_address": "0.0.0.0:9000"
+        }))
+        .unwrap();
+        assert_eq!(
+            transport,
+            TransportConfig::Http {
+                bind_address: "0.0.0.0:9000".to_string(),
+                path: "/mcp".to_string(),
+                allowed_origins: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_rate_limits_follow_reload() {
+        let server = ConfigurableServer::new(ServerConfig::default());
+        assert!(server.rate_limiter.check("client", "echo").is_none());
+
+        let config: ServerConfig = serde_json::from_value(serde_json::json!({
+            "server_name": "Limited Server",
+            "version": "1.0.0",
+            "max_connections": 10,
+            "timeout_seconds": 30,
+            "enabled_features": [],
+            "tool_configs": {},
+            "rate_limits": {
+                "default": { "capacity": 5, "refill_per_second": 1.0 },
+                "tools": { "echo": { "capacity": 1, "refill_per_second": 0.1 } }
+            }
+        }))
+        .unwrap();
+        server.reload_config(config);
+
+        // The reloaded limits apply to the next call
+        assert!(server.rate_limiter.check("client", "echo").unwrap().allowed);
+        assert!(!server.rate_limiter.check("client", "echo").unwrap().allowed);
+        assert_eq!(
+            server
+                .rate_limiter
+                .check("client", "status")
+                .unwrap()
+                .remaining,
+            4
+        );
+
+        server.reload_config(ServerConfig::default());
+        assert!(server.rate_limiter.check("client", "echo").is_none());
     }
 }
@@ -3,20 +3,80 @@
 // This example demonstrates safe file system operations in an MCP server.
 // It includes security controls, path validation, and various file operations
 // while maintaining safety and preventing unauthorized access.
-
+//
+// Requested paths are normalized before they are checked: NUL and control
+// characters are rejected, Unicode is composed (NFC), and on Windows and macOS
+// paths are compared without regard to case, so a path cannot slip past the
+// sandbox by being spelled differently.
+//
+// change_directory sets a working directory for the rest of the session:
+// relative paths in later calls on the same connection start from it, and are
+// then checked like any other path.
+//
+// list_directory can descend into subdirectories, returning every entry in one
+// flat list or nested under its directory, with the bytes in each directory
+// totalled. A glob such as **/*.rs keeps only the files whose path below the
+// listed directory matches. Symbolic links are listed but not followed, so a
+// recursive listing stays inside the directory it started from.
+//
+// watch_path watches a file or directory and sends the client
+// notifications/resources/updated, naming the file:// URI of what was
+// created, modified or deleted, until it calls unwatch or disconnects.
+// Subscribing to such a URI with resources/subscribe watches it too.
+//
+// Files too large for read_file can still be read in pieces: read_file with
+// chunked: true reads one page from disk at a time, resuming from
+// next_cursor, and read_file_range reads any byte range of a file.
+//
+// copy_file and move_file check both the source and the destination against
+// the allowed directories, keep the file's modification time, and put the
+// file inside the destination when that is a directory. An existing file at
+// the destination is replaced with overwrite, kept with rename_on_conflict
+// (the file is written as "name (1).txt" instead), and otherwise handled like
+// write_file handles one. create_directory can create missing parents too.
+
+use base64::Engine;
+use futures::future::BoxFuture;
+use mcp_rust_examples::diagnostics::{DependencyCheck, DiagnosticsProvider};
+use mcp_rust_examples::elicitation::{
+    ChannelElicitor, Elicitation, ElicitationError, ElicitationOutcome, ElicitationResponse,
+    ElicitationSchema, TransportElicitor,
+};
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::logging::ToolCallLog;
+use mcp_rust_examples::path_policy::{self, PathError, PathPolicy};
+use mcp_rust_examples::protocol::{Resource, Tool};
+use mcp_rust_examples::resource_diff::{DiffConfig, ResourceUpdate};
+use mcp_rust_examples::response::{ResponseGuard, ResponseGuardError, Truncation};
+use mcp_rust_examples::schema::{mcp_tool, JsonSchema, TypedTool};
+use mcp_rust_examples::server::{McpServer, ResourceProvider};
+use mcp_rust_examples::session;
+use mcp_rust_examples::tools::ToolServer;
+use mcp_rust_examples::transport::{self, StdioTransport};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tokio::fs as async_fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::broadcast;
 
 // Configuration for file operations with security settings
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileOperationsConfig {
     pub allowed_directories: Vec<PathBuf>,
     pub max_file_size: u64,
+    pub max_response_bytes: usize,
     pub allowed_extensions: Vec<String>,
     pub read_only_mode: bool,
     pub enable_directory_listing: bool,
+    // How requested paths are normalized and compared with the allowed directories
+    #[serde(default)]
+    pub path_policy: PathPolicy,
 }
 
 impl Default for FileOperationsConfig {
@@ -27,44 +87,345 @@ impl Default for FileOperationsConfig {
                 PathBuf::from("./data"),
                 PathBuf::from("./examples"),
             ],
-            max_file_size: 1024 * 1024, // 1MB
+            max_file_size: 1024 * 1024,    // 1MB
+            max_response_bytes: 64 * 1024, // 64KB per read_file result
             allowed_extensions: vec![
                 ".txt".to_string(),
                 ".json".to_string(),
                 ".md".to_string(),
                 ".csv".to_string(),
                 ".log".to_string(),
+                ".png".to_string(),
+                ".jpg".to_string(),
+                ".jpeg".to_string(),
+                ".gif".to_string(),
+                ".bmp".to_string(),
             ],
             read_only_mode: false,
             enable_directory_listing: true,
+            path_policy: PathPolicy::default(),
         }
     }
 }
 
-// Request and response structures
+// Request and response structures. Each request describes its tool through
+// #[mcp_tool], which generates the input schema from the fields.
+#[mcp_tool(
+    name = "read_file",
+    description = "Read the contents of a text file safely",
+    read_only
+)]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ReadFileRequest {
+    #[tool(description = "Path to the file to read")]
+    pub file_path: String,
+    #[tool(
+        description = "Byte offset to resume from (the next_cursor of a truncated read)",
+        default = 0
+    )]
+    pub offset: Option<usize>,
+    #[tool(
+        description = "Read one page from disk at a time, for files over the size limit",
+        default = false
+    )]
+    pub chunked: Option<bool>,
+}
+
+#[mcp_tool(
+    name = "read_file_range",
+    description = "Read part of a file of any size by byte offset and length, as UTF-8 text or base64 if the bytes are not text",
+    read_only
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReadFileRangeRequest {
+    #[tool(description = "Path to the file to read")]
+    pub file_path: String,
+    #[tool(description = "Byte offset to start from", default = 0)]
+    pub offset: Option<u64>,
+    #[tool(
+        description = "Most bytes to read; reads are capped at the server's response size",
+        minimum = 1
+    )]
+    pub length: Option<usize>,
+}
+
+#[mcp_tool(
+    name = "get_file_info",
+    description = "Get information about a file or directory",
+    read_only
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileInfoRequest {
+    #[tool(description = "Path to the file or directory")]
     pub file_path: String,
 }
 
+#[mcp_tool(
+    name = "write_file",
+    description = "Write content to a file safely",
+    destructive,
+    idempotent
+)]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct WriteFileRequest {
+    #[tool(description = "Path to the file to write")]
     pub file_path: String,
+    #[tool(description = "Content to write to the file")]
     pub content: String,
+    #[tool(
+        description = "Whether to create parent directories if they don't exist",
+        default = false
+    )]
     pub create_directories: Option<bool>,
+    // Whether to replace an existing file. When omitted, the user is asked.
+    #[tool(description = "Whether to replace an existing file; if omitted, the user is asked")]
+    pub overwrite: Option<bool>,
 }
 
+#[mcp_tool(
+    name = "list_directory",
+    description = "List contents of a directory",
+    read_only
+)]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ListDirectoryRequest {
+    #[tool(description = "Path to the directory to list")]
     pub directory_path: String,
+    #[tool(description = "Whether to include hidden files", default = false)]
     pub include_hidden: Option<bool>,
+    #[tool(description = "Whether to list subdirectories too", default = false)]
+    pub recursive: Option<bool>,
+    #[tool(
+        description = "How many levels to descend in a recursive listing; 1 lists only the directory itself",
+        default = 16,
+        minimum = 1,
+        maximum = 16
+    )]
+    pub max_depth: Option<usize>,
+    #[tool(
+        description = "Only list files whose path below the directory matches, e.g. **/*.rs or *.txt"
+    )]
+    pub glob: Option<String>,
+    #[tool(description = "Return a flat list, or a tree with each directory's entries under it")]
+    pub layout: Option<ListingLayout>,
+}
+
+// How a listing is returned
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ListingLayout {
+    #[default]
+    Flat,
+    // Directories hold their entries in children
+    Tree,
+}
+
+impl JsonSchema for ListingLayout {
+    fn json_schema() -> Value {
+        serde_json::json!({ "type": "string", "enum": ["flat", "tree"], "default": "flat" })
+    }
 }
 
+// Recursive listings go at most this deep, and stop after this many entries
+const MAX_LISTING_DEPTH: usize = 16;
+const MAX_LISTING_ENTRIES: usize = 5000;
+
+#[mcp_tool(
+    name = "delete_file",
+    description = "Delete a file safely",
+    destructive,
+    idempotent
+)]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DeleteFileRequest {
+    #[tool(description = "Path to the file to delete")]
     pub file_path: String,
 }
 
+#[mcp_tool(
+    name = "copy_file",
+    description = "Copy a file, keeping its modification time",
+    destructive
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CopyFileRequest {
+    #[tool(description = "Path to the file to copy")]
+    pub source_path: String,
+    #[tool(description = "Path to copy to; a directory receives the file under its own name")]
+    pub destination_path: String,
+    #[tool(description = "Whether to replace an existing file; if omitted, the user is asked")]
+    pub overwrite: Option<bool>,
+    #[tool(
+        description = "Whether to write to a free name such as \"notes (1).txt\" when the destination exists",
+        default = false
+    )]
+    pub rename_on_conflict: Option<bool>,
+}
+
+#[mcp_tool(
+    name = "move_file",
+    description = "Move or rename a file, keeping its modification time",
+    destructive
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MoveFileRequest {
+    #[tool(description = "Path to the file to move")]
+    pub source_path: String,
+    #[tool(description = "Path to move to; a directory receives the file under its own name")]
+    pub destination_path: String,
+    #[tool(description = "Whether to replace an existing file; if omitted, the user is asked")]
+    pub overwrite: Option<bool>,
+    #[tool(
+        description = "Whether to move to a free name such as \"notes (1).txt\" when the destination exists",
+        default = false
+    )]
+    pub rename_on_conflict: Option<bool>,
+}
+
+// rename_on_conflict tries "name (1)" up to "name (N)" before giving up
+const MAX_CONFLICT_RENAMES: usize = 100;
+
+#[mcp_tool(
+    name = "create_directory",
+    description = "Create a directory",
+    idempotent
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateDirectoryRequest {
+    #[tool(description = "Path to the directory to create")]
+    pub directory_path: String,
+    #[tool(
+        description = "Whether to create missing parent directories too",
+        default = false
+    )]
+    pub parents: Option<bool>,
+}
+
+#[mcp_tool(
+    name = "change_directory",
+    description = "Set the directory that relative paths start from for the rest of the session",
+    idempotent
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChangeDirectoryRequest {
+    #[tool(description = "Directory to change to; a relative path starts from the current one")]
+    pub directory_path: String,
+}
+
+// The session key of the working directory, a canonical path within an
+// allowed directory
+const WORKING_DIRECTORY_KEY: &str = "file_operations.working_directory";
+
+#[mcp_tool(
+    name = "watch_path",
+    description = "Watch a file or directory, sending notifications/resources/updated for each change until unwatched or the connection closes"
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WatchPathRequest {
+    #[tool(description = "Path to the file or directory to watch")]
+    pub path: String,
+    #[tool(
+        description = "Whether to watch the subdirectories of a directory too",
+        default = false
+    )]
+    pub recursive: Option<bool>,
+}
+
+#[mcp_tool(
+    name = "unwatch",
+    description = "Stop a watch started with watch_path",
+    idempotent
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UnwatchRequest {
+    #[tool(description = "The watch_id returned by watch_path")]
+    pub watch_id: String,
+}
+
+// Watches are kept in the session of the connection that started them, under
+// this prefix, so they stop when it closes. Each connection may keep this many.
+const WATCH_KEY_PREFIX: &str = "file_operations.watch.";
+const MAX_WATCHES_PER_SESSION: usize = 16;
+// Changes not yet sent to the connections
+const WATCH_UPDATE_CAPACITY: usize = 256;
+
+// A path being watched, as watch_path returns it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WatchedPath {
+    pub watch_id: String,
+    pub path: String,
+    pub uri: String,
+    pub recursive: bool,
+}
+
+// The watches of every connection, and the channel their changes go out on
+struct FileWatches {
+    active: Mutex<BTreeMap<String, WatchedPath>>,
+    updates: broadcast::Sender<ResourceUpdate>,
+    diff: DiffConfig,
+}
+
+impl FileWatches {
+    fn new() -> Self {
+        Self {
+            active: Mutex::new(BTreeMap::new()),
+            updates: broadcast::channel(WATCH_UPDATE_CAPACITY).0,
+            diff: DiffConfig::from_env(),
+        }
+    }
+
+    // Announce the paths an event touched. Files with extensions the server
+    // does not serve are left out, as is access to files that did not change
+    // them.
+    fn announce(&self, event: &notify::Event, allowed_extensions: &[String]) {
+        let deleted = match event.kind {
+            EventKind::Create(_) | EventKind::Modify(_) => None,
+            EventKind::Remove(_) => self.diff.deleted(),
+            _ => return,
+        };
+        for (index, path) in event.paths.iter().enumerate() {
+            let allowed = path.extension().is_none_or(|extension| {
+                let ext = format!(".{}", extension.to_string_lossy().to_lowercase());
+                allowed_extensions.contains(&ext)
+            });
+            if !allowed {
+                continue;
+            }
+            // A rename names the old path, then the new one if it is known
+            let change = match event.kind {
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)) => self.diff.deleted(),
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if index == 0 => {
+                    self.diff.deleted()
+                }
+                _ => deleted.clone(),
+            };
+            // Nobody may be listening
+            let _ = self
+                .updates
+                .send(ResourceUpdate::new(file_uri(path)).with_change(change));
+        }
+    }
+}
+
+// A running watch. Dropping it, when it is unwatched or its session ends,
+// stops the watcher.
+struct FileWatch {
+    id: String,
+    watches: Arc<FileWatches>,
+    _watcher: Mutex<RecommendedWatcher>,
+}
+
+impl Drop for FileWatch {
+    fn drop(&mut self) {
+        self.watches.active.lock().unwrap().remove(&self.id);
+    }
+}
+
+// The resource URI of a file, e.g. file:///srv/data/report.txt
+fn file_uri(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FileInfo {
     pub name: String,
@@ -74,20 +435,106 @@ pub struct FileInfo {
     pub modified: String,
     pub readable: bool,
     pub writable: bool,
+    // In recursive listings, the bytes in the files listed below a directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_size: Option<u64>,
+    // In tree listings, the entries of a directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<FileInfo>>,
+}
+
+#[mcp_tool(
+    name = "preview_file",
+    description = "Preview a file: first lines of text, CSV headers, image dimensions and thumbnail, or metadata for large binaries",
+    read_only
+)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PreviewFileRequest {
+    #[tool(description = "Path to the file to preview")]
+    pub file_path: String,
+    #[tool(
+        description = "Number of lines to include for text files",
+        default = 20,
+        maximum = 200
+    )]
+    pub max_lines: Option<usize>,
+}
+
+// Type-specific part of a file preview
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PreviewContent {
+    Text {
+        lines: Vec<String>,
+        // Known only when the whole file fit in the preview read
+        total_lines: Option<usize>,
+        truncated: bool,
+    },
+    Csv {
+        headers: Vec<String>,
+        sample_rows: Vec<Vec<String>>,
+    },
+    Image {
+        format: String,
+        width: u32,
+        height: u32,
+        // data: URI of the image itself, only for images small enough to inline
+        thumbnail: Option<String>,
+    },
+    Binary {
+        // Hex of the first bytes, useful for identifying the format
+        header_hex: String,
+    },
+    // Files over the size cap are described but not read
+    Metadata,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FilePreview {
+    pub path: String,
+    pub size: u64,
+    pub mime_type: String,
+    #[serde(flatten)]
+    pub content: PreviewContent,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DirectoryListing {
     pub path: String,
+    // In a tree listing, only the entries of the directory itself
     pub files: Vec<FileInfo>,
+    // Entries at every level
     pub total_count: usize,
+    #[serde(default)]
+    pub file_count: usize,
+    #[serde(default)]
+    pub directory_count: usize,
+    // Bytes in the files listed
+    #[serde(default)]
+    pub total_size: u64,
+    // The listing stopped at MAX_LISTING_ENTRIES
+    #[serde(default)]
+    pub truncated: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Tool {
-    pub name: String,
-    pub description: String,
-    pub input_schema: Value,
+// What a recursive walk keeps, and how far it has got
+struct ListingWalk {
+    include_hidden: bool,
+    recursive: bool,
+    max_depth: usize,
+    glob: Option<glob::Pattern>,
+    match_options: glob::MatchOptions,
+    visited: usize,
+    truncated: bool,
+}
+
+impl ListingWalk {
+    // Files are kept when their path below the listed directory matches
+    fn keeps_file(&self, relative_path: &str) -> bool {
+        self.glob
+            .as_ref()
+            .is_none_or(|glob| glob.matches_with(relative_path, self.match_options))
+    }
 }
 
 // Custom error types for file operations
@@ -120,62 +567,224 @@ impl std::fmt::Display for FileOperationError {
 
 impl std::error::Error for FileOperationError {}
 
+impl From<PathError> for FileOperationError {
+    fn from(error: PathError) -> Self {
+        match error {
+            PathError::Invalid(message) => FileOperationError::InvalidPath(message),
+            PathError::NotAllowed(_) => FileOperationError::SecurityViolation(error.to_string()),
+        }
+    }
+}
+
+impl From<FileOperationError> for McpError {
+    fn from(error: FileOperationError) -> Self {
+        let message = error.to_string();
+        match error {
+            FileOperationError::SecurityViolation(_) | FileOperationError::PermissionDenied(_) => {
+                McpError::PermissionDenied(message)
+            }
+            FileOperationError::InvalidPath(_)
+            | FileOperationError::FileTooLarge(_)
+            | FileOperationError::UnsupportedExtension(_) => McpError::InvalidParams(message),
+            FileOperationError::FileNotFound(_) => McpError::NotFound(message),
+            FileOperationError::IoError(_) => McpError::Internal(message),
+        }
+    }
+}
+
+// Failed file system calls keep the reason when the caller can act on it
+fn io_error(action: &str, error: std::io::Error) -> McpError {
+    let message = format!("{}: {}", action, error);
+    match error.kind() {
+        std::io::ErrorKind::NotFound => McpError::NotFound(message),
+        std::io::ErrorKind::PermissionDenied => McpError::PermissionDenied(message),
+        _ => McpError::Internal(message),
+    }
+}
+
+// Previews read at most this much of a file
+const PREVIEW_READ_BYTES: u64 = 64 * 1024;
+// Images up to this size are returned inline as their own thumbnail
+const THUMBNAIL_MAX_BYTES: u64 = 16 * 1024;
+const CSV_SAMPLE_ROWS: usize = 5;
+
+// Identify a file from its first bytes, falling back to the extension
+fn detect_mime_type(path: &Path, head: &[u8]) -> &'static str {
+    let magic: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+    ];
+    if let Some((_, mime)) = magic.iter().find(|(prefix, _)| head.starts_with(prefix)) {
+        return mime;
+    }
+
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "md" => "text/markdown",
+        _ if looks_like_text(head) => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+// UTF-8 without NUL bytes, allowing a character cut off at the end of the read
+fn looks_like_text(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+// Read width and height from an image header without decoding the image
+fn image_dimensions(mime_type: &str, head: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |i: usize| Some(u16::from_be_bytes(head.get(i..i + 2)?.try_into().ok()?) as u32);
+    let le16 = |i: usize| Some(u16::from_le_bytes(head.get(i..i + 2)?.try_into().ok()?) as u32);
+    let be32 = |i: usize| Some(u32::from_be_bytes(head.get(i..i + 4)?.try_into().ok()?));
+    let le32 = |i: usize| Some(i32::from_le_bytes(head.get(i..i + 4)?.try_into().ok()?));
+
+    match mime_type {
+        // IHDR is always the first chunk
+        "image/png" => Some((be32(16)?, be32(20)?)),
+        "image/gif" => Some((le16(6)?, le16(8)?)),
+        // Height is negative for top-down bitmaps
+        "image/bmp" => Some((le32(18)?.unsigned_abs(), le32(22)?.unsigned_abs())),
+        "image/jpeg" => {
+            // Walk the marker segments until a start-of-frame marker
+            let mut i = 2;
+            while i + 9 < head.len() {
+                if head[i] != 0xff {
+                    return None;
+                }
+                let marker = head[i + 1];
+                let is_sof =
+                    (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+                if is_sof {
+                    return Some((be16(i + 7)?, be16(i + 5)?));
+                }
+                i += 2 + be16(i + 2)? as usize;
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+// Split one CSV line, honouring double-quoted fields
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
 // File Operations Server
 pub struct FileOperationsServer {
     config: FileOperationsConfig,
+    // Used to confirm overwrites; without it, existing files are replaced
+    elicitation: Option<Elicitation>,
+    watches: Arc<FileWatches>,
 }
 
 impl FileOperationsServer {
     pub fn new(config: FileOperationsConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            elicitation: None,
+            watches: Arc::new(FileWatches::new()),
+        }
     }
 
-    // Validate that a path is safe and allowed
-    fn validate_path(&self, path: &str) -> Result<PathBuf, FileOperationError> {
-        let path = Path::new(path);
-
-        // Convert to absolute path to prevent directory traversal
-        let canonical_path = match path.canonicalize() {
-            Ok(p) => p,
-            Err(_) => {
-                // If canonicalize fails, the file might not exist yet
-                // Try to canonicalize the parent directory
-                if let Some(parent) = path.parent() {
-                    if parent.exists() {
-                        let canonical_parent = parent
-                            .canonicalize()
-                            .map_err(|e| FileOperationError::InvalidPath(e.to_string()))?;
-                        canonical_parent.join(path.file_name().unwrap_or_default())
-                    } else {
-                        return Err(FileOperationError::InvalidPath(
-                            "Parent directory does not exist".to_string(),
-                        ));
-                    }
-                } else {
-                    return Err(FileOperationError::InvalidPath(
-                        "Invalid path structure".to_string(),
-                    ));
-                }
-            }
+    pub fn with_elicitation(mut self, elicitation: Elicitation) -> Self {
+        self.elicitation = Some(elicitation);
+        self
+    }
+
+    // Decide whether an existing file may be replaced, asking the user when
+    // the caller did not say and the client supports elicitation. A client
+    // that cannot be asked gets the same answer as when nobody can be.
+    async fn confirm_overwrite(
+        &self,
+        path: &Path,
+        overwrite: Option<bool>,
+    ) -> Result<(), McpError> {
+        let refused = |reason: &str| {
+            McpError::Conflict(format!("File already exists: {}{}", path.display(), reason))
         };
 
-        // Check if path is within allowed directories
-        let mut allowed = false;
-        for allowed_dir in &self.config.allowed_directories {
-            if let Ok(canonical_allowed) = allowed_dir.canonicalize() {
-                if canonical_path.starts_with(&canonical_allowed) {
-                    allowed = true;
-                    break;
+        match (overwrite, &self.elicitation) {
+            (Some(true), _) | (None, None) => Ok(()),
+            (Some(false), _) => Err(refused("")),
+            (None, Some(elicitation)) => {
+                let schema = ElicitationSchema::new().boolean(
+                    "overwrite",
+                    "Replace the existing file with the new content",
+                    true,
+                );
+                let outcome = match elicitation
+                    .ask(
+                        format!("{} already exists. Overwrite it?", path.display()),
+                        schema,
+                    )
+                    .await
+                {
+                    Ok(outcome) => outcome,
+                    Err(ElicitationError::Unsupported) => return Ok(()),
+                    Err(e) => {
+                        return Err(McpError::Unavailable(format!(
+                            "Could not confirm overwrite: {}",
+                            e
+                        )))
+                    }
+                };
+
+                match outcome {
+                    ElicitationOutcome::Accepted(content) if content["overwrite"] == true => Ok(()),
+                    ElicitationOutcome::Accepted(_) | ElicitationOutcome::Declined => {
+                        Err(refused(" (overwrite declined)"))
+                    }
+                    ElicitationOutcome::Cancelled => Err(refused(" (overwrite cancelled)")),
+                    ElicitationOutcome::TimedOut => {
+                        Err(refused(" (no answer to the overwrite prompt)"))
+                    }
                 }
             }
         }
+    }
 
-        if !allowed {
-            return Err(FileOperationError::SecurityViolation(format!(
-                "Path '{}' is not in an allowed directory",
-                canonical_path.display()
-            )));
-        }
+    // Validate that a path is safe and allowed
+    fn validate_path(&self, path: &str) -> Result<PathBuf, FileOperationError> {
+        let policy = &self.config.path_policy;
+        let path = resolve_in_session(Path::new(&policy.normalize(path)?));
+
+        // Canonicalize to prevent directory traversal, then check the path
+        // is within the allowed directories
+        let canonical_path = policy.resolve(&path, &self.config.allowed_directories)?;
 
         // Check file extension if it exists
         if let Some(extension) = canonical_path.extension() {
@@ -191,6 +800,100 @@ impl FileOperationsServer {
         Ok(canonical_path)
     }
 
+    // Validate a directory that may not exist yet, with any missing parents.
+    // The nearest existing ancestor must be within the allowed directories;
+    // directory names are not checked against the allowed extensions.
+    fn validate_new_directory(
+        &self,
+        path: &str,
+        parents: bool,
+    ) -> Result<PathBuf, FileOperationError> {
+        let policy = &self.config.path_policy;
+        let path = resolve_in_session(Path::new(&policy.normalize(path)?));
+
+        let mut ancestor = path.as_path();
+        let mut missing = Vec::new();
+        while !ancestor.exists() {
+            match (ancestor.parent(), ancestor.file_name()) {
+                (Some(parent), Some(name)) => {
+                    missing.push(name);
+                    ancestor = if parent.as_os_str().is_empty() {
+                        Path::new(".")
+                    } else {
+                        parent
+                    };
+                }
+                _ => {
+                    return Err(FileOperationError::InvalidPath(
+                        "Invalid path structure".to_string(),
+                    ))
+                }
+            }
+        }
+        if missing.len() > 1 && !parents {
+            return Err(FileOperationError::InvalidPath(
+                "Parent directory does not exist; set parents to create it".to_string(),
+            ));
+        }
+
+        let mut canonical_path = policy.resolve(ancestor, &self.config.allowed_directories)?;
+        canonical_path.extend(missing.iter().rev());
+        Ok(canonical_path)
+    }
+
+    // Decide where a copied or moved file goes. A directory receives the file
+    // under its own name; an existing file is kept by writing to a free name
+    // with rename_on_conflict, and otherwise replaced as confirm_overwrite
+    // decides. Returns the path and whether it was renamed.
+    async fn resolve_destination(
+        &self,
+        source: &Path,
+        destination: &str,
+        overwrite: Option<bool>,
+        rename_on_conflict: bool,
+    ) -> Result<(PathBuf, bool), McpError> {
+        if rename_on_conflict && overwrite == Some(true) {
+            return Err(McpError::InvalidParams(
+                "overwrite and rename_on_conflict cannot both be set".to_string(),
+            ));
+        }
+
+        let mut path = self.validate_path(destination)?;
+        if path.is_dir() {
+            let name = source.file_name().unwrap_or_default();
+            path = self.validate_path(&path.join(name).to_string_lossy())?;
+        }
+        if path == source {
+            return Err(FileOperationError::InvalidPath(format!(
+                "Source and destination are the same file: {}",
+                path.display()
+            ))
+            .into());
+        }
+        if path.is_dir() {
+            return Err(McpError::Conflict(format!(
+                "A directory already exists at {}",
+                path.display()
+            )));
+        }
+
+        if !async_fs::try_exists(&path).await.unwrap_or(false) {
+            return Ok((path, false));
+        }
+        if rename_on_conflict {
+            return match free_name(&path) {
+                Some(renamed) => Ok((renamed, true)),
+                None => Err(McpError::Conflict(format!(
+                    "No free name for {} after {} attempts",
+                    path.display(),
+                    MAX_CONFLICT_RENAMES
+                ))),
+            };
+        }
+        self.confirm_overwrite(&path, overwrite).await?;
+        Ok((path, false))
+    }
+
     // Check file size constraints
     fn validate_file_size(&self, size: u64) -> Result<(), FileOperationError> {
         if size > self.config.max_file_size {
@@ -238,169 +941,281 @@ impl FileOperationsServer {
             modified,
             readable: true, // Simplified for demo
             writable: !self.config.read_only_mode,
+            total_size: None,
+            children: None,
         })
     }
 
     pub fn list_tools(&self) -> Vec<Tool> {
         let mut tools = vec![
-            Tool {
-                name: "read_file".to_string(),
-                description: "Read the contents of a text file safely".to_string(),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "file_path": {
-                            "type": "string",
-                            "description": "Path to the file to read"
-                        }
-                    },
-                    "required": ["file_path"]
-                }),
-            },
-            Tool {
-                name: "get_file_info".to_string(),
-                description: "Get information about a file or directory".to_string(),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "file_path": {
-                            "type": "string",
-                            "description": "Path to the file or directory"
-                        }
-                    },
-                    "required": ["file_path"]
-                }),
-            },
+            ReadFileRequest::tool(),
+            ReadFileRangeRequest::tool(),
+            PreviewFileRequest::tool(),
+            FileInfoRequest::tool(),
+            ChangeDirectoryRequest::tool(),
+            WatchPathRequest::tool(),
+            UnwatchRequest::tool(),
         ];
 
         if !self.config.read_only_mode {
             tools.extend([
-                Tool {
-                    name: "write_file".to_string(),
-                    description: "Write content to a file safely".to_string(),
-                    input_schema: serde_json::json!({
-                        "type": "object",
-                        "properties": {
-                            "file_path": {
-                                "type": "string",
-                                "description": "Path to the file to write"
-                            },
-                            "content": {
-                                "type": "string",
-                                "description": "Content to write to the file"
-                            },
-                            "create_directories": {
-                                "type": "boolean",
-                                "description": "Whether to create parent directories if they don't exist",
-                                "default": false
-                            }
-                        },
-                        "required": ["file_path", "content"]
-                    }),
-                },
-                Tool {
-                    name: "delete_file".to_string(),
-                    description: "Delete a file safely".to_string(),
-                    input_schema: serde_json::json!({
-                        "type": "object",
-                        "properties": {
-                            "file_path": {
-                                "type": "string",
-                                "description": "Path to the file to delete"
-                            }
-                        },
-                        "required": ["file_path"]
-                    }),
-                },
+                WriteFileRequest::tool(),
+                DeleteFileRequest::tool(),
+                CopyFileRequest::tool(),
+                MoveFileRequest::tool(),
+                CreateDirectoryRequest::tool(),
             ]);
         }
 
         if self.config.enable_directory_listing {
-            tools.push(Tool {
-                name: "list_directory".to_string(),
-                description: "List contents of a directory".to_string(),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "directory_path": {
-                            "type": "string",
-                            "description": "Path to the directory to list"
-                        },
-                        "include_hidden": {
-                            "type": "boolean",
-                            "description": "Whether to include hidden files",
-                            "default": false
-                        }
-                    },
-                    "required": ["directory_path"]
-                }),
-            });
+            tools.push(ListDirectoryRequest::tool());
         }
 
         tools
     }
 
-    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        ToolCallLog::run_async(name, arguments, |arguments| {
+            self.dispatch_tool(name, arguments)
+        })
+        .await
+    }
+
+    async fn dispatch_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
         match name {
             "read_file" => self.read_file(arguments).await,
+            "read_file_range" => self.read_file_range(arguments).await,
+            "preview_file" => self.preview_file(arguments).await,
             "write_file" => self.write_file(arguments).await,
             "delete_file" => self.delete_file(arguments).await,
+            "copy_file" => self.copy_file(arguments).await,
+            "move_file" => self.move_file(arguments).await,
+            "create_directory" => self.create_directory(arguments).await,
             "list_directory" => self.list_directory(arguments).await,
             "get_file_info" => self.get_file_info(arguments).await,
-            _ => Err(format!("Unknown tool: {}", name)),
+            "change_directory" => self.change_directory(arguments).await,
+            "watch_path" => self.watch_path(arguments).await,
+            "unwatch" => self.unwatch(arguments).await,
+            _ => Err(McpError::NotFound(format!("Unknown tool: {}", name))),
         }
     }
 
-    async fn read_file(&self, arguments: Value) -> Result<Value, String> {
-        let request: ReadFileRequest = serde_json::from_value(arguments)
-            .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+    async fn read_file(&self, arguments: Value) -> Result<Value, McpError> {
+        let request = ReadFileRequest::parse(arguments)?;
 
-        let path = self
-            .validate_path(&request.file_path)
-            .map_err(|e| e.to_string())?;
+        let path = self.validate_path(&request.file_path)?;
+        if request.chunked.unwrap_or(false) {
+            let offset = request.offset.unwrap_or(0) as u64;
+            return self.read_file_chunk(&path, offset).await;
+        }
 
         let content = async_fs::read_to_string(&path)
             .await
-            .map_err(|e| format!("Failed to read file: {}", e))?;
+            .map_err(|e| io_error("Failed to read file", e))?;
 
-        self.validate_file_size(content.len() as u64)
-            .map_err(|e| e.to_string())?;
+        self.validate_file_size(content.len() as u64)?;
 
-        Ok(serde_json::json!({
-            "content": content,
+        // Large files are returned in pages; the client resumes from next_cursor
+        let page = ResponseGuard::new(self.config.max_response_bytes)
+            .page_text(&content, request.offset.unwrap_or(0))?;
+
+        let mut result = serde_json::json!({
+            "content": page.content,
             "path": path.to_string_lossy(),
             "size": content.len(),
             "encoding": "utf-8"
-        }))
+        });
+        page.truncation.attach(&mut result);
+        Ok(result)
+    }
+
+    // Read one page of a text file straight from disk, so files over
+    // max_file_size can be read page by page. Pages end on a character
+    // boundary, and the next one starts at next_cursor.
+    async fn read_file_chunk(&self, path: &Path, offset: u64) -> Result<Value, McpError> {
+        let (bytes, size) = read_range(path, offset, self.config.max_response_bytes).await?;
+
+        let text = match std::str::from_utf8(&bytes) {
+            Ok(text) => text,
+            // The page ends partway through a character, which the next one starts with
+            Err(e) if e.error_len().is_none() && e.valid_up_to() > 0 => {
+                std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default()
+            }
+            Err(_) if bytes.first().is_some_and(|byte| byte & 0xC0 == 0x80) => {
+                return Err(ResponseGuardError::NotCharBoundary(offset as usize).into())
+            }
+            Err(e) => {
+                return Err(McpError::InvalidParams(format!(
+                    "File is not UTF-8 text at byte {}; use read_file_range for binary content",
+                    offset + e.valid_up_to() as u64
+                )))
+            }
+        };
+
+        let end = offset + text.len() as u64;
+        let mut result = serde_json::json!({
+            "content": text,
+            "path": path.to_string_lossy(),
+            "size": size,
+            "encoding": "utf-8"
+        });
+        Truncation {
+            truncated: end < size,
+            total_size: size as usize,
+            next_cursor: (end < size).then_some(end as usize),
+        }
+        .attach(&mut result);
+        Ok(result)
+    }
+
+    async fn read_file_range(&self, arguments: Value) -> Result<Value, McpError> {
+        let request = ReadFileRangeRequest::parse(arguments)?;
+
+        let path = self.validate_path(&request.file_path)?;
+        let offset = request.offset.unwrap_or(0);
+        let length = request
+            .length
+            .unwrap_or(self.config.max_response_bytes)
+            .clamp(1, self.config.max_response_bytes);
+
+        let (bytes, size) = read_range(&path, offset, length).await?;
+
+        // Bytes that are not text are sent as base64, which takes a third
+        // more room, so fewer of them fit in a response
+        let (content, read, encoding) = match String::from_utf8(bytes) {
+            Ok(text) => {
+                let read = text.len();
+                (text, read, "utf-8")
+            }
+            Err(e) => {
+                let mut bytes = e.into_bytes();
+                bytes.truncate((self.config.max_response_bytes / 4 * 3).max(1));
+                let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
+                (encoded, bytes.len(), "base64")
+            }
+        };
+        let end = offset + read as u64;
+
+        let mut result = serde_json::json!({
+            "content": content,
+            "path": path.to_string_lossy(),
+            "offset": offset,
+            "length": read,
+            "size": size,
+            "encoding": encoding
+        });
+        Truncation {
+            truncated: end < size,
+            total_size: size as usize,
+            next_cursor: (end < size).then_some(end as usize),
+        }
+        .attach(&mut result);
+        Ok(result)
+    }
+
+    async fn preview_file(&self, arguments: Value) -> Result<Value, McpError> {
+        let request = PreviewFileRequest::parse(arguments)?;
+        let max_lines = request.max_lines.unwrap_or(20).min(200);
+
+        let path = self.validate_path(&request.file_path)?;
+        let size = async_fs::metadata(&path)
+            .await
+            .map_err(|e| io_error("Failed to read file metadata", e))?
+            .len();
+
+        // Only the start of the file is read, however large it is
+        let mut head = Vec::new();
+        async_fs::File::open(&path)
+            .await
+            .map_err(|e| io_error("Failed to open file", e))?
+            .take(PREVIEW_READ_BYTES)
+            .read_to_end(&mut head)
+            .await
+            .map_err(|e| io_error("Failed to read file", e))?;
+        let complete = size <= PREVIEW_READ_BYTES;
+
+        let mime_type = detect_mime_type(&path, &head);
+        let content = if mime_type.starts_with("image/") {
+            let (width, height) = image_dimensions(mime_type, &head).ok_or_else(|| {
+                McpError::Internal(format!("Could not read {} dimensions", mime_type))
+            })?;
+            let thumbnail = (size <= THUMBNAIL_MAX_BYTES).then(|| {
+                format!(
+                    "data:{};base64,{}",
+                    mime_type,
+                    base64::engine::general_purpose::STANDARD.encode(&head)
+                )
+            });
+            PreviewContent::Image {
+                format: mime_type.trim_start_matches("image/").to_string(),
+                width,
+                height,
+                thumbnail,
+            }
+        } else if mime_type.starts_with("text/") || mime_type == "application/json" {
+            let text = String::from_utf8_lossy(&head);
+            let mut lines = text.lines();
+            if mime_type == "text/csv" {
+                PreviewContent::Csv {
+                    headers: lines.next().map(parse_csv_line).unwrap_or_default(),
+                    sample_rows: lines.take(CSV_SAMPLE_ROWS).map(parse_csv_line).collect(),
+                }
+            } else {
+                let total_lines = complete.then(|| text.lines().count());
+                let preview: Vec<String> =
+                    lines.by_ref().take(max_lines).map(String::from).collect();
+                PreviewContent::Text {
+                    truncated: !complete || lines.next().is_some(),
+                    lines: preview,
+                    total_lines,
+                }
+            }
+        } else if size > self.config.max_file_size {
+            PreviewContent::Metadata
+        } else {
+            let header_hex = head.iter().take(32).map(|b| format!("{:02x}", b)).collect();
+            PreviewContent::Binary { header_hex }
+        };
+
+        let preview = FilePreview {
+            path: path.to_string_lossy().to_string(),
+            size,
+            mime_type: mime_type.to_string(),
+            content,
+        };
+        serde_json::to_value(preview)
+            .map_err(|e| McpError::Internal(format!("Failed to serialize preview: {}", e)))
     }
 
-    async fn write_file(&self, arguments: Value) -> Result<Value, String> {
+    async fn write_file(&self, arguments: Value) -> Result<Value, McpError> {
         if self.config.read_only_mode {
-            return Err("Server is in read-only mode".to_string());
+            return Err(McpError::PermissionDenied(
+                "Server is in read-only mode".to_string(),
+            ));
         }
 
-        let request: WriteFileRequest = serde_json::from_value(arguments)
-            .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+        let request = WriteFileRequest::parse(arguments)?;
 
-        self.validate_file_size(request.content.len() as u64)
-            .map_err(|e| e.to_string())?;
+        self.validate_file_size(request.content.len() as u64)?;
 
-        let path = self
-            .validate_path(&request.file_path)
-            .map_err(|e| e.to_string())?;
+        let path = self.validate_path(&request.file_path)?;
+
+        if async_fs::try_exists(&path).await.unwrap_or(false) {
+            self.confirm_overwrite(&path, request.overwrite).await?;
+        }
 
         // Create parent directories if requested
         if request.create_directories.unwrap_or(false) {
             if let Some(parent) = path.parent() {
                 async_fs::create_dir_all(parent)
                     .await
-                    .map_err(|e| format!("Failed to create directories: {}", e))?;
+                    .map_err(|e| io_error("Failed to create directories", e))?;
             }
         }
 
         async_fs::write(&path, &request.content)
             .await
-            .map_err(|e| format!("Failed to write file: {}", e))?;
+            .map_err(|e| io_error("Failed to write file", e))?;
 
         Ok(serde_json::json!({
             "success": true,
@@ -410,21 +1225,20 @@ impl FileOperationsServer {
         }))
     }
 
-    async fn delete_file(&self, arguments: Value) -> Result<Value, String> {
+    async fn delete_file(&self, arguments: Value) -> Result<Value, McpError> {
         if self.config.read_only_mode {
-            return Err("Server is in read-only mode".to_string());
+            return Err(McpError::PermissionDenied(
+                "Server is in read-only mode".to_string(),
+            ));
         }
 
-        let request: DeleteFileRequest = serde_json::from_value(arguments)
-            .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+        let request = DeleteFileRequest::parse(arguments)?;
 
-        let path = self
-            .validate_path(&request.file_path)
-            .map_err(|e| e.to_string())?;
+        let path = self.validate_path(&request.file_path)?;
 
         async_fs::remove_file(&path)
             .await
-            .map_err(|e| format!("Failed to delete file: {}", e))?;
+            .map_err(|e| io_error("Failed to delete file", e))?;
 
         Ok(serde_json::json!({
             "success": true,
@@ -433,73 +1247,646 @@ impl FileOperationsServer {
         }))
     }
 
-    async fn list_directory(&self, arguments: Value) -> Result<Value, String> {
-        if !self.config.enable_directory_listing {
-            return Err("Directory listing is disabled".to_string());
+    async fn copy_file(&self, arguments: Value) -> Result<Value, McpError> {
+        if self.config.read_only_mode {
+            return Err(McpError::PermissionDenied(
+                "Server is in read-only mode".to_string(),
+            ));
         }
 
-        let request: ListDirectoryRequest = serde_json::from_value(arguments)
-            .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+        let request = CopyFileRequest::parse(arguments)?;
 
-        let path = self
-            .validate_path(&request.directory_path)
-            .map_err(|e| e.to_string())?;
+        let (source, metadata) = self.validate_source_file(&request.source_path).await?;
+        self.validate_file_size(metadata.len())?;
+        let (destination, renamed) = self
+            .resolve_destination(
+                &source,
+                &request.destination_path,
+                request.overwrite,
+                request.rename_on_conflict.unwrap_or(false),
+            )
+            .await?;
 
-        let mut entries = async_fs::read_dir(&path)
+        let bytes_copied = copy_preserving_times(&source, &destination)
             .await
-            .map_err(|e| format!("Failed to read directory: {}", e))?;
+            .map_err(|e| io_error("Failed to copy file", e))?;
 
-        let mut files = Vec::new();
-        let include_hidden = request.include_hidden.unwrap_or(false);
-
-        while let Some(entry) = entries
-            .next_entry()
-            .await
-            .map_err(|e| format!("Failed to read directory entry: {}", e))?
-        {
-            let entry_path = entry.path();
-            let name = entry_path.file_name().unwrap_or_default().to_string_lossy();
+        Ok(serde_json::json!({
+            "success": true,
+            "source": source.to_string_lossy(),
+            "destination": destination.to_string_lossy(),
+            "bytes_copied": bytes_copied,
+            "renamed": renamed,
+            "message": "File copied successfully"
+        }))
+    }
 
-            // Skip hidden files unless requested
-            if !include_hidden && name.starts_with('.') {
-                continue;
-            }
+    async fn move_file(&self, arguments: Value) -> Result<Value, McpError> {
+        if self.config.read_only_mode {
+            return Err(McpError::PermissionDenied(
+                "Server is in read-only mode".to_string(),
+            ));
+        }
 
-            match self.create_file_info(&entry_path).await {
-                Ok(file_info) => files.push(file_info),
-                Err(_) => continue, // Skip files we can't read
+        let request = MoveFileRequest::parse(arguments)?;
+
+        let (source, _) = self.validate_source_file(&request.source_path).await?;
+        let (destination, renamed) = self
+            .resolve_destination(
+                &source,
+                &request.destination_path,
+                request.overwrite,
+                request.rename_on_conflict.unwrap_or(false),
+            )
+            .await?;
+
+        // A rename keeps the file's times; across file systems the file is
+        // copied with its times and the original removed
+        match async_fs::rename(&source, &destination).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                copy_preserving_times(&source, &destination)
+                    .await
+                    .map_err(|e| io_error("Failed to move file", e))?;
+                async_fs::remove_file(&source)
+                    .await
+                    .map_err(|e| io_error("Failed to remove moved file", e))?;
             }
+            Err(e) => return Err(io_error("Failed to move file", e)),
         }
 
-        let listing = DirectoryListing {
-            path: path.to_string_lossy().to_string(),
-            total_count: files.len(),
-            files,
-        };
+        Ok(serde_json::json!({
+            "success": true,
+            "source": source.to_string_lossy(),
+            "destination": destination.to_string_lossy(),
+            "renamed": renamed,
+            "message": "File moved successfully"
+        }))
+    }
 
-        serde_json::to_value(listing).map_err(|e| format!("Failed to serialize listing: {}", e))
+    // Validate the source of a copy or move, which must be an existing file
+    async fn validate_source_file(
+        &self,
+        path: &str,
+    ) -> Result<(PathBuf, std::fs::Metadata), McpError> {
+        let path = self.validate_path(path)?;
+        let metadata = async_fs::metadata(&path)
+            .await
+            .map_err(|e| io_error("Failed to read file metadata", e))?;
+        if !metadata.is_file() {
+            return Err(
+                FileOperationError::InvalidPath(format!("Not a file: {}", path.display())).into(),
+            );
+        }
+        Ok((path, metadata))
     }
 
-    async fn get_file_info(&self, arguments: Value) -> Result<Value, String> {
-        let request: ReadFileRequest = serde_json::from_value(arguments)
-            .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+    async fn create_directory(&self, arguments: Value) -> Result<Value, McpError> {
+        if self.config.read_only_mode {
+            return Err(McpError::PermissionDenied(
+                "Server is in read-only mode".to_string(),
+            ));
+        }
+
+        let request = CreateDirectoryRequest::parse(arguments)?;
 
-        let path = self
-            .validate_path(&request.file_path)
-            .map_err(|e| e.to_string())?;
+        let path =
+            self.validate_new_directory(&request.directory_path, request.parents.unwrap_or(false))?;
+        if path.exists() && !path.is_dir() {
+            return Err(McpError::Conflict(format!(
+                "A file already exists at {}",
+                path.display()
+            )));
+        }
 
-        let file_info = self
-            .create_file_info(&path)
+        let created = !path.exists();
+        async_fs::create_dir_all(&path)
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| io_error("Failed to create directory", e))?;
 
-        serde_json::to_value(file_info).map_err(|e| format!("Failed to serialize file info: {}", e))
+        Ok(serde_json::json!({
+            "success": true,
+            "path": path.to_string_lossy(),
+            "created": created,
+            "message": if created { "Directory created successfully" } else { "Directory already exists" }
+        }))
+    }
+
+    async fn list_directory(&self, arguments: Value) -> Result<Value, McpError> {
+        if !self.config.enable_directory_listing {
+            return Err(McpError::PermissionDenied(
+                "Directory listing is disabled".to_string(),
+            ));
+        }
+
+        let request = ListDirectoryRequest::parse(arguments)?;
+
+        let path = self.validate_path(&request.directory_path)?;
+
+        let recursive = request.recursive.unwrap_or(false);
+        let max_depth = if recursive {
+            request.max_depth.unwrap_or(MAX_LISTING_DEPTH)
+        } else {
+            1
+        };
+        if !(1..=MAX_LISTING_DEPTH).contains(&max_depth) {
+            return Err(McpError::InvalidParams(format!(
+                "max_depth must be between 1 and {}",
+                MAX_LISTING_DEPTH
+            )));
+        }
+        let glob = request
+            .glob
+            .as_deref()
+            .map(glob::Pattern::new)
+            .transpose()
+            .map_err(|e| McpError::InvalidParams(format!("Invalid glob: {}", e)))?;
+        let mut walk = ListingWalk {
+            include_hidden: request.include_hidden.unwrap_or(false),
+            recursive,
+            max_depth,
+            glob,
+            // * stays within one directory, ** crosses them
+            match_options: glob::MatchOptions {
+                case_sensitive: !path_policy::is_case_insensitive(&path),
+                require_literal_separator: true,
+                require_literal_leading_dot: false,
+            },
+            visited: 0,
+            truncated: false,
+        };
+
+        let entries = async_fs::read_dir(&path)
+            .await
+            .map_err(|e| io_error("Failed to read directory", e))?;
+        let tree = self.list_entries(entries, "", 1, &mut walk).await?;
+
+        let files = match request.layout.unwrap_or_default() {
+            ListingLayout::Tree => tree,
+            ListingLayout::Flat => {
+                // Filtered listings are of the matching files alone
+                let mut files = Vec::new();
+                flatten_listing(tree, walk.glob.is_none(), &mut files);
+                files
+            }
+        };
+        let (file_count, directory_count, total_size) = tally_listing(&files);
+
+        let listing = DirectoryListing {
+            path: path.to_string_lossy().to_string(),
+            total_count: file_count + directory_count,
+            file_count,
+            directory_count,
+            total_size,
+            truncated: walk.truncated,
+            files,
+        };
+
+        serde_json::to_value(listing)
+            .map_err(|e| McpError::Internal(format!("Failed to serialize listing: {}", e)))
+    }
+
+    // The entries of one directory of a listing, `depth` levels below the
+    // listed one, with those of its subdirectories when recursive.
+    // Subdirectories that cannot be read are listed without their entries.
+    fn list_entries<'a>(
+        &'a self,
+        mut entries: async_fs::ReadDir,
+        relative: &'a str,
+        depth: usize,
+        walk: &'a mut ListingWalk,
+    ) -> BoxFuture<'a, Result<Vec<FileInfo>, McpError>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|e| io_error("Failed to read directory entry", e))?
+            {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // Skip hidden files unless requested
+                if !walk.include_hidden && name.starts_with('.') {
+                    continue;
+                }
+                // Not followed when it is a symbolic link
+                let is_directory = entry.file_type().await.is_ok_and(|t| t.is_dir());
+                found.push((name, entry.path(), is_directory));
+            }
+            found.sort_by(|a, b| a.0.cmp(&b.0));
+
+            let mut listed = Vec::new();
+            for (name, entry_path, is_directory) in found {
+                if walk.visited == MAX_LISTING_ENTRIES {
+                    walk.truncated = true;
+                    break;
+                }
+                walk.visited += 1;
+
+                let Ok(mut info) = self.create_file_info(&entry_path).await else {
+                    continue; // Skip files we can't read
+                };
+                let relative_path = if relative.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", relative, name)
+                };
+                if !is_directory {
+                    if walk.keeps_file(&relative_path) {
+                        listed.push(info);
+                    }
+                    continue;
+                }
+                if !walk.recursive {
+                    if walk.glob.is_none() {
+                        listed.push(info);
+                    }
+                    continue;
+                }
+
+                let children = if depth < walk.max_depth {
+                    match async_fs::read_dir(&entry_path).await {
+                        Ok(entries) => {
+                            self.list_entries(entries, &relative_path, depth + 1, walk)
+                                .await?
+                        }
+                        Err(_) => Vec::new(),
+                    }
+                } else {
+                    Vec::new()
+                };
+                // With a glob, directories are kept only around matching files
+                if walk.glob.is_some() && children.is_empty() {
+                    continue;
+                }
+                info.total_size = Some(tally_listing(&children).2);
+                info.children = Some(children);
+                listed.push(info);
+            }
+            Ok(listed)
+        })
+    }
+
+    async fn get_file_info(&self, arguments: Value) -> Result<Value, McpError> {
+        let request = FileInfoRequest::parse(arguments)?;
+
+        let path = self.validate_path(&request.file_path)?;
+
+        let file_info = self.create_file_info(&path).await?;
+
+        serde_json::to_value(file_info)
+            .map_err(|e| McpError::Internal(format!("Failed to serialize file info: {}", e)))
+    }
+
+    async fn change_directory(&self, arguments: Value) -> Result<Value, McpError> {
+        let request = ChangeDirectoryRequest::parse(arguments)?;
+
+        let path = self.validate_path(&request.directory_path)?;
+        if !path.is_dir() {
+            return Err(FileOperationError::InvalidPath(format!(
+                "Not a directory: {}",
+                path.display()
+            ))
+            .into());
+        }
+        session::current().set(WORKING_DIRECTORY_KEY, path.clone())?;
+
+        Ok(serde_json::json!({
+            "working_directory": path.to_string_lossy()
+        }))
+    }
+
+    async fn watch_path(&self, arguments: Value) -> Result<Value, McpError> {
+        let request = WatchPathRequest::parse(arguments)?;
+
+        let path = self.validate_path(&request.path)?;
+        let watch_id = uuid::Uuid::new_v4().to_string();
+        let watched = self.start_watch(watch_id, path, request.recursive.unwrap_or(false))?;
+
+        serde_json::to_value(watched)
+            .map_err(|e| McpError::Internal(format!("Failed to serialize watch: {}", e)))
+    }
+
+    async fn unwatch(&self, arguments: Value) -> Result<Value, McpError> {
+        let request = UnwatchRequest::parse(arguments)?;
+
+        // Only the connection that started a watch can stop it
+        let removed =
+            session::current().remove(&format!("{}{}", WATCH_KEY_PREFIX, request.watch_id));
+        if !removed {
+            return Err(McpError::NotFound(format!(
+                "No such watch: {}",
+                request.watch_id
+            )));
+        }
+
+        Ok(serde_json::json!({
+            "success": true,
+            "watch_id": request.watch_id
+        }))
+    }
+
+    // Start watching a validated path for the current session, replacing its
+    // watch with the same id
+    fn start_watch(
+        &self,
+        watch_id: String,
+        path: PathBuf,
+        recursive: bool,
+    ) -> Result<WatchedPath, McpError> {
+        let session = session::current();
+        let key = format!("{}{}", WATCH_KEY_PREFIX, watch_id);
+        session.remove(&key);
+        let watching = session
+            .keys()
+            .iter()
+            .filter(|key| key.starts_with(WATCH_KEY_PREFIX))
+            .count();
+        if watching >= MAX_WATCHES_PER_SESSION {
+            return Err(McpError::RateLimited {
+                message: format!(
+                    "At most {} paths can be watched at once; unwatch one first",
+                    MAX_WATCHES_PER_SESSION
+                ),
+                retry_after: None,
+            });
+        }
+
+        let watches = self.watches.clone();
+        let allowed_extensions = self.config.allowed_extensions.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) => watches.announce(&event, &allowed_extensions),
+                Err(e) => tracing::warn!(error = %e, "File watcher failed"),
+            })
+            .map_err(|e| McpError::Internal(format!("Failed to start file watcher: {}", e)))?;
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(&path, mode).map_err(|e| {
+            McpError::Internal(format!("Failed to watch {}: {}", path.display(), e))
+        })?;
+
+        let watched = WatchedPath {
+            watch_id: watch_id.clone(),
+            path: path.to_string_lossy().to_string(),
+            uri: file_uri(&path),
+            recursive,
+        };
+        self.watches
+            .active
+            .lock()
+            .unwrap()
+            .insert(watch_id.clone(), watched.clone());
+        // Over the session's limits, the watch is dropped and forgotten again
+        session.set(
+            key,
+            FileWatch {
+                id: watch_id,
+                watches: self.watches.clone(),
+                _watcher: Mutex::new(watcher),
+            },
+        )?;
+
+        Ok(watched)
+    }
+}
+
+// Reads up to `length` bytes of a file from `offset` without loading the
+// rest of it, returning them with the file's size
+async fn read_range(path: &Path, offset: u64, length: usize) -> Result<(Vec<u8>, u64), McpError> {
+    let mut file = async_fs::File::open(path)
+        .await
+        .map_err(|e| io_error("Failed to open file", e))?;
+    let size = file
+        .metadata()
+        .await
+        .map_err(|e| io_error("Failed to read file metadata", e))?
+        .len();
+    if offset > size {
+        return Err(ResponseGuardError::OffsetOutOfRange {
+            offset: offset as usize,
+            len: size as usize,
+        }
+        .into());
+    }
+
+    file.seek(SeekFrom::Start(offset))
+        .await
+        .map_err(|e| io_error("Failed to read file", e))?;
+    let mut bytes = Vec::with_capacity(length.min((size - offset) as usize));
+    file.take(length as u64)
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(|e| io_error("Failed to read file", e))?;
+    Ok((bytes, size))
+}
+
+// Copy a file and give the copy the original's access and modification times
+async fn copy_preserving_times(source: &Path, destination: &Path) -> std::io::Result<u64> {
+    let (source, destination) = (source.to_path_buf(), destination.to_path_buf());
+    tokio::task::spawn_blocking(move || {
+        let metadata = std::fs::metadata(&source)?;
+        let bytes = std::fs::copy(&source, &destination)?;
+        let mut times = std::fs::FileTimes::new().set_modified(metadata.modified()?);
+        if let Ok(accessed) = metadata.accessed() {
+            times = times.set_accessed(accessed);
+        }
+        std::fs::File::options()
+            .write(true)
+            .open(&destination)?
+            .set_times(times)?;
+        Ok(bytes)
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+// The first "name (n).ext" next to a file that does not exist yet
+fn free_name(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..=MAX_CONFLICT_RENAMES)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+}
+
+// Moves the entries of a tree listing into one list, each directory before
+// its contents
+fn flatten_listing(entries: Vec<FileInfo>, keep_directories: bool, files: &mut Vec<FileInfo>) {
+    for mut info in entries {
+        let children = info.children.take();
+        if keep_directories || info.file_type != "directory" {
+            files.push(info);
+        }
+        if let Some(children) = children {
+            flatten_listing(children, keep_directories, files);
+        }
+    }
+}
+
+// The files, directories and bytes in files of a listing, at every level
+fn tally_listing(entries: &[FileInfo]) -> (usize, usize, u64) {
+    entries
+        .iter()
+        .fold((0, 0, 0), |(files, directories, size), info| {
+            let (nested_files, nested_directories, nested_size) =
+                tally_listing(info.children.as_deref().unwrap_or_default());
+            if info.file_type == "directory" {
+                (
+                    files + nested_files,
+                    directories + nested_directories + 1,
+                    size + nested_size,
+                )
+            } else {
+                (files + 1, directories, size + info.size)
+            }
+        })
+}
+
+// Relative paths start from the session's working directory, once one is set
+fn resolve_in_session(path: &Path) -> PathBuf {
+    match session::current().get::<PathBuf>(WORKING_DIRECTORY_KEY) {
+        Some(working_directory) if path.is_relative() => working_directory.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+// Served over stdio with --stdio, and to clients that work with several servers
+impl ToolServer for FileOperationsServer {
+    fn server_name(&self) -> &str {
+        "file_operations"
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.list_tools()
+    }
+
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(self.call_tool(name, arguments))
+    }
+}
+
+// Watched paths are resources: a client subscribing to the file:// URI of a
+// file or directory in an allowed directory watches it, and watch_path
+// announces changes the same way
+impl ResourceProvider for FileOperationsServer {
+    fn list_resources(&self) -> BoxFuture<'_, Result<Vec<Resource>, McpError>> {
+        Box::pin(async move {
+            let active = self.watches.active.lock().unwrap();
+            Ok(active
+                .values()
+                .map(|watched| Resource {
+                    uri: watched.uri.clone(),
+                    name: Some(watched.path.clone()),
+                    description: Some(format!("Watched by {}", watched.watch_id)),
+                    mime_type: None,
+                })
+                .collect())
+        })
+    }
+
+    fn read_resource<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(async move {
+            let path = uri
+                .strip_prefix("file://")
+                .ok_or_else(|| McpError::NotFound(format!("Unknown resource: {}", uri)))?;
+            let path = self.validate_path(path)?;
+
+            let content = async_fs::read_to_string(&path)
+                .await
+                .map_err(|e| io_error("Failed to read file", e))?;
+            self.validate_file_size(content.len() as u64)?;
+
+            Ok(serde_json::json!({ "contents": [{
+                "uri": uri,
+                "mimeType": detect_mime_type(&path, content.as_bytes()),
+                "text": content
+            }] }))
+        })
+    }
+
+    fn subscribe(&self, uri: &str) -> Result<(), McpError> {
+        let path = uri
+            .strip_prefix("file://")
+            .ok_or_else(|| McpError::NotFound(format!("Unknown resource: {}", uri)))?;
+        let path = self.validate_path(path)?;
+        self.start_watch(uri.to_string(), path, false).map(|_| ())
+    }
+
+    fn unsubscribe(&self, uri: &str) -> Result<(), McpError> {
+        session::current().remove(&format!("{}{}", WATCH_KEY_PREFIX, uri));
+        Ok(())
+    }
+
+    fn updates(&self) -> broadcast::Receiver<ResourceUpdate> {
+        self.watches.updates.subscribe()
+    }
+}
+
+// Reported by the self_diagnostics tool
+impl DiagnosticsProvider for FileOperationsServer {
+    fn config_summary(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap_or(Value::Null)
+    }
+
+    fn feature_flags(&self) -> BTreeMap<String, bool> {
+        BTreeMap::from([
+            ("read_only_mode".to_string(), self.config.read_only_mode),
+            (
+                "directory_listing".to_string(),
+                self.config.enable_directory_listing,
+            ),
+            (
+                "overwrite_confirmation".to_string(),
+                self.elicitation.is_some(),
+            ),
+        ])
+    }
+
+    // Every allowed directory must exist, and be writable unless the server
+    // is read-only
+    fn check_dependencies(&self) -> BoxFuture<'_, Vec<DependencyCheck>> {
+        Box::pin(async move {
+            let mut checks = Vec::new();
+            for directory in &self.config.allowed_directories {
+                let check = async {
+                    let metadata = async_fs::metadata(directory)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    if !metadata.is_dir() {
+                        return Err("not a directory".to_string());
+                    }
+                    if metadata.permissions().readonly() && !self.config.read_only_mode {
+                        return Err("directory is read-only".to_string());
+                    }
+                    Ok("directory is accessible".to_string())
+                };
+                checks.push(
+                    DependencyCheck::run(
+                        "allowed_directory",
+                        directory.display().to_string(),
+                        check,
+                    )
+                    .await,
+                );
+            }
+            checks
+        })
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
+    let _telemetry = mcp_rust_examples::logging::init("error");
 
     eprintln!("📁 Starting File Operations MCP Server");
     eprintln!("=====================================");
@@ -510,11 +1897,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("⚙️  Security Configuration:");
     eprintln!("   Read-only mode: {}", config.read_only_mode);
     eprintln!("   Max file size: {} bytes", config.max_file_size);
+    eprintln!("   Max response size: {} bytes", config.max_response_bytes);
     eprintln!("   Allowed extensions: {:?}", config.allowed_extensions);
     eprintln!("   Allowed directories: {:?}", config.allowed_directories);
+    eprintln!("   Path policy: {:?}", config.path_policy);
+
+    // With --stdio, serve the tools to an MCP client instead of running the
+    // demo. Overwrites the caller did not decide on are confirmed by asking
+    // the client with elicitation/create. Changes to watched paths are sent
+    // as notifications/resources/updated.
+    if transport::stdio_requested() {
+        let server = Arc::new(FileOperationsServer::new(config).with_elicitation(
+            Elicitation::new(
+                Arc::new(TransportElicitor),
+                std::time::Duration::from_secs(30),
+            ),
+        ));
+        StdioTransport::new()
+            .serve(
+                &McpServer::new(server.clone())
+                    .with_resources(server.clone())
+                    .with_diagnostics(server),
+            )
+            .await?;
+        return Ok(());
+    }
 
-    // Create server
-    let server = FileOperationsServer::new(config);
+    // Create server. Overwrites are confirmed through elicitation; in this
+    // demo a background task stands in for the user and always says yes.
+    let (elicitor, mut prompts) = ChannelElicitor::channel();
+    tokio::spawn(async move {
+        while let Some(prompt) = prompts.recv().await {
+            eprintln!("  💬 Client asked: {}", prompt.request.message);
+            prompt.respond(ElicitationResponse::accept(
+                serde_json::json!({ "overwrite": true }),
+            ));
+        }
+    });
+    let server = FileOperationsServer::new(config).with_elicitation(Elicitation::new(
+        Arc::new(elicitor),
+        std::time::Duration::from_secs(30),
+    ));
 
     // Ensure demo directories exist
     for dir in ["./temp", "./data", "./examples"] {
@@ -557,6 +1980,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Err(e) => eprintln!("  ❌ Read failed: {}", e),
     }
 
+    // Test chunked reads of a log too large for one response
+    eprintln!("\n📜 Reading a large log in chunks:");
+    let log_lines: String = (1..=5000)
+        .map(|line| format!("2024-01-01T00:00:00Z INFO request {} served\n", line))
+        .collect();
+    let _ = async_fs::write("./temp/large.log", &log_lines).await;
+    let mut pages = 0;
+    let mut bytes_read = 0;
+    let mut cursor = Some(0);
+    while let Some(offset) = cursor {
+        let chunk_args = serde_json::json!({
+            "file_path": "./temp/large.log",
+            "chunked": true,
+            "offset": offset
+        });
+        match server.call_tool("read_file", chunk_args).await {
+            Ok(result) => {
+                pages += 1;
+                bytes_read += result["content"].as_str().unwrap_or("").len();
+                cursor = result.get("next_cursor").and_then(Value::as_u64);
+            }
+            Err(e) => {
+                eprintln!("  ❌ Chunked read failed: {}", e);
+                break;
+            }
+        }
+    }
+    eprintln!("  ✅ Read {} bytes in {} pages", bytes_read, pages);
+
+    let last_line = log_lines.lines().last().map_or(0, |line| line.len() + 1);
+    let range_args = serde_json::json!({
+        "file_path": "./temp/large.log",
+        "offset": log_lines.len() - last_line,
+        "length": last_line
+    });
+    match server.call_tool("read_file_range", range_args).await {
+        Ok(result) => eprintln!(
+            "  ✅ Last line: {}",
+            result["content"].as_str().unwrap_or("").trim()
+        ),
+        Err(e) => eprintln!("  ❌ Range read failed: {}", e),
+    }
+
     // Test list directory
     if server.config.enable_directory_listing {
         eprintln!("\n📂 Listing temp directory:");
@@ -578,6 +2044,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             Err(e) => eprintln!("  ❌ List failed: {}", e),
         }
+
+        eprintln!("\n🌲 Text files anywhere under the temp directory:");
+        let list_args = serde_json::json!({
+            "directory_path": "./temp",
+            "recursive": true,
+            "glob": "**/*.txt"
+        });
+        match server.call_tool("list_directory", list_args).await {
+            Ok(result) => {
+                if let Ok(listing) = serde_json::from_value::<DirectoryListing>(result) {
+                    eprintln!(
+                        "  ✅ {} file(s), {} bytes in all",
+                        listing.file_count, listing.total_size
+                    );
+                }
+            }
+            Err(e) => eprintln!("  ❌ List failed: {}", e),
+        }
     }
 
     // Test get file info
@@ -598,13 +2082,113 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Err(e) => eprintln!("  ❌ Info failed: {}", e),
     }
 
+    // Test preview
+    eprintln!("\n🔎 Previewing config file:");
+    let preview_args = serde_json::json!({
+        "file_path": "./temp/config.json"
+    });
+
+    match server.call_tool("preview_file", preview_args).await {
+        Ok(result) => {
+            eprintln!(
+                "  ✅ {} ({}): {}",
+                result["mime_type"].as_str().unwrap_or("unknown"),
+                result["kind"].as_str().unwrap_or("unknown"),
+                result["lines"][0].as_str().unwrap_or("")
+            );
+        }
+        Err(e) => eprintln!("  ❌ Preview failed: {}", e),
+    }
+
+    // Test overwrite confirmation
+    eprintln!("\n✏️  Overwriting demo file:");
+    let write_args = serde_json::json!({
+        "file_path": "./temp/demo.txt",
+        "content": demo_content
+    });
+
+    match server.call_tool("write_file", write_args).await {
+        Ok(result) => eprintln!("  ✅ Wrote {} bytes", result["bytes_written"]),
+        Err(e) => eprintln!("  ❌ Write failed: {}", e),
+    }
+
+    // Test copy, move and directory creation with collision policies
+    eprintln!("\n🗂️  Archiving demo file:");
+    let mkdir_args =
+        serde_json::json!({ "directory_path": "./temp/archive/2024", "parents": true });
+    match server.call_tool("create_directory", mkdir_args).await {
+        Ok(result) => eprintln!(
+            "  ✅ {}: {}",
+            result["message"].as_str().unwrap_or(""),
+            result["path"].as_str().unwrap_or("")
+        ),
+        Err(e) => eprintln!("  ❌ Create directory failed: {}", e),
+    }
+    for _ in 0..2 {
+        let copy_args = serde_json::json!({
+            "source_path": "./temp/demo.txt",
+            "destination_path": "./temp/archive/2024",
+            "rename_on_conflict": true
+        });
+        match server.call_tool("copy_file", copy_args).await {
+            Ok(result) => eprintln!(
+                "  ✅ Copied to {}{}",
+                result["destination"].as_str().unwrap_or(""),
+                if result["renamed"] == true {
+                    " (renamed)"
+                } else {
+                    ""
+                }
+            ),
+            Err(e) => eprintln!("  ❌ Copy failed: {}", e),
+        }
+    }
+    let move_args = serde_json::json!({
+        "source_path": "./temp/archive/2024/demo.txt",
+        "destination_path": "./temp/archive/2024/demo-original.txt",
+        "overwrite": true
+    });
+    match server.call_tool("move_file", move_args).await {
+        Ok(result) => eprintln!(
+            "  ✅ Renamed to {}",
+            result["destination"].as_str().unwrap_or("")
+        ),
+        Err(e) => eprintln!("  ❌ Move failed: {}", e),
+    }
+
+    // Test watching: a change to the watched directory is announced
+    eprintln!("\n👀 Watching temp directory:");
+    let mut updates = server.updates();
+    match server
+        .call_tool("watch_path", serde_json::json!({ "path": "./temp" }))
+        .await
+    {
+        Ok(result) => {
+            eprintln!("  ✅ Watching {}", result["uri"].as_str().unwrap_or(""));
+            let _ = async_fs::write("./temp/watched.txt", "Changed while watched").await;
+            match tokio::time::timeout(std::time::Duration::from_secs(2), updates.recv()).await {
+                Ok(Ok(update)) => eprintln!("  🔔 Updated: {}", update.uri),
+                _ => eprintln!("  ⚠️  No change was announced"),
+            }
+            let unwatch_args = serde_json::json!({ "watch_id": result["watch_id"] });
+            let _ = server.call_tool("unwatch", unwatch_args).await;
+        }
+        Err(e) => eprintln!("  ❌ Watch failed: {}", e),
+    }
+
     eprintln!("\n🎉 File operations demo completed!");
     eprintln!("\n🔒 Security features demonstrated:");
     eprintln!("   ✅ Path validation and sanitization");
     eprintln!("   ✅ Directory traversal prevention");
     eprintln!("   ✅ File extension filtering");
     eprintln!("   ✅ File size limits");
+    eprintln!("   ✅ Paged reads for large files");
+    eprintln!("   ✅ Chunked and ranged reads of files over the size limit");
+    eprintln!("   ✅ Type-aware previews that read only the start of a file");
     eprintln!("   ✅ Read-only mode support");
+    eprintln!("   ✅ Overwrite confirmation through elicitation");
+    eprintln!("   ✅ Copies and moves checked at both ends, keeping modification times");
+    eprintln!("   ✅ Watches confined to allowed directories, stopped on disconnect");
 
     Ok(())
 }
@@ -612,6 +2196,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use base64::Engine;
+    use mcp_rust_examples::protocol::ToolAnnotations;
+    use mcp_rust_examples::resource_diff::ResourceChange;
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -622,9 +2209,11 @@ mod tests {
         let config = FileOperationsConfig {
             allowed_directories: vec![temp_path.clone()],
             max_file_size: 1024,
+            max_response_bytes: 512,
             allowed_extensions: vec![".txt".to_string()],
             read_only_mode: false,
             enable_directory_listing: true,
+            path_policy: PathPolicy::default(),
         };
 
         let server = FileOperationsServer::new(config);
@@ -635,6 +2224,79 @@ mod tests {
         assert!(tools.iter().any(|t| t.name == "read_file"));
         assert!(tools.iter().any(|t| t.name == "write_file"));
         assert!(tools.iter().any(|t| t.name == "list_directory"));
+
+        // Schemas and annotations come from the request structs
+        let write = tools.iter().find(|t| t.name == "write_file").unwrap();
+        assert_eq!(
+            write.input_schema["required"],
+            serde_json::json!(["file_path", "content"])
+        );
+        assert_eq!(
+            write.annotations,
+            Some(ToolAnnotations::destructive().idempotent())
+        );
+        let preview = tools.iter().find(|t| t.name == "preview_file").unwrap();
+        assert_eq!(
+            preview.input_schema["properties"]["max_lines"]["maximum"],
+            200
+        );
+    }
+
+    #[tokio::test]
+    async fn test_working_directory_is_kept_per_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let reports = temp_dir.path().join("reports");
+        std::fs::create_dir(&reports).unwrap();
+        std::fs::write(reports.join("q1.txt"), "first quarter").unwrap();
+        let config = FileOperationsConfig {
+            allowed_directories: vec![temp_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let server = FileOperationsServer::new(config);
+        let read_q1 =
+            || server.call_tool("read_file", serde_json::json!({ "file_path": "q1.txt" }));
+
+        session::scope(Arc::new(session::SessionStore::new()), async {
+            let args = serde_json::json!({ "directory_path": reports.to_string_lossy() });
+            let result = server.call_tool("change_directory", args).await.unwrap();
+            assert_eq!(
+                result["working_directory"],
+                reports.canonicalize().unwrap().to_string_lossy().as_ref()
+            );
+            assert!(read_q1().await.is_ok());
+
+            // Relative paths are still confined to the allowed directories
+            let args = serde_json::json!({ "directory_path": "../.." });
+            let result = server.call_tool("change_directory", args).await;
+            assert!(matches!(result, Err(McpError::PermissionDenied(_))));
+            let args = serde_json::json!({ "directory_path": "q1.txt" });
+            let result = server.call_tool("change_directory", args).await;
+            assert!(matches!(result, Err(McpError::InvalidParams(_))));
+            assert!(read_q1().await.is_ok());
+        })
+        .await;
+
+        // A new session starts without a working directory
+        session::scope(Arc::new(session::SessionStore::new()), async {
+            assert!(read_q1().await.is_err());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_checks_allowed_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing");
+        let config = FileOperationsConfig {
+            allowed_directories: vec![temp_dir.path().to_path_buf(), missing.clone()],
+            ..Default::default()
+        };
+
+        let checks = FileOperationsServer::new(config).check_dependencies().await;
+        assert_eq!(checks.len(), 2);
+        assert!(checks[0].reachable);
+        assert!(!checks[1].reachable);
+        assert_eq!(checks[1].target, missing.display().to_string());
     }
 
     #[tokio::test]
@@ -657,7 +2319,42 @@ mod tests {
         // Path outside allowed directory should fail
         let invalid_path = "/etc/passwd";
         let result = server.validate_path(invalid_path);
-        assert!(result.is_err());
+        assert!(matches!(
+            result.map_err(McpError::from),
+            Err(McpError::PermissionDenied(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_path_normalization() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = FileOperationsConfig {
+            allowed_directories: vec![temp_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let server = FileOperationsServer::new(config);
+        let dir = temp_dir.path().to_string_lossy();
+
+        // A decomposed name refers to the same file as the composed one
+        std::fs::write(temp_dir.path().join("caf\u{e9}.txt"), "test").unwrap();
+        let path = server
+            .validate_path(&format!("{}/cafe\u{301}.txt", dir))
+            .unwrap();
+        assert_eq!(path.file_name().unwrap(), "caf\u{e9}.txt");
+
+        for name in ["a\0.txt", "a\n.txt", "a\u{202E}txt.txt"] {
+            let result = server.validate_path(&format!("{}/{}", dir, name));
+            assert!(matches!(
+                result.map_err(McpError::from),
+                Err(McpError::InvalidParams(_))
+            ));
+        }
+
+        // Canonical paths are compared byte for byte, by whole components
+        let policy = PathPolicy::default();
+        assert!(policy.is_within(Path::new("/data/notes.txt"), Path::new("/data")));
+        assert!(!policy.is_within(Path::new("/Data/Notes.txt"), Path::new("/data")));
+        assert!(!policy.is_within(Path::new("/database/notes.txt"), Path::new("/data")));
     }
 
     #[tokio::test]
@@ -678,7 +2375,592 @@ mod tests {
         });
 
         let result = server.call_tool("write_file", write_args).await;
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("read-only"));
+        assert_eq!(
+            result,
+            Err(McpError::PermissionDenied(
+                "Server is in read-only mode".to_string()
+            ))
+        );
+        assert!(!server.list_tools().iter().any(|t| t.name == "copy_file"));
+    }
+
+    #[tokio::test]
+    async fn test_write_file_confirms_overwrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = FileOperationsConfig {
+            allowed_directories: vec![temp_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let (elicitor, mut prompts) = ChannelElicitor::channel();
+        let server = FileOperationsServer::new(config).with_elicitation(Elicitation::new(
+            Arc::new(elicitor),
+            std::time::Duration::from_secs(5),
+        ));
+
+        let file_path = temp_dir.path().join("notes.txt");
+        std::fs::write(&file_path, "original").unwrap();
+        let write = |content: &str, overwrite: Option<bool>| {
+            let mut args = serde_json::json!({
+                "file_path": file_path.to_string_lossy(),
+                "content": content
+            });
+            if let Some(overwrite) = overwrite {
+                args["overwrite"] = overwrite.into();
+            }
+            server.call_tool("write_file", args)
+        };
+
+        // The user declines, then accepts
+        let responder = tokio::spawn(async move {
+            let prompt = prompts.recv().await.unwrap();
+            assert!(prompt.request.message.contains("already exists"));
+            prompt.respond(ElicitationResponse::decline());
+
+            let prompt = prompts.recv().await.unwrap();
+            prompt.respond(ElicitationResponse::accept(
+                serde_json::json!({ "overwrite": true }),
+            ));
+            prompts
+        });
+
+        let result = write("declined", None).await;
+        assert!(
+            matches!(result, Err(McpError::Conflict(message)) if message.ends_with("(overwrite declined)"))
+        );
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "original");
+
+        write("accepted", None).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "accepted");
+
+        // An explicit choice skips the prompt
+        let mut prompts = responder.await.unwrap();
+        assert!(write("refused", Some(false)).await.is_err());
+        write("forced", Some(true)).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "forced");
+        assert!(prompts.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recursive_listing_with_glob() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("project");
+        for (path, content) in [
+            ("a.txt", "hello"),
+            (".hidden.txt", "secret"),
+            ("src/main.rs", "fn main() {}"),
+            ("src/lib.rs", "//!"),
+            ("src/nested/deep.rs", "mod x;"),
+            ("src/nested/notes.txt", "notes"),
+            ("docs/readme.md", "# docs"),
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        std::fs::create_dir(root.join("empty")).unwrap();
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir(&outside).unwrap();
+        std::fs::write(outside.join("secret.rs"), "leaked").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+        let config = FileOperationsConfig {
+            allowed_directories: vec![root.clone()],
+            ..Default::default()
+        };
+        let server = &FileOperationsServer::new(config);
+        let list = |extra: Value| {
+            let mut args = serde_json::json!({ "directory_path": root.to_string_lossy() });
+            args.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            async move {
+                let result = server.call_tool("list_directory", args).await?;
+                Ok::<_, McpError>(serde_json::from_value::<DirectoryListing>(result).unwrap())
+            }
+        };
+        let names = |files: &[FileInfo]| files.iter().map(|f| f.name.clone()).collect::<Vec<_>>();
+
+        // Without recursive, only the directory itself is listed
+        let listing = list(serde_json::json!({})).await.unwrap();
+        assert!(names(&listing.files).starts_with(&["a.txt".into(), "docs".into()]));
+        assert!(listing.files.iter().all(|f| f.children.is_none()));
+
+        let listing = list(serde_json::json!({ "recursive": true, "glob": "**/*.rs" }))
+            .await
+            .unwrap();
+        assert_eq!(names(&listing.files), ["lib.rs", "main.rs", "deep.rs"]);
+        assert_eq!(
+            (
+                listing.file_count,
+                listing.directory_count,
+                listing.total_size
+            ),
+            (3, 0, 21)
+        );
+        assert!(!listing.truncated);
+
+        // * does not cross directories
+        let listing = list(serde_json::json!({ "recursive": true, "glob": "*.rs" }))
+            .await
+            .unwrap();
+        assert_eq!(listing.total_count, 0);
+
+        // A tree keeps the directories leading to the matches, with their sizes
+        let listing = list(serde_json::json!({
+            "recursive": true,
+            "glob": "src/**/*.rs",
+            "layout": "tree"
+        }))
+        .await
+        .unwrap();
+        assert_eq!(names(&listing.files), ["src"]);
+        let src = &listing.files[0];
+        assert_eq!(src.total_size, Some(21));
+        let children = src.children.as_deref().unwrap();
+        assert_eq!(names(children), ["lib.rs", "main.rs", "nested"]);
+        assert_eq!(names(children[2].children.as_deref().unwrap()), ["deep.rs"]);
+        assert_eq!((listing.file_count, listing.directory_count), (3, 2));
+
+        // Everything, to a depth, with hidden files on request
+        let listing = list(serde_json::json!({
+            "recursive": true,
+            "max_depth": 2,
+            "include_hidden": true
+        }))
+        .await
+        .unwrap();
+        let listed = names(&listing.files);
+        assert!(listed.contains(&".hidden.txt".to_string()));
+        assert!(listed.contains(&"nested".to_string()));
+        assert!(!listed.contains(&"deep.rs".to_string()));
+        // Symbolic links are not followed out of the directory
+        assert!(!listed.contains(&"secret.rs".to_string()));
+        let empty = listing.files.iter().find(|f| f.name == "empty").unwrap();
+        assert_eq!(empty.total_size, Some(0));
+        assert_eq!(listing.total_size, 5 + 6 + 6 + 12 + 3);
+
+        for extra in [
+            serde_json::json!({ "glob": "[" }),
+            serde_json::json!({ "recursive": true, "max_depth": 0 }),
+            serde_json::json!({ "recursive": true, "max_depth": 17 }),
+        ] {
+            let result = list(extra).await;
+            assert!(matches!(result, Err(McpError::InvalidParams(_))));
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dangling_symlinks_are_not_written_through() {
+        let temp_dir = TempDir::new().unwrap();
+        let outside_dir = TempDir::new().unwrap();
+        let target = outside_dir.path().join("planted.txt");
+        let link = temp_dir.path().join("link.txt");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), "notes").unwrap();
+
+        let config = FileOperationsConfig {
+            allowed_directories: vec![temp_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let server = FileOperationsServer::new(config);
+        let args = serde_json::json!({ "file_path": link, "content": "escaped" });
+        let result = server.call_tool("write_file", args).await;
+        assert!(matches!(result, Err(McpError::InvalidParams(_))));
+        let args = serde_json::json!({
+            "source_path": temp_dir.path().join("notes.txt"),
+            "destination_path": link
+        });
+        let result = server.call_tool("copy_file", args).await;
+        assert!(matches!(result, Err(McpError::InvalidParams(_))));
+        assert!(!target.exists());
+    }
+
+    #[tokio::test]
+    async fn test_read_file_is_paged() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = FileOperationsConfig {
+            allowed_directories: vec![temp_dir.path().to_path_buf()],
+            max_response_bytes: 10,
+            ..Default::default()
+        };
+        let server = FileOperationsServer::new(config);
+
+        let file_path = temp_dir.path().join("large.txt");
+        let content = "0123456789abcdefghijklmnopqrstuvwxyz";
+        std::fs::write(&file_path, content).unwrap();
+
+        // Follow next_cursor until the whole file has been read
+        let mut pages = Vec::new();
+        let mut offset = 0;
+        loop {
+            let args = serde_json::json!({
+                "file_path": file_path.to_string_lossy(),
+                "offset": offset
+            });
+            let result = server.call_tool("read_file", args).await.unwrap();
+            assert_eq!(result["total_size"], content.len());
+            pages.push(result["content"].as_str().unwrap().to_string());
+
+            match result.get("next_cursor") {
+                Some(cursor) => {
+                    assert_eq!(result["truncated"], true);
+                    offset = cursor.as_u64().unwrap();
+                }
+                None => {
+                    assert_eq!(result["truncated"], false);
+                    break;
+                }
+            }
+        }
+
+        assert_eq!(pages.len(), 4);
+        assert_eq!(pages.concat(), content);
+    }
+
+    #[tokio::test]
+    async fn test_preview_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = FileOperationsConfig {
+            allowed_directories: vec![temp_dir.path().to_path_buf()],
+            allowed_extensions: vec![".txt".to_string(), ".csv".to_string(), ".png".to_string()],
+            max_file_size: 1024,
+            ..Default::default()
+        };
+        let server = FileOperationsServer::new(config);
+
+        let preview = |name: &str| {
+            serde_json::json!({
+                "file_path": temp_dir.path().join(name).to_string_lossy(),
+                "max_lines": 2
+            })
+        };
+
+        std::fs::write(temp_dir.path().join("notes.txt"), "one\ntwo\nthree\n").unwrap();
+        let result = server
+            .call_tool("preview_file", preview("notes.txt"))
+            .await
+            .unwrap();
+        assert_eq!(result["kind"], "text");
+        assert_eq!(result["lines"], serde_json::json!(["one", "two"]));
+        assert_eq!(result["total_lines"], 3);
+        assert_eq!(result["truncated"], true);
+
+        std::fs::write(
+            temp_dir.path().join("sales.csv"),
+            "region,\"total, USD\"\nnorth,100\nsouth,200\n",
+        )
+        .unwrap();
+        let result = server
+            .call_tool("preview_file", preview("sales.csv"))
+            .await
+            .unwrap();
+        assert_eq!(result["kind"], "csv");
+        assert_eq!(
+            result["headers"],
+            serde_json::json!(["region", "total, USD"])
+        );
+        assert_eq!(
+            result["sample_rows"][1],
+            serde_json::json!(["south", "200"])
+        );
+
+        // PNG signature followed by an IHDR chunk for a 640x480 image
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0]);
+        std::fs::write(temp_dir.path().join("chart.png"), &png).unwrap();
+        let result = server
+            .call_tool("preview_file", preview("chart.png"))
+            .await
+            .unwrap();
+        assert_eq!(result["kind"], "image");
+        assert_eq!(result["mime_type"], "image/png");
+        assert_eq!(
+            (result["width"].clone(), result["height"].clone()),
+            (640.into(), 480.into())
+        );
+        assert!(result["thumbnail"]
+            .as_str()
+            .unwrap()
+            .starts_with("data:image/png;base64,"));
+
+        // Binaries over the size cap are described, not read
+        std::fs::write(temp_dir.path().join("blob.txt"), vec![0u8; 2048]).unwrap();
+        let result = server
+            .call_tool("preview_file", preview("blob.txt"))
+            .await
+            .unwrap();
+        assert_eq!(result["kind"], "metadata");
+        assert_eq!(result["size"], 2048);
+    }
+
+    // Collect updates until one is accepted, failing if none comes in time
+    async fn updates_until(
+        updates: &mut broadcast::Receiver<ResourceUpdate>,
+        accept: impl Fn(&ResourceUpdate) -> bool,
+    ) -> Vec<ResourceUpdate> {
+        let mut seen = Vec::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                let update = updates.recv().await.unwrap();
+                let accepted = accept(&update);
+                seen.push(update);
+                if accepted {
+                    break;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        seen
+    }
+
+    #[tokio::test]
+    async fn test_watch_path_announces_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let directory = temp_dir.path().canonicalize().unwrap();
+        let config = FileOperationsConfig {
+            allowed_directories: vec![directory.clone()],
+            ..Default::default()
+        };
+        let server = FileOperationsServer::new(config);
+        let mut updates = server.updates();
+        let notes = directory.join("notes.txt");
+        let notes_uri = file_uri(&notes);
+
+        let session = Arc::new(session::SessionStore::new());
+        session::scope(session.clone(), async {
+            let args = serde_json::json!({ "path": directory.to_string_lossy() });
+            let watched = server.call_tool("watch_path", args).await.unwrap();
+            assert_eq!(watched["uri"], file_uri(&directory));
+            assert_eq!(watched["recursive"], false);
+
+            // Creating a file is announced without a change
+            std::fs::write(&notes, "first").unwrap();
+            let seen = updates_until(&mut updates, |update| update.uri == notes_uri).await;
+            assert_eq!(seen.last().unwrap().change, None);
+
+            // Deleting it is, with files the server does not serve left out
+            std::fs::write(directory.join("ignored.bin"), [0u8]).unwrap();
+            std::fs::remove_file(&notes).unwrap();
+            let seen = updates_until(&mut updates, |update| {
+                update.uri == notes_uri && update.change == Some(ResourceChange::Deleted)
+            })
+            .await;
+            assert!(seen.iter().all(|update| !update.uri.ends_with(".bin")));
+
+            // Watches are resources, and subscribing to a file watches it too
+            std::fs::write(&notes, "second").unwrap();
+            server.subscribe(&notes_uri).unwrap();
+            let resources = server.list_resources().await.unwrap();
+            assert_eq!(resources.len(), 2);
+            let read = server.read_resource(&notes_uri).await.unwrap();
+            assert_eq!(read["contents"][0]["text"], "second");
+            server.unsubscribe(&notes_uri).unwrap();
+
+            // Only allowed directories can be watched
+            let args = serde_json::json!({ "path": "/" });
+            let result = server.call_tool("watch_path", args).await;
+            assert!(matches!(result, Err(McpError::PermissionDenied(_))));
+
+            let args = serde_json::json!({ "watch_id": watched["watch_id"] });
+            server.call_tool("unwatch", args.clone()).await.unwrap();
+            assert!(server.list_resources().await.unwrap().is_empty());
+            let result = server.call_tool("unwatch", args).await;
+            assert!(matches!(result, Err(McpError::NotFound(_))));
+
+            // Another connection cannot stop this session's watches
+            let args = serde_json::json!({ "path": directory.to_string_lossy() });
+            let watched = server.call_tool("watch_path", args).await.unwrap();
+            session::scope(Arc::new(session::SessionStore::new()), async {
+                let args = serde_json::json!({ "watch_id": watched["watch_id"] });
+                assert!(server.call_tool("unwatch", args).await.is_err());
+            })
+            .await;
+        })
+        .await;
+
+        // The watches of a session stop when it ends
+        assert_eq!(server.list_resources().await.unwrap().len(), 1);
+        drop(session);
+        assert!(server.list_resources().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_large_files_are_read_in_chunks_and_ranges() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = FileOperationsConfig {
+            allowed_directories: vec![temp_dir.path().to_path_buf()],
+            max_file_size: 64,
+            max_response_bytes: 16,
+            ..Default::default()
+        };
+        let server = FileOperationsServer::new(config);
+
+        // Over max_file_size, with characters of several bytes across pages
+        let log = temp_dir.path().join("app.log");
+        let content = "héllo wörld, ünïcode línes\n".repeat(8);
+        std::fs::write(&log, &content).unwrap();
+        let file_path = log.to_string_lossy().to_string();
+        let args = serde_json::json!({ "file_path": file_path });
+        assert!(server.call_tool("read_file", args).await.is_err());
+
+        // In chunked mode, pages follow next_cursor and never split a character
+        let mut pages = Vec::new();
+        let mut offset = 0;
+        loop {
+            let args = serde_json::json!({
+                "file_path": file_path,
+                "chunked": true,
+                "offset": offset
+            });
+            let result = server.call_tool("read_file", args).await.unwrap();
+            let page = result["content"].as_str().unwrap();
+            assert!(page.len() <= 16);
+            assert_eq!(result["total_size"], content.len());
+            pages.push(page.to_string());
+            match result.get("next_cursor") {
+                Some(cursor) => offset = cursor.as_u64().unwrap(),
+                None => break,
+            }
+        }
+        assert_eq!(pages.concat(), content);
+
+        // A cursor inside a character is refused
+        let args = serde_json::json!({ "file_path": file_path, "chunked": true, "offset": 2 });
+        let result = server.call_tool("read_file", args).await;
+        assert!(matches!(result, Err(McpError::InvalidParams(_))));
+
+        // Ranges are text, or base64 when they split a character
+        let args = serde_json::json!({ "file_path": file_path, "offset": 7, "length": 5 });
+        let result = server.call_tool("read_file_range", args).await.unwrap();
+        assert_eq!(result["content"], "wörl");
+        assert_eq!(result["encoding"], "utf-8");
+        assert_eq!(result["next_cursor"], 12);
+        let args = serde_json::json!({ "file_path": file_path, "offset": 2, "length": 2 });
+        let result = server.call_tool("read_file_range", args).await.unwrap();
+        assert_eq!(result["encoding"], "base64");
+        assert_eq!(
+            result["content"],
+            base64::engine::general_purpose::STANDARD.encode(&content.as_bytes()[2..4])
+        );
+
+        // Lengths are capped at the response size, and the end is the end
+        let args = serde_json::json!({ "file_path": file_path, "offset": 7, "length": 1000 });
+        let result = server.call_tool("read_file_range", args).await.unwrap();
+        assert_eq!(result["length"], 16);
+        let args = serde_json::json!({ "file_path": file_path, "offset": content.len() });
+        let result = server.call_tool("read_file_range", args).await.unwrap();
+        assert_eq!(result["content"], "");
+        assert_eq!(result["truncated"], false);
+        let args = serde_json::json!({ "file_path": file_path, "offset": content.len() + 1 });
+        let result = server.call_tool("read_file_range", args).await;
+        assert!(matches!(result, Err(McpError::InvalidParams(_))));
+    }
+
+    #[tokio::test]
+    async fn test_copy_move_and_create_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let outside_dir = TempDir::new().unwrap();
+        let config = FileOperationsConfig {
+            allowed_directories: vec![temp_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let server = FileOperationsServer::new(config);
+        let path = |name: &str| temp_dir.path().join(name).to_string_lossy().to_string();
+
+        // Missing parents are only created when asked for
+        let args = serde_json::json!({ "directory_path": path("archive/2024") });
+        let result = server.call_tool("create_directory", args).await;
+        assert!(matches!(result, Err(McpError::InvalidParams(_))));
+        let args = serde_json::json!({ "directory_path": path("archive/2024"), "parents": true });
+        let result = server
+            .call_tool("create_directory", args.clone())
+            .await
+            .unwrap();
+        assert_eq!(result["created"], true);
+        let result = server.call_tool("create_directory", args).await.unwrap();
+        assert_eq!(result["created"], false);
+        let args = serde_json::json!({ "directory_path": outside_dir.path().join("escape") });
+        let result = server.call_tool("create_directory", args).await;
+        assert!(matches!(result, Err(McpError::PermissionDenied(_))));
+
+        // Copies keep the modification time and land inside a directory
+        let source = temp_dir.path().join("notes.txt");
+        std::fs::write(&source, "first draft").unwrap();
+        let modified =
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        std::fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let copy = |rename_on_conflict: bool, overwrite: Option<bool>| {
+            let mut args = serde_json::json!({
+                "source_path": path("notes.txt"),
+                "destination_path": path("archive/2024"),
+                "rename_on_conflict": rename_on_conflict
+            });
+            if let Some(overwrite) = overwrite {
+                args["overwrite"] = overwrite.into();
+            }
+            server.call_tool("copy_file", args)
+        };
+        let result = copy(false, None).await.unwrap();
+        assert_eq!(result["bytes_copied"], 11);
+        let copied = temp_dir.path().join("archive/2024/notes.txt");
+        assert_eq!(std::fs::read_to_string(&copied).unwrap(), "first draft");
+        assert_eq!(
+            std::fs::metadata(&copied).unwrap().modified().unwrap(),
+            modified
+        );
+
+        // Collisions are refused, renamed around or overwritten
+        assert!(matches!(
+            copy(false, Some(false)).await,
+            Err(McpError::Conflict(_))
+        ));
+        let result = copy(true, None).await.unwrap();
+        assert_eq!(result["renamed"], true);
+        assert!(result["destination"]
+            .as_str()
+            .unwrap()
+            .ends_with("notes (1).txt"));
+        assert!(matches!(
+            copy(true, Some(true)).await,
+            Err(McpError::InvalidParams(_))
+        ));
+        std::fs::write(&source, "second draft").unwrap();
+        copy(false, Some(true)).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&copied).unwrap(), "second draft");
+
+        // Moves rename in place, and both ends must be allowed
+        let args = serde_json::json!({
+            "source_path": path("notes.txt"),
+            "destination_path": path("renamed.txt")
+        });
+        let result = server.call_tool("move_file", args).await.unwrap();
+        assert_eq!(result["renamed"], false);
+        assert!(!source.exists());
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("renamed.txt")).unwrap(),
+            "second draft"
+        );
+        let args = serde_json::json!({
+            "source_path": path("renamed.txt"),
+            "destination_path": outside_dir.path().join("stolen.txt")
+        });
+        let result = server.call_tool("move_file", args).await;
+        assert!(matches!(result, Err(McpError::PermissionDenied(_))));
+        let args = serde_json::json!({
+            "source_path": path("renamed.txt"),
+            "destination_path": path("renamed.txt")
+        });
+        let result = server.call_tool("move_file", args).await;
+        assert!(matches!(result, Err(McpError::InvalidParams(_))));
     }
 }
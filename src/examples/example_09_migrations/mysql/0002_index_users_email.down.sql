@@ -0,0 +1 @@
+-- Nothing to do: the unique constraint on email already indexes it
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS operation_logs (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    operation VARCHAR(64) NOT NULL,
+    user_id BIGINT,
+    details TEXT,
+    timestamp VARCHAR(19) NOT NULL DEFAULT (DATE_FORMAT(UTC_TIMESTAMP(), '%Y-%m-%d %H:%i:%s'))
+);
@@ -0,0 +1,10 @@
+ALTER TABLE users ADD COLUMN deleted_at VARCHAR(19) NULL;
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    operation VARCHAR(64) NOT NULL,
+    before_json TEXT,
+    after_json TEXT,
+    changed_at VARCHAR(19) NOT NULL DEFAULT (DATE_FORMAT(UTC_TIMESTAMP(), '%Y-%m-%d %H:%i:%s')),
+    INDEX idx_audit_log_user_id (user_id)
+);
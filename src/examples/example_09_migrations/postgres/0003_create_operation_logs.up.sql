@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS operation_logs (
+    id BIGSERIAL PRIMARY KEY,
+    operation TEXT NOT NULL,
+    user_id BIGINT,
+    details TEXT,
+    timestamp TEXT NOT NULL DEFAULT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+);
@@ -0,0 +1 @@
+-- Nothing to do: only SQLite has a full-text index (FTS5); searches here match patterns
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS operation_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    operation TEXT NOT NULL,
+    user_id INTEGER,
+    details TEXT,
+    timestamp TEXT NOT NULL DEFAULT (datetime('now'))
+);
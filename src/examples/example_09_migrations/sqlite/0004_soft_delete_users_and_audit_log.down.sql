@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS audit_log;
+-- Needs SQLite 3.35
+ALTER TABLE users DROP COLUMN deleted_at;
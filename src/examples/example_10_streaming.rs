@@ -2,13 +2,37 @@
 //
 // This example demonstrates real-time streaming capabilities in an MCP server.
 // It shows how to handle live data feeds, async channels, and streaming responses
-// for real-time applications.
-
+// for real-time applications. The inject_test_scenario tool produces bursts,
+// gaps and malformed payloads so stream consumers can be tested for robustness.
+// Messages on critical topics are also kept for acknowledging consumers until
+// they confirm them, and redelivered if they do not within the ack timeout.
+// With MCP_CHECKPOINT_PATH set, those queues and the message counter are
+// checkpointed, so after a restart unacked messages are redelivered and
+// message ids are not reused.
+
+use futures::future::BoxFuture;
+use mcp_rust_examples::checkpoint::{CheckpointError, CheckpointStore};
+use mcp_rust_examples::diagnostics::DiagnosticsProvider;
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::keepalive::KeepaliveConfig;
+use mcp_rust_examples::logging::ToolCallLog;
+use mcp_rust_examples::protocol::{Tool, ToolAnnotations};
+use mcp_rust_examples::server::McpServer;
+use mcp_rust_examples::session::{self, SessionLimitExceeded};
+use mcp_rust_examples::tools::ToolServer;
+use mcp_rust_examples::transport::{self, StdioTransport};
+use mcp_rust_examples::websocket::{ReconnectPolicy, WebSocketClient, WebSocketTransport};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, Weak};
 use tokio::sync::broadcast;
+use tokio::task::AbortHandle;
 use tokio::time::{Duration, Instant};
 
 // Streaming configuration
@@ -19,6 +43,20 @@ pub struct StreamingConfig {
     pub heartbeat_interval_ms: u64,
     pub data_generation_interval_ms: u64,
     pub enable_metrics: bool,
+    // Message types delivered to acknowledging consumers until they ack them
+    #[serde(default = "default_critical_topics")]
+    pub critical_topics: Vec<String>,
+    // How long a delivered message waits for its ack before redelivery
+    #[serde(default = "default_ack_timeout_ms")]
+    pub ack_timeout_ms: u64,
+}
+
+fn default_critical_topics() -> Vec<String> {
+    vec!["custom".to_string(), "events".to_string()]
+}
+
+fn default_ack_timeout_ms() -> u64 {
+    30_000
 }
 
 impl Default for StreamingConfig {
@@ -29,6 +67,8 @@ impl Default for StreamingConfig {
             heartbeat_interval_ms: 5000,
             data_generation_interval_ms: 1000,
             enable_metrics: true,
+            critical_topics: default_critical_topics(),
+            ack_timeout_ms: default_ack_timeout_ms(),
         }
     }
 }
@@ -77,7 +117,54 @@ pub struct SendCustomMessageRequest {
     pub data: Option<Value>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubscribeWithAcksRequest {
+    pub consumer_id: String,
+    pub topics: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PollMessagesRequest {
+    pub consumer_id: String,
+    pub max_messages: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AckMessagesRequest {
+    pub consumer_id: String,
+    pub message_ids: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetPendingAcksRequest {
+    pub consumer_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InjectTestScenarioRequest {
+    pub burst_size: Option<u32>,
+    pub burst_count: Option<u32>,
+    pub gap_seconds: Option<f64>,
+    pub malformed_ratio: Option<f64>,
+    // Makes the choice of malformed messages reproducible
+    pub seed: Option<u64>,
+}
+
 // Response structures
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TestScenario {
+    pub scenario_id: String,
+    pub burst_size: u32,
+    pub burst_count: u32,
+    pub gap_seconds: f64,
+    pub malformed_ratio: f64,
+    pub seed: u64,
+    pub total_messages: u32,
+    // Positions within the scenario (0-based) whose payload is malformed
+    pub malformed_sequences: Vec<u32>,
+    pub estimated_duration_seconds: f64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct StreamStats {
     pub active_streams: u32,
@@ -87,36 +174,484 @@ pub struct StreamStats {
     pub uptime_seconds: u64,
 }
 
+// A message handed to an acknowledging consumer. The message id is the
+// exactly-once marker: a consumer that is sent an id again (redelivered is
+// true) should skip it if it already processed it before acking
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Delivery {
+    pub message: StreamMessage,
+    pub delivery_attempt: u32,
+    pub redelivered: bool,
+    pub ack_within_ms: u64,
+}
+
+// A delivered message the consumer has not acknowledged yet
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingAck {
+    pub message_id: u64,
+    pub message_type: String,
+    pub delivery_attempt: u32,
+    pub delivered_ms_ago: u64,
+    // 0 once the ack is overdue; the next poll redelivers the message
+    pub redelivery_in_ms: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Tool {
-    pub name: String,
-    pub description: String,
-    pub input_schema: Value,
+pub struct ConsumerAckStatus {
+    pub consumer_id: String,
+    pub topics: Vec<String>,
+    pub awaiting_ack: Vec<PendingAck>,
+    // Messages not delivered yet
+    pub queued: usize,
+    pub acked_total: u64,
+    pub redelivered_total: u64,
+}
+
+const MAX_POLL_MESSAGES: usize = 100;
+// What delivered messages awaiting their ack count as in a session's usage
+const PENDING_ACKS_KIND: &str = "pending_acks";
+
+// A consumer's queue as checkpointed: its topics and every message it has
+// not acked yet, with how often each was delivered
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConsumerCheckpoint {
+    pub topics: Vec<String>,
+    pub messages: Vec<CheckpointedMessage>,
+    pub acked_total: u64,
+    pub redelivered_total: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheckpointedMessage {
+    pub message: StreamMessage,
+    pub attempts: u32,
+}
+
+// A message kept for one consumer until it is acknowledged
+struct Unacked {
+    message: StreamMessage,
+    attempts: u32,
+    delivered_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct ConsumerQueue {
+    topics: BTreeSet<String>,
+    // Oldest first; delivered messages stay here until acked
+    messages: VecDeque<Unacked>,
+    acked_total: u64,
+    redelivered_total: u64,
+    // Changed since it was last checkpointed
+    dirty: bool,
+}
+
+impl ConsumerQueue {
+    fn checkpoint(&self) -> ConsumerCheckpoint {
+        ConsumerCheckpoint {
+            topics: self.topics.iter().cloned().collect(),
+            messages: self
+                .messages
+                .iter()
+                .map(|unacked| CheckpointedMessage {
+                    message: unacked.message.clone(),
+                    attempts: unacked.attempts,
+                })
+                .collect(),
+            acked_total: self.acked_total,
+            redelivered_total: self.redelivered_total,
+        }
+    }
+}
+
+// Per-consumer queues for critical topics. Every message published on a
+// topic a consumer subscribed to is kept until that consumer acks it, and
+// delivered messages are redelivered once their ack is overdue.
+pub struct AckTracker {
+    critical_topics: BTreeSet<String>,
+    ack_timeout: Duration,
+    consumers: Mutex<BTreeMap<String, ConsumerQueue>>,
+}
+
+impl AckTracker {
+    pub fn new(critical_topics: &[String], ack_timeout: Duration) -> Self {
+        Self {
+            critical_topics: critical_topics.iter().cloned().collect(),
+            ack_timeout,
+            consumers: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    // Registers a consumer, or changes its topics; messages it already holds
+    // stay queued. Only critical topics can be subscribed to.
+    pub fn subscribe(&self, consumer_id: &str, topics: &[String]) -> Result<(), String> {
+        if topics.is_empty() {
+            return Err("At least one topic is required".to_string());
+        }
+        if let Some(topic) = topics.iter().find(|t| !self.critical_topics.contains(*t)) {
+            return Err(format!(
+                "'{}' is not a critical topic; expected one of {:?}",
+                topic, self.critical_topics
+            ));
+        }
+
+        let mut consumers = self.consumers.lock().unwrap();
+        let queue = consumers.entry(consumer_id.to_string()).or_default();
+        queue.topics = topics.iter().cloned().collect();
+        queue.dirty = true;
+        Ok(())
+    }
+
+    // Queues `message` for every consumer of its topic and returns how many
+    // consumers will get it
+    pub fn enqueue(&self, message: &StreamMessage) -> usize {
+        if !self.critical_topics.contains(&message.message_type) {
+            return 0;
+        }
+
+        let mut consumers = self.consumers.lock().unwrap();
+        let mut queued = 0;
+        for queue in consumers.values_mut() {
+            if queue.topics.contains(&message.message_type) {
+                queue.messages.push_back(Unacked {
+                    message: message.clone(),
+                    attempts: 0,
+                    delivered_at: None,
+                });
+                queue.dirty = true;
+                queued += 1;
+            }
+        }
+        queued
+    }
+
+    // Delivers up to `max` messages that were never delivered or whose ack
+    // is overdue, oldest first. None if the consumer is unknown.
+    pub fn poll(&self, consumer_id: &str, max: usize) -> Option<Vec<Delivery>> {
+        let mut consumers = self.consumers.lock().unwrap();
+        let queue = consumers.get_mut(consumer_id)?;
+        let now = Instant::now();
+
+        let mut deliveries = Vec::new();
+        for unacked in queue.messages.iter_mut() {
+            if deliveries.len() >= max {
+                break;
+            }
+            let due = match unacked.delivered_at {
+                Some(delivered_at) => now.duration_since(delivered_at) >= self.ack_timeout,
+                None => true,
+            };
+            if !due {
+                continue;
+            }
+
+            let redelivered = unacked.attempts > 0;
+            if redelivered {
+                queue.redelivered_total += 1;
+            }
+            unacked.attempts += 1;
+            unacked.delivered_at = Some(now);
+            deliveries.push(Delivery {
+                message: unacked.message.clone(),
+                delivery_attempt: unacked.attempts,
+                redelivered,
+                ack_within_ms: self.ack_timeout.as_millis() as u64,
+            });
+        }
+        queue.dirty |= !deliveries.is_empty();
+        Some(deliveries)
+    }
+
+    // Drops acknowledged messages from the consumer's queue. Returns the ids
+    // that were acked and those that were not awaiting an ack (already acked,
+    // not delivered yet, or unknown), or None if the consumer is unknown.
+    pub fn ack(&self, consumer_id: &str, message_ids: &[u64]) -> Option<(Vec<u64>, Vec<u64>)> {
+        let mut consumers = self.consumers.lock().unwrap();
+        let queue = consumers.get_mut(consumer_id)?;
+
+        let (mut acked, mut unknown) = (Vec::new(), Vec::new());
+        for &id in message_ids {
+            let position = queue
+                .messages
+                .iter()
+                .position(|unacked| unacked.message.id == id && unacked.delivered_at.is_some());
+            match position {
+                Some(position) => {
+                    queue.messages.remove(position);
+                    queue.acked_total += 1;
+                    acked.push(id);
+                }
+                None => unknown.push(id),
+            }
+        }
+        queue.dirty |= !acked.is_empty();
+        Some((acked, unknown))
+    }
+
+    pub fn status(&self, consumer_id: &str) -> Option<ConsumerAckStatus> {
+        let consumers = self.consumers.lock().unwrap();
+        let queue = consumers.get(consumer_id)?;
+        let now = Instant::now();
+
+        let awaiting_ack = queue
+            .messages
+            .iter()
+            .filter_map(|unacked| {
+                let elapsed = now.duration_since(unacked.delivered_at?);
+                Some(PendingAck {
+                    message_id: unacked.message.id,
+                    message_type: unacked.message.message_type.clone(),
+                    delivery_attempt: unacked.attempts,
+                    delivered_ms_ago: elapsed.as_millis() as u64,
+                    redelivery_in_ms: self.ack_timeout.saturating_sub(elapsed).as_millis() as u64,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Some(ConsumerAckStatus {
+            consumer_id: consumer_id.to_string(),
+            topics: queue.topics.iter().cloned().collect(),
+            queued: queue.messages.len() - awaiting_ack.len(),
+            awaiting_ack,
+            acked_total: queue.acked_total,
+            redelivered_total: queue.redelivered_total,
+        })
+    }
+
+    pub fn consumer_ids(&self) -> Vec<String> {
+        self.consumers.lock().unwrap().keys().cloned().collect()
+    }
+
+    // The queues that changed since the last call, ready to be checkpointed
+    pub fn take_changes(&self) -> Vec<(String, ConsumerCheckpoint)> {
+        let mut consumers = self.consumers.lock().unwrap();
+        consumers
+            .iter_mut()
+            .filter(|(_, queue)| queue.dirty)
+            .map(|(id, queue)| {
+                queue.dirty = false;
+                (id.clone(), queue.checkpoint())
+            })
+            .collect()
+    }
+
+    // Marks a queue to be checkpointed again, e.g. after writing it failed
+    pub fn mark_changed(&self, consumer_id: &str) {
+        if let Some(queue) = self.consumers.lock().unwrap().get_mut(consumer_id) {
+            queue.dirty = true;
+        }
+    }
+
+    // Puts back a queue checkpointed before a restart. Messages that had
+    // been delivered are due again at once, since their ack deadline did
+    // not survive the restart; they are redelivered with their attempts.
+    pub fn restore(&self, consumer_id: &str, checkpoint: ConsumerCheckpoint) {
+        let queue = ConsumerQueue {
+            topics: checkpoint.topics.into_iter().collect(),
+            messages: checkpoint
+                .messages
+                .into_iter()
+                .map(|checkpointed| Unacked {
+                    message: checkpointed.message,
+                    attempts: checkpointed.attempts,
+                    delivered_at: None,
+                })
+                .collect(),
+            acked_total: checkpoint.acked_total,
+            redelivered_total: checkpoint.redelivered_total,
+            dirty: false,
+        };
+        let mut consumers = self.consumers.lock().unwrap();
+        consumers.insert(consumer_id.to_string(), queue);
+    }
+
+    // The highest message id any consumer still holds
+    pub fn max_message_id(&self) -> Option<u64> {
+        let consumers = self.consumers.lock().unwrap();
+        consumers
+            .values()
+            .flat_map(|queue| queue.messages.iter().map(|unacked| unacked.message.id))
+            .max()
+    }
+}
+
+const CHECKPOINT_NAMESPACE: &str = "streaming";
+const CONSUMER_CHECKPOINT_NAMESPACE: &str = "streaming.consumers";
+const NEXT_MESSAGE_ID_KEY: &str = "next_message_id";
+// How often queues changed by published messages are checkpointed; polls
+// and acks are checkpointed before the tool returns
+const JOURNAL_FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+// Writes the acknowledging consumers' queues and the message counter to
+// the checkpoint store
+struct Journal {
+    store: CheckpointStore,
+    acks: Arc<AckTracker>,
+    message_counter: Arc<AtomicU64>,
+}
+
+impl Journal {
+    async fn flush(&self) -> Result<(), CheckpointError> {
+        let mut changes = self.acks.take_changes().into_iter();
+        while let Some((consumer_id, checkpoint)) = changes.next() {
+            let written = self
+                .store
+                .put(CONSUMER_CHECKPOINT_NAMESPACE, &consumer_id, &checkpoint)
+                .await;
+            if let Err(e) = written {
+                // Try these again on the next flush
+                self.acks.mark_changed(&consumer_id);
+                changes.for_each(|(consumer_id, _)| self.acks.mark_changed(&consumer_id));
+                return Err(e);
+            }
+        }
+        let next_id = self.message_counter.load(Ordering::Relaxed);
+        self.store
+            .put(CHECKPOINT_NAMESPACE, NEXT_MESSAGE_ID_KEY, &next_id)
+            .await
+    }
+
+    // Checkpoints periodically until the journal is dropped with its server
+    fn spawn_flusher(journal: Weak<Journal>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(JOURNAL_FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(journal) = journal.upgrade() else {
+                    break;
+                };
+                if let Err(e) = journal.flush().await {
+                    tracing::warn!(error = %e, "Failed to checkpoint the streaming journal");
+                }
+            }
+        });
+    }
+}
+
+// Sends messages to broadcast subscribers and queues critical ones for
+// acknowledging consumers
+#[derive(Clone)]
+struct Publisher {
+    tx: broadcast::Sender<StreamMessage>,
+    acks: Arc<AckTracker>,
+}
+
+impl Publisher {
+    // Returns how many broadcast subscribers and acknowledging consumers
+    // the message reached
+    fn publish(&self, message: StreamMessage) -> (usize, usize) {
+        let queued = self.acks.enqueue(&message);
+        let subscribers = self.tx.send(message).unwrap_or(0);
+        (subscribers, queued)
+    }
 }
 
 // Streaming Server
 pub struct StreamingServer {
     config: StreamingConfig,
     broadcast_tx: broadcast::Sender<StreamMessage>,
+    acks: Arc<AckTracker>,
     message_counter: Arc<AtomicU64>,
     start_time: Instant,
+    // Streams started by the connected client; they stop when it disconnects
+    subscriptions: Mutex<Vec<AbortHandle>>,
+    journal: Option<Arc<Journal>>,
 }
 
 impl StreamingServer {
     pub fn new(config: StreamingConfig) -> Self {
         let (broadcast_tx, _) = broadcast::channel(config.buffer_size);
+        let acks = Arc::new(AckTracker::new(
+            &config.critical_topics,
+            Duration::from_millis(config.ack_timeout_ms),
+        ));
 
         Self {
             config,
             broadcast_tx,
+            acks,
             message_counter: Arc::new(AtomicU64::new(0)),
             start_time: Instant::now(),
+            subscriptions: Mutex::new(Vec::new()),
+            journal: None,
+        }
+    }
+
+    // Restores the acknowledging consumers' queues and the message counter
+    // from `store`, and checkpoints them there from now on
+    pub async fn with_checkpoints(
+        mut self,
+        store: CheckpointStore,
+    ) -> Result<Self, CheckpointError> {
+        let consumers: Vec<(String, ConsumerCheckpoint)> =
+            store.list(CONSUMER_CHECKPOINT_NAMESPACE).await?;
+        for (consumer_id, checkpoint) in consumers {
+            self.acks.restore(&consumer_id, checkpoint);
+        }
+
+        // The counter is only flushed periodically, so ids handed out since
+        // then may be held by a consumer
+        let saved_next_id: Option<u64> =
+            store.get(CHECKPOINT_NAMESPACE, NEXT_MESSAGE_ID_KEY).await?;
+        let next_id = saved_next_id
+            .unwrap_or(0)
+            .max(self.acks.max_message_id().map_or(0, |id| id + 1));
+        self.message_counter.store(next_id, Ordering::Relaxed);
+
+        let journal = Arc::new(Journal {
+            store,
+            acks: self.acks.clone(),
+            message_counter: self.message_counter.clone(),
+        });
+        Journal::spawn_flusher(Arc::downgrade(&journal));
+        self.journal = Some(journal);
+        Ok(self)
+    }
+
+    async fn flush_journal(&self) {
+        if let Some(journal) = &self.journal {
+            if let Err(e) = journal.flush().await {
+                tracing::warn!(error = %e, "Failed to checkpoint the streaming journal");
+            }
         }
     }
 
+    fn publisher(&self) -> Publisher {
+        Publisher {
+            tx: self.broadcast_tx.clone(),
+            acks: self.acks.clone(),
+        }
+    }
+
+    fn track_subscription(&self, handle: AbortHandle) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.retain(|handle| !handle.is_finished());
+        subscriptions.push(handle);
+    }
+
+    // Streams started by the client that are still running
+    pub fn active_subscriptions(&self) -> usize {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions
+            .iter()
+            .filter(|handle| !handle.is_finished())
+            .count()
+    }
+
+    // Stops every stream the client started, e.g. once it has disconnected,
+    // and returns how many were still running
+    pub fn close_subscriptions(&self) -> usize {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let active = subscriptions
+            .drain(..)
+            .filter(|handle| !handle.is_finished())
+            .inspect(|handle| handle.abort())
+            .count();
+        active
+    }
+
     // Start background data generation
     pub fn start_background_streams(&self) {
-        let tx = self.broadcast_tx.clone();
+        let publisher = self.publisher();
         let counter = self.message_counter.clone();
         let interval = self.config.data_generation_interval_ms;
 
@@ -144,12 +679,12 @@ impl StreamingServer {
                     source: "metrics_generator".to_string(),
                 };
 
-                let _ = tx.send(message);
+                publisher.publish(message);
             }
         });
 
         // Spawn log stream
-        let tx = self.broadcast_tx.clone();
+        let publisher = self.publisher();
         let counter = self.message_counter.clone();
         let log_interval = interval * 2; // Less frequent logs
 
@@ -184,7 +719,7 @@ impl StreamingServer {
                     source: "log_generator".to_string(),
                 };
 
-                let _ = tx.send(message);
+                publisher.publish(message);
             }
         });
     }
@@ -247,6 +782,7 @@ impl StreamingServer {
                     },
                     "required": ["stream_type"]
                 }),
+                annotations: Some(ToolAnnotations::additive()),
             },
             Tool {
                 name: "get_stream_stats".to_string(),
@@ -256,6 +792,7 @@ impl StreamingServer {
                     "properties": {},
                     "additionalProperties": false
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
             Tool {
                 name: "get_recent_messages".to_string(),
@@ -276,6 +813,50 @@ impl StreamingServer {
                         }
                     }
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "inject_test_scenario".to_string(),
+                description: "Inject bursts, gaps and malformed payloads to test stream consumers"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "burst_size": {
+                            "type": "integer",
+                            "description": "Messages sent back to back in each burst",
+                            "default": 10,
+                            "minimum": 1,
+                            "maximum": MAX_SCENARIO_BURST_SIZE
+                        },
+                        "burst_count": {
+                            "type": "integer",
+                            "description": "Number of bursts",
+                            "default": 1,
+                            "minimum": 1,
+                            "maximum": MAX_SCENARIO_BURST_COUNT
+                        },
+                        "gap_seconds": {
+                            "type": "number",
+                            "description": "Silence between bursts in seconds",
+                            "default": 0,
+                            "minimum": 0,
+                            "maximum": MAX_SCENARIO_GAP_SECONDS
+                        },
+                        "malformed_ratio": {
+                            "type": "number",
+                            "description": "Fraction of messages with a malformed payload",
+                            "default": 0,
+                            "minimum": 0,
+                            "maximum": 1
+                        },
+                        "seed": {
+                            "type": "integer",
+                            "description": "Seed for reproducible scenarios (optional)"
+                        }
+                    }
+                }),
+                annotations: Some(ToolAnnotations::additive()),
             },
             Tool {
                 name: "send_custom_message".to_string(),
@@ -294,34 +875,129 @@ impl StreamingServer {
                     },
                     "required": ["message"]
                 }),
+                annotations: Some(ToolAnnotations::additive()),
+            },
+            Tool {
+                name: "subscribe_with_acks".to_string(),
+                description: "Subscribe a consumer to critical topics; their messages are kept \
+                              until the consumer acknowledges them"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "consumer_id": {
+                            "type": "string",
+                            "description": "Name of the consumer, used to poll and ack"
+                        },
+                        "topics": {
+                            "type": "array",
+                            "description": "Critical topics to receive",
+                            "items": { "type": "string", "enum": self.config.critical_topics },
+                            "minItems": 1
+                        }
+                    },
+                    "required": ["consumer_id", "topics"]
+                }),
+                annotations: Some(ToolAnnotations::additive().idempotent()),
+            },
+            Tool {
+                name: "poll_messages".to_string(),
+                description: "Deliver a consumer's unacknowledged messages, redelivering those \
+                              whose ack timed out"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "consumer_id": {
+                            "type": "string",
+                            "description": "Consumer registered with subscribe_with_acks"
+                        },
+                        "max_messages": {
+                            "type": "integer",
+                            "description": "Maximum number of messages to deliver",
+                            "default": 10,
+                            "minimum": 1,
+                            "maximum": MAX_POLL_MESSAGES
+                        }
+                    },
+                    "required": ["consumer_id"]
+                }),
+                annotations: Some(ToolAnnotations::additive()),
+            },
+            Tool {
+                name: "ack_messages".to_string(),
+                description: "Acknowledge delivered messages so they are not redelivered"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "consumer_id": {
+                            "type": "string",
+                            "description": "Consumer the messages were delivered to"
+                        },
+                        "message_ids": {
+                            "type": "array",
+                            "description": "IDs of the processed messages",
+                            "items": { "type": "integer" }
+                        }
+                    },
+                    "required": ["consumer_id", "message_ids"]
+                }),
+                annotations: Some(ToolAnnotations::destructive().idempotent()),
+            },
+            Tool {
+                name: "get_pending_acks".to_string(),
+                description: "Show the messages each consumer has not acknowledged yet".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "consumer_id": {
+                            "type": "string",
+                            "description": "Only this consumer (optional)"
+                        }
+                    }
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
         ]
     }
 
-    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        ToolCallLog::run_async(name, arguments, |arguments| {
+            self.dispatch_tool(name, arguments)
+        })
+        .await
+    }
+
+    async fn dispatch_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
         match name {
             "start_stream" => self.start_stream(arguments).await,
             "get_stream_stats" => self.get_stream_stats(arguments).await,
             "get_recent_messages" => self.get_recent_messages_tool(arguments).await,
             "send_custom_message" => self.send_custom_message(arguments).await,
-            _ => Err(format!("Unknown tool: {}", name)),
+            "inject_test_scenario" => self.inject_test_scenario(arguments).await,
+            "subscribe_with_acks" => self.subscribe_with_acks(arguments).await,
+            "poll_messages" => self.poll_messages(arguments).await,
+            "ack_messages" => self.ack_messages(arguments).await,
+            "get_pending_acks" => self.get_pending_acks(arguments).await,
+            _ => Err(McpError::NotFound(format!("Unknown tool: {}", name))),
         }
     }
 
-    async fn start_stream(&self, arguments: Value) -> Result<Value, String> {
+    async fn start_stream(&self, arguments: Value) -> Result<Value, McpError> {
         let request: StartStreamRequest = serde_json::from_value(arguments)
-            .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+            .map_err(|e| McpError::InvalidParams(format!("Failed to parse arguments: {}", e)))?;
 
         let duration = request.duration_seconds.unwrap_or(30);
         let stream_type = request.stream_type.clone();
         let stream_type_for_message = request.stream_type.clone();
 
         // Start a temporary stream for the specified duration
-        let tx = self.broadcast_tx.clone();
+        let publisher = self.publisher();
         let counter = self.message_counter.clone();
         let frequency = request.frequency_ms.unwrap_or(1000);
 
-        tokio::spawn(async move {
+        let stream = tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_millis(frequency));
             let start = Instant::now();
             let duration = Duration::from_secs(duration);
@@ -360,9 +1036,10 @@ impl StreamingServer {
                     source: "streaming_tool".to_string(),
                 };
 
-                let _ = tx.send(message);
+                publisher.publish(message);
             }
         });
+        self.track_subscription(stream.abort_handle());
 
         Ok(serde_json::json!({
             "success": true,
@@ -373,9 +1050,9 @@ impl StreamingServer {
         }))
     }
 
-    async fn get_stream_stats(&self, _arguments: Value) -> Result<Value, String> {
+    async fn get_stream_stats(&self, _arguments: Value) -> Result<Value, McpError> {
         let stats = StreamStats {
-            active_streams: 2, // Background streams
+            active_streams: 2 + self.active_subscriptions() as u32, // Background streams plus the client's
             total_messages: self.message_counter.load(Ordering::Relaxed),
             subscriber_count: self.broadcast_tx.receiver_count(),
             buffer_utilization: (self.broadcast_tx.len() as f64 / self.config.buffer_size as f64)
@@ -383,10 +1060,11 @@ impl StreamingServer {
             uptime_seconds: self.start_time.elapsed().as_secs(),
         };
 
-        serde_json::to_value(stats).map_err(|e| format!("Failed to serialize stats: {}", e))
+        serde_json::to_value(stats)
+            .map_err(|e| McpError::Internal(format!("Failed to serialize stats: {}", e)))
     }
 
-    async fn get_recent_messages_tool(&self, arguments: Value) -> Result<Value, String> {
+    async fn get_recent_messages_tool(&self, arguments: Value) -> Result<Value, McpError> {
         let count = arguments
             .get("count")
             .and_then(|c| c.as_u64())
@@ -406,9 +1084,9 @@ impl StreamingServer {
         }))
     }
 
-    async fn send_custom_message(&self, arguments: Value) -> Result<Value, String> {
+    async fn send_custom_message(&self, arguments: Value) -> Result<Value, McpError> {
         let request: SendCustomMessageRequest = serde_json::from_value(arguments)
-            .map_err(|e| format!("Failed to parse arguments: {}", e))?;
+            .map_err(|e| McpError::InvalidParams(format!("Failed to parse arguments: {}", e)))?;
 
         let id = self.message_counter.fetch_add(1, Ordering::Relaxed);
         let message = StreamMessage {
@@ -422,21 +1100,332 @@ impl StreamingServer {
             source: "user".to_string(),
         };
 
-        match self.broadcast_tx.send(message.clone()) {
-            Ok(subscriber_count) => Ok(serde_json::json!({
+        match self.publisher().publish(message.clone()) {
+            (0, 0) => Err(McpError::Unavailable(
+                "Failed to send message (no active subscribers)".to_string(),
+            )),
+            (subscriber_count, queued_for_ack) => Ok(serde_json::json!({
                 "success": true,
                 "message_id": id,
                 "subscriber_count": subscriber_count,
+                "queued_for_ack": queued_for_ack,
                 "sent_message": message
             })),
-            Err(_) => Err("Failed to send message (no active subscribers)".to_string()),
         }
     }
+
+    async fn subscribe_with_acks(&self, arguments: Value) -> Result<Value, McpError> {
+        let request: SubscribeWithAcksRequest = serde_json::from_value(arguments)
+            .map_err(|e| McpError::InvalidParams(format!("Failed to parse arguments: {}", e)))?;
+
+        self.acks
+            .subscribe(&request.consumer_id, &request.topics)
+            .map_err(McpError::InvalidParams)?;
+        self.flush_journal().await;
+
+        Ok(serde_json::json!({
+            "success": true,
+            "consumer_id": request.consumer_id,
+            "topics": request.topics,
+            "ack_timeout_ms": self.config.ack_timeout_ms
+        }))
+    }
+
+    async fn poll_messages(&self, arguments: Value) -> Result<Value, McpError> {
+        let request: PollMessagesRequest = serde_json::from_value(arguments)
+            .map_err(|e| McpError::InvalidParams(format!("Failed to parse arguments: {}", e)))?;
+
+        // Every message awaiting its ack counts towards the connection's
+        // limits, so a client that never acks stops getting more
+        let session = session::current();
+        let room = session.remaining_objects();
+        if room == 0 {
+            let usage = session.usage();
+            return Err(SessionLimitExceeded {
+                kind: PENDING_ACKS_KIND.to_string(),
+                objects: usage.objects + 1,
+                bytes: usage.bytes,
+                limits: usage.limits,
+            }
+            .into());
+        }
+        let max_messages = request
+            .max_messages
+            .unwrap_or(10)
+            .clamp(1, MAX_POLL_MESSAGES)
+            .min(room);
+        let deliveries = self
+            .acks
+            .poll(&request.consumer_id, max_messages)
+            .ok_or_else(|| unknown_consumer(&request.consumer_id))?;
+        self.flush_journal().await;
+        for delivery in &deliveries {
+            // Over the byte limit the deliveries are lost to this poll, and
+            // handed out again once their ack is overdue
+            let id = pending_ack_id(&request.consumer_id, delivery.message.id);
+            let bytes = std::mem::size_of::<Delivery>() + delivery.message.data.to_string().len();
+            session.track(PENDING_ACKS_KIND, &id, bytes)?;
+        }
+
+        Ok(serde_json::json!({
+            "consumer_id": request.consumer_id,
+            "count": deliveries.len(),
+            "deliveries": deliveries
+        }))
+    }
+
+    async fn ack_messages(&self, arguments: Value) -> Result<Value, McpError> {
+        let request: AckMessagesRequest = serde_json::from_value(arguments)
+            .map_err(|e| McpError::InvalidParams(format!("Failed to parse arguments: {}", e)))?;
+
+        let (acked, not_pending) = self
+            .acks
+            .ack(&request.consumer_id, &request.message_ids)
+            .ok_or_else(|| unknown_consumer(&request.consumer_id))?;
+        self.flush_journal().await;
+        let session = session::current();
+        for message_id in &acked {
+            session.untrack(
+                PENDING_ACKS_KIND,
+                &pending_ack_id(&request.consumer_id, *message_id),
+            );
+        }
+
+        Ok(serde_json::json!({
+            "consumer_id": request.consumer_id,
+            "acked": acked,
+            "not_pending": not_pending
+        }))
+    }
+
+    async fn get_pending_acks(&self, arguments: Value) -> Result<Value, McpError> {
+        let request: GetPendingAcksRequest = serde_json::from_value(arguments)
+            .map_err(|e| McpError::InvalidParams(format!("Failed to parse arguments: {}", e)))?;
+
+        let consumer_ids = match request.consumer_id {
+            Some(consumer_id) => vec![consumer_id],
+            None => self.acks.consumer_ids(),
+        };
+        let consumers = consumer_ids
+            .iter()
+            .map(|id| self.acks.status(id).ok_or_else(|| unknown_consumer(id)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(serde_json::json!({
+            "ack_timeout_ms": self.config.ack_timeout_ms,
+            "consumers": consumers
+        }))
+    }
+
+    async fn inject_test_scenario(&self, arguments: Value) -> Result<Value, McpError> {
+        let request: InjectTestScenarioRequest = serde_json::from_value(arguments)
+            .map_err(|e| McpError::InvalidParams(format!("Failed to parse arguments: {}", e)))?;
+
+        let burst_size = request.burst_size.unwrap_or(10);
+        let burst_count = request.burst_count.unwrap_or(1);
+        let gap_seconds = request.gap_seconds.unwrap_or(0.0);
+        let malformed_ratio = request.malformed_ratio.unwrap_or(0.0);
+
+        if !(1..=MAX_SCENARIO_BURST_SIZE).contains(&burst_size) {
+            return Err(McpError::InvalidParams(format!(
+                "burst_size must be between 1 and {}",
+                MAX_SCENARIO_BURST_SIZE
+            )));
+        }
+        if !(1..=MAX_SCENARIO_BURST_COUNT).contains(&burst_count) {
+            return Err(McpError::InvalidParams(format!(
+                "burst_count must be between 1 and {}",
+                MAX_SCENARIO_BURST_COUNT
+            )));
+        }
+        if !(0.0..=MAX_SCENARIO_GAP_SECONDS).contains(&gap_seconds) {
+            return Err(McpError::InvalidParams(format!(
+                "gap_seconds must be between 0 and {}",
+                MAX_SCENARIO_GAP_SECONDS
+            )));
+        }
+        if !(0.0..=1.0).contains(&malformed_ratio) {
+            return Err(McpError::InvalidParams(
+                "malformed_ratio must be between 0 and 1".to_string(),
+            ));
+        }
+
+        // Pick exactly round(total * ratio) malformed positions, so small
+        // scenarios still get the requested share
+        let seed = request.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let total_messages = burst_size * burst_count;
+        let malformed_count = (total_messages as f64 * malformed_ratio).round() as usize;
+        let mut positions: Vec<u32> = (0..total_messages).collect();
+        positions.shuffle(&mut rng);
+        let mut malformed_sequences = positions[..malformed_count].to_vec();
+        malformed_sequences.sort_unstable();
+
+        let scenario = TestScenario {
+            scenario_id: uuid::Uuid::new_v4().to_string(),
+            burst_size,
+            burst_count,
+            gap_seconds,
+            malformed_ratio,
+            seed,
+            total_messages,
+            malformed_sequences,
+            estimated_duration_seconds: gap_seconds * (burst_count - 1) as f64,
+        };
+
+        let publisher = self.publisher();
+        let counter = self.message_counter.clone();
+        let scenario_id = scenario.scenario_id.clone();
+        let malformed = scenario.malformed_sequences.clone();
+
+        let stream = tokio::spawn(async move {
+            let mut sequence = 0;
+            for burst in 0..burst_count {
+                if burst > 0 && gap_seconds > 0.0 {
+                    tokio::time::sleep(Duration::from_secs_f64(gap_seconds)).await;
+                }
+
+                for _ in 0..burst_size {
+                    let data = if malformed.binary_search(&sequence).is_ok() {
+                        malformed_payload(&mut rng, &scenario_id, burst, sequence)
+                    } else {
+                        serde_json::json!({
+                            "scenario_id": scenario_id,
+                            "burst": burst,
+                            "sequence": sequence,
+                            "value": rng.gen::<f64>()
+                        })
+                    };
+
+                    let message = StreamMessage {
+                        id: counter.fetch_add(1, Ordering::Relaxed),
+                        message_type: "test".to_string(),
+                        data,
+                        timestamp: chrono::Utc::now().to_rfc3339(),
+                        source: "scenario_injector".to_string(),
+                    };
+                    publisher.publish(message);
+                    sequence += 1;
+                }
+            }
+        });
+        self.track_subscription(stream.abort_handle());
+
+        serde_json::to_value(scenario)
+            .map_err(|e| McpError::Internal(format!("Failed to serialize scenario: {}", e)))
+    }
+}
+
+fn pending_ack_id(consumer_id: &str, message_id: u64) -> String {
+    format!("{}/{}", consumer_id, message_id)
+}
+
+fn unknown_consumer(consumer_id: &str) -> McpError {
+    McpError::NotFound(format!(
+        "Unknown consumer '{}'; subscribe it with subscribe_with_acks first",
+        consumer_id
+    ))
+}
+
+// Serves the tools to WebSocket clients instead of running the demo
+const WEBSOCKET_FLAG: &str = "--websocket";
+const WEBSOCKET_ADDRESS_ENV: &str = "MCP_WS_ADDRESS";
+const DEFAULT_WEBSOCKET_ADDRESS: &str = "127.0.0.1:8765";
+
+fn websocket_address() -> Result<SocketAddr, std::net::AddrParseError> {
+    std::env::var(WEBSOCKET_ADDRESS_ENV)
+        .unwrap_or_else(|_| DEFAULT_WEBSOCKET_ADDRESS.to_string())
+        .parse()
+}
+
+const MAX_SCENARIO_BURST_SIZE: u32 = 1000;
+const MAX_SCENARIO_BURST_COUNT: u32 = 100;
+const MAX_SCENARIO_GAP_SECONDS: f64 = 300.0;
+
+// Payloads a consumer should reject or tolerate without crashing
+fn malformed_payload(rng: &mut StdRng, scenario_id: &str, burst: u32, sequence: u32) -> Value {
+    match rng.gen_range(0..4) {
+        // JSON text cut off mid-object, delivered as a string
+        0 => Value::String(format!(
+            "{{\"scenario_id\": \"{}\", \"sequence\": {}",
+            scenario_id, sequence
+        )),
+        // No payload at all
+        1 => Value::Null,
+        // Required fields missing
+        2 => serde_json::json!({ "burst": burst }),
+        // Fields with the wrong types
+        _ => serde_json::json!({
+            "scenario_id": sequence,
+            "burst": burst.to_string(),
+            "sequence": format!("#{}", sequence),
+            "value": "NaN"
+        }),
+    }
+}
+
+// Served over stdio with --stdio, and to clients that work with several servers
+impl ToolServer for StreamingServer {
+    fn server_name(&self) -> &str {
+        "streaming"
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.list_tools()
+    }
+
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(self.call_tool(name, arguments))
+    }
+}
+
+// Reported by the self_diagnostics tool
+impl DiagnosticsProvider for StreamingServer {
+    fn config_summary(&self) -> Value {
+        serde_json::to_value(&self.config).unwrap_or(Value::Null)
+    }
+
+    fn feature_flags(&self) -> BTreeMap<String, bool> {
+        BTreeMap::from([("metrics".to_string(), self.config.enable_metrics)])
+    }
+}
+
+// Serves `server` on a free local port and fetches its stats through a
+// WebSocket client, as a remote consumer would
+async fn websocket_round_trip(
+    server: Arc<StreamingServer>,
+) -> Result<StreamStats, Box<dyn std::error::Error>> {
+    let (addr, running) =
+        WebSocketTransport::new("127.0.0.1:0".parse()?).spawn(Arc::new(McpServer::new(server)))?;
+    let client =
+        WebSocketClient::connect(&format!("ws://{}/ws", addr), ReconnectPolicy::default()).await?;
+    client
+        .request(
+            "initialize",
+            serde_json::json!({ "protocolVersion": "2025-06-18" }),
+        )
+        .await?;
+    let result = client
+        .request(
+            "tools/call",
+            serde_json::json!({ "name": "get_stream_stats", "arguments": {} }),
+        )
+        .await?;
+    running.abort();
+
+    let text = result["content"][0]["text"]
+        .as_str()
+        .ok_or("tools/call returned no text")?;
+    Ok(serde_json::from_str(text)?)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
+    let _telemetry = mcp_rust_examples::logging::init("error");
 
     eprintln!("📡 Starting Real-time Streaming MCP Server");
     eprintln!("==========================================");
@@ -450,12 +1439,52 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("   Data interval: {}ms", config.data_generation_interval_ms);
     eprintln!("   Heartbeat interval: {}ms", config.heartbeat_interval_ms);
 
-    // Create server
-    let server = StreamingServer::new(config);
+    // Clients that stop answering heartbeat pings are dropped
+    let keepalive = KeepaliveConfig::new(
+        Duration::from_millis(config.heartbeat_interval_ms),
+        Duration::from_millis(config.heartbeat_interval_ms * 2),
+    );
+
+    // Create server, resuming from its checkpoints if MCP_CHECKPOINT_PATH
+    // is set
+    let server = match CheckpointStore::from_env().await? {
+        Some(store) => {
+            eprintln!("💾 Checkpointing acknowledged delivery");
+            StreamingServer::new(config).with_checkpoints(store).await?
+        }
+        None => StreamingServer::new(config),
+    };
+    let server = Arc::new(server);
 
     // Start background streams
     server.start_background_streams();
 
+    // With --stdio, serve the tools to an MCP client instead of running the
+    // demo. Whichever way the client goes, its streams stop with it.
+    if transport::stdio_requested() {
+        let reason = StdioTransport::new()
+            .with_keepalive(keepalive)
+            .serve(&McpServer::new(server.clone()).with_diagnostics(server.clone()))
+            .await?;
+        let closed = server.close_subscriptions();
+        tracing::info!(%reason, closed, "Closed the client's streams");
+        return Ok(());
+    }
+
+    // With --websocket, serve them at ws://MCP_WS_ADDRESS/ws until stopped.
+    // Every client gets its own session with the same heartbeat.
+    if std::env::args().skip(1).any(|arg| arg == WEBSOCKET_FLAG) {
+        let addr = websocket_address()?;
+        eprintln!("🔌 Serving over WebSocket at ws://{}/ws", addr);
+        WebSocketTransport::new(addr)
+            .with_keepalive(keepalive)
+            .serve(Arc::new(
+                McpServer::new(server.clone()).with_diagnostics(server.clone()),
+            ))
+            .await?;
+        return Ok(());
+    }
+
     eprintln!("\n🧪 Streaming Demo:");
 
     // List tools
@@ -518,6 +1547,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Err(e) => eprintln!("  ❌ Get messages failed: {}", e),
     }
 
+    // Register a consumer that acknowledges custom messages, so the message
+    // below is kept for it until acked
+    eprintln!("\n📬 Subscribing an acknowledging consumer:");
+    match server
+        .call_tool(
+            "subscribe_with_acks",
+            serde_json::json!({ "consumer_id": "billing", "topics": ["custom"] }),
+        )
+        .await
+    {
+        Ok(result) => eprintln!(
+            "  ✅ Consumer 'billing' subscribed to {} (ack within {}ms)",
+            result["topics"], result["ack_timeout_ms"]
+        ),
+        Err(e) => eprintln!("  ❌ Subscribe failed: {}", e),
+    }
+
     // Send custom message
     eprintln!("\n📤 Sending custom message:");
     match server
@@ -534,13 +1580,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let message_id = result.get("message_id").unwrap_or(&Value::Null);
             let subscriber_count = result.get("subscriber_count").unwrap_or(&Value::Null);
             eprintln!(
-                "  ✅ Sent message {} to {} subscribers",
-                message_id, subscriber_count
+                "  ✅ Sent message {} to {} subscribers, queued for {} acknowledging consumers",
+                message_id, subscriber_count, result["queued_for_ack"]
             );
         }
         Err(e) => eprintln!("  ❌ Send message failed: {}", e),
     }
 
+    // The consumer polls, then acks what it processed
+    eprintln!("\n✉️  Polling and acknowledging:");
+    let poll = serde_json::json!({ "consumer_id": "billing" });
+    if let Ok(result) = server.call_tool("poll_messages", poll).await {
+        let deliveries: Vec<Delivery> =
+            serde_json::from_value(result["deliveries"].clone()).unwrap_or_default();
+        let pending = server
+            .call_tool(
+                "get_pending_acks",
+                serde_json::json!({ "consumer_id": "billing" }),
+            )
+            .await
+            .map(|status| status["consumers"][0]["awaiting_ack"].clone())
+            .unwrap_or_default();
+        eprintln!(
+            "  ✅ Delivered {} messages; awaiting ack: {}",
+            deliveries.len(),
+            pending
+        );
+
+        let ids: Vec<u64> = deliveries.iter().map(|d| d.message.id).collect();
+        let ack = serde_json::json!({ "consumer_id": "billing", "message_ids": ids });
+        match server.call_tool("ack_messages", ack).await {
+            Ok(result) => eprintln!("  ✅ Acknowledged {}", result["acked"]),
+            Err(e) => eprintln!("  ❌ Ack failed: {}", e),
+        }
+    }
+
     // Start a temporary stream
     eprintln!("\n🎬 Starting demo stream:");
     match server
@@ -576,6 +1650,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Err(e) => eprintln!("  ❌ Start stream failed: {}", e),
     }
 
+    // Inject a test scenario
+    eprintln!("\n🧨 Injecting test scenario:");
+    match server
+        .call_tool(
+            "inject_test_scenario",
+            serde_json::json!({
+                "burst_size": 20,
+                "burst_count": 3,
+                "gap_seconds": 0.5,
+                "malformed_ratio": 0.1
+            }),
+        )
+        .await
+    {
+        Ok(result) => {
+            if let Ok(scenario) = serde_json::from_value::<TestScenario>(result) {
+                eprintln!(
+                    "  ✅ Scenario {} (seed {}): {} messages in {} bursts",
+                    scenario.scenario_id,
+                    scenario.seed,
+                    scenario.total_messages,
+                    scenario.burst_count
+                );
+                eprintln!(
+                    "     Malformed sequences: {:?}",
+                    scenario.malformed_sequences
+                );
+            }
+        }
+        Err(e) => eprintln!("  ❌ Scenario injection failed: {}", e),
+    }
+
+    // Call the server again, this time over a local WebSocket
+    eprintln!("\n🔌 WebSocket round trip:");
+    match websocket_round_trip(server.clone()).await {
+        Ok(stats) => eprintln!(
+            "  ✅ Stats over ws://: {} active streams, {} messages",
+            stats.active_streams, stats.total_messages
+        ),
+        Err(e) => eprintln!("  ❌ WebSocket call failed: {}", e),
+    }
+
     eprintln!("\n🎉 Streaming demo completed!");
     eprintln!("\n🌊 Streaming features demonstrated:");
     eprintln!("   ✅ Real-time message broadcasting");
@@ -584,6 +1700,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("   ✅ Subscriber management");
     eprintln!("   ✅ Message filtering and retrieval");
     eprintln!("   ✅ Stream statistics and monitoring");
+    eprintln!("   ✅ Test scenario injection for consumer robustness");
+    eprintln!("   ✅ Acknowledged delivery with redelivery for critical topics");
+    eprintln!("   ✅ WebSocket transport with a reconnecting client");
 
     Ok(())
 }
@@ -598,10 +1717,12 @@ mod tests {
         let server = StreamingServer::new(config);
 
         let tools = server.list_tools();
-        assert_eq!(tools.len(), 4);
+        assert_eq!(tools.len(), 9);
         assert!(tools.iter().any(|t| t.name == "start_stream"));
         assert!(tools.iter().any(|t| t.name == "get_stream_stats"));
         assert!(tools.iter().any(|t| t.name == "send_custom_message"));
+        assert!(tools.iter().any(|t| t.name == "inject_test_scenario"));
+        assert!(tools.iter().any(|t| t.name == "get_pending_acks"));
     }
 
     #[tokio::test]
@@ -619,6 +1740,37 @@ mod tests {
         assert_eq!(stats.subscriber_count, 0); // No subscribers in test
     }
 
+    #[tokio::test]
+    async fn test_close_subscriptions() {
+        let server = StreamingServer::new(StreamingConfig::default());
+
+        server
+            .call_tool(
+                "start_stream",
+                serde_json::json!({ "stream_type": "metrics", "duration_seconds": 60 }),
+            )
+            .await
+            .unwrap();
+        let stats = server
+            .call_tool("get_stream_stats", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(stats["active_streams"], 3);
+
+        // A disconnect stops the client's stream but not the background ones
+        assert_eq!(server.close_subscriptions(), 1);
+        tokio::task::yield_now().await;
+        assert_eq!(server.active_subscriptions(), 0);
+        assert_eq!(server.close_subscriptions(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_round_trip() {
+        let server = Arc::new(StreamingServer::new(StreamingConfig::default()));
+        let stats = websocket_round_trip(server).await.unwrap();
+        assert_eq!(stats.active_streams, 2);
+    }
+
     #[tokio::test]
     async fn test_custom_message() {
         let config = StreamingConfig::default();
@@ -631,7 +1783,242 @@ mod tests {
 
         // This will fail with no subscribers, which is expected
         let result = server.call_tool("send_custom_message", args).await;
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("no active subscribers"));
+        assert!(
+            matches!(result, Err(McpError::Unavailable(message)) if message.contains("no active subscribers"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_acknowledged_delivery() {
+        let server = StreamingServer::new(StreamingConfig {
+            ack_timeout_ms: 50,
+            ..Default::default()
+        });
+        let subscribe = serde_json::json!({ "consumer_id": "audit", "topics": ["custom"] });
+        server
+            .call_tool("subscribe_with_acks", subscribe)
+            .await
+            .unwrap();
+
+        // With no broadcast subscribers the message is still kept for the
+        // acknowledging consumer
+        for text in ["first", "second"] {
+            let result = server
+                .call_tool(
+                    "send_custom_message",
+                    serde_json::json!({ "message": text }),
+                )
+                .await
+                .unwrap();
+            assert_eq!(result["queued_for_ack"], 1);
+        }
+
+        let poll = serde_json::json!({ "consumer_id": "audit" });
+        let result = server
+            .call_tool("poll_messages", poll.clone())
+            .await
+            .unwrap();
+        let deliveries: Vec<Delivery> =
+            serde_json::from_value(result["deliveries"].clone()).unwrap();
+        assert_eq!(deliveries.len(), 2);
+        assert!(deliveries
+            .iter()
+            .all(|d| d.delivery_attempt == 1 && !d.redelivered));
+        let (first, second) = (deliveries[0].message.id, deliveries[1].message.id);
+
+        // Delivered messages are not handed out again while their ack is due
+        let result = server
+            .call_tool("poll_messages", poll.clone())
+            .await
+            .unwrap();
+        assert_eq!(result["count"], 0);
+
+        let ack = serde_json::json!({ "consumer_id": "audit", "message_ids": [first, first] });
+        let result = server.call_tool("ack_messages", ack).await.unwrap();
+        assert_eq!(result["acked"], serde_json::json!([first]));
+        assert_eq!(result["not_pending"], serde_json::json!([first]));
+
+        let status = server
+            .call_tool(
+                "get_pending_acks",
+                serde_json::json!({ "consumer_id": "audit" }),
+            )
+            .await
+            .unwrap();
+        let status: ConsumerAckStatus =
+            serde_json::from_value(status["consumers"][0].clone()).unwrap();
+        assert_eq!(status.awaiting_ack.len(), 1);
+        assert_eq!(status.awaiting_ack[0].message_id, second);
+        assert_eq!((status.queued, status.acked_total), (0, 1));
+
+        // The unacked message comes back once its ack is overdue
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let result = server.call_tool("poll_messages", poll).await.unwrap();
+        let deliveries: Vec<Delivery> =
+            serde_json::from_value(result["deliveries"].clone()).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].message.id, second);
+        assert_eq!(
+            (deliveries[0].delivery_attempt, deliveries[0].redelivered),
+            (2, true)
+        );
+        assert_eq!(server.acks.status("audit").unwrap().redelivered_total, 1);
+
+        // Only critical topics take acks, and consumers must subscribe first
+        let metrics = serde_json::json!({ "consumer_id": "audit", "topics": ["metrics"] });
+        assert!(matches!(
+            server.call_tool("subscribe_with_acks", metrics).await,
+            Err(McpError::InvalidParams(_))
+        ));
+        assert!(matches!(
+            server
+                .call_tool(
+                    "poll_messages",
+                    serde_json::json!({ "consumer_id": "nobody" })
+                )
+                .await,
+            Err(McpError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pending_acks_count_towards_session_limits() {
+        let server = StreamingServer::new(StreamingConfig::default());
+        let subscribe = serde_json::json!({ "consumer_id": "billing", "topics": ["custom"] });
+        server
+            .call_tool("subscribe_with_acks", subscribe)
+            .await
+            .unwrap();
+        for text in ["first", "second", "third"] {
+            let message = serde_json::json!({ "message": text });
+            server
+                .call_tool("send_custom_message", message)
+                .await
+                .unwrap();
+        }
+
+        let limits = session::SessionLimits {
+            max_objects: 2,
+            ..Default::default()
+        };
+        let store = Arc::new(session::SessionStore::with_limits(limits));
+        let call = |name: &'static str, arguments: Value| {
+            session::scope(store.clone(), server.call_tool(name, arguments))
+        };
+        let poll = serde_json::json!({ "consumer_id": "billing" });
+
+        // Polls hand out only as many messages as the session has room for
+        let result = call("poll_messages", poll.clone()).await.unwrap();
+        assert_eq!(result["count"], 2);
+        assert_eq!(store.usage().by_kind[PENDING_ACKS_KIND].objects, 2);
+        assert!(matches!(
+            call("poll_messages", poll.clone()).await,
+            Err(McpError::RateLimited { .. })
+        ));
+
+        // Acking makes room for the rest
+        let first = result["deliveries"][0]["message"]["id"].clone();
+        let ack = serde_json::json!({ "consumer_id": "billing", "message_ids": [first] });
+        call("ack_messages", ack).await.unwrap();
+        let result = call("poll_messages", poll).await.unwrap();
+        assert_eq!(result["count"], 1);
+        assert_eq!(store.usage().objects, 2);
+    }
+
+    #[tokio::test]
+    async fn test_unacked_messages_survive_a_restart() {
+        let store = CheckpointStore::in_memory().await.unwrap();
+        let server = StreamingServer::new(StreamingConfig::default())
+            .with_checkpoints(store.clone())
+            .await
+            .unwrap();
+        let subscribe = serde_json::json!({ "consumer_id": "audit", "topics": ["custom"] });
+        server
+            .call_tool("subscribe_with_acks", subscribe)
+            .await
+            .unwrap();
+        for text in ["first", "second"] {
+            server
+                .call_tool(
+                    "send_custom_message",
+                    serde_json::json!({ "message": text }),
+                )
+                .await
+                .unwrap();
+        }
+        let poll = serde_json::json!({ "consumer_id": "audit" });
+        let result = server
+            .call_tool("poll_messages", poll.clone())
+            .await
+            .unwrap();
+        let deliveries: Vec<Delivery> =
+            serde_json::from_value(result["deliveries"].clone()).unwrap();
+        let (first, second) = (deliveries[0].message.id, deliveries[1].message.id);
+        let ack = serde_json::json!({ "consumer_id": "audit", "message_ids": [first] });
+        server.call_tool("ack_messages", ack).await.unwrap();
+        drop(server);
+
+        // The unacked message is redelivered at once after the restart, and
+        // new messages do not reuse its id
+        let server = StreamingServer::new(StreamingConfig::default())
+            .with_checkpoints(store)
+            .await
+            .unwrap();
+        let result = server.call_tool("poll_messages", poll).await.unwrap();
+        let deliveries: Vec<Delivery> =
+            serde_json::from_value(result["deliveries"].clone()).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].message.id, second);
+        assert_eq!(
+            (deliveries[0].delivery_attempt, deliveries[0].redelivered),
+            (2, true)
+        );
+        assert_eq!(server.acks.status("audit").unwrap().acked_total, 1);
+
+        let result = server
+            .call_tool(
+                "send_custom_message",
+                serde_json::json!({ "message": "third" }),
+            )
+            .await
+            .unwrap();
+        assert!(result["message_id"].as_u64().unwrap() > second);
+    }
+
+    #[tokio::test]
+    async fn test_inject_test_scenario() {
+        let server = StreamingServer::new(StreamingConfig::default());
+        let mut rx = server.broadcast_tx.subscribe();
+
+        let args = serde_json::json!({
+            "burst_size": 5,
+            "burst_count": 2,
+            "gap_seconds": 0.05,
+            "malformed_ratio": 0.3,
+            "seed": 7
+        });
+        let result = server
+            .call_tool("inject_test_scenario", args)
+            .await
+            .unwrap();
+        let scenario: TestScenario = serde_json::from_value(result).unwrap();
+        assert_eq!(scenario.total_messages, 10);
+        assert_eq!(scenario.malformed_sequences.len(), 3);
+
+        let mut malformed = Vec::new();
+        for sequence in 0..scenario.total_messages {
+            let message = rx.recv().await.unwrap();
+            assert_eq!(message.message_type, "test");
+            if message.data.get("sequence") != Some(&Value::from(sequence)) {
+                malformed.push(sequence);
+            }
+        }
+        assert_eq!(malformed, scenario.malformed_sequences);
+
+        let invalid = serde_json::json!({ "malformed_ratio": 1.5 });
+        assert!(matches!(
+            server.call_tool("inject_test_scenario", invalid).await,
+            Err(McpError::InvalidParams(_))
+        ));
     }
 }
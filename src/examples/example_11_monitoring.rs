@@ -12,20 +12,123 @@
 // - Performance monitoring and alerting
 // - Resource usage tracking
 // - Custom metric definitions and collection
-// - Time-series data handling
+// - Time-series data handling with tiered retention and downsampling
 // - Integration with monitoring tools
+// - Publishing status pages with per-service availability
+// - Reading real host metrics through a swappable collector
+// - Exposing metrics to Prometheus over HTTP (set MCP_METRICS_ADDRESS)
+// - Alert thresholds configured at runtime and persisted to a JSON file
+// - Grouping related alerts into incidents with postmortem timelines
+// - Delivering alerts to webhooks and MCP clients, routed by severity
+// - Accepting metrics and alerts pushed by other servers, such as example 12
+// - Checking services for real through health probes, such as example 9's
+//   ping_database tool over HTTP (set MCP_DATABASE_HEALTH_URL)
+// - Deriving counters and histograms from log events, the server's own or
+//   those of example 10's log stream (set MCP_LOG_METRIC_RULES_FILE)
 
+use futures::future::BoxFuture;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::http_transport;
+use mcp_rust_examples::logging::ToolCallLog;
+use mcp_rust_examples::metrics::{
+    self, is_valid_prometheus_name, prometheus_labels, MetricsRegistry,
+};
+use mcp_rust_examples::protocol::{
+    LoggingLevel, LoggingMessageParams, Tool, ToolAnnotations, PROTOCOL_VERSION,
+};
+use mcp_rust_examples::server::McpServer;
+use mcp_rust_examples::state::{StateCommand, StateError, StatefulServer};
+use mcp_rust_examples::tools::ToolServer;
+use mcp_rust_examples::transport::{self, StdioTransport};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use sysinfo::{Disks, Networks, ProcessesToUpdate, System, MINIMUM_CPU_UPDATE_INTERVAL};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
 // Constants: Define monitoring configuration values as named constants
 // This follows clean code principles by avoiding magic numbers
-const MAX_METRIC_HISTORY_SIZE: usize = 1000;
-const ALERT_THRESHOLD_CPU_PERCENT: f64 = 80.0;
-const ALERT_THRESHOLD_MEMORY_PERCENT: f64 = 85.0;
+// Constants: Tiered retention of the metrics history. Raw samples are kept
+// for an hour, then compacted into 1-minute averages kept for a day, and
+// those into hourly averages kept for 30 days
+const RAW_RETENTION_SECONDS: u64 = 60 * 60;
+const MINUTE_RETENTION_SECONDS: u64 = 24 * 60 * 60;
+const HOUR_RETENTION_SECONDS: u64 = 30 * 24 * 60 * 60;
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(60);
+// Caps raw samples however often metrics are collected
+const MAX_RAW_METRIC_SAMPLES: usize = 10_000;
+const DEFAULT_CPU_THRESHOLD_PERCENT: f64 = 80.0;
+const DEFAULT_MEMORY_THRESHOLD_PERCENT: f64 = 85.0;
+const MAX_HEALTH_HISTORY_SIZE: usize = 10_000;
+const STATUS_WINDOW_DAYS: u64 = 30;
+const STATUS_MAX_INCIDENTS: usize = 10;
+
+// Constants: Alerts for the same service within this many seconds of an open
+// incident's latest alert join that incident instead of opening a new one
+const INCIDENT_CORRELATION_WINDOW_SECONDS: u64 = 15 * 60;
+const INCIDENT_STATUSES: [&str; 4] = ["open", "investigating", "mitigated", "resolved"];
+// The service threshold alerts on host metrics are attributed to
+const HOST_SERVICE: &str = "host";
+
+// Constants: The Prometheus endpoint, served while the tools are served over
+// stdio when MCP_METRICS_ADDRESS is set
+const METRICS_ADDRESS_ENV: &str = "MCP_METRICS_ADDRESS";
+const METRICS_LABELS_ENV: &str = "MCP_METRICS_LABELS";
+const METRICS_PATH: &str = "/metrics";
+// Other servers POST a MetricsPush here, Pushgateway style
+const PUSH_PATH: &str = "/metrics/push";
+const SEVERITIES: [&str; 3] = ["info", "warning", "critical"];
+
+// Constants: Where alert thresholds are persisted between runs, and the
+// metrics they can be set on, with the label and unit used in alerts
+const THRESHOLDS_FILE_ENV: &str = "MCP_ALERT_THRESHOLDS_FILE";
+const DEFAULT_THRESHOLDS_FILE: &str = "./data/alert_thresholds.json";
+const THRESHOLD_METRICS: [(&str, &str, &str); 8] = [
+    ("cpu_usage_percent", "CPU Usage", "%"),
+    ("memory_usage_percent", "Memory Usage", "%"),
+    ("disk_usage_percent", "Disk Usage", "%"),
+    ("process_rss_bytes", "Server Memory", " bytes"),
+    ("network_bytes_sent", "Network Traffic Sent", " bytes"),
+    (
+        "network_bytes_received",
+        "Network Traffic Received",
+        " bytes",
+    ),
+    ("active_connections", "Active Connections", ""),
+    ("uptime_seconds", "Uptime", "s"),
+];
+
+// Constants: Where alert sinks are configured, and how long a webhook may
+// take to accept an alert
+const ALERT_SINKS_FILE_ENV: &str = "MCP_ALERT_SINKS_FILE";
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+const ALERT_NOTIFICATION_CAPACITY: usize = 64;
+const ALERT_LOGGER: &str = "alerts";
+
+// Constants: The MCP endpoint whose ping_database tool checks the database
+// service, e.g. example 9 run with MCP_TRANSPORT=http, and how long a health
+// probe may take
+const DATABASE_HEALTH_URL_ENV: &str = "MCP_DATABASE_HEALTH_URL";
+const DATABASE_PING_TOOL: &str = "ping_database";
+const HEALTH_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Constants: Rules deriving metrics from log events, read from a JSON array
+// at startup. Each rule keeps at most MAX_LOG_METRIC_SERIES label
+// combinations, so a label taken from a field like request_id cannot grow
+// the registry without bound.
+const LOG_METRIC_RULES_FILE_ENV: &str = "MCP_LOG_METRIC_RULES_FILE";
+const MAX_LOG_METRIC_SERIES: usize = 100;
+const MAX_LOG_EVENTS_PER_INGEST: usize = 1000;
 
 // Struct: SystemMetrics
 //
@@ -36,7 +139,9 @@ const ALERT_THRESHOLD_MEMORY_PERCENT: f64 = 85.0;
 // Fields:
 //     timestamp: Unix timestamp when metrics were collected
 //     cpu_usage_percent: CPU utilization as a percentage (0.0-100.0)
+//     cpu_per_core_percent: Utilization of each logical core (0.0-100.0)
 //     memory_usage_percent: Memory utilization as a percentage (0.0-100.0)
+//     process_rss_bytes: Resident memory of the monitoring server itself
 //     disk_usage_percent: Disk space utilization as a percentage (0.0-100.0)
 //     network_bytes_sent: Total bytes sent over network interfaces
 //     network_bytes_received: Total bytes received over network interfaces
@@ -46,7 +151,12 @@ const ALERT_THRESHOLD_MEMORY_PERCENT: f64 = 85.0;
 pub struct SystemMetrics {
     pub timestamp: u64,
     pub cpu_usage_percent: f64,
+    // Snapshots taken before per-core and RSS figures were collected lack them
+    #[serde(default)]
+    pub cpu_per_core_percent: Vec<f64>,
     pub memory_usage_percent: f64,
+    #[serde(default)]
+    pub process_rss_bytes: u64,
     pub disk_usage_percent: f64,
     pub network_bytes_sent: u64,
     pub network_bytes_received: u64,
@@ -54,6 +164,288 @@ pub struct SystemMetrics {
     pub uptime_seconds: u64,
 }
 
+// Enum: Resolution
+//
+// The granularity get_metrics_history returns: every sample, or averages
+// over each minute or hour.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    Raw,
+    Minute,
+    Hour,
+}
+
+// Struct: MetricsPoint
+//
+// A point of the metrics history: a sample, or the average of the samples in
+// a minute or hour. Averaged points carry the start of their period as the
+// timestamp, and the latest network totals and uptime of the period.
+//
+// Fields:
+//     metrics: The sample or averaged metrics
+//     samples: How many raw samples the point stands for
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetricsPoint {
+    #[serde(flatten)]
+    pub metrics: SystemMetrics,
+    pub samples: u32,
+}
+
+// Struct: MetricsStore
+//
+// The metrics history, split into retention tiers. compact() moves samples
+// from one tier to the next as they age, a whole minute or hour at a time.
+//
+// Fields:
+//     raw: Samples from the last hour, oldest first
+//     minute: 1-minute averages from the last day, oldest first
+//     hourly: Hourly averages from the last 30 days, oldest first
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MetricsStore {
+    pub raw: Vec<SystemMetrics>,
+    pub minute: Vec<MetricsPoint>,
+    pub hourly: Vec<MetricsPoint>,
+}
+
+impl MetricsStore {
+    // Function: push
+    //
+    // Adds a sample, dropping the oldest raw samples beyond the cap.
+    fn push(&mut self, metrics: SystemMetrics) {
+        self.raw.push(metrics);
+        let excess = self.raw.len().saturating_sub(MAX_RAW_METRIC_SAMPLES);
+        self.raw.drain(..excess);
+    }
+
+    // Function: compact
+    //
+    // Averages raw samples older than an hour into minutes, minutes older
+    // than a day into hours, and drops hours older than 30 days. Cutoffs are
+    // aligned to the period, so a minute or hour is never split across tiers.
+    //
+    // Arguments:
+    //     now: The current Unix timestamp
+    fn compact(&mut self, now: u64) {
+        let cutoff = align(now.saturating_sub(RAW_RETENTION_SECONDS), 60);
+        let aged = self.raw.partition_point(|m| m.timestamp < cutoff);
+        let aged: Vec<SystemMetrics> = self.raw.drain(..aged).collect();
+        self.minute
+            .extend(downsample(aged.iter().map(|m| (m, 1)), 60));
+
+        let cutoff = align(now.saturating_sub(MINUTE_RETENTION_SECONDS), 3600);
+        let aged = self
+            .minute
+            .partition_point(|p| p.metrics.timestamp < cutoff);
+        let aged: Vec<MetricsPoint> = self.minute.drain(..aged).collect();
+        self.hourly.extend(downsample(
+            aged.iter().map(|p| (&p.metrics, p.samples)),
+            3600,
+        ));
+
+        let cutoff = now.saturating_sub(HOUR_RETENTION_SECONDS);
+        let expired = self
+            .hourly
+            .partition_point(|p| p.metrics.timestamp < cutoff);
+        self.hourly.drain(..expired);
+    }
+
+    // Function: history
+    //
+    // The most recent points at a resolution. Coarser resolutions average
+    // the newer tiers on the fly, so they reach up to the latest sample.
+    //
+    // Arguments:
+    //     resolution: Every sample, or minute or hour averages
+    //     limit: Maximum number of points to return
+    //
+    // Returns:
+    //     Points oldest first
+    fn history(&self, resolution: Resolution, limit: usize) -> Vec<MetricsPoint> {
+        let raw = self.raw.iter().map(|m| (m, 1));
+        let points: Vec<MetricsPoint> = match resolution {
+            Resolution::Raw => raw
+                .map(|(metrics, samples)| MetricsPoint {
+                    metrics: metrics.clone(),
+                    samples,
+                })
+                .collect(),
+            Resolution::Minute => self
+                .minute
+                .iter()
+                .cloned()
+                .chain(downsample(raw, 60))
+                .collect(),
+            Resolution::Hour => {
+                let minutes = self.minute.iter().map(|p| (&p.metrics, p.samples));
+                self.hourly
+                    .iter()
+                    .cloned()
+                    .chain(downsample(minutes.chain(raw), 3600))
+                    .collect()
+            }
+        };
+        let start = points.len().saturating_sub(limit);
+        points[start..].to_vec()
+    }
+}
+
+// Struct: MetricSummary
+//
+// Statistics for one metric over a time window, computed from the finest
+// retention tier that covers the whole window. Averaged points count once
+// per sample they stand for, so on coarser tiers the figures are
+// approximate and min and max can miss short spikes.
+//
+// Fields:
+//     metric_name: The summarized metric
+//     window_seconds: How far back the window reaches
+//     resolution: The retention tier the figures come from
+//     samples: Raw samples the window covers; the statistics are None if 0
+//     from, to: Timestamps of the first and last point in the window
+//     min, max, avg, p50, p95, p99: The statistics
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MetricSummary {
+    pub metric_name: String,
+    pub window_seconds: u64,
+    pub resolution: Resolution,
+    pub samples: u64,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub avg: Option<f64>,
+    pub p50: Option<f64>,
+    pub p95: Option<f64>,
+    pub p99: Option<f64>,
+}
+
+// Function: summarize
+//
+// Computes a MetricSummary from the points in the window.
+//
+// Arguments:
+//     metric_name: A metric metric_value knows
+//     window_seconds: Length of the window
+//     resolution: The tier the points come from
+//     points: Points inside the window, oldest first
+fn summarize(
+    metric_name: &str,
+    window_seconds: u64,
+    resolution: Resolution,
+    points: &[MetricsPoint],
+) -> MetricSummary {
+    let mut values: Vec<(f64, u64)> = points
+        .iter()
+        .filter_map(|p| Some((metric_value(&p.metrics, metric_name)?, p.samples as u64)))
+        .collect();
+    values.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let samples: u64 = values.iter().map(|(_, n)| n).sum();
+
+    // Nearest-rank percentile, with each value repeated for its samples
+    let percentile = |p: f64| {
+        let rank = ((p / 100.0 * samples as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        values.iter().find_map(|(value, n)| {
+            seen += n;
+            (seen >= rank).then_some(*value)
+        })
+    };
+    let weighted_sum: f64 = values.iter().map(|(value, n)| value * *n as f64).sum();
+    MetricSummary {
+        metric_name: metric_name.to_string(),
+        window_seconds,
+        resolution,
+        samples,
+        from: points.first().map(|p| p.metrics.timestamp),
+        to: points.last().map(|p| p.metrics.timestamp),
+        min: values.first().map(|(value, _)| *value),
+        max: values.last().map(|(value, _)| *value),
+        avg: (samples > 0).then(|| weighted_sum / samples as f64),
+        p50: percentile(50.0),
+        p95: percentile(95.0),
+        p99: percentile(99.0),
+    }
+}
+
+// Function: align
+//
+// Rounds a timestamp down to the start of its period.
+fn align(timestamp: u64, period_seconds: u64) -> u64 {
+    timestamp - timestamp % period_seconds
+}
+
+// Function: downsample
+//
+// Averages points into one point per period, weighting each point by the
+// samples it stands for.
+//
+// Arguments:
+//     points: Metrics with their sample counts, oldest first
+//     period_seconds: Length of each period
+//
+// Returns:
+//     One averaged point per period that has points, oldest first
+fn downsample<'a>(
+    points: impl Iterator<Item = (&'a SystemMetrics, u32)>,
+    period_seconds: u64,
+) -> Vec<MetricsPoint> {
+    let mut periods: Vec<(u64, Vec<(&SystemMetrics, u32)>)> = Vec::new();
+    for (metrics, samples) in points {
+        let start = align(metrics.timestamp, period_seconds);
+        match periods.last_mut() {
+            Some((period, members)) if *period == start => members.push((metrics, samples)),
+            _ => periods.push((start, vec![(metrics, samples)])),
+        }
+    }
+    periods
+        .into_iter()
+        .map(|(start, members)| average(start, &members))
+        .collect()
+}
+
+// Function: average
+//
+// Averages the members of one period. Per-core figures are averaged only
+// when every member reports the same number of cores.
+fn average(start: u64, members: &[(&SystemMetrics, u32)]) -> MetricsPoint {
+    let samples: u32 = members.iter().map(|(_, n)| n).sum();
+    let mean = |value: &dyn Fn(&SystemMetrics) -> f64| {
+        members
+            .iter()
+            .map(|(m, n)| value(m) * *n as f64)
+            .sum::<f64>()
+            / samples.max(1) as f64
+    };
+    let latest = members[members.len() - 1].0;
+    let cores = latest.cpu_per_core_percent.len();
+    let cpu_per_core_percent = if members
+        .iter()
+        .all(|(m, _)| m.cpu_per_core_percent.len() == cores)
+    {
+        (0..cores)
+            .map(|core| mean(&|m| m.cpu_per_core_percent[core]))
+            .collect()
+    } else {
+        latest.cpu_per_core_percent.clone()
+    };
+    MetricsPoint {
+        metrics: SystemMetrics {
+            timestamp: start,
+            cpu_usage_percent: mean(&|m| m.cpu_usage_percent),
+            cpu_per_core_percent,
+            memory_usage_percent: mean(&|m| m.memory_usage_percent),
+            process_rss_bytes: mean(&|m| m.process_rss_bytes as f64) as u64,
+            disk_usage_percent: mean(&|m| m.disk_usage_percent),
+            network_bytes_sent: latest.network_bytes_sent,
+            network_bytes_received: latest.network_bytes_received,
+            active_connections: mean(&|m| m.active_connections as f64).round() as u32,
+            uptime_seconds: latest.uptime_seconds,
+        },
+        samples,
+    }
+}
+
 // Struct: HealthCheckResult
 //
 // Represents the result of a health check operation.
@@ -85,6 +477,7 @@ pub struct HealthCheckResult {
 //     severity: Alert severity level ("info", "warning", "critical")
 //     title: Brief description of the alert
 //     description: Detailed description of the issue
+//     service: The service the alert concerns; incidents group by it
 //     metric_name: Name of the metric that triggered the alert
 //     threshold: The threshold value that was exceeded
 //     current_value: The current value of the metric
@@ -95,187 +488,1885 @@ pub struct Alert {
     pub severity: String, // "info", "warning", "critical"
     pub title: String,
     pub description: String,
+    #[serde(default = "host_service")]
+    pub service: String,
     pub metric_name: String,
     pub threshold: f64,
     pub current_value: f64,
     pub timestamp: u64,
 }
 
-// Struct: Tool
+fn host_service() -> String {
+    HOST_SERVICE.to_string()
+}
+
+// Struct: Incident
 //
-// Represents an MCP tool that can be called by clients.
-// This follows the MCP specification for tool definitions.
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Tool {
-    pub name: String,
+// Related alerts grouped into one problem to work on: alerts for the same
+// service that arrive within the correlation window of each other.
+//
+// Fields:
+//     id: Unique identifier, e.g. "INC-3"
+//     title: Taken from the alert that opened the incident
+//     service: The service all of the incident's alerts concern
+//     severity: The highest severity among the incident's alerts
+//     status: "open", "investigating", "mitigated" or "resolved"
+//     assignee: Who is working on the incident, if anyone
+//     alerts: Every alert grouped into the incident, oldest first
+//     timeline: What happened to the incident, oldest first
+//     opened_at: When the first alert fired
+//     resolved_at: When the incident was resolved, if it is
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Incident {
+    pub id: String,
+    pub title: String,
+    pub service: String,
+    pub severity: String,
+    pub status: String,
+    pub assignee: Option<String>,
+    pub alerts: Vec<Alert>,
+    pub timeline: Vec<TimelineEntry>,
+    pub opened_at: u64,
+    pub resolved_at: Option<u64>,
+}
+
+// Struct: TimelineEntry
+//
+// One event in an incident's history.
+//
+// Fields:
+//     timestamp: When it happened
+//     kind: "opened", "alert", "status", "assigned" or "note"
+//     author: Who made the change, for changes made through tools
+//     detail: Human-readable description of the event
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimelineEntry {
+    pub timestamp: u64,
+    pub kind: String,
+    pub author: Option<String>,
+    pub detail: String,
+}
+
+impl Incident {
+    // Function: open
+    //
+    // Starts an incident from the alert that opened it.
+    fn open(id: String, alert: Alert) -> Self {
+        let mut incident = Self {
+            id,
+            title: alert.title.clone(),
+            service: alert.service.clone(),
+            severity: alert.severity.clone(),
+            status: "open".to_string(),
+            assignee: None,
+            alerts: Vec::new(),
+            timeline: Vec::new(),
+            opened_at: alert.timestamp,
+            resolved_at: None,
+        };
+        incident.record(alert.timestamp, "opened", None, &alert.title);
+        incident.add_alert(alert);
+        incident
+    }
+
+    // Function: accepts
+    //
+    // Whether an alert belongs to this incident: same service, incident not
+    // resolved, and close enough in time to the incident's latest alert.
+    fn accepts(&self, alert: &Alert) -> bool {
+        let latest = self.alerts.last().map_or(self.opened_at, |a| a.timestamp);
+        self.status != "resolved"
+            && self.service == alert.service
+            && alert.timestamp.abs_diff(latest) <= INCIDENT_CORRELATION_WINDOW_SECONDS
+    }
+
+    fn add_alert(&mut self, alert: Alert) {
+        if severity_rank(&alert.severity) > severity_rank(&self.severity) {
+            self.severity = alert.severity.clone();
+        }
+        let detail = format!("{} ({}): {}", alert.id, alert.severity, alert.description);
+        self.record(alert.timestamp, "alert", None, &detail);
+        self.alerts.push(alert);
+    }
+
+    fn record(&mut self, timestamp: u64, kind: &str, author: Option<&str>, detail: &str) {
+        self.timeline.push(TimelineEntry {
+            timestamp,
+            kind: kind.to_string(),
+            author: author.map(str::to_string),
+            detail: detail.to_string(),
+        });
+    }
+
+    // Function: duration_seconds
+    //
+    // How long the incident lasted, or has lasted so far at `now`.
+    fn duration_seconds(&self, now: u64) -> u64 {
+        self.resolved_at
+            .unwrap_or(now)
+            .saturating_sub(self.opened_at)
+    }
+}
+
+// Function: severity_rank
+//
+// Orders severities from "info" (lowest) to "critical".
+fn severity_rank(severity: &str) -> usize {
+    SEVERITIES.iter().position(|s| *s == severity).unwrap_or(0)
+}
+
+// Struct: AlertThreshold
+//
+// Raises an alert of the given severity whenever a metric exceeds a value.
+//
+// Fields:
+//     metric_name: The SystemMetrics field to watch, e.g. "cpu_usage_percent"
+//     threshold: Alerts are raised when the metric is above this value
+//     severity: Severity of the alerts raised ("info", "warning", "critical")
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AlertThreshold {
+    pub metric_name: String,
+    pub threshold: f64,
+    pub severity: String,
+}
+
+impl AlertThreshold {
+    fn new(metric_name: &str, threshold: f64, severity: &str) -> Self {
+        Self {
+            metric_name: metric_name.to_string(),
+            threshold,
+            severity: severity.to_string(),
+        }
+    }
+}
+
+// Function: default_thresholds
+//
+// The thresholds a server starts with when none have been persisted.
+fn default_thresholds() -> BTreeMap<String, AlertThreshold> {
+    [
+        AlertThreshold::new(
+            "cpu_usage_percent",
+            DEFAULT_CPU_THRESHOLD_PERCENT,
+            "warning",
+        ),
+        AlertThreshold::new(
+            "memory_usage_percent",
+            DEFAULT_MEMORY_THRESHOLD_PERCENT,
+            "critical",
+        ),
+    ]
+    .into_iter()
+    .map(|threshold| (threshold.metric_name.clone(), threshold))
+    .collect()
+}
+
+// Function: metric_value
+//
+// Reads the metric an alert threshold refers to, or None if there is no
+// such metric.
+fn metric_value(metrics: &SystemMetrics, metric_name: &str) -> Option<f64> {
+    let value = match metric_name {
+        "cpu_usage_percent" => metrics.cpu_usage_percent,
+        "memory_usage_percent" => metrics.memory_usage_percent,
+        "disk_usage_percent" => metrics.disk_usage_percent,
+        "process_rss_bytes" => metrics.process_rss_bytes as f64,
+        "network_bytes_sent" => metrics.network_bytes_sent as f64,
+        "network_bytes_received" => metrics.network_bytes_received as f64,
+        "active_connections" => metrics.active_connections as f64,
+        "uptime_seconds" => metrics.uptime_seconds as f64,
+        _ => return None,
+    };
+    Some(value)
+}
+
+// Struct: MetricsPush
+//
+// Metrics another server pushes to POST /metrics/push, with the alerts it
+// raised on them. Each push replaces the source's previous metrics.
+//
+// Fields:
+//     source: The pushing server; its alerts are attributed to this service
+//     metrics: Gauge values by Prometheus metric name
+//     alerts: Alerts that started firing since the previous push
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetricsPush {
+    pub source: String,
+    pub metrics: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub alerts: Vec<PushedAlert>,
+}
+
+// Struct: PushedAlert
+//
+// An alert raised by a pushing server; the monitoring server gives it an ID
+// and timestamp when it arrives.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PushedAlert {
+    pub severity: String,
+    pub title: String,
     pub description: String,
-    pub input_schema: Value,
+    pub metric_name: String,
+    pub threshold: f64,
+    pub current_value: f64,
 }
 
-// Struct: MonitoringServer
+// Struct: HealthRecord
 //
-// The main monitoring server that provides comprehensive system monitoring
-// capabilities through MCP tools. This server demonstrates real-world
-// monitoring patterns used in production systems.
+// A single health check outcome kept for availability reporting.
 //
 // Fields:
-//     name: Server name for identification
-//     version: Server version for tracking
-//     metrics_history: Thread-safe storage for historical metrics
-//     active_alerts: Thread-safe storage for current alerts
-//     services_to_monitor: List of services to perform health checks on
-//     start_time: Server start time for uptime calculations
-pub struct MonitoringServer {
-    #[allow(dead_code)]
-    name: String,
-    #[allow(dead_code)]
-    version: String,
-    metrics_history: Arc<Mutex<Vec<SystemMetrics>>>,
-    active_alerts: Arc<Mutex<Vec<Alert>>>,
-    services_to_monitor: Vec<String>,
-    start_time: SystemTime,
+//     service_name: Name of the service that was checked
+//     status: Health status at the time of the check
+//     timestamp: When the check was performed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HealthRecord {
+    pub service_name: String,
+    pub status: String,
+    pub timestamp: u64,
 }
 
-impl Default for MonitoringServer {
+// Struct: ServiceStatus
+//
+// One service's row on the status page.
+//
+// Fields:
+//     service_name: Name of the service
+//     current_status: Status from the most recent check, or "unknown"
+//     last_checked: When the service was last checked
+//     availability_percent: Share of checks in the window that were not
+//         "unhealthy", or None when the service was never checked
+//     checks_in_window: Number of checks the availability is based on
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServiceStatus {
+    pub service_name: String,
+    pub current_status: String,
+    pub last_checked: Option<u64>,
+    pub availability_percent: Option<f64>,
+    pub checks_in_window: usize,
+}
+
+// Struct: StatusReport
+//
+// A publishable summary of service health, recent incidents and availability.
+//
+// Fields:
+//     generated_at: When the report was generated
+//     overall_status: "operational", "degraded", "major_outage" or "unknown"
+//     window_days: Length of the availability window in days
+//     services: Status and availability per monitored service
+//     incidents: Most recent alerts in the window, newest first
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StatusReport {
+    pub generated_at: u64,
+    pub overall_status: String,
+    pub window_days: u64,
+    pub services: Vec<ServiceStatus>,
+    pub incidents: Vec<Alert>,
+}
+
+// Trait: MetricsCollector
+//
+// A source of system metrics. The server reads the host through
+// SysinfoCollector; tests and demos can use SimulatedCollector instead.
+// The server fills in timestamp and uptime_seconds itself.
+pub trait MetricsCollector: Send + Sync {
+    fn collect(&self) -> BoxFuture<'_, Result<SystemMetrics, String>>;
+}
+
+// Struct: SysinfoState
+//
+// The sysinfo handles a SysinfoCollector refreshes on every collection.
+//
+// Fields:
+//     system: CPU, memory and process figures
+//     disks: Mounted disks and their free space
+//     networks: Network interfaces and their byte counters
+//     cpu_refreshed: When CPU usage was last read
+struct SysinfoState {
+    system: System,
+    disks: Disks,
+    networks: Networks,
+    cpu_refreshed: Instant,
+}
+
+// Struct: SysinfoCollector
+//
+// Collects real CPU, memory, disk and network figures with the sysinfo crate.
+//
+// Fields:
+//     state: The sysinfo handles, behind a lock so collections don't overlap
+pub struct SysinfoCollector {
+    state: Mutex<SysinfoState>,
+}
+
+impl Default for SysinfoCollector {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl MonitoringServer {
+impl SysinfoCollector {
     // Function: new
     //
-    // Creates a new monitoring server instance with default configuration.
-    // This initializes all the necessary data structures and sets up the
-    // monitoring infrastructure.
-    //
-    // Returns:
-    //     A new MonitoringServer instance ready to handle monitoring requests.
+    // Takes the first CPU reading, which later readings are measured against.
     pub fn new() -> Self {
+        let mut system = System::new();
+        system.refresh_cpu_usage();
         Self {
-            name: "Monitoring and Metrics Server".to_string(),
-            version: "1.0.0".to_string(),
-            metrics_history: Arc::new(Mutex::new(Vec::new())),
-            active_alerts: Arc::new(Mutex::new(Vec::new())),
-            services_to_monitor: vec![
-                "database".to_string(),
-                "web_server".to_string(),
-                "cache".to_string(),
-                "message_queue".to_string(),
-            ],
-            start_time: SystemTime::now(),
+            state: Mutex::new(SysinfoState {
+                system,
+                disks: Disks::new_with_refreshed_list(),
+                networks: Networks::new_with_refreshed_list(),
+                cpu_refreshed: Instant::now(),
+            }),
         }
     }
+}
 
-    // Function: list_tools
+impl MetricsCollector for SysinfoCollector {
+    fn collect(&self) -> BoxFuture<'_, Result<SystemMetrics, String>> {
+        Box::pin(async move {
+            // CPU usage is the change between two readings, which sysinfo
+            // needs at least MINIMUM_CPU_UPDATE_INTERVAL apart
+            let since = self
+                .state
+                .lock()
+                .map_err(|e| format!("Failed to acquire collector lock: {}", e))?
+                .cpu_refreshed
+                .elapsed();
+            if let Some(wait) = MINIMUM_CPU_UPDATE_INTERVAL.checked_sub(since) {
+                sleep(wait).await;
+            }
+
+            let mut state = self
+                .state
+                .lock()
+                .map_err(|e| format!("Failed to acquire collector lock: {}", e))?;
+            let SysinfoState {
+                system,
+                disks,
+                networks,
+                cpu_refreshed,
+            } = &mut *state;
+
+            system.refresh_cpu_usage();
+            system.refresh_memory();
+            *cpu_refreshed = Instant::now();
+            let pid = sysinfo::get_current_pid()?;
+            system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+            disks.refresh(true);
+            networks.refresh(true);
+
+            let (disk_total, disk_available) = disks.list().iter().fold((0, 0), |acc, disk| {
+                (acc.0 + disk.total_space(), acc.1 + disk.available_space())
+            });
+
+            Ok(SystemMetrics {
+                timestamp: 0,
+                cpu_usage_percent: system.global_cpu_usage() as f64,
+                cpu_per_core_percent: system
+                    .cpus()
+                    .iter()
+                    .map(|cpu| cpu.cpu_usage() as f64)
+                    .collect(),
+                memory_usage_percent: percent(system.used_memory(), system.total_memory()),
+                process_rss_bytes: system.process(pid).map_or(0, |process| process.memory()),
+                disk_usage_percent: percent(disk_total - disk_available, disk_total),
+                network_bytes_sent: networks
+                    .list()
+                    .values()
+                    .map(|data| data.total_transmitted())
+                    .sum(),
+                network_bytes_received: networks
+                    .list()
+                    .values()
+                    .map(|data| data.total_received())
+                    .sum(),
+                active_connections: count_established_connections(),
+                uptime_seconds: 0,
+            })
+        })
+    }
+}
+
+// Function: percent
+//
+// Share of `part` in `total` as a percentage, or 0 when there is no total.
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 / total as f64 * 100.0
+}
+
+// Function: count_established_connections
+//
+// Counts established TCP connections from /proc/net, since sysinfo does not
+// report connections. Always 0 on systems without /proc.
+fn count_established_connections() -> u32 {
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .map(|table| {
+            // The fourth column is the socket state; 01 is ESTABLISHED
+            table
+                .lines()
+                .skip(1)
+                .filter(|line| line.split_whitespace().nth(3) == Some("01"))
+                .count() as u32
+        })
+        .sum()
+}
+
+// Struct: SimulatedCollector
+//
+// Generates plausible metrics from the clock, for tests and for machines
+// where real figures are not wanted.
+//
+// Fields:
+//     cpu_usage_percent: Fixed CPU usage to report instead of the generated one
+#[derive(Default)]
+pub struct SimulatedCollector {
+    pub cpu_usage_percent: Option<f64>,
+}
+
+impl MetricsCollector for SimulatedCollector {
+    fn collect(&self) -> BoxFuture<'_, Result<SystemMetrics, String>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let cpu_usage_percent = self
+            .cpu_usage_percent
+            .unwrap_or(20.0 + (timestamp % 60) as f64 * 0.8); // Varies between 20-68%
+
+        Box::pin(async move {
+            Ok(SystemMetrics {
+                timestamp,
+                cpu_usage_percent,
+                cpu_per_core_percent: vec![cpu_usage_percent; 4],
+                memory_usage_percent: 45.0 + (timestamp % 40) as f64 * 0.5, // Varies between 45-65%
+                process_rss_bytes: 32 * 1024 * 1024,
+                disk_usage_percent: 35.0 + (timestamp % 10) as f64 * 0.2, // Varies between 35-37%
+                network_bytes_sent: 1024 * 1024 * (timestamp % 1000),
+                network_bytes_received: 2 * 1024 * 1024 * (timestamp % 1000),
+                active_connections: 50 + (timestamp % 100) as u32, // 50-149 connections
+                uptime_seconds: 0,
+            })
+        })
+    }
+}
+
+// Trait: AlertSink
+//
+// Somewhere alerts are delivered to once they are raised. The server routes
+// each alert to the sinks configured for its severity.
+pub trait AlertSink: Send + Sync {
+    fn name(&self) -> &str;
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), String>>;
+}
+
+// Enum: WebhookFormat
+//
+// The payload a webhook expects: Slack incoming-webhook messages, PagerDuty
+// Events API v2 triggers, or the alert itself as JSON.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    #[default]
+    Json,
+    Slack,
+    PagerDuty,
+}
+
+// Struct: WebhookSink
+//
+// Posts alerts to an HTTP webhook.
+//
+// Fields:
+//     name: The sink's name in logs and metrics, after its format
+//     url: Where alerts are posted
+//     format: The payload the webhook expects
+//     routing_key: The PagerDuty integration key, for the pagerduty format
+//     client: HTTP client with a timeout
+pub struct WebhookSink {
+    name: String,
+    url: reqwest::Url,
+    format: WebhookFormat,
+    routing_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    // Function: new
     //
-    // Returns the list of available monitoring tools that clients can call.
-    // Each tool provides specific monitoring capabilities like metrics collection,
-    // health checks, alerting, and system status reporting.
+    // Arguments:
+    //     url: Where alerts are posted, over http or https
+    //     format: The payload the webhook expects
+    //     routing_key: Required for the pagerduty format, ignored otherwise
     //
     // Returns:
-    //     A vector of Tool structs describing all available monitoring tools.
-    pub fn list_tools(&self) -> Vec<Tool> {
-        vec![
+    //     The sink, or an error for an invalid URL or missing routing key
+    pub fn new(
+        url: &str,
+        format: WebhookFormat,
+        routing_key: Option<String>,
+    ) -> Result<Self, String> {
+        let url = reqwest::Url::parse(url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Webhook URL must use http or https: {}", url));
+        }
+        if format == WebhookFormat::PagerDuty && routing_key.is_none() {
+            return Err("PagerDuty webhooks need a routing_key".to_string());
+        }
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let name = match format {
+            WebhookFormat::Json => "webhook",
+            WebhookFormat::Slack => "slack",
+            WebhookFormat::PagerDuty => "pagerduty",
+        };
+        Ok(Self {
+            name: name.to_string(),
+            url,
+            format,
+            routing_key,
+            client,
+        })
+    }
+
+    // Function: payload
+    //
+    // Builds the request body for an alert in the webhook's format.
+    fn payload(&self, alert: &Alert) -> Value {
+        match self.format {
+            WebhookFormat::Json => serde_json::json!(alert),
+            WebhookFormat::Slack => {
+                let color = match alert.severity.as_str() {
+                    "critical" => "danger",
+                    "warning" => "warning",
+                    _ => "good",
+                };
+                let field = |title: &str, value: String| serde_json::json!({"title": title, "value": value, "short": true});
+                serde_json::json!({
+                    "text": format!("[{}] {}", alert.severity.to_uppercase(), alert.title),
+                    "attachments": [{
+                        "color": color,
+                        "title": alert.title,
+                        "text": alert.description,
+                        "fields": [
+                            field("Service", alert.service.clone()),
+                            field("Metric", alert.metric_name.clone()),
+                            field("Value", alert.current_value.to_string()),
+                            field("Threshold", alert.threshold.to_string()),
+                        ],
+                        "ts": alert.timestamp,
+                    }],
+                })
+            }
+            // Repeated triggers with the same dedup_key update one PagerDuty
+            // incident rather than opening new ones
+            WebhookFormat::PagerDuty => serde_json::json!({
+                "routing_key": self.routing_key,
+                "event_action": "trigger",
+                "dedup_key": alert.id,
+                "payload": {
+                    "summary": format!("{}: {}", alert.title, alert.description),
+                    "source": alert.service,
+                    "severity": alert.severity,
+                    "timestamp": chrono::DateTime::from_timestamp(alert.timestamp as i64, 0)
+                        .map(|t| t.to_rfc3339()),
+                    "component": alert.metric_name,
+                    "custom_details": {
+                        "threshold": alert.threshold,
+                        "current_value": alert.current_value,
+                    },
+                },
+            }),
+        }
+    }
+}
+
+impl AlertSink for WebhookSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.client
+                .post(self.url.clone())
+                .json(&self.payload(alert))
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map(|_| ())
+                .map_err(|e| format!("Webhook {} failed: {}", self.url, e))
+        })
+    }
+}
+
+// Struct: McpNotificationSink
+//
+// Pushes alerts to the connected MCP client as notifications/message log
+// messages, with the alert as the message data.
+//
+// Fields:
+//     sender: The channel the transport forwards to the client
+pub struct McpNotificationSink {
+    sender: broadcast::Sender<LoggingMessageParams>,
+}
+
+impl McpNotificationSink {
+    pub fn new(sender: broadcast::Sender<LoggingMessageParams>) -> Self {
+        Self { sender }
+    }
+}
+
+impl AlertSink for McpNotificationSink {
+    fn name(&self) -> &str {
+        "mcp"
+    }
+
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), String>> {
+        let level = match alert.severity.as_str() {
+            "critical" => LoggingLevel::Critical,
+            "warning" => LoggingLevel::Warning,
+            _ => LoggingLevel::Info,
+        };
+        let message = LoggingMessageParams {
+            level,
+            logger: Some(ALERT_LOGGER.to_string()),
+            data: serde_json::json!(alert),
+        };
+        let sent = self
+            .sender
+            .send(message)
+            .map(|_| ())
+            .map_err(|_| "No MCP client is connected".to_string());
+        Box::pin(async move { sent })
+    }
+}
+
+// Struct: ProbeOutcome
+//
+// What a health probe found.
+//
+// Fields:
+//     status: "healthy", "degraded" or "unhealthy"
+//     message: Why, in a sentence
+//     details: Anything else the probe learned, such as pool statistics
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProbeOutcome {
+    pub status: String,
+    pub message: String,
+    pub details: Option<Value>,
+}
+
+// Trait: HealthProbe
+//
+// Checks one monitored service for real. Services without a probe get a
+// synthetic result, as in the rest of the demo.
+pub trait HealthProbe: Send + Sync {
+    // Function: describe
+    //
+    // Where the probe checks, for the check_type and endpoint of results.
+    fn describe(&self) -> (&str, String);
+
+    fn check(&self) -> BoxFuture<'_, ProbeOutcome>;
+}
+
+// Struct: McpToolProbe
+//
+// Checks a service by calling a tool of its MCP server over the streamable
+// HTTP transport. The tool answers with a JSON object whose status and
+// message fields become the outcome, as ping_database of example 9 does.
+//
+// Fields:
+//     url: The server's MCP endpoint, e.g. http://127.0.0.1:8080/mcp
+//     tool: The tool to call
+//     arguments: What it is called with
+//     session: The MCP session, opened on the first check
+//     client: HTTP client with a timeout
+pub struct McpToolProbe {
+    url: reqwest::Url,
+    tool: String,
+    arguments: Value,
+    session: Mutex<Option<String>>,
+    client: reqwest::Client,
+}
+
+impl McpToolProbe {
+    // Function: new
+    //
+    // Arguments:
+    //     url: The server's MCP endpoint, over http or https
+    //     tool: The tool to call on every check
+    //     arguments: What the tool is called with
+    //
+    // Returns:
+    //     The probe, or an error for an invalid URL
+    pub fn new(url: &str, tool: &str, arguments: Value) -> Result<Self, String> {
+        let url =
+            reqwest::Url::parse(url).map_err(|e| format!("Invalid health check URL: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Health check URL must use http or https: {}", url));
+        }
+        let client = reqwest::Client::builder()
+            .timeout(HEALTH_PROBE_TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            url,
+            tool: tool.to_string(),
+            arguments,
+            session: Mutex::new(None),
+            client,
+        })
+    }
+
+    // Function: post
+    //
+    // Sends one JSON-RPC message, in the session if there is one.
+    async fn post(
+        &self,
+        session: Option<&str>,
+        message: Value,
+    ) -> Result<reqwest::Response, String> {
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .header(
+                reqwest::header::ACCEPT,
+                "application/json, text/event-stream",
+            )
+            .json(&message);
+        if let Some(session) = session {
+            request = request.header(http_transport::SESSION_HEADER, session);
+        }
+        request.send().await.map_err(|e| e.to_string())
+    }
+
+    // Function: open_session
+    //
+    // Initializes a session with the server and returns its id.
+    async fn open_session(&self) -> Result<String, String> {
+        let initialize = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "monitoring", "version": "1.0.0" }
+            }
+        });
+        let response = self
+            .post(None, initialize)
+            .await?
+            .error_for_status()
+            .map_err(|e| e.to_string())?;
+        let session = response
+            .headers()
+            .get(http_transport::SESSION_HEADER)
+            .and_then(|session| session.to_str().ok())
+            .ok_or("the server opened no session")?
+            .to_string();
+        let initialized = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized"
+        });
+        self.post(Some(&session), initialized).await?;
+        Ok(session)
+    }
+
+    // Function: call_tool
+    //
+    // Calls the tool and returns the JSON object it answered with. A server
+    // that forgot the session, e.g. after a restart, gets a new one.
+    async fn call_tool(&self) -> Result<Value, String> {
+        let call = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": self.tool, "arguments": self.arguments }
+        });
+        let mut retried = false;
+        let response = loop {
+            let known = self.session.lock().unwrap().clone();
+            let session = match known {
+                Some(session) => session,
+                None => {
+                    let session = self.open_session().await?;
+                    *self.session.lock().unwrap() = Some(session.clone());
+                    session
+                }
+            };
+            let response = self.post(Some(&session), call.clone()).await?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND && !retried {
+                *self.session.lock().unwrap() = None;
+                retried = true;
+                continue;
+            }
+            break response;
+        };
+
+        let body: Value = response
+            .error_for_status()
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some(message) = body["error"]["message"].as_str() {
+            return Err(message.to_string());
+        }
+        let text = body["result"]["content"][0]["text"]
+            .as_str()
+            .ok_or("the tool returned no text")?;
+        if body["result"]["isError"] == true {
+            return Err(text.to_string());
+        }
+        serde_json::from_str(text).map_err(|e| format!("the tool returned invalid JSON: {}", e))
+    }
+}
+
+impl HealthProbe for McpToolProbe {
+    fn describe(&self) -> (&str, String) {
+        ("mcp_tool", format!("{}#{}", self.url, self.tool))
+    }
+
+    fn check(&self) -> BoxFuture<'_, ProbeOutcome> {
+        Box::pin(async move {
+            match self.call_tool().await {
+                Ok(result) => {
+                    let status = result["status"]
+                        .as_str()
+                        .filter(|status| matches!(*status, "healthy" | "degraded" | "unhealthy"))
+                        .unwrap_or("unknown");
+                    ProbeOutcome {
+                        status: status.to_string(),
+                        message: result["message"]
+                            .as_str()
+                            .unwrap_or("No message")
+                            .to_string(),
+                        details: Some(result),
+                    }
+                }
+                Err(e) => ProbeOutcome {
+                    status: "unhealthy".to_string(),
+                    message: format!("{} failed: {}", self.tool, e),
+                    details: None,
+                },
+            }
+        })
+    }
+}
+
+// Struct: AlertRoute
+//
+// Sends alerts of the listed severities to one sink.
+//
+// Fields:
+//     sink: Where the alerts go
+//     severities: Which alert severities are sent there
+#[derive(Clone)]
+struct AlertRoute {
+    sink: Arc<dyn AlertSink>,
+    severities: Vec<String>,
+}
+
+// Struct: AlertSinkConfig
+//
+// One entry of the alert sinks file, a JSON array such as
+// [{"type": "webhook", "url": "...", "format": "slack", "severities": ["critical"]}].
+// An empty or missing severities list routes every severity.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AlertSinkConfig {
+    Webhook {
+        url: String,
+        #[serde(default)]
+        format: WebhookFormat,
+        #[serde(default)]
+        routing_key: Option<String>,
+        #[serde(default)]
+        severities: Vec<String>,
+    },
+    Mcp {
+        #[serde(default)]
+        severities: Vec<String>,
+    },
+}
+
+// Struct: LogMetricKind
+//
+// Whether a log metric rule counts the events it matches or observes a
+// numeric field of them in a histogram.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogMetricKind {
+    #[default]
+    Counter,
+    Histogram,
+}
+
+// Struct: LogMetricRule
+//
+// Derives a metric from the log events that match it. For example
+// {"name": "tool_failures_total", "level": "warn", "fields": {"status": "^error$"},
+// "labels": ["tool"]} counts failed tool calls by tool, and
+// {"name": "tool_duration_ms", "kind": "histogram", "value_field": "duration_ms",
+// "buckets": [10, 100, 1000]} observes how long tool calls took.
+//
+// Fields:
+//     name: The metric's name, a valid Prometheus name
+//     kind: Whether matching events are counted or a field of them observed
+//     level: The lowest level that matches, e.g. warn for warnings and errors
+//     target: A prefix the event's target must start with
+//     fields: Regular expressions the named fields must match; the message
+//             is a field too, and dotted names reach into objects
+//     value_field: The numeric field a histogram observes
+//     buckets: The histogram's bucket bounds; Prometheus's defaults if empty
+//     labels: Fields copied into the metric's labels, empty when missing
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LogMetricRule {
+    pub name: String,
+    #[serde(default)]
+    pub kind: LogMetricKind,
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+    #[serde(default)]
+    pub value_field: Option<String>,
+    #[serde(default)]
+    pub buckets: Vec<f64>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+// Struct: LogMetricStats
+//
+// What a log metric rule has done since it was defined.
+//
+// Fields:
+//     matched: Events that matched the rule
+//     dropped: Matching events not recorded, for want of a numeric value or
+//              because they would have added a series past the limit
+//     series: Label combinations recorded
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LogMetricStats {
+    pub matched: u64,
+    pub dropped: u64,
+    pub series: usize,
+}
+
+// Struct: CompiledLogRule
+//
+// A log metric rule ready to match events, with what it has recorded.
+struct CompiledLogRule {
+    rule: LogMetricRule,
+    min_level: Option<usize>,
+    patterns: Vec<(String, regex::Regex)>,
+    series: HashSet<Vec<String>>,
+    stats: LogMetricStats,
+}
+
+impl CompiledLogRule {
+    // Function: new
+    //
+    // Checks a rule and compiles its patterns.
+    //
+    // Returns:
+    //     The compiled rule, or an error naming what is wrong with it
+    fn new(rule: LogMetricRule) -> Result<Self, String> {
+        if !is_valid_prometheus_name(&rule.name) {
+            return Err(format!("'{}' is not a valid metric name", rule.name));
+        }
+        let min_level = rule
+            .level
+            .as_deref()
+            .map(|level| {
+                log_level_rank(level).ok_or_else(|| {
+                    format!(
+                        "Unknown level '{}'; use trace, debug, info, warn or error",
+                        level
+                    )
+                })
+            })
+            .transpose()?;
+        let patterns = rule
+            .fields
+            .iter()
+            .map(|(field, pattern)| {
+                regex::Regex::new(pattern)
+                    .map(|regex| (field.clone(), regex))
+                    .map_err(|e| format!("Invalid pattern for field '{}': {}", field, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match (rule.kind, &rule.value_field) {
+            (LogMetricKind::Histogram, None) => {
+                return Err("A histogram needs a value_field to observe".to_string())
+            }
+            (LogMetricKind::Counter, Some(_)) => {
+                return Err("Only histograms observe a value_field".to_string())
+            }
+            _ => {}
+        }
+        if !rule.buckets.windows(2).all(|pair| pair[0] < pair[1])
+            || rule.buckets.iter().any(|bound| !bound.is_finite())
+        {
+            return Err("buckets must be finite and ascending".to_string());
+        }
+        if let Some(label) = rule
+            .labels
+            .iter()
+            .find(|label| !is_valid_prometheus_name(label))
+        {
+            return Err(format!("'{}' is not a valid label name", label));
+        }
+        Ok(Self {
+            rule,
+            min_level,
+            patterns,
+            series: HashSet::new(),
+            stats: LogMetricStats::default(),
+        })
+    }
+
+    // Function: matches
+    //
+    // Whether an event has the rule's level, target and field values.
+    fn matches(&self, event: &Value) -> bool {
+        if let Some(min_level) = self.min_level {
+            let level = event["level"].as_str().and_then(log_level_rank);
+            if level.is_none_or(|level| level < min_level) {
+                return false;
+            }
+        }
+        if let Some(target) = &self.rule.target {
+            let event_target = event["target"].as_str().unwrap_or_default();
+            if !event_target.starts_with(target.as_str()) {
+                return false;
+            }
+        }
+        self.patterns.iter().all(|(field, pattern)| {
+            log_field(event, field).is_some_and(|value| pattern.is_match(&log_field_text(value)))
+        })
+    }
+}
+
+// Function: log_level_rank
+//
+// Orders log levels from trace up to error, whatever their case. Example
+// 10's log streams and other sources may say warning, critical or fatal.
+fn log_level_rank(level: &str) -> Option<usize> {
+    match level.to_ascii_lowercase().as_str() {
+        "trace" => Some(0),
+        "debug" => Some(1),
+        "info" => Some(2),
+        "warn" | "warning" => Some(3),
+        "error" | "critical" | "fatal" => Some(4),
+        _ => None,
+    }
+}
+
+// Function: log_field
+//
+// A field of a log event: one named exactly so, such as otel.status_code,
+// or else the dotted path into nested objects, such as arguments.path.
+fn log_field<'a>(event: &'a Value, name: &str) -> Option<&'a Value> {
+    event
+        .get(name)
+        .or_else(|| name.split('.').try_fold(event, |value, key| value.get(key)))
+}
+
+// Function: log_field_text
+//
+// A field's value as matched by patterns and used in labels.
+fn log_field_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Struct: LogMetrics
+//
+// Applies log metric rules to log events and records what they derive in a
+// metrics registry, the process-wide one unless a test gives its own, so
+// derived metrics are served on the Prometheus endpoint with the rest.
+pub struct LogMetrics {
+    rules: Mutex<Vec<CompiledLogRule>>,
+    registry: Arc<MetricsRegistry>,
+}
+
+impl LogMetrics {
+    pub fn new(registry: Arc<MetricsRegistry>) -> Self {
+        Self {
+            rules: Mutex::new(Vec::new()),
+            registry,
+        }
+    }
+
+    // Function: define
+    //
+    // Adds a rule, replacing the one with the same name.
+    //
+    // Returns:
+    //     The replaced rule, or an error for an invalid rule
+    pub fn define(&self, rule: LogMetricRule) -> Result<Option<LogMetricRule>, String> {
+        let compiled = CompiledLogRule::new(rule)?;
+        let mut rules = self.rules.lock().unwrap();
+        match rules.iter_mut().find(|r| r.rule.name == compiled.rule.name) {
+            Some(existing) => Ok(Some(std::mem::replace(existing, compiled).rule)),
+            None => {
+                rules.push(compiled);
+                Ok(None)
+            }
+        }
+    }
+
+    // Function: remove
+    //
+    // Stops deriving a metric. What it recorded stays in the registry.
+    pub fn remove(&self, name: &str) -> bool {
+        let mut rules = self.rules.lock().unwrap();
+        let before = rules.len();
+        rules.retain(|r| r.rule.name != name);
+        rules.len() != before
+    }
+
+    // Function: rules
+    //
+    // The rules in the order they were defined, with what each has done.
+    pub fn rules(&self) -> Vec<(LogMetricRule, LogMetricStats)> {
+        self.rules
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                let stats = LogMetricStats {
+                    series: r.series.len(),
+                    ..r.stats.clone()
+                };
+                (r.rule.clone(), stats)
+            })
+            .collect()
+    }
+
+    // Function: apply
+    //
+    // Records the metrics of every rule an event matches.
+    //
+    // Returns:
+    //     How many rules recorded something
+    pub fn apply(&self, event: &Value) -> usize {
+        let mut recorded = 0;
+        for rule in self.rules.lock().unwrap().iter_mut() {
+            if !rule.matches(event) {
+                continue;
+            }
+            rule.stats.matched += 1;
+
+            let value = match &rule.rule.value_field {
+                Some(field) => match log_field(event, field).and_then(|value| match value {
+                    Value::String(text) => text.trim().parse::<f64>().ok(),
+                    other => other.as_f64(),
+                }) {
+                    Some(value) => Some(value),
+                    None => {
+                        rule.stats.dropped += 1;
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let label_values: Vec<String> = rule
+                .rule
+                .labels
+                .iter()
+                .map(|label| {
+                    log_field(event, label)
+                        .map(log_field_text)
+                        .unwrap_or_default()
+                })
+                .collect();
+            if !rule.series.contains(&label_values) {
+                if rule.series.len() >= MAX_LOG_METRIC_SERIES {
+                    rule.stats.dropped += 1;
+                    continue;
+                }
+                rule.series.insert(label_values.clone());
+            }
+
+            let labels: Vec<(&str, &str)> = rule
+                .rule
+                .labels
+                .iter()
+                .map(String::as_str)
+                .zip(label_values.iter().map(String::as_str))
+                .collect();
+            match value {
+                Some(value) if rule.rule.buckets.is_empty() => {
+                    self.registry.observe(&rule.rule.name, &labels, value)
+                }
+                Some(value) => self.registry.observe_with_buckets(
+                    &rule.rule.name,
+                    &labels,
+                    value,
+                    &rule.rule.buckets,
+                ),
+                None => self.registry.increment(&rule.rule.name, &labels, 1),
+            }
+            recorded += 1;
+        }
+        recorded
+    }
+}
+
+// Struct: MonitoringServer
+//
+// The main monitoring server that provides comprehensive system monitoring
+// capabilities through MCP tools. This server demonstrates real-world
+// monitoring patterns used in production systems.
+//
+// Fields:
+//     name: Server name for identification
+//     version: Server version for tracking
+//     metrics_history: Thread-safe metrics history in retention tiers
+//     active_alerts: Thread-safe storage for current alerts
+//     health_history: Thread-safe storage for past health check outcomes
+//     services_to_monitor: List of services to perform health checks on
+//     start_time: Server start time for uptime calculations
+//     collector: Where system metrics are read from
+//     alert_thresholds: Thread-safe thresholds keyed by metric name
+//     thresholds_file: Where thresholds are persisted, if anywhere
+//     incidents: Thread-safe storage for incidents grouping related alerts
+//     alert_routes: Which sinks alerts are delivered to, by severity
+//     alert_notifications: Alerts for MCP clients, sent as log messages
+//     pushed_metrics: Thread-safe metrics pushed by other servers, by source
+//     health_probes: Probes that check services for real, by service name
+//     log_metrics: Rules deriving metrics from log events
+pub struct MonitoringServer {
+    #[allow(dead_code)]
+    name: String,
+    #[allow(dead_code)]
+    version: String,
+    metrics_history: Arc<Mutex<MetricsStore>>,
+    active_alerts: Arc<Mutex<Vec<Alert>>>,
+    health_history: Arc<Mutex<Vec<HealthRecord>>>,
+    services_to_monitor: Vec<String>,
+    start_time: SystemTime,
+    collector: Arc<dyn MetricsCollector>,
+    alert_thresholds: Arc<Mutex<BTreeMap<String, AlertThreshold>>>,
+    thresholds_file: Option<PathBuf>,
+    incidents: Arc<Mutex<Vec<Incident>>>,
+    alert_routes: Vec<AlertRoute>,
+    alert_notifications: broadcast::Sender<LoggingMessageParams>,
+    pushed_metrics: Arc<Mutex<BTreeMap<String, BTreeMap<String, f64>>>>,
+    health_probes: BTreeMap<String, Arc<dyn HealthProbe>>,
+    log_metrics: Arc<LogMetrics>,
+}
+
+// Struct: MonitoringState
+//
+// The durable part of the monitoring server, captured by snapshots.
+// Uptime is deliberately excluded: a restored server starts a fresh clock.
+//
+// Fields:
+//     metrics_history: Raw metrics from the last hour, oldest first
+//     minute_history: 1-minute averages from the last day
+//     hourly_history: Hourly averages from the last 30 days
+//     active_alerts: Alerts that have not been cleared
+//     health_history: Health check outcomes used for availability reports
+//     services_to_monitor: Services included in health checks
+//     incidents: Incidents with their alerts and timelines
+#[derive(Serialize, Deserialize, Debug)]
+struct MonitoringState {
+    metrics_history: Vec<SystemMetrics>,
+    #[serde(default)]
+    minute_history: Vec<MetricsPoint>,
+    #[serde(default)]
+    hourly_history: Vec<MetricsPoint>,
+    active_alerts: Vec<Alert>,
+    #[serde(default)]
+    health_history: Vec<HealthRecord>,
+    services_to_monitor: Vec<String>,
+    #[serde(default)]
+    incidents: Vec<Incident>,
+}
+
+impl Default for MonitoringServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MonitoringServer {
+    // Function: new
+    //
+    // Creates a new monitoring server instance with default configuration.
+    // This initializes all the necessary data structures and sets up the
+    // monitoring infrastructure.
+    //
+    // Returns:
+    //     A new MonitoringServer instance ready to handle monitoring requests.
+    pub fn new() -> Self {
+        Self {
+            name: "Monitoring and Metrics Server".to_string(),
+            version: "1.0.0".to_string(),
+            metrics_history: Arc::new(Mutex::new(MetricsStore::default())),
+            active_alerts: Arc::new(Mutex::new(Vec::new())),
+            health_history: Arc::new(Mutex::new(Vec::new())),
+            services_to_monitor: vec![
+                "database".to_string(),
+                "web_server".to_string(),
+                "cache".to_string(),
+                "message_queue".to_string(),
+            ],
+            start_time: SystemTime::now(),
+            collector: Arc::new(SysinfoCollector::new()),
+            alert_thresholds: Arc::new(Mutex::new(default_thresholds())),
+            thresholds_file: None,
+            incidents: Arc::new(Mutex::new(Vec::new())),
+            alert_routes: Vec::new(),
+            alert_notifications: broadcast::channel(ALERT_NOTIFICATION_CAPACITY).0,
+            pushed_metrics: Arc::new(Mutex::new(BTreeMap::new())),
+            health_probes: BTreeMap::new(),
+            log_metrics: Arc::new(LogMetrics::new(metrics::global().clone())),
+        }
+    }
+
+    // Function: with_collector
+    //
+    // Replaces the sysinfo collector, e.g. with a SimulatedCollector in tests.
+    pub fn with_collector(mut self, collector: Arc<dyn MetricsCollector>) -> Self {
+        self.collector = collector;
+        self
+    }
+
+    // Function: with_thresholds_file
+    //
+    // Persists alert thresholds to a JSON file, loading the thresholds
+    // already saved there. A missing file keeps the default thresholds.
+    //
+    // Arguments:
+    //     path: The JSON file thresholds are read from and written to
+    //
+    // Returns:
+    //     The server, or an error if the file exists but cannot be read
+    pub fn with_thresholds_file(mut self, path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                let thresholds: Vec<AlertThreshold> = serde_json::from_slice(&bytes)
+                    .map_err(|e| format!("Invalid thresholds file {}: {}", path.display(), e))?;
+                *self.alert_thresholds.lock().unwrap() = thresholds
+                    .into_iter()
+                    .map(|threshold| (threshold.metric_name.clone(), threshold))
+                    .collect();
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+        self.thresholds_file = Some(path);
+        Ok(self)
+    }
+
+    // Function: with_alert_sink
+    //
+    // Delivers alerts of the given severities to a sink.
+    //
+    // Arguments:
+    //     sink: Where alerts are delivered
+    //     severities: Which severities to deliver; empty for all of them
+    //
+    // Returns:
+    //     The server, or an error for an unknown severity
+    pub fn with_alert_sink(
+        mut self,
+        sink: Arc<dyn AlertSink>,
+        severities: &[String],
+    ) -> Result<Self, String> {
+        if let Some(unknown) = severities
+            .iter()
+            .find(|severity| !SEVERITIES.contains(&severity.as_str()))
+        {
+            return Err(format!(
+                "Unknown severity '{}' for the {} sink; use one of {}",
+                unknown,
+                sink.name(),
+                SEVERITIES.join(", ")
+            ));
+        }
+        let severities = if severities.is_empty() {
+            SEVERITIES.iter().map(|s| s.to_string()).collect()
+        } else {
+            severities.to_vec()
+        };
+        self.alert_routes.push(AlertRoute { sink, severities });
+        Ok(self)
+    }
+
+    // Function: with_alert_sinks_file
+    //
+    // Adds the alert sinks listed in a JSON file of AlertSinkConfig entries.
+    //
+    // Arguments:
+    //     path: The JSON file the sinks are read from
+    //
+    // Returns:
+    //     The server, or an error if the file cannot be read or lists an
+    //     invalid sink
+    pub fn with_alert_sinks_file(mut self, path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let configs: Vec<AlertSinkConfig> = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Invalid alert sinks file {}: {}", path.display(), e))?;
+        for config in configs {
+            let (sink, severities): (Arc<dyn AlertSink>, _) = match config {
+                AlertSinkConfig::Webhook {
+                    url,
+                    format,
+                    routing_key,
+                    severities,
+                } => (
+                    Arc::new(WebhookSink::new(&url, format, routing_key)?),
+                    severities,
+                ),
+                AlertSinkConfig::Mcp { severities } => (
+                    Arc::new(McpNotificationSink::new(self.alert_notifications())),
+                    severities,
+                ),
+            };
+            self = self.with_alert_sink(sink, &severities)?;
+        }
+        Ok(self)
+    }
+
+    // Function: with_health_probe
+    //
+    // Checks a service with a probe instead of a synthetic result, adding
+    // it to the monitored services if it is not among them.
+    //
+    // Arguments:
+    //     service_name: The service the probe checks
+    //     probe: How it is checked
+    pub fn with_health_probe(mut self, service_name: &str, probe: Arc<dyn HealthProbe>) -> Self {
+        if !self.services_to_monitor.iter().any(|s| s == service_name) {
+            self.services_to_monitor.push(service_name.to_string());
+        }
+        self.health_probes.insert(service_name.to_string(), probe);
+        self
+    }
+
+    // Function: with_log_metric_rules_file
+    //
+    // Derives metrics from log events by the rules in a JSON file, an array
+    // of LogMetricRule objects.
+    //
+    // Arguments:
+    //     path: The JSON file the rules are read from
+    //
+    // Returns:
+    //     The server, or an error if the file cannot be read or holds an
+    //     invalid rule
+    pub fn with_log_metric_rules_file(self, path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let rules: Vec<LogMetricRule> = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Invalid log metric rules file {}: {}", path.display(), e))?;
+        for rule in rules {
+            let name = rule.name.clone();
+            self.log_metrics
+                .define(rule)
+                .map_err(|e| format!("Invalid log metric rule {}: {}", name, e))?;
+        }
+        Ok(self)
+    }
+
+    // Function: has_alert_sinks
+    pub fn has_alert_sinks(&self) -> bool {
+        !self.alert_routes.is_empty()
+    }
+
+    // Function: alert_notifications
+    //
+    // The channel MCP notification sinks publish alerts on, for the transport
+    // to forward to the client as notifications/message.
+    pub fn alert_notifications(&self) -> broadcast::Sender<LoggingMessageParams> {
+        self.alert_notifications.clone()
+    }
+
+    // Function: list_tools
+    //
+    // Returns the list of available monitoring tools that clients can call.
+    // Each tool provides specific monitoring capabilities like metrics collection,
+    // health checks, alerting, and system status reporting.
+    //
+    // Returns:
+    //     A vector of Tool structs describing all available monitoring tools.
+    pub fn list_tools(&self) -> Vec<Tool> {
+        vec![
+            Tool {
+                name: "get_current_metrics".to_string(),
+                description:
+                    "Get current system metrics including CPU, memory, disk, and network usage"
+                        .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {},
+                    "additionalProperties": false
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "get_metrics_history".to_string(),
+                description: "Get historical metrics data for trend analysis".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum number of historical records to return",
+                            "minimum": 1,
+                            "maximum": 1000,
+                            "default": 100
+                        },
+                        "resolution": {
+                            "type": "string",
+                            "enum": ["raw", "minute", "hour"],
+                            "description": "Every sample (kept for an hour), or averages per minute (a day) or hour (30 days)",
+                            "default": "raw"
+                        }
+                    },
+                    "additionalProperties": false
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "get_metrics_summary".to_string(),
+                description: "Get min, max, average and percentiles of a metric over a recent time window"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "metric_name": {
+                            "type": "string",
+                            "enum": THRESHOLD_METRICS.map(|(name, _, _)| name),
+                            "description": "The metric to summarize"
+                        },
+                        "window_seconds": {
+                            "type": "integer",
+                            "description": "How far back to look; windows over an hour use minute averages, over a day hourly averages",
+                            "minimum": 1,
+                            "maximum": HOUR_RETENTION_SECONDS,
+                            "default": 3600
+                        }
+                    },
+                    "required": ["metric_name"],
+                    "additionalProperties": false
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "perform_health_check".to_string(),
+                description: "Perform health checks on monitored services".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "service_name": {
+                            "type": "string",
+                            "description": "Specific service to check, or 'all' for all services"
+                        }
+                    },
+                    "additionalProperties": false
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "get_active_alerts".to_string(),
+                description: "Get list of current active alerts".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "severity": {
+                            "type": "string",
+                            "enum": ["info", "warning", "critical"],
+                            "description": "Filter alerts by severity level"
+                        }
+                    },
+                    "additionalProperties": false
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "clear_alert".to_string(),
+                description: "Clear a specific alert by ID".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "alert_id": {
+                            "type": "string",
+                            "description": "ID of the alert to clear"
+                        }
+                    },
+                    "required": ["alert_id"],
+                    "additionalProperties": false
+                }),
+                annotations: Some(ToolAnnotations::destructive().idempotent()),
+            },
+            Tool {
+                name: "set_alert_threshold".to_string(),
+                description: "Configure alert thresholds for metrics".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "metric_name": {
+                            "type": "string",
+                            "enum": THRESHOLD_METRICS.map(|(name, _, _)| name),
+                            "description": "Name of the metric to configure"
+                        },
+                        "threshold": {
+                            "type": "number",
+                            "description": "Threshold value for triggering alerts"
+                        },
+                        "severity": {
+                            "type": "string",
+                            "enum": ["info", "warning", "critical"],
+                            "description": "Severity level for alerts triggered by this threshold"
+                        }
+                    },
+                    "required": ["metric_name", "threshold", "severity"],
+                    "additionalProperties": false
+                }),
+                annotations: Some(ToolAnnotations::destructive().idempotent()),
+            },
             Tool {
-                name: "get_current_metrics".to_string(),
-                description:
-                    "Get current system metrics including CPU, memory, disk, and network usage"
-                        .to_string(),
+                name: "list_alert_thresholds".to_string(),
+                description: "List the configured alert thresholds".to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
                     "properties": {},
                     "additionalProperties": false
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
             Tool {
-                name: "get_metrics_history".to_string(),
-                description: "Get historical metrics data for trend analysis".to_string(),
+                name: "list_incidents".to_string(),
+                description: "List incidents grouping related alerts, newest first".to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "limit": {
-                            "type": "integer",
-                            "description": "Maximum number of historical records to return",
-                            "minimum": 1,
-                            "maximum": 1000,
-                            "default": 100
+                        "status": {
+                            "type": "string",
+                            "enum": INCIDENT_STATUSES,
+                            "description": "Only incidents with this status"
                         }
                     },
                     "additionalProperties": false
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
             Tool {
-                name: "perform_health_check".to_string(),
-                description: "Perform health checks on monitored services".to_string(),
+                name: "update_incident".to_string(),
+                description: "Change an incident's status or assignee, or add a note".to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "service_name": {
+                        "incident_id": {
                             "type": "string",
-                            "description": "Specific service to check, or 'all' for all services"
+                            "description": "ID of the incident to update"
+                        },
+                        "status": {
+                            "type": "string",
+                            "enum": INCIDENT_STATUSES,
+                            "description": "New status"
+                        },
+                        "assignee": {
+                            "type": "string",
+                            "description": "Who is working on the incident; empty to unassign"
+                        },
+                        "note": {
+                            "type": "string",
+                            "description": "Note to add to the incident's timeline"
+                        },
+                        "author": {
+                            "type": "string",
+                            "description": "Who is making the change"
                         }
                     },
+                    "required": ["incident_id"],
                     "additionalProperties": false
                 }),
+                annotations: Some(ToolAnnotations::destructive()),
             },
             Tool {
-                name: "get_active_alerts".to_string(),
-                description: "Get list of current active alerts".to_string(),
+                name: "export_postmortem".to_string(),
+                description: "Export an incident's timeline, alerts and notes for a postmortem"
+                    .to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "severity": {
+                        "incident_id": {
                             "type": "string",
-                            "enum": ["info", "warning", "critical"],
-                            "description": "Filter alerts by severity level"
+                            "description": "ID of the incident to export"
+                        },
+                        "format": {
+                            "type": "string",
+                            "enum": ["markdown", "json"],
+                            "default": "markdown"
                         }
                     },
+                    "required": ["incident_id"],
                     "additionalProperties": false
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
             Tool {
-                name: "clear_alert".to_string(),
-                description: "Clear a specific alert by ID".to_string(),
+                name: "generate_status_report".to_string(),
+                description: "Generate a status page with service health, recent incidents and 30-day availability".to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "alert_id": {
+                        "format": {
                             "type": "string",
-                            "description": "ID of the alert to clear"
+                            "enum": ["json", "html"],
+                            "description": "Output format; HTML can be published with the file operations server",
+                            "default": "json"
+                        },
+                        "refresh": {
+                            "type": "boolean",
+                            "description": "Run health checks before generating the report",
+                            "default": true
                         }
                     },
-                    "required": ["alert_id"],
                     "additionalProperties": false
                 }),
+                annotations: Some(ToolAnnotations::read_only()),
             },
             Tool {
-                name: "set_alert_threshold".to_string(),
-                description: "Configure alert thresholds for metrics".to_string(),
+                name: "define_log_metric".to_string(),
+                description: "Derive a counter or histogram from the server's log events, or \
+                              events passed to ingest_log_events, that match a level, target \
+                              and field patterns. Replaces the rule with the same name"
+                    .to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "metric_name": {
+                        "name": {
                             "type": "string",
-                            "description": "Name of the metric to configure"
+                            "description": "Name of the metric, e.g. tool_failures_total"
                         },
-                        "threshold": {
-                            "type": "number",
-                            "description": "Threshold value for triggering alerts"
+                        "kind": {
+                            "type": "string",
+                            "enum": ["counter", "histogram"],
+                            "default": "counter"
                         },
-                        "severity": {
+                        "level": {
                             "type": "string",
-                            "enum": ["info", "warning", "critical"],
-                            "description": "Severity level for alerts triggered by this threshold"
+                            "enum": ["trace", "debug", "info", "warn", "error"],
+                            "description": "Lowest level that matches"
+                        },
+                        "target": {
+                            "type": "string",
+                            "description": "Prefix of the targets that match"
+                        },
+                        "fields": {
+                            "type": "object",
+                            "additionalProperties": { "type": "string" },
+                            "description": "Regular expressions fields must match, e.g. {\"message\": \"failed\"}; dotted names reach into objects"
+                        },
+                        "value_field": {
+                            "type": "string",
+                            "description": "Numeric field a histogram observes, e.g. duration_ms"
+                        },
+                        "buckets": {
+                            "type": "array",
+                            "items": { "type": "number" },
+                            "description": "Histogram bucket bounds, ascending"
+                        },
+                        "labels": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Fields copied into the metric's labels"
                         }
                     },
-                    "required": ["metric_name", "threshold", "severity"],
+                    "required": ["name"],
+                    "additionalProperties": false
+                }),
+                annotations: Some(ToolAnnotations::destructive().idempotent()),
+            },
+            Tool {
+                name: "list_log_metrics".to_string(),
+                description: "List the rules deriving metrics from log events, with how many \
+                              events each matched"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {},
+                    "additionalProperties": false
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "remove_log_metric".to_string(),
+                description: "Stop deriving a metric from log events".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "Name of the metric"
+                        }
+                    },
+                    "required": ["name"],
+                    "additionalProperties": false
+                }),
+                annotations: Some(ToolAnnotations::destructive().idempotent()),
+            },
+            Tool {
+                name: "ingest_log_events".to_string(),
+                description: "Apply the log metric rules to log events from elsewhere, such \
+                              as the log stream of example 10"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "source": {
+                            "type": "string",
+                            "description": "Added to events without one as their source field"
+                        },
+                        "events": {
+                            "type": "array",
+                            "items": { "type": "object" },
+                            "maxItems": MAX_LOG_EVENTS_PER_INGEST,
+                            "description": "Events with fields such as level, message and target"
+                        }
+                    },
+                    "required": ["events"],
                     "additionalProperties": false
                 }),
+                annotations: Some(ToolAnnotations::additive()),
             },
         ]
     }
 
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        ToolCallLog::run_async(name, arguments, |arguments| {
+            self.dispatch_tool(name, arguments)
+        })
+        .await
+    }
+
     // Function: call_tool
     //
     // Handles tool calls from MCP clients. This is the main entry point for
@@ -288,35 +2379,57 @@ impl MonitoringServer {
     //
     // Returns:
     //     Result containing the tool response as JSON or an error message
-    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+    async fn dispatch_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
         match name {
             "get_current_metrics" => {
-                // Collect current system metrics
-                let metrics = self.collect_current_metrics().await?;
-
-                // Store in history for trend analysis
-                self.store_metrics(metrics.clone()).await?;
-
-                // Check for threshold violations and create alerts
-                self.check_alert_thresholds(&metrics).await?;
+                let metrics = self.record_current_metrics().await?;
 
                 serde_json::to_value(metrics)
-                    .map_err(|e| format!("Failed to serialize metrics: {}", e))
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize metrics: {}", e)))
             }
             "get_metrics_history" => {
                 let limit = arguments
                     .get("limit")
                     .and_then(|v| v.as_u64())
                     .unwrap_or(100) as usize;
+                let resolution = match arguments.get("resolution") {
+                    Some(value) => serde_json::from_value(value.clone()).map_err(|_| {
+                        McpError::InvalidParams(format!(
+                            "Invalid resolution {}; use raw, minute or hour",
+                            value
+                        ))
+                    })?,
+                    None => Resolution::Raw,
+                };
 
-                let history = self.get_metrics_history(limit).await?;
+                let history = self.get_metrics_history(resolution, limit).await?;
 
                 serde_json::to_value(serde_json::json!({
                     "total_records": history.len(),
                     "limit": limit,
+                    "resolution": resolution,
                     "metrics": history
                 }))
-                .map_err(|e| format!("Failed to serialize history: {}", e))
+                .map_err(|e| McpError::Internal(format!("Failed to serialize history: {}", e)))
+            }
+            "get_metrics_summary" => {
+                let metric_name = arguments
+                    .get("metric_name")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| {
+                        McpError::InvalidParams(
+                            "Missing required parameter: metric_name".to_string(),
+                        )
+                    })?;
+                let window_seconds = arguments
+                    .get("window_seconds")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(3600);
+
+                let summary = self.get_metrics_summary(metric_name, window_seconds)?;
+
+                serde_json::to_value(summary)
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize summary: {}", e)))
             }
             "perform_health_check" => {
                 let service_name = arguments
@@ -331,7 +2444,9 @@ impl MonitoringServer {
                     "checks_performed": results.len(),
                     "results": results
                 }))
-                .map_err(|e| format!("Failed to serialize health check results: {}", e))
+                .map_err(|e| {
+                    McpError::Internal(format!("Failed to serialize health check results: {}", e))
+                })
             }
             "get_active_alerts" => {
                 let severity_filter = arguments.get("severity").and_then(|v| v.as_str());
@@ -343,13 +2458,15 @@ impl MonitoringServer {
                     "severity_filter": severity_filter,
                     "alerts": alerts
                 }))
-                .map_err(|e| format!("Failed to serialize alerts: {}", e))
+                .map_err(|e| McpError::Internal(format!("Failed to serialize alerts: {}", e)))
             }
             "clear_alert" => {
                 let alert_id = arguments
                     .get("alert_id")
                     .and_then(|v| v.as_str())
-                    .ok_or("Missing required parameter: alert_id")?;
+                    .ok_or_else(|| {
+                        McpError::InvalidParams("Missing required parameter: alert_id".to_string())
+                    })?;
 
                 let cleared = self.clear_alert(alert_id).await?;
 
@@ -361,183 +2478,913 @@ impl MonitoringServer {
                         format!("Alert {} not found", alert_id)
                     }
                 }))
-                .map_err(|e| format!("Failed to serialize response: {}", e))
+                .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
             }
             "set_alert_threshold" => {
                 let metric_name = arguments
                     .get("metric_name")
                     .and_then(|v| v.as_str())
-                    .ok_or("Missing required parameter: metric_name")?;
+                    .ok_or_else(|| {
+                        McpError::InvalidParams(
+                            "Missing required parameter: metric_name".to_string(),
+                        )
+                    })?;
 
                 let threshold = arguments
                     .get("threshold")
                     .and_then(|v| v.as_f64())
-                    .ok_or("Missing required parameter: threshold")?;
+                    .ok_or_else(|| {
+                        McpError::InvalidParams("Missing required parameter: threshold".to_string())
+                    })?;
 
                 let severity = arguments
                     .get("severity")
                     .and_then(|v| v.as_str())
-                    .ok_or("Missing required parameter: severity")?;
+                    .ok_or_else(|| {
+                        McpError::InvalidParams("Missing required parameter: severity".to_string())
+                    })?;
+
+                let threshold = AlertThreshold::new(metric_name, threshold, severity);
+                let previous = self.set_alert_threshold(threshold.clone()).await?;
 
-                // In a real implementation, this would store threshold configuration
-                // For this demo, we'll just acknowledge the configuration
                 serde_json::to_value(serde_json::json!({
                     "success": true,
                     "message": format!("Alert threshold configured for {}", metric_name),
-                    "configuration": {
-                        "metric_name": metric_name,
-                        "threshold": threshold,
-                        "severity": severity
+                    "configuration": threshold,
+                    "previous": previous,
+                    "persisted": self.thresholds_file.is_some()
+                }))
+                .map_err(|e| McpError::Internal(format!("Failed to serialize response: {}", e)))
+            }
+            "list_alert_thresholds" => {
+                let thresholds = self.list_alert_thresholds()?;
+
+                serde_json::to_value(serde_json::json!({
+                    "total_thresholds": thresholds.len(),
+                    "thresholds": thresholds,
+                    "thresholds_file": self.thresholds_file
+                }))
+                .map_err(|e| McpError::Internal(format!("Failed to serialize thresholds: {}", e)))
+            }
+            "list_incidents" => {
+                let status_filter = arguments.get("status").and_then(|v| v.as_str());
+
+                let incidents = self.list_incidents(status_filter)?;
+
+                serde_json::to_value(serde_json::json!({
+                    "total_incidents": incidents.len(),
+                    "status_filter": status_filter,
+                    "incidents": incidents
+                }))
+                .map_err(|e| McpError::Internal(format!("Failed to serialize incidents: {}", e)))
+            }
+            "update_incident" => {
+                let incident_id = arguments
+                    .get("incident_id")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| {
+                        McpError::InvalidParams(
+                            "Missing required parameter: incident_id".to_string(),
+                        )
+                    })?;
+                let field = |name: &str| arguments.get(name).and_then(|v| v.as_str());
+
+                let incident = self.update_incident(
+                    incident_id,
+                    field("status"),
+                    field("assignee"),
+                    field("note"),
+                    field("author"),
+                )?;
+
+                serde_json::to_value(incident)
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize incident: {}", e)))
+            }
+            "export_postmortem" => {
+                let incident_id = arguments
+                    .get("incident_id")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| {
+                        McpError::InvalidParams(
+                            "Missing required parameter: incident_id".to_string(),
+                        )
+                    })?;
+                let format = arguments
+                    .get("format")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("markdown");
+
+                let incident = self.find_incident(incident_id)?;
+                let now = self.get_current_timestamp();
+                match format {
+                    "markdown" => Ok(serde_json::json!({
+                        "incident_id": incident.id,
+                        "content_type": "text/markdown",
+                        "suggested_file_name": format!("postmortem-{}.md", incident.id),
+                        "content": render_postmortem(&incident, now)
+                    })),
+                    "json" => Ok(serde_json::json!({
+                        "incident_id": incident.id,
+                        "duration_seconds": incident.duration_seconds(now),
+                        "incident": incident
+                    })),
+                    other => Err(McpError::InvalidParams(format!(
+                        "Unsupported postmortem format: {}",
+                        other
+                    ))),
+                }
+            }
+            "generate_status_report" => {
+                let format = arguments
+                    .get("format")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("json");
+                let refresh = arguments
+                    .get("refresh")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+
+                if refresh {
+                    self.perform_health_checks("all").await?;
+                }
+                let report = self.generate_status_report()?;
+
+                match format {
+                    "json" => serde_json::to_value(report).map_err(|e| {
+                        McpError::Internal(format!("Failed to serialize status report: {}", e))
+                    }),
+                    "html" => Ok(serde_json::json!({
+                        "overall_status": report.overall_status,
+                        "content_type": "text/html",
+                        "suggested_file_name": "status.html",
+                        "content": render_status_page(&report)
+                    })),
+                    other => Err(McpError::InvalidParams(format!(
+                        "Unsupported report format: {}",
+                        other
+                    ))),
+                }
+            }
+            "define_log_metric" => {
+                let rule: LogMetricRule = serde_json::from_value(arguments)
+                    .map_err(|e| McpError::InvalidParams(format!("Invalid rule: {}", e)))?;
+                let previous = self
+                    .log_metrics
+                    .define(rule.clone())
+                    .map_err(McpError::InvalidParams)?;
+
+                Ok(serde_json::json!({
+                    "success": true,
+                    "rule": rule,
+                    "previous": previous
+                }))
+            }
+            "list_log_metrics" => {
+                let rules: Vec<Value> = self
+                    .log_metrics
+                    .rules()
+                    .into_iter()
+                    .map(|(rule, stats)| serde_json::json!({ "rule": rule, "stats": stats }))
+                    .collect();
+
+                Ok(serde_json::json!({
+                    "total_rules": rules.len(),
+                    "rules": rules
+                }))
+            }
+            "remove_log_metric" => {
+                let name = arguments
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| {
+                        McpError::InvalidParams("Missing required parameter: name".to_string())
+                    })?;
+
+                Ok(serde_json::json!({ "success": self.log_metrics.remove(name) }))
+            }
+            "ingest_log_events" => {
+                let events = arguments
+                    .get("events")
+                    .and_then(|v| v.as_array())
+                    .ok_or_else(|| {
+                        McpError::InvalidParams("Missing required parameter: events".to_string())
+                    })?;
+                if events.len() > MAX_LOG_EVENTS_PER_INGEST {
+                    return Err(McpError::InvalidParams(format!(
+                        "At most {} events can be ingested at once",
+                        MAX_LOG_EVENTS_PER_INGEST
+                    )));
+                }
+                let source = arguments.get("source").and_then(|v| v.as_str());
+
+                let mut recorded = 0;
+                for event in events {
+                    let Value::Object(fields) = event else {
+                        return Err(McpError::InvalidParams(
+                            "Each event must be a JSON object".to_string(),
+                        ));
+                    };
+                    let mut fields = fields.clone();
+                    if let Some(source) = source {
+                        fields
+                            .entry("source")
+                            .or_insert_with(|| Value::from(source));
                     }
+                    recorded += self.log_metrics.apply(&Value::Object(fields));
+                }
+
+                Ok(serde_json::json!({
+                    "events": events.len(),
+                    "recorded": recorded
                 }))
-                .map_err(|e| format!("Failed to serialize response: {}", e))
             }
-            _ => Err(format!("Unknown tool: {}", name)),
+            _ => Err(McpError::NotFound(format!("Unknown tool: {}", name))),
+        }
+    }
+
+    // Function: collect_current_metrics
+    //
+    // Reads current system metrics from the collector and stamps them with
+    // the collection time and the server's uptime.
+    //
+    // Returns:
+    //     Result containing current SystemMetrics or an error
+    async fn collect_current_metrics(&self) -> Result<SystemMetrics, String> {
+        let mut metrics = self.collector.collect().await?;
+
+        metrics.timestamp = self.get_current_timestamp();
+        metrics.uptime_seconds = self
+            .start_time
+            .elapsed()
+            .map_err(|e| format!("Failed to calculate uptime: {}", e))?
+            .as_secs();
+
+        Ok(metrics)
+    }
+
+    // Function: record_current_metrics
+    //
+    // Collects current metrics, stores them in history for trend analysis and
+    // checks them for threshold violations.
+    //
+    // Returns:
+    //     Result containing the collected SystemMetrics
+    async fn record_current_metrics(&self) -> Result<SystemMetrics, String> {
+        let metrics = self.collect_current_metrics().await?;
+        self.store_metrics(metrics.clone()).await?;
+        self.check_alert_thresholds(&metrics).await?;
+        Ok(metrics)
+    }
+
+    // Function: prometheus_metrics
+    //
+    // Renders the monitoring data in the Prometheus text exposition format.
+    // Each scrape records a fresh sample, the same way get_current_metrics
+    // does; Prometheus keeps its own history, so only that sample is exported
+    // along with the size of the history, alert counts per severity, the last
+    // health of each service and the tool call metrics of the MCP runtime.
+    //
+    // Arguments:
+    //     labels: Labels added to every series, e.g. instance="web-1"
+    //
+    // Returns:
+    //     Result containing the exposition text
+    async fn prometheus_metrics(&self, labels: &[(String, String)]) -> Result<String, String> {
+        let metrics = self.record_current_metrics().await?;
+        let common: Vec<(&str, &str)> = labels
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        let mut out = PrometheusText::new(&common);
+
+        out.gauge(
+            "system_cpu_usage_percent",
+            "CPU utilization of the host",
+            metrics.cpu_usage_percent,
+        );
+        out.labelled_gauge(
+            "system_cpu_core_usage_percent",
+            "CPU utilization of each logical core",
+            "core",
+            metrics
+                .cpu_per_core_percent
+                .iter()
+                .enumerate()
+                .map(|(core, usage)| (core.to_string(), *usage))
+                .collect(),
+        );
+        out.gauge(
+            "system_memory_usage_percent",
+            "Memory utilization of the host",
+            metrics.memory_usage_percent,
+        );
+        out.gauge(
+            "process_resident_memory_bytes",
+            "Resident memory of the monitoring server",
+            metrics.process_rss_bytes as f64,
+        );
+        out.gauge(
+            "system_disk_usage_percent",
+            "Disk space utilization of the host",
+            metrics.disk_usage_percent,
+        );
+        out.counter(
+            "system_network_sent_bytes_total",
+            "Bytes sent over all network interfaces",
+            metrics.network_bytes_sent,
+        );
+        out.counter(
+            "system_network_received_bytes_total",
+            "Bytes received over all network interfaces",
+            metrics.network_bytes_received,
+        );
+        out.gauge(
+            "system_active_connections",
+            "Established TCP connections",
+            metrics.active_connections as f64,
+        );
+        out.gauge(
+            "monitoring_uptime_seconds",
+            "Time since the monitoring server started",
+            metrics.uptime_seconds as f64,
+        );
+
+        let history_points = {
+            let history = self
+                .metrics_history
+                .lock()
+                .map_err(|e| format!("Failed to acquire metrics history lock: {}", e))?;
+            vec![
+                ("raw".to_string(), history.raw.len() as f64),
+                ("minute".to_string(), history.minute.len() as f64),
+                ("hour".to_string(), history.hourly.len() as f64),
+            ]
+        };
+        out.labelled_gauge(
+            "monitoring_metrics_history_points",
+            "Points kept in each retention tier of the metrics history",
+            "resolution",
+            history_points,
+        );
+
+        let alerts = self.get_active_alerts(None).await?;
+        out.labelled_gauge(
+            "monitoring_active_alerts",
+            "Alerts that have not been cleared",
+            "severity",
+            SEVERITIES
+                .iter()
+                .map(|severity| {
+                    let count = alerts.iter().filter(|a| a.severity == *severity).count();
+                    (severity.to_string(), count as f64)
+                })
+                .collect(),
+        );
+
+        // 1 while the last check found a service healthy or degraded
+        let health = self
+            .health_history
+            .lock()
+            .map_err(|e| format!("Failed to acquire health history lock: {}", e))?
+            .clone();
+        out.labelled_gauge(
+            "monitoring_service_up",
+            "Whether the last health check found the service available",
+            "service",
+            self.services_to_monitor
+                .iter()
+                .filter_map(|service| {
+                    let latest = health.iter().rev().find(|r| r.service_name == *service)?;
+                    let up = latest.status != "unhealthy";
+                    Some((service.clone(), if up { 1.0 } else { 0.0 }))
+                })
+                .collect(),
+        );
+
+        // Pushed metrics become one gauge per name, labelled by source
+        let mut pushed: BTreeMap<String, Vec<(String, f64)>> = BTreeMap::new();
+        for (source, values) in self
+            .pushed_metrics
+            .lock()
+            .map_err(|e| format!("Failed to acquire pushed metrics lock: {}", e))?
+            .iter()
+        {
+            for (name, value) in values {
+                pushed
+                    .entry(name.clone())
+                    .or_default()
+                    .push((source.clone(), *value));
+            }
+        }
+        for (name, samples) in pushed {
+            out.labelled_gauge(&name, "Pushed by another server", "source", samples);
+        }
+
+        let mut text = out.finish();
+        text.push_str(&metrics::global().prometheus_text(&common));
+        Ok(text)
+    }
+
+    // Function: ingest_push
+    //
+    // Stores metrics pushed by another server for the Prometheus endpoint and
+    // raises its alerts like threshold alerts: they join incidents for the
+    // source and go to the alert sinks.
+    //
+    // Arguments:
+    //     push: The pushed metrics and alerts
+    //
+    // Returns:
+    //     The alerts raised, or an error for an invalid push
+    fn ingest_push(&self, push: MetricsPush) -> Result<Vec<Alert>, McpError> {
+        if push.source.trim().is_empty() {
+            return Err(McpError::InvalidParams(
+                "source must not be empty".to_string(),
+            ));
+        }
+        for (name, value) in &push.metrics {
+            if !is_valid_prometheus_name(name) || !value.is_finite() {
+                return Err(McpError::InvalidParams(format!(
+                    "Invalid pushed metric {} = {}",
+                    name, value
+                )));
+            }
+        }
+        if let Some(alert) = push
+            .alerts
+            .iter()
+            .find(|alert| !SEVERITIES.contains(&alert.severity.as_str()))
+        {
+            return Err(McpError::InvalidParams(format!(
+                "Unknown severity '{}'; use one of {}",
+                alert.severity,
+                SEVERITIES.join(", ")
+            )));
+        }
+
+        let timestamp = self.get_current_timestamp();
+        let raised: Vec<Alert> = push
+            .alerts
+            .into_iter()
+            .map(|alert| Alert {
+                id: format!("{}-{}-{}", push.source, alert.metric_name, timestamp),
+                severity: alert.severity,
+                title: alert.title,
+                description: alert.description,
+                service: push.source.clone(),
+                metric_name: alert.metric_name,
+                threshold: alert.threshold,
+                current_value: alert.current_value,
+                timestamp,
+            })
+            .collect();
+        self.pushed_metrics
+            .lock()
+            .map_err(|e| {
+                McpError::Internal(format!("Failed to acquire pushed metrics lock: {}", e))
+            })?
+            .insert(push.source, push.metrics);
+        self.correlate_alerts(&raised).map_err(McpError::Internal)?;
+        self.deliver_alerts(&raised);
+        self.active_alerts
+            .lock()
+            .map_err(|e| McpError::Internal(format!("Failed to acquire alerts lock: {}", e)))?
+            .extend(raised.iter().cloned());
+        Ok(raised)
+    }
+
+    // Function: store_metrics
+    //
+    // Stores metrics in the raw tier of the history. Older samples are
+    // averaged into coarser tiers by compact_metrics_history.
+    //
+    // Arguments:
+    //     metrics: SystemMetrics to store in history
+    //
+    // Returns:
+    //     Result indicating success or failure
+    async fn store_metrics(&self, metrics: SystemMetrics) -> Result<(), String> {
+        self.metrics_history
+            .lock()
+            .map_err(|e| format!("Failed to acquire metrics history lock: {}", e))?
+            .push(metrics);
+        Ok(())
+    }
+
+    // Function: compact_metrics_history
+    //
+    // Moves aged samples into the coarser retention tiers and drops points
+    // older than 30 days.
+    //
+    // Returns:
+    //     Result indicating success or failure
+    fn compact_metrics_history(&self) -> Result<(), String> {
+        let now = self.get_current_timestamp();
+        self.metrics_history
+            .lock()
+            .map_err(|e| format!("Failed to acquire metrics history lock: {}", e))?
+            .compact(now);
+        Ok(())
+    }
+
+    // Function: spawn_compaction
+    //
+    // Compacts the metrics history every minute on a background task.
+    //
+    // Arguments:
+    //     server: The monitoring server whose history is compacted
+    //
+    // Returns:
+    //     The compaction task
+    pub fn spawn_compaction(server: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(COMPACTION_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = server.compact_metrics_history() {
+                    tracing::warn!(error = %e, "Metrics history compaction failed");
+                }
+            }
+        })
+    }
+
+    // Function: spawn_log_metrics
+    //
+    // Applies the log metric rules to every event the server logs, read from
+    // the logging module's tap on a background task. Events missed because
+    // the task fell behind are counted in log_metric_events_lagged_total.
+    //
+    // Arguments:
+    //     server: The monitoring server whose rules are applied
+    //
+    // Returns:
+    //     The task tailing the log
+    pub fn spawn_log_metrics(server: Arc<Self>) -> JoinHandle<()> {
+        let mut events = mcp_rust_examples::logging::subscribe();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        server.log_metrics.apply(&event);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        server.log_metrics.registry.increment(
+                            "log_metric_events_lagged_total",
+                            &[],
+                            missed,
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    // Function: get_metrics_history
+    //
+    // Retrieves historical metrics data for trend analysis and reporting.
+    //
+    // Arguments:
+    //     resolution: Every sample, or minute or hour averages
+    //     limit: Maximum number of records to return
+    //
+    // Returns:
+    //     Result containing the most recent points, oldest first
+    async fn get_metrics_history(
+        &self,
+        resolution: Resolution,
+        limit: usize,
+    ) -> Result<Vec<MetricsPoint>, String> {
+        Ok(self
+            .metrics_history
+            .lock()
+            .map_err(|e| format!("Failed to acquire metrics history lock: {}", e))?
+            .history(resolution, limit))
+    }
+
+    // Function: get_metrics_summary
+    //
+    // Summarizes one metric over a recent window without returning the
+    // history itself, reading the finest tier that still covers the window.
+    //
+    // Arguments:
+    //     metric_name: The metric to summarize
+    //     window_seconds: How far back to look, up to the 30 days kept
+    //
+    // Returns:
+    //     The summary, or an error for an unknown metric or window
+    fn get_metrics_summary(
+        &self,
+        metric_name: &str,
+        window_seconds: u64,
+    ) -> Result<MetricSummary, McpError> {
+        if !THRESHOLD_METRICS
+            .iter()
+            .any(|(name, _, _)| *name == metric_name)
+        {
+            return Err(McpError::InvalidParams(format!(
+                "Unknown metric: {}",
+                metric_name
+            )));
         }
+        if window_seconds == 0 || window_seconds > HOUR_RETENTION_SECONDS {
+            return Err(McpError::InvalidParams(format!(
+                "window_seconds must be between 1 and {}",
+                HOUR_RETENTION_SECONDS
+            )));
+        }
+
+        let (resolution, period_seconds) = if window_seconds <= RAW_RETENTION_SECONDS {
+            (Resolution::Raw, 1)
+        } else if window_seconds <= MINUTE_RETENTION_SECONDS {
+            (Resolution::Minute, 60)
+        } else {
+            (Resolution::Hour, 3600)
+        };
+        let since = self.get_current_timestamp().saturating_sub(window_seconds);
+        let points: Vec<MetricsPoint> = self
+            .metrics_history
+            .lock()
+            .map_err(|e| {
+                McpError::Internal(format!("Failed to acquire metrics history lock: {}", e))
+            })?
+            .history(resolution, usize::MAX)
+            .into_iter()
+            // An averaged point counts if its period ends inside the window
+            .filter(|p| p.metrics.timestamp + period_seconds > since)
+            .collect();
+        Ok(summarize(metric_name, window_seconds, resolution, &points))
     }
 
-    // Function: collect_current_metrics
+    // Function: check_alert_thresholds
     //
-    // Simulates collection of current system metrics.
-    // In a real implementation, this would interface with system APIs
-    // to gather actual performance data.
+    // Checks current metrics against the configured thresholds and creates
+    // alerts when thresholds are exceeded. This is critical for proactive
+    // monitoring.
+    //
+    // Arguments:
+    //     metrics: Current SystemMetrics to check against thresholds
     //
     // Returns:
-    //     Result containing current SystemMetrics or an error
-    async fn collect_current_metrics(&self) -> Result<SystemMetrics, String> {
-        // Simulate metric collection with realistic but randomized values
-        // In production, this would query actual system resources
+    //     Result indicating success or failure of threshold checking
+    async fn check_alert_thresholds(&self, metrics: &SystemMetrics) -> Result<(), String> {
+        let thresholds = self.list_alert_thresholds()?;
+        let mut raised = Vec::new();
 
-        let timestamp = self.get_current_timestamp();
-        let uptime = self
-            .start_time
-            .elapsed()
-            .map_err(|e| format!("Failed to calculate uptime: {}", e))?
-            .as_secs();
+        for threshold in thresholds {
+            let Some(current_value) = metric_value(metrics, &threshold.metric_name) else {
+                continue;
+            };
+            if current_value <= threshold.threshold {
+                continue;
+            }
 
-        // Generate realistic but simulated metrics
-        // In production, these would come from system monitoring APIs
-        let metrics = SystemMetrics {
-            timestamp,
-            cpu_usage_percent: 20.0 + (timestamp % 60) as f64 * 0.8, // Varies between 20-68%
-            memory_usage_percent: 45.0 + (timestamp % 40) as f64 * 0.5, // Varies between 45-65%
-            disk_usage_percent: 35.0 + (timestamp % 10) as f64 * 0.2, // Varies between 35-37%
-            network_bytes_sent: 1024 * 1024 * (timestamp % 1000),    // Simulated network activity
-            network_bytes_received: 2 * 1024 * 1024 * (timestamp % 1000),
-            active_connections: 50 + (timestamp % 100) as u32, // 50-149 connections
-            uptime_seconds: uptime,
-        };
+            let (label, unit) = THRESHOLD_METRICS
+                .iter()
+                .find(|(name, _, _)| *name == threshold.metric_name)
+                .map(|(_, label, unit)| (*label, *unit))
+                .unwrap_or((threshold.metric_name.as_str(), ""));
+            raised.push(Alert {
+                id: format!("{}-{}", threshold.metric_name, metrics.timestamp),
+                severity: threshold.severity,
+                title: format!("High {}", label),
+                description: format!(
+                    "{} is {}{}, exceeding threshold of {}{}",
+                    label, current_value, unit, threshold.threshold, unit
+                ),
+                service: host_service(),
+                metric_name: threshold.metric_name,
+                threshold: threshold.threshold,
+                current_value,
+                timestamp: metrics.timestamp,
+            });
+        }
 
-        Ok(metrics)
+        self.correlate_alerts(&raised)?;
+        self.deliver_alerts(&raised);
+        self.active_alerts
+            .lock()
+            .map_err(|e| format!("Failed to acquire alerts lock: {}", e))?
+            .extend(raised);
+        Ok(())
     }
 
-    // Function: store_metrics
+    // Function: deliver_alerts
     //
-    // Stores metrics in the historical data collection with size management.
-    // This implements a circular buffer pattern to prevent unbounded memory growth.
+    // Sends each alert to the sinks routed its severity, in the background so
+    // a slow webhook never holds up metrics collection. Failed deliveries are
+    // logged and counted in alert_deliveries_total.
     //
     // Arguments:
-    //     metrics: SystemMetrics to store in history
+    //     alerts: Newly raised alerts
+    fn deliver_alerts(&self, alerts: &[Alert]) {
+        for alert in alerts {
+            for route in &self.alert_routes {
+                if !route.severities.contains(&alert.severity) {
+                    continue;
+                }
+                let sink = route.sink.clone();
+                let alert = alert.clone();
+                tokio::spawn(async move {
+                    let outcome = match sink.send(&alert).await {
+                        Ok(()) => "delivered",
+                        Err(e) => {
+                            tracing::warn!(sink = sink.name(), alert = %alert.id, error = %e, "Alert delivery failed");
+                            "failed"
+                        }
+                    };
+                    metrics::global().increment(
+                        "alert_deliveries_total",
+                        &[("sink", sink.name()), ("outcome", outcome)],
+                        1,
+                    );
+                });
+            }
+        }
+    }
+
+    // Function: correlate_alerts
+    //
+    // Adds each alert to the open incident for its service when the alert is
+    // within the correlation window, and opens a new incident otherwise.
+    //
+    // Arguments:
+    //     alerts: Newly raised alerts, oldest first
     //
     // Returns:
     //     Result indicating success or failure
-    async fn store_metrics(&self, metrics: SystemMetrics) -> Result<(), String> {
-        let mut history = self
-            .metrics_history
+    fn correlate_alerts(&self, alerts: &[Alert]) -> Result<(), String> {
+        let mut incidents = self
+            .incidents
             .lock()
-            .map_err(|e| format!("Failed to acquire metrics history lock: {}", e))?;
+            .map_err(|e| format!("Failed to acquire incidents lock: {}", e))?;
 
-        // Add new metrics to history
-        history.push(metrics);
-
-        // Implement circular buffer: remove oldest entries if we exceed maximum size
-        // This prevents unbounded memory growth in long-running systems
-        if history.len() > MAX_METRIC_HISTORY_SIZE {
-            let excess = history.len() - MAX_METRIC_HISTORY_SIZE;
-            history.drain(0..excess);
+        for alert in alerts {
+            match incidents.iter_mut().rev().find(|i| i.accepts(alert)) {
+                Some(incident) => incident.add_alert(alert.clone()),
+                None => {
+                    let id = format!("INC-{}", incidents.len() + 1);
+                    incidents.push(Incident::open(id, alert.clone()));
+                }
+            }
         }
-
         Ok(())
     }
 
-    // Function: get_metrics_history
+    // Function: list_incidents
     //
-    // Retrieves historical metrics data for trend analysis and reporting.
+    // Returns incidents newest first, optionally only those with a status.
+    fn list_incidents(&self, status_filter: Option<&str>) -> Result<Vec<Incident>, String> {
+        let incidents = self
+            .incidents
+            .lock()
+            .map_err(|e| format!("Failed to acquire incidents lock: {}", e))?;
+
+        Ok(incidents
+            .iter()
+            .rev()
+            .filter(|incident| status_filter.is_none_or(|status| incident.status == status))
+            .cloned()
+            .collect())
+    }
+
+    fn find_incident(&self, incident_id: &str) -> Result<Incident, McpError> {
+        self.list_incidents(None)?
+            .into_iter()
+            .find(|incident| incident.id == incident_id)
+            .ok_or_else(|| McpError::NotFound(format!("Incident {} not found", incident_id)))
+    }
+
+    // Function: update_incident
+    //
+    // Applies status, assignee and note changes to an incident, recording
+    // each one on its timeline.
     //
     // Arguments:
-    //     limit: Maximum number of records to return
+    //     incident_id: The incident to update
+    //     status: New status, if changing
+    //     assignee: New assignee, if changing; empty unassigns
+    //     note: Note to add, if any
+    //     author: Who is making the changes
     //
     // Returns:
-    //     Result containing vector of historical SystemMetrics
-    async fn get_metrics_history(&self, limit: usize) -> Result<Vec<SystemMetrics>, String> {
-        let history = self
-            .metrics_history
+    //     The updated incident
+    fn update_incident(
+        &self,
+        incident_id: &str,
+        status: Option<&str>,
+        assignee: Option<&str>,
+        note: Option<&str>,
+        author: Option<&str>,
+    ) -> Result<Incident, McpError> {
+        if let Some(status) = status {
+            if !INCIDENT_STATUSES.contains(&status) {
+                return Err(McpError::InvalidParams(format!(
+                    "Unknown incident status: {}",
+                    status
+                )));
+            }
+        }
+        if status.is_none() && assignee.is_none() && note.is_none() {
+            return Err(McpError::InvalidParams(
+                "Nothing to update: pass status, assignee or note".to_string(),
+            ));
+        }
+
+        let now = self.get_current_timestamp();
+        let mut incidents = self
+            .incidents
             .lock()
-            .map_err(|e| format!("Failed to acquire metrics history lock: {}", e))?;
+            .map_err(|e| McpError::Internal(format!("Failed to acquire incidents lock: {}", e)))?;
+        let incident = incidents
+            .iter_mut()
+            .find(|incident| incident.id == incident_id)
+            .ok_or_else(|| McpError::NotFound(format!("Incident {} not found", incident_id)))?;
 
-        // Return the most recent 'limit' entries
-        let start_index = if history.len() > limit {
-            history.len() - limit
-        } else {
-            0
-        };
+        if let Some(status) = status.filter(|status| *status != incident.status) {
+            let detail = format!("Status changed from {} to {}", incident.status, status);
+            incident.record(now, "status", author, &detail);
+            incident.status = status.to_string();
+            incident.resolved_at = (status == "resolved").then_some(now);
+        }
+        if let Some(assignee) = assignee {
+            let assignee = Some(assignee.to_string()).filter(|a| !a.is_empty());
+            if assignee != incident.assignee {
+                let detail = match &assignee {
+                    Some(assignee) => format!("Assigned to {}", assignee),
+                    None => "Unassigned".to_string(),
+                };
+                incident.record(now, "assigned", author, &detail);
+                incident.assignee = assignee;
+            }
+        }
+        if let Some(note) = note {
+            incident.record(now, "note", author, note);
+        }
 
-        Ok(history[start_index..].to_vec())
+        Ok(incident.clone())
     }
 
-    // Function: check_alert_thresholds
+    // Function: set_alert_threshold
     //
-    // Checks current metrics against predefined thresholds and creates alerts
-    // when thresholds are exceeded. This is critical for proactive monitoring.
+    // Stores a threshold, replacing any earlier one for the same metric, and
+    // persists all thresholds if the server has a thresholds file.
     //
     // Arguments:
-    //     metrics: Current SystemMetrics to check against thresholds
+    //     threshold: The threshold to store
     //
     // Returns:
-    //     Result indicating success or failure of threshold checking
-    async fn check_alert_thresholds(&self, metrics: &SystemMetrics) -> Result<(), String> {
-        let mut alerts = self
-            .active_alerts
-            .lock()
-            .map_err(|e| format!("Failed to acquire alerts lock: {}", e))?;
-
-        // Check CPU usage threshold
-        if metrics.cpu_usage_percent > ALERT_THRESHOLD_CPU_PERCENT {
-            let alert = Alert {
-                id: format!("cpu-{}", metrics.timestamp),
-                severity: "warning".to_string(),
-                title: "High CPU Usage".to_string(),
-                description: format!(
-                    "CPU usage is {}%, exceeding threshold of {}%",
-                    metrics.cpu_usage_percent, ALERT_THRESHOLD_CPU_PERCENT
-                ),
-                metric_name: "cpu_usage_percent".to_string(),
-                threshold: ALERT_THRESHOLD_CPU_PERCENT,
-                current_value: metrics.cpu_usage_percent,
-                timestamp: metrics.timestamp,
-            };
-            alerts.push(alert);
+    //     The threshold it replaced, if any
+    async fn set_alert_threshold(
+        &self,
+        threshold: AlertThreshold,
+    ) -> Result<Option<AlertThreshold>, McpError> {
+        if !THRESHOLD_METRICS
+            .iter()
+            .any(|(name, _, _)| *name == threshold.metric_name)
+        {
+            return Err(McpError::InvalidParams(format!(
+                "Unknown metric: {}",
+                threshold.metric_name
+            )));
+        }
+        if !SEVERITIES.contains(&threshold.severity.as_str()) {
+            return Err(McpError::InvalidParams(format!(
+                "Unknown severity: {}",
+                threshold.severity
+            )));
+        }
+        if !threshold.threshold.is_finite() {
+            return Err(McpError::InvalidParams(
+                "Threshold must be a finite number".to_string(),
+            ));
         }
 
-        // Check memory usage threshold
-        if metrics.memory_usage_percent > ALERT_THRESHOLD_MEMORY_PERCENT {
-            let alert = Alert {
-                id: format!("memory-{}", metrics.timestamp),
-                severity: "critical".to_string(),
-                title: "High Memory Usage".to_string(),
-                description: format!(
-                    "Memory usage is {}%, exceeding threshold of {}%",
-                    metrics.memory_usage_percent, ALERT_THRESHOLD_MEMORY_PERCENT
-                ),
-                metric_name: "memory_usage_percent".to_string(),
-                threshold: ALERT_THRESHOLD_MEMORY_PERCENT,
-                current_value: metrics.memory_usage_percent,
-                timestamp: metrics.timestamp,
-            };
-            alerts.push(alert);
+        let (previous, thresholds) = {
+            let mut thresholds = self.alert_thresholds.lock().map_err(|e| {
+                McpError::Internal(format!("Failed to acquire thresholds lock: {}", e))
+            })?;
+            let previous = thresholds.insert(threshold.metric_name.clone(), threshold);
+            (previous, thresholds.values().cloned().collect::<Vec<_>>())
+        };
+
+        if let Some(path) = &self.thresholds_file {
+            save_thresholds(path, &thresholds)
+                .await
+                .map_err(McpError::Internal)?;
         }
+        Ok(previous)
+    }
 
-        Ok(())
+    // Function: list_alert_thresholds
+    //
+    // Returns the configured thresholds, ordered by metric name.
+    fn list_alert_thresholds(&self) -> Result<Vec<AlertThreshold>, String> {
+        let thresholds = self
+            .alert_thresholds
+            .lock()
+            .map_err(|e| format!("Failed to acquire thresholds lock: {}", e))?;
+        Ok(thresholds.values().cloned().collect())
     }
 
     // Function: perform_health_checks
@@ -553,7 +3400,7 @@ impl MonitoringServer {
     async fn perform_health_checks(
         &self,
         service_filter: &str,
-    ) -> Result<Vec<HealthCheckResult>, String> {
+    ) -> Result<Vec<HealthCheckResult>, McpError> {
         let mut results = Vec::new();
 
         let services_to_check: Vec<String> = if service_filter == "all" {
@@ -566,16 +3413,34 @@ impl MonitoringServer {
             {
                 vec![service_filter.to_string()]
             } else {
-                return Err(format!(
+                return Err(McpError::NotFound(format!(
                     "Service '{}' is not being monitored",
                     service_filter
-                ));
+                )));
             }
         };
 
         for service_name in &services_to_check {
             let start_time = std::time::Instant::now();
 
+            if let Some(probe) = self.health_probes.get(service_name) {
+                let outcome = probe.check().await;
+                let (check_type, endpoint) = probe.describe();
+                results.push(HealthCheckResult {
+                    service_name: service_name.clone(),
+                    status: outcome.status,
+                    response_time_ms: start_time.elapsed().as_millis() as u64,
+                    message: outcome.message,
+                    details: Some(serde_json::json!({
+                        "checked_at": self.get_current_timestamp(),
+                        "check_type": check_type,
+                        "endpoint": endpoint,
+                        "result": outcome.details
+                    })),
+                });
+                continue;
+            }
+
             // Simulate health check operation
             // In production, this would make actual HTTP requests or service calls
             sleep(Duration::from_millis(10)).await; // Simulate check latency
@@ -606,9 +3471,120 @@ impl MonitoringServer {
             results.push(result);
         }
 
+        self.record_health_results(&results)?;
+
         Ok(results)
     }
 
+    // Function: record_health_results
+    //
+    // Appends health check outcomes to the history used for availability
+    // reporting, dropping the oldest records beyond the history limit.
+    //
+    // Arguments:
+    //     results: Health check results to record
+    //
+    // Returns:
+    //     Result indicating success or failure
+    fn record_health_results(&self, results: &[HealthCheckResult]) -> Result<(), String> {
+        let mut history = self
+            .health_history
+            .lock()
+            .map_err(|e| format!("Failed to acquire health history lock: {}", e))?;
+
+        let timestamp = self.get_current_timestamp();
+        history.extend(results.iter().map(|result| HealthRecord {
+            service_name: result.service_name.clone(),
+            status: result.status.clone(),
+            timestamp,
+        }));
+
+        let excess = history.len().saturating_sub(MAX_HEALTH_HISTORY_SIZE);
+        history.drain(..excess);
+
+        Ok(())
+    }
+
+    // Function: generate_status_report
+    //
+    // Summarizes recorded health checks and alerts into a status report.
+    // Availability counts "healthy" and "degraded" checks as available,
+    // since a degraded service is still serving requests.
+    //
+    // Returns:
+    //     Result containing the StatusReport
+    fn generate_status_report(&self) -> Result<StatusReport, String> {
+        let now = self.get_current_timestamp();
+        let window_start = now.saturating_sub(STATUS_WINDOW_DAYS * 24 * 60 * 60);
+
+        let history = self
+            .health_history
+            .lock()
+            .map_err(|e| format!("Failed to acquire health history lock: {}", e))?;
+
+        let services: Vec<ServiceStatus> = self
+            .services_to_monitor
+            .iter()
+            .map(|service_name| {
+                let checks: Vec<&HealthRecord> = history
+                    .iter()
+                    .filter(|record| {
+                        record.service_name == *service_name && record.timestamp >= window_start
+                    })
+                    .collect();
+                let available = checks
+                    .iter()
+                    .filter(|record| record.status != "unhealthy")
+                    .count();
+                let latest = checks.last();
+
+                ServiceStatus {
+                    service_name: service_name.clone(),
+                    current_status: latest
+                        .map_or("unknown".to_string(), |record| record.status.clone()),
+                    last_checked: latest.map(|record| record.timestamp),
+                    availability_percent: (!checks.is_empty())
+                        .then(|| available as f64 / checks.len() as f64 * 100.0),
+                    checks_in_window: checks.len(),
+                }
+            })
+            .collect();
+        drop(history);
+
+        let statuses: Vec<&str> = services
+            .iter()
+            .map(|service| service.current_status.as_str())
+            .collect();
+        let overall_status = if statuses.contains(&"unhealthy") {
+            "major_outage"
+        } else if statuses.is_empty() || statuses.contains(&"unknown") {
+            "unknown"
+        } else if statuses.contains(&"degraded") {
+            "degraded"
+        } else {
+            "operational"
+        };
+
+        let mut incidents: Vec<Alert> = self
+            .active_alerts
+            .lock()
+            .map_err(|e| format!("Failed to acquire alerts lock: {}", e))?
+            .iter()
+            .filter(|alert| alert.timestamp >= window_start)
+            .cloned()
+            .collect();
+        incidents.sort_by_key(|alert| std::cmp::Reverse(alert.timestamp));
+        incidents.truncate(STATUS_MAX_INCIDENTS);
+
+        Ok(StatusReport {
+            generated_at: now,
+            overall_status: overall_status.to_string(),
+            window_days: STATUS_WINDOW_DAYS,
+            services,
+            incidents,
+        })
+    }
+
     // Function: get_active_alerts
     //
     // Retrieves current active alerts, optionally filtered by severity level.
@@ -653,24 +3629,495 @@ impl MonitoringServer {
             .lock()
             .map_err(|e| format!("Failed to acquire alerts lock: {}", e))?;
 
-        let initial_len = alerts.len();
-        alerts.retain(|alert| alert.id != alert_id);
-        let cleared = alerts.len() < initial_len;
+        let initial_len = alerts.len();
+        alerts.retain(|alert| alert.id != alert_id);
+        let cleared = alerts.len() < initial_len;
+
+        Ok(cleared)
+    }
+
+    // Function: get_current_timestamp
+    //
+    // Gets the current Unix timestamp for consistent time tracking.
+    //
+    // Returns:
+    //     Current Unix timestamp in seconds
+    fn get_current_timestamp(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+}
+
+// Function: render_postmortem
+//
+// Renders an incident as a Markdown postmortem: a summary, the timeline of
+// alerts and changes, and the metrics that fired.
+//
+// Arguments:
+//     incident: The incident to render
+//     now: Current time, for the duration of unresolved incidents
+//
+// Returns:
+//     The Markdown document
+fn render_postmortem(incident: &Incident, now: u64) -> String {
+    let mut doc = String::new();
+    let _ = writeln!(doc, "# Postmortem: {} {}", incident.id, incident.title);
+    let _ = writeln!(doc);
+    let _ = writeln!(doc, "- **Service:** {}", incident.service);
+    let _ = writeln!(doc, "- **Severity:** {}", incident.severity);
+    let _ = writeln!(doc, "- **Status:** {}", incident.status);
+    let _ = writeln!(
+        doc,
+        "- **Assignee:** {}",
+        incident.assignee.as_deref().unwrap_or("unassigned")
+    );
+    let _ = writeln!(
+        doc,
+        "- **Opened:** {}",
+        format_timestamp(incident.opened_at)
+    );
+    if let Some(resolved_at) = incident.resolved_at {
+        let _ = writeln!(doc, "- **Resolved:** {}", format_timestamp(resolved_at));
+    }
+    let duration = incident.duration_seconds(now);
+    let _ = writeln!(doc, "- **Duration:** {}m {}s", duration / 60, duration % 60);
+    let _ = writeln!(doc, "- **Alerts:** {}", incident.alerts.len());
+
+    let _ = writeln!(doc, "\n## Timeline\n");
+    let _ = writeln!(doc, "| Time (UTC) | Event | By | Details |");
+    let _ = writeln!(doc, "|---|---|---|---|");
+    let mut timeline = incident.timeline.clone();
+    timeline.sort_by_key(|entry| entry.timestamp);
+    for entry in &timeline {
+        let _ = writeln!(
+            doc,
+            "| {} | {} | {} | {} |",
+            format_timestamp(entry.timestamp),
+            entry.kind,
+            entry.author.as_deref().unwrap_or("-"),
+            entry.detail.replace('|', "\\|").replace('\n', " ")
+        );
+    }
+
+    // The worst value each metric reached during the incident
+    let mut peaks: BTreeMap<&str, (f64, f64)> = BTreeMap::new();
+    for alert in &incident.alerts {
+        let peak = peaks
+            .entry(&alert.metric_name)
+            .or_insert((alert.current_value, alert.threshold));
+        peak.0 = peak.0.max(alert.current_value);
+    }
+    let _ = writeln!(doc, "\n## Metrics\n");
+    let _ = writeln!(doc, "| Metric | Peak | Threshold |");
+    let _ = writeln!(doc, "|---|---|---|");
+    for (metric, (peak, threshold)) in peaks {
+        let _ = writeln!(doc, "| {} | {:.1} | {} |", metric, peak, threshold);
+    }
+    doc
+}
+
+// Function: format_timestamp
+//
+// Formats a Unix timestamp as UTC for humans, e.g. "2024-01-01 12:00:00".
+fn format_timestamp(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0).map_or_else(
+        || timestamp.to_string(),
+        |t| t.format("%Y-%m-%d %H:%M:%S").to_string(),
+    )
+}
+
+// Function: save_thresholds
+//
+// Writes thresholds to a temporary file next to `path` and renames it into
+// place, so a crash mid-write never leaves a truncated thresholds file.
+async fn save_thresholds(path: &Path, thresholds: &[AlertThreshold]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let json = serde_json::to_vec_pretty(thresholds)
+        .map_err(|e| format!("Failed to serialize thresholds: {}", e))?;
+    let temp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&temp_path, json)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", temp_path.display(), e))?;
+    tokio::fs::rename(&temp_path, path)
+        .await
+        .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+// Struct: PrometheusText
+//
+// Builds a Prometheus text exposition, adding the same labels to every series.
+//
+// Fields:
+//     out: The text written so far
+//     labels: Labels added to every series
+struct PrometheusText<'a> {
+    out: String,
+    labels: &'a [(&'a str, &'a str)],
+}
+
+impl<'a> PrometheusText<'a> {
+    fn new(labels: &'a [(&'a str, &'a str)]) -> Self {
+        Self {
+            out: String::new(),
+            labels,
+        }
+    }
+
+    // Function: gauge
+    //
+    // Writes a gauge with a single, unlabelled sample.
+    fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.header(name, help, "gauge");
+        self.sample(name, &[], value);
+    }
+
+    // Function: labelled_gauge
+    //
+    // Writes a gauge with one sample per value of `label`. Gauges without
+    // samples, e.g. before any health check, are left out.
+    fn labelled_gauge(&mut self, name: &str, help: &str, label: &str, samples: Vec<(String, f64)>) {
+        if samples.is_empty() {
+            return;
+        }
+        self.header(name, help, "gauge");
+        for (value_label, value) in &samples {
+            self.sample(name, &[(label, value_label)], *value);
+        }
+    }
+
+    // Function: counter
+    //
+    // Writes a counter with a single, unlabelled sample.
+    fn counter(&mut self, name: &str, help: &str, value: u64) {
+        self.header(name, help, "counter");
+        self.sample(name, &[], value as f64);
+    }
+
+    fn header(&mut self, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let mut all = self.labels.to_vec();
+        all.extend_from_slice(labels);
+        let _ = writeln!(self.out, "{}{} {}", name, prometheus_labels(&all), value);
+    }
+
+    fn finish(self) -> String {
+        self.out
+    }
+}
+
+// Struct: MetricsEndpointConfig
+//
+// Where the Prometheus endpoint listens and the labels it adds to every series.
+//
+// Fields:
+//     bind_address: Address to listen on, from MCP_METRICS_ADDRESS
+//     labels: Labels from MCP_METRICS_LABELS, written as name=value,name=value
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsEndpointConfig {
+    pub bind_address: SocketAddr,
+    pub labels: Vec<(String, String)>,
+}
+
+impl MetricsEndpointConfig {
+    // Function: from_env
+    //
+    // Reads the endpoint configuration from the environment.
+    //
+    // Returns:
+    //     None when MCP_METRICS_ADDRESS is not set, or an error for an
+    //     invalid address or label
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(address) = std::env::var(METRICS_ADDRESS_ENV) else {
+            return Ok(None);
+        };
+        let bind_address = address
+            .parse()
+            .map_err(|e| format!("Invalid {}: {}", METRICS_ADDRESS_ENV, e))?;
+        let labels = Self::parse_labels(&std::env::var(METRICS_LABELS_ENV).unwrap_or_default())?;
+        Ok(Some(Self {
+            bind_address,
+            labels,
+        }))
+    }
+
+    // Function: parse_labels
+    //
+    // Parses labels written as name=value pairs separated by commas.
+    fn parse_labels(text: &str) -> Result<Vec<(String, String)>, String> {
+        text.split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("Label '{}' must be written as name=value", pair))?;
+                let name = name.trim();
+                // Names starting with __ are reserved by Prometheus
+                if !is_valid_prometheus_name(name) || name.starts_with("__") {
+                    return Err(format!("Invalid label name: '{}'", name));
+                }
+                Ok((name.to_string(), value.trim().to_string()))
+            })
+            .collect()
+    }
+}
+
+// Function: spawn_metrics_endpoint
+//
+// Serves GET /metrics in the Prometheus text format on a background task,
+// and accepts metrics pushed by other servers on POST /metrics/push.
+//
+// Arguments:
+//     server: The monitoring server whose metrics are exported
+//     config: Where to listen and which labels to add
+//
+// Returns:
+//     The address the endpoint listens on and the task serving it
+fn spawn_metrics_endpoint(
+    server: Arc<MonitoringServer>,
+    config: MetricsEndpointConfig,
+) -> hyper::Result<(SocketAddr, JoinHandle<hyper::Result<()>>)> {
+    let labels = Arc::new(config.labels);
+    let make_service = make_service_fn(move |_connection| {
+        let server = server.clone();
+        let labels = labels.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let server = server.clone();
+                let labels = labels.clone();
+                async move {
+                    if request.uri().path() == PUSH_PATH {
+                        return Ok::<_, Infallible>(receive_push(&server, request).await);
+                    }
+                    if request.uri().path() != METRICS_PATH {
+                        return Ok::<_, Infallible>(plain_response(StatusCode::NOT_FOUND, ""));
+                    }
+                    if request.method() != Method::GET {
+                        return Ok(plain_response(StatusCode::METHOD_NOT_ALLOWED, ""));
+                    }
+                    Ok(match server.prometheus_metrics(&labels).await {
+                        Ok(text) => Response::builder()
+                            .header("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
+                            .body(Body::from(text))
+                            .unwrap(),
+                        Err(e) => plain_response(StatusCode::INTERNAL_SERVER_ERROR, &e),
+                    })
+                }
+            }))
+        }
+    });
+
+    let running = hyper::Server::try_bind(&config.bind_address)?.serve(make_service);
+    let addr = running.local_addr();
+    Ok((addr, tokio::spawn(running)))
+}
+
+// Function: receive_push
+//
+// Handles POST /metrics/push, answering with the IDs of the alerts raised.
+async fn receive_push(server: &MonitoringServer, request: Request<Body>) -> Response<Body> {
+    if request.method() != Method::POST {
+        return plain_response(StatusCode::METHOD_NOT_ALLOWED, "");
+    }
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return plain_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let push: MetricsPush = match serde_json::from_slice(&body) {
+        Ok(push) => push,
+        Err(e) => return plain_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    match server.ingest_push(push) {
+        Ok(raised) => {
+            let ids: Vec<&str> = raised.iter().map(|alert| alert.id.as_str()).collect();
+            Response::builder()
+                .header("Content-Type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "alerts_raised": ids }).to_string(),
+                ))
+                .unwrap()
+        }
+        Err(e) => plain_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    }
+}
+
+fn plain_response(status: StatusCode, body: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+// Function: render_status_page
+//
+// Renders a status report as a self-contained HTML page.
+//
+// Arguments:
+//     report: The StatusReport to render
+//
+// Returns:
+//     The HTML document as a string
+fn render_status_page(report: &StatusReport) -> String {
+    let mut rows = String::new();
+    for service in &report.services {
+        let availability = service
+            .availability_percent
+            .map_or("n/a".to_string(), |percent| format!("{:.2}%", percent));
+        rows.push_str(&format!(
+            "      <tr><td>{}</td><td class=\"{}\">{}</td><td>{}</td></tr>\n",
+            html_escape(&service.service_name),
+            html_escape(&service.current_status),
+            html_escape(&service.current_status),
+            availability
+        ));
+    }
+
+    let incidents = if report.incidents.is_empty() {
+        "    <p>No incidents reported.</p>\n".to_string()
+    } else {
+        let items: String = report
+            .incidents
+            .iter()
+            .map(|alert| {
+                format!(
+                    "      <li class=\"{}\"><strong>{}</strong> ({}) at {}: {}</li>\n",
+                    html_escape(&alert.severity),
+                    html_escape(&alert.title),
+                    html_escape(&alert.severity),
+                    alert.timestamp,
+                    html_escape(&alert.description)
+                )
+            })
+            .collect();
+        format!("    <ul>\n{}    </ul>\n", items)
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Service Status</title>
+  <style>
+    body {{ font-family: sans-serif; max-width: 48rem; margin: 2rem auto; }}
+    table {{ width: 100%; border-collapse: collapse; }}
+    td, th {{ padding: 0.4rem; border-bottom: 1px solid #ddd; text-align: left; }}
+    .healthy, .operational {{ color: #1a7f37; }}
+    .degraded, .warning {{ color: #9a6700; }}
+    .unhealthy, .major_outage, .critical {{ color: #cf222e; }}
+  </style>
+</head>
+<body>
+  <h1>Service Status: <span class="{status}">{status}</span></h1>
+  <p>Generated at {generated_at} (Unix time)</p>
+  <table>
+    <thead>
+      <tr><th>Service</th><th>Status</th><th>{window}-day availability</th></tr>
+    </thead>
+    <tbody>
+{rows}    </tbody>
+  </table>
+  <h2>Recent incidents</h2>
+{incidents}</body>
+</html>
+"#,
+        status = html_escape(&report.overall_status),
+        generated_at = report.generated_at,
+        window = report.window_days,
+        rows = rows,
+        incidents = incidents,
+    )
+}
+
+// Function: html_escape
+//
+// Escapes text for safe inclusion in HTML element content and attributes.
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// Trait implementation: StatefulServer
+//
+// Allows the metrics history and active alerts to be dumped and restored,
+// so a demo can start from a realistic history instead of an empty one.
+impl StatefulServer for MonitoringServer {
+    const STATE_NAME: &'static str = "monitoring";
+
+    async fn snapshot(&self) -> Result<Value, StateError> {
+        let history = self.metrics_history.lock().unwrap().clone();
+        let state = MonitoringState {
+            metrics_history: history.raw,
+            minute_history: history.minute,
+            hourly_history: history.hourly,
+            active_alerts: self.active_alerts.lock().unwrap().clone(),
+            health_history: self.health_history.lock().unwrap().clone(),
+            services_to_monitor: self.services_to_monitor.clone(),
+            incidents: self.incidents.lock().unwrap().clone(),
+        };
+        Ok(serde_json::to_value(state)?)
+    }
 
-        Ok(cleared)
+    async fn restore(&mut self, state: Value) -> Result<(), StateError> {
+        let mut state: MonitoringState = serde_json::from_value(state)?;
+
+        // Respect the history limits even if the snapshot was edited by hand
+        // or is older than the retention periods
+        let mut history = MetricsStore {
+            raw: Vec::new(),
+            minute: state.minute_history,
+            hourly: state.hourly_history,
+        };
+        for metrics in state.metrics_history {
+            history.push(metrics);
+        }
+        history.compact(self.get_current_timestamp());
+        let excess = state
+            .health_history
+            .len()
+            .saturating_sub(MAX_HEALTH_HISTORY_SIZE);
+        state.health_history.drain(..excess);
+
+        *self.metrics_history.lock().unwrap() = history;
+        *self.active_alerts.lock().unwrap() = state.active_alerts;
+        *self.health_history.lock().unwrap() = state.health_history;
+        *self.incidents.lock().unwrap() = state.incidents;
+        self.services_to_monitor = state.services_to_monitor;
+        Ok(())
     }
+}
 
-    // Function: get_current_timestamp
-    //
-    // Gets the current Unix timestamp for consistent time tracking.
-    //
-    // Returns:
-    //     Current Unix timestamp in seconds
-    fn get_current_timestamp(&self) -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
+// Trait implementation: ToolServer
+//
+// Serves the monitoring tools over stdio (--stdio) and to clients that work
+// with several servers.
+impl ToolServer for MonitoringServer {
+    fn server_name(&self) -> &str {
+        "monitoring"
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.list_tools()
+    }
+
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(self.call_tool(name, arguments))
     }
 }
 
@@ -682,12 +4129,55 @@ impl MonitoringServer {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logging for observability
-    tracing_subscriber::fmt::init();
+    let _telemetry = mcp_rust_examples::logging::init("error");
 
     eprintln!("🚀 Starting Monitoring and Metrics Server");
     eprintln!("==========================================");
 
-    let server = MonitoringServer::new();
+    let state_command = StateCommand::from_env()?;
+    let thresholds_file =
+        std::env::var(THRESHOLDS_FILE_ENV).unwrap_or_else(|_| DEFAULT_THRESHOLDS_FILE.to_string());
+    let mut server = MonitoringServer::new().with_thresholds_file(thresholds_file)?;
+    if let Ok(path) = std::env::var(ALERT_SINKS_FILE_ENV) {
+        server = server.with_alert_sinks_file(path)?;
+    }
+    if let Ok(url) = std::env::var(DATABASE_HEALTH_URL_ENV) {
+        let arguments = serde_json::json!({ "timeout_ms": 2000 });
+        let probe = McpToolProbe::new(&url, DATABASE_PING_TOOL, arguments)?;
+        server = server.with_health_probe("database", Arc::new(probe));
+    }
+    if let Ok(path) = std::env::var(LOG_METRIC_RULES_FILE_ENV) {
+        server = server.with_log_metric_rules_file(path)?;
+    }
+    state_command.restore(&mut server).await?;
+
+    // With --stdio, serve the tools to an MCP client instead of running the
+    // demo, and Prometheus metrics over HTTP if MCP_METRICS_ADDRESS is set.
+    // Alerts are pushed to the client unless MCP_ALERT_SINKS_FILE routes
+    // them elsewhere. State is dumped once the client disconnects.
+    if transport::stdio_requested() {
+        let notifications = server.alert_notifications();
+        if !server.has_alert_sinks() {
+            let sink = Arc::new(McpNotificationSink::new(notifications.clone()));
+            server = server.with_alert_sink(sink, &[])?;
+        }
+        let server = Arc::new(server);
+        MonitoringServer::spawn_compaction(server.clone());
+        MonitoringServer::spawn_log_metrics(server.clone());
+        if let Some(config) = MetricsEndpointConfig::from_env()? {
+            let (addr, _) = spawn_metrics_endpoint(server.clone(), config)?;
+            eprintln!("📈 Prometheus metrics at http://{}{}", addr, METRICS_PATH);
+            eprintln!(
+                "📥 Accepting pushed metrics at http://{}{}",
+                addr, PUSH_PATH
+            );
+        }
+        StdioTransport::new()
+            .serve(&McpServer::new(server.clone()).with_log_messages(notifications))
+            .await?;
+        state_command.dump(&*server).await?;
+        return Ok(());
+    }
 
     eprintln!("\n🧪 Monitoring and Metrics Demo:");
 
@@ -707,8 +4197,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Ok(result) => {
             let metrics: SystemMetrics = serde_json::from_value(result).unwrap();
             eprintln!("  ✅ Metrics collected successfully");
-            eprintln!("     CPU Usage: {:.1}%", metrics.cpu_usage_percent);
+            eprintln!(
+                "     CPU Usage: {:.1}% across {} cores",
+                metrics.cpu_usage_percent,
+                metrics.cpu_per_core_percent.len()
+            );
             eprintln!("     Memory Usage: {:.1}%", metrics.memory_usage_percent);
+            eprintln!(
+                "     Server RSS: {:.1} MiB",
+                metrics.process_rss_bytes as f64 / (1024.0 * 1024.0)
+            );
             eprintln!("     Disk Usage: {:.1}%", metrics.disk_usage_percent);
             eprintln!("     Active Connections: {}", metrics.active_connections);
             eprintln!("     Uptime: {} seconds", metrics.uptime_seconds);
@@ -736,6 +4234,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         Err(e) => eprintln!("  ❌ History retrieval failed: {}", e),
     }
+    match server
+        .call_tool(
+            "get_metrics_history",
+            serde_json::json!({"resolution": "minute"}),
+        )
+        .await
+    {
+        Ok(result) => {
+            let points = result["metrics"].as_array().cloned().unwrap_or_default();
+            let samples: u64 = points.iter().filter_map(|p| p["samples"].as_u64()).sum();
+            eprintln!(
+                "  ✅ {} minute averages covering {} samples",
+                points.len(),
+                samples
+            );
+        }
+        Err(e) => eprintln!("  ❌ History retrieval failed: {}", e),
+    }
+    match server
+        .call_tool(
+            "get_metrics_summary",
+            serde_json::json!({"metric_name": "cpu_usage_percent", "window_seconds": 3600}),
+        )
+        .await
+    {
+        Ok(result) => {
+            let summary: MetricSummary = serde_json::from_value(result).unwrap();
+            eprintln!(
+                "  ✅ CPU over the last hour: peak {:.1}%, p95 {:.1}%, average {:.1}%",
+                summary.max.unwrap_or_default(),
+                summary.p95.unwrap_or_default(),
+                summary.avg.unwrap_or_default()
+            );
+        }
+        Err(e) => eprintln!("  ❌ Summary failed: {}", e),
+    }
 
     // Demonstrate health checks
     eprintln!("\n🏥 Performing health checks:");
@@ -804,6 +4338,175 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         Err(e) => eprintln!("  ❌ Threshold configuration failed: {}", e),
     }
+    match server
+        .call_tool("list_alert_thresholds", serde_json::json!({}))
+        .await
+    {
+        Ok(result) => {
+            let thresholds: Vec<AlertThreshold> =
+                serde_json::from_value(result["thresholds"].clone()).unwrap_or_default();
+            for threshold in thresholds {
+                eprintln!(
+                    "     {} > {} ({})",
+                    threshold.metric_name, threshold.threshold, threshold.severity
+                );
+            }
+            eprintln!("     Saved to {}", result["thresholds_file"]);
+        }
+        Err(e) => eprintln!("  ❌ Listing thresholds failed: {}", e),
+    }
+
+    // Demonstrate incident handling: simulated CPU spikes become one incident
+    // that is worked on and exported as a postmortem
+    eprintln!("\n🚨 Incident handling:");
+    let spiking = MonitoringServer::new().with_collector(Arc::new(SimulatedCollector {
+        cpu_usage_percent: Some(96.0),
+    }));
+    for _ in 0..3 {
+        let _ = spiking.record_current_metrics().await;
+    }
+    let update = serde_json::json!({
+        "incident_id": "INC-1",
+        "status": "resolved",
+        "assignee": "on-call",
+        "note": "Throttled the batch job",
+        "author": "on-call"
+    });
+    match spiking.call_tool("update_incident", update).await {
+        Ok(result) => {
+            let incident: Incident = serde_json::from_value(result).unwrap();
+            eprintln!(
+                "  ✅ {} grouped {} alerts; {} by {}",
+                incident.id,
+                incident.alerts.len(),
+                incident.status,
+                incident.assignee.as_deref().unwrap_or("nobody")
+            );
+        }
+        Err(e) => eprintln!("  ❌ Incident update failed: {}", e),
+    }
+    match spiking
+        .call_tool(
+            "export_postmortem",
+            serde_json::json!({"incident_id": "INC-1"}),
+        )
+        .await
+    {
+        Ok(result) => {
+            let lines = result["content"].as_str().unwrap_or_default().lines();
+            eprintln!("  ✅ Postmortem: {} lines", lines.count());
+        }
+        Err(e) => eprintln!("  ❌ Postmortem export failed: {}", e),
+    }
+
+    // Demonstrate alert delivery: an MCP sink receives critical alerts only
+    eprintln!("\n🔔 Alert delivery:");
+    let notifications = broadcast::channel(ALERT_NOTIFICATION_CAPACITY).0;
+    let mut client = notifications.subscribe();
+    let routed = MonitoringServer::new()
+        .with_collector(Arc::new(SimulatedCollector {
+            cpu_usage_percent: Some(97.0),
+        }))
+        .with_alert_sink(
+            Arc::new(McpNotificationSink::new(notifications)),
+            &["critical".to_string()],
+        )?;
+    let _ = routed
+        .call_tool(
+            "set_alert_threshold",
+            serde_json::json!({"metric_name": "cpu_usage_percent", "threshold": 90.0, "severity": "critical"}),
+        )
+        .await;
+    let _ = routed.record_current_metrics().await;
+    match tokio::time::timeout(Duration::from_secs(1), client.recv()).await {
+        Ok(Ok(message)) => eprintln!(
+            "  ✅ Pushed {:?} notification: {}",
+            message.level, message.data["title"]
+        ),
+        _ => eprintln!("  ❌ No alert notification arrived"),
+    }
+
+    // Demonstrate status page generation
+    eprintln!("\n📰 Generating status report:");
+    match server
+        .call_tool(
+            "generate_status_report",
+            serde_json::json!({"format": "json", "refresh": false}),
+        )
+        .await
+    {
+        Ok(result) => {
+            if let Ok(report) = serde_json::from_value::<StatusReport>(result) {
+                eprintln!("  ✅ Overall status: {}", report.overall_status);
+                for service in &report.services {
+                    eprintln!(
+                        "     - {}: {} ({:.1}% over {} days)",
+                        service.service_name,
+                        service.current_status,
+                        service.availability_percent.unwrap_or_default(),
+                        report.window_days
+                    );
+                }
+                eprintln!("     Recent incidents: {}", report.incidents.len());
+            }
+        }
+        Err(e) => eprintln!("  ❌ Status report failed: {}", e),
+    }
+
+    // Demonstrate metrics derived from log events, here relayed from a log
+    // stream like example 10's
+    eprintln!("\n🪵 Metrics from log events:");
+    let rule = serde_json::json!({
+        "name": "stream_log_errors_total",
+        "level": "error",
+        "labels": ["component"]
+    });
+    let events = serde_json::json!([
+        {"level": "ERROR", "component": "database", "message": "Connection refused"},
+        {"level": "INFO", "component": "api", "message": "Request served"},
+        {"level": "ERROR", "component": "api", "message": "Upstream timed out"}
+    ]);
+    match server.call_tool("define_log_metric", rule).await {
+        Ok(_) => {
+            let arguments = serde_json::json!({ "source": "example_10", "events": events });
+            if let Ok(result) = server.call_tool("ingest_log_events", arguments).await {
+                eprintln!(
+                    "  ✅ Ingested {} events, {} recorded",
+                    result["events"], result["recorded"]
+                );
+            }
+            if let Ok(result) = server
+                .call_tool("list_log_metrics", serde_json::json!({}))
+                .await
+            {
+                for entry in result["rules"].as_array().into_iter().flatten() {
+                    eprintln!(
+                        "     {}: {} matched",
+                        entry["rule"]["name"].as_str().unwrap_or_default(),
+                        entry["stats"]["matched"]
+                    );
+                }
+            }
+        }
+        Err(e) => eprintln!("  ❌ Defining the rule failed: {}", e),
+    }
+
+    // Demonstrate the Prometheus exposition served at /metrics
+    eprintln!("\n📈 Prometheus exposition:");
+    match server
+        .prometheus_metrics(&[("instance".to_string(), "demo".to_string())])
+        .await
+    {
+        Ok(text) => {
+            for line in text.lines().filter(|line| !line.starts_with('#')).take(5) {
+                eprintln!("     {}", line);
+            }
+            eprintln!("     ...");
+        }
+        Err(e) => eprintln!("  ❌ Exposition failed: {}", e),
+    }
+
+    state_command.dump(&server).await?;
 
     eprintln!("\n🎉 Monitoring and Metrics demo completed!");
     eprintln!("\n✨ This is example 11 of 20 progressive MCP examples.");
@@ -814,11 +4517,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("   - Threshold-based alerting and notification");
     eprintln!("   - Historical data management and trend analysis");
     eprintln!("   - Configurable monitoring parameters");
+    eprintln!("   - Status pages with per-service availability");
     eprintln!("\n🔧 Key production monitoring concepts covered:");
-    eprintln!("   - Circular buffer for metrics history management");
+    eprintln!("   - Tiered retention with downsampling for metrics history");
     eprintln!("   - Thread-safe data structures for concurrent access");
-    eprintln!("   - Realistic simulation of system resource monitoring");
+    eprintln!("   - Real host metrics behind a collector trait, simulated in tests");
     eprintln!("   - Alert lifecycle management (creation, filtering, clearing)");
+    eprintln!("   - Incidents correlating alerts, with postmortem timelines");
+    eprintln!("   - Alert sinks for Slack, PagerDuty and MCP clients by severity");
+    eprintln!("   - Counters and histograms derived from log events by match rules");
     eprintln!("   - Extensible architecture for additional monitoring tools");
 
     Ok(())
@@ -841,13 +4548,19 @@ mod tests {
         let server = MonitoringServer::new();
         let tools = server.list_tools();
 
-        assert_eq!(tools.len(), 6);
+        assert_eq!(tools.len(), 16);
         assert!(tools.iter().any(|t| t.name == "get_current_metrics"));
         assert!(tools.iter().any(|t| t.name == "get_metrics_history"));
+        assert!(tools.iter().any(|t| t.name == "get_metrics_summary"));
         assert!(tools.iter().any(|t| t.name == "perform_health_check"));
         assert!(tools.iter().any(|t| t.name == "get_active_alerts"));
         assert!(tools.iter().any(|t| t.name == "clear_alert"));
         assert!(tools.iter().any(|t| t.name == "set_alert_threshold"));
+        assert!(tools.iter().any(|t| t.name == "list_alert_thresholds"));
+        assert!(tools.iter().any(|t| t.name == "list_incidents"));
+        assert!(tools.iter().any(|t| t.name == "update_incident"));
+        assert!(tools.iter().any(|t| t.name == "export_postmortem"));
+        assert!(tools.iter().any(|t| t.name == "generate_status_report"));
     }
 
     #[tokio::test]
@@ -863,6 +4576,30 @@ mod tests {
         assert!(metrics.memory_usage_percent >= 0.0 && metrics.memory_usage_percent <= 100.0);
         // uptime_seconds might be 0 in fast test environments
         assert!(metrics.uptime_seconds < 1000); // Just verify it's a reasonable value
+
+        // Figures that only real collection provides
+        assert!(!metrics.cpu_per_core_percent.is_empty());
+        assert!(metrics
+            .cpu_per_core_percent
+            .iter()
+            .all(|usage| (0.0..=100.0).contains(usage)));
+        assert!(metrics.process_rss_bytes > 0);
+    }
+
+    #[tokio::test]
+    async fn test_simulated_collector_alerts() {
+        let server = MonitoringServer::new().with_collector(Arc::new(SimulatedCollector {
+            cpu_usage_percent: Some(95.0),
+        }));
+        let result = server
+            .call_tool("get_current_metrics", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(result["cpu_usage_percent"], 95.0);
+
+        let alerts = server.get_active_alerts(None).await.unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].metric_name, "cpu_usage_percent");
     }
 
     #[tokio::test]
@@ -880,6 +4617,74 @@ mod tests {
         assert_eq!(health_data.get("checks_performed").unwrap(), 1);
     }
 
+    // A database server's ping_database tool, answering what it is told to
+    struct Pinger(Mutex<Result<Value, McpError>>);
+
+    impl ToolServer for Pinger {
+        fn server_name(&self) -> &str {
+            "database"
+        }
+
+        fn tool_descriptors(&self) -> Vec<Tool> {
+            vec![Tool {
+                name: DATABASE_PING_TOOL.to_string(),
+                description: "Check the database".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: Some(ToolAnnotations::read_only()),
+            }]
+        }
+
+        fn invoke_tool<'a>(
+            &'a self,
+            _name: &'a str,
+            _arguments: Value,
+        ) -> BoxFuture<'a, Result<Value, McpError>> {
+            let answer = self.0.lock().unwrap().clone();
+            Box::pin(async move { answer })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_probes_call_mcp_tools() {
+        let degraded =
+            serde_json::json!({ "status": "degraded", "message": "2 call(s) are waiting" });
+        let pinger = Arc::new(Pinger(Mutex::new(Ok(degraded))));
+        let (addr, running) = http_transport::HttpTransport::new("127.0.0.1:0".parse().unwrap())
+            .spawn(Arc::new(McpServer::new(pinger.clone())))
+            .unwrap();
+        let url = format!("http://{}/mcp", addr);
+        let probe = McpToolProbe::new(&url, DATABASE_PING_TOOL, Value::Null).unwrap();
+        let server = MonitoringServer::new().with_health_probe("database", Arc::new(probe));
+
+        let results = server.perform_health_checks("database").await.unwrap();
+        assert_eq!(results[0].status, "degraded");
+        assert_eq!(results[0].message, "2 call(s) are waiting");
+        let details = results[0].details.clone().unwrap();
+        assert_eq!(details["check_type"], "mcp_tool");
+        assert_eq!(details["endpoint"], format!("{}#ping_database", url));
+
+        // Tool errors and unreachable servers are both unhealthy
+        *pinger.0.lock().unwrap() = Err(McpError::Unavailable("pool closed".to_string()));
+        let results = server.perform_health_checks("database").await.unwrap();
+        assert_eq!(results[0].status, "unhealthy");
+        assert!(
+            results[0].message.contains("pool closed"),
+            "{}",
+            results[0].message
+        );
+        running.abort();
+        let _ = running.await;
+        let results = server.perform_health_checks("database").await.unwrap();
+        assert_eq!(results[0].status, "unhealthy");
+
+        // Services without a probe are still checked synthetically
+        let results = server.perform_health_checks("cache").await.unwrap();
+        assert_eq!(
+            results[0].details.as_ref().unwrap()["check_type"],
+            "synthetic"
+        );
+    }
+
     #[tokio::test]
     async fn test_alert_management() {
         let server = MonitoringServer::new();
@@ -898,7 +4703,7 @@ mod tests {
     async fn test_threshold_configuration() {
         let server = MonitoringServer::new();
         let threshold_config = serde_json::json!({
-            "metric_name": "test_metric",
+            "metric_name": "disk_usage_percent",
             "threshold": 80.0,
             "severity": "warning"
         });
@@ -910,5 +4715,698 @@ mod tests {
 
         let config_data: Value = result.unwrap();
         assert_eq!(config_data.get("success").unwrap(), true);
+
+        let result = server
+            .call_tool(
+                "set_alert_threshold",
+                serde_json::json!({"metric_name": "test_metric", "threshold": 1.0, "severity": "info"}),
+            )
+            .await;
+        assert!(matches!(result, Err(McpError::InvalidParams(_))));
+    }
+
+    #[tokio::test]
+    async fn test_thresholds_drive_alerts_and_persist() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("thresholds.json");
+        let collector = Arc::new(SimulatedCollector {
+            cpu_usage_percent: Some(70.0),
+        });
+        let server = MonitoringServer::new()
+            .with_collector(collector.clone())
+            .with_thresholds_file(&path)
+            .unwrap();
+
+        // 70% is below the default CPU threshold; lowering it raises an alert
+        // with the configured severity
+        server.record_current_metrics().await.unwrap();
+        assert!(server.get_active_alerts(None).await.unwrap().is_empty());
+        let result = server
+            .call_tool(
+                "set_alert_threshold",
+                serde_json::json!({"metric_name": "cpu_usage_percent", "threshold": 60.0, "severity": "critical"}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result["previous"]["threshold"],
+            DEFAULT_CPU_THRESHOLD_PERCENT
+        );
+        assert_eq!(result["persisted"], true);
+        server.record_current_metrics().await.unwrap();
+        let alerts = server.get_active_alerts(None).await.unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(
+            (alerts[0].severity.as_str(), alerts[0].threshold),
+            ("critical", 60.0)
+        );
+
+        // A new server picks the thresholds up from the file
+        let restarted = MonitoringServer::new()
+            .with_collector(collector)
+            .with_thresholds_file(&path)
+            .unwrap();
+        let listed = restarted
+            .call_tool("list_alert_thresholds", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(listed["total_thresholds"], 2);
+        let thresholds: Vec<AlertThreshold> =
+            serde_json::from_value(listed["thresholds"].clone()).unwrap();
+        assert_eq!(
+            thresholds[0],
+            AlertThreshold::new("cpu_usage_percent", 60.0, "critical")
+        );
+        assert_eq!(thresholds[1].metric_name, "memory_usage_percent");
+    }
+
+    fn sample(timestamp: u64, cpu_usage_percent: f64) -> SystemMetrics {
+        SystemMetrics {
+            timestamp,
+            cpu_usage_percent,
+            cpu_per_core_percent: vec![cpu_usage_percent; 2],
+            memory_usage_percent: 50.0,
+            process_rss_bytes: 1024,
+            disk_usage_percent: 40.0,
+            network_bytes_sent: timestamp,
+            network_bytes_received: timestamp,
+            active_connections: 10,
+            uptime_seconds: timestamp,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_retention_tiers() {
+        // One sample every 30 seconds for two days
+        let now = 2 * 24 * 60 * 60;
+        let mut store = MetricsStore::default();
+        for timestamp in (0..now).step_by(30) {
+            let cpu = if timestamp % 60 == 0 { 10.0 } else { 30.0 };
+            store.push(sample(timestamp, cpu));
+        }
+        store.compact(now);
+
+        // The last hour stays raw; the day before it is kept as minute
+        // averages and everything older as hourly averages
+        assert_eq!(store.raw.len(), 120);
+        assert_eq!(store.raw[0].timestamp, now - RAW_RETENTION_SECONDS);
+        assert_eq!(store.minute.len(), 23 * 60);
+        assert_eq!(
+            store.minute[0].metrics.timestamp,
+            now - MINUTE_RETENTION_SECONDS
+        );
+        assert_eq!(store.hourly.len(), 24);
+        let hour = &store.hourly[0];
+        assert_eq!((hour.metrics.timestamp, hour.samples), (0, 120));
+        assert_eq!(hour.metrics.cpu_usage_percent, 20.0);
+        assert_eq!(hour.metrics.cpu_per_core_percent, vec![20.0, 20.0]);
+        assert_eq!(hour.metrics.network_bytes_sent, 3570);
+
+        // Coarser resolutions reach up to the latest sample
+        let minutes = store.history(Resolution::Minute, 5);
+        assert_eq!(minutes.len(), 5);
+        assert_eq!(minutes[4].metrics.timestamp, now - 60);
+        assert_eq!(minutes[4].samples, 2);
+        let hours = store.history(Resolution::Hour, 1000);
+        assert_eq!(hours.len(), 48);
+        assert!(hours.iter().all(|p| p.samples == 120));
+        assert_eq!(store.history(Resolution::Raw, 1000).len(), 120);
+
+        // Hourly averages expire after 30 days
+        store.compact(now + HOUR_RETENTION_SECONDS);
+        assert!(store.raw.is_empty() && store.minute.is_empty() && store.hourly.is_empty());
+
+        let server =
+            MonitoringServer::new().with_collector(Arc::new(SimulatedCollector::default()));
+        server.record_current_metrics().await.unwrap();
+        let result = server
+            .call_tool(
+                "get_metrics_history",
+                serde_json::json!({"resolution": "hour"}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result["resolution"], "hour");
+        assert_eq!(result["metrics"][0]["samples"], 1);
+        let invalid = server
+            .call_tool(
+                "get_metrics_history",
+                serde_json::json!({"resolution": "day"}),
+            )
+            .await;
+        assert!(matches!(invalid, Err(McpError::InvalidParams(_))));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_summary() {
+        let server = MonitoringServer::new();
+        let now = server.get_current_timestamp();
+        {
+            let mut history = server.metrics_history.lock().unwrap();
+            // CPU at 1% to 100% over the last 100 seconds, and an old spike
+            // outside the window
+            history.push(sample(now - 7200, 100.0));
+            for i in 1..=100 {
+                history.push(sample(now - 100 + i, i as f64));
+            }
+        }
+
+        let result = server
+            .call_tool(
+                "get_metrics_summary",
+                serde_json::json!({"metric_name": "cpu_usage_percent", "window_seconds": 600}),
+            )
+            .await
+            .unwrap();
+        let summary: MetricSummary = serde_json::from_value(result).unwrap();
+        assert_eq!(summary.resolution, Resolution::Raw);
+        assert_eq!(summary.samples, 100);
+        assert_eq!((summary.min, summary.max), (Some(1.0), Some(100.0)));
+        assert_eq!(summary.avg, Some(50.5));
+        assert_eq!(
+            (summary.p50, summary.p95, summary.p99),
+            (Some(50.0), Some(95.0), Some(99.0))
+        );
+        assert_eq!(summary.to, Some(now));
+
+        // Longer windows read minute averages and include the old spike
+        let summary = server
+            .get_metrics_summary("cpu_usage_percent", 3 * 3600)
+            .unwrap();
+        assert_eq!(summary.resolution, Resolution::Minute);
+        assert_eq!(summary.samples, 101);
+        assert_eq!(summary.max, Some(100.0));
+
+        let empty = MonitoringServer::new()
+            .get_metrics_summary("active_connections", 60)
+            .unwrap();
+        assert_eq!((empty.samples, empty.avg), (0, None));
+        assert!(server.get_metrics_summary("load_average", 60).is_err());
+        assert!(server
+            .get_metrics_summary("cpu_usage_percent", HOUR_RETENTION_SECONDS + 1)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_restore() {
+        let server =
+            MonitoringServer::new().with_collector(Arc::new(SimulatedCollector::default()));
+        server
+            .call_tool("get_current_metrics", serde_json::json!({}))
+            .await
+            .unwrap();
+        let snapshot = server.snapshot().await.unwrap();
+
+        let mut restored = MonitoringServer::new();
+        restored.restore(snapshot).await.unwrap();
+
+        let history = restored
+            .get_metrics_history(Resolution::Raw, 10)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_incidents_group_related_alerts() {
+        let server = MonitoringServer::new().with_collector(Arc::new(SimulatedCollector {
+            cpu_usage_percent: Some(95.0),
+        }));
+
+        // Two CPU alerts in quick succession are one incident
+        server.record_current_metrics().await.unwrap();
+        server.record_current_metrics().await.unwrap();
+        let incidents = server.list_incidents(None).unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(
+            (incidents[0].id.as_str(), incidents[0].alerts.len()),
+            ("INC-1", 2)
+        );
+        assert_eq!(incidents[0].service, HOST_SERVICE);
+
+        // A critical alert outside the window opens a new incident
+        let mut late = incidents[0].alerts[0].clone();
+        late.id = "memory-late".to_string();
+        late.severity = "critical".to_string();
+        late.timestamp += INCIDENT_CORRELATION_WINDOW_SECONDS + 60;
+        server.correlate_alerts(&[late.clone()]).unwrap();
+        let incidents = server.list_incidents(Some("open")).unwrap();
+        assert_eq!(incidents.len(), 2);
+        assert_eq!(
+            (incidents[0].id.as_str(), incidents[0].severity.as_str()),
+            ("INC-2", "critical")
+        );
+
+        // Status, assignee and notes are tracked on the timeline
+        let update = serde_json::json!({
+            "incident_id": "INC-1",
+            "status": "resolved",
+            "assignee": "alice",
+            "note": "Runaway | batch job killed",
+            "author": "alice"
+        });
+        let result = server.call_tool("update_incident", update).await.unwrap();
+        let incident: Incident = serde_json::from_value(result).unwrap();
+        assert_eq!(incident.assignee.as_deref(), Some("alice"));
+        assert!(incident.resolved_at.is_some());
+        let kinds: Vec<&str> = incident.timeline.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(
+            kinds,
+            ["opened", "alert", "alert", "status", "assigned", "note"]
+        );
+
+        // Resolved incidents take no more alerts
+        late.id = "cpu-after".to_string();
+        late.timestamp = incident.opened_at;
+        server.correlate_alerts(&[late]).unwrap();
+        assert_eq!(server.list_incidents(None).unwrap().len(), 3);
+
+        let result = server
+            .call_tool(
+                "export_postmortem",
+                serde_json::json!({"incident_id": "INC-1"}),
+            )
+            .await
+            .unwrap();
+        let content = result["content"].as_str().unwrap();
+        assert!(content.starts_with("# Postmortem: INC-1 High CPU Usage"));
+        assert!(content.contains("| note | alice | Runaway \\| batch job killed |"));
+        assert!(content.contains("| cpu_usage_percent | 95.0 | 80 |"));
+
+        let invalid = serde_json::json!({"incident_id": "INC-1", "status": "closed"});
+        assert!(matches!(
+            server.call_tool("update_incident", invalid).await,
+            Err(McpError::InvalidParams(_))
+        ));
+        let missing = serde_json::json!({"incident_id": "INC-9"});
+        assert!(matches!(
+            server.call_tool("export_postmortem", missing).await,
+            Err(McpError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_status_report() {
+        let server = MonitoringServer::new();
+        server.active_alerts.lock().unwrap().push(Alert {
+            id: "cpu-1".to_string(),
+            severity: "warning".to_string(),
+            title: "High <CPU> Usage".to_string(),
+            description: "CPU usage is high".to_string(),
+            service: host_service(),
+            metric_name: "cpu_usage_percent".to_string(),
+            threshold: 80.0,
+            current_value: 91.0,
+            timestamp: server.get_current_timestamp(),
+        });
+
+        let result = server
+            .call_tool("generate_status_report", serde_json::json!({}))
+            .await
+            .unwrap();
+        let report: StatusReport = serde_json::from_value(result).unwrap();
+        assert_eq!(report.overall_status, "degraded"); // The cache is degraded
+        assert_eq!(report.services.len(), 4);
+        assert!(report
+            .services
+            .iter()
+            .all(|s| s.availability_percent == Some(100.0) && s.checks_in_window == 1));
+        assert_eq!(report.incidents.len(), 1);
+
+        let html = server
+            .call_tool(
+                "generate_status_report",
+                serde_json::json!({"format": "html", "refresh": false}),
+            )
+            .await
+            .unwrap();
+        let content = html["content"].as_str().unwrap();
+        assert!(content.starts_with("<!DOCTYPE html>"));
+        assert!(content.contains("High &lt;CPU&gt; Usage"));
+        assert!(content.contains("100.00%"));
+    }
+
+    #[tokio::test]
+    async fn test_prometheus_endpoint() {
+        let server = Arc::new(MonitoringServer::new().with_collector(Arc::new(
+            SimulatedCollector {
+                cpu_usage_percent: Some(95.0),
+            },
+        )));
+        server.perform_health_checks("all").await.unwrap();
+        let config = MetricsEndpointConfig {
+            bind_address: "127.0.0.1:0".parse().unwrap(),
+            labels: MetricsEndpointConfig::parse_labels("instance=web-1, env=test").unwrap(),
+        };
+        let (addr, endpoint) = spawn_metrics_endpoint(server.clone(), config).unwrap();
+
+        let response = reqwest::get(format!("http://{}/metrics", addr))
+            .await
+            .unwrap();
+        assert!(response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain; version=0.0.4"));
+        let text = response.text().await.unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.contains(&"# TYPE system_cpu_usage_percent gauge"));
+        assert!(lines.contains(&r#"system_cpu_usage_percent{instance="web-1",env="test"} 95"#));
+        assert!(lines.contains(
+            &r#"system_cpu_core_usage_percent{instance="web-1",env="test",core="3"} 95"#
+        ));
+        // The scrape recorded a sample, which raised a CPU alert
+        assert!(lines.contains(
+            &r#"monitoring_active_alerts{instance="web-1",env="test",severity="warning"} 1"#
+        ));
+        assert!(lines
+            .contains(&r#"monitoring_service_up{instance="web-1",env="test",service="cache"} 1"#));
+        assert_eq!(
+            server
+                .get_metrics_history(Resolution::Raw, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let missing = reqwest::get(format!("http://{}/other", addr))
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), 404);
+        endpoint.abort();
+
+        assert!(MetricsEndpointConfig::parse_labels("no_value").is_err());
+        assert!(MetricsEndpointConfig::parse_labels("bad-name=1").is_err());
+        assert!(MetricsEndpointConfig::parse_labels("__reserved=1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pushed_metrics_and_alerts() {
+        let server = Arc::new(MonitoringServer::new().with_collector(Arc::new(
+            SimulatedCollector {
+                cpu_usage_percent: Some(10.0),
+            },
+        )));
+        let config = MetricsEndpointConfig {
+            bind_address: "127.0.0.1:0".parse().unwrap(),
+            labels: Vec::new(),
+        };
+        let (addr, endpoint) = spawn_metrics_endpoint(server.clone(), config).unwrap();
+        let client = reqwest::Client::new();
+        let push_url = format!("http://{}/metrics/push", addr);
+
+        // The queue of example 12 pushes its depth along with a backlog alert
+        let push = serde_json::json!({
+            "source": "task_queue",
+            "metrics": {"task_queue_depth": 12.0, "task_queue_oldest_wait_seconds": 1.5},
+            "alerts": [{
+                "severity": "warning",
+                "title": "Task Queue Backlog",
+                "description": "12 tasks are queued, more than the limit of 5",
+                "metric_name": "task_queue_depth",
+                "threshold": 5.0,
+                "current_value": 12.0
+            }]
+        });
+        let response = client.post(&push_url).json(&push).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["alerts_raised"].as_array().unwrap().len(), 1);
+
+        let alerts = server.get_active_alerts(Some("warning")).await.unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].service, "task_queue");
+        let incidents = server.list_incidents(None).unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(incidents[0].service, "task_queue");
+
+        let text = reqwest::get(format!("http://{}/metrics", addr))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.contains(&"# TYPE task_queue_depth gauge"));
+        assert!(lines.contains(&r#"task_queue_depth{source="task_queue"} 12"#));
+        assert!(lines.contains(&r#"task_queue_oldest_wait_seconds{source="task_queue"} 1.5"#));
+
+        // A later push replaces the source's metrics; bad pushes are rejected
+        let push =
+            serde_json::json!({"source": "task_queue", "metrics": {"task_queue_depth": 0.0}});
+        client.post(&push_url).json(&push).send().await.unwrap();
+        let text = reqwest::get(format!("http://{}/metrics", addr))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(text.contains(r#"task_queue_depth{source="task_queue"} 0"#));
+        assert!(!text.contains("task_queue_oldest_wait_seconds"));
+        for invalid in [
+            serde_json::json!({"source": "", "metrics": {}}),
+            serde_json::json!({"source": "q", "metrics": {"bad-name": 1.0}}),
+            serde_json::json!({"source": "q", "metrics": {}, "alerts": [{
+                "severity": "urgent", "title": "t", "description": "d",
+                "metric_name": "m", "threshold": 1.0, "current_value": 2.0
+            }]}),
+        ] {
+            let response = client.post(&push_url).json(&invalid).send().await.unwrap();
+            assert_eq!(response.status(), 400);
+        }
+        let response = client.get(&push_url).send().await.unwrap();
+        assert_eq!(response.status(), 405);
+        endpoint.abort();
+    }
+
+    // Serves webhooks locally, passing each JSON body received to the channel
+    fn spawn_webhook_receiver() -> (SocketAddr, tokio::sync::mpsc::UnboundedReceiver<Value>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let make_service = make_service_fn(move |_connection| {
+            let tx = tx.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let tx = tx.clone();
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                        tx.send(serde_json::from_slice(&body).unwrap()).unwrap();
+                        Ok::<_, Infallible>(plain_response(StatusCode::OK, "ok"))
+                    }
+                }))
+            }
+        });
+        let running = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let addr = running.local_addr();
+        tokio::spawn(running);
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_alerts_are_routed_to_sinks_by_severity() {
+        let wait = Duration::from_secs(5);
+        let (addr, mut received) = spawn_webhook_receiver();
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("sinks.json");
+        let sinks = serde_json::json!([
+            {"type": "webhook", "url": format!("http://{}/slack", addr), "format": "slack", "severities": ["warning"]},
+            {"type": "webhook", "url": format!("http://{}/pagerduty", addr), "format": "pagerduty", "routing_key": "key-1", "severities": ["critical"]},
+            {"type": "mcp"}
+        ]);
+        std::fs::write(&path, sinks.to_string()).unwrap();
+        let server = MonitoringServer::new()
+            .with_collector(Arc::new(SimulatedCollector {
+                cpu_usage_percent: Some(95.0),
+            }))
+            .with_alert_sinks_file(&path)
+            .unwrap();
+        let mut client = server.alert_notifications().subscribe();
+
+        // The default CPU threshold raises a warning, which goes to Slack
+        server.record_current_metrics().await.unwrap();
+        let slack = tokio::time::timeout(wait, received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(slack["text"], "[WARNING] High CPU Usage");
+        assert_eq!(slack["attachments"][0]["color"], "warning");
+        let notification = tokio::time::timeout(wait, client.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(notification.level, LoggingLevel::Warning);
+        assert_eq!(notification.logger.as_deref(), Some(ALERT_LOGGER));
+        assert_eq!(notification.data["metric_name"], "cpu_usage_percent");
+
+        // A critical threshold pages instead
+        server
+            .set_alert_threshold(AlertThreshold::new("cpu_usage_percent", 90.0, "critical"))
+            .await
+            .unwrap();
+        server.record_current_metrics().await.unwrap();
+        let page = tokio::time::timeout(wait, received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(page["routing_key"], "key-1");
+        assert_eq!(page["event_action"], "trigger");
+        assert_eq!(page["payload"]["severity"], "critical");
+        assert_eq!(page["payload"]["source"], HOST_SERVICE);
+        let alerts = server.get_active_alerts(Some("critical")).await.unwrap();
+        assert_eq!(page["dedup_key"], alerts[0].id.as_str());
+        let notification = tokio::time::timeout(wait, client.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(notification.level, LoggingLevel::Critical);
+
+        // Invalid sinks are rejected when the file is loaded
+        for invalid in [
+            serde_json::json!([{"type": "webhook", "url": "http://localhost/", "format": "pagerduty"}]),
+            serde_json::json!([{"type": "webhook", "url": "ftp://localhost/"}]),
+            serde_json::json!([{"type": "mcp", "severities": ["urgent"]}]),
+        ] {
+            std::fs::write(&path, invalid.to_string()).unwrap();
+            assert!(MonitoringServer::new()
+                .with_alert_sinks_file(&path)
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log_metrics_from_ingested_events() {
+        let registry = Arc::new(MetricsRegistry::new());
+        let mut server = MonitoringServer::new();
+        server.log_metrics = Arc::new(LogMetrics::new(registry.clone()));
+
+        let rule = serde_json::json!({
+            "name": "db_errors_total",
+            "level": "warn",
+            "fields": {"message": "(?i)connection", "context.db": "^main$"},
+            "labels": ["component"]
+        });
+        server.call_tool("define_log_metric", rule).await.unwrap();
+        let rule = serde_json::json!({
+            "name": "query_duration_ms",
+            "kind": "histogram",
+            "fields": {"message": "^query$"},
+            "value_field": "duration_ms",
+            "buckets": [10.0, 100.0]
+        });
+        server.call_tool("define_log_metric", rule).await.unwrap();
+
+        let events = serde_json::json!([
+            {"level": "ERROR", "component": "database", "message": "Connection refused", "context": {"db": "main"}},
+            {"level": "WARNING", "component": "database", "message": "connection slow", "context": {"db": "main"}},
+            {"level": "INFO", "component": "database", "message": "Connection opened", "context": {"db": "main"}},
+            {"level": "ERROR", "component": "database", "message": "Connection refused", "context": {"db": "replica"}},
+            {"level": "INFO", "message": "query", "duration_ms": 42},
+            {"level": "INFO", "message": "query", "duration_ms": "7"},
+            {"level": "INFO", "message": "query", "duration_ms": "soon"}
+        ]);
+        let result = server
+            .call_tool("ingest_log_events", serde_json::json!({ "events": events }))
+            .await
+            .unwrap();
+        assert_eq!(result["events"], 7);
+        assert_eq!(result["recorded"], 4);
+        assert_eq!(
+            registry.counter("db_errors_total", &[("component", "database")]),
+            2
+        );
+        let histogram = registry.histogram("query_duration_ms", &[]).unwrap();
+        assert_eq!(histogram.bounds, vec![10.0, 100.0]);
+        assert_eq!(histogram.buckets, vec![1, 2]);
+        assert_eq!(histogram.sum, 49.0);
+
+        let listing = server
+            .call_tool("list_log_metrics", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(listing["total_rules"], 2);
+        assert_eq!(listing["rules"][0]["stats"]["matched"], 2);
+        assert_eq!(listing["rules"][0]["stats"]["series"], 1);
+        assert_eq!(listing["rules"][1]["stats"]["matched"], 3);
+        assert_eq!(listing["rules"][1]["stats"]["dropped"], 1);
+
+        // Labels taken from a field stop adding series at the limit
+        let rule = serde_json::json!({ "name": "requests_total", "labels": ["request_id"] });
+        server.call_tool("define_log_metric", rule).await.unwrap();
+        let events: Vec<Value> = (0..MAX_LOG_METRIC_SERIES + 5)
+            .map(|i| serde_json::json!({ "level": "info", "request_id": i }))
+            .collect();
+        server
+            .call_tool("ingest_log_events", serde_json::json!({ "events": events }))
+            .await
+            .unwrap();
+        let (_, stats) = server.log_metrics.rules().pop().unwrap();
+        assert_eq!(stats.series, MAX_LOG_METRIC_SERIES);
+        assert_eq!(stats.dropped, 5);
+
+        let removed = server
+            .call_tool(
+                "remove_log_metric",
+                serde_json::json!({ "name": "requests_total" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(removed["success"], true);
+        assert_eq!(server.log_metrics.rules().len(), 2);
+
+        // Invalid rules are rejected
+        for invalid in [
+            serde_json::json!({ "name": "bad-name" }),
+            serde_json::json!({ "name": "a", "level": "loud" }),
+            serde_json::json!({ "name": "a", "fields": {"message": "("} }),
+            serde_json::json!({ "name": "a", "kind": "histogram" }),
+            serde_json::json!({ "name": "a", "value_field": "duration_ms" }),
+            serde_json::json!({ "name": "a", "kind": "histogram", "value_field": "v", "buckets": [2.0, 1.0] }),
+            serde_json::json!({ "name": "a", "labels": ["context.db"] }),
+        ] {
+            assert!(server
+                .call_tool("define_log_metric", invalid)
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log_metrics_tail_the_log_tap() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let registry = Arc::new(MetricsRegistry::new());
+        let mut server = MonitoringServer::new();
+        server.log_metrics = Arc::new(LogMetrics::new(registry.clone()));
+        let rule = LogMetricRule {
+            name: "tapped_tool_calls_total".to_string(),
+            kind: LogMetricKind::Counter,
+            level: None,
+            target: None,
+            fields: BTreeMap::from([("message".to_string(), "^tool call finished$".to_string())]),
+            value_field: None,
+            buckets: Vec::new(),
+            labels: vec!["tool".to_string()],
+        };
+        server.log_metrics.define(rule).unwrap();
+        let server = Arc::new(server);
+        let task = MonitoringServer::spawn_log_metrics(server.clone());
+
+        let subscriber = tracing_subscriber::registry().with(
+            mcp_rust_examples::logging::LogTap::new(mcp_rust_examples::logging::Redactor::new(&[])),
+        );
+        let _guard = tracing::subscriber::set_default(subscriber);
+        server
+            .call_tool("get_active_alerts", serde_json::json!({}))
+            .await
+            .unwrap();
+
+        let labels = [("tool", "get_active_alerts")];
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while registry.counter("tapped_tool_calls_total", &labels) == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        task.abort();
     }
 }
@@ -3,18 +3,50 @@
 // This example demonstrates how to implement a background task queue system
 // using Rust's async capabilities and channels. It shows how to create a
 // system that can process tasks asynchronously in the background while
-// allowing the main application to continue running.
+// allowing the main application to continue running. Running tasks report
+// heartbeats, and a supervisor flags (and optionally requeues) tasks that go
+// silent for too long. A task queued while handling an MCP request is dropped
+// if that request is cancelled before the task starts. A monitor watches
+// queue depth, how long the oldest task has waited and throughput, raising
+// alerts past configurable thresholds and pushing the figures to the
+// monitoring server of example 11 when MCP_METRICS_PUSH_URL is set. With
+// MCP_CHECKPOINT_PATH set, every task's record is checkpointed as its status
+// changes, so task history survives a restart. Tasks can emit output as they
+// go, such as progress lines or partial results, which subscribers follow
+// with stream_task_output while the task runs.
 
-use std::collections::VecDeque;
+use futures::{Stream, StreamExt};
+use mcp_rust_examples::cancellation::{self, CancellationToken};
+use mcp_rust_examples::checkpoint::{CheckpointError, CheckpointStore};
+use mcp_rust_examples::state::{StateCommand, StateError, StatefulServer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex, Notify};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, watch, Mutex, Notify};
+use tokio::task::JoinHandle;
 use tokio::time::{sleep, Duration};
 use tracing::{error, info, warn};
 
 // Type alias for task functions
 // This represents a task that can be executed asynchronously
-// Tasks are boxed functions that return a Result
-type Task = Box<dyn Fn() -> Result<String, String> + Send + 'static>;
+// Tasks are shared functions that receive a heartbeat handle and return a
+// Result; sharing lets a stuck task be requeued and run again
+type TaskFn = dyn Fn(&Heartbeat) -> Result<String, String> + Send + Sync + 'static;
+type Task = Arc<TaskFn>;
+
+// Where the queue monitor pushes its metrics, e.g. example 11's
+// http://127.0.0.1:9464/metrics/push
+const METRICS_PUSH_URL_ENV: &str = "MCP_METRICS_PUSH_URL";
+const METRICS_SOURCE: &str = "task_queue";
+// Throughput counts the tasks finished within this window
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(60);
+// Task records are checkpointed under their id in this namespace
+const CHECKPOINT_NAMESPACE: &str = "task_queue";
+// Each task keeps its latest output chunks, up to this many
+const MAX_TASK_OUTPUT_CHUNKS: usize = 1000;
 
 // Enum: TaskPriority
 //
@@ -28,16 +60,333 @@ pub enum TaskPriority {
     Critical = 4,
 }
 
+// Enum: TaskStatus
+//
+// This enum tracks where a task is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Queued,
+    Running,
+    // Running, but no heartbeat within the supervisor's stuck_after window
+    Stuck,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+// Struct: TaskRecord
+//
+// This struct holds the observable state of a task: its status and, once
+// finished, its result or error. Records outlive the task itself so callers
+// can retrieve results after execution.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskRecord {
+    pub id: u64,
+    pub description: String,
+    pub status: TaskStatus,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub attempts: u32,
+    // Unix milliseconds when the task was queued and when it completed or failed
+    #[serde(default)]
+    pub queued_at_ms: u64,
+    #[serde(default)]
+    pub finished_at_ms: Option<u64>,
+}
+
+impl TaskRecord {
+    // Marks a task that was queued or running when the queue went away as
+    // failed, since its closure is gone; returns whether it was
+    fn interrupt(&mut self, reason: &str) -> bool {
+        if !matches!(
+            self.status,
+            TaskStatus::Queued | TaskStatus::Running | TaskStatus::Stuck
+        ) {
+            return false;
+        }
+        self.status = TaskStatus::Failed;
+        self.error = Some(reason.to_string());
+        true
+    }
+}
+
+type TaskRecords = Arc<Mutex<HashMap<u64, TaskRecord>>>;
+
+// Struct: OutputChunk
+//
+// A piece of output a running task emitted, such as a progress line or a
+// partial result. Sequence numbers count up from 0 for each task, so a gap
+// shows where the oldest chunks of a long output were dropped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputChunk {
+    pub task_id: u64,
+    pub sequence: u64,
+    pub text: String,
+    pub emitted_at_ms: u64,
+}
+
+// Struct: TaskOutput
+//
+// The output of one task: the latest chunks it emitted, and a channel that
+// wakes subscribers when another arrives or the task finishes.
+struct TaskOutput {
+    task_id: u64,
+    buffer: std::sync::Mutex<OutputBuffer>,
+    changed: watch::Sender<()>,
+}
+
+#[derive(Default)]
+struct OutputBuffer {
+    chunks: VecDeque<OutputChunk>,
+    next_sequence: u64,
+    finished: bool,
+}
+
+impl TaskOutput {
+    fn new(task_id: u64) -> Self {
+        Self {
+            task_id,
+            buffer: std::sync::Mutex::new(OutputBuffer::default()),
+            changed: watch::channel(()).0,
+        }
+    }
+
+    // Function: emit
+    //
+    // Appends a chunk, dropping the oldest once MAX_TASK_OUTPUT_CHUNKS are
+    // kept. Output after the task finished, e.g. from an aborted attempt
+    // that is still running, is ignored.
+    fn emit(&self, text: String) {
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.finished {
+            return;
+        }
+        let chunk = OutputChunk {
+            task_id: self.task_id,
+            sequence: buffer.next_sequence,
+            text,
+            emitted_at_ms: now_ms(),
+        };
+        buffer.next_sequence += 1;
+        if buffer.chunks.len() == MAX_TASK_OUTPUT_CHUNKS {
+            buffer.chunks.pop_front();
+        }
+        buffer.chunks.push_back(chunk);
+        drop(buffer);
+        self.changed.send_replace(());
+    }
+
+    // Function: finish
+    //
+    // Marks the output complete, ending the streams that reach its end.
+    fn finish(&self) {
+        self.buffer.lock().unwrap().finished = true;
+        self.changed.send_replace(());
+    }
+
+    // Function: next_chunk
+    //
+    // Looks for the first chunk still kept from `sequence` on.
+    //
+    // Returns:
+    //     The chunk if there is one, and whether the output is complete
+    fn next_chunk(&self, sequence: u64) -> (Option<OutputChunk>, bool) {
+        let buffer = self.buffer.lock().unwrap();
+        let chunk = buffer
+            .chunks
+            .iter()
+            .find(|chunk| chunk.sequence >= sequence)
+            .cloned();
+        (chunk, buffer.finished)
+    }
+}
+
+type TaskOutputs = Arc<std::sync::Mutex<HashMap<u64, Arc<TaskOutput>>>>;
+
+// Struct: Heartbeat
+//
+// A handle passed to every running task. Tasks call beat() while they make
+// progress; the supervisor treats a long silence as a stuck task. Tasks
+// call emit() to send output to the subscribers of stream_task_output.
+#[derive(Clone)]
+pub struct Heartbeat {
+    last_beat: Arc<std::sync::Mutex<Instant>>,
+    output: Option<Arc<TaskOutput>>,
+}
+
+impl Heartbeat {
+    fn new(output: Option<Arc<TaskOutput>>) -> Self {
+        Self {
+            last_beat: Arc::new(std::sync::Mutex::new(Instant::now())),
+            output,
+        }
+    }
+
+    // Function: beat
+    //
+    // Records that the task is still making progress.
+    pub fn beat(&self) {
+        *self.last_beat.lock().unwrap() = Instant::now();
+    }
+
+    // Function: emit
+    //
+    // Sends a chunk of output, e.g. a progress line or a partial result, to
+    // the task's subscribers. Emitting output also counts as a beat.
+    //
+    // Arguments:
+    //     text: The chunk to send
+    pub fn emit(&self, text: impl Into<String>) {
+        self.beat();
+        if let Some(output) = &self.output {
+            output.emit(text.into());
+        }
+    }
+
+    fn silence(&self) -> Duration {
+        self.last_beat.lock().unwrap().elapsed()
+    }
+}
+
+// Struct: RunningTask
+//
+// What the supervisor needs to watch a running task and to abort it.
+struct RunningTask {
+    heartbeat: Heartbeat,
+    abort: Arc<Notify>,
+}
+
+type RunningTasks = Arc<std::sync::Mutex<HashMap<u64, RunningTask>>>;
+
+// Struct: SupervisorConfig
+//
+// Controls stuck-task detection.
+//
+// Fields:
+//     stuck_after: Heartbeat silence after which a running task counts as stuck
+//     check_interval: How often the supervisor looks at running tasks
+//     requeue_stuck: Whether stuck tasks are aborted and queued again
+//     max_attempts: Runs allowed before a stuck task is marked failed instead
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    pub stuck_after: Duration,
+    pub check_interval: Duration,
+    pub requeue_stuck: bool,
+    pub max_attempts: u32,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            stuck_after: Duration::from_secs(30),
+            check_interval: Duration::from_secs(1),
+            requeue_stuck: false,
+            max_attempts: 3,
+        }
+    }
+}
+
+// Struct: QueueStats
+//
+// Task counts by status, plus how often the supervisor had to step in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueueStats {
+    pub queued: usize,
+    pub running: usize,
+    pub stuck: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    pub stuck_detected: u64,
+    pub requeued: u64,
+}
+
+// Struct: QueueMetrics
+//
+// How the queue is keeping up, as pushed to the monitoring server.
+//
+// Fields:
+//     depth: Tasks waiting to run
+//     oldest_wait_ms: How long the longest-waiting queued task has waited
+//     throughput_per_minute: Tasks completed or failed within the last minute
+//     running: Tasks executing now, including stuck ones
+//     failed: Tasks that failed since the queue started
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueueMetrics {
+    pub depth: usize,
+    pub oldest_wait_ms: u64,
+    pub throughput_per_minute: usize,
+    pub running: usize,
+    pub failed: usize,
+}
+
+// Struct: QueueMonitorConfig
+//
+// Controls the queue monitor and the thresholds its alerts fire at.
+//
+// Fields:
+//     max_depth: Queue depth above which a warning is raised
+//     max_wait: Wait of the oldest queued task above which an alert is raised
+//     check_interval: How often metrics are computed, checked and pushed
+//     push_url: Where metrics are pushed, e.g. example 11's /metrics/push
+#[derive(Debug, Clone)]
+pub struct QueueMonitorConfig {
+    pub max_depth: usize,
+    pub max_wait: Duration,
+    pub check_interval: Duration,
+    pub push_url: Option<String>,
+}
+
+impl Default for QueueMonitorConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 100,
+            max_wait: Duration::from_secs(60),
+            check_interval: Duration::from_secs(5),
+            push_url: None,
+        }
+    }
+}
+
+// Struct: QueueAlert
+//
+// An alert raised when a queue metric crosses its threshold, in the shape
+// example 11 accepts alongside pushed metrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueAlert {
+    pub severity: String,
+    pub title: String,
+    pub description: String,
+    pub metric_name: String,
+    pub threshold: f64,
+    pub current_value: f64,
+}
+
+// Struct: TaskQueueState
+//
+// The part of the queue captured by snapshots. Task closures cannot be
+// serialized, so only the records and the ID counter are kept.
+#[derive(Debug, Serialize, Deserialize)]
+struct TaskQueueState {
+    next_task_id: u64,
+    records: Vec<TaskRecord>,
+}
+
 // Struct: TaskItem
 //
 // This struct represents a single task item in the queue.
 // It contains the task function itself along with metadata
-// like priority and a unique identifier.
+// like priority and a unique identifier, and the cancellation token of
+// the request that queued it.
+#[derive(Clone)]
 pub struct TaskItem {
     id: u64,
     priority: TaskPriority,
     task: Task,
     description: String,
+    cancel: CancellationToken,
 }
 
 impl std::fmt::Debug for TaskItem {
@@ -63,26 +412,30 @@ impl TaskItem {
     //     description: A human-readable description of the task
     //
     // Returns:
-    //     A new TaskItem instance
+    //     A new TaskItem instance, tied to the current request's cancellation
     pub fn new(id: u64, priority: TaskPriority, task: Task, description: String) -> Self {
         Self {
             id,
             priority,
             task,
             description,
+            cancel: cancellation::current(),
         }
     }
 
     // Function: execute
     //
     // Executes the task function and returns the result.
-    // This consumes the TaskItem since tasks should only be executed once.
+    // This consumes the TaskItem; the worker keeps a clone for requeueing.
+    //
+    // Arguments:
+    //     heartbeat: Handle the task uses to report progress
     //
     // Returns:
     //     Result containing the task output or an error message
-    pub fn execute(self) -> Result<String, String> {
+    pub fn execute(self, heartbeat: &Heartbeat) -> Result<String, String> {
         info!("Executing task {}: {}", self.id, self.description);
-        (self.task)()
+        (self.task)(heartbeat)
     }
 }
 
@@ -95,6 +448,18 @@ pub struct TaskQueue {
     sender: mpsc::UnboundedSender<TaskItem>,
     shutdown_notify: Arc<Notify>,
     next_task_id: Arc<Mutex<u64>>,
+    records: TaskRecords,
+    outputs: TaskOutputs,
+    counters: Arc<SupervisorCounters>,
+    shut_down: Arc<AtomicBool>,
+    checkpoints: Option<CheckpointStore>,
+}
+
+// Cumulative supervisor interventions, shared by the supervisor and worker
+#[derive(Default)]
+struct SupervisorCounters {
+    stuck_detected: AtomicU64,
+    requeued: AtomicU64,
 }
 
 impl Default for TaskQueue {
@@ -106,13 +471,75 @@ impl Default for TaskQueue {
 impl TaskQueue {
     // Function: new
     //
-    // Creates a new task queue and starts the background worker.
-    // The worker will continuously poll for new tasks and execute them
-    // based on their priority.
+    // Creates a new task queue with the default supervisor settings.
     //
     // Returns:
     //     A new TaskQueue instance
     pub fn new() -> Self {
+        Self::with_supervisor(SupervisorConfig::default())
+    }
+
+    // Function: with_supervisor
+    //
+    // Creates a new task queue and starts the background worker and the
+    // stuck-task supervisor. The worker will continuously poll for new tasks
+    // and execute them based on their priority.
+    //
+    // Arguments:
+    //     config: Stuck-task detection settings
+    //
+    // Returns:
+    //     A new TaskQueue instance
+    pub fn with_supervisor(config: SupervisorConfig) -> Self {
+        Self::start(config, HashMap::new(), None)
+    }
+
+    // Function: with_checkpoints
+    //
+    // Creates a task queue that checkpoints every task record to `store`,
+    // starting from the records checkpointed before a restart. Tasks that
+    // were queued or running then are marked as failed, as after a state
+    // restore.
+    //
+    // Arguments:
+    //     config: Stuck-task detection settings
+    //     store: Where task records are checkpointed
+    //
+    // Returns:
+    //     A new TaskQueue instance, or the error reading the checkpoints
+    pub async fn with_checkpoints(
+        config: SupervisorConfig,
+        store: CheckpointStore,
+    ) -> Result<Self, CheckpointError> {
+        let mut records = HashMap::new();
+        for (_, mut record) in store.list::<TaskRecord>(CHECKPOINT_NAMESPACE).await? {
+            if record.interrupt("Task was interrupted by a restart before it finished") {
+                store
+                    .put(CHECKPOINT_NAMESPACE, &record.id.to_string(), &record)
+                    .await?;
+            }
+            records.insert(record.id, record);
+        }
+        info!("Restored {} task records from checkpoints", records.len());
+        Ok(Self::start(config, records, Some(store)))
+    }
+
+    // Function: start
+    //
+    // Starts the worker and supervisor for a queue holding `records`.
+    //
+    // Arguments:
+    //     config: Stuck-task detection settings
+    //     records: Records of earlier tasks
+    //     checkpoints: Where task records are checkpointed, if anywhere
+    //
+    // Returns:
+    //     A new TaskQueue instance
+    fn start(
+        config: SupervisorConfig,
+        records: HashMap<u64, TaskRecord>,
+        checkpoints: Option<CheckpointStore>,
+    ) -> Self {
         // Create an unbounded channel for task communication
         // Unbounded channels allow unlimited queueing of tasks
         let (sender, receiver) = mpsc::unbounded_channel::<TaskItem>();
@@ -121,32 +548,67 @@ impl TaskQueue {
         let shutdown_notify = Arc::new(Notify::new());
         let shutdown_notify_worker = shutdown_notify.clone();
 
-        // Initialize the task ID counter
-        let next_task_id = Arc::new(Mutex::new(1u64));
+        // Initialize the task ID counter past any earlier task
+        let first_id = records.keys().max().map_or(1, |id| id + 1);
+        let next_task_id = Arc::new(Mutex::new(first_id));
+
+        // Shared task records so callers can observe status and results
+        let records: TaskRecords = Arc::new(Mutex::new(records));
+        let records_worker = records.clone();
+        let outputs: TaskOutputs = Arc::new(std::sync::Mutex::new(HashMap::new()));
+
+        // Tasks currently executing, watched by the supervisor
+        let running: RunningTasks = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let counters = Arc::new(SupervisorCounters::default());
+        let shut_down = Arc::new(AtomicBool::new(false));
 
         // Spawn the background worker task
         // This task will run continuously until shutdown is requested
+        let worker = Worker {
+            records: records_worker,
+            outputs: outputs.clone(),
+            running: running.clone(),
+            counters: counters.clone(),
+            max_attempts: config.max_attempts,
+            checkpoints: checkpoints.clone(),
+        };
         tokio::spawn(async move {
-            Self::worker_loop(receiver, shutdown_notify_worker).await;
+            Self::worker_loop(receiver, shutdown_notify_worker, worker).await;
         });
 
+        // Spawn the supervisor that flags tasks whose heartbeat went quiet
+        tokio::spawn(Self::supervisor_loop(
+            config,
+            running,
+            records.clone(),
+            counters.clone(),
+            shut_down.clone(),
+        ));
+
         info!("Task queue initialized and worker started");
 
         Self {
             sender,
             shutdown_notify,
             next_task_id,
+            records,
+            outputs,
+            counters,
+            shut_down,
+            checkpoints,
         }
     }
 
     // Function: add_task
     //
     // Adds a new task to the queue with the specified priority.
-    // The task will be executed by the background worker when its turn comes.
+    // The task will be executed by the background worker when its turn comes,
+    // unless it was queued while handling a request that gets cancelled first.
     //
     // Arguments:
     //     priority: The priority level for this task
-    //     task: The function to execute
+    //     task: The function to execute; it should call Heartbeat::beat
+    //         regularly so the supervisor does not consider it stuck
     //     description: A description of what this task does
     //
     // Returns:
@@ -158,7 +620,7 @@ impl TaskQueue {
         description: String,
     ) -> Result<u64, String>
     where
-        F: Fn() -> Result<String, String> + Send + 'static,
+        F: Fn(&Heartbeat) -> Result<String, String> + Send + Sync + 'static,
     {
         // Generate a unique ID for this task
         let mut next_id = self.next_task_id.lock().await;
@@ -167,7 +629,27 @@ impl TaskQueue {
         drop(next_id); // Release the lock early
 
         // Create the task item
-        let task_item = TaskItem::new(task_id, priority, Box::new(task), description.clone());
+        let task_item = TaskItem::new(task_id, priority, Arc::new(task), description.clone());
+
+        // Record the task before sending so its status is visible immediately
+        let record = TaskRecord {
+            id: task_id,
+            description: description.clone(),
+            status: TaskStatus::Queued,
+            result: None,
+            error: None,
+            attempts: 0,
+            queued_at_ms: now_ms(),
+            finished_at_ms: None,
+        };
+        let mut records = self.records.lock().await;
+        checkpoint_record(self.checkpoints.as_ref(), &record).await;
+        records.insert(task_id, record);
+        drop(records);
+        self.outputs
+            .lock()
+            .unwrap()
+            .insert(task_id, Arc::new(TaskOutput::new(task_id)));
 
         // Send the task to the worker
         // If the channel is closed, the worker has shut down
@@ -181,11 +663,116 @@ impl TaskQueue {
             }
             Err(_) => {
                 error!("Failed to queue task: worker has shut down");
+                self.records.lock().await.remove(&task_id);
+                self.outputs.lock().unwrap().remove(&task_id);
+                if let Some(store) = &self.checkpoints {
+                    let _ = store
+                        .remove(CHECKPOINT_NAMESPACE, &task_id.to_string())
+                        .await;
+                }
                 Err("Task queue is shut down".to_string())
             }
         }
     }
 
+    // Function: get_task
+    //
+    // Looks up the current status and result of a task.
+    //
+    // Arguments:
+    //     task_id: The ID returned by add_task
+    //
+    // Returns:
+    //     The task record, or None if the ID is unknown
+    pub async fn get_task(&self, task_id: u64) -> Option<TaskRecord> {
+        self.records.lock().await.get(&task_id).cloned()
+    }
+
+    // Function: stream_task_output
+    //
+    // Follows the output a task emits through Heartbeat::emit. The stream
+    // starts with the chunks emitted so far, then yields each new one as it
+    // arrives, and ends once the task has finished; its result is in the
+    // record get_task returns. Tasks restored from a snapshot or checkpoints
+    // have no output left, so their streams end at once.
+    //
+    // Arguments:
+    //     task_id: The ID returned by add_task
+    //
+    // Returns:
+    //     A stream of the task's output chunks, or an error if the ID is unknown
+    pub async fn stream_task_output(
+        &self,
+        task_id: u64,
+    ) -> Result<impl Stream<Item = OutputChunk> + Send + 'static, String> {
+        if !self.records.lock().await.contains_key(&task_id) {
+            return Err(format!("Task {} not found", task_id));
+        }
+        let output = self
+            .outputs
+            .lock()
+            .unwrap()
+            .get(&task_id)
+            .cloned()
+            .unwrap_or_else(|| {
+                let output = TaskOutput::new(task_id);
+                output.finish();
+                Arc::new(output)
+            });
+
+        // Subscribing before the first look means no chunk is missed
+        let changes = output.changed.subscribe();
+        Ok(futures::stream::unfold(
+            (output, changes, 0),
+            |(output, mut changes, sequence)| async move {
+                loop {
+                    match output.next_chunk(sequence) {
+                        (Some(chunk), _) => {
+                            let next = chunk.sequence + 1;
+                            return Some((chunk, (output, changes, next)));
+                        }
+                        (None, true) => return None,
+                        // The output holds the sender, so this only waits
+                        (None, false) => {
+                            let _ = changes.changed().await;
+                        }
+                    }
+                }
+            },
+        ))
+    }
+
+    // Function: cancel_task
+    //
+    // Cancels a task that has not started yet. The worker skips cancelled
+    // tasks when it reaches them; running or finished tasks cannot be cancelled.
+    //
+    // Arguments:
+    //     task_id: The ID of the task to cancel
+    //
+    // Returns:
+    //     Result indicating whether the task was cancelled
+    pub async fn cancel_task(&self, task_id: u64) -> Result<(), String> {
+        let mut records = self.records.lock().await;
+        let record = records
+            .get_mut(&task_id)
+            .ok_or_else(|| format!("Task {} not found", task_id))?;
+
+        match record.status {
+            TaskStatus::Queued => {
+                record.status = TaskStatus::Cancelled;
+                checkpoint_record(self.checkpoints.as_ref(), record).await;
+                finish_output(&self.outputs, task_id);
+                info!("Cancelled task {}", task_id);
+                Ok(())
+            }
+            status => Err(format!(
+                "Task {} cannot be cancelled in state {:?}",
+                task_id, status
+            )),
+        }
+    }
+
     // Function: shutdown
     //
     // Initiates a graceful shutdown of the task queue.
@@ -193,9 +780,210 @@ impl TaskQueue {
     // and complete any currently running tasks.
     pub fn shutdown(&self) {
         info!("Initiating task queue shutdown");
+        self.shut_down.store(true, Ordering::Relaxed);
         self.shutdown_notify.notify_one();
     }
 
+    // Function: stats
+    //
+    // Counts tasks by status, including tasks currently flagged as stuck.
+    //
+    // Returns:
+    //     A QueueStats snapshot
+    pub async fn stats(&self) -> QueueStats {
+        let mut stats = QueueStats {
+            stuck_detected: self.counters.stuck_detected.load(Ordering::Relaxed),
+            requeued: self.counters.requeued.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        for record in self.records.lock().await.values() {
+            match record.status {
+                TaskStatus::Queued => stats.queued += 1,
+                TaskStatus::Running => stats.running += 1,
+                TaskStatus::Stuck => stats.stuck += 1,
+                TaskStatus::Completed => stats.completed += 1,
+                TaskStatus::Failed => stats.failed += 1,
+                TaskStatus::Cancelled => stats.cancelled += 1,
+            }
+        }
+        stats
+    }
+
+    // Function: metrics
+    //
+    // Computes queue depth, the oldest queued task's wait and throughput.
+    //
+    // Returns:
+    //     A QueueMetrics snapshot
+    pub async fn metrics(&self) -> QueueMetrics {
+        Self::metrics_of(&self.records).await
+    }
+
+    async fn metrics_of(records: &TaskRecords) -> QueueMetrics {
+        let now = now_ms();
+        let window_start = now.saturating_sub(THROUGHPUT_WINDOW.as_millis() as u64);
+        let mut metrics = QueueMetrics::default();
+        for record in records.lock().await.values() {
+            match record.status {
+                TaskStatus::Queued => {
+                    metrics.depth += 1;
+                    let waited = now.saturating_sub(record.queued_at_ms);
+                    metrics.oldest_wait_ms = metrics.oldest_wait_ms.max(waited);
+                }
+                TaskStatus::Running | TaskStatus::Stuck => metrics.running += 1,
+                TaskStatus::Failed => metrics.failed += 1,
+                TaskStatus::Completed | TaskStatus::Cancelled => {}
+            }
+            if record.finished_at_ms.is_some_and(|at| at >= window_start) {
+                metrics.throughput_per_minute += 1;
+            }
+        }
+        metrics
+    }
+
+    // Function: spawn_monitor
+    //
+    // Starts a background task that checks the queue metrics against the
+    // configured thresholds and pushes them, with any alerts that started
+    // firing, to the monitoring server. An alert is raised once when its
+    // threshold is crossed and logged again when the metric recovers.
+    //
+    // Arguments:
+    //     config: Thresholds, check interval and push destination
+    //
+    // Returns:
+    //     The monitor task, which stops when the queue shuts down
+    pub fn spawn_monitor(&self, config: QueueMonitorConfig) -> JoinHandle<()> {
+        let records = self.records.clone();
+        let counters = self.counters.clone();
+        let shut_down = self.shut_down.clone();
+        let client = reqwest::Client::new();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(config.check_interval);
+            // Alerts currently firing, and those of them that have been
+            // pushed. An alert is pushed again on every tick until a push
+            // gets through, so a failed push does not lose it.
+            let mut firing: Vec<String> = Vec::new();
+            let mut delivered: Vec<String> = Vec::new();
+
+            while !shut_down.load(Ordering::Relaxed) {
+                interval.tick().await;
+                let metrics = Self::metrics_of(&records).await;
+
+                let current = threshold_alerts(&metrics, &config);
+                let still_firing =
+                    |metric_name: &String| current.iter().any(|a| a.metric_name == *metric_name);
+                firing.retain(|metric_name| {
+                    if !still_firing(metric_name) {
+                        info!("Queue alert on {} has recovered", metric_name);
+                    }
+                    still_firing(metric_name)
+                });
+                delivered.retain(still_firing);
+                for alert in &current {
+                    if !firing.contains(&alert.metric_name) {
+                        warn!("Queue alert: {}", alert.description);
+                        firing.push(alert.metric_name.clone());
+                    }
+                }
+                let alerts: Vec<QueueAlert> = current
+                    .into_iter()
+                    .filter(|alert| !delivered.contains(&alert.metric_name))
+                    .collect();
+
+                if let Some(url) = &config.push_url {
+                    let push = serde_json::json!({
+                        "source": METRICS_SOURCE,
+                        "metrics": {
+                            "task_queue_depth": metrics.depth,
+                            "task_queue_oldest_wait_seconds": metrics.oldest_wait_ms as f64 / 1000.0,
+                            "task_queue_throughput_per_minute": metrics.throughput_per_minute,
+                            "task_queue_running": metrics.running,
+                            "task_queue_failed": metrics.failed,
+                            "task_queue_requeued": counters.requeued.load(Ordering::Relaxed),
+                        },
+                        "alerts": alerts,
+                    });
+                    let pushed = client
+                        .post(url)
+                        .timeout(config.check_interval)
+                        .json(&push)
+                        .send()
+                        .await
+                        .and_then(reqwest::Response::error_for_status);
+                    match pushed {
+                        Ok(_) => delivered.extend(alerts.into_iter().map(|a| a.metric_name)),
+                        Err(e) => warn!("Failed to push queue metrics to {}: {}", url, e),
+                    }
+                }
+            }
+        })
+    }
+
+    // Function: supervisor_loop
+    //
+    // Periodically compares each running task's last heartbeat against the
+    // stuck_after window. Silent tasks are marked stuck and, if configured,
+    // aborted so the worker can requeue them. A stuck task that starts
+    // beating again goes back to running.
+    //
+    // Arguments:
+    //     config: Stuck-task detection settings
+    //     running: Tasks currently executing
+    //     records: Shared task records to update
+    //     counters: Cumulative intervention counters
+    //     shut_down: Set when the queue shuts down
+    async fn supervisor_loop(
+        config: SupervisorConfig,
+        running: RunningTasks,
+        records: TaskRecords,
+        counters: Arc<SupervisorCounters>,
+        shut_down: Arc<AtomicBool>,
+    ) {
+        let mut interval = tokio::time::interval(config.check_interval);
+
+        while !shut_down.load(Ordering::Relaxed) {
+            interval.tick().await;
+
+            // Collect under the std lock, then update records without holding it
+            let silences: Vec<(u64, Duration, Arc<Notify>)> = running
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(id, task)| (*id, task.heartbeat.silence(), task.abort.clone()))
+                .collect();
+
+            let mut records = records.lock().await;
+            for (task_id, silence, abort) in silences {
+                let Some(record) = records.get_mut(&task_id) else {
+                    continue;
+                };
+
+                if silence < config.stuck_after {
+                    if record.status == TaskStatus::Stuck {
+                        info!("Task {} is sending heartbeats again", task_id);
+                        record.status = TaskStatus::Running;
+                    }
+                    continue;
+                }
+
+                if record.status == TaskStatus::Running {
+                    warn!(
+                        "Task {} is stuck: no heartbeat for {:.1}s",
+                        task_id,
+                        silence.as_secs_f64()
+                    );
+                    record.status = TaskStatus::Stuck;
+                    counters.stuck_detected.fetch_add(1, Ordering::Relaxed);
+                }
+                if config.requeue_stuck {
+                    abort.notify_one();
+                }
+            }
+        }
+    }
+
     // Function: worker_loop
     //
     // This is the main worker loop that runs in the background.
@@ -205,9 +993,11 @@ impl TaskQueue {
     // Arguments:
     //     receiver: The channel receiver for incoming tasks
     //     shutdown_notify: Notification mechanism for shutdown
+    //     worker: Shared state updated as tasks run
     async fn worker_loop(
         mut receiver: mpsc::UnboundedReceiver<TaskItem>,
         shutdown_notify: Arc<Notify>,
+        worker: Worker,
     ) {
         // Use a priority queue to ensure high-priority tasks are executed first
         let mut task_buffer: VecDeque<TaskItem> = VecDeque::new();
@@ -225,7 +1015,7 @@ impl TaskQueue {
                             Self::insert_task_by_priority(&mut task_buffer, task);
 
                             // Process all available tasks in the buffer
-                            Self::process_task_buffer(&mut task_buffer).await;
+                            Self::process_task_buffer(&mut task_buffer, &worker).await;
                         }
                         None => {
                             // Channel closed, no more tasks will arrive
@@ -240,13 +1030,13 @@ impl TaskQueue {
                     info!("Shutdown signal received, processing remaining tasks");
 
                     // Process any remaining tasks in the buffer
-                    Self::process_task_buffer(&mut task_buffer).await;
+                    Self::process_task_buffer(&mut task_buffer, &worker).await;
 
                     // Process any remaining tasks in the channel
                     while let Ok(task) = receiver.try_recv() {
                         Self::insert_task_by_priority(&mut task_buffer, task);
                     }
-                    Self::process_task_buffer(&mut task_buffer).await;
+                    Self::process_task_buffer(&mut task_buffer, &worker).await;
 
                     info!("Worker shutdown complete");
                     break;
@@ -277,15 +1067,65 @@ impl TaskQueue {
     //
     // Processes all tasks currently in the buffer.
     // Tasks are executed in priority order (highest priority first).
+    // Each task runs on a blocking thread so the supervisor can abort it;
+    // an aborted task is requeued until it runs out of attempts.
     //
     // Arguments:
     //     buffer: The task buffer to process
-    async fn process_task_buffer(buffer: &mut VecDeque<TaskItem>) {
+    //     worker: Shared state to update
+    async fn process_task_buffer(buffer: &mut VecDeque<TaskItem>, worker: &Worker) {
+        let records = &worker.records;
         while let Some(task) = buffer.pop_front() {
             let task_id = task.id;
 
+            // Skip tasks that were cancelled while waiting, otherwise mark as running
+            {
+                let mut records = records.lock().await;
+                if let Some(record) = records.get_mut(&task_id) {
+                    if task.cancel.is_cancelled() && record.status == TaskStatus::Queued {
+                        info!("Dropping task {}: its request was cancelled", task_id);
+                        record.status = TaskStatus::Cancelled;
+                    }
+                    if record.status == TaskStatus::Cancelled {
+                        info!("Skipping cancelled task {}", task_id);
+                        checkpoint_record(worker.checkpoints.as_ref(), record).await;
+                        finish_output(&worker.outputs, task_id);
+                        continue;
+                    }
+                    record.status = TaskStatus::Running;
+                    record.attempts += 1;
+                    checkpoint_record(worker.checkpoints.as_ref(), record).await;
+                }
+            }
+
+            // Register the task with the supervisor before it starts
+            let output = worker.outputs.lock().unwrap().get(&task_id).cloned();
+            let heartbeat = Heartbeat::new(output);
+            let abort = Arc::new(Notify::new());
+            worker.running.lock().unwrap().insert(
+                task_id,
+                RunningTask {
+                    heartbeat: heartbeat.clone(),
+                    abort: abort.clone(),
+                },
+            );
+
             // Execute the task and handle the result
-            match task.execute() {
+            let attempt = task.clone();
+            let handle = tokio::task::spawn_blocking(move || attempt.execute(&heartbeat));
+            let outcome = tokio::select! {
+                joined = handle => joined.unwrap_or_else(|e| Err(format!("Task panicked: {}", e))),
+                _ = abort.notified() => {
+                    worker.running.lock().unwrap().remove(&task_id);
+                    // A blocking closure cannot be killed; its eventual
+                    // result is simply ignored
+                    Self::handle_aborted_task(buffer, task, worker).await;
+                    continue;
+                }
+            };
+            worker.running.lock().unwrap().remove(&task_id);
+
+            match &outcome {
                 Ok(result) => {
                     info!("Task {} completed successfully: {}", task_id, result);
                 }
@@ -294,17 +1134,171 @@ impl TaskQueue {
                 }
             }
 
+            if let Some(record) = records.lock().await.get_mut(&task_id) {
+                match outcome {
+                    Ok(result) => {
+                        record.status = TaskStatus::Completed;
+                        record.result = Some(result);
+                    }
+                    Err(error) => {
+                        record.status = TaskStatus::Failed;
+                        record.error = Some(error);
+                    }
+                }
+                record.finished_at_ms = Some(now_ms());
+                checkpoint_record(worker.checkpoints.as_ref(), record).await;
+            }
+            finish_output(&worker.outputs, task_id);
+
             // Add a small delay between tasks to prevent overwhelming the system
             // In a real-world scenario, this might be configurable
             sleep(Duration::from_millis(10)).await;
         }
     }
+
+    // Function: handle_aborted_task
+    //
+    // Requeues a task the supervisor aborted, or marks it failed once it
+    // has used up its attempts.
+    //
+    // Arguments:
+    //     buffer: The task buffer to requeue into
+    //     task: The aborted task
+    //     worker: Shared state to update
+    async fn handle_aborted_task(buffer: &mut VecDeque<TaskItem>, task: TaskItem, worker: &Worker) {
+        let mut records = worker.records.lock().await;
+        let Some(record) = records.get_mut(&task.id) else {
+            return;
+        };
+
+        if record.attempts < worker.max_attempts {
+            warn!(
+                "Requeueing stuck task {} (attempt {} of {})",
+                task.id, record.attempts, worker.max_attempts
+            );
+            record.status = TaskStatus::Queued;
+            worker.counters.requeued.fetch_add(1, Ordering::Relaxed);
+            Self::insert_task_by_priority(buffer, task);
+        } else {
+            error!(
+                "Task {} stuck on all {} attempts, giving up",
+                task.id, record.attempts
+            );
+            record.status = TaskStatus::Failed;
+            record.error = Some(format!(
+                "Task stopped sending heartbeats on all {} attempts",
+                record.attempts
+            ));
+            record.finished_at_ms = Some(now_ms());
+            finish_output(&worker.outputs, task.id);
+        }
+        checkpoint_record(worker.checkpoints.as_ref(), record).await;
+    }
+}
+
+// Struct: Worker
+//
+// State the background worker shares with the queue and supervisor.
+struct Worker {
+    records: TaskRecords,
+    outputs: TaskOutputs,
+    running: RunningTasks,
+    counters: Arc<SupervisorCounters>,
+    max_attempts: u32,
+    checkpoints: Option<CheckpointStore>,
+}
+
+// Function: finish_output
+//
+// Ends the output of a task that will not run again, so that its streams end.
+//
+// Arguments:
+//     outputs: The queue's task outputs
+//     task_id: The finished task
+fn finish_output(outputs: &TaskOutputs, task_id: u64) {
+    if let Some(output) = outputs.lock().unwrap().get(&task_id) {
+        output.finish();
+    }
+}
+
+// Function: checkpoint_record
+//
+// Saves a task's record to the checkpoint store, if the queue has one. A
+// failed write is logged and the task carries on.
+//
+// Arguments:
+//     store: The queue's checkpoint store
+//     record: The record to save
+async fn checkpoint_record(store: Option<&CheckpointStore>, record: &TaskRecord) {
+    let Some(store) = store else {
+        return;
+    };
+    if let Err(e) = store
+        .put(CHECKPOINT_NAMESPACE, &record.id.to_string(), record)
+        .await
+    {
+        warn!("Failed to checkpoint task {}: {}", record.id, e);
+    }
+}
+
+// Function: threshold_alerts
+//
+// The alerts whose thresholds the metrics exceed right now.
+//
+// Arguments:
+//     metrics: The current queue metrics
+//     config: The thresholds to check against
+//
+// Returns:
+//     A warning for excess depth and a critical alert for excess wait
+fn threshold_alerts(metrics: &QueueMetrics, config: &QueueMonitorConfig) -> Vec<QueueAlert> {
+    let mut alerts = Vec::new();
+    if metrics.depth > config.max_depth {
+        alerts.push(QueueAlert {
+            severity: "warning".to_string(),
+            title: "Task Queue Backlog".to_string(),
+            description: format!(
+                "{} tasks are queued, more than the limit of {}",
+                metrics.depth, config.max_depth
+            ),
+            metric_name: "task_queue_depth".to_string(),
+            threshold: config.max_depth as f64,
+            current_value: metrics.depth as f64,
+        });
+    }
+    let max_wait_ms = config.max_wait.as_millis() as u64;
+    if metrics.oldest_wait_ms > max_wait_ms {
+        alerts.push(QueueAlert {
+            severity: "critical".to_string(),
+            title: "Task Queue Wait".to_string(),
+            description: format!(
+                "The oldest queued task has waited {:.1}s, more than the limit of {:.1}s",
+                metrics.oldest_wait_ms as f64 / 1000.0,
+                config.max_wait.as_secs_f64()
+            ),
+            metric_name: "task_queue_oldest_wait_seconds".to_string(),
+            threshold: config.max_wait.as_secs_f64(),
+            current_value: metrics.oldest_wait_ms as f64 / 1000.0,
+        });
+    }
+    alerts
+}
+
+// Function: now_ms
+//
+// The current time in Unix milliseconds.
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
 }
 
 // Function: create_sample_task
 //
 // Creates a sample task function for demonstration purposes.
-// This function simulates some work by sleeping and then returning a result.
+// This function simulates some work by sleeping and then returning a result,
+// sending a heartbeat after every slice of work.
 //
 // Arguments:
 //     task_name: A name for this task
@@ -313,14 +1307,16 @@ impl TaskQueue {
 //
 // Returns:
 //     A boxed task function that can be added to the queue
-fn create_sample_task(
-    task_name: String,
-    work_duration_ms: u64,
-    should_fail: bool,
-) -> Box<dyn Fn() -> Result<String, String> + Send + 'static> {
-    Box::new(move || {
+fn create_sample_task(task_name: String, work_duration_ms: u64, should_fail: bool) -> Box<TaskFn> {
+    Box::new(move |heartbeat| {
         // Simulate some work
-        std::thread::sleep(Duration::from_millis(work_duration_ms));
+        let mut remaining = work_duration_ms;
+        while remaining > 0 {
+            let slice = remaining.min(25);
+            std::thread::sleep(Duration::from_millis(slice));
+            heartbeat.beat();
+            remaining -= slice;
+        }
 
         if should_fail {
             Err(format!("Task '{}' failed as requested", task_name))
@@ -333,6 +1329,84 @@ fn create_sample_task(
     })
 }
 
+// Function: create_reporting_task
+//
+// Creates a task that works through batches, emitting a progress line and
+// the running total after each one, so subscribers see partial results
+// before the task finishes.
+//
+// Arguments:
+//     batches: How many batches to process
+//
+// Returns:
+//     A boxed task function that can be added to the queue
+fn create_reporting_task(batches: u64) -> Box<TaskFn> {
+    Box::new(move |heartbeat| {
+        let mut total = 0;
+        for batch in 1..=batches {
+            std::thread::sleep(Duration::from_millis(40));
+            total += batch * 100;
+            heartbeat.emit(format!("Processed batch {} of {}", batch, batches));
+            heartbeat.emit(format!("Running total: {}", total));
+        }
+        Ok(format!("Report complete: {} records", total))
+    })
+}
+
+// Function: create_hanging_task
+//
+// Creates a task that works without ever sending a heartbeat, to show how
+// the supervisor detects and requeues stuck tasks.
+//
+// Arguments:
+//     hang_ms: How long each attempt blocks
+//
+// Returns:
+//     A boxed task function that can be added to the queue
+fn create_hanging_task(hang_ms: u64) -> Box<TaskFn> {
+    Box::new(move |_heartbeat| {
+        std::thread::sleep(Duration::from_millis(hang_ms));
+        Ok(format!("Hanging task finished after {}ms", hang_ms))
+    })
+}
+
+// Trait implementation: StatefulServer
+//
+// Snapshots preserve task history and results. Tasks that were still queued
+// or running have no closure to resume after a restore, so they are marked
+// as failed instead of silently disappearing.
+impl StatefulServer for TaskQueue {
+    const STATE_NAME: &'static str = "task_queue";
+
+    async fn snapshot(&self) -> Result<Value, StateError> {
+        let mut records: Vec<TaskRecord> = self.records.lock().await.values().cloned().collect();
+        records.sort_by_key(|record| record.id);
+
+        let state = TaskQueueState {
+            next_task_id: *self.next_task_id.lock().await,
+            records,
+        };
+        Ok(serde_json::to_value(state)?)
+    }
+
+    async fn restore(&mut self, state: Value) -> Result<(), StateError> {
+        let state: TaskQueueState = serde_json::from_value(state)?;
+
+        let mut records = self.records.lock().await;
+        records.clear();
+        self.outputs.lock().unwrap().clear();
+        for mut record in state.records {
+            record.interrupt("Task was interrupted by a state restore");
+            records.insert(record.id, record);
+        }
+
+        // Never hand out an ID that a restored record already uses
+        let max_id = records.keys().max().copied().unwrap_or(0);
+        *self.next_task_id.lock().await = state.next_task_id.max(max_id + 1);
+        Ok(())
+    }
+}
+
 // Function: main
 //
 // This is the entry point of the program.
@@ -342,12 +1416,35 @@ fn create_sample_task(
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize the tracing subscriber for logging
     // This will show us what's happening with our tasks
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    let _telemetry = mcp_rust_examples::logging::init("info");
 
     info!("Starting Task Queue Example");
 
-    // Create a new task queue
-    let task_queue = TaskQueue::new();
+    // Create a new task queue, optionally restoring earlier task history
+    let state_command = StateCommand::from_env()?;
+    // Tasks silent for 300ms count as stuck and are retried once
+    let supervisor = SupervisorConfig {
+        stuck_after: Duration::from_millis(300),
+        check_interval: Duration::from_millis(100),
+        requeue_stuck: true,
+        max_attempts: 2,
+    };
+    // With MCP_CHECKPOINT_PATH set, task records are checkpointed and the
+    // history of earlier runs is picked up
+    let mut task_queue = match CheckpointStore::from_env().await? {
+        Some(store) => TaskQueue::with_checkpoints(supervisor, store).await?,
+        None => TaskQueue::with_supervisor(supervisor),
+    };
+    state_command.restore(&mut task_queue).await?;
+
+    // Watch the queue with low thresholds so the demo burst raises alerts,
+    // pushing metrics to example 11 if MCP_METRICS_PUSH_URL is set
+    let monitor = task_queue.spawn_monitor(QueueMonitorConfig {
+        max_depth: 5,
+        max_wait: Duration::from_millis(500),
+        check_interval: Duration::from_millis(100),
+        push_url: std::env::var(METRICS_PUSH_URL_ENV).ok(),
+    });
 
     // Add various tasks with different priorities
     info!("Adding tasks to the queue...");
@@ -399,6 +1496,48 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         )
         .await?;
 
+    // Add a task that never sends a heartbeat
+    task_queue
+        .add_task(
+            TaskPriority::Low,
+            create_hanging_task(600),
+            "Task that hangs without heartbeats".to_string(),
+        )
+        .await?;
+
+    // Queue a task on behalf of a request that the client then cancels;
+    // the worker drops it instead of running it
+    let request = CancellationToken::new();
+    let abandoned = cancellation::scope(
+        request.clone(),
+        task_queue.add_task(
+            TaskPriority::Low,
+            create_sample_task("Abandoned Task".to_string(), 100, false),
+            "Report nobody is waiting for anymore".to_string(),
+        ),
+    )
+    .await
+    .transpose()?;
+    request.cancel();
+
+    // Queue a task that reports as it goes, and follow its output
+    let report_id = task_queue
+        .add_task(
+            TaskPriority::Normal,
+            create_reporting_task(5),
+            "Monthly report with progress updates".to_string(),
+        )
+        .await?;
+    let mut report_output = Box::pin(task_queue.stream_task_output(report_id).await?);
+    let follower = tokio::spawn(async move {
+        while let Some(chunk) = report_output.next().await {
+            info!(
+                "Task {} output #{}: {}",
+                chunk.task_id, chunk.sequence, chunk.text
+            );
+        }
+    });
+
     info!("All tasks queued. Waiting for processing...");
 
     // Give the worker some time to process the tasks
@@ -419,6 +1558,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Wait a bit more for the additional tasks to process
     sleep(Duration::from_secs(1)).await;
+    follower.await?;
+
+    let stats = task_queue.stats().await;
+    info!(
+        "Queue stats: {} completed, {} failed, {} stuck now, {} stuck detections, {} requeued",
+        stats.completed, stats.failed, stats.stuck, stats.stuck_detected, stats.requeued
+    );
+    let metrics = task_queue.metrics().await;
+    info!(
+        "Queue metrics: depth {}, oldest wait {}ms, {} tasks finished in the last minute",
+        metrics.depth, metrics.oldest_wait_ms, metrics.throughput_per_minute
+    );
+
+    // Inspect the results recorded for finished and dropped tasks
+    for task_id in [1, 7, report_id].into_iter().chain(abandoned) {
+        if let Some(record) = task_queue.get_task(task_id).await {
+            info!(
+                "Task {} is {:?}: {:?}",
+                record.id,
+                record.status,
+                record.result.or(record.error)
+            );
+        }
+    }
+
+    state_command.dump(&task_queue).await?;
 
     // Demonstrate graceful shutdown
     info!("Initiating graceful shutdown...");
@@ -426,8 +1591,165 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Give the worker time to complete shutdown
     sleep(Duration::from_millis(500)).await;
+    monitor.await?;
 
     info!("Task Queue Example completed successfully");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Polls a task's record until `done` accepts it
+    async fn wait_for(
+        queue: &TaskQueue,
+        task_id: u64,
+        done: impl Fn(&TaskRecord) -> bool,
+    ) -> TaskRecord {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(record) = queue.get_task(task_id).await {
+                    if done(&record) {
+                        return record;
+                    }
+                }
+                sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("task did not reach the expected state")
+    }
+
+    fn supervisor(requeue_stuck: bool, max_attempts: u32) -> SupervisorConfig {
+        SupervisorConfig {
+            stuck_after: Duration::from_millis(50),
+            check_interval: Duration::from_millis(10),
+            requeue_stuck,
+            max_attempts,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_silent_task_is_flagged_stuck() {
+        let queue = TaskQueue::with_supervisor(supervisor(false, 3));
+        let task_id = queue
+            .add_task(
+                TaskPriority::Normal,
+                |_| {
+                    std::thread::sleep(std::time::Duration::from_millis(200));
+                    Ok("done".to_string())
+                },
+                "silent".to_string(),
+            )
+            .await
+            .unwrap();
+
+        wait_for(&queue, task_id, |r| r.status == TaskStatus::Stuck).await;
+        let stats = queue.stats().await;
+        assert_eq!(stats.stuck, 1);
+        assert_eq!(stats.stuck_detected, 1);
+
+        // Without requeueing, a stuck task is left to finish on its own
+        let record = wait_for(&queue, task_id, |r| r.status == TaskStatus::Completed).await;
+        assert_eq!(record.attempts, 1);
+        assert_eq!(queue.stats().await.requeued, 0);
+    }
+
+    #[tokio::test]
+    async fn test_stuck_task_is_requeued_until_attempts_run_out() {
+        let queue = TaskQueue::with_supervisor(supervisor(true, 2));
+        let task_id = queue
+            .add_task(
+                TaskPriority::Normal,
+                |_| {
+                    std::thread::sleep(std::time::Duration::from_millis(300));
+                    Ok("too late".to_string())
+                },
+                "hangs".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let record = wait_for(&queue, task_id, |r| r.status == TaskStatus::Failed).await;
+        assert_eq!(record.attempts, 2);
+        assert_eq!(
+            record.error.as_deref(),
+            Some("Task stopped sending heartbeats on all 2 attempts")
+        );
+        let stats = queue.stats().await;
+        assert_eq!(stats.stuck_detected, 2);
+        assert_eq!(stats.requeued, 1);
+        assert_eq!(stats.failed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeats_keep_a_slow_task_running() {
+        let queue = TaskQueue::with_supervisor(supervisor(true, 2));
+        let task_id = queue
+            .add_task(
+                TaskPriority::Normal,
+                |heartbeat| {
+                    for _ in 0..10 {
+                        std::thread::sleep(std::time::Duration::from_millis(20));
+                        heartbeat.beat();
+                    }
+                    Ok("done".to_string())
+                },
+                "beats".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let record = wait_for(&queue, task_id, |r| r.status == TaskStatus::Completed).await;
+        assert_eq!(record.attempts, 1);
+        let stats = queue.stats().await;
+        assert_eq!(stats.stuck_detected, 0);
+        assert_eq!(stats.requeued, 0);
+    }
+
+    #[tokio::test]
+    async fn test_output_streams_in_order_and_ends_with_the_task() {
+        let queue = TaskQueue::new();
+        for fails in [false, true] {
+            let task_id = queue
+                .add_task(
+                    TaskPriority::Normal,
+                    move |heartbeat| {
+                        for line in ["one", "two", "three"] {
+                            heartbeat.emit(line);
+                            std::thread::sleep(std::time::Duration::from_millis(5));
+                        }
+                        if fails {
+                            Err("failed after output".to_string())
+                        } else {
+                            Ok("done".to_string())
+                        }
+                    },
+                    "emits".to_string(),
+                )
+                .await
+                .unwrap();
+
+            let stream = queue.stream_task_output(task_id).await.unwrap();
+            let chunks: Vec<OutputChunk> =
+                tokio::time::timeout(Duration::from_secs(5), stream.collect())
+                    .await
+                    .expect("stream did not end with the task");
+
+            let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+            assert_eq!(texts, ["one", "two", "three"]);
+            let sequences: Vec<u64> = chunks.iter().map(|c| c.sequence).collect();
+            assert_eq!(sequences, [0, 1, 2]);
+
+            let expected = if fails {
+                TaskStatus::Failed
+            } else {
+                TaskStatus::Completed
+            };
+            assert_eq!(queue.get_task(task_id).await.unwrap().status, expected);
+        }
+        assert!(queue.stream_task_output(999).await.is_err());
+    }
+}
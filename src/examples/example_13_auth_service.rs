@@ -4,23 +4,60 @@
 // using JWT tokens, password hashing, and proper session management.
 // It shows how to implement user registration, login, token validation,
 // and role-based access control in a production-ready manner.
+// Administrators can impersonate users for support work through short-lived,
+// scope-limited tokens that are audited and visible to the impersonated user.
+// Other servers authenticate to each other with signed service tokens: a
+// registered service client exchanges its credentials for a token scoped to
+// one audience, which the receiving server verifies with the shared key.
+// Third-party agents act for users through consent: an agent asks a user for
+// scopes, the user approves some or all of them for a limited time, and the
+// token the agent exchanges its request for carries only the granted scopes.
+// Users list their grants and revoke them, which also ends the agent's session.
+// Security alerts, such as a stolen refresh token, are sent to the user
+// through the notification service from example 14.
 
 use chrono::{DateTime, Duration, Utc};
+use mcp_rust_examples::identity::Identity;
+use mcp_rust_examples::service_token::{ServiceTokenKey, SERVICE_TOKEN_KEY_ENV};
+use mcp_rust_examples::state::{StateCommand, StateError, StatefulServer};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+// Security alerts are delivered by the notification service from example 14
+#[path = "example_14_notification_service.rs"]
+#[allow(dead_code)]
+mod notifications;
+
+use notifications::{NotificationService, NotificationSubscription};
+
 // Constants for authentication configuration
 // These values should be configurable in a real application
 #[allow(dead_code)]
 const JWT_SECRET: &str = "your-secret-key-here"; // In production, use environment variables
 const TOKEN_EXPIRY_HOURS: i64 = 24;
+const REFRESH_TOKEN_EXPIRY_DAYS: i64 = 30;
 const MAX_LOGIN_ATTEMPTS: u32 = 5;
 const LOCKOUT_DURATION_MINUTES: i64 = 30;
+const MAX_AUDIT_EVENTS: usize = 10_000;
+const IMPERSONATION_EXPIRY_MINUTES: i64 = 30;
+const MIN_IMPERSONATION_REASON_LEN: usize = 10;
+// Actions an impersonation token may ever be granted; anything that changes
+// credentials or reads the audit trail is deliberately absent
+const IMPERSONATION_SCOPES: &[&str] = &["get_user_info", "list_sessions"];
+// Service tokens are not stored or revocable, so they are kept short-lived
+const SERVICE_TOKEN_EXPIRY_MINUTES: i64 = 5;
+// Actions a third-party agent may ask a user for; managing grants, credentials
+// and the audit trail stays with the user
+const AGENT_SCOPES: &[&str] = &["get_user_info", "list_sessions"];
+const CONSENT_REQUEST_EXPIRY_MINUTES: i64 = 10;
+const DEFAULT_CONSENT_EXPIRY_DAYS: i64 = 30;
+const MAX_CONSENT_EXPIRY_DAYS: i64 = 90;
 
 // Enum: UserRole
 //
@@ -150,7 +187,35 @@ pub struct AuthToken {
     role: UserRole,
     issued_at: DateTime<Utc>,
     expires_at: DateTime<Utc>,
-    token_id: Uuid, // Unique identifier for this token
+    token_id: Uuid,          // Unique identifier for this token
+    family_id: Option<Uuid>, // Refresh token family this token was issued from
+    #[serde(default)]
+    impersonation: Option<Impersonation>, // Set on tokens issued by impersonate_user
+    #[serde(default)]
+    delegation: Option<Delegation>, // Set on tokens issued to agents under a consent grant
+}
+
+// Struct: Impersonation
+//
+// This struct flags a token as issued to an administrator acting as another
+// user. The token may only be used for the listed scopes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Impersonation {
+    admin_id: Uuid,
+    admin_username: String,
+    reason: String,
+    scopes: Vec<String>,
+}
+
+// Struct: Delegation
+//
+// This struct flags a token as issued to a third-party agent acting for the
+// user under a consent grant. The token may only be used for granted scopes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delegation {
+    agent_id: String,
+    grant_id: Uuid,
+    scopes: Vec<String>,
 }
 
 impl AuthToken {
@@ -172,6 +237,9 @@ impl AuthToken {
             issued_at: now,
             expires_at: now + Duration::hours(TOKEN_EXPIRY_HOURS),
             token_id: Uuid::new_v4(),
+            family_id: None,
+            impersonation: None,
+            delegation: None,
         }
     }
 
@@ -194,6 +262,231 @@ impl AuthToken {
     pub fn is_valid(&self) -> bool {
         !self.is_expired()
     }
+
+    // Function: scopes
+    //
+    // Returns the scopes an impersonation or agent token is limited to.
+    //
+    // Returns:
+    //     The token's scopes, or None for tokens a user obtained themselves
+    pub fn scopes(&self) -> Option<&[String]> {
+        match (&self.impersonation, &self.delegation) {
+            (Some(imp), _) => Some(&imp.scopes),
+            (None, Some(delegation)) => Some(&delegation.scopes),
+            (None, None) => None,
+        }
+    }
+
+    // Function: identity
+    //
+    // Describes the token holder to other servers, such as the document
+    // collections in example 05, which grant access by username and role.
+    //
+    // Returns:
+    //     The caller identity carried by this token
+    pub fn identity(&self) -> Identity {
+        Identity::new(
+            self.user_id,
+            self.username.clone(),
+            format!("{:?}", self.role),
+        )
+    }
+}
+
+// Struct: RefreshToken
+//
+// This struct represents a long-lived refresh token bound to the device it was
+// issued to. Refresh tokens are rotated on every use; all tokens descending from
+// the same login share a family ID so the whole chain can be revoked at once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshToken {
+    token_id: Uuid,
+    family_id: Uuid,
+    user_id: Uuid,
+    username: String,
+    device_fingerprint: String,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    rotated: bool, // Set once this token has been exchanged for a new one
+}
+
+impl RefreshToken {
+    // Function: new
+    //
+    // Creates a new refresh token for the given user, device and token family.
+    //
+    // Arguments:
+    //     user_id: The ID of the user the token belongs to
+    //     username: The username the token belongs to
+    //     family_id: The token family (one per login)
+    //     device_fingerprint: The fingerprint of the device the token is bound to
+    //
+    // Returns:
+    //     A new RefreshToken valid for REFRESH_TOKEN_EXPIRY_DAYS
+    pub fn new(
+        user_id: Uuid,
+        username: String,
+        family_id: Uuid,
+        device_fingerprint: String,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            token_id: Uuid::new_v4(),
+            family_id,
+            user_id,
+            username,
+            device_fingerprint,
+            issued_at: now,
+            expires_at: now + Duration::days(REFRESH_TOKEN_EXPIRY_DAYS),
+            rotated: false,
+        }
+    }
+
+    // Function: is_expired
+    //
+    // Checks if this refresh token has expired.
+    //
+    // Returns:
+    //     true if the token is expired, false otherwise
+    pub fn is_expired(&self) -> bool {
+        Utc::now() > self.expires_at
+    }
+}
+
+// Struct: TokenPair
+//
+// This struct bundles the short-lived access token with its refresh token.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenPair {
+    access_token: AuthToken,
+    refresh_token: RefreshToken,
+}
+
+// Struct: SecurityAlert
+//
+// This struct describes a security alert in the shape accepted by the
+// notification service from example 14: a recipient, a template name and the
+// variables for that template. The "security_alert" template expects
+// alert_type, alert_message, timestamp and action_required.
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityAlert {
+    recipient_id: String,
+    template_name: String,
+    variables: HashMap<String, String>,
+    priority: String,
+}
+
+impl SecurityAlert {
+    // Function: new
+    //
+    // Creates a "security_alert" notification for the given user.
+    //
+    // Arguments:
+    //     recipient_id: The user to notify
+    //     alert_type: Short alert title
+    //     alert_message: Human-readable description of what happened
+    //     action_required: What the user should do next
+    //
+    // Returns:
+    //     A new SecurityAlert with Critical priority
+    pub fn new(
+        recipient_id: &str,
+        alert_type: &str,
+        alert_message: &str,
+        action_required: &str,
+    ) -> Self {
+        let mut variables = HashMap::new();
+        variables.insert("alert_type".to_string(), alert_type.to_string());
+        variables.insert("alert_message".to_string(), alert_message.to_string());
+        variables.insert("timestamp".to_string(), Utc::now().to_rfc3339());
+        variables.insert("action_required".to_string(), action_required.to_string());
+
+        Self {
+            recipient_id: recipient_id.to_string(),
+            template_name: "security_alert".to_string(),
+            variables,
+            priority: "Critical".to_string(),
+        }
+    }
+}
+
+// Enum: AuthEventKind
+//
+// This enum identifies the type of authentication event recorded in the audit trail.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthEventKind {
+    LoginSuccess,
+    LoginFailure,
+    AccountLocked,
+    TokenRefresh,
+    PermissionDenied,
+    Logout,
+    SuspiciousTokenReuse,
+    ImpersonationStarted,
+    ServiceTokenIssued,
+    ConsentRequested,
+    ConsentGranted,
+    ConsentRevoked,
+    AgentTokenIssued,
+}
+
+// Enum: AuthOutcome
+//
+// This enum records whether the audited operation succeeded or was rejected.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthOutcome {
+    Success,
+    Failure,
+}
+
+// Struct: AuthEvent
+//
+// This struct represents a single entry in the authentication audit trail.
+// Events are append-only and never contain passwords or token secrets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthEvent {
+    id: Uuid,
+    kind: AuthEventKind,
+    outcome: AuthOutcome,
+    username: String,
+    user_id: Option<Uuid>, // None when the username does not exist
+    timestamp: DateTime<Utc>,
+    detail: Option<String>,
+}
+
+// Struct: AuthEventFilter
+//
+// This struct describes the filters accepted by the query_auth_events tool.
+// All fields are optional; an empty filter returns the most recent events.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuthEventFilter {
+    username: Option<String>,
+    kind: Option<AuthEventKind>,
+    outcome: Option<AuthOutcome>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: Option<usize>,
+}
+
+impl AuthEventFilter {
+    // Function: matches
+    //
+    // Checks if an audit event satisfies every filter that is set.
+    //
+    // Arguments:
+    //     event: The audit event to test
+    //
+    // Returns:
+    //     true if the event matches the filter, false otherwise
+    pub fn matches(&self, event: &AuthEvent) -> bool {
+        self.username.as_ref().is_none_or(|u| &event.username == u)
+            && self.kind.as_ref().is_none_or(|k| &event.kind == k)
+            && self.outcome.as_ref().is_none_or(|o| &event.outcome == o)
+            && self.since.is_none_or(|since| event.timestamp >= since)
+            && self.until.is_none_or(|until| event.timestamp <= until)
+    }
 }
 
 // Struct: LoginRequest
@@ -205,6 +498,137 @@ pub struct LoginRequest {
     password: String,
 }
 
+// Struct: ImpersonationRequest
+//
+// This struct represents an administrator's request to act as another user.
+// A reason is mandatory and is recorded in the audit trail; scopes default
+// to every scope impersonation tokens may hold.
+#[derive(Debug, Deserialize)]
+pub struct ImpersonationRequest {
+    target_username: String,
+    reason: String,
+    scopes: Option<Vec<String>>,
+}
+
+// Struct: ServiceClient
+//
+// This struct represents a machine client, such as the gateway of example 19,
+// that may obtain service tokens. Each grant names an audience the client may
+// call and the scopes it may request there. The secret is hashed like a
+// password.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceClient {
+    client_id: String,
+    secret_hash: String,
+    grants: HashMap<String, Vec<String>>, // audience -> scopes
+    created_at: DateTime<Utc>,
+}
+
+// Struct: ServiceClientRequest
+//
+// This struct represents an administrator's request to register a service
+// client with the audiences and scopes it is granted.
+#[derive(Debug, Deserialize)]
+pub struct ServiceClientRequest {
+    client_id: String,
+    client_secret: String,
+    grants: HashMap<String, Vec<String>>,
+}
+
+// Struct: ServiceTokenRequest
+//
+// This struct represents a service client's request for a token to call one
+// audience. Scopes default to everything granted for that audience.
+#[derive(Debug, Deserialize)]
+pub struct ServiceTokenRequest {
+    client_id: String,
+    client_secret: String,
+    audience: String,
+    scopes: Option<Vec<String>>,
+}
+
+// Struct: ConsentRequest
+//
+// This struct represents a third-party agent asking a user for scopes.
+#[derive(Debug, Deserialize)]
+pub struct ConsentRequest {
+    agent_id: String,
+    username: String,
+    scopes: Vec<String>,
+}
+
+// Struct: PendingConsent
+//
+// This struct tracks a consent request until the agent exchanges it for a
+// token. The request ID is only known to the agent and the user it asked, and
+// the request expires after CONSENT_REQUEST_EXPIRY_MINUTES either way.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingConsent {
+    request_id: Uuid,
+    agent_id: String,
+    user_id: Uuid,
+    username: String,
+    scopes: Vec<String>,
+    requested_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    grant_id: Option<Uuid>, // Set once the user approves
+}
+
+// Struct: ConsentApproval
+//
+// This struct represents a user's answer to a consent request. Scopes default
+// to everything the agent asked for and may only narrow it; the grant expires
+// after DEFAULT_CONSENT_EXPIRY_DAYS unless the user picks another duration.
+#[derive(Debug, Deserialize)]
+pub struct ConsentApproval {
+    request_id: Uuid,
+    scopes: Option<Vec<String>>,
+    expires_in_days: Option<i64>,
+}
+
+// Struct: ConsentGrant
+//
+// This struct records the scopes a user granted to an agent and until when.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsentGrant {
+    grant_id: Uuid,
+    agent_id: String,
+    user_id: Uuid,
+    username: String,
+    scopes: Vec<String>,
+    granted_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+impl ConsentGrant {
+    // Function: is_expired
+    //
+    // Checks if the user's consent has run out.
+    //
+    // Returns:
+    //     true if the grant is expired, false otherwise
+    pub fn is_expired(&self) -> bool {
+        Utc::now() > self.expires_at
+    }
+}
+
+// Struct: SessionInfo
+//
+// This struct describes one active session in a user's session list.
+// Impersonation sessions name the administrator and their stated reason, and
+// agent sessions name the agent acting under a consent grant.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    token_id: Uuid,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    current: bool,
+    impersonated_by: Option<String>,
+    impersonation_reason: Option<String>,
+    agent_id: Option<String>,
+    scopes: Option<Vec<String>>,
+}
+
 // Struct: RegistrationRequest
 //
 // This struct represents a user registration request.
@@ -222,6 +646,31 @@ pub struct RegistrationRequest {
 pub struct AuthService {
     users: Arc<RwLock<HashMap<String, User>>>, // username -> User
     active_tokens: Arc<RwLock<HashMap<Uuid, AuthToken>>>, // token_id -> AuthToken
+    refresh_tokens: Arc<RwLock<HashMap<Uuid, RefreshToken>>>, // token_id -> RefreshToken
+    audit_log: Arc<RwLock<VecDeque<AuthEvent>>>, // oldest first
+    alert_sender: Option<mpsc::UnboundedSender<SecurityAlert>>,
+    service_clients: Arc<RwLock<HashMap<String, ServiceClient>>>, // client ID -> client
+    service_key: ServiceTokenKey,
+    pending_consents: Arc<RwLock<HashMap<Uuid, PendingConsent>>>, // request ID -> request
+    consent_grants: Arc<RwLock<HashMap<Uuid, ConsentGrant>>>,     // grant ID -> grant
+}
+
+// Struct: AuthServiceState
+//
+// The durable state of the auth service, captured by snapshots. The alert
+// sink is runtime wiring and is configured again by the caller.
+#[derive(Debug, Serialize, Deserialize)]
+struct AuthServiceState {
+    users: Vec<User>,
+    active_tokens: Vec<AuthToken>,
+    refresh_tokens: Vec<RefreshToken>,
+    audit_log: VecDeque<AuthEvent>,
+    #[serde(default)]
+    service_clients: Vec<ServiceClient>,
+    #[serde(default)]
+    pending_consents: Vec<PendingConsent>,
+    #[serde(default)]
+    consent_grants: Vec<ConsentGrant>,
 }
 
 impl Default for AuthService {
@@ -236,14 +685,87 @@ impl AuthService {
     // Creates a new authentication service instance.
     //
     // Returns:
-    //     A new AuthService with empty user and token stores
+    //     A new AuthService with empty user and token stores, signing service
+    //     tokens with the key from MCP_SERVICE_TOKEN_KEY
     pub fn new() -> Self {
         Self {
             users: Arc::new(RwLock::new(HashMap::new())),
             active_tokens: Arc::new(RwLock::new(HashMap::new())),
+            refresh_tokens: Arc::new(RwLock::new(HashMap::new())),
+            audit_log: Arc::new(RwLock::new(VecDeque::new())),
+            alert_sender: None,
+            service_clients: Arc::new(RwLock::new(HashMap::new())),
+            service_key: ServiceTokenKey::from_env(),
+            pending_consents: Arc::new(RwLock::new(HashMap::new())),
+            consent_grants: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    // Function: with_alert_sink
+    //
+    // Configures a channel that receives security alerts, typically forwarded
+    // to the notification service (example 14).
+    //
+    // Arguments:
+    //     sender: The channel to publish alerts on
+    //
+    // Returns:
+    //     The AuthService with alerting enabled
+    pub fn with_alert_sink(mut self, sender: mpsc::UnboundedSender<SecurityAlert>) -> Self {
+        self.alert_sender = Some(sender);
+        self
+    }
+
+    // Function: emit_alert
+    //
+    // Publishes a security alert if an alert sink is configured.
+    //
+    // Arguments:
+    //     alert: The alert to publish
+    fn emit_alert(&self, alert: SecurityAlert) {
+        if let Some(sender) = &self.alert_sender {
+            if let Err(e) = sender.send(alert) {
+                error!("Failed to emit security alert: {}", e);
+            }
         }
     }
 
+    // Function: record_event
+    //
+    // Appends an event to the audit trail, discarding the oldest entries
+    // once MAX_AUDIT_EVENTS is reached.
+    //
+    // Arguments:
+    //     kind: The type of authentication event
+    //     outcome: Whether the operation succeeded
+    //     username: The username involved in the event
+    //     user_id: The user ID, if the user exists
+    //     detail: Optional human-readable context
+    async fn record_event(
+        &self,
+        kind: AuthEventKind,
+        outcome: AuthOutcome,
+        username: &str,
+        user_id: Option<Uuid>,
+        detail: Option<&str>,
+    ) {
+        let mut audit_log = self.audit_log.write().await;
+
+        if audit_log.len() >= MAX_AUDIT_EVENTS {
+            audit_log.pop_front();
+        }
+
+        audit_log.push_back(AuthEvent {
+            id: Uuid::new_v4(),
+            kind,
+            outcome,
+            username: username.to_string(),
+            user_id,
+            timestamp: Utc::now(),
+            detail: detail.map(str::to_string),
+        });
+    }
+
     // Function: register_user
     //
     // Registers a new user account in the system.
@@ -254,6 +776,25 @@ impl AuthService {
     // Returns:
     //     Result with the created user ID or an error message
     pub async fn register_user(&self, request: RegistrationRequest) -> Result<Uuid, String> {
+        self.register_user_with_role(request, UserRole::User).await
+    }
+
+    // Function: register_user_with_role
+    //
+    // Registers a new user account with an explicit role. This is intended for
+    // provisioning administrators and should not be exposed to end users.
+    //
+    // Arguments:
+    //     request: The registration request containing user details
+    //     role: The role to assign to the new account
+    //
+    // Returns:
+    //     Result with the created user ID or an error message
+    pub async fn register_user_with_role(
+        &self,
+        request: RegistrationRequest,
+        role: UserRole,
+    ) -> Result<Uuid, String> {
         let mut users = self.users.write().await;
 
         // Check if username already exists
@@ -266,12 +807,11 @@ impl AuthService {
             return Err("Password does not meet security requirements".to_string());
         }
 
-        // Create new user with default role
         let user = User::new(
             request.username.clone(),
             request.email,
             request.password,
-            UserRole::User,
+            role,
         );
 
         let user_id = user.id;
@@ -295,12 +835,31 @@ impl AuthService {
         let mut users = self.users.write().await;
 
         // Find the user
-        let user = users
-            .get_mut(&request.username)
-            .ok_or("Invalid username or password")?;
+        let Some(user) = users.get_mut(&request.username) else {
+            drop(users);
+            self.record_event(
+                AuthEventKind::LoginFailure,
+                AuthOutcome::Failure,
+                &request.username,
+                None,
+                Some("unknown username"),
+            )
+            .await;
+            return Err("Invalid username or password".to_string());
+        };
+        let user_id = user.id;
 
         // Check if account is locked
         if user.is_locked() {
+            drop(users);
+            self.record_event(
+                AuthEventKind::LoginFailure,
+                AuthOutcome::Failure,
+                &request.username,
+                Some(user_id),
+                Some("account locked"),
+            )
+            .await;
             return Err(
                 "Account is temporarily locked due to too many failed attempts".to_string(),
             );
@@ -308,13 +867,43 @@ impl AuthService {
 
         // Check if account is active
         if !user.is_active {
+            drop(users);
+            self.record_event(
+                AuthEventKind::LoginFailure,
+                AuthOutcome::Failure,
+                &request.username,
+                Some(user_id),
+                Some("account deactivated"),
+            )
+            .await;
             return Err("Account is deactivated".to_string());
         }
 
         // Verify password
         if !user.verify_password(&request.password) {
             user.increment_failed_attempts();
+            let locked = user.is_locked();
+            drop(users);
             warn!("Failed login attempt for user: {}", request.username);
+
+            self.record_event(
+                AuthEventKind::LoginFailure,
+                AuthOutcome::Failure,
+                &request.username,
+                Some(user_id),
+                Some("invalid password"),
+            )
+            .await;
+            if locked {
+                self.record_event(
+                    AuthEventKind::AccountLocked,
+                    AuthOutcome::Failure,
+                    &request.username,
+                    Some(user_id),
+                    Some("too many failed attempts"),
+                )
+                .await;
+            }
             return Err("Invalid username or password".to_string());
         }
 
@@ -324,10 +913,21 @@ impl AuthService {
 
         // Create authentication token
         let token = AuthToken::new(user);
+        drop(users);
 
         // Store the token
         let mut active_tokens = self.active_tokens.write().await;
         active_tokens.insert(token.token_id, token.clone());
+        drop(active_tokens);
+
+        self.record_event(
+            AuthEventKind::LoginSuccess,
+            AuthOutcome::Success,
+            &request.username,
+            Some(user_id),
+            None,
+        )
+        .await;
 
         info!("User authenticated successfully: {}", request.username);
         Ok(token)
@@ -368,6 +968,25 @@ impl AuthService {
 
         match active_tokens.remove(&token_id) {
             Some(token) => {
+                drop(active_tokens);
+                let detail = match (&token.impersonation, &token.delegation) {
+                    (Some(imp), _) => Some(format!(
+                        "impersonation session by {} ended",
+                        imp.admin_username
+                    )),
+                    (None, Some(delegation)) => {
+                        Some(format!("session of agent {} ended", delegation.agent_id))
+                    }
+                    (None, None) => None,
+                };
+                self.record_event(
+                    AuthEventKind::Logout,
+                    AuthOutcome::Success,
+                    &token.username,
+                    Some(token.user_id),
+                    detail.as_deref(),
+                )
+                .await;
                 info!("User logged out: {}", token.username);
                 Ok(())
             }
@@ -375,6 +994,188 @@ impl AuthService {
         }
     }
 
+    // Function: authenticate_device
+    //
+    // Authenticates a user and issues a refresh token bound to the caller's
+    // device fingerprint. Each call starts a new token family.
+    //
+    // Arguments:
+    //     request: The login request containing credentials
+    //     device_fingerprint: A stable identifier for the client device
+    //
+    // Returns:
+    //     Result with the access/refresh token pair or an error message
+    pub async fn authenticate_device(
+        &self,
+        request: LoginRequest,
+        device_fingerprint: &str,
+    ) -> Result<TokenPair, String> {
+        let mut access_token = self.authenticate(request).await?;
+        let family_id = Uuid::new_v4();
+
+        access_token.family_id = Some(family_id);
+        self.active_tokens
+            .write()
+            .await
+            .insert(access_token.token_id, access_token.clone());
+
+        let refresh_token = RefreshToken::new(
+            access_token.user_id,
+            access_token.username.clone(),
+            family_id,
+            device_fingerprint.to_string(),
+        );
+        self.refresh_tokens
+            .write()
+            .await
+            .insert(refresh_token.token_id, refresh_token.clone());
+
+        Ok(TokenPair {
+            access_token,
+            refresh_token,
+        })
+    }
+
+    // Function: refresh_token
+    //
+    // Rotates a refresh token, returning a new access/refresh token pair.
+    // Presenting the token from a different device, or presenting a token that
+    // was already rotated, is treated as theft: the whole token family is
+    // revoked and a security alert is emitted.
+    //
+    // Arguments:
+    //     refresh_token_id: The refresh token being exchanged
+    //     device_fingerprint: The fingerprint of the device presenting the token
+    //
+    // Returns:
+    //     Result with the new token pair, or an error message
+    pub async fn refresh_token(
+        &self,
+        refresh_token_id: Uuid,
+        device_fingerprint: &str,
+    ) -> Result<TokenPair, String> {
+        let mut refresh_tokens = self.refresh_tokens.write().await;
+
+        let presented = refresh_tokens
+            .get(&refresh_token_id)
+            .cloned()
+            .ok_or("Invalid refresh token")?;
+
+        let suspicious_reason = if presented.device_fingerprint != device_fingerprint {
+            Some("refresh token presented from a different device")
+        } else if presented.rotated {
+            Some("previously rotated refresh token was reused")
+        } else {
+            None
+        };
+
+        if let Some(reason) = suspicious_reason {
+            drop(refresh_tokens);
+            self.revoke_token_family(presented.family_id).await;
+            self.record_event(
+                AuthEventKind::SuspiciousTokenReuse,
+                AuthOutcome::Failure,
+                &presented.username,
+                Some(presented.user_id),
+                Some(reason),
+            )
+            .await;
+            self.emit_alert(SecurityAlert::new(
+                &presented.username,
+                "Suspicious Token Reuse",
+                &format!("{}; all sessions from that login were signed out", reason),
+                "Please sign in again and change your password if this was not you",
+            ));
+            warn!(
+                "Revoked token family {} for {}: {}",
+                presented.family_id, presented.username, reason
+            );
+            return Err("Refresh token rejected".to_string());
+        }
+
+        if presented.is_expired() {
+            drop(refresh_tokens);
+            self.record_event(
+                AuthEventKind::TokenRefresh,
+                AuthOutcome::Failure,
+                &presented.username,
+                Some(presented.user_id),
+                Some("refresh token expired"),
+            )
+            .await;
+            return Err("Refresh token has expired".to_string());
+        }
+
+        // Rotate: the presented token stays on record (marked rotated) so that
+        // a later replay can be detected.
+        if let Some(token) = refresh_tokens.get_mut(&refresh_token_id) {
+            token.rotated = true;
+        }
+        let refresh_token = RefreshToken::new(
+            presented.user_id,
+            presented.username.clone(),
+            presented.family_id,
+            presented.device_fingerprint.clone(),
+        );
+        refresh_tokens.insert(refresh_token.token_id, refresh_token.clone());
+        drop(refresh_tokens);
+
+        let role = self
+            .users
+            .read()
+            .await
+            .get(&presented.username)
+            .map(|user| user.role.clone())
+            .ok_or("User not found")?;
+        let now = Utc::now();
+        let access_token = AuthToken {
+            user_id: presented.user_id,
+            username: presented.username.clone(),
+            role,
+            issued_at: now,
+            expires_at: now + Duration::hours(TOKEN_EXPIRY_HOURS),
+            token_id: Uuid::new_v4(),
+            family_id: Some(presented.family_id),
+            impersonation: None,
+            delegation: None,
+        };
+        self.active_tokens
+            .write()
+            .await
+            .insert(access_token.token_id, access_token.clone());
+
+        self.record_event(
+            AuthEventKind::TokenRefresh,
+            AuthOutcome::Success,
+            &presented.username,
+            Some(presented.user_id),
+            None,
+        )
+        .await;
+
+        Ok(TokenPair {
+            access_token,
+            refresh_token,
+        })
+    }
+
+    // Function: revoke_token_family
+    //
+    // Revokes every refresh token and access token issued from a token family.
+    //
+    // Arguments:
+    //     family_id: The token family to revoke
+    async fn revoke_token_family(&self, family_id: Uuid) {
+        self.refresh_tokens
+            .write()
+            .await
+            .retain(|_, token| token.family_id != family_id);
+        self.active_tokens
+            .write()
+            .await
+            .retain(|_, token| token.family_id != Some(family_id));
+    }
+
     // Function: check_permission
     //
     // Checks if a user has permission to perform a specific action based on their role.
@@ -395,9 +1196,738 @@ impl AuthService {
         }
     }
 
+    // Function: authorize
+    //
+    // Enforces a role requirement, recording a permission-denied audit event
+    // when the token's role is insufficient. Impersonation and agent tokens
+    // are also limited to the scopes they were issued with.
+    //
+    // Arguments:
+    //     token: The authentication token containing user role
+    //     required_role: The minimum role required for the action
+    //     action: A short description of the attempted action for the audit trail
+    //
+    // Returns:
+    //     Result indicating whether the action is permitted
+    pub async fn authorize(
+        &self,
+        token: &AuthToken,
+        required_role: &UserRole,
+        action: &str,
+    ) -> Result<(), String> {
+        let in_scope = token
+            .scopes()
+            .is_none_or(|scopes| scopes.iter().any(|scope| scope == action));
+        if in_scope && self.check_permission(token, required_role) {
+            return Ok(());
+        }
+
+        self.record_event(
+            AuthEventKind::PermissionDenied,
+            AuthOutcome::Failure,
+            &token.username,
+            Some(token.user_id),
+            Some(&match (&token.impersonation, &token.delegation) {
+                (Some(imp), _) => format!("{} (impersonated by {})", action, imp.admin_username),
+                (None, Some(delegation)) => {
+                    format!("{} (by agent {})", action, delegation.agent_id)
+                }
+                (None, None) => action.to_string(),
+            }),
+        )
+        .await;
+        warn!("Permission denied for {} on {}", token.username, action);
+        if !in_scope {
+            let kind = if token.impersonation.is_some() {
+                "impersonation token"
+            } else {
+                "agent token"
+            };
+            return Err(format!(
+                "Permission denied: {} is outside this {}'s scopes",
+                action, kind
+            ));
+        }
+        Err(format!(
+            "Permission denied: {} requires {:?}",
+            action, required_role
+        ))
+    }
+
+    // Function: impersonate_user
+    //
+    // Admin-only operation that issues a short-lived token acting as another
+    // user, for support workflows. The token is flagged with the administrator
+    // and reason, limited to IMPERSONATION_SCOPES, cannot be refreshed, and
+    // shows up in the impersonated user's session list. Administrators cannot
+    // be impersonated, and every attempt is audited.
+    //
+    // Arguments:
+    //     admin_token_id: The token of the administrator
+    //     request: The target user, the reason and the requested scopes
+    //
+    // Returns:
+    //     Result with the impersonation token or an error message
+    pub async fn impersonate_user(
+        &self,
+        admin_token_id: Uuid,
+        request: ImpersonationRequest,
+    ) -> Result<AuthToken, String> {
+        let admin = self.validate_token(admin_token_id).await?;
+        if admin.impersonation.is_some() {
+            return Err("Impersonation tokens cannot start another impersonation".to_string());
+        }
+        self.authorize(&admin, &UserRole::Admin, "impersonate_user")
+            .await?;
+
+        let reason = request.reason.trim();
+        let scopes = request.scopes.unwrap_or_else(|| {
+            IMPERSONATION_SCOPES
+                .iter()
+                .map(|scope| scope.to_string())
+                .collect()
+        });
+
+        let rejection = if reason.len() < MIN_IMPERSONATION_REASON_LEN {
+            Some(format!(
+                "A reason of at least {} characters is required",
+                MIN_IMPERSONATION_REASON_LEN
+            ))
+        } else {
+            scopes
+                .iter()
+                .find(|scope| !IMPERSONATION_SCOPES.contains(&scope.as_str()))
+                .map(|scope| {
+                    format!(
+                        "Scope '{}' cannot be granted to impersonation tokens",
+                        scope
+                    )
+                })
+        };
+
+        let target = self
+            .users
+            .read()
+            .await
+            .get(&request.target_username)
+            .cloned();
+        let rejection = rejection.or_else(|| match &target {
+            None => Some("User not found".to_string()),
+            Some(user) if user.role == UserRole::Admin => {
+                Some("Administrators cannot be impersonated".to_string())
+            }
+            Some(user) if !user.is_active => Some("Account is deactivated".to_string()),
+            Some(_) => None,
+        });
+
+        if let Some(error) = rejection {
+            self.record_event(
+                AuthEventKind::ImpersonationStarted,
+                AuthOutcome::Failure,
+                &request.target_username,
+                target.as_ref().map(|user| user.id),
+                Some(&format!("by {}: {}", admin.username, error)),
+            )
+            .await;
+            return Err(error);
+        }
+        let target = target.expect("rejected above when missing");
+
+        let mut token = AuthToken::new(&target);
+        token.expires_at = token.issued_at + Duration::minutes(IMPERSONATION_EXPIRY_MINUTES);
+        token.impersonation = Some(Impersonation {
+            admin_id: admin.user_id,
+            admin_username: admin.username.clone(),
+            reason: reason.to_string(),
+            scopes: scopes.clone(),
+        });
+        self.active_tokens
+            .write()
+            .await
+            .insert(token.token_id, token.clone());
+
+        self.record_event(
+            AuthEventKind::ImpersonationStarted,
+            AuthOutcome::Success,
+            &target.username,
+            Some(target.id),
+            Some(&format!(
+                "by {} (scopes: {}): {}",
+                admin.username,
+                scopes.join(", "),
+                reason
+            )),
+        )
+        .await;
+        warn!(
+            "Admin {} is impersonating {}: {}",
+            admin.username, target.username, reason
+        );
+
+        Ok(token)
+    }
+
+    // Function: list_sessions
+    //
+    // Lists the active sessions of the token's user, newest first, so users
+    // can see where they are signed in, including impersonation sessions and
+    // agents acting for them.
+    //
+    // Arguments:
+    //     token_id: A token of the user whose sessions to list
+    //
+    // Returns:
+    //     Result with the user's sessions or an error message
+    pub async fn list_sessions(&self, token_id: Uuid) -> Result<Vec<SessionInfo>, String> {
+        let token = self.validate_token(token_id).await?;
+        self.authorize(&token, &UserRole::Guest, "list_sessions")
+            .await?;
+
+        let mut sessions: Vec<SessionInfo> = self
+            .active_tokens
+            .read()
+            .await
+            .values()
+            .filter(|session| session.user_id == token.user_id && !session.is_expired())
+            .map(|session| SessionInfo {
+                token_id: session.token_id,
+                issued_at: session.issued_at,
+                expires_at: session.expires_at,
+                current: session.token_id == token_id,
+                impersonated_by: session
+                    .impersonation
+                    .as_ref()
+                    .map(|imp| imp.admin_username.clone()),
+                impersonation_reason: session.impersonation.as_ref().map(|imp| imp.reason.clone()),
+                agent_id: session
+                    .delegation
+                    .as_ref()
+                    .map(|delegation| delegation.agent_id.clone()),
+                scopes: session.scopes().map(<[String]>::to_vec),
+            })
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.issued_at));
+        Ok(sessions)
+    }
+
+    // Function: query_auth_events
+    //
+    // Admin-only tool that returns audit events matching the given filter,
+    // newest first.
+    //
+    // Arguments:
+    //     token_id: The token of the administrator making the query
+    //     filter: Filters by user, event kind, outcome and time range
+    //
+    // Returns:
+    //     Result with the matching events or an error message
+    pub async fn query_auth_events(
+        &self,
+        token_id: Uuid,
+        filter: AuthEventFilter,
+    ) -> Result<Vec<AuthEvent>, String> {
+        let token = self.validate_token(token_id).await?;
+        self.authorize(&token, &UserRole::Admin, "query_auth_events")
+            .await?;
+
+        let audit_log = self.audit_log.read().await;
+        let limit = filter.limit.unwrap_or(100);
+
+        Ok(audit_log
+            .iter()
+            .rev()
+            .filter(|event| filter.matches(event))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    // Function: register_service_client
+    //
+    // Admin-only operation that registers a machine client allowed to obtain
+    // service tokens for the audiences and scopes in its grants.
+    //
+    // Arguments:
+    //     admin_token_id: The token of the administrator
+    //     request: The client ID, its secret and its grants
+    //
+    // Returns:
+    //     Result indicating whether the client was registered
+    pub async fn register_service_client(
+        &self,
+        admin_token_id: Uuid,
+        request: ServiceClientRequest,
+    ) -> Result<(), String> {
+        let admin = self.validate_token(admin_token_id).await?;
+        self.authorize(&admin, &UserRole::Admin, "register_service_client")
+            .await?;
+
+        if !is_password_strong(&request.client_secret) {
+            return Err("Client secret does not meet security requirements".to_string());
+        }
+        if request.grants.is_empty() {
+            return Err("A service client needs at least one audience".to_string());
+        }
+
+        let mut clients = self.service_clients.write().await;
+        if clients.contains_key(&request.client_id) {
+            return Err("Service client already exists".to_string());
+        }
+        info!(
+            "Service client {} registered by {} for {:?}",
+            request.client_id,
+            admin.username,
+            request.grants.keys().collect::<Vec<_>>()
+        );
+        clients.insert(
+            request.client_id.clone(),
+            ServiceClient {
+                client_id: request.client_id,
+                secret_hash: hash_password(&request.client_secret),
+                grants: request.grants,
+                created_at: Utc::now(),
+            },
+        );
+        Ok(())
+    }
+
+    // Function: issue_service_token
+    //
+    // Exchanges a service client's credentials for a signed token that lets
+    // it call one audience, like an OAuth client credentials grant. Tokens
+    // expire after SERVICE_TOKEN_EXPIRY_MINUTES and carry only the requested
+    // scopes, which must be granted to the client. Every attempt is audited.
+    //
+    // Arguments:
+    //     request: The client credentials, the audience and the scopes
+    //
+    // Returns:
+    //     Result with the service token or an error message
+    pub async fn issue_service_token(
+        &self,
+        request: ServiceTokenRequest,
+    ) -> Result<String, String> {
+        let granted = {
+            let clients = self.service_clients.read().await;
+            clients
+                .get(&request.client_id)
+                .filter(|client| verify_password(&request.client_secret, &client.secret_hash))
+                .map(|client| client.grants.get(&request.audience).cloned())
+        };
+        let detail = format!("audience {}", request.audience);
+
+        // Unknown clients and wrong secrets get the same answer
+        let Some(granted) = granted else {
+            self.record_event(
+                AuthEventKind::ServiceTokenIssued,
+                AuthOutcome::Failure,
+                &request.client_id,
+                None,
+                Some(&format!("{}: invalid client credentials", detail)),
+            )
+            .await;
+            warn!(
+                "Service token refused: invalid credentials for {}",
+                request.client_id
+            );
+            return Err("Invalid client credentials".to_string());
+        };
+
+        let scopes = request
+            .scopes
+            .unwrap_or_else(|| granted.clone().unwrap_or_default());
+        let refusal = match &granted {
+            None => Some(format!(
+                "{} is not granted {}",
+                request.client_id, request.audience
+            )),
+            Some(granted) => scopes
+                .iter()
+                .find(|scope| !granted.contains(scope))
+                .map(|scope| format!("Scope {} is not granted for {}", scope, request.audience)),
+        };
+        if let Some(reason) = refusal {
+            self.record_event(
+                AuthEventKind::ServiceTokenIssued,
+                AuthOutcome::Failure,
+                &request.client_id,
+                None,
+                Some(&format!("{}: {}", detail, reason)),
+            )
+            .await;
+            warn!("Service token refused: {}", reason);
+            return Err(format!("Permission denied: {}", reason));
+        }
+
+        let scopes: Vec<&str> = scopes.iter().map(String::as_str).collect();
+        let token = self.service_key.issue(
+            &request.client_id,
+            &request.audience,
+            &scopes,
+            Duration::minutes(SERVICE_TOKEN_EXPIRY_MINUTES),
+        );
+        self.record_event(
+            AuthEventKind::ServiceTokenIssued,
+            AuthOutcome::Success,
+            &request.client_id,
+            None,
+            Some(&format!("{} scopes {:?}", detail, scopes)),
+        )
+        .await;
+        info!(
+            "Service token issued to {} for {}",
+            request.client_id, request.audience
+        );
+        Ok(token)
+    }
+
+    // Function: request_consent
+    //
+    // Called by a third-party agent to ask a user for scopes. The returned
+    // request is shown to the user, who approves it with approve_consent; the
+    // agent then exchanges it with issue_agent_token. Requests for scopes
+    // outside AGENT_SCOPES are refused, and every request is audited.
+    //
+    // Arguments:
+    //     request: The agent, the user it acts for and the scopes it needs
+    //
+    // Returns:
+    //     Result with the pending request or an error message
+    pub async fn request_consent(&self, request: ConsentRequest) -> Result<PendingConsent, String> {
+        let user = self.users.read().await.get(&request.username).cloned();
+        let rejection = if request.agent_id.trim().is_empty() {
+            Some("An agent ID is required".to_string())
+        } else if request.scopes.is_empty() {
+            Some("At least one scope must be requested".to_string())
+        } else {
+            request
+                .scopes
+                .iter()
+                .find(|scope| !AGENT_SCOPES.contains(&scope.as_str()))
+                .map(|scope| format!("Scope '{}' cannot be granted to agents", scope))
+        };
+        let rejection = rejection.or_else(|| match &user {
+            None => Some("User not found".to_string()),
+            Some(user) if !user.is_active => Some("Account is deactivated".to_string()),
+            Some(_) => None,
+        });
+
+        if let Some(error) = rejection {
+            self.record_event(
+                AuthEventKind::ConsentRequested,
+                AuthOutcome::Failure,
+                &request.username,
+                user.as_ref().map(|user| user.id),
+                Some(&format!("by agent {}: {}", request.agent_id, error)),
+            )
+            .await;
+            return Err(error);
+        }
+        let user = user.expect("rejected above when missing");
+
+        let now = Utc::now();
+        let pending = PendingConsent {
+            request_id: Uuid::new_v4(),
+            agent_id: request.agent_id,
+            user_id: user.id,
+            username: user.username,
+            scopes: request.scopes,
+            requested_at: now,
+            expires_at: now + Duration::minutes(CONSENT_REQUEST_EXPIRY_MINUTES),
+            grant_id: None,
+        };
+        self.pending_consents
+            .write()
+            .await
+            .insert(pending.request_id, pending.clone());
+
+        self.record_event(
+            AuthEventKind::ConsentRequested,
+            AuthOutcome::Success,
+            &pending.username,
+            Some(pending.user_id),
+            Some(&format!(
+                "by agent {} (scopes: {})",
+                pending.agent_id,
+                pending.scopes.join(", ")
+            )),
+        )
+        .await;
+        info!(
+            "Agent {} asked {} for {:?}",
+            pending.agent_id, pending.username, pending.scopes
+        );
+        Ok(pending)
+    }
+
+    // Function: approve_consent
+    //
+    // Lets a user grant an agent some or all of the scopes it asked for,
+    // recording the grant with its expiry. Only the user the request was
+    // addressed to can approve it, with a token they obtained themselves.
+    //
+    // Arguments:
+    //     token_id: The user's token
+    //     approval: The request, the scopes to grant and the grant's lifetime
+    //
+    // Returns:
+    //     Result with the recorded grant or an error message
+    pub async fn approve_consent(
+        &self,
+        token_id: Uuid,
+        approval: ConsentApproval,
+    ) -> Result<ConsentGrant, String> {
+        let token = self.validate_token(token_id).await?;
+        self.authorize(&token, &UserRole::Guest, "approve_consent")
+            .await?;
+
+        let mut pending_consents = self.pending_consents.write().await;
+        let pending = pending_consents
+            .get_mut(&approval.request_id)
+            .filter(|pending| pending.user_id == token.user_id)
+            .ok_or("Consent request not found")?;
+        if Utc::now() > pending.expires_at {
+            return Err("Consent request has expired".to_string());
+        }
+        if pending.grant_id.is_some() {
+            return Err("Consent request was already approved".to_string());
+        }
+
+        let scopes = approval.scopes.unwrap_or_else(|| pending.scopes.clone());
+        let expires_in_days = approval
+            .expires_in_days
+            .unwrap_or(DEFAULT_CONSENT_EXPIRY_DAYS);
+        let rejection = if scopes.is_empty() {
+            Some("At least one scope must be granted".to_string())
+        } else if !(1..=MAX_CONSENT_EXPIRY_DAYS).contains(&expires_in_days) {
+            Some(format!(
+                "Consent must expire within 1 to {} days",
+                MAX_CONSENT_EXPIRY_DAYS
+            ))
+        } else {
+            scopes
+                .iter()
+                .find(|scope| !pending.scopes.contains(scope))
+                .map(|scope| format!("Scope '{}' was not requested by the agent", scope))
+        };
+        if let Some(error) = rejection {
+            return Err(error);
+        }
+
+        let now = Utc::now();
+        let grant = ConsentGrant {
+            grant_id: Uuid::new_v4(),
+            agent_id: pending.agent_id.clone(),
+            user_id: token.user_id,
+            username: token.username.clone(),
+            scopes,
+            granted_at: now,
+            expires_at: now + Duration::days(expires_in_days),
+        };
+        pending.grant_id = Some(grant.grant_id);
+        drop(pending_consents);
+        self.consent_grants
+            .write()
+            .await
+            .insert(grant.grant_id, grant.clone());
+
+        self.record_event(
+            AuthEventKind::ConsentGranted,
+            AuthOutcome::Success,
+            &grant.username,
+            Some(grant.user_id),
+            Some(&format!(
+                "to agent {} (scopes: {}) until {}",
+                grant.agent_id,
+                grant.scopes.join(", "),
+                grant.expires_at
+            )),
+        )
+        .await;
+        info!(
+            "{} granted {:?} to agent {}",
+            grant.username, grant.scopes, grant.agent_id
+        );
+        Ok(grant)
+    }
+
+    // Function: issue_agent_token
+    //
+    // Exchanges an approved consent request for a token acting as the user.
+    // The token carries only the granted scopes, cannot be refreshed, expires
+    // with the grant, and shows up in the user's session list. Each request
+    // can be exchanged once.
+    //
+    // Arguments:
+    //     agent_id: The agent that made the request
+    //     request_id: The approved consent request
+    //
+    // Returns:
+    //     Result with the agent token or an error message
+    pub async fn issue_agent_token(
+        &self,
+        agent_id: &str,
+        request_id: Uuid,
+    ) -> Result<AuthToken, String> {
+        let pending = {
+            let mut pending_consents = self.pending_consents.write().await;
+            match pending_consents.get(&request_id) {
+                Some(pending) if pending.agent_id == agent_id => {
+                    if Utc::now() > pending.expires_at {
+                        pending_consents.remove(&request_id);
+                        return Err("Consent request has expired".to_string());
+                    }
+                    if pending.grant_id.is_none() {
+                        return Err("Consent has not been granted yet".to_string());
+                    }
+                    pending_consents.remove(&request_id)
+                }
+                _ => None,
+            }
+        }
+        .ok_or("Consent request not found")?;
+
+        let grant = match pending.grant_id {
+            Some(grant_id) => self.consent_grants.read().await.get(&grant_id).cloned(),
+            None => None,
+        };
+        let user = self.users.read().await.get(&pending.username).cloned();
+        let refusal = match (&grant, &user) {
+            (None, _) => Some("Consent was revoked"),
+            (Some(grant), _) if grant.is_expired() => Some("Consent has expired"),
+            (_, None) => Some("User not found"),
+            (_, Some(user)) if !user.is_active => Some("Account is deactivated"),
+            _ => None,
+        };
+        if let Some(reason) = refusal {
+            self.record_event(
+                AuthEventKind::AgentTokenIssued,
+                AuthOutcome::Failure,
+                &pending.username,
+                Some(pending.user_id),
+                Some(&format!("to agent {}: {}", agent_id, reason)),
+            )
+            .await;
+            return Err(reason.to_string());
+        }
+        let (grant, user) = (
+            grant.expect("refused above when missing"),
+            user.expect("refused above when missing"),
+        );
+
+        let mut token = AuthToken::new(&user);
+        token.expires_at = grant.expires_at;
+        token.delegation = Some(Delegation {
+            agent_id: grant.agent_id.clone(),
+            grant_id: grant.grant_id,
+            scopes: grant.scopes.clone(),
+        });
+        self.active_tokens
+            .write()
+            .await
+            .insert(token.token_id, token.clone());
+
+        self.record_event(
+            AuthEventKind::AgentTokenIssued,
+            AuthOutcome::Success,
+            &user.username,
+            Some(user.id),
+            Some(&format!(
+                "to agent {} (scopes: {})",
+                grant.agent_id,
+                grant.scopes.join(", ")
+            )),
+        )
+        .await;
+        info!("Agent {} now acts for {}", grant.agent_id, user.username);
+        Ok(token)
+    }
+
+    // Function: list_grants
+    //
+    // Lists the consent grants of the token's user, newest first.
+    //
+    // Arguments:
+    //     token_id: The user's token
+    //
+    // Returns:
+    //     Result with the user's unexpired grants or an error message
+    pub async fn list_grants(&self, token_id: Uuid) -> Result<Vec<ConsentGrant>, String> {
+        let token = self.validate_token(token_id).await?;
+        self.authorize(&token, &UserRole::Guest, "list_grants")
+            .await?;
+
+        let mut grants: Vec<ConsentGrant> = self
+            .consent_grants
+            .read()
+            .await
+            .values()
+            .filter(|grant| grant.user_id == token.user_id && !grant.is_expired())
+            .cloned()
+            .collect();
+        grants.sort_by_key(|grant| std::cmp::Reverse(grant.granted_at));
+        Ok(grants)
+    }
+
+    // Function: revoke_grant
+    //
+    // Withdraws a consent grant and ends every session the agent holds under
+    // it. Users revoke their own grants; administrators may revoke any grant.
+    //
+    // Arguments:
+    //     token_id: The token of the user or administrator
+    //     grant_id: The grant to revoke
+    //
+    // Returns:
+    //     Result indicating whether the grant was revoked
+    pub async fn revoke_grant(&self, token_id: Uuid, grant_id: Uuid) -> Result<(), String> {
+        let token = self.validate_token(token_id).await?;
+        self.authorize(&token, &UserRole::Guest, "revoke_grant")
+            .await?;
+
+        let grant = {
+            let mut consent_grants = self.consent_grants.write().await;
+            let allowed = consent_grants.get(&grant_id).is_some_and(|grant| {
+                grant.user_id == token.user_id || self.check_permission(&token, &UserRole::Admin)
+            });
+            if !allowed {
+                return Err("Grant not found".to_string());
+            }
+            consent_grants
+                .remove(&grant_id)
+                .expect("grant was just found")
+        };
+
+        self.active_tokens.write().await.retain(|_, session| {
+            session
+                .delegation
+                .as_ref()
+                .is_none_or(|delegation| delegation.grant_id != grant_id)
+        });
+        self.pending_consents
+            .write()
+            .await
+            .retain(|_, pending| pending.grant_id != Some(grant_id));
+
+        self.record_event(
+            AuthEventKind::ConsentRevoked,
+            AuthOutcome::Success,
+            &grant.username,
+            Some(grant.user_id),
+            Some(&format!("agent {} by {}", grant.agent_id, token.username)),
+        )
+        .await;
+        info!(
+            "{} revoked the grant of agent {} for {}",
+            token.username, grant.agent_id, grant.username
+        );
+        Ok(())
+    }
+
     // Function: cleanup_expired_tokens
     //
-    // Removes expired tokens from the active token store.
+    // Removes expired tokens from the active token store, along with expired
+    // consent requests and grants.
     // This should be called periodically to prevent memory leaks.
     pub async fn cleanup_expired_tokens(&self) {
         let mut active_tokens = self.active_tokens.write().await;
@@ -409,6 +1939,17 @@ impl AuthService {
         if cleaned_count > 0 {
             info!("Cleaned up {} expired tokens", cleaned_count);
         }
+        drop(active_tokens);
+
+        let now = Utc::now();
+        self.pending_consents
+            .write()
+            .await
+            .retain(|_, pending| pending.expires_at >= now);
+        self.consent_grants
+            .write()
+            .await
+            .retain(|_, grant| !grant.is_expired());
     }
 
     // Function: get_user_info
@@ -546,7 +2087,10 @@ async fn demo_authentication_flow(
 
     // Validate the token
     match auth_service.validate_token(token.token_id).await {
-        Ok(valid_token) => info!("Token is valid for user: {}", valid_token.username),
+        Ok(valid_token) => {
+            info!("Token is valid for user: {}", valid_token.username);
+            info!("Identity for other servers: {:?}", valid_token.identity());
+        }
         Err(e) => error!("Token validation failed: {}", e),
     }
 
@@ -632,6 +2176,562 @@ async fn demo_security_features(
     Ok(())
 }
 
+// Function: demo_audit_trail
+//
+// Demonstrates token refresh, permission denials and querying the audit
+// trail as an administrator.
+async fn demo_audit_trail(auth_service: &AuthService) -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Audit Trail Demo ===");
+
+    let registration = RegistrationRequest {
+        username: "admin".to_string(),
+        email: "admin@example.com".to_string(),
+        password: "AdminPass789!".to_string(),
+    };
+    auth_service
+        .register_user_with_role(registration, UserRole::Admin)
+        .await?;
+
+    let admin_tokens = auth_service
+        .authenticate_device(
+            LoginRequest {
+                username: "admin".to_string(),
+                password: "AdminPass789!".to_string(),
+            },
+            "admin-laptop",
+        )
+        .await?;
+    let admin_token = auth_service
+        .refresh_token(admin_tokens.refresh_token.token_id, "admin-laptop")
+        .await?
+        .access_token;
+
+    // A regular user attempting to read the audit trail is denied (and audited)
+    let user_token = auth_service
+        .authenticate(LoginRequest {
+            username: "john_doe".to_string(),
+            password: "SecurePass123!".to_string(),
+        })
+        .await?;
+    match auth_service
+        .query_auth_events(user_token.token_id, AuthEventFilter::default())
+        .await
+    {
+        Ok(_) => warn!("Regular users should not read the audit trail!"),
+        Err(e) => info!("Correctly denied: {}", e),
+    }
+
+    let failures = auth_service
+        .query_auth_events(
+            admin_token.token_id,
+            AuthEventFilter {
+                outcome: Some(AuthOutcome::Failure),
+                ..Default::default()
+            },
+        )
+        .await?;
+    info!("Found {} failed authentication events", failures.len());
+    for event in &failures {
+        info!(
+            "  {} {:?} user={} detail={:?}",
+            event.timestamp, event.kind, event.username, event.detail
+        );
+    }
+
+    Ok(())
+}
+
+// Function: demo_impersonation
+//
+// Demonstrates a support engineer impersonating a user: the reason is
+// mandatory, the token is limited to its scopes, and the user can see the
+// session in their own session list.
+async fn demo_impersonation(auth_service: &AuthService) -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Impersonation Demo ===");
+
+    let admin_token = auth_service
+        .authenticate(LoginRequest {
+            username: "admin".to_string(),
+            password: "AdminPass789!".to_string(),
+        })
+        .await?;
+
+    // A reason is mandatory
+    match auth_service
+        .impersonate_user(
+            admin_token.token_id,
+            ImpersonationRequest {
+                target_username: "john_doe".to_string(),
+                reason: "".to_string(),
+                scopes: None,
+            },
+        )
+        .await
+    {
+        Ok(_) => warn!("Impersonation without a reason should be rejected!"),
+        Err(e) => info!("Correctly rejected: {}", e),
+    }
+
+    let impersonation = auth_service
+        .impersonate_user(
+            admin_token.token_id,
+            ImpersonationRequest {
+                target_username: "john_doe".to_string(),
+                reason: "Ticket #4821: user cannot see their sessions".to_string(),
+                scopes: None,
+            },
+        )
+        .await?;
+    info!(
+        "Impersonation token for {} expires at {}",
+        impersonation.username, impersonation.expires_at
+    );
+
+    // Actions outside the token's scopes are denied and audited
+    match auth_service
+        .authorize(&impersonation, &UserRole::User, "change_password")
+        .await
+    {
+        Ok(()) => warn!("Impersonation tokens must not change passwords!"),
+        Err(e) => info!("Correctly denied: {}", e),
+    }
+
+    // The user sees the impersonation session alongside their own
+    let user_token = auth_service
+        .authenticate(LoginRequest {
+            username: "john_doe".to_string(),
+            password: "SecurePass123!".to_string(),
+        })
+        .await?;
+    for session in auth_service.list_sessions(user_token.token_id).await? {
+        match &session.impersonated_by {
+            Some(admin) => info!(
+                "  Session {} (impersonated by {}: {:?})",
+                session.token_id, admin, session.impersonation_reason
+            ),
+            None => info!(
+                "  Session {}{}",
+                session.token_id,
+                if session.current { " (current)" } else { "" }
+            ),
+        }
+    }
+
+    auth_service.logout(impersonation.token_id).await?;
+
+    let events = auth_service
+        .query_auth_events(
+            admin_token.token_id,
+            AuthEventFilter {
+                kind: Some(AuthEventKind::ImpersonationStarted),
+                ..Default::default()
+            },
+        )
+        .await?;
+    for event in &events {
+        info!(
+            "  {:?} {:?} user={} detail={:?}",
+            event.kind, event.outcome, event.username, event.detail
+        );
+    }
+
+    Ok(())
+}
+
+// Function: demo_service_tokens
+//
+// Demonstrates machine-to-machine authentication: the gateway of example 19
+// is registered as a service client, exchanges its credentials for a token
+// to call the enterprise server of example 20, and that server verifies the
+// token with nothing but the shared key.
+async fn demo_service_tokens(auth_service: &AuthService) -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Service Token Demo ===");
+
+    let admin_token = auth_service
+        .authenticate(LoginRequest {
+            username: "admin".to_string(),
+            password: "AdminPass789!".to_string(),
+        })
+        .await?;
+    auth_service
+        .register_service_client(
+            admin_token.token_id,
+            ServiceClientRequest {
+                client_id: "gateway".to_string(),
+                client_secret: "Gateway-Secret-42".to_string(),
+                grants: HashMap::from([(
+                    "enterprise_server".to_string(),
+                    vec!["metrics:read".to_string()],
+                )]),
+            },
+        )
+        .await?;
+
+    let token = auth_service
+        .issue_service_token(ServiceTokenRequest {
+            client_id: "gateway".to_string(),
+            client_secret: "Gateway-Secret-42".to_string(),
+            audience: "enterprise_server".to_string(),
+            scopes: None,
+        })
+        .await?;
+
+    // What the enterprise server does with the token it receives
+    let receiver_key = ServiceTokenKey::from_env();
+    let claims = receiver_key.verify(&token, "enterprise_server")?;
+    info!(
+        "enterprise_server accepted {} with scopes {:?} until {}",
+        claims.subject, claims.scopes, claims.expires_at
+    );
+    match receiver_key.verify(&token, "monitoring") {
+        Ok(_) => warn!("A token for another audience must be rejected!"),
+        Err(e) => info!("monitoring correctly rejected it: {}", e),
+    }
+    info!(
+        "Servers share the signing key through {}",
+        SERVICE_TOKEN_KEY_ENV
+    );
+
+    // Ungranted scopes and wrong secrets are refused and audited
+    for (secret, scopes) in [
+        ("Gateway-Secret-42", Some(vec!["jobs:submit".to_string()])),
+        ("wrong-secret", None),
+    ] {
+        match auth_service
+            .issue_service_token(ServiceTokenRequest {
+                client_id: "gateway".to_string(),
+                client_secret: secret.to_string(),
+                audience: "enterprise_server".to_string(),
+                scopes,
+            })
+            .await
+        {
+            Ok(_) => warn!("This service token request should be refused!"),
+            Err(e) => info!("Correctly refused: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+// Function: demo_agent_consent
+//
+// Demonstrates the consent flow: an agent asks for two scopes, the user
+// grants only one of them for a week, the agent's token is limited to it,
+// and revoking the grant ends the agent's session.
+async fn demo_agent_consent(auth_service: &AuthService) -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Agent Consent Demo ===");
+
+    // Agents may only ask for scopes in AGENT_SCOPES
+    match auth_service
+        .request_consent(ConsentRequest {
+            agent_id: "calendar-assistant".to_string(),
+            username: "john_doe".to_string(),
+            scopes: vec!["change_password".to_string()],
+        })
+        .await
+    {
+        Ok(_) => warn!("Agents must not be granted password changes!"),
+        Err(e) => info!("Correctly refused: {}", e),
+    }
+
+    let pending = auth_service
+        .request_consent(ConsentRequest {
+            agent_id: "calendar-assistant".to_string(),
+            username: "john_doe".to_string(),
+            scopes: vec!["get_user_info".to_string(), "list_sessions".to_string()],
+        })
+        .await?;
+
+    // The agent cannot use the request before the user answers it
+    match auth_service
+        .issue_agent_token("calendar-assistant", pending.request_id)
+        .await
+    {
+        Ok(_) => warn!("Unapproved consent requests must not yield tokens!"),
+        Err(e) => info!("Correctly refused: {}", e),
+    }
+
+    // The user grants only part of what was asked, for a week
+    let user_token = auth_service
+        .authenticate(LoginRequest {
+            username: "john_doe".to_string(),
+            password: "SecurePass123!".to_string(),
+        })
+        .await?;
+    let grant = auth_service
+        .approve_consent(
+            user_token.token_id,
+            ConsentApproval {
+                request_id: pending.request_id,
+                scopes: Some(vec!["get_user_info".to_string()]),
+                expires_in_days: Some(7),
+            },
+        )
+        .await?;
+    info!(
+        "Granted {:?} to {} until {}",
+        grant.scopes, grant.agent_id, grant.expires_at
+    );
+
+    let agent_token = auth_service
+        .issue_agent_token("calendar-assistant", pending.request_id)
+        .await?;
+    auth_service
+        .authorize(&agent_token, &UserRole::Guest, "get_user_info")
+        .await?;
+    info!("Agent token may call get_user_info");
+    match auth_service.list_sessions(agent_token.token_id).await {
+        Ok(_) => warn!("The agent was not granted list_sessions!"),
+        Err(e) => info!("Correctly denied: {}", e),
+    }
+
+    // The user sees the grant and the agent's session, and revokes both
+    for grant in auth_service.list_grants(user_token.token_id).await? {
+        info!(
+            "  Grant {} for {}: {:?} until {}",
+            grant.grant_id, grant.agent_id, grant.scopes, grant.expires_at
+        );
+    }
+    for session in auth_service.list_sessions(user_token.token_id).await? {
+        if let Some(agent_id) = &session.agent_id {
+            info!(
+                "  Session {} (agent {}: {:?})",
+                session.token_id, agent_id, session.scopes
+            );
+        }
+    }
+    auth_service
+        .revoke_grant(user_token.token_id, grant.grant_id)
+        .await?;
+    match auth_service.validate_token(agent_token.token_id).await {
+        Ok(_) => warn!("Revoking a grant must end the agent's sessions!"),
+        Err(e) => info!("Agent token revoked with its grant: {}", e),
+    }
+
+    Ok(())
+}
+
+// Function: demo_refresh_token_binding
+//
+// Demonstrates refresh token rotation and the revocation that follows when a
+// stolen refresh token is replayed from another device.
+async fn demo_refresh_token_binding(
+    auth_service: &AuthService,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Refresh Token Binding Demo ===");
+
+    let tokens = auth_service
+        .authenticate_device(
+            LoginRequest {
+                username: "john_doe".to_string(),
+                password: "SecurePass123!".to_string(),
+            },
+            "johns-phone",
+        )
+        .await?;
+
+    // Legitimate rotation from the bound device
+    let rotated = auth_service
+        .refresh_token(tokens.refresh_token.token_id, "johns-phone")
+        .await?;
+    info!("Refresh token rotated on the bound device");
+
+    // An attacker replays the new refresh token from a different device
+    match auth_service
+        .refresh_token(rotated.refresh_token.token_id, "attacker-laptop")
+        .await
+    {
+        Ok(_) => warn!("Token replay from another device should be rejected!"),
+        Err(e) => info!("Replay rejected: {}", e),
+    }
+
+    // The whole family was revoked, including the legitimate access token
+    match auth_service
+        .validate_token(rotated.access_token.token_id)
+        .await
+    {
+        Ok(_) => warn!("Access token should have been revoked with its family!"),
+        Err(e) => info!("Family access token revoked: {}", e),
+    }
+
+    Ok(())
+}
+
+// Trait implementation: StatefulServer
+//
+// Snapshots include password hashes and live tokens, so dump files must be
+// protected like the credential store itself.
+impl StatefulServer for AuthService {
+    const STATE_NAME: &'static str = "auth_service";
+
+    async fn snapshot(&self) -> Result<Value, StateError> {
+        let state = AuthServiceState {
+            users: self.users.read().await.values().cloned().collect(),
+            active_tokens: self.active_tokens.read().await.values().cloned().collect(),
+            refresh_tokens: self.refresh_tokens.read().await.values().cloned().collect(),
+            audit_log: self.audit_log.read().await.clone(),
+            service_clients: self
+                .service_clients
+                .read()
+                .await
+                .values()
+                .cloned()
+                .collect(),
+            pending_consents: self
+                .pending_consents
+                .read()
+                .await
+                .values()
+                .cloned()
+                .collect(),
+            consent_grants: self.consent_grants.read().await.values().cloned().collect(),
+        };
+        Ok(serde_json::to_value(state)?)
+    }
+
+    async fn restore(&mut self, state: Value) -> Result<(), StateError> {
+        let state: AuthServiceState = serde_json::from_value(state)?;
+
+        *self.users.write().await = state
+            .users
+            .into_iter()
+            .map(|user| (user.username.clone(), user))
+            .collect();
+        *self.active_tokens.write().await = state
+            .active_tokens
+            .into_iter()
+            .map(|token| (token.token_id, token))
+            .collect();
+        *self.refresh_tokens.write().await = state
+            .refresh_tokens
+            .into_iter()
+            .map(|token| (token.token_id, token))
+            .collect();
+        *self.audit_log.write().await = state.audit_log;
+        *self.service_clients.write().await = state
+            .service_clients
+            .into_iter()
+            .map(|client| (client.client_id.clone(), client))
+            .collect();
+        *self.pending_consents.write().await = state
+            .pending_consents
+            .into_iter()
+            .map(|pending| (pending.request_id, pending))
+            .collect();
+        *self.consent_grants.write().await = state
+            .consent_grants
+            .into_iter()
+            .map(|grant| (grant.grant_id, grant))
+            .collect();
+        Ok(())
+    }
+}
+
+// Function: demo_restored_state
+//
+// Summarizes a service restored from a snapshot. The regular demos register
+// fixed usernames, so they are skipped when starting from restored state.
+async fn demo_restored_state(auth_service: &AuthService) {
+    info!("=== Restored State ===");
+    info!("Users: {}", auth_service.users.read().await.len());
+    info!(
+        "Active access tokens: {}",
+        auth_service.active_tokens.read().await.len()
+    );
+    info!(
+        "Refresh tokens: {}",
+        auth_service.refresh_tokens.read().await.len()
+    );
+    info!(
+        "Audit events: {}",
+        auth_service.audit_log.read().await.len()
+    );
+    info!(
+        "Service clients: {}",
+        auth_service.service_clients.read().await.len()
+    );
+    info!(
+        "Consent grants: {}",
+        auth_service.consent_grants.read().await.len()
+    );
+}
+
+// Function: subscribe_to_security_alerts
+//
+// Subscribes a user to security alerts by email, falling back to push
+// notifications when email cannot be delivered.
+//
+// Arguments:
+//     notifications: The notification service sending the alerts
+//     username: The user, as named in their alerts
+//     email: Where to email the alerts
+//
+// Returns:
+//     Result indicating success or failure
+async fn subscribe_to_security_alerts(
+    notifications: &NotificationService,
+    username: &str,
+    email: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let subscription: NotificationSubscription = serde_json::from_value(serde_json::json!({
+        "user_id": username,
+        "channel": "Email",
+        "endpoint": email,
+        "is_active": true,
+        "preferences": {},
+        "failover_channels": ["PushNotification"]
+    }))?;
+    notifications
+        .subscribe_user(username.to_string(), subscription)
+        .await?;
+    Ok(())
+}
+
+// Function: forward_security_alerts
+//
+// Sends each security alert through the notification service until the
+// alert channel closes.
+//
+// Arguments:
+//     receiver: The alert channel given to AuthService::with_alert_sink
+//     notifications: The notification service sending the alerts
+//
+// Returns:
+//     The number of notifications queued for the alerts
+async fn forward_security_alerts(
+    mut receiver: mpsc::UnboundedReceiver<SecurityAlert>,
+    notifications: Arc<NotificationService>,
+) -> usize {
+    let mut queued = 0;
+    while let Some(alert) = receiver.recv().await {
+        let arguments = serde_json::json!({
+            "user_id": alert.recipient_id,
+            "template_name": alert.template_name,
+            "variables": alert.variables,
+            "priority": alert.priority
+        });
+        match notifications
+            .call_tool("send_notification", arguments)
+            .await
+        {
+            Ok(result) => {
+                let sent = result["notifications_queued"].as_u64().unwrap_or(0) as usize;
+                info!(
+                    "🚨 Security alert for {} queued as {} notifications",
+                    alert.recipient_id, sent
+                );
+                queued += sent;
+            }
+            Err(e) => warn!(
+                "Failed to send security alert to {}: {}",
+                alert.recipient_id, e
+            ),
+        }
+    }
+    queued
+}
+
 // Function: main
 //
 // This is the entry point of the program.
@@ -640,24 +2740,303 @@ async fn demo_security_features(
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize the tracing subscriber for logging
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    let _telemetry = mcp_rust_examples::logging::init("info");
 
     info!("Starting Authentication Service Example");
 
-    // Create a new authentication service
-    let auth_service = AuthService::new();
+    // Forward security alerts to the notification service (example 14)
+    let notifications = Arc::new(NotificationService::new());
+    notifications.create_security_alert_template().await;
+    subscribe_to_security_alerts(&notifications, "john_doe", "john@example.com").await?;
+    let (alert_sender, alert_receiver) = mpsc::unbounded_channel::<SecurityAlert>();
+    let alert_forwarder = tokio::spawn(forward_security_alerts(
+        alert_receiver,
+        notifications.clone(),
+    ));
+
+    // Create a new authentication service, optionally from a snapshot
+    let state_command = StateCommand::from_env()?;
+    let mut auth_service = AuthService::new().with_alert_sink(alert_sender);
+    state_command.restore(&mut auth_service).await?;
+
+    if state_command.restore_from.is_some() {
+        demo_restored_state(&auth_service).await;
+    } else {
+        // Demonstrate the complete authentication flow
+        demo_authentication_flow(&auth_service).await?;
+
+        // Demonstrate security features
+        demo_security_features(&auth_service).await?;
+
+        // Demonstrate device-bound refresh tokens
+        demo_refresh_token_binding(&auth_service).await?;
 
-    // Demonstrate the complete authentication flow
-    demo_authentication_flow(&auth_service).await?;
+        // Demonstrate the audit trail
+        demo_audit_trail(&auth_service).await?;
 
-    // Demonstrate security features
-    demo_security_features(&auth_service).await?;
+        // Demonstrate audited impersonation for support workflows
+        demo_impersonation(&auth_service).await?;
+
+        // Demonstrate signed tokens for calls between servers
+        demo_service_tokens(&auth_service).await?;
+
+        // Demonstrate consent for third-party agents
+        demo_agent_consent(&auth_service).await?;
+    }
 
     // Demonstrate token cleanup
     info!("=== Token Cleanup Demo ===");
     auth_service.cleanup_expired_tokens().await;
 
+    state_command.dump(&auth_service).await?;
+
+    // Dropping the service closes the alert channel and stops the forwarder;
+    // then wait for the queued alerts to be delivered
+    drop(auth_service);
+    let queued = alert_forwarder.await?;
+    let deadline = tokio::time::Instant::now() + tokio::time::Duration::from_secs(5);
+    while notifications.get_delivery_status(None).await.len() < queued
+        && tokio::time::Instant::now() < deadline
+    {
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    }
+    for result in notifications.get_delivery_status(None).await {
+        info!(
+            "Security alert delivery: {}",
+            serde_json::to_string(&result)?
+        );
+    }
+
     info!("Authentication Service Example completed successfully");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWORD: &str = "SecurePass123!";
+
+    // Creates a service with an administrator and a regular user
+    async fn service() -> AuthService {
+        let service = AuthService::new();
+        for (username, role) in [("admin", UserRole::Admin), ("alice", UserRole::User)] {
+            let request = RegistrationRequest {
+                username: username.to_string(),
+                email: format!("{}@example.com", username),
+                password: PASSWORD.to_string(),
+            };
+            service
+                .register_user_with_role(request, role)
+                .await
+                .unwrap();
+        }
+        service
+    }
+
+    fn login(username: &str) -> LoginRequest {
+        LoginRequest {
+            username: username.to_string(),
+            password: PASSWORD.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_from_another_device_revokes_the_family() {
+        let service = service().await;
+        let tokens = service
+            .authenticate_device(login("alice"), "alices-phone")
+            .await
+            .unwrap();
+
+        let result = service
+            .refresh_token(tokens.refresh_token.token_id, "attacker-laptop")
+            .await;
+        assert!(result.is_err());
+        // Neither the thief nor the rightful device can go on with the family
+        assert!(service
+            .validate_token(tokens.access_token.token_id)
+            .await
+            .is_err());
+        let result = service
+            .refresh_token(tokens.refresh_token.token_id, "alices-phone")
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_token_theft_alert_is_delivered_by_the_notification_service() {
+        let notifications = Arc::new(NotificationService::new());
+        notifications.create_security_alert_template().await;
+        subscribe_to_security_alerts(&notifications, "alice", "alice@example.com")
+            .await
+            .unwrap();
+        let (alert_sender, alert_receiver) = mpsc::unbounded_channel();
+        let forwarder = tokio::spawn(forward_security_alerts(
+            alert_receiver,
+            notifications.clone(),
+        ));
+
+        let service = service().await.with_alert_sink(alert_sender);
+        let tokens = service
+            .authenticate_device(login("alice"), "alices-phone")
+            .await
+            .unwrap();
+        let result = service
+            .refresh_token(tokens.refresh_token.token_id, "attacker-laptop")
+            .await;
+        assert!(result.is_err());
+
+        drop(service);
+        assert_eq!(forwarder.await.unwrap(), 1);
+        let results = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                let results = notifications.get_delivery_status(None).await;
+                if !results.is_empty() {
+                    return results;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the alert was not delivered");
+
+        // Email fails now and then, in which case push takes over
+        let result = serde_json::to_value(&results[0]).unwrap();
+        assert_eq!(result["success"], true);
+        assert!(matches!(
+            result["delivered_via"].as_str(),
+            Some("Email" | "PushNotification")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_replayed_refresh_token_revokes_the_family() {
+        let service = service().await;
+        let tokens = service
+            .authenticate_device(login("alice"), "alices-phone")
+            .await
+            .unwrap();
+        let rotated = service
+            .refresh_token(tokens.refresh_token.token_id, "alices-phone")
+            .await
+            .unwrap();
+
+        // The token was already exchanged, so presenting it again is theft
+        let result = service
+            .refresh_token(tokens.refresh_token.token_id, "alices-phone")
+            .await;
+        assert!(result.is_err());
+        assert!(service
+            .validate_token(rotated.access_token.token_id)
+            .await
+            .is_err());
+        let result = service
+            .refresh_token(rotated.refresh_token.token_id, "alices-phone")
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_impersonation_tokens_carry_only_limited_scopes() {
+        let service = service().await;
+        let admin = service.authenticate(login("admin")).await.unwrap();
+        let request = |scopes: Option<Vec<&str>>| ImpersonationRequest {
+            target_username: "alice".to_string(),
+            reason: "Investigating ticket #4521".to_string(),
+            scopes: scopes.map(|scopes| scopes.into_iter().map(String::from).collect()),
+        };
+
+        let token = service
+            .impersonate_user(admin.token_id, request(None))
+            .await
+            .unwrap();
+        assert_eq!(token.username, "alice");
+        assert_eq!(token.scopes().unwrap(), IMPERSONATION_SCOPES);
+        assert!(service
+            .authorize(&token, &UserRole::Guest, "get_user_info")
+            .await
+            .is_ok());
+        assert!(service
+            .authorize(&token, &UserRole::Guest, "change_password")
+            .await
+            .is_err());
+
+        let result = service
+            .impersonate_user(admin.token_id, request(Some(vec!["change_password"])))
+            .await;
+        assert!(result.is_err());
+        // Only administrators impersonate
+        let alice = service.authenticate(login("alice")).await.unwrap();
+        let result = service
+            .impersonate_user(alice.token_id, request(None))
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_consent_grants_expire_and_can_be_revoked() {
+        let service = service().await;
+        let alice = service.authenticate(login("alice")).await.unwrap();
+        let approve = |request_id: Uuid| ConsentApproval {
+            request_id,
+            scopes: Some(vec!["get_user_info".to_string()]),
+            expires_in_days: Some(7),
+        };
+        let ask = || ConsentRequest {
+            agent_id: "calendar-assistant".to_string(),
+            username: "alice".to_string(),
+            scopes: vec!["get_user_info".to_string(), "list_sessions".to_string()],
+        };
+
+        // A grant that has run out yields no token and is no longer listed
+        let pending = service.request_consent(ask()).await.unwrap();
+        let grant = service
+            .approve_consent(alice.token_id, approve(pending.request_id))
+            .await
+            .unwrap();
+        assert_eq!(grant.scopes, ["get_user_info"]);
+        service
+            .consent_grants
+            .write()
+            .await
+            .get_mut(&grant.grant_id)
+            .unwrap()
+            .expires_at = Utc::now() - Duration::seconds(1);
+        let result = service
+            .issue_agent_token("calendar-assistant", pending.request_id)
+            .await;
+        assert_eq!(result.unwrap_err(), "Consent has expired");
+        assert!(service
+            .list_grants(alice.token_id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Revoking a grant ends the agent's session under it
+        let pending = service.request_consent(ask()).await.unwrap();
+        let grant = service
+            .approve_consent(alice.token_id, approve(pending.request_id))
+            .await
+            .unwrap();
+        let agent = service
+            .issue_agent_token("calendar-assistant", pending.request_id)
+            .await
+            .unwrap();
+        assert!(service
+            .authorize(&agent, &UserRole::Guest, "list_sessions")
+            .await
+            .is_err());
+        service
+            .revoke_grant(alice.token_id, grant.grant_id)
+            .await
+            .unwrap();
+        assert!(service.validate_token(agent.token_id).await.is_err());
+        assert!(service
+            .list_grants(alice.token_id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
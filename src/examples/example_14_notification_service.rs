@@ -3,15 +3,53 @@
 // This example demonstrates how to build a comprehensive notification service
 // that supports multiple delivery channels (email, SMS, webhooks, push notifications),
 // subscription management, and reliable delivery with retry mechanisms.
+// A/B experiments split recipients between template variants and report
+// delivery results per variant. Bulk sends render one template for up to
+// MAX_BULK_RECIPIENTS recipients, in chunks queued a few at a time, and
+// report which recipients could not be reached. Templates can carry
+// translations; each recipient gets the one closest to their language. With MCP_CHECKPOINT_PATH
+// set, queued notifications are kept in an outbox until they are delivered
+// or given up on, and a restarted service sends whatever was left in it.
+// Delivery providers limit how much they accept, such as 30 SMS a minute or
+// 1000 emails an hour. Each channel has a token bucket sized to its provider's
+// quota (MCP_CHANNEL_QUOTAS overrides the defaults); a notification that finds
+// its channel's quota used up is deferred, held back by the delivery worker's
+// scheduler until the bucket refills, and get_channel_quotas reports how much
+// of each quota is used and how many notifications are waiting.
+// set_channel_outage marks a provider as down, failing its deliveries over
+// to the next channel in the subscription's chain.
 
 use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::stream::{self, StreamExt};
+use mcp_rust_examples::checkpoint::{CheckpointError, CheckpointStore};
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::i18n;
+use mcp_rust_examples::logging::ToolCallLog;
+use mcp_rust_examples::protocol::{Tool, ToolAnnotations};
+use mcp_rust_examples::rate_limit::{BucketConfig, RateLimitConfig, RateLimiter};
+use mcp_rust_examples::state::{StateCommand, StateError, StatefulServer};
+use mcp_rust_examples::tools::ToolServer;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use tokio::sync::{mpsc, RwLock};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+// Bulk sends: recipients per call, per chunk, and chunks queued at once
+const MAX_BULK_RECIPIENTS: usize = 1000;
+const BULK_CHUNK_SIZE: usize = 50;
+const BULK_CONCURRENCY: usize = 4;
+// Notifications not yet delivered are checkpointed under their id here
+const OUTBOX_NAMESPACE: &str = "notification_outbox";
+// JSON object of channel quotas, e.g. {"Sms": {"limit": 30, "window_seconds": 60}}
+const CHANNEL_QUOTAS_ENV: &str = "MCP_CHANNEL_QUOTAS";
+// The rate limiter keys buckets by client and name; all channels share this client
+const QUOTA_BUCKET_OWNER: &str = "providers";
+
 // Enum: NotificationChannel
 //
 // This enum defines the different channels through which notifications can be sent.
@@ -45,6 +83,54 @@ pub struct NotificationTemplate {
     subject_template: String,
     body_template: String,
     supported_channels: Vec<NotificationChannel>,
+    #[serde(default = "default_template_locale")]
+    locale: String, // The language of subject_template and body_template
+    #[serde(default)]
+    translations: HashMap<String, TemplateTranslation>, // locale -> translation
+}
+
+// Templates are written in English unless created with another locale
+const DEFAULT_TEMPLATE_LOCALE: &str = "en";
+
+fn default_template_locale() -> String {
+    DEFAULT_TEMPLATE_LOCALE.to_string()
+}
+
+// Struct: TemplateTranslation
+//
+// This struct represents a template's subject and body in another language.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateTranslation {
+    subject_template: String,
+    body_template: String,
+}
+
+impl NotificationTemplate {
+    // Function: localized
+    //
+    // Picks the subject and body for a recipient: the translation that best
+    // matches their language (es-MX falls back to es), or the template's own
+    // text if none does.
+    //
+    // Arguments:
+    //     locale: The recipient's preferred language, if known
+    //
+    // Returns:
+    //     The subject and body templates to render
+    pub fn localized(&self, locale: Option<&str>) -> (&str, &str) {
+        let available: Vec<&str> = std::iter::once(self.locale.as_str())
+            .chain(self.translations.keys().map(String::as_str))
+            .collect();
+        let chosen = i18n::negotiate(locale.as_slice(), &available, &self.locale);
+
+        match chosen
+            .first()
+            .and_then(|locale| self.translations.get(locale))
+        {
+            Some(translation) => (&translation.subject_template, &translation.body_template),
+            None => (&self.subject_template, &self.body_template),
+        }
+    }
 }
 
 // Struct: Notification
@@ -63,6 +149,9 @@ pub struct Notification {
     scheduled_for: Option<DateTime<Utc>>,
     retry_count: u32,
     max_retries: u32,
+    failover_channels: Vec<NotificationChannel>, // Tried in order if `channel` fails
+    #[serde(default)]
+    experiment: Option<ExperimentAssignment>, // Set when sent through an experiment
 }
 
 // Struct: NotificationSubscription
@@ -75,6 +164,10 @@ pub struct NotificationSubscription {
     endpoint: String, // email address, phone number, webhook URL, etc.
     is_active: bool,
     preferences: HashMap<String, String>,
+    #[serde(default)]
+    failover_channels: Vec<NotificationChannel>, // e.g. Push -> SMS -> Email
+    #[serde(default)]
+    locale: Option<String>, // Preferred language, e.g. "es-MX"
 }
 
 // Struct: DeliveryResult
@@ -87,18 +180,451 @@ pub struct DeliveryResult {
     attempt_count: u32,
     delivered_at: DateTime<Utc>,
     error_message: Option<String>,
+    delivered_via: Option<NotificationChannel>,
+    channel_path: Vec<NotificationChannel>, // Channels attempted, in order
+    #[serde(default)]
+    experiment: Option<ExperimentAssignment>,
+}
+
+// Struct: ChannelQuota
+//
+// This struct describes a delivery provider's limit: at most `limit` sends
+// every `window_seconds`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ChannelQuota {
+    limit: u32,
+    window_seconds: u64,
+}
+
+impl ChannelQuota {
+    // Function: bucket
+    //
+    // Sizes a token bucket to the quota: full at `limit` sends, refilling
+    // evenly over the window.
+    //
+    // Returns:
+    //     The bucket configuration for the rate limiter
+    fn bucket(&self) -> BucketConfig {
+        BucketConfig {
+            capacity: self.limit,
+            refill_per_second: self.limit as f64 / self.window_seconds.max(1) as f64,
+        }
+    }
+}
+
+// Function: default_channel_quotas
+//
+// The quotas of the simulated SMS and email providers. Other channels are
+// not limited.
+//
+// Returns:
+//     The quota of each limited channel
+fn default_channel_quotas() -> HashMap<NotificationChannel, ChannelQuota> {
+    HashMap::from([
+        (
+            NotificationChannel::Sms,
+            ChannelQuota {
+                limit: 30,
+                window_seconds: 60,
+            },
+        ),
+        (
+            NotificationChannel::Email,
+            ChannelQuota {
+                limit: 1000,
+                window_seconds: 3600,
+            },
+        ),
+    ])
+}
+
+// Struct: QuotaUsage
+//
+// This struct reports how much of one channel's quota is used, as returned
+// by the get_channel_quotas tool.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaUsage {
+    channel: NotificationChannel,
+    limit: u32,
+    window_seconds: u64,
+    used: u32,
+    remaining: u32,
+    reset_seconds: u64,
+    deferred: usize, // Notifications waiting for the quota to refill
+}
+
+// Struct: ChannelQuotas
+//
+// This struct enforces the channel quotas with one token bucket per limited
+// channel. Every delivery attempt on a limited channel takes a token.
+struct ChannelQuotas {
+    quotas: Mutex<HashMap<NotificationChannel, ChannelQuota>>,
+    limiter: RateLimiter,
+    deferred: Mutex<HashMap<NotificationChannel, usize>>,
+}
+
+impl ChannelQuotas {
+    // Function: from_env
+    //
+    // Loads the quotas from MCP_CHANNEL_QUOTAS, falling back to the defaults
+    // when it is unset or cannot be parsed.
+    //
+    // Returns:
+    //     The quotas, with full buckets
+    fn from_env() -> Self {
+        let quotas = match std::env::var(CHANNEL_QUOTAS_ENV) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(
+                    "Ignoring {}, using the default quotas: {}",
+                    CHANNEL_QUOTAS_ENV, e
+                );
+                default_channel_quotas()
+            }),
+            Err(_) => default_channel_quotas(),
+        };
+        let channel_quotas = Self {
+            quotas: Mutex::new(HashMap::new()),
+            limiter: RateLimiter::default(),
+            deferred: Mutex::new(HashMap::new()),
+        };
+        channel_quotas.set(quotas);
+        channel_quotas
+    }
+
+    // Function: set
+    //
+    // Replaces the quotas. Buckets keep their tokens, up to the new limits.
+    //
+    // Arguments:
+    //     quotas: The quota of each limited channel
+    fn set(&self, quotas: HashMap<NotificationChannel, ChannelQuota>) {
+        let config = RateLimitConfig {
+            default: BucketConfig::default(),
+            tools: quotas
+                .iter()
+                .map(|(channel, quota)| (format!("{:?}", channel), quota.bucket()))
+                .collect(),
+        };
+        self.limiter.set_config(Some(config));
+        *self.quotas.lock().unwrap() = quotas;
+    }
+
+    // Function: take
+    //
+    // Takes a token for one delivery attempt on a channel.
+    //
+    // Arguments:
+    //     channel: The channel about to be used
+    //
+    // Returns:
+    //     None if the attempt may go ahead, or how long until the quota has
+    //     room again
+    fn take(&self, channel: &NotificationChannel) -> Option<std::time::Duration> {
+        if !self.quotas.lock().unwrap().contains_key(channel) {
+            return None;
+        }
+        self.limiter
+            .check(QUOTA_BUCKET_OWNER, &format!("{:?}", channel))
+            .filter(|decision| !decision.allowed)
+            .map(|decision| decision.retry_after)
+    }
+
+    // Function: track_deferred
+    //
+    // Counts a notification that starts or stops waiting for a channel.
+    //
+    // Arguments:
+    //     channel: The channel the notification waits for
+    //     waiting: Whether it starts (true) or stops (false) waiting
+    fn track_deferred(&self, channel: &NotificationChannel, waiting: bool) {
+        let mut deferred = self.deferred.lock().unwrap();
+        let count = deferred.entry(channel.clone()).or_default();
+        *count = if waiting {
+            *count + 1
+        } else {
+            count.saturating_sub(1)
+        };
+    }
+
+    // Function: usage
+    //
+    // Reports the consumption of every limited channel's quota.
+    //
+    // Returns:
+    //     The usage of each quota, ordered by channel name
+    fn usage(&self) -> Vec<QuotaUsage> {
+        let deferred = self.deferred.lock().unwrap().clone();
+        let mut usage: Vec<QuotaUsage> = self
+            .quotas
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(channel, quota)| {
+                let bucket = self
+                    .limiter
+                    .peek(QUOTA_BUCKET_OWNER, &format!("{:?}", channel))?;
+                Some(QuotaUsage {
+                    channel: channel.clone(),
+                    limit: quota.limit,
+                    window_seconds: quota.window_seconds,
+                    used: quota.limit.saturating_sub(bucket.remaining),
+                    remaining: bucket.remaining,
+                    reset_seconds: bucket.reset_after.as_secs_f64().ceil() as u64,
+                    deferred: deferred.get(channel).copied().unwrap_or(0),
+                })
+            })
+            .collect();
+        usage.sort_by_key(|usage| format!("{:?}", usage.channel));
+        usage
+    }
+}
+
+// Struct: ExperimentVariant
+//
+// This struct represents one arm of an A/B experiment: a template and its
+// share of recipients relative to the other variants' weights.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentVariant {
+    name: String,
+    template_name: String,
+    #[serde(default = "default_variant_weight")]
+    weight: u32,
+}
+
+fn default_variant_weight() -> u32 {
+    1
+}
+
+// Struct: Experiment
+//
+// This struct represents an A/B experiment over notification templates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Experiment {
+    name: String,
+    variants: Vec<ExperimentVariant>,
+    created_at: DateTime<Utc>,
+}
+
+impl Experiment {
+    // Function: assign
+    //
+    // Picks the variant for a recipient. The choice depends only on the
+    // experiment name and recipient ID, so a recipient always gets the same
+    // variant and assignments survive restarts.
+    //
+    // Arguments:
+    //     recipient_id: The user being notified
+    //
+    // Returns:
+    //     The variant for this recipient
+    pub fn assign(&self, recipient_id: &str) -> &ExperimentVariant {
+        let digest = Sha256::digest(format!("{}:{}", self.name, recipient_id).as_bytes());
+        let hash = u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"));
+
+        let total_weight: u64 = self.variants.iter().map(|v| v.weight as u64).sum();
+        let mut bucket = hash % total_weight;
+        for variant in &self.variants {
+            if bucket < variant.weight as u64 {
+                return variant;
+            }
+            bucket -= variant.weight as u64;
+        }
+        unreachable!("bucket is below the total weight")
+    }
+}
+
+// Struct: ExperimentAssignment
+//
+// This struct records which experiment variant a notification was sent with.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExperimentAssignment {
+    experiment: String,
+    variant: String,
 }
 
+// Struct: VariantStats
+//
+// This struct summarizes deliveries for one experiment variant.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct VariantStats {
+    variant: String,
+    template_name: String,
+    deliveries: usize,
+    failures: usize,
+    success_rate: Option<f64>,
+}
+
+// Struct: InboxMessage
+//
+// This struct represents a notification delivered to a user's in-app inbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboxMessage {
+    id: Uuid,
+    notification_id: Uuid,
+    subject: String,
+    body: String,
+    priority: NotificationPriority,
+    received_at: DateTime<Utc>,
+    read: bool,
+}
+
+// Struct: Resource
+//
+// This struct describes an MCP resource (see example 05).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Resource {
+    uri: String,
+    name: Option<String>,
+    description: Option<String>,
+    mime_type: Option<String>,
+}
+
+// Struct: InboxMessageRequest
+//
+// This struct represents the arguments of the inbox tools that act on one message.
+#[derive(Debug, Deserialize)]
+pub struct InboxMessageRequest {
+    user_id: String,
+    message_id: Uuid,
+}
+
+// Struct: SendNotificationRequest
+//
+// This struct represents the arguments of the send_notification tool.
+#[derive(Debug, Deserialize)]
+pub struct SendNotificationRequest {
+    user_id: String,
+    template_name: String,
+    #[serde(default)]
+    variables: HashMap<String, String>,
+    priority: Option<NotificationPriority>,
+}
+
+// Struct: BulkRecipient
+//
+// This struct represents one recipient of a bulk send. Its variables
+// override the shared ones with the same name.
+#[derive(Debug, Deserialize)]
+pub struct BulkRecipient {
+    user_id: String,
+    #[serde(default)]
+    variables: HashMap<String, String>,
+    priority: Option<NotificationPriority>,
+}
+
+// Struct: SendBulkRequest
+//
+// This struct represents the arguments of the send_bulk_notifications tool.
+#[derive(Debug, Deserialize)]
+pub struct SendBulkRequest {
+    template_name: String,
+    #[serde(default)]
+    variables: HashMap<String, String>,
+    priority: Option<NotificationPriority>,
+    recipients: Vec<BulkRecipient>,
+}
+
+// Struct: BulkFailure
+//
+// This struct records a bulk recipient that could not be sent to, and why.
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkFailure {
+    user_id: String,
+    error: String,
+}
+
+// Struct: BulkSendReport
+//
+// This struct summarizes a bulk send. Recipients without an active
+// subscription the template supports are skipped rather than failed.
+#[derive(Debug, Default, Serialize)]
+pub struct BulkSendReport {
+    template_name: String,
+    recipients: usize,
+    succeeded: usize,
+    skipped: usize,
+    failed: usize,
+    notifications_queued: usize,
+    chunks: usize,
+    failures: Vec<BulkFailure>,
+}
+
+// Struct: SendExperimentRequest
+//
+// This struct represents the arguments of the send_experiment_notification tool.
+#[derive(Debug, Deserialize)]
+pub struct SendExperimentRequest {
+    user_id: String,
+    experiment: String,
+    #[serde(default)]
+    variables: HashMap<String, String>,
+    priority: Option<NotificationPriority>,
+}
+
+// Struct: CreateExperimentRequest
+//
+// This struct represents the arguments of the create_experiment tool.
+#[derive(Debug, Deserialize)]
+pub struct CreateExperimentRequest {
+    name: String,
+    variants: Vec<ExperimentVariant>,
+}
+
+// Struct: ExperimentStatsRequest
+//
+// This struct represents the arguments of the get_experiment_stats tool.
+#[derive(Debug, Deserialize)]
+pub struct ExperimentStatsRequest {
+    experiment: String,
+}
+
+// Struct: ListInboxRequest
+//
+// This struct represents the arguments of the list_inbox_messages tool.
+// Messages are returned a page at a time, starting `offset` messages in.
+#[derive(Debug, Deserialize)]
+pub struct ListInboxRequest {
+    user_id: String,
+    #[serde(default)]
+    unread_only: bool,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+type Inboxes = Arc<RwLock<HashMap<String, Vec<InboxMessage>>>>; // user_id -> messages
+type Outages = Arc<Mutex<HashSet<NotificationChannel>>>; // Channels whose provider is down
+
 // Struct: NotificationService
 //
 // This struct implements the main notification service functionality.
 pub struct NotificationService {
     templates: Arc<RwLock<HashMap<String, NotificationTemplate>>>,
     subscriptions: Arc<RwLock<HashMap<String, Vec<NotificationSubscription>>>>,
-    #[allow(dead_code)]
     pending_notifications: Arc<RwLock<Vec<Notification>>>,
     delivery_results: Arc<RwLock<Vec<DeliveryResult>>>,
+    inboxes: Inboxes,
+    experiments: Arc<RwLock<HashMap<String, Experiment>>>,
     notification_sender: mpsc::UnboundedSender<Notification>,
+    outbox: Option<CheckpointStore>,
+    quotas: Arc<ChannelQuotas>,
+    outages: Outages,
+}
+
+// Struct: NotificationServiceState
+//
+// The durable state of the notification service, captured by snapshots.
+// Notifications already handed to the delivery worker are in flight and are
+// not part of a snapshot.
+#[derive(Debug, Serialize, Deserialize)]
+struct NotificationServiceState {
+    templates: Vec<NotificationTemplate>,
+    subscriptions: HashMap<String, Vec<NotificationSubscription>>,
+    pending_notifications: Vec<Notification>,
+    delivery_results: Vec<DeliveryResult>,
+    inboxes: HashMap<String, Vec<InboxMessage>>,
+    #[serde(default)]
+    experiments: Vec<Experiment>,
 }
 
 impl Default for NotificationService {
@@ -115,6 +641,48 @@ impl NotificationService {
     // Returns:
     //     A new NotificationService instance
     pub fn new() -> Self {
+        Self::start(None)
+    }
+
+    // Function: with_checkpoints
+    //
+    // Creates a notification service that keeps queued notifications in an
+    // outbox in `store` until they are delivered, and queues the ones left
+    // there by an earlier run again.
+    //
+    // Arguments:
+    //     store: Where the outbox is checkpointed
+    //
+    // Returns:
+    //     A new NotificationService instance, or the error reading the outbox
+    pub async fn with_checkpoints(store: CheckpointStore) -> Result<Self, CheckpointError> {
+        let outstanding: Vec<(String, Notification)> = store.list(OUTBOX_NAMESPACE).await?;
+        let service = Self::start(Some(store));
+
+        if !outstanding.is_empty() {
+            info!(
+                "Resending {} notifications left in the outbox",
+                outstanding.len()
+            );
+        }
+        for (_, notification) in outstanding {
+            if let Err(e) = service.notification_sender.send(notification) {
+                error!("Failed to queue notification: {}", e);
+            }
+        }
+        Ok(service)
+    }
+
+    // Function: start
+    //
+    // Creates the service and starts the background worker.
+    //
+    // Arguments:
+    //     outbox: Where undelivered notifications are checkpointed, if anywhere
+    //
+    // Returns:
+    //     A new NotificationService instance
+    fn start(outbox: Option<CheckpointStore>) -> Self {
         let (sender, receiver) = mpsc::unbounded_channel();
 
         let service = Self {
@@ -122,11 +690,26 @@ impl NotificationService {
             subscriptions: Arc::new(RwLock::new(HashMap::new())),
             pending_notifications: Arc::new(RwLock::new(Vec::new())),
             delivery_results: Arc::new(RwLock::new(Vec::new())),
+            inboxes: Arc::new(RwLock::new(HashMap::new())),
+            experiments: Arc::new(RwLock::new(HashMap::new())),
             notification_sender: sender,
+            outbox,
+            quotas: Arc::new(ChannelQuotas::from_env()),
+            outages: Arc::new(Mutex::new(HashSet::new())),
         };
 
-        // Start the background delivery worker
-        let delivery_worker = DeliveryWorker::new(receiver, service.delivery_results.clone());
+        // Start the background delivery worker. It schedules deferred
+        // notifications through a weak sender, so it still stops once the
+        // service is dropped.
+        let delivery_worker = DeliveryWorker::new(
+            receiver,
+            service.notification_sender.downgrade(),
+            service.delivery_results.clone(),
+            service.inboxes.clone(),
+            service.outbox.clone(),
+            service.quotas.clone(),
+            service.outages.clone(),
+        );
 
         tokio::spawn(async move {
             delivery_worker.run().await;
@@ -135,6 +718,35 @@ impl NotificationService {
         service
     }
 
+    // Function: set_channel_quotas
+    //
+    // Replaces the provider quotas, for example after a provider raises its
+    // limits. Channels left out are not limited.
+    //
+    // Arguments:
+    //     quotas: The quota of each limited channel
+    pub fn set_channel_quotas(&self, quotas: HashMap<NotificationChannel, ChannelQuota>) {
+        self.quotas.set(quotas);
+    }
+
+    // Function: set_channel_outage
+    //
+    // Marks a channel's provider as down or back up. While it is down, every
+    // delivery attempt on the channel fails, so notifications fail over to
+    // the next channel in their chain.
+    //
+    // Arguments:
+    //     channel: The channel whose provider changed
+    //     down: Whether the provider is down
+    pub fn set_channel_outage(&self, channel: NotificationChannel, down: bool) {
+        let mut outages = self.outages.lock().unwrap();
+        if down {
+            outages.insert(channel);
+        } else {
+            outages.remove(&channel);
+        }
+    }
+
     // Function: create_template
     //
     // Creates a new notification template.
@@ -160,6 +772,8 @@ impl NotificationService {
             subject_template,
             body_template,
             supported_channels,
+            locale: default_template_locale(),
+            translations: HashMap::new(),
         };
 
         let template_id = template.id;
@@ -170,6 +784,66 @@ impl NotificationService {
         template_id
     }
 
+    // Function: create_security_alert_template
+    //
+    // Creates the "security_alert" template, which the auth service in
+    // example 13 sends its alerts with. It expects alert_type, alert_message,
+    // timestamp and action_required.
+    //
+    // Returns:
+    //     The ID of the created template
+    pub async fn create_security_alert_template(&self) -> Uuid {
+        self.create_template(
+            "security_alert".to_string(),
+            "Security Alert: {{alert_type}}".to_string(),
+            "ALERT: {{alert_message}}\nTime: {{timestamp}}\nAction required: {{action_required}}"
+                .to_string(),
+            vec![
+                NotificationChannel::Email,
+                NotificationChannel::Sms,
+                NotificationChannel::PushNotification,
+            ],
+        )
+        .await
+    }
+
+    // Function: add_template_translation
+    //
+    // Adds a template's subject and body in another language, replacing any
+    // earlier translation into it.
+    //
+    // Arguments:
+    //     template_name: The name of the template
+    //     locale: The language of the translation, e.g. "es"
+    //     subject_template: The translated subject template
+    //     body_template: The translated body template
+    //
+    // Returns:
+    //     Result indicating success or failure
+    pub async fn add_template_translation(
+        &self,
+        template_name: &str,
+        locale: &str,
+        subject_template: String,
+        body_template: String,
+    ) -> Result<(), String> {
+        let locale = i18n::canonical_locale(locale).map_err(|e| e.to_string())?;
+        let mut templates = self.templates.write().await;
+        let template = templates
+            .get_mut(template_name)
+            .ok_or_else(|| format!("Template not found: {}", template_name))?;
+
+        template.translations.insert(
+            locale.clone(),
+            TemplateTranslation {
+                subject_template,
+                body_template,
+            },
+        );
+        info!("Added {} translation of template {}", locale, template_name);
+        Ok(())
+    }
+
     // Function: subscribe_user
     //
     // Subscribes a user to notifications on a specific channel.
@@ -227,18 +901,353 @@ impl NotificationService {
         template_name: String,
         variables: HashMap<String, String>,
         priority: NotificationPriority,
-    ) -> Result<usize, String> {
+    ) -> Result<usize, McpError> {
+        self.queue_notifications(user_id, template_name, variables, priority, None)
+            .await
+    }
+
+    // Function: send_bulk_notifications
+    //
+    // Sends one template to many recipients. The batch is checked as a whole
+    // first; recipients are then queued in chunks of BULK_CHUNK_SIZE, with up
+    // to BULK_CONCURRENCY chunks in flight, and a recipient that cannot be
+    // sent to is reported without stopping the rest.
+    //
+    // Arguments:
+    //     request: The template, shared variables and priority, and the
+    //              recipients with their overrides
+    //
+    // Returns:
+    //     Result with the consolidated report, or an error if the batch is
+    //     empty, too large, has duplicate recipients or an unknown template
+    pub async fn send_bulk_notifications(
+        &self,
+        request: SendBulkRequest,
+    ) -> Result<BulkSendReport, McpError> {
+        if request.recipients.is_empty() {
+            return Err(McpError::InvalidParams(
+                "At least one recipient is required".to_string(),
+            ));
+        }
+        if request.recipients.len() > MAX_BULK_RECIPIENTS {
+            return Err(McpError::InvalidParams(format!(
+                "At most {} recipients per bulk send, got {}",
+                MAX_BULK_RECIPIENTS,
+                request.recipients.len()
+            )));
+        }
+        let mut seen = HashSet::new();
+        if let Some(duplicate) = request
+            .recipients
+            .iter()
+            .find(|r| !seen.insert(r.user_id.as_str()))
+        {
+            return Err(McpError::InvalidParams(format!(
+                "Duplicate recipient: {}",
+                duplicate.user_id
+            )));
+        }
+        if !self
+            .templates
+            .read()
+            .await
+            .contains_key(&request.template_name)
+        {
+            return Err(McpError::NotFound(format!(
+                "Template not found: {}",
+                request.template_name
+            )));
+        }
+
+        let priority = request.priority.unwrap_or(NotificationPriority::Normal);
+        let mut sends = Vec::new();
+        for chunk in request.recipients.chunks(BULK_CHUNK_SIZE) {
+            sends.push(self.send_bulk_chunk(
+                &request.template_name,
+                &request.variables,
+                &priority,
+                chunk,
+            ));
+        }
+        let chunks = sends.len();
+        let outcomes: Vec<Vec<(String, Result<usize, McpError>)>> = stream::iter(sends)
+            .buffered(BULK_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut report = BulkSendReport {
+            template_name: request.template_name.clone(),
+            recipients: request.recipients.len(),
+            chunks,
+            ..Default::default()
+        };
+        for (user_id, outcome) in outcomes.into_iter().flatten() {
+            match outcome {
+                Ok(0) => report.skipped += 1,
+                Ok(queued) => {
+                    report.succeeded += 1;
+                    report.notifications_queued += queued;
+                }
+                Err(e) => {
+                    report.failed += 1;
+                    report.failures.push(BulkFailure {
+                        user_id,
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        info!(
+            "Bulk send of {}: {} sent, {} skipped, {} failed in {} chunks",
+            report.template_name, report.succeeded, report.skipped, report.failed, report.chunks
+        );
+        Ok(report)
+    }
+
+    // Function: send_bulk_chunk
+    //
+    // Queues the notifications for one chunk of a bulk send, recipient by
+    // recipient.
+    //
+    // Arguments:
+    //     template_name: The template shared by the batch
+    //     shared: Variables for every recipient
+    //     priority: The priority of recipients without their own
+    //     chunk: The recipients to send to
+    //
+    // Returns:
+    //     Each recipient's ID with the number of notifications queued or the error
+    async fn send_bulk_chunk(
+        &self,
+        template_name: &str,
+        shared: &HashMap<String, String>,
+        priority: &NotificationPriority,
+        chunk: &[BulkRecipient],
+    ) -> Vec<(String, Result<usize, McpError>)> {
+        let mut outcomes = Vec::with_capacity(chunk.len());
+        for recipient in chunk {
+            let mut variables = shared.clone();
+            variables.extend(recipient.variables.clone());
+            let outcome = self
+                .queue_notifications(
+                    recipient.user_id.clone(),
+                    template_name.to_string(),
+                    variables,
+                    recipient
+                        .priority
+                        .clone()
+                        .unwrap_or_else(|| priority.clone()),
+                    None,
+                )
+                .await;
+            outcomes.push((recipient.user_id.clone(), outcome));
+        }
+        outcomes
+    }
+
+    // Function: create_experiment
+    //
+    // Creates an A/B experiment that splits recipients between templates.
+    //
+    // Arguments:
+    //     name: The name of the experiment
+    //     variants: At least two variants, each with an existing template
+    //
+    // Returns:
+    //     Result indicating success or failure
+    pub async fn create_experiment(
+        &self,
+        name: String,
+        variants: Vec<ExperimentVariant>,
+    ) -> Result<(), McpError> {
+        if variants.len() < 2 {
+            return Err(McpError::InvalidParams(
+                "An experiment needs at least two variants".to_string(),
+            ));
+        }
+        if variants.iter().any(|v| v.weight == 0) {
+            return Err(McpError::InvalidParams(
+                "Variant weights must be positive".to_string(),
+            ));
+        }
+        let mut names: Vec<&str> = variants.iter().map(|v| v.name.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        if names.len() != variants.len() {
+            return Err(McpError::InvalidParams(
+                "Variant names must be unique".to_string(),
+            ));
+        }
+
+        let templates = self.templates.read().await;
+        if let Some(missing) = variants
+            .iter()
+            .find(|v| !templates.contains_key(&v.template_name))
+        {
+            return Err(McpError::NotFound(format!(
+                "Template not found: {}",
+                missing.template_name
+            )));
+        }
+        drop(templates);
+
+        let mut experiments = self.experiments.write().await;
+        if experiments.contains_key(&name) {
+            return Err(McpError::Conflict(format!(
+                "Experiment already exists: {}",
+                name
+            )));
+        }
+        info!(
+            "Created experiment {} with variants {:?}",
+            name,
+            variants.iter().map(|v| &v.name).collect::<Vec<_>>()
+        );
+        experiments.insert(
+            name.clone(),
+            Experiment {
+                name,
+                variants,
+                created_at: Utc::now(),
+            },
+        );
+
+        Ok(())
+    }
+
+    // Function: send_experiment_notification
+    //
+    // Sends a notification using the template of the recipient's experiment
+    // variant. Every resulting delivery records the variant it used.
+    //
+    // Arguments:
+    //     user_id: The recipient user ID
+    //     experiment_name: The experiment to draw the template from
+    //     variables: Variables to substitute in the template
+    //     priority: The priority of the notification
+    //
+    // Returns:
+    //     Result with the chosen variant name and the number of notifications queued
+    pub async fn send_experiment_notification(
+        &self,
+        user_id: String,
+        experiment_name: &str,
+        variables: HashMap<String, String>,
+        priority: NotificationPriority,
+    ) -> Result<(String, usize), McpError> {
+        let experiments = self.experiments.read().await;
+        let experiment = experiments.get(experiment_name).ok_or_else(|| {
+            McpError::NotFound(format!("Experiment not found: {}", experiment_name))
+        })?;
+        let variant = experiment.assign(&user_id).clone();
+        drop(experiments);
+
+        let assignment = ExperimentAssignment {
+            experiment: experiment_name.to_string(),
+            variant: variant.name.clone(),
+        };
+        let sent = self
+            .queue_notifications(
+                user_id,
+                variant.template_name,
+                variables,
+                priority,
+                Some(assignment),
+            )
+            .await?;
+
+        Ok((variant.name, sent))
+    }
+
+    // Function: experiment_stats
+    //
+    // Summarizes delivery results per variant of an experiment.
+    //
+    // Arguments:
+    //     experiment_name: The experiment to report on
+    //
+    // Returns:
+    //     Result with one entry per variant, in the order they were defined
+    pub async fn experiment_stats(
+        &self,
+        experiment_name: &str,
+    ) -> Result<Vec<VariantStats>, McpError> {
+        let experiments = self.experiments.read().await;
+        let experiment = experiments.get(experiment_name).ok_or_else(|| {
+            McpError::NotFound(format!("Experiment not found: {}", experiment_name))
+        })?;
+
+        let mut stats: Vec<VariantStats> = experiment
+            .variants
+            .iter()
+            .map(|variant| VariantStats {
+                variant: variant.name.clone(),
+                template_name: variant.template_name.clone(),
+                ..Default::default()
+            })
+            .collect();
+        drop(experiments);
+
+        for result in self.delivery_results.read().await.iter() {
+            let Some(assignment) = &result.experiment else {
+                continue;
+            };
+            if assignment.experiment != experiment_name {
+                continue;
+            }
+            if let Some(entry) = stats.iter_mut().find(|s| s.variant == assignment.variant) {
+                entry.deliveries += 1;
+                if !result.success {
+                    entry.failures += 1;
+                }
+            }
+        }
+
+        for entry in &mut stats {
+            entry.success_rate = (entry.deliveries > 0)
+                .then(|| (entry.deliveries - entry.failures) as f64 / entry.deliveries as f64);
+        }
+
+        Ok(stats)
+    }
+
+    // Function: queue_notifications
+    //
+    // Renders a template for each of the user's active subscriptions that the
+    // template supports and queues the notifications for delivery.
+    //
+    // Arguments:
+    //     user_id: The recipient user ID
+    //     template_name: The name of the template to use
+    //     variables: Variables to substitute in the template
+    //     priority: The priority of the notification
+    //     experiment: The experiment variant this send belongs to, if any
+    //
+    // Returns:
+    //     Result with the number of notifications queued
+    async fn queue_notifications(
+        &self,
+        user_id: String,
+        template_name: String,
+        variables: HashMap<String, String>,
+        priority: NotificationPriority,
+        experiment: Option<ExperimentAssignment>,
+    ) -> Result<usize, McpError> {
         // Get the template
         let templates = self.templates.read().await;
         let template = templates
             .get(&template_name)
-            .ok_or("Template not found")?
+            .ok_or_else(|| McpError::NotFound(format!("Template not found: {}", template_name)))?
             .clone();
         drop(templates);
 
         // Get user subscriptions
         let subscriptions = self.subscriptions.read().await;
-        let user_subscriptions = subscriptions.get(&user_id).ok_or("User not found")?.clone();
+        let user_subscriptions = subscriptions
+            .get(&user_id)
+            .ok_or_else(|| McpError::NotFound(format!("User not found: {}", user_id)))?
+            .clone();
         drop(subscriptions);
 
         let mut notifications_sent = 0;
@@ -250,9 +1259,19 @@ impl NotificationService {
                 continue;
             }
 
-            // Process template variables
-            let subject = self.process_template(&template.subject_template, &variables);
-            let body = self.process_template(&template.body_template, &variables);
+            // Process template variables, in the recipient's language
+            let (subject_template, body_template) =
+                template.localized(subscription.locale.as_deref());
+            let subject = self.process_template(subject_template, &variables);
+            let body = self.process_template(body_template, &variables);
+
+            // Only fail over to channels the template can render for
+            let failover_channels = subscription
+                .failover_channels
+                .iter()
+                .filter(|c| **c != subscription.channel && template.supported_channels.contains(c))
+                .cloned()
+                .collect();
 
             let notification = Notification {
                 id: Uuid::new_v4(),
@@ -266,8 +1285,19 @@ impl NotificationService {
                 scheduled_for: None,
                 retry_count: 0,
                 max_retries: 3,
+                failover_channels,
+                experiment: experiment.clone(),
             };
 
+            // Keep it in the outbox until the worker is done with it, so it
+            // is not lost if the service stops first
+            if let Some(outbox) = &self.outbox {
+                let key = notification.id.to_string();
+                if let Err(e) = outbox.put(OUTBOX_NAMESPACE, &key, &notification).await {
+                    warn!("Failed to checkpoint notification {}: {}", key, e);
+                }
+            }
+
             // Queue the notification for delivery
             if let Err(e) = self.notification_sender.send(notification) {
                 error!("Failed to queue notification: {}", e);
@@ -283,50 +1313,490 @@ impl NotificationService {
         Ok(notifications_sent)
     }
 
-    // Function: process_template
+    // Function: process_template
+    //
+    // Processes a template by substituting variables.
+    //
+    // Arguments:
+    //     template: The template string with {{variable}} placeholders
+    //     variables: The variables to substitute
+    //
+    // Returns:
+    //     The processed template string
+    fn process_template(&self, template: &str, variables: &HashMap<String, String>) -> String {
+        let mut result = template.to_string();
+
+        for (key, value) in variables {
+            let placeholder = format!("{{{{{}}}}}", key);
+            result = result.replace(&placeholder, value);
+        }
+
+        result
+    }
+
+    // Function: get_delivery_status
+    //
+    // Gets the delivery status for notifications.
+    //
+    // Arguments:
+    //     user_id: Optional user ID to filter by
+    //
+    // Returns:
+    //     Vector of delivery results
+    pub async fn get_delivery_status(&self, user_id: Option<String>) -> Vec<DeliveryResult> {
+        let results = self.delivery_results.read().await;
+
+        match user_id {
+            Some(_uid) => results
+                .iter()
+                .filter(|_r| {
+                    // This is a simplified check; in practice you'd need to track user_id in DeliveryResult
+                    true // For demo purposes
+                })
+                .cloned()
+                .collect(),
+            None => results.clone(),
+        }
+    }
+
+    // Function: list_resources
+    //
+    // Lists one `inbox://{user_id}` resource per user with an in-app inbox.
+    //
+    // Returns:
+    //     Vector of inbox resources
+    pub async fn list_resources(&self) -> Vec<Resource> {
+        let inboxes = self.inboxes.read().await;
+
+        inboxes
+            .iter()
+            .map(|(user_id, messages)| Resource {
+                uri: format!("inbox://{}", user_id),
+                name: Some(format!("Inbox for {}", user_id)),
+                description: Some(format!(
+                    "{} messages, {} unread",
+                    messages.len(),
+                    messages.iter().filter(|m| !m.read).count()
+                )),
+                mime_type: Some("application/json".to_string()),
+            })
+            .collect()
+    }
+
+    // Function: read_resource
+    //
+    // Reads an `inbox://{user_id}` resource, returning the messages newest first.
+    //
+    // Arguments:
+    //     uri: The resource URI
+    //
+    // Returns:
+    //     Result with the resource contents or an error message
+    pub async fn read_resource(&self, uri: &str) -> Result<Value, McpError> {
+        let user_id = uri
+            .strip_prefix("inbox://")
+            .ok_or_else(|| McpError::InvalidParams(format!("Invalid inbox URI: {}", uri)))?;
+
+        let messages = self.inbox_messages(user_id, false).await?;
+        let text = serde_json::to_string(&messages)
+            .map_err(|e| McpError::Internal(format!("Failed to serialize inbox: {}", e)))?;
+
+        Ok(serde_json::json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": text
+            }]
+        }))
+    }
+
+    // Function: list_tools
+    //
+    // Lists the tools for sending notifications and managing in-app inboxes.
+    //
+    // Returns:
+    //     Vector of tool definitions
+    pub fn list_tools(&self) -> Vec<Tool> {
+        let message_schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "user_id": { "type": "string", "description": "Owner of the inbox" },
+                "message_id": { "type": "string", "description": "ID of the inbox message" }
+            },
+            "required": ["user_id", "message_id"]
+        });
+
+        vec![
+            Tool {
+                name: "send_notification".to_string(),
+                description: "Send a templated notification to all of a user's subscribed channels"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "user_id": { "type": "string", "description": "Recipient of the notification" },
+                        "template_name": { "type": "string", "description": "Template to render" },
+                        "variables": {
+                            "type": "object",
+                            "additionalProperties": { "type": "string" },
+                            "description": "Values for the template placeholders"
+                        },
+                        "priority": {
+                            "type": "string",
+                            "enum": ["Low", "Normal", "High", "Critical"],
+                            "description": "Delivery priority (default: Normal)"
+                        }
+                    },
+                    "required": ["user_id", "template_name"]
+                }),
+                annotations: Some(ToolAnnotations::additive().open_world()),
+            },
+            Tool {
+                name: "send_bulk_notifications".to_string(),
+                description: "Send one templated notification to many recipients and report which could not be reached"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "template_name": { "type": "string", "description": "Template to render" },
+                        "variables": {
+                            "type": "object",
+                            "additionalProperties": { "type": "string" },
+                            "description": "Values for the template placeholders, shared by all recipients"
+                        },
+                        "priority": {
+                            "type": "string",
+                            "enum": ["Low", "Normal", "High", "Critical"],
+                            "description": "Delivery priority (default: Normal)"
+                        },
+                        "recipients": {
+                            "type": "array",
+                            "minItems": 1,
+                            "maxItems": MAX_BULK_RECIPIENTS,
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "user_id": { "type": "string" },
+                                    "variables": {
+                                        "type": "object",
+                                        "additionalProperties": { "type": "string" },
+                                        "description": "Overrides for the shared variables"
+                                    },
+                                    "priority": {
+                                        "type": "string",
+                                        "enum": ["Low", "Normal", "High", "Critical"]
+                                    }
+                                },
+                                "required": ["user_id"]
+                            }
+                        }
+                    },
+                    "required": ["template_name", "recipients"]
+                }),
+                annotations: Some(ToolAnnotations::additive().open_world()),
+            },
+            Tool {
+                name: "create_experiment".to_string(),
+                description: "Create an A/B experiment that splits recipients between templates"
+                    .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string", "description": "Experiment name" },
+                        "variants": {
+                            "type": "array",
+                            "minItems": 2,
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "name": { "type": "string" },
+                                    "template_name": { "type": "string" },
+                                    "weight": {
+                                        "type": "integer",
+                                        "minimum": 1,
+                                        "description": "Relative share of recipients (default: 1)"
+                                    }
+                                },
+                                "required": ["name", "template_name"]
+                            }
+                        }
+                    },
+                    "required": ["name", "variants"]
+                }),
+                annotations: Some(ToolAnnotations::additive()),
+            },
+            Tool {
+                name: "send_experiment_notification".to_string(),
+                description:
+                    "Send a notification using the template of the recipient's experiment variant"
+                        .to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "user_id": { "type": "string", "description": "Recipient of the notification" },
+                        "experiment": { "type": "string", "description": "Experiment to use" },
+                        "variables": {
+                            "type": "object",
+                            "additionalProperties": { "type": "string" },
+                            "description": "Values for the template placeholders"
+                        },
+                        "priority": {
+                            "type": "string",
+                            "enum": ["Low", "Normal", "High", "Critical"],
+                            "description": "Delivery priority (default: Normal)"
+                        }
+                    },
+                    "required": ["user_id", "experiment"]
+                }),
+                annotations: Some(ToolAnnotations::additive().open_world()),
+            },
+            Tool {
+                name: "get_experiment_stats".to_string(),
+                description: "Get delivery and failure counts per experiment variant".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "experiment": { "type": "string", "description": "Experiment name" }
+                    },
+                    "required": ["experiment"]
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "get_channel_quotas".to_string(),
+                description: "Report each limited channel's provider quota: sends used and left in the current window, seconds until it refills, and notifications deferred until then".to_string(),
+                input_schema: serde_json::json!({ "type": "object", "properties": {} }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "list_inbox_messages".to_string(),
+                description: "List in-app inbox messages for a user, newest first".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "user_id": { "type": "string", "description": "Owner of the inbox" },
+                        "unread_only": {
+                            "type": "boolean",
+                            "description": "Only return unread messages (default: false)"
+                        },
+                        "offset": {
+                            "type": "integer",
+                            "minimum": 0,
+                            "description": "Messages to skip (the next_offset of the previous page)"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "minimum": 1,
+                            "description": "Maximum number of messages to return (default: all)"
+                        }
+                    },
+                    "required": ["user_id"]
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            },
+            Tool {
+                name: "mark_inbox_message_read".to_string(),
+                description: "Mark an in-app inbox message as read".to_string(),
+                input_schema: message_schema.clone(),
+                annotations: Some(ToolAnnotations::additive().idempotent()),
+            },
+            Tool {
+                name: "delete_inbox_message".to_string(),
+                description: "Delete a message from an in-app inbox".to_string(),
+                input_schema: message_schema,
+                annotations: Some(ToolAnnotations::destructive().idempotent()),
+            },
+        ]
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        ToolCallLog::run_async(name, arguments, |arguments| {
+            self.dispatch_tool(name, arguments)
+        })
+        .await
+    }
+
+    // Function: call_tool
     //
-    // Processes a template by substituting variables.
+    // Executes one of the notification or inbox tools.
     //
     // Arguments:
-    //     template: The template string with {{variable}} placeholders
-    //     variables: The variables to substitute
+    //     name: The tool name
+    //     arguments: The tool arguments as JSON
     //
     // Returns:
-    //     The processed template string
-    fn process_template(&self, template: &str, variables: &HashMap<String, String>) -> String {
-        let mut result = template.to_string();
-
-        for (key, value) in variables {
-            let placeholder = format!("{{{{{}}}}}", key);
-            result = result.replace(&placeholder, value);
+    //     Result with the tool output or an error message
+    async fn dispatch_tool(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        match name {
+            "send_notification" => {
+                let request: SendNotificationRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+                let sent = self
+                    .send_notification(
+                        request.user_id.clone(),
+                        request.template_name,
+                        request.variables,
+                        request.priority.unwrap_or(NotificationPriority::Normal),
+                    )
+                    .await?;
+
+                Ok(serde_json::json!({
+                    "user_id": request.user_id,
+                    "notifications_queued": sent
+                }))
+            }
+            "send_bulk_notifications" => {
+                let request: SendBulkRequest = serde_json::from_value(arguments).map_err(|e| {
+                    McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                })?;
+                let report = self.send_bulk_notifications(request).await?;
+
+                serde_json::to_value(report)
+                    .map_err(|e| McpError::Internal(format!("Failed to serialize report: {}", e)))
+            }
+            "create_experiment" => {
+                let request: CreateExperimentRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+                self.create_experiment(request.name.clone(), request.variants)
+                    .await?;
+
+                Ok(serde_json::json!({ "experiment": request.name, "created": true }))
+            }
+            "send_experiment_notification" => {
+                let request: SendExperimentRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+                let (variant, sent) = self
+                    .send_experiment_notification(
+                        request.user_id.clone(),
+                        &request.experiment,
+                        request.variables,
+                        request.priority.unwrap_or(NotificationPriority::Normal),
+                    )
+                    .await?;
+
+                Ok(serde_json::json!({
+                    "user_id": request.user_id,
+                    "experiment": request.experiment,
+                    "variant": variant,
+                    "notifications_queued": sent
+                }))
+            }
+            "get_experiment_stats" => {
+                let request: ExperimentStatsRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+                let variants = self.experiment_stats(&request.experiment).await?;
+
+                Ok(serde_json::json!({
+                    "experiment": request.experiment,
+                    "variants": variants
+                }))
+            }
+            "get_channel_quotas" => Ok(serde_json::json!({ "quotas": self.quotas.usage() })),
+            "list_inbox_messages" => {
+                let request: ListInboxRequest = serde_json::from_value(arguments).map_err(|e| {
+                    McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                })?;
+                let messages = self
+                    .inbox_messages(&request.user_id, request.unread_only)
+                    .await?;
+                let unread = self.inbox_messages(&request.user_id, true).await?.len();
+                let total = messages.len();
+                let page: Vec<InboxMessage> = messages
+                    .into_iter()
+                    .skip(request.offset)
+                    .take(request.limit.unwrap_or(usize::MAX))
+                    .collect();
+                let end = request.offset.saturating_add(page.len());
+
+                Ok(serde_json::json!({
+                    "user_id": request.user_id,
+                    "count": page.len(),
+                    "total": total,
+                    "unread": unread,
+                    "next_offset": (end < total).then_some(end),
+                    "messages": page
+                }))
+            }
+            "mark_inbox_message_read" => {
+                let request: InboxMessageRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+                let mut inboxes = self.inboxes.write().await;
+
+                let message = inboxes
+                    .get_mut(&request.user_id)
+                    .and_then(|messages| messages.iter_mut().find(|m| m.id == request.message_id))
+                    .ok_or_else(|| {
+                        McpError::NotFound(format!(
+                            "Inbox message not found: {}",
+                            request.message_id
+                        ))
+                    })?;
+                message.read = true;
+
+                Ok(serde_json::json!({ "message_id": request.message_id, "read": true }))
+            }
+            "delete_inbox_message" => {
+                let request: InboxMessageRequest =
+                    serde_json::from_value(arguments).map_err(|e| {
+                        McpError::InvalidParams(format!("Failed to parse arguments: {}", e))
+                    })?;
+                let mut inboxes = self.inboxes.write().await;
+
+                let messages = inboxes.get_mut(&request.user_id).ok_or_else(|| {
+                    McpError::NotFound(format!("Inbox not found: {}", request.user_id))
+                })?;
+                let before = messages.len();
+                messages.retain(|m| m.id != request.message_id);
+
+                if messages.len() == before {
+                    return Err(McpError::NotFound(format!(
+                        "Inbox message not found: {}",
+                        request.message_id
+                    )));
+                }
+
+                Ok(serde_json::json!({ "message_id": request.message_id, "deleted": true }))
+            }
+            _ => Err(McpError::NotFound(format!("Unknown tool: {}", name))),
         }
-
-        result
     }
 
-    // Function: get_delivery_status
+    // Function: inbox_messages
     //
-    // Gets the delivery status for notifications.
+    // Returns a snapshot of a user's inbox, newest first.
     //
     // Arguments:
-    //     user_id: Optional user ID to filter by
+    //     user_id: The owner of the inbox
+    //     unread_only: Whether to skip messages that were already read
     //
     // Returns:
-    //     Vector of delivery results
-    pub async fn get_delivery_status(&self, user_id: Option<String>) -> Vec<DeliveryResult> {
-        let results = self.delivery_results.read().await;
-
-        match user_id {
-            Some(_uid) => results
-                .iter()
-                .filter(|_r| {
-                    // This is a simplified check; in practice you'd need to track user_id in DeliveryResult
-                    true // For demo purposes
-                })
-                .cloned()
-                .collect(),
-            None => results.clone(),
-        }
+    //     Result with the messages or an error if the user has no inbox
+    async fn inbox_messages(
+        &self,
+        user_id: &str,
+        unread_only: bool,
+    ) -> Result<Vec<InboxMessage>, McpError> {
+        let inboxes = self.inboxes.read().await;
+        let messages = inboxes
+            .get(user_id)
+            .ok_or_else(|| McpError::NotFound(format!("Inbox not found: {}", user_id)))?;
+
+        Ok(messages
+            .iter()
+            .rev()
+            .filter(|m| !unread_only || !m.read)
+            .cloned()
+            .collect())
     }
 }
 
@@ -335,7 +1805,12 @@ impl NotificationService {
 // This struct handles the background delivery of notifications.
 struct DeliveryWorker {
     receiver: mpsc::UnboundedReceiver<Notification>,
+    scheduler: mpsc::WeakUnboundedSender<Notification>, // Queues deferred notifications again
     delivery_results: Arc<RwLock<Vec<DeliveryResult>>>,
+    inboxes: Inboxes,
+    outbox: Option<CheckpointStore>,
+    quotas: Arc<ChannelQuotas>,
+    outages: Outages,
 }
 
 impl DeliveryWorker {
@@ -344,59 +1819,212 @@ impl DeliveryWorker {
     // Creates a new delivery worker.
     fn new(
         receiver: mpsc::UnboundedReceiver<Notification>,
+        scheduler: mpsc::WeakUnboundedSender<Notification>,
         delivery_results: Arc<RwLock<Vec<DeliveryResult>>>,
+        inboxes: Inboxes,
+        outbox: Option<CheckpointStore>,
+        quotas: Arc<ChannelQuotas>,
+        outages: Outages,
     ) -> Self {
         Self {
             receiver,
+            scheduler,
             delivery_results,
+            inboxes,
+            outbox,
+            quotas,
+            outages,
         }
     }
 
     // Function: run
     //
-    // Runs the delivery worker loop.
+    // Runs the delivery worker loop. Notifications scheduled for later, such
+    // as deferred ones found in the outbox after a restart, are held back.
     async fn run(mut self) {
         while let Some(notification) = self.receiver.recv().await {
-            self.deliver_notification(notification).await;
+            match notification.scheduled_for {
+                Some(due) if due > Utc::now() => self.schedule(notification),
+                _ => self.deliver_notification(notification).await,
+            }
+        }
+    }
+
+    // Function: schedule
+    //
+    // Holds a notification back until its scheduled_for time, then queues it
+    // for delivery again. If the service has stopped by then, the notification
+    // is left in the outbox for the next run.
+    //
+    // Arguments:
+    //     notification: The notification to deliver later
+    fn schedule(&self, notification: Notification) {
+        let scheduler = self.scheduler.clone();
+        let quotas = self.quotas.clone();
+        let wait = notification
+            .scheduled_for
+            .and_then(|due| (due - Utc::now()).to_std().ok())
+            .unwrap_or_default();
+        quotas.track_deferred(&notification.channel, true);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(wait).await;
+            quotas.track_deferred(&notification.channel, false);
+            if let Some(sender) = scheduler.upgrade() {
+                if let Err(e) = sender.send(notification) {
+                    error!("Failed to queue deferred notification: {}", e);
+                }
+            }
+        });
+    }
+
+    // Function: defer
+    //
+    // Defers a notification whose channel has used up its quota, to resume
+    // on that channel once the quota has room again. The outbox entry is
+    // updated so a restart keeps the new schedule.
+    //
+    // Arguments:
+    //     notification: The notification being delivered
+    //     remaining: The channel out of quota, then the failovers after it
+    //     wait: How long until the channel's quota has room again
+    async fn defer(
+        &self,
+        mut notification: Notification,
+        remaining: &[NotificationChannel],
+        wait: std::time::Duration,
+    ) {
+        notification.channel = remaining[0].clone();
+        notification.failover_channels = remaining[1..].to_vec();
+        notification.scheduled_for =
+            Some(Utc::now() + chrono::Duration::from_std(wait).unwrap_or_default());
+
+        if let Some(outbox) = &self.outbox {
+            let key = notification.id.to_string();
+            if let Err(e) = outbox.put(OUTBOX_NAMESPACE, &key, &notification).await {
+                warn!("Failed to checkpoint deferred notification {}: {}", key, e);
+            }
         }
+        info!(
+            "Quota for {:?} used up, deferring notification {} for {:.1}s",
+            notification.channel,
+            notification.id,
+            wait.as_secs_f64()
+        );
+        self.schedule(notification);
     }
 
     // Function: deliver_notification
     //
-    // Delivers a single notification.
+    // Delivers a single notification, retrying each channel up to max_retries
+    // times before moving on to the next channel in the failover chain. An
+    // attempt on a channel whose quota is used up defers the notification.
     async fn deliver_notification(&self, mut notification: Notification) {
-        notification.retry_count += 1;
-
-        let result = match notification.channel {
-            NotificationChannel::Email => self.deliver_email(&notification).await,
-            NotificationChannel::Sms => self.deliver_sms(&notification).await,
-            NotificationChannel::Webhook => self.deliver_webhook(&notification).await,
-            NotificationChannel::PushNotification => self.deliver_push(&notification).await,
-            NotificationChannel::InApp => self.deliver_in_app(&notification).await,
-        };
+        let chain: Vec<NotificationChannel> = std::iter::once(notification.channel.clone())
+            .chain(notification.failover_channels.iter().cloned())
+            .collect();
+
+        let mut channel_path = Vec::new();
+        let mut delivered_via = None;
+        let mut last_error = None;
+
+        'chain: for (position, channel) in chain.iter().cloned().enumerate() {
+            channel_path.push(channel.clone());
+
+            for _ in 0..notification.max_retries.max(1) {
+                if let Some(wait) = self.quotas.take(&channel) {
+                    self.defer(notification, &chain[position..], wait).await;
+                    return;
+                }
+                notification.retry_count += 1;
+
+                match self.deliver_on_channel(&channel, &notification).await {
+                    Ok(()) => {
+                        delivered_via = Some(channel);
+                        break 'chain;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to deliver notification {} via {:?} (attempt {}): {}",
+                            notification.id, channel, notification.retry_count, e
+                        );
+                        last_error = Some(e);
+                    }
+                }
+            }
+
+            if channel_path.len() <= notification.failover_channels.len() {
+                warn!(
+                    "Channel {:?} exhausted for notification {}, failing over",
+                    channel, notification.id
+                );
+            }
+        }
 
         let delivery_result = DeliveryResult {
             notification_id: notification.id,
-            success: result.is_ok(),
+            success: delivered_via.is_some(),
             attempt_count: notification.retry_count,
             delivered_at: Utc::now(),
-            error_message: result.err(),
+            error_message: if delivered_via.is_some() {
+                None
+            } else {
+                last_error
+            },
+            delivered_via,
+            channel_path,
+            experiment: notification.experiment.clone(),
         };
 
         // Store the delivery result
         let mut results = self.delivery_results.write().await;
         results.push(delivery_result.clone());
+        drop(results);
+
+        // The notification is done with, delivered or not
+        if let Some(outbox) = &self.outbox {
+            let key = notification.id.to_string();
+            if let Err(e) = outbox.remove(OUTBOX_NAMESPACE, &key).await {
+                warn!(
+                    "Failed to clear notification {} from the outbox: {}",
+                    key, e
+                );
+            }
+        }
 
-        if delivery_result.success {
-            info!(
-                "Successfully delivered notification {} via {:?}",
-                notification.id, notification.channel
-            );
-        } else {
-            warn!(
-                "Failed to deliver notification {} (attempt {}): {:?}",
-                notification.id, notification.retry_count, delivery_result.error_message
-            );
+        match &delivery_result.delivered_via {
+            Some(channel) => info!(
+                "Successfully delivered notification {} via {:?} (path: {:?})",
+                notification.id, channel, delivery_result.channel_path
+            ),
+            None => error!(
+                "Giving up on notification {} after {} attempts (path: {:?}): {:?}",
+                notification.id,
+                notification.retry_count,
+                delivery_result.channel_path,
+                delivery_result.error_message
+            ),
+        }
+    }
+
+    // Function: deliver_on_channel
+    //
+    // Dispatches a notification to the delivery function for a channel, or
+    // fails it if the channel's provider is down.
+    async fn deliver_on_channel(
+        &self,
+        channel: &NotificationChannel,
+        notification: &Notification,
+    ) -> Result<(), String> {
+        if self.outages.lock().unwrap().contains(channel) {
+            return Err(format!("{:?} provider is down", channel));
+        }
+        match channel {
+            NotificationChannel::Email => self.deliver_email(notification).await,
+            NotificationChannel::Sms => self.deliver_sms(notification).await,
+            NotificationChannel::Webhook => self.deliver_webhook(notification).await,
+            NotificationChannel::PushNotification => self.deliver_push(notification).await,
+            NotificationChannel::InApp => self.deliver_in_app(notification).await,
         }
     }
 
@@ -456,9 +2084,21 @@ impl DeliveryWorker {
 
     // Function: deliver_in_app
     //
-    // Simulates in-app notification delivery.
+    // Delivers an in-app notification by appending it to the recipient's inbox.
     async fn deliver_in_app(&self, notification: &Notification) -> Result<(), String> {
-        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        let mut inboxes = self.inboxes.write().await;
+        inboxes
+            .entry(notification.recipient_id.clone())
+            .or_default()
+            .push(InboxMessage {
+                id: Uuid::new_v4(),
+                notification_id: notification.id,
+                subject: notification.subject.clone(),
+                body: notification.body.clone(),
+                priority: notification.priority.clone(),
+                received_at: Utc::now(),
+                read: false,
+            });
 
         info!("🔔 In-app notification: {}", notification.subject);
         Ok(())
@@ -468,8 +2108,24 @@ impl DeliveryWorker {
 // Function: demo_notification_service
 //
 // Demonstrates the notification service functionality.
-async fn demo_notification_service() -> Result<(), Box<dyn std::error::Error>> {
-    let service = NotificationService::new();
+async fn demo_notification_service(
+    state_command: &StateCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // With MCP_CHECKPOINT_PATH set, notifications an earlier run did not
+    // get to are sent first
+    let mut service = match CheckpointStore::from_env().await? {
+        Some(store) => NotificationService::with_checkpoints(store).await?,
+        None => NotificationService::new(),
+    };
+    state_command.restore(&mut service).await?;
+
+    // The setup below registers fixed subscriptions, which a restored
+    // service already has
+    if state_command.restore_from.is_some() {
+        demo_restored_state(&service).await;
+        state_command.dump(&service).await?;
+        return Ok(());
+    }
 
     info!("=== Creating notification templates ===");
 
@@ -482,19 +2138,18 @@ async fn demo_notification_service() -> Result<(), Box<dyn std::error::Error>> {
     ).await;
 
     // Create an alert template
+    service.create_security_alert_template().await;
+
+    // Translate the welcome email; recipients in other languages still get
+    // the English one
     service
-        .create_template(
-            "security_alert".to_string(),
-            "Security Alert: {{alert_type}}".to_string(),
-            "ALERT: {{alert_message}}\nTime: {{timestamp}}\nAction required: {{action_required}}"
-                .to_string(),
-            vec![
-                NotificationChannel::Email,
-                NotificationChannel::Sms,
-                NotificationChannel::PushNotification,
-            ],
+        .add_template_translation(
+            "welcome_email",
+            "es",
+            "¡Bienvenido a {{app_name}}, {{user_name}}!".to_string(),
+            "Hola {{user_name}},\n\n¡Bienvenido a {{app_name}}! Nos alegra tenerte con nosotros.\n\nSaludos,\nEl equipo de {{app_name}}".to_string(),
         )
-        .await;
+        .await?;
 
     info!("=== Setting up user subscriptions ===");
 
@@ -508,6 +2163,8 @@ async fn demo_notification_service() -> Result<(), Box<dyn std::error::Error>> {
                 endpoint: "user123@example.com".to_string(),
                 is_active: true,
                 preferences: HashMap::new(),
+                failover_channels: Vec::new(),
+                locale: None,
             },
         )
         .await?;
@@ -522,6 +2179,40 @@ async fn demo_notification_service() -> Result<(), Box<dyn std::error::Error>> {
                 endpoint: "+1234567890".to_string(),
                 is_active: true,
                 preferences: HashMap::new(),
+                failover_channels: Vec::new(),
+                locale: None,
+            },
+        )
+        .await?;
+
+    // Subscribe the same user to the in-app inbox
+    service
+        .subscribe_user(
+            "user123".to_string(),
+            NotificationSubscription {
+                user_id: "user123".to_string(),
+                channel: NotificationChannel::InApp,
+                endpoint: "inbox://user123".to_string(),
+                is_active: true,
+                preferences: HashMap::new(),
+                failover_channels: Vec::new(),
+                locale: None,
+            },
+        )
+        .await?;
+
+    // Subscribe to push notifications, falling back to SMS and then email
+    service
+        .subscribe_user(
+            "user123".to_string(),
+            NotificationSubscription {
+                user_id: "user123".to_string(),
+                channel: NotificationChannel::PushNotification,
+                endpoint: "device-token-abc123".to_string(),
+                is_active: true,
+                preferences: HashMap::new(),
+                failover_channels: vec![NotificationChannel::Sms, NotificationChannel::Email],
+                locale: None,
             },
         )
         .await?;
@@ -572,20 +2263,285 @@ async fn demo_notification_service() -> Result<(), Box<dyn std::error::Error>> {
 
     for result in delivery_status {
         info!(
-            "Notification {}: {} (attempt {})",
+            "Notification {}: {} (attempt {}, path {:?})",
             result.notification_id,
             if result.success {
                 "✅ Delivered"
             } else {
                 "❌ Failed"
             },
-            result.attempt_count
+            result.attempt_count,
+            result.channel_path
+        );
+    }
+
+    info!("=== Reading the in-app inbox ===");
+    for resource in service.list_resources().await {
+        info!(
+            "Resource {}: {}",
+            resource.uri,
+            resource.description.unwrap_or_default()
+        );
+    }
+
+    let inbox = service
+        .call_tool(
+            "list_inbox_messages",
+            serde_json::json!({ "user_id": "user123", "unread_only": true }),
+        )
+        .await?;
+    info!("Unread inbox messages: {}", inbox["count"]);
+
+    if let Some(message_id) = inbox["messages"][0]["id"].as_str() {
+        let args = serde_json::json!({ "user_id": "user123", "message_id": message_id });
+        service
+            .call_tool("mark_inbox_message_read", args.clone())
+            .await?;
+        info!("Marked message {} as read", message_id);
+        service.call_tool("delete_inbox_message", args).await?;
+        info!("Deleted message {}", message_id);
+    }
+
+    info!("=== A/B template experiment ===");
+
+    service
+        .create_template(
+            "welcome_short".to_string(),
+            "Hi {{user_name}} 👋".to_string(),
+            "You're all set on {{app_name}}.".to_string(),
+            vec![NotificationChannel::Email, NotificationChannel::InApp],
+        )
+        .await;
+    service
+        .call_tool(
+            "create_experiment",
+            serde_json::json!({
+                "name": "welcome_copy",
+                "variants": [
+                    { "name": "control", "template_name": "welcome_email" },
+                    { "name": "short", "template_name": "welcome_short" }
+                ]
+            }),
+        )
+        .await?;
+
+    for user_id in ["user123", "user456", "user789"] {
+        if user_id != "user123" {
+            service
+                .subscribe_user(
+                    user_id.to_string(),
+                    NotificationSubscription {
+                        user_id: user_id.to_string(),
+                        channel: NotificationChannel::Email,
+                        endpoint: format!("{}@example.com", user_id),
+                        is_active: true,
+                        preferences: HashMap::new(),
+                        failover_channels: Vec::new(),
+                        // user456 reads Mexican Spanish, and gets the
+                        // Spanish translation where there is one
+                        locale: (user_id == "user456").then(|| "es-MX".to_string()),
+                    },
+                )
+                .await?;
+        }
+
+        let sent = service
+            .call_tool(
+                "send_experiment_notification",
+                serde_json::json!({
+                    "user_id": user_id,
+                    "experiment": "welcome_copy",
+                    "variables": { "user_name": user_id, "app_name": "MCP Examples" }
+                }),
+            )
+            .await?;
+        info!("{} received variant {}", user_id, sent["variant"]);
+    }
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+    let stats = service
+        .call_tool(
+            "get_experiment_stats",
+            serde_json::json!({ "experiment": "welcome_copy" }),
+        )
+        .await?;
+    for variant in stats["variants"].as_array().into_iter().flatten() {
+        info!(
+            "Variant {}: {} deliveries, {} failures",
+            variant["variant"], variant["deliveries"], variant["failures"]
         );
     }
 
+    info!("=== Bulk send ===");
+
+    // One recipient overrides the shared variables; one does not exist
+    let report = service
+        .call_tool(
+            "send_bulk_notifications",
+            serde_json::json!({
+                "template_name": "welcome_email",
+                "variables": { "app_name": "MCP Examples", "user_name": "there" },
+                "recipients": [
+                    { "user_id": "user123" },
+                    { "user_id": "user456", "variables": { "user_name": "Sam" }, "priority": "High" },
+                    { "user_id": "user789" },
+                    { "user_id": "unknown_user" }
+                ]
+            }),
+        )
+        .await?;
+    info!(
+        "Bulk send: {} of {} recipients sent, {} notifications queued in {} chunk(s)",
+        report["succeeded"], report["recipients"], report["notifications_queued"], report["chunks"]
+    );
+    for failure in report["failures"].as_array().into_iter().flatten() {
+        info!("  Not sent to {}: {}", failure["user_id"], failure["error"]);
+    }
+
+    info!("=== Provider quotas ===");
+
+    // A tight SMS quota: the third alert's SMS waits for the bucket to refill
+    service.set_channel_quotas(HashMap::from([(
+        NotificationChannel::Sms,
+        ChannelQuota {
+            limit: 2,
+            window_seconds: 6,
+        },
+    )]));
+    for attempt in 1..=3 {
+        let variables = HashMap::from([
+            ("alert_type".to_string(), "Password Reset".to_string()),
+            (
+                "alert_message".to_string(),
+                format!("Reset code requested ({})", attempt),
+            ),
+            ("timestamp".to_string(), Utc::now().to_rfc3339()),
+            ("action_required".to_string(), "None".to_string()),
+        ]);
+        service
+            .send_notification(
+                "user123".to_string(),
+                "security_alert".to_string(),
+                variables,
+                NotificationPriority::High,
+            )
+            .await?;
+    }
+
+    for wait_seconds in [2, 4] {
+        tokio::time::sleep(tokio::time::Duration::from_secs(wait_seconds)).await;
+        let quotas = service
+            .call_tool("get_channel_quotas", serde_json::json!({}))
+            .await?;
+        for quota in quotas["quotas"].as_array().into_iter().flatten() {
+            info!(
+                "{}: {} of {} used, {} deferred, full again in {}s",
+                quota["channel"].as_str().unwrap_or(""),
+                quota["used"],
+                quota["limit"],
+                quota["deferred"],
+                quota["reset_seconds"]
+            );
+        }
+    }
+
+    state_command.dump(&service).await?;
+
     Ok(())
 }
 
+// Function: demo_restored_state
+//
+// Summarizes a notification service restored from a snapshot.
+async fn demo_restored_state(service: &NotificationService) {
+    info!("=== Restored State ===");
+    info!("Templates: {}", service.templates.read().await.len());
+    info!(
+        "Subscriptions: {}",
+        service
+            .subscriptions
+            .read()
+            .await
+            .values()
+            .map(Vec::len)
+            .sum::<usize>()
+    );
+    info!(
+        "Delivery results: {}",
+        service.delivery_results.read().await.len()
+    );
+    info!("Experiments: {}", service.experiments.read().await.len());
+    for resource in service.list_resources().await {
+        info!(
+            "Resource {}: {}",
+            resource.uri,
+            resource.description.unwrap_or_default()
+        );
+    }
+}
+
+// Trait implementation: ToolServer
+//
+// Exposes the notification tools to clients that work with several servers.
+impl ToolServer for NotificationService {
+    fn server_name(&self) -> &str {
+        "notification_service"
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.list_tools()
+    }
+
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(self.call_tool(name, arguments))
+    }
+}
+
+// Trait implementation: StatefulServer
+//
+// Restoring replaces templates, subscriptions, delivery history and inboxes.
+// The delivery worker keeps running and shares the restored inboxes.
+impl StatefulServer for NotificationService {
+    const STATE_NAME: &'static str = "notification_service";
+
+    async fn snapshot(&self) -> Result<Value, StateError> {
+        let state = NotificationServiceState {
+            templates: self.templates.read().await.values().cloned().collect(),
+            subscriptions: self.subscriptions.read().await.clone(),
+            pending_notifications: self.pending_notifications.read().await.clone(),
+            delivery_results: self.delivery_results.read().await.clone(),
+            inboxes: self.inboxes.read().await.clone(),
+            experiments: self.experiments.read().await.values().cloned().collect(),
+        };
+        Ok(serde_json::to_value(state)?)
+    }
+
+    async fn restore(&mut self, state: Value) -> Result<(), StateError> {
+        let state: NotificationServiceState = serde_json::from_value(state)?;
+
+        *self.templates.write().await = state
+            .templates
+            .into_iter()
+            .map(|template| (template.name.clone(), template))
+            .collect();
+        *self.subscriptions.write().await = state.subscriptions;
+        *self.pending_notifications.write().await = state.pending_notifications;
+        *self.delivery_results.write().await = state.delivery_results;
+        *self.inboxes.write().await = state.inboxes;
+        *self.experiments.write().await = state
+            .experiments
+            .into_iter()
+            .map(|experiment| (experiment.name.clone(), experiment))
+            .collect();
+        Ok(())
+    }
+}
+
 // Function: main
 //
 // This is the entry point of the program.
@@ -594,14 +2550,498 @@ async fn demo_notification_service() -> Result<(), Box<dyn std::error::Error>> {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize the tracing subscriber for logging
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    let _telemetry = mcp_rust_examples::logging::init("info");
 
     info!("Starting Notification Service Example");
 
     // Run the notification service demo
-    demo_notification_service().await?;
+    let state_command = StateCommand::from_env()?;
+    demo_notification_service(&state_command).await?;
 
     info!("Notification Service Example completed successfully");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(
+        user_id: &str,
+        channel: NotificationChannel,
+        failover_channels: Vec<NotificationChannel>,
+    ) -> NotificationSubscription {
+        NotificationSubscription {
+            user_id: user_id.to_string(),
+            channel,
+            endpoint: format!("{}@example.com", user_id),
+            is_active: true,
+            preferences: HashMap::new(),
+            failover_channels,
+            locale: None,
+        }
+    }
+
+    // Polls until `done` holds
+    async fn wait_until(done: impl Fn() -> bool) {
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while !done() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("service did not reach the expected state")
+    }
+
+    // Waits for `count` delivery results and returns them
+    async fn delivered(service: &NotificationService, count: usize) -> Vec<DeliveryResult> {
+        wait_until(|| {
+            service
+                .delivery_results
+                .try_read()
+                .is_ok_and(|results| results.len() >= count)
+        })
+        .await;
+        service.delivery_results.read().await.clone()
+    }
+
+    #[tokio::test]
+    async fn test_sends_over_the_quota_are_deferred_and_reported() {
+        let service = NotificationService::new();
+        service.set_channel_quotas(HashMap::from([(
+            NotificationChannel::InApp,
+            ChannelQuota {
+                limit: 2,
+                window_seconds: 60,
+            },
+        )]));
+        service
+            .create_template(
+                "notice".to_string(),
+                "Notice {{n}}".to_string(),
+                "Body {{n}}".to_string(),
+                vec![NotificationChannel::InApp],
+            )
+            .await;
+        service
+            .subscribe_user(
+                "alice".to_string(),
+                subscription("alice", NotificationChannel::InApp, vec![]),
+            )
+            .await
+            .unwrap();
+
+        for n in 1..=3 {
+            let variables = HashMap::from([("n".to_string(), n.to_string())]);
+            let queued = service
+                .send_notification(
+                    "alice".to_string(),
+                    "notice".to_string(),
+                    variables,
+                    NotificationPriority::Normal,
+                )
+                .await
+                .unwrap();
+            assert_eq!(queued, 1);
+        }
+
+        // Two sends fit the quota; the third waits for the bucket to refill
+        delivered(&service, 2).await;
+        wait_until(|| {
+            service
+                .quotas
+                .usage()
+                .iter()
+                .any(|usage| usage.deferred == 1)
+        })
+        .await;
+        assert_eq!(service.delivery_results.read().await.len(), 2);
+        assert_eq!(
+            service.inbox_messages("alice", false).await.unwrap().len(),
+            2
+        );
+
+        let report = service
+            .call_tool("get_channel_quotas", serde_json::json!({}))
+            .await
+            .unwrap();
+        let quotas = report["quotas"].as_array().unwrap();
+        assert_eq!(quotas.len(), 1);
+        assert_eq!(quotas[0]["channel"], "InApp");
+        assert_eq!(quotas[0]["limit"], 2);
+        assert_eq!(quotas[0]["window_seconds"], 60);
+        assert_eq!(quotas[0]["used"], 2);
+        assert_eq!(quotas[0]["remaining"], 0);
+        assert_eq!(quotas[0]["deferred"], 1);
+        assert!(quotas[0]["reset_seconds"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_failover_follows_the_chain_past_channels_that_are_down() {
+        let service = NotificationService::new();
+        service.set_channel_outage(NotificationChannel::Email, true);
+        service.set_channel_outage(NotificationChannel::Sms, true);
+        service
+            .create_template(
+                "outage".to_string(),
+                "Heads up".to_string(),
+                "Something happened".to_string(),
+                vec![
+                    NotificationChannel::Email,
+                    NotificationChannel::Sms,
+                    NotificationChannel::InApp,
+                ],
+            )
+            .await;
+        service
+            .subscribe_user(
+                "bob".to_string(),
+                subscription(
+                    "bob",
+                    NotificationChannel::Email,
+                    vec![NotificationChannel::Sms, NotificationChannel::InApp],
+                ),
+            )
+            .await
+            .unwrap();
+
+        service
+            .send_notification(
+                "bob".to_string(),
+                "outage".to_string(),
+                HashMap::new(),
+                NotificationPriority::High,
+            )
+            .await
+            .unwrap();
+
+        // Email and SMS each use up their retries before the inbox takes it
+        let results = delivered(&service, 1).await;
+        let result = &results[0];
+        assert!(result.success);
+        assert_eq!(result.delivered_via, Some(NotificationChannel::InApp));
+        assert_eq!(
+            result.channel_path,
+            vec![
+                NotificationChannel::Email,
+                NotificationChannel::Sms,
+                NotificationChannel::InApp,
+            ]
+        );
+        assert_eq!(result.attempt_count, 7);
+        assert_eq!(result.error_message, None);
+        assert_eq!(service.inbox_messages("bob", false).await.unwrap().len(), 1);
+
+        // With every channel down, the last error is reported
+        service.set_channel_outage(NotificationChannel::InApp, true);
+        service
+            .send_notification(
+                "bob".to_string(),
+                "outage".to_string(),
+                HashMap::new(),
+                NotificationPriority::High,
+            )
+            .await
+            .unwrap();
+        let results = delivered(&service, 2).await;
+        let result = &results[1];
+        assert!(!result.success);
+        assert_eq!(result.delivered_via, None);
+        assert_eq!(result.channel_path.len(), 3);
+        assert_eq!(result.attempt_count, 9);
+        assert_eq!(
+            result.error_message.as_deref(),
+            Some("InApp provider is down")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inbox_pages_and_tracks_unread_messages() {
+        let service = NotificationService::new();
+        service
+            .create_template(
+                "notice".to_string(),
+                "Notice {{n}}".to_string(),
+                "Body {{n}}".to_string(),
+                vec![NotificationChannel::InApp],
+            )
+            .await;
+        service
+            .subscribe_user(
+                "carol".to_string(),
+                subscription("carol", NotificationChannel::InApp, vec![]),
+            )
+            .await
+            .unwrap();
+        for n in 1..=5 {
+            let variables = HashMap::from([("n".to_string(), n.to_string())]);
+            service
+                .send_notification(
+                    "carol".to_string(),
+                    "notice".to_string(),
+                    variables,
+                    NotificationPriority::Normal,
+                )
+                .await
+                .unwrap();
+        }
+        delivered(&service, 5).await;
+
+        let resources = service.list_resources().await;
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].uri, "inbox://carol");
+        assert_eq!(
+            resources[0].description.as_deref(),
+            Some("5 messages, 5 unread")
+        );
+
+        // Pages run newest first until next_offset runs out
+        let mut subjects = Vec::new();
+        let mut offset = Some(0);
+        while let Some(next) = offset {
+            let page = service
+                .call_tool(
+                    "list_inbox_messages",
+                    serde_json::json!({ "user_id": "carol", "offset": next, "limit": 2 }),
+                )
+                .await
+                .unwrap();
+            assert_eq!(page["total"], 5);
+            assert!(page["count"].as_u64().unwrap() <= 2);
+            for message in page["messages"].as_array().unwrap() {
+                subjects.push(message["subject"].as_str().unwrap().to_string());
+            }
+            offset = page["next_offset"].as_u64();
+        }
+        assert_eq!(
+            subjects,
+            ["Notice 5", "Notice 4", "Notice 3", "Notice 2", "Notice 1"]
+        );
+
+        let newest = service.inbox_messages("carol", false).await.unwrap()[0].clone();
+        let marked = service
+            .call_tool(
+                "mark_inbox_message_read",
+                serde_json::json!({ "user_id": "carol", "message_id": newest.id }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(marked["read"], true);
+
+        let unread = service
+            .call_tool(
+                "list_inbox_messages",
+                serde_json::json!({ "user_id": "carol", "unread_only": true }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(unread["unread"], 4);
+        assert_eq!(unread["total"], 4);
+        assert_eq!(unread["next_offset"], Value::Null);
+        assert!(unread["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|m| m["id"] != serde_json::json!(newest.id)));
+        assert_eq!(
+            service.list_resources().await[0].description.as_deref(),
+            Some("5 messages, 4 unread")
+        );
+
+        let missing = service
+            .call_tool(
+                "mark_inbox_message_read",
+                serde_json::json!({ "user_id": "carol", "message_id": Uuid::new_v4() }),
+            )
+            .await;
+        assert!(matches!(missing, Err(McpError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_experiment_assignment_is_deterministic_and_counted() {
+        let service = NotificationService::new();
+        for (name, subject) in [("plain", "Your report"), ("bold", "Your report is ready!")] {
+            service
+                .create_template(
+                    name.to_string(),
+                    subject.to_string(),
+                    "See the dashboard".to_string(),
+                    vec![NotificationChannel::InApp],
+                )
+                .await;
+        }
+        let variants = vec![
+            ExperimentVariant {
+                name: "control".to_string(),
+                template_name: "plain".to_string(),
+                weight: 1,
+            },
+            ExperimentVariant {
+                name: "treatment".to_string(),
+                template_name: "bold".to_string(),
+                weight: 1,
+            },
+        ];
+        service
+            .create_experiment("report_subject".to_string(), variants.clone())
+            .await
+            .unwrap();
+
+        // The same name and recipient always pick the same variant
+        let experiment = Experiment {
+            name: "report_subject".to_string(),
+            variants,
+            created_at: Utc::now(),
+        };
+        let users: Vec<String> = (0..20).map(|i| format!("user{}", i)).collect();
+        let mut expected = HashMap::new();
+        for user in &users {
+            service
+                .subscribe_user(
+                    user.clone(),
+                    subscription(user, NotificationChannel::InApp, vec![]),
+                )
+                .await
+                .unwrap();
+            let variant = experiment.assign(user).name.clone();
+            assert_eq!(experiment.assign(user).name, variant);
+
+            let (chosen, sent) = service
+                .send_experiment_notification(
+                    user.clone(),
+                    "report_subject",
+                    HashMap::new(),
+                    NotificationPriority::Normal,
+                )
+                .await
+                .unwrap();
+            assert_eq!(chosen, variant);
+            assert_eq!(sent, 1);
+            *expected.entry(variant).or_insert(0) += 1;
+        }
+        delivered(&service, users.len()).await;
+
+        let stats = service.experiment_stats("report_subject").await.unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].variant, "control");
+        assert_eq!(stats[1].variant, "treatment");
+        for entry in &stats {
+            assert!(entry.deliveries > 0, "{} got no recipients", entry.variant);
+            assert_eq!(entry.deliveries, expected[&entry.variant]);
+            assert_eq!(entry.failures, 0);
+            assert_eq!(entry.success_rate, Some(1.0));
+        }
+
+        // Each recipient got their variant's template
+        for user in &users {
+            let subject = &service.inbox_messages(user, false).await.unwrap()[0].subject;
+            match experiment.assign(user).name.as_str() {
+                "control" => assert_eq!(subject, "Your report"),
+                _ => assert_eq!(subject, "Your report is ready!"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bulk_send_reports_each_recipient_it_could_not_reach() {
+        let service = NotificationService::new();
+        service
+            .create_template(
+                "digest".to_string(),
+                "{{greeting}}, {{name}}".to_string(),
+                "Here is your weekly digest".to_string(),
+                vec![NotificationChannel::InApp],
+            )
+            .await;
+
+        // 100 subscribers, 10 with only a channel the template cannot use,
+        // and 10 users that do not exist, spread over three chunks
+        let mut recipients = Vec::new();
+        for i in 0..120 {
+            let user_id = format!("user{}", i);
+            match i % 12 {
+                10 => {
+                    service
+                        .subscribe_user(
+                            user_id.clone(),
+                            subscription(&user_id, NotificationChannel::Sms, vec![]),
+                        )
+                        .await
+                        .unwrap();
+                }
+                11 => {}
+                _ => {
+                    service
+                        .subscribe_user(
+                            user_id.clone(),
+                            subscription(&user_id, NotificationChannel::InApp, vec![]),
+                        )
+                        .await
+                        .unwrap();
+                }
+            }
+            recipients.push(BulkRecipient {
+                variables: HashMap::from([("name".to_string(), format!("User {}", i))]),
+                user_id,
+                priority: None,
+            });
+        }
+
+        let report = service
+            .send_bulk_notifications(SendBulkRequest {
+                template_name: "digest".to_string(),
+                variables: HashMap::from([
+                    ("greeting".to_string(), "Hi".to_string()),
+                    ("name".to_string(), "there".to_string()),
+                ]),
+                priority: None,
+                recipients,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(report.recipients, 120);
+        assert_eq!(report.chunks, 3);
+        assert_eq!(report.succeeded, 100);
+        assert_eq!(report.notifications_queued, 100);
+        assert_eq!(report.skipped, 10);
+        assert_eq!(report.failed, 10);
+        let mut failed: Vec<&str> = report.failures.iter().map(|f| f.user_id.as_str()).collect();
+        failed.sort_unstable();
+        let mut unknown: Vec<String> = (0..10).map(|k| format!("user{}", k * 12 + 11)).collect();
+        unknown.sort_unstable();
+        assert_eq!(failed, unknown);
+        assert!(report
+            .failures
+            .iter()
+            .all(|f| f.error.contains(&format!("User not found: {}", f.user_id))));
+
+        // Per-recipient variables override the shared ones
+        delivered(&service, 100).await;
+        let inbox = service.inbox_messages("user7", false).await.unwrap();
+        assert_eq!(inbox[0].subject, "Hi, User 7");
+
+        // Problems with the batch as a whole reject it before anything is sent
+        let duplicate = service
+            .send_bulk_notifications(SendBulkRequest {
+                template_name: "digest".to_string(),
+                variables: HashMap::new(),
+                priority: None,
+                recipients: ["user0", "user0"]
+                    .into_iter()
+                    .map(|user_id| BulkRecipient {
+                        user_id: user_id.to_string(),
+                        variables: HashMap::new(),
+                        priority: None,
+                    })
+                    .collect(),
+            })
+            .await;
+        assert!(matches!(duplicate, Err(McpError::InvalidParams(_))));
+        assert_eq!(
+            service.inbox_messages("user0", false).await.unwrap().len(),
+            1
+        );
+    }
+}
@@ -250,7 +250,7 @@ fn demo_data_pipeline() -> Result<(), Box<dyn std::error::Error>> {
 // Entry point demonstrating the data pipeline implementation.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    let _telemetry = mcp_rust_examples::logging::init("info");
 
     info!("Starting Data Pipeline Example");
     demo_data_pipeline()?;
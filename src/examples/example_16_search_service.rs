@@ -202,7 +202,7 @@ fn demo_search_service() -> Result<(), Box<dyn std::error::Error>> {
 // Entry point demonstrating the search service implementation.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    let _telemetry = mcp_rust_examples::logging::init("info");
 
     info!("Starting Search Service Example");
     demo_search_service()?;
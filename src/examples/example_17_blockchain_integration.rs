@@ -263,7 +263,7 @@ fn demo_blockchain() -> Result<(), Box<dyn std::error::Error>> {
 // Entry point demonstrating blockchain implementation.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    let _telemetry = mcp_rust_examples::logging::init("info");
 
     info!("Starting Blockchain Integration Example");
     demo_blockchain()?;
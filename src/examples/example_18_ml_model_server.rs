@@ -343,7 +343,7 @@ fn demo_ml_server() -> Result<(), Box<dyn std::error::Error>> {
 // Entry point demonstrating the ML model server implementation.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    let _telemetry = mcp_rust_examples::logging::init("info");
 
     info!("Starting ML Model Server Example");
     demo_ml_server()?;
@@ -2,13 +2,41 @@
 //
 // This example demonstrates how to build a microservice gateway with
 // service routing, load balancing, and basic service discovery.
+// Slow backends can be hedged: if an endpoint has not answered within a
+// percentile of recent latencies, a second endpoint is tried as well.
+// Every discovered backend is offered to MCP clients as a tool. The tools live
+// in a ToolRegistry, so backends coming and going update the tool list and
+// clients are told to fetch it again. With a service token key configured,
+// every forwarded request carries a short-lived token for its backend, which
+// the backend verifies before answering. Composite routes fan one request out
+// to several backends in parallel and merge their answers into one payload.
 
+use futures::future::BoxFuture;
+use mcp_rust_examples::error::McpError;
+use mcp_rust_examples::protocol::{Tool, ToolAnnotations};
+use mcp_rust_examples::registry::ToolRegistry;
+use mcp_rust_examples::server::{McpServer, ToolHandler};
+use mcp_rust_examples::service_token::{
+    ServiceTokenKey, SERVICE_TOKEN_HEADER, SERVICE_TOKEN_KEY_ENV,
+};
+use mcp_rust_examples::tools::ToolServer;
+use mcp_rust_examples::transport::{self, StdioTransport};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use tracing::{info, warn};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::{info, info_span, warn};
 use uuid::Uuid;
 
+// Route configuration files are read with the loader from example 06
+#[path = "example_06_configurable_server.rs"]
+#[allow(dead_code)]
+mod configurable_server;
+
+use configurable_server::ConfigurableServer;
+
 // Struct: ServiceEndpoint
 //
 // Represents a service endpoint in the gateway.
@@ -21,6 +49,10 @@ pub struct ServiceEndpoint {
     health_check_url: String,
     is_healthy: bool,
     weight: u32,
+    #[serde(default)]
+    protocol: BackendProtocol,
+    #[serde(default)]
+    simulated_latency_ms: u64, // How long the mock backend takes to answer
 }
 
 impl ServiceEndpoint {
@@ -33,8 +65,393 @@ impl ServiceEndpoint {
             health_check_url: format!("http://{}:{}/health", host, port),
             is_healthy: true,
             weight: 1,
+            protocol: BackendProtocol::Http,
+            simulated_latency_ms: 0,
+        }
+    }
+
+    // Makes the mock backend answer after the given delay
+    pub fn with_simulated_latency(mut self, latency_ms: u64) -> Self {
+        self.simulated_latency_ms = latency_ms;
+        self
+    }
+
+    // Creates an endpoint for a gRPC backend. Requests are transcoded from JSON
+    // using the method descriptors registered with the gateway.
+    pub fn grpc(service_name: String, host: String, port: u16) -> Self {
+        Self {
+            health_check_url: format!("grpc://{}:{}/grpc.health.v1.Health/Check", host, port),
+            protocol: BackendProtocol::Grpc,
+            ..Self::new(service_name, host, port)
+        }
+    }
+}
+
+// Enum: BackendProtocol
+//
+// The wire protocol spoken by a backend endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum BackendProtocol {
+    #[default]
+    Http,
+    Grpc,
+}
+
+// Enum: ProtoFieldType
+//
+// The protobuf scalar types supported by the transcoder.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtoFieldType {
+    String,
+    Int64,
+    Bool,
+    Double,
+}
+
+// Struct: ProtoField
+//
+// A single field in a protobuf message descriptor.
+#[derive(Debug, Clone)]
+pub struct ProtoField {
+    name: String,
+    number: u32,
+    field_type: ProtoFieldType,
+}
+
+impl ProtoField {
+    pub fn new(name: &str, number: u32, field_type: ProtoFieldType) -> Self {
+        Self {
+            name: name.to_string(),
+            number,
+            field_type,
+        }
+    }
+}
+
+// Struct: MessageDescriptor
+//
+// Describes the layout of a protobuf message, enough to translate it to and
+// from JSON without generated code.
+#[derive(Debug, Clone)]
+pub struct MessageDescriptor {
+    name: String,
+    fields: Vec<ProtoField>,
+}
+
+impl MessageDescriptor {
+    pub fn new(name: &str, fields: Vec<ProtoField>) -> Self {
+        Self {
+            name: name.to_string(),
+            fields,
+        }
+    }
+}
+
+// Struct: GrpcMethodDescriptor
+//
+// Describes a unary gRPC method: its fully-qualified service and the request
+// and response message types.
+#[derive(Debug, Clone)]
+pub struct GrpcMethodDescriptor {
+    service: String, // e.g. "inventory.v1.InventoryService"
+    method: String,  // e.g. "GetStock"
+    input: MessageDescriptor,
+    output: MessageDescriptor,
+}
+
+impl GrpcMethodDescriptor {
+    pub fn new(
+        service: &str,
+        method: &str,
+        input: MessageDescriptor,
+        output: MessageDescriptor,
+    ) -> Self {
+        Self {
+            service: service.to_string(),
+            method: method.to_string(),
+            input,
+            output,
+        }
+    }
+
+    // The HTTP/2 `:path` used for this method
+    pub fn path(&self) -> String {
+        format!("/{}/{}", self.service, self.method)
+    }
+}
+
+// Struct: GrpcTranscoder
+//
+// Translates JSON objects to protobuf-encoded gRPC frames and back, driven by
+// message descriptors. Only scalar fields are supported, which is enough to
+// show the mechanics without pulling in a full protobuf runtime.
+pub struct GrpcTranscoder;
+
+impl GrpcTranscoder {
+    // Encodes a JSON object as a length-prefixed gRPC message frame
+    pub fn encode_request(descriptor: &MessageDescriptor, json: &Value) -> Result<Vec<u8>, String> {
+        let object = json
+            .as_object()
+            .ok_or_else(|| format!("{} must be a JSON object", descriptor.name))?;
+
+        if let Some(unknown) = object
+            .keys()
+            .find(|key| !descriptor.fields.iter().any(|f| &f.name == *key))
+        {
+            return Err(format!(
+                "Unknown field '{}' for {}",
+                unknown, descriptor.name
+            ));
+        }
+
+        let mut message = Vec::new();
+        for field in &descriptor.fields {
+            let Some(value) = object.get(&field.name).filter(|v| !v.is_null()) else {
+                continue;
+            };
+            let type_error = || format!("Field '{}' has the wrong type", field.name);
+
+            match field.field_type {
+                ProtoFieldType::String => {
+                    let text = value.as_str().ok_or_else(type_error)?;
+                    write_varint(&mut message, ((field.number as u64) << 3) | 2);
+                    write_varint(&mut message, text.len() as u64);
+                    message.extend_from_slice(text.as_bytes());
+                }
+                ProtoFieldType::Int64 => {
+                    // proto3 JSON allows int64 values to be encoded as strings
+                    let number = value
+                        .as_i64()
+                        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+                        .ok_or_else(type_error)?;
+                    write_varint(&mut message, (field.number as u64) << 3);
+                    write_varint(&mut message, number as u64);
+                }
+                ProtoFieldType::Bool => {
+                    let flag = value.as_bool().ok_or_else(type_error)?;
+                    write_varint(&mut message, (field.number as u64) << 3);
+                    write_varint(&mut message, flag as u64);
+                }
+                ProtoFieldType::Double => {
+                    let number = value.as_f64().ok_or_else(type_error)?;
+                    write_varint(&mut message, ((field.number as u64) << 3) | 1);
+                    message.extend_from_slice(&number.to_le_bytes());
+                }
+            }
+        }
+
+        // gRPC framing: 1-byte compressed flag + 4-byte big-endian length
+        let mut frame = Vec::with_capacity(message.len() + 5);
+        frame.push(0);
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&message);
+        Ok(frame)
+    }
+
+    // Decodes a length-prefixed gRPC message frame into a JSON object.
+    // Missing fields are emitted with their proto3 default values.
+    pub fn decode_response(descriptor: &MessageDescriptor, frame: &[u8]) -> Result<Value, String> {
+        if frame.len() < 5 {
+            return Err("gRPC frame is truncated".to_string());
+        }
+        if frame[0] != 0 {
+            return Err("Compressed gRPC frames are not supported".to_string());
+        }
+        let length = u32::from_be_bytes([frame[1], frame[2], frame[3], frame[4]]) as usize;
+        let message = field_bytes(frame, 5, length).ok_or("gRPC frame length exceeds payload")?;
+
+        let mut object = serde_json::Map::new();
+        for field in &descriptor.fields {
+            let default = match field.field_type {
+                ProtoFieldType::String => Value::from(""),
+                ProtoFieldType::Int64 => Value::from(0),
+                ProtoFieldType::Bool => Value::from(false),
+                ProtoFieldType::Double => Value::from(0.0),
+            };
+            object.insert(field.name.clone(), default);
+        }
+
+        let mut cursor = 0;
+        while cursor < message.len() {
+            let key = read_varint(message, &mut cursor)?;
+            let number = (key >> 3) as u32;
+            let wire_type = key & 0x7;
+            let field = descriptor.fields.iter().find(|f| f.number == number);
+
+            let value = match wire_type {
+                0 => {
+                    let raw = read_varint(message, &mut cursor)?;
+                    match field.map(|f| &f.field_type) {
+                        Some(ProtoFieldType::Bool) => Some(Value::from(raw != 0)),
+                        Some(ProtoFieldType::Int64) => Some(Value::from(raw as i64)),
+                        _ => None,
+                    }
+                }
+                1 => {
+                    let bytes = field_bytes(message, cursor, 8).ok_or("Truncated fixed64 field")?;
+                    cursor += 8;
+                    match field.map(|f| &f.field_type) {
+                        Some(ProtoFieldType::Double) => Some(Value::from(f64::from_le_bytes(
+                            bytes.try_into().expect("slice has 8 bytes"),
+                        ))),
+                        _ => None,
+                    }
+                }
+                2 => {
+                    let length = usize::try_from(read_varint(message, &mut cursor)?)
+                        .map_err(|_| "Length-delimited field is too long")?;
+                    let bytes = field_bytes(message, cursor, length)
+                        .ok_or("Truncated length-delimited field")?;
+                    cursor += length;
+                    match field.map(|f| &f.field_type) {
+                        Some(ProtoFieldType::String) => Some(Value::from(
+                            String::from_utf8(bytes.to_vec())
+                                .map_err(|_| "Invalid UTF-8 in string field")?,
+                        )),
+                        _ => None,
+                    }
+                }
+                5 => {
+                    field_bytes(message, cursor, 4).ok_or("Truncated fixed32 field")?;
+                    cursor += 4;
+                    None
+                }
+                other => return Err(format!("Unsupported wire type {}", other)),
+            };
+
+            // Unknown fields are skipped, as protobuf requires
+            if let (Some(field), Some(value)) = (field, value) {
+                object.insert(field.name.clone(), value);
+            }
+        }
+
+        Ok(Value::Object(object))
+    }
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+// The `length` bytes at `start`, or None if they run past the end of the
+// buffer (or past usize::MAX, for a corrupt length prefix)
+fn field_bytes(buffer: &[u8], start: usize, length: usize) -> Option<&[u8]> {
+    buffer.get(start..start.checked_add(length)?)
+}
+
+fn read_varint(buffer: &[u8], cursor: &mut usize) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buffer.get(*cursor).ok_or("Truncated varint")?;
+        *cursor += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("Varint is too long".to_string())
+}
+
+// Struct: TraceContext
+//
+// W3C Trace Context (https://www.w3.org/TR/trace-context/) carried in the
+// `traceparent` and `tracestate` headers. Each hop keeps the trace ID and
+// generates a new span ID, recording the caller's span as its parent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceContext {
+    trace_id: String,               // 32 lowercase hex characters
+    span_id: String,                // 16 lowercase hex characters
+    parent_span_id: Option<String>, // Span ID of the caller, if any
+    sampled: bool,
+    tracestate: Option<String>, // Vendor-specific state, forwarded untouched
+}
+
+impl TraceContext {
+    // Starts a new trace with a fresh trace ID and no parent
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: format!("{:032x}", rand::random::<u128>().max(1)),
+            span_id: Self::new_span_id(),
+            parent_span_id: None,
+            sampled: true,
+            tracestate: None,
+        }
+    }
+
+    // Parses incoming `traceparent`/`tracestate` headers. Returns None if the
+    // traceparent is missing or malformed, in which case a new trace is started.
+    pub fn from_headers(headers: &HashMap<String, String>) -> Option<Self> {
+        let traceparent = headers.get("traceparent")?;
+        let parts: Vec<&str> = traceparent.trim().split('-').collect();
+
+        let [version, trace_id, parent_id, flags] = parts.as_slice() else {
+            return None;
+        };
+
+        let is_hex = |value: &str, len: usize| {
+            value.len() == len
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+        };
+
+        if *version != "00"
+            || !is_hex(trace_id, 32)
+            || !is_hex(parent_id, 16)
+            || !is_hex(flags, 2)
+            || trace_id.chars().all(|c| c == '0')
+            || parent_id.chars().all(|c| c == '0')
+        {
+            return None;
+        }
+
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            span_id: parent_id.to_string(),
+            parent_span_id: None,
+            sampled: flags & 0x01 == 0x01,
+            tracestate: headers.get("tracestate").cloned(),
+        })
+    }
+
+    // Creates the context for the next hop: same trace, new span, parented to this one
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id.clone(),
+            span_id: Self::new_span_id(),
+            parent_span_id: Some(self.span_id.clone()),
+            sampled: self.sampled,
+            tracestate: self.tracestate.clone(),
         }
     }
+
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            self.trace_id,
+            self.span_id,
+            if self.sampled { 0x01 } else { 0x00 }
+        )
+    }
+
+    // Writes the propagation headers for an outgoing request
+    pub fn inject(&self, headers: &mut HashMap<String, String>) {
+        headers.insert("traceparent".to_string(), self.traceparent());
+        match &self.tracestate {
+            Some(state) => headers.insert("tracestate".to_string(), state.clone()),
+            None => headers.remove("tracestate"),
+        };
+    }
+
+    fn new_span_id() -> String {
+        format!("{:016x}", rand::random::<u64>().max(1))
+    }
 }
 
 // Struct: GatewayRequest
@@ -45,11 +462,8 @@ pub struct GatewayRequest {
     id: Uuid,
     service_name: String,
     path: String,
-    #[allow(dead_code)]
     method: String,
-    #[allow(dead_code)]
     headers: HashMap<String, String>,
-    #[allow(dead_code)]
     body: Option<String>,
 }
 
@@ -64,6 +478,16 @@ impl GatewayRequest {
             body: None,
         }
     }
+
+    pub fn with_body(mut self, body: &str) -> Self {
+        self.body = Some(body.to_string());
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
 }
 
 // Struct: GatewayResponse
@@ -73,20 +497,19 @@ impl GatewayRequest {
 pub struct GatewayResponse {
     #[allow(dead_code)]
     request_id: Uuid,
-    #[allow(dead_code)]
     status_code: u16,
     #[allow(dead_code)]
     headers: HashMap<String, String>,
-    #[allow(dead_code)]
     body: String,
     response_time_ms: u64,
     service_endpoint: String,
+    trace_id: String,
 }
 
 // Enum: LoadBalancingStrategy
 //
 // Defines different load balancing strategies.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LoadBalancingStrategy {
     RoundRobin,
     WeightedRoundRobin,
@@ -135,6 +558,28 @@ impl ServiceRegistry {
         );
     }
 
+    // Removes every endpoint of a service, e.g. when it leaves the cluster
+    pub fn deregister_service(&mut self, service_name: &str) -> bool {
+        self.round_robin_counters.remove(service_name);
+        let removed = self.services.remove(service_name).is_some();
+        if removed {
+            info!("Deregistered service: {}", service_name);
+        }
+        removed
+    }
+
+    // Names of the services with at least one healthy endpoint, sorted
+    pub fn discovered_services(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .services
+            .iter()
+            .filter(|(_, endpoints)| endpoints.iter().any(|endpoint| endpoint.is_healthy))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
     pub fn get_healthy_endpoints(&self, service_name: &str) -> Vec<&ServiceEndpoint> {
         self.services
             .get(service_name)
@@ -217,118 +662,1091 @@ pub struct ServiceStats {
     unhealthy_endpoints: usize,
 }
 
-// Struct: MicroserviceGateway
+// Struct: RouteConfig
 //
-// Main gateway that handles routing and load balancing.
-pub struct MicroserviceGateway {
-    service_registry: ServiceRegistry,
-    load_balancing_strategy: LoadBalancingStrategy,
-    request_count: u64,
-    total_response_time: u64,
-    route_mappings: HashMap<String, String>, // path prefix -> service name
+// A single route mapping together with its per-route policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteConfig {
+    path_prefix: String,
+    service_name: String,
+    #[serde(default)]
+    strategy: Option<LoadBalancingStrategy>, // Overrides the gateway default
+    #[serde(default)]
+    allowed_methods: Vec<String>, // Empty means all methods are allowed
+    #[serde(default)]
+    hedging: Option<HedgingPolicy>, // Hedge slow requests on this route
 }
 
-impl MicroserviceGateway {
-    pub fn new(strategy: LoadBalancingStrategy) -> Self {
+// Struct: HedgingPolicy
+//
+// Controls when a second request is sent for a slow route. The hedge delay is
+// the given percentile of the service's recent latencies, so only the slowest
+// requests are duplicated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HedgingPolicy {
+    #[serde(default = "default_hedge_percentile")]
+    percentile: f64, // In (0, 1], e.g. 0.95 for p95
+    #[serde(default = "default_hedge_initial_delay_ms")]
+    initial_delay_ms: u64, // Used until enough latencies have been observed
+    #[serde(default = "default_hedge_min_samples")]
+    min_samples: usize,
+    #[serde(default)]
+    min_delay_ms: u64, // Lower bound, so fast services are not hedged constantly
+}
+
+fn default_hedge_percentile() -> f64 {
+    0.95
+}
+
+fn default_hedge_initial_delay_ms() -> u64 {
+    100
+}
+
+fn default_hedge_min_samples() -> usize {
+    10
+}
+
+impl Default for HedgingPolicy {
+    fn default() -> Self {
         Self {
-            service_registry: ServiceRegistry::new(),
-            load_balancing_strategy: strategy,
-            request_count: 0,
-            total_response_time: 0,
-            route_mappings: HashMap::new(),
+            percentile: default_hedge_percentile(),
+            initial_delay_ms: default_hedge_initial_delay_ms(),
+            min_samples: default_hedge_min_samples(),
+            min_delay_ms: 0,
         }
     }
+}
 
-    pub fn register_service(&mut self, endpoint: ServiceEndpoint) {
-        self.service_registry.register_service(endpoint);
+impl HedgingPolicy {
+    pub fn with_percentile(mut self, percentile: f64) -> Self {
+        self.percentile = percentile;
+        self
     }
 
-    pub fn add_route(&mut self, path_prefix: String, service_name: String) {
-        self.route_mappings
-            .insert(path_prefix.clone(), service_name.clone());
-        info!("Added route: {} -> {}", path_prefix, service_name);
+    // Works out how long to wait before hedging, given recent latencies
+    pub fn hedge_delay(&self, samples: &VecDeque<u64>) -> std::time::Duration {
+        let delay_ms = if samples.len() < self.min_samples.max(1) {
+            self.initial_delay_ms
+        } else {
+            let mut sorted: Vec<u64> = samples.iter().copied().collect();
+            sorted.sort_unstable();
+            // Nearest-rank percentile
+            let rank = (self.percentile * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        std::time::Duration::from_millis(delay_ms.max(self.min_delay_ms))
     }
+}
 
-    pub fn resolve_service(&self, path: &str) -> Option<String> {
-        // Find the longest matching prefix
-        self.route_mappings
-            .iter()
-            .filter(|(prefix, _)| path.starts_with(*prefix))
-            .max_by_key(|(prefix, _)| prefix.len())
-            .map(|(_, service)| service.clone())
-    }
+// Enum: HedgeOutcome
+//
+// What happened to a request on the hedged path, for the hedge statistics.
+enum HedgeOutcome {
+    NotHedgeable,  // The route has no hedging policy
+    Answered,      // The primary answered before the hedge delay
+    NoHedgeTarget, // Slow, but there was no second healthy endpoint
+    PrimaryWon,
+    HedgeWon { time_saved_ms: u64 },
+}
 
-    pub fn handle_request(
-        &mut self,
-        mut request: GatewayRequest,
-    ) -> Result<GatewayResponse, String> {
-        let start_time = std::time::Instant::now();
+// Number of recent latencies kept per service for the hedge delay
+const LATENCY_WINDOW: usize = 100;
 
-        // Resolve service from path if not explicitly set
-        if request.service_name.is_empty() {
-            request.service_name = self
-                .resolve_service(&request.path)
-                .ok_or("No route found for path")?;
+// The gateway's identity in the service tokens it attaches
+const GATEWAY_SERVICE_NAME: &str = "gateway";
+const SERVICE_TOKEN_TTL_SECONDS: i64 = 60;
+
+// Struct: HedgeStats
+//
+// Tracks how often hedging fires and whether the hedge actually helped.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HedgeStats {
+    hedgeable_requests: u64, // Requests on routes with a hedging policy
+    hedges_sent: u64,
+    hedge_wins: u64,      // The hedge answered first
+    primary_wins: u64,    // A hedge was sent but the primary still answered first
+    no_hedge_target: u64, // Slow requests with no second healthy endpoint
+    time_saved_ms: u64,   // Primary time still outstanding when a hedge won
+}
+
+impl HedgeStats {
+    // Share of hedged requests where the hedge beat the primary
+    pub fn hedge_win_rate(&self) -> f64 {
+        if self.hedges_sent == 0 {
+            0.0
+        } else {
+            self.hedge_wins as f64 / self.hedges_sent as f64
         }
+    }
+}
 
-        // Select an endpoint using load balancing
-        let endpoint = self
-            .service_registry
-            .select_endpoint(&request.service_name, &self.load_balancing_strategy)
-            .ok_or("No healthy endpoints available")?;
+impl RouteConfig {
+    pub fn new(path_prefix: String, service_name: String) -> Self {
+        Self {
+            path_prefix,
+            service_name,
+            strategy: None,
+            allowed_methods: Vec::new(),
+            hedging: None,
+        }
+    }
+}
 
-        // Simulate request forwarding
-        let response = self.forward_request(&request, endpoint)?;
+// Struct: CompositeRoute
+//
+// A path answered by calling several backends in parallel and merging their
+// JSON responses into one payload, the way a backend-for-frontend saves a
+// client several round trips. Each part is an ordinary request through the
+// routing table, so route policies, load balancing and tokens still apply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompositeRoute {
+    path: String, // Matched exactly, ignoring the query string
+    parts: Vec<CompositePart>,
+}
 
-        let response_time = start_time.elapsed().as_millis() as u64;
+// Struct: CompositePart
+//
+// One backend call of a composite route. Its response lands under `name` in
+// the merged payload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompositePart {
+    name: String,
+    path: String, // Routed like a request path; gets the caller's query string
+    #[serde(default = "default_part_timeout_ms")]
+    timeout_ms: u64,
+    #[serde(default)]
+    required: bool, // Fail the whole request instead of answering without it
+}
 
-        // Update statistics
-        self.request_count += 1;
-        self.total_response_time += response_time;
+fn default_part_timeout_ms() -> u64 {
+    1000
+}
 
-        info!(
-            "Request {} routed to {}:{} in {}ms",
-            request.id, endpoint.host, endpoint.port, response_time
-        );
+impl CompositeRoute {
+    pub fn new(path: &str, parts: Vec<CompositePart>) -> Self {
+        Self {
+            path: path.to_string(),
+            parts,
+        }
+    }
+}
 
-        Ok(GatewayResponse {
-            request_id: request.id,
-            status_code: response.status_code,
-            headers: response.headers,
-            body: response.body,
-            response_time_ms: response_time,
-            service_endpoint: format!("{}:{}", endpoint.host, endpoint.port),
-        })
+impl CompositePart {
+    pub fn new(name: &str, path: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            timeout_ms: default_part_timeout_ms(),
+            required: false,
+        }
     }
 
-    fn forward_request(
-        &self,
-        request: &GatewayRequest,
-        endpoint: &ServiceEndpoint,
-    ) -> Result<MockResponse, String> {
-        // Simulate request forwarding (in real implementation, use HTTP client)
-        // Note: In async context, this would need to be awaited
-        // tokio::time::sleep(tokio::time::Duration::from_millis(10)).await; // Simulate network delay
+    pub fn with_timeout(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
 
-        // Mock successful response
-        Ok(MockResponse {
-            status_code: 200,
-            headers: {
-                let mut headers = HashMap::new();
-                headers.insert("Content-Type".to_string(), "application/json".to_string());
-                headers.insert("X-Service".to_string(), endpoint.service_name.clone());
-                headers
-            },
-            body: format!(
-                r#"{{"message": "Response from {}", "request_id": "{}"}}"#,
-                endpoint.service_name, request.id
-            ),
-        })
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
     }
+}
 
-    pub fn get_statistics(&self) -> GatewayStatistics {
-        let avg_response_time = if self.request_count > 0 {
-            self.total_response_time as f64 / self.request_count as f64
+// Struct: GatewayConfig
+//
+// Routing configuration loaded from a JSON file at startup and on every change.
+// With MCP_GATEWAY_CONFIG set, the stdio server routes by that file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayConfig {
+    default_strategy: LoadBalancingStrategy,
+    routes: Vec<RouteConfig>,
+    #[serde(default)]
+    composites: Vec<CompositeRoute>,
+}
+
+impl GatewayConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        const METHODS: [&str; 7] = ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"];
+        let mut seen = std::collections::HashSet::new();
+
+        for route in &self.routes {
+            if !route.path_prefix.starts_with('/') {
+                return Err(format!(
+                    "Route prefix '{}' must start with '/'",
+                    route.path_prefix
+                ));
+            }
+            if route.service_name.trim().is_empty() {
+                return Err(format!(
+                    "Route '{}' has an empty service name",
+                    route.path_prefix
+                ));
+            }
+            if !seen.insert(route.path_prefix.as_str()) {
+                return Err(format!("Duplicate route prefix '{}'", route.path_prefix));
+            }
+            if let Some(hedging) = &route.hedging {
+                if !(hedging.percentile > 0.0 && hedging.percentile <= 1.0) {
+                    return Err(format!(
+                        "Route '{}' has a hedging percentile outside (0, 1]",
+                        route.path_prefix
+                    ));
+                }
+            }
+            if let Some(method) = route
+                .allowed_methods
+                .iter()
+                .find(|m| !METHODS.contains(&m.as_str()))
+            {
+                return Err(format!(
+                    "Route '{}' allows unknown method '{}'",
+                    route.path_prefix, method
+                ));
+            }
+        }
+
+        let mut composite_paths = std::collections::HashSet::new();
+        for composite in &self.composites {
+            if !composite.path.starts_with('/') {
+                return Err(format!(
+                    "Composite path '{}' must start with '/'",
+                    composite.path
+                ));
+            }
+            if !composite_paths.insert(composite.path.as_str()) {
+                return Err(format!("Duplicate composite path '{}'", composite.path));
+            }
+            if composite.parts.is_empty() {
+                return Err(format!("Composite '{}' has no parts", composite.path));
+            }
+            let mut part_names = std::collections::HashSet::new();
+            for part in &composite.parts {
+                if part.name.trim().is_empty() || !part_names.insert(part.name.as_str()) {
+                    return Err(format!(
+                        "Composite '{}' needs a unique, non-empty name for every part",
+                        composite.path
+                    ));
+                }
+                if !part.path.starts_with('/') {
+                    return Err(format!(
+                        "Part '{}' of composite '{}' must have a path starting with '/'",
+                        part.name, composite.path
+                    ));
+                }
+                if part.timeout_ms == 0 {
+                    return Err(format!(
+                        "Part '{}' of composite '{}' has a zero timeout",
+                        part.name, composite.path
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Struct: RoutingTable
+//
+// Immutable snapshot of the routes. Reloads build a new table and swap the
+// Arc, so in-flight requests keep using the table they started with.
+#[derive(Debug)]
+pub struct RoutingTable {
+    default_strategy: LoadBalancingStrategy,
+    routes: HashMap<String, RouteConfig>, // path prefix -> route
+    composites: HashMap<String, CompositeRoute>, // exact path -> composite
+}
+
+impl RoutingTable {
+    fn from_config(config: GatewayConfig) -> Self {
+        Self {
+            default_strategy: config.default_strategy,
+            routes: config
+                .routes
+                .into_iter()
+                .map(|route| (route.path_prefix.clone(), route))
+                .collect(),
+            composites: config
+                .composites
+                .into_iter()
+                .map(|composite| (composite.path.clone(), composite))
+                .collect(),
+        }
+    }
+
+    fn resolve(&self, path: &str) -> Option<&RouteConfig> {
+        // Find the longest matching prefix
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(*prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, route)| route)
+    }
+}
+
+pub type RoutingTableHandle = Arc<RwLock<Arc<RoutingTable>>>;
+
+// Function: spawn_config_watcher
+//
+// Polls the config file and atomically swaps in a new routing table whenever
+// its contents change. Invalid configs are logged and the current table is kept.
+pub fn spawn_config_watcher(
+    path: PathBuf,
+    routing_table: RoutingTableHandle,
+    poll_interval: std::time::Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last_content = std::fs::read_to_string(&path).ok();
+        let mut interval = tokio::time::interval(poll_interval);
+
+        loop {
+            interval.tick().await;
+
+            let content = std::fs::read_to_string(&path).ok();
+            if content == last_content {
+                continue;
+            }
+            last_content = content;
+
+            let loaded = ConfigurableServer::read_config_file::<GatewayConfig>(&path)
+                .and_then(|config| config.validate().map(|()| config));
+            match loaded {
+                Ok(config) => {
+                    let route_count = config.routes.len();
+                    let table = Arc::new(RoutingTable::from_config(config));
+                    *routing_table.write().unwrap() = table;
+                    info!(
+                        "Reloaded routing table from {} ({} routes)",
+                        path.display(),
+                        route_count
+                    );
+                }
+                Err(e) => warn!("Ignoring invalid gateway config: {}", e),
+            }
+        }
+    })
+}
+
+// Struct: MicroserviceGateway
+//
+// Main gateway that handles routing and load balancing.
+pub struct MicroserviceGateway {
+    service_registry: ServiceRegistry,
+    request_count: u64,
+    total_response_time: u64,
+    routing_table: RoutingTableHandle,
+    grpc_methods: HashMap<String, HashMap<String, GrpcMethodDescriptor>>, // service -> method -> descriptor
+    latency_samples: HashMap<String, VecDeque<u64>>, // service -> recent latencies in ms
+    hedge_stats: HedgeStats,
+    service_tokens: Option<ServiceTokenKey>,
+}
+
+impl MicroserviceGateway {
+    pub fn new(strategy: LoadBalancingStrategy) -> Self {
+        Self {
+            service_registry: ServiceRegistry::new(),
+            request_count: 0,
+            total_response_time: 0,
+            routing_table: Arc::new(RwLock::new(Arc::new(RoutingTable {
+                default_strategy: strategy,
+                routes: HashMap::new(),
+                composites: HashMap::new(),
+            }))),
+            grpc_methods: HashMap::new(),
+            latency_samples: HashMap::new(),
+            hedge_stats: HedgeStats::default(),
+            service_tokens: None,
+        }
+    }
+
+    // Attaches a service token for the target service to every forwarded
+    // request, and has the mock backends require one
+    pub fn with_service_tokens(mut self, key: ServiceTokenKey) -> Self {
+        self.service_tokens = Some(key);
+        self
+    }
+
+    pub fn from_config(config: GatewayConfig) -> Result<Self, String> {
+        let gateway = Self::new(config.default_strategy.clone());
+        gateway.apply_config(config)?;
+        Ok(gateway)
+    }
+
+    // Validates the config and atomically replaces the routing table
+    pub fn apply_config(&self, config: GatewayConfig) -> Result<(), String> {
+        config.validate()?;
+        *self.routing_table.write().unwrap() = Arc::new(RoutingTable::from_config(config));
+        Ok(())
+    }
+
+    pub fn routing_table_handle(&self) -> RoutingTableHandle {
+        self.routing_table.clone()
+    }
+
+    fn routing_snapshot(&self) -> Arc<RoutingTable> {
+        self.routing_table.read().unwrap().clone()
+    }
+
+    pub fn register_service(&mut self, endpoint: ServiceEndpoint) {
+        self.service_registry.register_service(endpoint);
+    }
+
+    pub fn deregister_service(&mut self, service_name: &str) -> bool {
+        self.service_registry.deregister_service(service_name)
+    }
+
+    pub fn discovered_services(&self) -> Vec<String> {
+        self.service_registry.discovered_services()
+    }
+
+    // Registers a unary gRPC method for a gateway service. Requests routed to a
+    // gRPC endpoint of that service call the method named by the last path segment.
+    pub fn register_grpc_method(&mut self, service_name: &str, descriptor: GrpcMethodDescriptor) {
+        info!(
+            "Registered gRPC method {} for {}",
+            descriptor.path(),
+            service_name
+        );
+        self.grpc_methods
+            .entry(service_name.to_string())
+            .or_default()
+            .insert(descriptor.method.clone(), descriptor);
+    }
+
+    pub fn add_route(&mut self, path_prefix: String, service_name: String) {
+        let mut table = self.routing_table.write().unwrap();
+        let mut routes = table.routes.clone();
+        routes.insert(
+            path_prefix.clone(),
+            RouteConfig::new(path_prefix.clone(), service_name.clone()),
+        );
+        *table = Arc::new(RoutingTable {
+            default_strategy: table.default_strategy.clone(),
+            routes,
+            composites: table.composites.clone(),
+        });
+        info!("Added route: {} -> {}", path_prefix, service_name);
+    }
+
+    // Enables hedging on an existing route
+    pub fn set_route_hedging(
+        &mut self,
+        path_prefix: &str,
+        policy: HedgingPolicy,
+    ) -> Result<(), String> {
+        let mut table = self.routing_table.write().unwrap();
+        let mut routes = table.routes.clone();
+        routes
+            .get_mut(path_prefix)
+            .ok_or_else(|| format!("No route with prefix '{}'", path_prefix))?
+            .hedging = Some(policy);
+        *table = Arc::new(RoutingTable {
+            default_strategy: table.default_strategy.clone(),
+            routes,
+            composites: table.composites.clone(),
+        });
+        Ok(())
+    }
+
+    // Adds or replaces a composite route
+    pub fn add_composite_route(&mut self, composite: CompositeRoute) -> Result<(), String> {
+        let mut table = self.routing_table.write().unwrap();
+        let mut composites = table.composites.clone();
+        composites.insert(composite.path.clone(), composite);
+        let config = GatewayConfig {
+            default_strategy: table.default_strategy.clone(),
+            routes: table.routes.values().cloned().collect(),
+            composites: composites.into_values().collect(),
+        };
+        config.validate()?;
+        *table = Arc::new(RoutingTable::from_config(config));
+        Ok(())
+    }
+
+    pub fn resolve_service(&self, path: &str) -> Option<String> {
+        self.routing_snapshot()
+            .resolve(path)
+            .map(|route| route.service_name.clone())
+    }
+
+    pub fn handle_request(
+        &mut self,
+        mut request: GatewayRequest,
+    ) -> Result<GatewayResponse, String> {
+        let start_time = std::time::Instant::now();
+
+        // Continue the caller's trace, or start a new one at the edge
+        let incoming = TraceContext::from_headers(&request.headers);
+        let gateway_context = match &incoming {
+            Some(parent) => parent.child(),
+            None => TraceContext::new_root(),
+        };
+        let span = info_span!(
+            "gateway.request",
+            trace_id = %gateway_context.trace_id,
+            span_id = %gateway_context.span_id,
+            parent_span_id = gateway_context.parent_span_id.as_deref().unwrap_or(""),
+            path = %request.path,
+        );
+        let _guard = span.enter();
+
+        // Take a snapshot so a concurrent reload cannot change routing mid-request
+        let routing_table = self.routing_snapshot();
+        let route = Self::apply_route(&routing_table, &mut request)?;
+
+        let strategy = route
+            .and_then(|route| route.strategy.as_ref())
+            .unwrap_or(&routing_table.default_strategy);
+
+        // Select an endpoint using load balancing
+        let endpoint = self
+            .service_registry
+            .select_endpoint(&request.service_name, strategy)
+            .ok_or("No healthy endpoints available")?;
+
+        // Propagate the trace to the backend as a child of the gateway span
+        let backend_context = gateway_context.child();
+        backend_context.inject(&mut request.headers);
+        self.attach_service_token(&mut request);
+
+        // Simulate request forwarding
+        let response = self.forward_request(&request, endpoint)?;
+
+        let response_time = start_time.elapsed().as_millis() as u64;
+
+        // Update statistics
+        self.request_count += 1;
+        self.total_response_time += response_time;
+
+        info!(
+            "Request {} routed to {}:{} in {}ms",
+            request.id, endpoint.host, endpoint.port, response_time
+        );
+
+        Ok(GatewayResponse {
+            request_id: request.id,
+            status_code: response.status_code,
+            headers: response.headers,
+            body: response.body,
+            response_time_ms: response_time,
+            service_endpoint: format!("{}:{}", endpoint.host, endpoint.port),
+            trace_id: gateway_context.trace_id,
+        })
+    }
+
+    // Resolves the route for a request, fills in the service name if it was not
+    // set explicitly, and enforces the route's method policy
+    fn apply_route<'a>(
+        routing_table: &'a RoutingTable,
+        request: &mut GatewayRequest,
+    ) -> Result<Option<&'a RouteConfig>, String> {
+        let route = routing_table.resolve(&request.path);
+
+        // Resolve service from path if not explicitly set
+        if request.service_name.is_empty() {
+            request.service_name = route
+                .map(|route| route.service_name.clone())
+                .ok_or("No route found for path")?;
+        }
+
+        // Enforce the per-route method policy
+        if let Some(route) = route {
+            if !route.allowed_methods.is_empty()
+                && !route
+                    .allowed_methods
+                    .iter()
+                    .any(|m| m.eq_ignore_ascii_case(&request.method))
+            {
+                return Err(format!(
+                    "Method {} not allowed on {}",
+                    request.method, route.path_prefix
+                ));
+            }
+        }
+
+        Ok(route)
+    }
+
+    // Function: handle_request_hedged
+    //
+    // Like handle_request, but waits on the backend asynchronously. On routes
+    // with a hedging policy, if the first endpoint has not answered within the
+    // hedge delay, the same request is sent to another healthy endpoint. The
+    // first successful response wins and the other request is cancelled by
+    // dropping its future.
+    //
+    // Arguments:
+    //     request: The incoming request
+    //
+    // Returns:
+    //     Result with the winning response
+    pub async fn handle_request_hedged(
+        &mut self,
+        mut request: GatewayRequest,
+    ) -> Result<GatewayResponse, String> {
+        let start_time = std::time::Instant::now();
+
+        let gateway_context = match TraceContext::from_headers(&request.headers) {
+            Some(parent) => parent.child(),
+            None => TraceContext::new_root(),
+        };
+
+        let routing_table = self.routing_snapshot();
+        let route = Self::apply_route(&routing_table, &mut request)?;
+        let strategy = route
+            .and_then(|route| route.strategy.as_ref())
+            .unwrap_or(&routing_table.default_strategy);
+        let policy = route.and_then(|route| route.hedging.as_ref());
+        self.attach_service_token(&mut request);
+
+        let primary = self
+            .service_registry
+            .select_endpoint(&request.service_name, strategy)
+            .ok_or("No healthy endpoints available")?;
+
+        let (outcome, result) = self
+            .race_hedged(&request, primary, policy, &gateway_context, start_time)
+            .await;
+
+        match outcome {
+            HedgeOutcome::NotHedgeable => {}
+            HedgeOutcome::Answered => self.hedge_stats.hedgeable_requests += 1,
+            HedgeOutcome::NoHedgeTarget => {
+                self.hedge_stats.hedgeable_requests += 1;
+                self.hedge_stats.no_hedge_target += 1;
+            }
+            HedgeOutcome::PrimaryWon => {
+                self.hedge_stats.hedgeable_requests += 1;
+                self.hedge_stats.hedges_sent += 1;
+                self.hedge_stats.primary_wins += 1;
+            }
+            HedgeOutcome::HedgeWon { time_saved_ms } => {
+                self.hedge_stats.hedgeable_requests += 1;
+                self.hedge_stats.hedges_sent += 1;
+                self.hedge_stats.hedge_wins += 1;
+                self.hedge_stats.time_saved_ms += time_saved_ms;
+            }
+        }
+
+        let (endpoint, response) = result?;
+        let response_time = start_time.elapsed().as_millis() as u64;
+        self.request_count += 1;
+        self.total_response_time += response_time;
+
+        let samples = self
+            .latency_samples
+            .entry(request.service_name.clone())
+            .or_default();
+        if samples.len() == LATENCY_WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(response_time);
+
+        Ok(GatewayResponse {
+            request_id: request.id,
+            status_code: response.status_code,
+            headers: response.headers,
+            body: response.body,
+            response_time_ms: response_time,
+            service_endpoint: endpoint,
+            trace_id: gateway_context.trace_id,
+        })
+    }
+
+    // Sends the request to the primary endpoint and, if the policy says so and
+    // it is slow, to a second endpoint. Returns what happened together with the
+    // winning endpoint address and response.
+    async fn race_hedged(
+        &self,
+        request: &GatewayRequest,
+        primary: &ServiceEndpoint,
+        policy: Option<&HedgingPolicy>,
+        gateway_context: &TraceContext,
+        start_time: std::time::Instant,
+    ) -> (HedgeOutcome, Result<(String, MockResponse), String>) {
+        let address = |endpoint: &ServiceEndpoint| format!("{}:{}", endpoint.host, endpoint.port);
+
+        // Each attempt is its own child span of the gateway span
+        let mut primary_request = request.clone();
+        gateway_context.child().inject(&mut primary_request.headers);
+        let primary_attempt = self.forward_request_async(&primary_request, primary);
+        tokio::pin!(primary_attempt);
+
+        let Some(policy) = policy else {
+            let result = primary_attempt.await.map(|r| (address(primary), r));
+            return (HedgeOutcome::NotHedgeable, result);
+        };
+
+        let empty = VecDeque::new();
+        let delay = policy.hedge_delay(
+            self.latency_samples
+                .get(&request.service_name)
+                .unwrap_or(&empty),
+        );
+
+        tokio::select! {
+            result = &mut primary_attempt => {
+                return (HedgeOutcome::Answered, result.map(|r| (address(primary), r)));
+            }
+            _ = tokio::time::sleep(delay) => {}
+        }
+
+        let hedge = self
+            .service_registry
+            .get_healthy_endpoints(&request.service_name)
+            .into_iter()
+            .find(|endpoint| endpoint.id != primary.id);
+        let Some(hedge) = hedge else {
+            let result = primary_attempt.await.map(|r| (address(primary), r));
+            return (HedgeOutcome::NoHedgeTarget, result);
+        };
+
+        info!(
+            "No response from {} after {}ms, hedging to {}",
+            address(primary),
+            delay.as_millis(),
+            address(hedge)
+        );
+
+        let mut hedge_request = request.clone();
+        gateway_context.child().inject(&mut hedge_request.headers);
+        let hedge_attempt = self.forward_request_async(&hedge_request, hedge);
+        tokio::pin!(hedge_attempt);
+
+        // Take the first successful response; if one attempt fails, wait for
+        // the other. Returning drops the losing future, cancelling its request.
+        let (hedge_won, result) = tokio::select! {
+            result = &mut primary_attempt => match result {
+                Ok(response) => (false, Ok(response)),
+                Err(_) => (true, hedge_attempt.await),
+            },
+            result = &mut hedge_attempt => match result {
+                Ok(response) => (true, Ok(response)),
+                Err(_) => (false, primary_attempt.await),
+            },
+        };
+
+        match hedge_won {
+            true => {
+                let elapsed_ms = start_time.elapsed().as_millis() as u64;
+                info!(
+                    "Hedged request {} answered by {}, cancelled {}",
+                    request.id,
+                    address(hedge),
+                    address(primary)
+                );
+                let outcome = HedgeOutcome::HedgeWon {
+                    time_saved_ms: primary.simulated_latency_ms.saturating_sub(elapsed_ms),
+                };
+                (outcome, result.map(|r| (address(hedge), r)))
+            }
+            false => {
+                info!(
+                    "Hedged request {} answered by {}, cancelled {}",
+                    request.id,
+                    address(primary),
+                    address(hedge)
+                );
+                (
+                    HedgeOutcome::PrimaryWon,
+                    result.map(|r| (address(primary), r)),
+                )
+            }
+        }
+    }
+
+    // Function: handle_composite
+    //
+    // Answers a composite route. All parts are sent at once, each under its
+    // own timeout, and their JSON bodies are merged under the part names in
+    // `data`. A part that fails, times out or answers with an error status is
+    // null in `data`, explained in `errors`, and marks the payload `partial`;
+    // if the part is required, the whole request fails instead.
+    //
+    // Arguments:
+    //     request: The incoming request for a composite path
+    //
+    // Returns:
+    //     Result with the merged response
+    pub async fn handle_composite(
+        &mut self,
+        request: GatewayRequest,
+    ) -> Result<GatewayResponse, String> {
+        let start_time = std::time::Instant::now();
+
+        let gateway_context = match TraceContext::from_headers(&request.headers) {
+            Some(parent) => parent.child(),
+            None => TraceContext::new_root(),
+        };
+
+        let routing_table = self.routing_snapshot();
+        let (path, query) = match request.path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (request.path.as_str(), None),
+        };
+        let composite = routing_table
+            .composites
+            .get(path)
+            .ok_or_else(|| format!("No composite route for {}", path))?;
+        // Fanning out writes would leave them half applied when a part fails
+        if !request.method.eq_ignore_ascii_case("GET") {
+            return Err(format!(
+                "Method {} not allowed on composite {}",
+                request.method, composite.path
+            ));
+        }
+
+        let mut attempts = Vec::new();
+        for part in &composite.parts {
+            let part_path = match query {
+                Some(query) => format!("{}?{}", part.path, query),
+                None => part.path.clone(),
+            };
+            let mut part_request =
+                GatewayRequest::new(String::new(), part_path, request.method.clone());
+            part_request.headers = request.headers.clone();
+            gateway_context.child().inject(&mut part_request.headers);
+            attempts.push(self.fetch_composite_part(&routing_table, part, part_request));
+        }
+        let results = futures::future::join_all(attempts).await;
+
+        let mut data = serde_json::Map::new();
+        let mut errors = serde_json::Map::new();
+        let mut endpoints = Vec::new();
+        for (part, result) in composite.parts.iter().zip(results) {
+            match result {
+                Ok((endpoint, body)) => {
+                    endpoints.push(endpoint);
+                    data.insert(part.name.clone(), body);
+                }
+                Err(reason) if part.required => {
+                    return Err(format!(
+                        "Required part '{}' of {} failed: {}",
+                        part.name, composite.path, reason
+                    ));
+                }
+                Err(reason) => {
+                    warn!(
+                        "Composite {} answering without '{}': {}",
+                        composite.path, part.name, reason
+                    );
+                    data.insert(part.name.clone(), Value::Null);
+                    errors.insert(part.name.clone(), Value::from(reason));
+                }
+            }
+        }
+
+        let response_time = start_time.elapsed().as_millis() as u64;
+        self.request_count += 1;
+        self.total_response_time += response_time;
+
+        let mut headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+        Ok(GatewayResponse {
+            request_id: request.id,
+            status_code: 200,
+            headers,
+            body: serde_json::json!({
+                "data": data,
+                "partial": !errors.is_empty(),
+                "errors": errors,
+            })
+            .to_string(),
+            response_time_ms: response_time,
+            service_endpoint: endpoints.join(", "),
+            trace_id: gateway_context.trace_id,
+        })
+    }
+
+    // Routes and sends one part of a composite request, giving up after the
+    // part's timeout. Returns the answering endpoint and its parsed body;
+    // bodies that are not JSON are kept as a string.
+    async fn fetch_composite_part(
+        &self,
+        routing_table: &RoutingTable,
+        part: &CompositePart,
+        mut request: GatewayRequest,
+    ) -> Result<(String, Value), String> {
+        let route = Self::apply_route(routing_table, &mut request)?;
+        let strategy = route
+            .and_then(|route| route.strategy.as_ref())
+            .unwrap_or(&routing_table.default_strategy);
+        let endpoint = self
+            .service_registry
+            .select_endpoint(&request.service_name, strategy)
+            .ok_or_else(|| format!("No healthy endpoints for {}", request.service_name))?;
+        self.attach_service_token(&mut request);
+
+        let timeout = std::time::Duration::from_millis(part.timeout_ms);
+        let response =
+            tokio::time::timeout(timeout, self.forward_request_async(&request, endpoint))
+                .await
+                .map_err(|_| format!("timed out after {}ms", part.timeout_ms))??;
+        if !(200..300).contains(&response.status_code) {
+            return Err(format!(
+                "{} answered with status {}",
+                endpoint.service_name, response.status_code
+            ));
+        }
+
+        let body = serde_json::from_str(&response.body).unwrap_or(Value::String(response.body));
+        Ok((format!("{}:{}", endpoint.host, endpoint.port), body))
+    }
+
+    // Signs a token naming the gateway as caller and the request's service as
+    // the only audience, replacing any token the client sent
+    fn attach_service_token(&self, request: &mut GatewayRequest) {
+        if let Some(key) = &self.service_tokens {
+            let token = key.issue(
+                GATEWAY_SERVICE_NAME,
+                &request.service_name,
+                &[],
+                chrono::Duration::seconds(SERVICE_TOKEN_TTL_SECONDS),
+            );
+            request
+                .headers
+                .insert(SERVICE_TOKEN_HEADER.to_string(), token);
+        }
+    }
+
+    // Forwards a request after the endpoint's simulated latency. Dropping the
+    // returned future cancels the request.
+    async fn forward_request_async(
+        &self,
+        request: &GatewayRequest,
+        endpoint: &ServiceEndpoint,
+    ) -> Result<MockResponse, String> {
+        tokio::time::sleep(std::time::Duration::from_millis(
+            endpoint.simulated_latency_ms,
+        ))
+        .await;
+        self.forward_request(request, endpoint)
+    }
+
+    fn forward_request(
+        &self,
+        request: &GatewayRequest,
+        endpoint: &ServiceEndpoint,
+    ) -> Result<MockResponse, String> {
+        // Simulate request forwarding (in real implementation, use HTTP client)
+        // Note: In async context, this would need to be awaited
+        // tokio::time::sleep(tokio::time::Duration::from_millis(10)).await; // Simulate network delay
+
+        // The mock backend joins the propagated trace with its own span
+        let backend_context = TraceContext::from_headers(&request.headers)
+            .map(|context| context.child())
+            .unwrap_or_else(TraceContext::new_root);
+        let span = info_span!(
+            "backend.request",
+            service = %endpoint.service_name,
+            trace_id = %backend_context.trace_id,
+            span_id = %backend_context.span_id,
+            parent_span_id = backend_context.parent_span_id.as_deref().unwrap_or(""),
+        );
+        let _guard = span.enter();
+
+        // The mock backend only answers callers with a token meant for it
+        if let Some(key) = &self.service_tokens {
+            let verified = request
+                .headers
+                .get(SERVICE_TOKEN_HEADER)
+                .ok_or_else(|| "missing service token".to_string())
+                .and_then(|token| {
+                    key.verify(token, &endpoint.service_name)
+                        .map_err(|e| e.to_string())
+                });
+            if let Err(reason) = verified {
+                warn!(
+                    "{} rejected request {}: {}",
+                    endpoint.service_name, request.id, reason
+                );
+                return Ok(MockResponse {
+                    status_code: 401,
+                    headers: HashMap::new(),
+                    body: serde_json::json!({ "error": reason }).to_string(),
+                });
+            }
+        }
+
+        if endpoint.protocol == BackendProtocol::Grpc {
+            return self.forward_grpc_request(request, endpoint, &backend_context);
+        }
+
+        // Mock successful response
+        Ok(MockResponse {
+            status_code: 200,
+            headers: {
+                let mut headers = HashMap::new();
+                headers.insert("Content-Type".to_string(), "application/json".to_string());
+                headers.insert("X-Service".to_string(), endpoint.service_name.clone());
+                backend_context.inject(&mut headers);
+                headers
+            },
+            body: format!(
+                r#"{{"message": "Response from {}", "request_id": "{}"}}"#,
+                endpoint.service_name, request.id
+            ),
+        })
+    }
+
+    fn forward_grpc_request(
+        &self,
+        request: &GatewayRequest,
+        endpoint: &ServiceEndpoint,
+        backend_context: &TraceContext,
+    ) -> Result<MockResponse, String> {
+        let method_name = request
+            .path
+            .rsplit('/')
+            .next()
+            .filter(|segment| !segment.is_empty())
+            .ok_or("Request path does not name a gRPC method")?;
+        let descriptor = self
+            .grpc_methods
+            .get(&endpoint.service_name)
+            .and_then(|methods| methods.get(method_name))
+            .ok_or_else(|| {
+                format!(
+                    "No gRPC method '{}' registered for {}",
+                    method_name, endpoint.service_name
+                )
+            })?;
+
+        let json_body: Value = match &request.body {
+            Some(body) => serde_json::from_str(body)
+                .map_err(|e| format!("Request body is not valid JSON: {}", e))?,
+            None => serde_json::json!({}),
+        };
+        let request_frame = GrpcTranscoder::encode_request(&descriptor.input, &json_body)?;
+
+        // Simulate the unary call (in a real implementation, use an HTTP/2 client)
+        info!(
+            "gRPC call {} on {}:{} ({} bytes)",
+            descriptor.path(),
+            endpoint.host,
+            endpoint.port,
+            request_frame.len()
+        );
+        let response_frame = mock_grpc_backend(descriptor, &request_frame)?;
+        let body = GrpcTranscoder::decode_response(&descriptor.output, &response_frame)?;
+
+        Ok(MockResponse {
+            status_code: 200,
+            headers: {
+                let mut headers = HashMap::new();
+                headers.insert("Content-Type".to_string(), "application/json".to_string());
+                headers.insert("X-Service".to_string(), endpoint.service_name.clone());
+                headers.insert("grpc-status".to_string(), "0".to_string());
+                backend_context.inject(&mut headers);
+                headers
+            },
+            body: body.to_string(),
+        })
+    }
+
+    pub fn get_statistics(&self) -> GatewayStatistics {
+        let avg_response_time = if self.request_count > 0 {
+            self.total_response_time as f64 / self.request_count as f64
         } else {
             0.0
         };
@@ -337,11 +1755,35 @@ impl MicroserviceGateway {
             total_requests: self.request_count,
             average_response_time_ms: avg_response_time,
             service_stats: self.service_registry.get_service_statistics(),
-            active_routes: self.route_mappings.len(),
+            active_routes: self.routing_snapshot().routes.len(),
+            hedging: self.hedge_stats.clone(),
         }
     }
 }
 
+// Function: mock_grpc_backend
+//
+// Stands in for a gRPC server: decodes the request frame and answers with a
+// response message that echoes fields shared with the request and fills the
+// remaining fields with sample values.
+fn mock_grpc_backend(descriptor: &GrpcMethodDescriptor, frame: &[u8]) -> Result<Vec<u8>, String> {
+    let request = GrpcTranscoder::decode_response(&descriptor.input, frame)?;
+    let mut response = serde_json::Map::new();
+
+    for field in &descriptor.output.fields {
+        let value = match (request.get(&field.name), &field.field_type) {
+            (Some(value), _) => value.clone(),
+            (None, ProtoFieldType::String) => Value::from("sample"),
+            (None, ProtoFieldType::Int64) => Value::from(42),
+            (None, ProtoFieldType::Bool) => Value::from(true),
+            (None, ProtoFieldType::Double) => Value::from(1.0),
+        };
+        response.insert(field.name.clone(), value);
+    }
+
+    GrpcTranscoder::encode_request(&descriptor.output, &Value::Object(response))
+}
+
 // Struct: MockResponse
 //
 // Mock response for demonstration purposes.
@@ -360,6 +1802,153 @@ pub struct GatewayStatistics {
     average_response_time_ms: f64,
     service_stats: HashMap<String, ServiceStats>,
     active_routes: usize,
+    hedging: HedgeStats,
+}
+
+pub type SharedGateway = Arc<Mutex<MicroserviceGateway>>;
+
+// How often the stdio server looks for backends that appeared or went away
+const DISCOVERY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+// How often the stdio server checks MCP_GATEWAY_CONFIG for changes
+const CONFIG_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+// Struct: ServiceTool
+//
+// An MCP tool that forwards requests to one discovered backend service
+// through the gateway, so routing, load balancing and tracing still apply.
+pub struct ServiceTool {
+    service_name: String,
+    gateway: SharedGateway,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ServiceCallRequest {
+    path: String,
+    #[serde(default = "default_method")]
+    method: String,
+    body: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+impl ServiceTool {
+    pub fn new(service_name: &str, gateway: SharedGateway) -> Self {
+        Self {
+            service_name: service_name.to_string(),
+            gateway,
+        }
+    }
+
+    // "order-service" is offered as "call_order_service"
+    pub fn tool_name(service_name: &str) -> String {
+        format!("call_{}", service_name.replace(['-', '.'], "_"))
+    }
+}
+
+impl ToolHandler for ServiceTool {
+    fn tool(&self) -> Tool {
+        Tool {
+            name: Self::tool_name(&self.service_name),
+            description: format!(
+                "Send a request to {} through the gateway",
+                self.service_name
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Request path, e.g. /api/orders/42" },
+                    "method": { "type": "string", "default": "GET" },
+                    "body": { "type": "string", "description": "Request body, usually JSON" }
+                },
+                "required": ["path"]
+            }),
+            // Backends may do anything with the request
+            annotations: Some(ToolAnnotations::destructive().open_world()),
+        }
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+        Box::pin(async move {
+            let call: ServiceCallRequest = serde_json::from_value(arguments)
+                .map_err(|e| McpError::InvalidParams(format!("Invalid arguments: {}", e)))?;
+            let mut request =
+                GatewayRequest::new(self.service_name.clone(), call.path, call.method);
+            if let Some(body) = &call.body {
+                request = request.with_body(body);
+            }
+
+            // The gateway found no backend to answer the request
+            let response = self
+                .gateway
+                .lock()
+                .unwrap()
+                .handle_request(request)
+                .map_err(McpError::Unavailable)?;
+            let body = serde_json::from_str(&response.body)
+                .unwrap_or_else(|_| Value::String(response.body.clone()));
+            Ok(serde_json::json!({
+                "endpoint": response.service_endpoint,
+                "response_time_ms": response.response_time_ms,
+                "trace_id": response.trace_id,
+                "body": body,
+            }))
+        })
+    }
+}
+
+// Function: sync_service_tools
+//
+// Registers a tool for every service with a healthy endpoint and unregisters
+// the tools of services that have none left, so clients only see backends
+// they can reach. Returns the number of tools added and removed.
+pub fn sync_service_tools(gateway: &SharedGateway, registry: &ToolRegistry) -> (usize, usize) {
+    let discovered = gateway.lock().unwrap().discovered_services();
+    let wanted: Vec<String> = discovered
+        .iter()
+        .map(|s| ServiceTool::tool_name(s))
+        .collect();
+
+    let mut removed = 0;
+    for tool in registry.tool_descriptors() {
+        if !wanted.contains(&tool.name) && registry.unregister(&tool.name) {
+            removed += 1;
+        }
+    }
+
+    let mut added = 0;
+    for (service_name, tool_name) in discovered.iter().zip(&wanted) {
+        if !registry.contains(tool_name) {
+            registry.register(ServiceTool::new(service_name, gateway.clone()));
+            added += 1;
+        }
+    }
+
+    (added, removed)
+}
+
+// Function: spawn_service_discovery
+//
+// Keeps the tool registry in step with the gateway's services.
+pub fn spawn_service_discovery(
+    gateway: SharedGateway,
+    registry: Arc<ToolRegistry>,
+    interval: std::time::Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            let (added, removed) = sync_service_tools(&gateway, &registry);
+            if added + removed > 0 {
+                info!(
+                    "Service tools updated: {} added, {} removed",
+                    added, removed
+                );
+            }
+        }
+    })
 }
 
 // Function: demo_microservice_gateway
@@ -388,9 +1977,42 @@ fn demo_microservice_gateway() -> Result<(), Box<dyn std::error::Error>> {
         8003,
     ));
 
+    // Register a gRPC backend alongside the HTTP services
+    gateway.register_service(ServiceEndpoint::grpc(
+        "inventory-service".to_string(),
+        "localhost".to_string(),
+        9001,
+    ));
+    gateway.register_grpc_method(
+        "inventory-service",
+        GrpcMethodDescriptor::new(
+            "inventory.v1.InventoryService",
+            "GetStock",
+            MessageDescriptor::new(
+                "GetStockRequest",
+                vec![
+                    ProtoField::new("sku", 1, ProtoFieldType::String),
+                    ProtoField::new("warehouse_id", 2, ProtoFieldType::Int64),
+                ],
+            ),
+            MessageDescriptor::new(
+                "GetStockResponse",
+                vec![
+                    ProtoField::new("sku", 1, ProtoFieldType::String),
+                    ProtoField::new("quantity", 2, ProtoFieldType::Int64),
+                    ProtoField::new("in_stock", 3, ProtoFieldType::Bool),
+                ],
+            ),
+        ),
+    );
+
     // Add routes
     gateway.add_route("/api/users".to_string(), "user-service".to_string());
     gateway.add_route("/api/orders".to_string(), "order-service".to_string());
+    gateway.add_route(
+        "/api/inventory".to_string(),
+        "inventory-service".to_string(),
+    );
 
     info!("=== Processing Requests ===");
 
@@ -420,15 +2042,31 @@ fn demo_microservice_gateway() -> Result<(), Box<dyn std::error::Error>> {
             "".to_string(),
             "/api/orders/111".to_string(),
             "GET".to_string(),
-        ),
+        )
+        // A caller that is already part of a trace
+        .with_header(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .with_header("tracestate", "congo=t61rcWkgMzE"),
+        GatewayRequest::new(
+            "".to_string(),
+            "/api/inventory/GetStock".to_string(),
+            "POST".to_string(),
+        )
+        .with_body(r#"{"sku": "WIDGET-1", "warehouse_id": 7}"#),
     ];
 
     for request in requests {
         match gateway.handle_request(request.clone()) {
             Ok(response) => {
                 info!(
-                    "✅ Request {} -> {} ({}ms)",
-                    request.path, response.service_endpoint, response.response_time_ms
+                    "✅ Request {} -> {} ({}ms, trace {}): {}",
+                    request.path,
+                    response.service_endpoint,
+                    response.response_time_ms,
+                    response.trace_id,
+                    response.body
                 );
             }
             Err(e) => {
@@ -457,16 +2095,466 @@ fn demo_microservice_gateway() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+// Function: demo_config_hot_reload
+//
+// Demonstrates loading routes from a config file and hot-reloading them.
+async fn demo_config_hot_reload() -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Route Configuration Hot Reload ===");
+
+    // The demo rewrites its config, so it never touches MCP_GATEWAY_CONFIG
+    let config_dir = std::env::temp_dir().join(format!("mcp_gateway_{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&config_dir)?;
+    let config_path = config_dir.join("routes.json");
+
+    let initial = serde_json::json!({
+        "default_strategy": "RoundRobin",
+        "routes": [
+            { "path_prefix": "/api/users", "service_name": "user-service" },
+            {
+                "path_prefix": "/api/orders",
+                "service_name": "order-service",
+                "allowed_methods": ["GET"]
+            }
+        ]
+    });
+    std::fs::write(&config_path, serde_json::to_string_pretty(&initial)?)?;
+
+    let mut gateway =
+        MicroserviceGateway::from_config(ConfigurableServer::read_config_file(&config_path)?)?;
+    for port in [8001, 8002] {
+        gateway.register_service(ServiceEndpoint::new(
+            "user-service".to_string(),
+            "localhost".to_string(),
+            port,
+        ));
+    }
+    gateway.register_service(ServiceEndpoint::new(
+        "order-service".to_string(),
+        "localhost".to_string(),
+        8003,
+    ));
+    gateway.register_service(ServiceEndpoint::new(
+        "order-service-v2".to_string(),
+        "localhost".to_string(),
+        8004,
+    ));
+
+    let poll_interval = std::time::Duration::from_millis(100);
+    let watcher = spawn_config_watcher(
+        config_path.clone(),
+        gateway.routing_table_handle(),
+        poll_interval,
+    );
+
+    let post_order = || {
+        GatewayRequest::new(
+            "".to_string(),
+            "/api/orders/42".to_string(),
+            "POST".to_string(),
+        )
+    };
+
+    match gateway.handle_request(post_order()) {
+        Ok(response) => info!("POST /api/orders -> {}", response.service_endpoint),
+        Err(e) => info!("POST /api/orders rejected by policy: {}", e),
+    }
+
+    // Point orders at the v2 service and allow POST
+    let updated = serde_json::json!({
+        "default_strategy": "RoundRobin",
+        "routes": [
+            { "path_prefix": "/api/users", "service_name": "user-service" },
+            {
+                "path_prefix": "/api/orders",
+                "service_name": "order-service-v2",
+                "allowed_methods": ["GET", "POST"]
+            }
+        ]
+    });
+    std::fs::write(&config_path, serde_json::to_string_pretty(&updated)?)?;
+    tokio::time::sleep(poll_interval * 3).await;
+
+    match gateway.handle_request(post_order()) {
+        Ok(response) => info!("POST /api/orders -> {}", response.service_endpoint),
+        Err(e) => warn!("POST /api/orders failed after reload: {}", e),
+    }
+
+    // An invalid config is rejected and the previous table stays in place
+    std::fs::write(
+        &config_path,
+        r#"{"default_strategy": "RoundRobin", "routes": [{"path_prefix": "orders", "service_name": ""}]}"#,
+    )?;
+    tokio::time::sleep(poll_interval * 3).await;
+    info!(
+        "Routes after invalid reload: {}",
+        gateway.get_statistics().active_routes
+    );
+
+    watcher.abort();
+    std::fs::remove_dir_all(&config_dir)?;
+    Ok(())
+}
+
+// Function: demo_hedged_requests
+//
+// Demonstrates hedging requests to a service with one slow endpoint.
+async fn demo_hedged_requests() -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Hedged Requests ===");
+    let mut gateway = MicroserviceGateway::new(LoadBalancingStrategy::RoundRobin);
+
+    // One endpoint is usually fast, the other is stuck behind a slow dependency
+    gateway.register_service(
+        ServiceEndpoint::new("search-service".to_string(), "localhost".to_string(), 8101)
+            .with_simulated_latency(20),
+    );
+    gateway.register_service(
+        ServiceEndpoint::new("search-service".to_string(), "localhost".to_string(), 8102)
+            .with_simulated_latency(400),
+    );
+    gateway.add_route("/api/search".to_string(), "search-service".to_string());
+    gateway.set_route_hedging(
+        "/api/search",
+        HedgingPolicy {
+            min_samples: 3,
+            initial_delay_ms: 50,
+            ..HedgingPolicy::default()
+        }
+        .with_percentile(0.9),
+    )?;
+
+    for i in 0..8 {
+        let request = GatewayRequest::new(
+            "".to_string(),
+            format!("/api/search?q=item{}", i),
+            "GET".to_string(),
+        );
+        let response = gateway.handle_request_hedged(request).await?;
+        info!(
+            "Search {} answered by {} in {}ms",
+            i, response.service_endpoint, response.response_time_ms
+        );
+    }
+
+    let hedging = gateway.get_statistics().hedging;
+    info!(
+        "Hedging: {} hedgeable, {} hedges sent, {} hedge wins ({:.0}%), {} primary wins, ~{}ms saved",
+        hedging.hedgeable_requests,
+        hedging.hedges_sent,
+        hedging.hedge_wins,
+        hedging.hedge_win_rate() * 100.0,
+        hedging.primary_wins,
+        hedging.time_saved_ms
+    );
+
+    Ok(())
+}
+
+// Function: demo_api_composition
+//
+// Demonstrates a dashboard endpoint assembled from three backends, one of
+// them too slow for its timeout, and what happens when a required part is
+// missing.
+async fn demo_api_composition() -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== API Composition ===");
+    let config: GatewayConfig = serde_json::from_value(serde_json::json!({
+        "default_strategy": "RoundRobin",
+        "routes": [
+            { "path_prefix": "/api/users", "service_name": "user-service" },
+            { "path_prefix": "/api/orders", "service_name": "order-service" },
+            { "path_prefix": "/api/recommendations", "service_name": "recommendation-service" }
+        ],
+        "composites": [{
+            "path": "/api/dashboard",
+            "parts": [
+                { "name": "user", "path": "/api/users/me", "required": true },
+                { "name": "orders", "path": "/api/orders/recent", "timeout_ms": 200 },
+                { "name": "recommendations", "path": "/api/recommendations", "timeout_ms": 100 }
+            ]
+        }]
+    }))?;
+    let mut gateway = MicroserviceGateway::from_config(config)?;
+    for (service_name, port, latency_ms) in [
+        ("user-service", 8001, 20),
+        ("order-service", 8003, 60),
+        ("recommendation-service", 8005, 400),
+    ] {
+        gateway.register_service(
+            ServiceEndpoint::new(service_name.to_string(), "localhost".to_string(), port)
+                .with_simulated_latency(latency_ms),
+        );
+    }
+    let dashboard = || {
+        GatewayRequest::new(
+            "".to_string(),
+            "/api/dashboard?user=42".to_string(),
+            "GET".to_string(),
+        )
+    };
+
+    // The parts run in parallel, so the slow one costs its timeout, not its latency
+    let response = gateway.handle_composite(dashboard()).await?;
+    info!(
+        "GET /api/dashboard from {} in {}ms: {}",
+        response.service_endpoint, response.response_time_ms, response.body
+    );
+
+    // Parts can be added in code too; this one names a service that is not running
+    gateway.add_composite_route(CompositeRoute::new(
+        "/api/profile",
+        vec![
+            CompositePart::new("user", "/api/users/me").required(),
+            CompositePart::new("preferences", "/api/preferences").with_timeout(50),
+        ],
+    ))?;
+    gateway.add_route(
+        "/api/preferences".to_string(),
+        "preference-service".to_string(),
+    );
+    let response = gateway
+        .handle_composite(GatewayRequest::new(
+            "".to_string(),
+            "/api/profile".to_string(),
+            "GET".to_string(),
+        ))
+        .await?;
+    info!("GET /api/profile: {}", response.body);
+
+    // Without the required user part there is nothing useful to return
+    gateway.deregister_service("user-service");
+    match gateway.handle_composite(dashboard()).await {
+        Ok(response) => info!("GET /api/dashboard: {}", response.body),
+        Err(e) => info!("GET /api/dashboard failed: {}", e),
+    }
+
+    Ok(())
+}
+
+// Function: demo_service_tokens
+//
+// Demonstrates backends that only answer the gateway: each forwarded request
+// carries a token signed for its target service, and a token for one service
+// is refused by another.
+fn demo_service_tokens() -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Service Tokens ===");
+    let key = ServiceTokenKey::from_env();
+    let mut gateway = MicroserviceGateway::new(LoadBalancingStrategy::RoundRobin)
+        .with_service_tokens(key.clone());
+    for (service_name, port) in [("user-service", 8001), ("order-service", 8003)] {
+        gateway.register_service(ServiceEndpoint::new(
+            service_name.to_string(),
+            "localhost".to_string(),
+            port,
+        ));
+    }
+    gateway.add_route("/api/users".to_string(), "user-service".to_string());
+
+    let response = gateway.handle_request(GatewayRequest::new(
+        "".to_string(),
+        "/api/users/123".to_string(),
+        "GET".to_string(),
+    ))?;
+    info!(
+        "user-service answered the gateway with {}",
+        response.status_code
+    );
+
+    // A caller skipping the gateway with a token issued for user-service
+    let token = key.issue(
+        GATEWAY_SERVICE_NAME,
+        "user-service",
+        &[],
+        chrono::Duration::seconds(SERVICE_TOKEN_TTL_SECONDS),
+    );
+    let endpoint = gateway
+        .service_registry
+        .get_healthy_endpoints("order-service")[0]
+        .clone();
+    let request = GatewayRequest::new(
+        "order-service".to_string(),
+        "/api/orders/789".to_string(),
+        "GET".to_string(),
+    )
+    .with_header(SERVICE_TOKEN_HEADER, &token);
+    let response = gateway.forward_request(&request, &endpoint)?;
+    info!(
+        "order-service answered a token for user-service with {}: {}",
+        response.status_code, response.body
+    );
+
+    Ok(())
+}
+
+// Function: demo_backend_discovery
+//
+// Demonstrates the tool list following backends as they come and go.
+async fn demo_backend_discovery() -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Backend Discovery ===");
+    let gateway: SharedGateway = Arc::new(Mutex::new(MicroserviceGateway::new(
+        LoadBalancingStrategy::RoundRobin,
+    )));
+    let registry = ToolRegistry::new("microservice-gateway");
+    let mut changes = registry
+        .tool_list_changes()
+        .ok_or("registry does not announce changes")?;
+    let tool_names = |registry: &ToolRegistry| -> Vec<String> {
+        registry
+            .tool_descriptors()
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect()
+    };
+
+    gateway
+        .lock()
+        .unwrap()
+        .register_service(ServiceEndpoint::new(
+            "user-service".to_string(),
+            "localhost".to_string(),
+            8001,
+        ));
+    sync_service_tools(&gateway, &registry);
+    info!("Tools: {:?}", tool_names(&registry));
+
+    // A new backend shows up
+    gateway
+        .lock()
+        .unwrap()
+        .register_service(ServiceEndpoint::new(
+            "order-service".to_string(),
+            "localhost".to_string(),
+            8003,
+        ));
+    sync_service_tools(&gateway, &registry);
+    info!("Tools: {:?}", tool_names(&registry));
+
+    let result = registry
+        .invoke_tool(
+            "call_order_service",
+            serde_json::json!({ "path": "/api/orders/42" }),
+        )
+        .await?;
+    info!("call_order_service -> {}", result["body"]);
+
+    // The user service leaves the cluster
+    gateway.lock().unwrap().deregister_service("user-service");
+    sync_service_tools(&gateway, &registry);
+    info!("Tools: {:?}", tool_names(&registry));
+
+    let mut notifications = 0;
+    while changes.try_recv().is_ok() {
+        notifications += 1;
+    }
+    info!(
+        "Clients would have received {} tools/list_changed notifications",
+        notifications
+    );
+
+    Ok(())
+}
+
+// Function: serve_stdio
+//
+// Serves one tool per discovered backend to an MCP client, re-checking the
+// backends periodically. With MCP_GATEWAY_CONFIG set, routes come from that
+// file and are reloaded whenever it changes.
+async fn serve_stdio() -> Result<(), Box<dyn std::error::Error>> {
+    let config_path = ConfigurableServer::config_path_from_env("MCP_GATEWAY_CONFIG");
+    let mut gateway = match &config_path {
+        Some(path) => {
+            MicroserviceGateway::from_config(ConfigurableServer::read_config_file(path)?)?
+        }
+        None => MicroserviceGateway::new(LoadBalancingStrategy::RoundRobin),
+    };
+    if let Some(path) = config_path {
+        spawn_config_watcher(path, gateway.routing_table_handle(), CONFIG_POLL_INTERVAL);
+    }
+    if std::env::var_os(SERVICE_TOKEN_KEY_ENV).is_some() {
+        gateway = gateway.with_service_tokens(ServiceTokenKey::from_env());
+    }
+    for (service_name, port) in [("user-service", 8001), ("order-service", 8003)] {
+        gateway.register_service(ServiceEndpoint::new(
+            service_name.to_string(),
+            "localhost".to_string(),
+            port,
+        ));
+    }
+
+    let gateway: SharedGateway = Arc::new(Mutex::new(gateway));
+    let registry = Arc::new(ToolRegistry::new("microservice-gateway"));
+    sync_service_tools(&gateway, &registry);
+    spawn_service_discovery(gateway, registry.clone(), DISCOVERY_INTERVAL);
+
+    StdioTransport::new()
+        .serve(&McpServer::new(registry))
+        .await?;
+    Ok(())
+}
+
 // Function: main
 //
 // Entry point demonstrating the microservice gateway implementation.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    let _telemetry = mcp_rust_examples::logging::init("info");
+
+    // With --stdio, serve the backends as tools instead of running the demo
+    if transport::stdio_requested() {
+        return serve_stdio().await;
+    }
 
     info!("Starting Microservice Gateway Example");
     demo_microservice_gateway()?;
+    demo_config_hot_reload().await?;
+    demo_hedged_requests().await?;
+    demo_api_composition().await?;
+    demo_backend_discovery().await?;
+    demo_service_tokens()?;
     info!("Microservice Gateway Example completed successfully");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(message: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0];
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(message);
+        frame
+    }
+
+    #[test]
+    fn test_grpc_decode_rejects_bad_lengths() {
+        let descriptor = MessageDescriptor::new(
+            "Stock",
+            vec![
+                ProtoField::new("sku", 1, ProtoFieldType::String),
+                ProtoField::new("price", 2, ProtoFieldType::Double),
+            ],
+        );
+        let valid = GrpcTranscoder::encode_request(
+            &descriptor,
+            &serde_json::json!({ "sku": "A-1", "price": 2.5 }),
+        )
+        .unwrap();
+        let decoded = GrpcTranscoder::decode_response(&descriptor, &valid).unwrap();
+        assert_eq!(decoded["sku"], "A-1");
+
+        // Frame length prefix larger than the payload
+        let mut oversized = valid.clone();
+        oversized[1..5].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(GrpcTranscoder::decode_response(&descriptor, &oversized).is_err());
+
+        // Field 1, length-delimited, with a length near u64::MAX
+        let mut message = vec![(1 << 3) | 2];
+        write_varint(&mut message, u64::MAX - 1);
+        assert!(GrpcTranscoder::decode_response(&descriptor, &frame(&message)).is_err());
+
+        // fixed64 and fixed32 fields cut short
+        for truncated in [[(2 << 3) | 1, 0, 0, 0], [(3 << 3) | 5, 0, 0, 0]] {
+            assert!(GrpcTranscoder::decode_response(&descriptor, &frame(&truncated)).is_err());
+        }
+    }
+}
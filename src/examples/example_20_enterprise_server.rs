@@ -2,16 +2,42 @@
 //
 // This example demonstrates how to build a comprehensive enterprise server
 // that combines authentication, monitoring, caching, HTTP endpoints, and
-// proper error handling in a production-ready application.
+// proper error handling in a production-ready application. Routes are
+// grouped by API version so old clients keep working while deprecated
+// versions announce their sunset date. Long-lived connections are pinged, and
+// a peer that stops answering loses its session. Session authentication and
+// request metrics run as middleware around the request handler. Other
+// servers call in with signed service tokens, which the same middleware
+// verifies before any handler runs. Large data exports run as background
+// jobs that write a file and hand back a short-lived signed download link.
+// Error messages are translated into the language the client asks for in
+// Accept-Language, from the catalogs in examples/locales.
 
 use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use mcp_rust_examples::i18n::{self, Catalog, FluentArgs, Localizer};
+use mcp_rust_examples::keepalive::{DisconnectReason, Keepalive, KeepaliveAction, KeepaliveConfig};
+use mcp_rust_examples::middleware::{Endpoint, Middleware, MiddlewareChain};
+use mcp_rust_examples::protocol::JsonRpcRequest;
+use mcp_rust_examples::service_token::{ServiceClaims, ServiceTokenKey, SERVICE_TOKEN_HEADER};
+use mcp_rust_examples::state::{StateCommand, StateError, StatefulServer};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{info, warn};
 use uuid::Uuid;
 
+// The background job endpoints delegate to the task queue from example 12
+#[path = "example_12_task_queue.rs"]
+#[allow(dead_code)]
+mod task_queue;
+
+use task_queue::{Heartbeat, TaskPriority, TaskQueue, TaskStatus};
+
 // Struct: User
 //
 // Represents a user in the enterprise system.
@@ -40,7 +66,7 @@ pub enum UserRole {
 // Struct: Session
 //
 // Represents an authenticated user session.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     id: Uuid,
     user_id: Uuid,
@@ -50,6 +76,28 @@ pub struct Session {
     last_accessed: DateTime<Utc>,
 }
 
+// Struct: PeerConnection
+//
+// A long-lived client connection, such as a WebSocket, bound to the session
+// it authenticated with. Its keepalive decides when the peer is gone.
+struct PeerConnection {
+    session_id: Uuid,
+    keepalive: Keepalive,
+}
+
+// Struct: KeepaliveSweep
+//
+// The outcome of one keepalive pass over the open connections.
+//
+// Fields:
+// - pings: Pings to write to quiet peers, by connection
+// - disconnected: Connections given up on, and why
+#[derive(Debug, Default)]
+pub struct KeepaliveSweep {
+    pub pings: Vec<(Uuid, JsonRpcRequest)>,
+    pub disconnected: Vec<(Uuid, DisconnectReason)>,
+}
+
 // Struct: CacheEntry
 //
 // Represents a cached value with expiration.
@@ -117,7 +165,7 @@ impl<T: Clone> Cache<T> {
 // Struct: Metrics
 //
 // Tracks various server metrics.
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Metrics {
     total_requests: u64,
     successful_requests: u64,
@@ -126,6 +174,486 @@ pub struct Metrics {
     active_sessions: u64,
     cache_hits: u64,
     cache_misses: u64,
+    throttled_requests: u64,
+    throttled_by_group: HashMap<String, u64>,
+    #[serde(default)]
+    deprecated_route_usage: HashMap<String, u64>, // "GET /api/v1/..." -> calls
+}
+
+// Struct: RouteGroupLimit
+//
+// Sliding-window limits for every route under a path prefix.
+#[derive(Debug, Clone)]
+pub struct RouteGroupLimit {
+    name: String,
+    path_prefix: String,
+    per_user: u32,
+    per_ip: u32,
+    window: Duration,
+}
+
+impl RouteGroupLimit {
+    pub fn new(
+        name: &str,
+        path_prefix: &str,
+        per_user: u32,
+        per_ip: u32,
+        window: Duration,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            path_prefix: path_prefix.to_string(),
+            per_user,
+            per_ip,
+            window,
+        }
+    }
+}
+
+// Struct: RateLimitConfig
+//
+// Route groups are matched by longest path prefix; unmatched paths use the default.
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    groups: Vec<RouteGroupLimit>,
+    default: RouteGroupLimit,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let minute = Duration::from_secs(60);
+        Self {
+            groups: vec![
+                RouteGroupLimit::new("health", "/api/health", 600, 600, minute),
+                RouteGroupLimit::new("jobs", "/api/jobs", 10, 20, minute),
+                RouteGroupLimit::new("exports", "/api/export", 30, 60, minute),
+            ],
+            default: RouteGroupLimit::new("api", "/api", 120, 240, minute),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    fn group_for(&self, path: &str) -> &RouteGroupLimit {
+        self.groups
+            .iter()
+            .filter(|group| path.starts_with(&group.path_prefix))
+            .max_by_key(|group| group.path_prefix.len())
+            .unwrap_or(&self.default)
+    }
+}
+
+// Struct: RateLimitDecision
+//
+// Outcome of a rate limit check, used to build the RateLimit headers.
+#[derive(Debug, Clone)]
+pub struct RateLimitDecision {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    reset_after: Duration,
+}
+
+// Struct: AdmittedLog
+//
+// The requests admitted for one key, with the window of the route group the
+// key belongs to.
+#[derive(Debug)]
+struct AdmittedLog {
+    window: Duration,
+    admitted: VecDeque<Instant>,
+}
+
+impl AdmittedLog {
+    fn is_idle(&self, now: Instant) -> bool {
+        self.admitted
+            .back()
+            .is_none_or(|latest| now.duration_since(*latest) >= self.window)
+    }
+}
+
+type AdmittedLogs = Arc<RwLock<HashMap<String, AdmittedLog>>>;
+
+// How often the limiter drops the logs of keys that have gone idle.
+const RATE_LIMIT_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+// Struct: RateLimiter
+//
+// Sliding-window log limiter. Each key keeps the timestamps of the requests
+// admitted within the current window. A background task drops idle keys
+// every RATE_LIMIT_CLEANUP_INTERVAL until the limiter is dropped.
+pub struct RateLimiter {
+    windows: AdmittedLogs,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        let windows: AdmittedLogs = Arc::new(RwLock::new(HashMap::new()));
+        tokio::spawn(Self::cleanup_loop(
+            Arc::downgrade(&windows),
+            RATE_LIMIT_CLEANUP_INTERVAL,
+        ));
+        Self { windows }
+    }
+
+    // Checks every key against its limit and only records the request if all
+    // of them have room, so a request rejected by the IP limit doesn't also
+    // use up the user's allowance.
+    pub async fn check(&self, keys: &[(String, u32)], window: Duration) -> RateLimitDecision {
+        let now = Instant::now();
+        let mut windows = self.windows.write().await;
+
+        let mut decision = RateLimitDecision {
+            allowed: true,
+            limit: u32::MAX,
+            remaining: u32::MAX,
+            reset_after: Duration::ZERO,
+        };
+
+        for (key, limit) in keys {
+            let log = &mut windows
+                .entry(key.clone())
+                .or_insert_with(|| AdmittedLog {
+                    window,
+                    admitted: VecDeque::new(),
+                })
+                .admitted;
+            while log
+                .front()
+                .is_some_and(|admitted| now.duration_since(*admitted) >= window)
+            {
+                log.pop_front();
+            }
+
+            let used = log.len() as u32;
+            let reset_after = log
+                .front()
+                .map(|oldest| window.saturating_sub(now.duration_since(*oldest)))
+                .unwrap_or(window);
+
+            if used >= *limit {
+                decision.allowed = false;
+            }
+
+            // Report the most restrictive key
+            let remaining = limit.saturating_sub(used + 1);
+            if remaining < decision.remaining || used >= *limit {
+                decision.limit = *limit;
+                decision.remaining = remaining;
+                decision.reset_after = reset_after;
+            }
+        }
+
+        if decision.allowed {
+            for (key, _) in keys {
+                if let Some(log) = windows.get_mut(key) {
+                    log.admitted.push_back(now);
+                }
+            }
+        }
+
+        decision
+    }
+
+    // Drops the keys with no request inside their own group's window.
+    pub async fn cleanup_expired(&self) {
+        Self::evict_idle(&self.windows).await;
+    }
+
+    async fn evict_idle(windows: &RwLock<HashMap<String, AdmittedLog>>) {
+        let now = Instant::now();
+        windows.write().await.retain(|_, log| !log.is_idle(now));
+    }
+
+    async fn cleanup_loop(windows: Weak<RwLock<HashMap<String, AdmittedLog>>>, period: Duration) {
+        let mut interval = tokio::time::interval(period);
+        // The first tick completes immediately
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let Some(windows) = windows.upgrade() else {
+                return;
+            };
+            Self::evict_idle(&windows).await;
+        }
+    }
+}
+
+// Struct: ApiVersionGroup
+//
+// One versioned route group, served under /api/{version}/. Disabled groups
+// are not routed at all, so a version can ship dark and be switched on later.
+#[derive(Debug, Clone)]
+pub struct ApiVersionGroup {
+    version: String,
+    enabled: bool,
+    sunset: Option<DateTime<Utc>>, // Set once the version is deprecated
+    successor: Option<String>,     // Version clients should migrate to
+}
+
+impl ApiVersionGroup {
+    pub fn new(version: &str) -> Self {
+        Self {
+            version: version.to_string(),
+            enabled: true,
+            sunset: None,
+            successor: None,
+        }
+    }
+
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    pub fn deprecated(mut self, sunset: DateTime<Utc>, successor: &str) -> Self {
+        self.sunset = Some(sunset);
+        self.successor = Some(successor.to_string());
+        self
+    }
+}
+
+// Struct: ApiVersionConfig
+//
+// The versioned route groups. Unversioned /api/ paths are served by the
+// legacy version, which is what clients used before versioning existed.
+#[derive(Debug, Clone)]
+pub struct ApiVersionConfig {
+    groups: Vec<ApiVersionGroup>,
+    legacy_version: String,
+}
+
+impl Default for ApiVersionConfig {
+    fn default() -> Self {
+        let sunset = DateTime::parse_from_rfc3339(V1_SUNSET)
+            .expect("V1_SUNSET is a valid RFC 3339 date")
+            .with_timezone(&Utc);
+        Self {
+            groups: vec![
+                ApiVersionGroup::new("v1").deprecated(sunset, "v2"),
+                ApiVersionGroup::new("v2"),
+            ],
+            legacy_version: "v1".to_string(),
+        }
+    }
+}
+
+// When the v1 API stops being served by default
+const V1_SUNSET: &str = "2027-06-30T00:00:00Z";
+
+impl ApiVersionConfig {
+    // Splits "/api/v2/users/profile" into ("v2", "/api/users/profile").
+    // Unversioned API paths resolve to the legacy version.
+    fn resolve(&self, path: &str) -> Option<(String, String)> {
+        let rest = path.strip_prefix("/api/")?;
+        let (segment, tail) = rest.split_once('/').unwrap_or((rest, ""));
+
+        let is_version = segment.len() > 1
+            && segment.starts_with('v')
+            && segment[1..].chars().all(|c| c.is_ascii_digit());
+        if is_version {
+            let canonical = if tail.is_empty() {
+                "/api".to_string()
+            } else {
+                format!("/api/{}", tail)
+            };
+            Some((segment.to_string(), canonical))
+        } else {
+            Some((self.legacy_version.clone(), path.to_string()))
+        }
+    }
+
+    fn group(&self, version: &str) -> Option<&ApiVersionGroup> {
+        self.groups
+            .iter()
+            .find(|group| group.version == version && group.enabled)
+    }
+}
+
+// Function: route_template
+//
+// Replaces path parameters with placeholders so usage counters stay bounded.
+fn route_template(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+                "{id}"
+            } else if segment.contains('.') {
+                // Signed download tokens are "<claims>.<signature>"
+                "{token}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Struct: SecurityConfig
+//
+// Security headers and CORS policy applied to every response.
+#[derive(Debug, Clone)]
+pub struct SecurityConfig {
+    content_security_policy: String,
+    hsts_max_age_secs: u64,
+    allowed_origins: Vec<String>,
+    allowed_methods: Vec<String>,
+    allowed_headers: Vec<String>,
+    preflight_max_age_secs: u64,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            content_security_policy: "default-src 'none'; frame-ancestors 'none'".to_string(),
+            hsts_max_age_secs: 31_536_000,
+            allowed_origins: vec!["https://portal.company.com".to_string()],
+            allowed_methods: ["GET", "POST", "DELETE"]
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+            allowed_headers: [
+                "Authorization",
+                "Content-Type",
+                "Idempotency-Key",
+                CSRF_HEADER,
+            ]
+            .iter()
+            .map(|h| h.to_string())
+            .collect(),
+            preflight_max_age_secs: 600,
+        }
+    }
+}
+
+impl SecurityConfig {
+    fn origin_allowed(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == origin)
+    }
+
+    // Rejects cross-origin requests from origins outside the allow list.
+    // Requests without an Origin header are same-origin or non-browser clients.
+    fn check_origin(&self, request: &ApiRequest) -> Option<ApiResponse> {
+        match request.headers.get("Origin") {
+            Some(origin) if !self.origin_allowed(origin) => Some(ApiResponse::localized_error(
+                403,
+                "error-origin-not-allowed",
+            )),
+            _ => None,
+        }
+    }
+
+    // Answers a CORS preflight. Only exact matches on method and headers pass.
+    fn preflight(&self, request: &ApiRequest) -> ApiResponse {
+        let method_allowed = request
+            .headers
+            .get("Access-Control-Request-Method")
+            .is_some_and(|method| self.allowed_methods.contains(method));
+        let headers_allowed = request
+            .headers
+            .get("Access-Control-Request-Headers")
+            .map(|requested| {
+                requested
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|h| !h.is_empty())
+                    .all(|h| {
+                        self.allowed_headers
+                            .iter()
+                            .any(|allowed| allowed.eq_ignore_ascii_case(h))
+                    })
+            })
+            .unwrap_or(true);
+
+        if !method_allowed || !headers_allowed {
+            return ApiResponse::localized_error(403, "error-preflight-rejected");
+        }
+
+        let mut response = ApiResponse {
+            status_code: 204,
+            headers: HashMap::new(),
+            body: String::new(),
+            processing_time_ms: 0,
+            message: None,
+        };
+        response.headers.insert(
+            "Access-Control-Allow-Methods".to_string(),
+            self.allowed_methods.join(", "),
+        );
+        response.headers.insert(
+            "Access-Control-Allow-Headers".to_string(),
+            self.allowed_headers.join(", "),
+        );
+        response.headers.insert(
+            "Access-Control-Max-Age".to_string(),
+            self.preflight_max_age_secs.to_string(),
+        );
+        response
+    }
+
+    fn apply_headers(&self, request: &ApiRequest, response: &mut ApiResponse) {
+        let headers = &mut response.headers;
+        headers.insert(
+            "Content-Security-Policy".to_string(),
+            self.content_security_policy.clone(),
+        );
+        headers.insert(
+            "Strict-Transport-Security".to_string(),
+            format!("max-age={}; includeSubDomains", self.hsts_max_age_secs),
+        );
+        headers.insert("X-Content-Type-Options".to_string(), "nosniff".to_string());
+        headers.insert("X-Frame-Options".to_string(), "DENY".to_string());
+        headers.insert("Referrer-Policy".to_string(), "no-referrer".to_string());
+
+        if let Some(origin) = request.headers.get("Origin") {
+            if self.origin_allowed(origin) {
+                headers.insert("Access-Control-Allow-Origin".to_string(), origin.clone());
+                headers.insert(
+                    "Access-Control-Allow-Credentials".to_string(),
+                    "true".to_string(),
+                );
+                headers.insert("Vary".to_string(), "Origin".to_string());
+            }
+        }
+    }
+}
+
+const CSRF_HEADER: &str = "X-CSRF-Token";
+
+// The audience service tokens must name to be accepted here
+const SERVICE_NAME: &str = "enterprise_server";
+// Lets another server, such as the monitoring server, read /api/metrics
+const METRICS_READ_SCOPE: &str = "metrics:read";
+// Download links are signed with the service token key for this audience,
+// so a link can't pass as a service token or the other way round
+const EXPORT_DOWNLOAD_AUDIENCE: &str = "enterprise_server/exports";
+// Overrides the directory export files are written to
+const EXPORT_DIR_ENV: &str = "MCP_EXPORT_DIR";
+// Upper limit on the rows of a generated dataset
+const MAX_EXPORT_ROWS: u64 = 100_000;
+
+// Function: is_state_changing
+//
+// Methods that modify server state and therefore require a CSRF token.
+fn is_state_changing(method: &str) -> bool {
+    matches!(method, "POST" | "PUT" | "PATCH" | "DELETE")
+}
+
+// Function: constant_time_eq
+//
+// Compares tokens without leaking the position of the first mismatch.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
 }
 
 // Struct: ApiRequest
@@ -138,11 +666,14 @@ pub struct ApiRequest {
     method: String,
     path: String,
     headers: HashMap<String, String>,
-    #[allow(dead_code)]
     body: Option<String>,
     user_id: Option<Uuid>,
+    session_id: Option<Uuid>,
+    service_caller: Option<ServiceClaims>, // Set for calls from other servers
+    client_ip: String,
     #[allow(dead_code)]
     timestamp: DateTime<Utc>,
+    api_version: Option<String>, // Set by the server once the path is resolved
 }
 
 impl ApiRequest {
@@ -154,7 +685,11 @@ impl ApiRequest {
             headers: HashMap::new(),
             body: None,
             user_id: None,
+            session_id: None,
+            service_caller: None,
+            client_ip: "127.0.0.1".to_string(),
             timestamp: Utc::now(),
+            api_version: None,
         }
     }
 }
@@ -165,11 +700,54 @@ impl ApiRequest {
 #[derive(Debug, Clone)]
 pub struct ApiResponse {
     status_code: u16,
-    #[allow(dead_code)]
     headers: HashMap<String, String>,
-    #[allow(dead_code)]
     body: String,
     processing_time_ms: u64,
+    message: Option<LocalizedMessage>, // Set for errors that can be translated
+}
+
+// Struct: LocalizedMessage
+//
+// An error message from the catalog, kept with the response so it can be
+// translated once the client's language is known.
+#[derive(Debug, Clone)]
+struct LocalizedMessage {
+    id: &'static str,
+    args: Vec<(&'static str, String)>,
+}
+
+impl LocalizedMessage {
+    fn body(&self, messages: &Localizer) -> String {
+        let args = FluentArgs::from_iter(
+            self.args
+                .iter()
+                .map(|(name, value)| (*name, value.as_str())),
+        );
+        serde_json::json!({ "error": messages.message(self.id, Some(&args)) }).to_string()
+    }
+}
+
+// The API's error messages, one catalog per language
+const DEFAULT_LANGUAGE: &str = "en";
+const ERROR_MESSAGES: &[(&str, &str)] = &[
+    ("en", include_str!("../../examples/locales/en/errors.ftl")),
+    ("es", include_str!("../../examples/locales/es/errors.ftl")),
+    ("fr", include_str!("../../examples/locales/fr/errors.ftl")),
+    ("de", include_str!("../../examples/locales/de/errors.ftl")),
+];
+
+fn error_catalog() -> &'static Catalog {
+    static CATALOG: OnceLock<Catalog> = OnceLock::new();
+    CATALOG.get_or_init(|| {
+        let mut catalog =
+            Catalog::new(DEFAULT_LANGUAGE).expect("default language is a valid locale");
+        for (locale, source) in ERROR_MESSAGES {
+            catalog
+                .add_messages(locale, source)
+                .expect("bundled error messages are valid Fluent");
+        }
+        catalog
+    })
 }
 
 impl ApiResponse {
@@ -182,6 +760,7 @@ impl ApiResponse {
             headers,
             body,
             processing_time_ms,
+            message: None,
         }
     }
 
@@ -194,99 +773,180 @@ impl ApiResponse {
             headers,
             body: format!(r#"{{"error": "{}"}}"#, message),
             processing_time_ms,
+            message: None,
         }
     }
-}
-
-// Struct: EnterpriseServer
-//
-// Main enterprise server that combines all components.
-pub struct EnterpriseServer {
-    users: Arc<RwLock<HashMap<Uuid, User>>>,
-    sessions: Arc<RwLock<HashMap<Uuid, Session>>>,
-    user_cache: Cache<User>,
-    #[allow(dead_code)]
-    data_cache: Cache<String>,
-    metrics: Arc<RwLock<Metrics>>,
-}
 
-impl Default for EnterpriseServer {
-    fn default() -> Self {
-        Self::new()
+    // An error whose message comes from the error catalog, in English until
+    // the response is localized for the client
+    pub fn localized_error(status_code: u16, id: &'static str) -> Self {
+        Self::localized_error_with(status_code, id, Vec::new())
     }
-}
 
-impl EnterpriseServer {
-    pub fn new() -> Self {
+    pub fn localized_error_with(
+        status_code: u16,
+        id: &'static str,
+        args: Vec<(&'static str, String)>,
+    ) -> Self {
+        let message = LocalizedMessage { id, args };
+        let no_preference: &[&str] = &[];
         Self {
-            users: Arc::new(RwLock::new(HashMap::new())),
-            sessions: Arc::new(RwLock::new(HashMap::new())),
-            user_cache: Cache::new(),
-            data_cache: Cache::new(),
-            metrics: Arc::new(RwLock::new(Metrics::default())),
+            body: message.body(&error_catalog().localizer(no_preference)),
+            message: Some(message),
+            ..Self::error(status_code, String::new(), 0)
         }
     }
 
-    // Authentication methods
-    pub async fn create_user(
-        &self,
-        username: String,
-        email: String,
-        role: UserRole,
-    ) -> Result<Uuid, String> {
-        let user = User {
-            id: Uuid::new_v4(),
-            username: username.clone(),
-            email,
-            role,
-            created_at: Utc::now(),
-            last_active: Utc::now(),
+    // Translates a catalog error message into the best match for the
+    // client's Accept-Language header, falling back to English
+    fn localize(&mut self, accept_language: Option<&str>) {
+        let Some(message) = &self.message else {
+            return;
         };
+        let requested = accept_language
+            .map(i18n::parse_accept_language)
+            .unwrap_or_default();
+        let messages = error_catalog().localizer(&requested);
+        self.body = message.body(&messages);
+        self.headers
+            .insert("Content-Language".to_string(), messages.locale());
+    }
+}
 
-        let user_id = user.id;
-        let mut users = self.users.write().await;
+// Struct: JobRequest
+//
+// Request body for submitting a background job.
+#[derive(Debug, Deserialize)]
+pub struct JobRequest {
+    job_type: String,
+    #[serde(default)]
+    payload: serde_json::Value,
+    #[serde(default)]
+    high_priority: bool,
+}
 
-        // Check if username already exists
-        if users.values().any(|u| u.username == username) {
-            return Err("Username already exists".to_string());
-        }
+// Struct: JobRecord
+//
+// Tracks who submitted a job so only the owner can query or cancel it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    owner: Uuid,
+    job_type: String,
+    submitted_at: DateTime<Utc>,
+}
 
-        users.insert(user_id, user.clone());
+// Struct: IdempotencyEntry
+//
+// One idempotency key mapping, flattened for snapshots since JSON object
+// keys can't be tuples.
+#[derive(Debug, Serialize, Deserialize)]
+struct IdempotencyEntry {
+    user_id: Uuid,
+    key: String,
+    job_id: u64,
+}
 
-        // Cache the user
-        self.user_cache.set(user_id.to_string(), user, 3600).await;
+// Enum: ExportFormat
+//
+// File formats a data export can be written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    #[default]
+    Jsonl,
+}
 
-        info!("Created user: {} ({})", username, user_id);
-        Ok(user_id)
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
     }
 
-    pub async fn create_session(&self, user_id: Uuid) -> Result<Uuid, String> {
-        // Verify user exists
-        if !self.users.read().await.contains_key(&user_id) {
-            return Err("User not found".to_string());
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Jsonl => "application/x-ndjson",
         }
+    }
+}
 
-        let session = Session {
-            id: Uuid::new_v4(),
-            user_id,
-            created_at: Utc::now(),
-            expires_at: Utc::now() + chrono::Duration::hours(8),
-            last_accessed: Utc::now(),
-        };
+// Struct: ExportRequest
+//
+// Request body for starting a data export.
+#[derive(Debug, Deserialize)]
+pub struct ExportRequest {
+    dataset: String,
+    #[serde(default)]
+    format: ExportFormat,
+    rows: Option<u64>, // Size of the generated activity dataset
+}
 
-        let session_id = session.id;
-        let mut sessions = self.sessions.write().await;
-        sessions.insert(session_id, session);
+// Struct: ExportRecord
+//
+// Tracks who started an export and the file its job writes. Download links
+// name the export, never the file, so only files the server created can be
+// served.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRecord {
+    owner: Uuid,
+    dataset: String,
+    format: ExportFormat,
+    file_name: String,
+    submitted_at: DateTime<Utc>,
+}
 
-        // Update metrics
-        let mut metrics = self.metrics.write().await;
-        metrics.active_sessions += 1;
+// Struct: ExportConfig
+//
+// Where export files are written and how long download links stay valid.
+#[derive(Debug, Clone)]
+pub struct ExportConfig {
+    directory: PathBuf,
+    link_ttl: chrono::Duration,
+}
 
-        info!("Created session: {} for user: {}", session_id, user_id);
-        Ok(session_id)
+impl ExportConfig {
+    // Writes to the directory in MCP_EXPORT_DIR, or to one under the system
+    // temp directory when unset, so running the demo leaves the checkout alone
+    pub fn from_env() -> Self {
+        let directory = std::env::var(EXPORT_DIR_ENV)
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::temp_dir().join("mcp_enterprise_exports"));
+        Self {
+            directory,
+            link_ttl: chrono::Duration::minutes(15),
+        }
     }
 
-    pub async fn validate_session(&self, session_id: Uuid) -> Option<Uuid> {
+    // Resolves a file inside the export directory, refusing names that
+    // would reach outside it
+    fn path_for(&self, file_name: &str) -> Option<PathBuf> {
+        let name = Path::new(file_name);
+        (name.file_name() == Some(name.as_os_str())).then(|| self.directory.join(name))
+    }
+}
+
+// Struct: SessionAuth
+//
+// Middleware that resolves the `Authorization: Bearer <session id>` header
+// to the session's user. Requests without a valid session carry on
+// anonymously; the handlers decide whether that is enough. A service token
+// from another server must be valid for this server, or the request is
+// rejected outright.
+struct SessionAuth {
+    sessions: Arc<RwLock<HashMap<Uuid, Session>>>,
+    csrf_tokens: Arc<RwLock<HashMap<Uuid, String>>>, // session ID -> token
+    metrics: Arc<RwLock<Metrics>>,
+    service_key: ServiceTokenKey,
+}
+
+impl SessionAuth {
+    // Returns the session's user, dropping the session once it has expired
+    async fn validate_session(&self, session_id: Uuid) -> Option<Uuid> {
         let mut sessions = self.sessions.write().await;
 
         if let Some(session) = sessions.get_mut(&session_id) {
@@ -295,6 +955,7 @@ impl EnterpriseServer {
                 return Some(session.user_id);
             } else {
                 sessions.remove(&session_id);
+                self.csrf_tokens.write().await.remove(&session_id);
 
                 // Update metrics
                 let mut metrics = self.metrics.write().await;
@@ -303,42 +964,478 @@ impl EnterpriseServer {
         }
         None
     }
+}
 
-    // API endpoints
-    pub async fn handle_request(&self, mut request: ApiRequest) -> ApiResponse {
-        let start_time = std::time::Instant::now();
-
-        // Extract session token from headers
-        if let Some(auth_header) = request.headers.get("Authorization") {
-            if let Some(session_id_str) = auth_header.strip_prefix("Bearer ") {
-                if let Ok(session_id) = Uuid::parse_str(session_id_str) {
-                    request.user_id = self.validate_session(session_id).await;
+impl Middleware<ApiRequest, ApiResponse> for SessionAuth {
+    fn on_request<'a>(&'a self, request: &'a mut ApiRequest) -> BoxFuture<'a, Option<ApiResponse>> {
+        Box::pin(async move {
+            if let Some(token) = request.headers.get(SERVICE_TOKEN_HEADER) {
+                match self.service_key.verify(token, SERVICE_NAME) {
+                    Ok(claims) => request.service_caller = Some(claims),
+                    Err(e) => {
+                        warn!("Rejected service token: {}", e);
+                        return Some(ApiResponse::error(
+                            401,
+                            format!("Invalid service token: {}", e),
+                            0,
+                        ));
+                    }
                 }
             }
+
+            let session_id = request
+                .headers
+                .get("Authorization")
+                .and_then(|header| header.strip_prefix("Bearer "))
+                .and_then(|session_id| Uuid::parse_str(session_id).ok())?;
+            request.user_id = self.validate_session(session_id).await;
+            request.session_id = request.user_id.map(|_| session_id);
+            None
+        })
+    }
+}
+
+// Struct: RequestMetrics
+//
+// Middleware that counts every response, rejected ones included, stamps it
+// with its processing time and logs it.
+struct RequestMetrics {
+    metrics: Arc<RwLock<Metrics>>,
+}
+
+impl Middleware<ApiRequest, ApiResponse> for RequestMetrics {
+    fn on_response<'a>(
+        &'a self,
+        request: &'a ApiRequest,
+        response: &'a mut ApiResponse,
+        elapsed: Duration,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let processing_time = elapsed.as_millis() as u64;
+            response.processing_time_ms = processing_time;
+
+            let mut metrics = self.metrics.write().await;
+            metrics.total_requests += 1;
+
+            if response.status_code < 400 {
+                metrics.successful_requests += 1;
+            } else {
+                metrics.failed_requests += 1;
+            }
+
+            // Update average response time
+            let total_time = metrics.average_response_time_ms * (metrics.total_requests - 1) as f64
+                + processing_time as f64;
+            metrics.average_response_time_ms = total_time / metrics.total_requests as f64;
+
+            info!(
+                "Request {} {} -> {} ({}ms)",
+                request.method, request.path, response.status_code, processing_time
+            );
+        })
+    }
+}
+
+// Struct: EnterpriseServerState
+//
+// The durable state of the server, captured by snapshots. Caches and rate
+// limit windows are rebuilt from scratch after a restore.
+#[derive(Debug, Serialize, Deserialize)]
+struct EnterpriseServerState {
+    users: Vec<User>,
+    sessions: Vec<Session>,
+    metrics: Metrics,
+    jobs: HashMap<u64, JobRecord>,
+    idempotency_keys: Vec<IdempotencyEntry>,
+    csrf_tokens: HashMap<Uuid, String>,
+    job_queue: serde_json::Value,
+    #[serde(default)]
+    exports: HashMap<u64, ExportRecord>,
+}
+
+// Struct: EnterpriseServer
+//
+// Main enterprise server that combines all components.
+pub struct EnterpriseServer {
+    users: Arc<RwLock<HashMap<Uuid, User>>>,
+    sessions: Arc<RwLock<HashMap<Uuid, Session>>>,
+    user_cache: Cache<User>,
+    #[allow(dead_code)]
+    data_cache: Cache<String>,
+    metrics: Arc<RwLock<Metrics>>,
+    job_queue: TaskQueue,
+    jobs: Arc<RwLock<HashMap<u64, JobRecord>>>, // task ID -> job
+    idempotency_keys: Arc<RwLock<HashMap<(Uuid, String), u64>>>, // (user, key) -> task ID
+    exports: Arc<RwLock<HashMap<u64, ExportRecord>>>, // task ID -> export
+    export_config: ExportConfig,
+    rate_limits: RateLimitConfig,
+    rate_limiter: RateLimiter,
+    security: SecurityConfig,
+    csrf_tokens: Arc<RwLock<HashMap<Uuid, String>>>, // session ID -> token
+    api_versions: ApiVersionConfig,
+    connections: Arc<RwLock<HashMap<Uuid, PeerConnection>>>, // connection ID -> peer
+    keepalive: KeepaliveConfig,
+    auth: Arc<SessionAuth>,
+    middleware: MiddlewareChain<ApiRequest, ApiResponse>, // metrics, then auth
+}
+
+impl Default for EnterpriseServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EnterpriseServer {
+    pub fn new() -> Self {
+        Self::with_rate_limits(RateLimitConfig::default())
+    }
+
+    pub fn with_rate_limits(rate_limits: RateLimitConfig) -> Self {
+        let metrics = Arc::new(RwLock::new(Metrics::default()));
+        let auth = Arc::new(SessionAuth {
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            csrf_tokens: Arc::new(RwLock::new(HashMap::new())),
+            metrics: metrics.clone(),
+            service_key: ServiceTokenKey::from_env(),
+        });
+        let middleware = MiddlewareChain::new()
+            .with(Arc::new(RequestMetrics {
+                metrics: metrics.clone(),
+            }))
+            .with(auth.clone());
+
+        Self {
+            users: Arc::new(RwLock::new(HashMap::new())),
+            sessions: auth.sessions.clone(),
+            user_cache: Cache::new(),
+            data_cache: Cache::new(),
+            metrics,
+            job_queue: TaskQueue::new(),
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+            idempotency_keys: Arc::new(RwLock::new(HashMap::new())),
+            exports: Arc::new(RwLock::new(HashMap::new())),
+            export_config: ExportConfig::from_env(),
+            rate_limits,
+            rate_limiter: RateLimiter::new(),
+            security: SecurityConfig::default(),
+            csrf_tokens: auth.csrf_tokens.clone(),
+            api_versions: ApiVersionConfig::default(),
+            connections: Arc::new(RwLock::new(HashMap::new())),
+            keepalive: KeepaliveConfig::default(),
+            auth,
+            middleware,
         }
+    }
 
-        let response = match request.path.as_str() {
-            "/api/health" => self.handle_health_check().await,
-            "/api/users/profile" => self.handle_user_profile(&request).await,
-            "/api/data" => self.handle_data_request(&request).await,
-            "/api/metrics" => self.handle_metrics_request(&request).await,
-            _ => ApiResponse::error(404, "Not Found".to_string(), 0),
+    pub fn with_keepalive(mut self, keepalive: KeepaliveConfig) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    pub fn with_api_versions(mut self, api_versions: ApiVersionConfig) -> Self {
+        self.api_versions = api_versions;
+        self
+    }
+
+    // Authentication methods
+    pub async fn create_user(
+        &self,
+        username: String,
+        email: String,
+        role: UserRole,
+    ) -> Result<Uuid, String> {
+        let user = User {
+            id: Uuid::new_v4(),
+            username: username.clone(),
+            email,
+            role,
+            created_at: Utc::now(),
+            last_active: Utc::now(),
         };
 
-        let processing_time = start_time.elapsed().as_millis() as u64;
+        let user_id = user.id;
+        let mut users = self.users.write().await;
+
+        // Check if username already exists
+        if users.values().any(|u| u.username == username) {
+            return Err("Username already exists".to_string());
+        }
+
+        users.insert(user_id, user.clone());
+
+        // Cache the user
+        self.user_cache.set(user_id.to_string(), user, 3600).await;
+
+        info!("Created user: {} ({})", username, user_id);
+        Ok(user_id)
+    }
+
+    pub async fn create_session(&self, user_id: Uuid) -> Result<Uuid, String> {
+        // Verify user exists
+        if !self.users.read().await.contains_key(&user_id) {
+            return Err("User not found".to_string());
+        }
+
+        let session = Session {
+            id: Uuid::new_v4(),
+            user_id,
+            created_at: Utc::now(),
+            expires_at: Utc::now() + chrono::Duration::hours(8),
+            last_accessed: Utc::now(),
+        };
+
+        let session_id = session.id;
+        let mut sessions = self.sessions.write().await;
+        sessions.insert(session_id, session);
 
         // Update metrics
-        self.update_metrics(&response, processing_time).await;
+        let mut metrics = self.metrics.write().await;
+        metrics.active_sessions += 1;
 
-        info!(
-            "Request {} {} -> {} ({}ms)",
-            request.method, request.path, response.status_code, processing_time
+        info!("Created session: {} for user: {}", session_id, user_id);
+        Ok(session_id)
+    }
+
+    pub async fn validate_session(&self, session_id: Uuid) -> Option<Uuid> {
+        self.auth.validate_session(session_id).await
+    }
+
+    // API endpoints. Requests pass through the middleware chain, which
+    // authenticates them and records metrics, on their way to `process`.
+    // Error messages are translated last, so every layer can report them
+    // the same way.
+    pub async fn handle_request(&self, request: ApiRequest) -> ApiResponse {
+        let accept_language = request.headers.get("Accept-Language").cloned();
+        let mut response = self.middleware.run(request, self).await;
+        response.localize(accept_language.as_deref());
+        response
+    }
+
+    // Everything after authentication: versioning, rate limits, security
+    // checks and routing
+    async fn process(&self, request: &mut ApiRequest) -> ApiResponse {
+        // Map versioned paths onto the routes they share, so rate limits and
+        // handlers see the same path whichever version was called
+        let requested_path = request.path.clone();
+        if let Some((version, canonical)) = self.api_versions.resolve(&request.path) {
+            request.api_version = Some(version);
+            request.path = canonical;
+        }
+
+        let group = self.rate_limits.group_for(&request.path).clone();
+        let decision = self.check_rate_limit(&group, request).await;
+
+        // Per-request checks: rate limit, origin check, preflight, CSRF, then routing
+        let mut response = if !decision.allowed {
+            let mut response = ApiResponse::localized_error(429, "error-too-many-requests");
+            response.headers.insert(
+                "Retry-After".to_string(),
+                decision.reset_after.as_secs().max(1).to_string(),
+            );
+            response
+        } else if let Some(rejection) = self.security.check_origin(request) {
+            rejection
+        } else if request.method == "OPTIONS" {
+            self.security.preflight(request)
+        } else if let Some(rejection) = self.check_csrf(request).await {
+            rejection
+        } else {
+            self.route(request).await
+        };
+        self.security.apply_headers(request, &mut response);
+        self.apply_deprecation(request, &requested_path, &mut response)
+            .await;
+
+        response
+            .headers
+            .insert("RateLimit-Limit".to_string(), decision.limit.to_string());
+        response.headers.insert(
+            "RateLimit-Remaining".to_string(),
+            decision.remaining.to_string(),
+        );
+        response.headers.insert(
+            "RateLimit-Reset".to_string(),
+            decision.reset_after.as_secs().to_string(),
+        );
+        response.headers.insert(
+            "RateLimit-Policy".to_string(),
+            format!("{};w={}", decision.limit, group.window.as_secs()),
         );
 
-        ApiResponse {
-            processing_time_ms: processing_time,
-            ..response
+        if !decision.allowed {
+            let mut metrics = self.metrics.write().await;
+            metrics.throttled_requests += 1;
+            *metrics.throttled_by_group.entry(group.name).or_insert(0) += 1;
+        }
+
+        response
+    }
+
+    async fn route(&self, request: &ApiRequest) -> ApiResponse {
+        let version = request.api_version.as_deref().unwrap_or_default();
+        let Some(group) = self.api_versions.group(version) else {
+            return ApiResponse::localized_error_with(
+                404,
+                "error-unknown-api-version",
+                vec![("version", version.to_string())],
+            );
+        };
+        if group.sunset.is_some_and(|sunset| sunset <= Utc::now()) {
+            return match &group.successor {
+                Some(successor) => ApiResponse::localized_error_with(
+                    410,
+                    "error-api-retired",
+                    vec![
+                        ("version", version.to_string()),
+                        ("successor", successor.clone()),
+                    ],
+                ),
+                None => ApiResponse::localized_error_with(
+                    410,
+                    "error-api-retired-no-successor",
+                    vec![("version", version.to_string())],
+                ),
+            };
+        }
+
+        // Routes that changed in v2; everything else is shared
+        match (version, request.path.as_str()) {
+            ("v2", "/api/health") => return self.handle_health_check_v2().await,
+            ("v2", "/api/users/profile") => return self.handle_user_profile_v2(request).await,
+            _ => {}
+        }
+
+        match request.path.as_str() {
+            "/api/health" => self.handle_health_check().await,
+            "/api/csrf-token" => self.handle_csrf_token(request).await,
+            "/api/users/profile" => self.handle_user_profile(request).await,
+            "/api/data" => self.handle_data_request(request).await,
+            "/api/metrics" => self.handle_metrics_request(request).await,
+            "/api/jobs" | "/api/jobs/" => self.handle_job_submit(request).await,
+            path if path.starts_with("/api/jobs/") => self.handle_job_request(request).await,
+            "/api/export" | "/api/export/" => self.handle_export_submit(request).await,
+            path if path.starts_with("/api/export/download/") => {
+                self.handle_export_download(request).await
+            }
+            path if path.starts_with("/api/export/") => self.handle_export_status(request).await,
+            _ => ApiResponse::localized_error(404, "error-not-found"),
+        }
+    }
+
+    // Marks responses from a deprecated version with Deprecation, Sunset and
+    // successor Link headers, and counts the call so the remaining usage of
+    // the old version can be tracked before it is retired
+    async fn apply_deprecation(
+        &self,
+        request: &ApiRequest,
+        requested_path: &str,
+        response: &mut ApiResponse,
+    ) {
+        let Some(group) = request
+            .api_version
+            .as_deref()
+            .and_then(|version| self.api_versions.group(version))
+        else {
+            return;
+        };
+        let Some(sunset) = group.sunset else {
+            return;
+        };
+
+        response
+            .headers
+            .insert("Deprecation".to_string(), "true".to_string());
+        response.headers.insert(
+            "Sunset".to_string(),
+            sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        );
+        if let Some(successor) = &group.successor {
+            let successor_path = request
+                .path
+                .replacen("/api", &format!("/api/{}", successor), 1);
+            response.headers.insert(
+                "Link".to_string(),
+                format!("<{}>; rel=\"successor-version\"", successor_path),
+            );
+        }
+
+        let route = format!("{} {}", request.method, route_template(requested_path));
+        *self
+            .metrics
+            .write()
+            .await
+            .deprecated_route_usage
+            .entry(route)
+            .or_insert(0) += 1;
+    }
+
+    // State-changing requests made with a session must echo the session's
+    // CSRF token. Anonymous requests are left to the handlers to reject.
+    async fn check_csrf(&self, request: &ApiRequest) -> Option<ApiResponse> {
+        if !is_state_changing(&request.method) {
+            return None;
+        }
+        let session_id = request.session_id?;
+
+        let tokens = self.csrf_tokens.read().await;
+        let valid = match (tokens.get(&session_id), request.headers.get(CSRF_HEADER)) {
+            (Some(expected), Some(provided)) => constant_time_eq(expected, provided),
+            _ => false,
+        };
+
+        if valid {
+            None
+        } else {
+            info!(
+                "Rejected {} {} with missing or invalid CSRF token",
+                request.method, request.path
+            );
+            Some(ApiResponse::localized_error(
+                403,
+                "error-invalid-csrf-token",
+            ))
+        }
+    }
+
+    // GET /api/csrf-token
+    //
+    // Issues the session's CSRF token, creating it on first use.
+    async fn handle_csrf_token(&self, request: &ApiRequest) -> ApiResponse {
+        let Some(session_id) = request.session_id else {
+            return ApiResponse::localized_error(401, "error-unauthorized");
+        };
+
+        let mut tokens = self.csrf_tokens.write().await;
+        let token = tokens
+            .entry(session_id)
+            .or_insert_with(|| Uuid::new_v4().simple().to_string());
+
+        ApiResponse::success(serde_json::json!({ "csrf_token": token }).to_string(), 0)
+    }
+
+    // Applies the route group's per-IP limit, plus the per-user limit for
+    // authenticated requests
+    async fn check_rate_limit(
+        &self,
+        group: &RouteGroupLimit,
+        request: &ApiRequest,
+    ) -> RateLimitDecision {
+        let mut keys = vec![(
+            format!("{}:ip:{}", group.name, request.client_ip),
+            group.per_ip,
+        )];
+        if let Some(user_id) = request.user_id {
+            keys.push((format!("{}:user:{}", group.name, user_id), group.per_user));
         }
+
+        let decision = self.rate_limiter.check(&keys, group.window).await;
+        if !decision.allowed {
+            info!(
+                "Rate limited {} {} from {} (group: {})",
+                request.method, request.path, request.client_ip, group.name
+            );
+        }
+        decision
     }
 
     async fn handle_health_check(&self) -> ApiResponse {
@@ -351,10 +1448,64 @@ impl EnterpriseServer {
         ApiResponse::success(health_data.to_string(), 0)
     }
 
+    // GET /api/v2/health also reports the lifecycle of each API version
+    async fn handle_health_check_v2(&self) -> ApiResponse {
+        let versions: Vec<serde_json::Value> = self
+            .api_versions
+            .groups
+            .iter()
+            .filter(|group| group.enabled)
+            .map(|group| {
+                serde_json::json!({
+                    "version": group.version,
+                    "status": match group.sunset {
+                        Some(sunset) if sunset <= Utc::now() => "retired",
+                        Some(_) => "deprecated",
+                        None => "current",
+                    },
+                    "sunset": group.sunset.map(|sunset| sunset.to_rfc3339()),
+                })
+            })
+            .collect();
+
+        let health_data = serde_json::json!({
+            "status": "healthy",
+            "timestamp": Utc::now().to_rfc3339(),
+            "version": "2.0.0",
+            "api_versions": versions,
+        });
+
+        ApiResponse::success(health_data.to_string(), 0)
+    }
+
+    // GET /api/v2/users/profile wraps the profile in a data envelope and
+    // leaves out internal timestamps
+    async fn handle_user_profile_v2(&self, request: &ApiRequest) -> ApiResponse {
+        let response = self.handle_user_profile(request).await;
+        if response.status_code != 200 {
+            return response;
+        }
+
+        let Ok(user) = serde_json::from_str::<User>(&response.body) else {
+            return ApiResponse::localized_error(500, "error-invalid-user-record");
+        };
+        let body = serde_json::json!({
+            "data": {
+                "id": user.id,
+                "username": user.username,
+                "email": user.email,
+                "role": user.role,
+            },
+            "api_version": "v2",
+        });
+
+        ApiResponse::success(body.to_string(), 0)
+    }
+
     async fn handle_user_profile(&self, request: &ApiRequest) -> ApiResponse {
         let user_id = match request.user_id {
             Some(id) => id,
-            None => return ApiResponse::error(401, "Unauthorized".to_string(), 0),
+            None => return ApiResponse::localized_error(401, "error-unauthorized"),
         };
 
         // Try cache first
@@ -378,13 +1529,13 @@ impl EnterpriseServer {
 
             ApiResponse::success(serde_json::to_string(user).unwrap(), 0)
         } else {
-            ApiResponse::error(404, "User not found".to_string(), 0)
+            ApiResponse::localized_error(404, "error-user-not-found")
         }
     }
 
     async fn handle_data_request(&self, request: &ApiRequest) -> ApiResponse {
         if request.user_id.is_none() {
-            return ApiResponse::error(401, "Unauthorized".to_string(), 0);
+            return ApiResponse::localized_error(401, "error-unauthorized");
         }
 
         // Simulate data processing
@@ -400,50 +1551,479 @@ impl EnterpriseServer {
     }
 
     async fn handle_metrics_request(&self, request: &ApiRequest) -> ApiResponse {
-        // Check if user is admin
-        if let Some(user_id) = request.user_id {
+        // Other servers need a service token granting metrics:read;
+        // users must be admins
+        if let Some(caller) = request
+            .service_caller
+            .as_ref()
+            .filter(|caller| caller.has_scope(METRICS_READ_SCOPE))
+        {
+            info!("Metrics read by service {}", caller.subject);
+        } else if let Some(user_id) = request.user_id {
             let users = self.users.read().await;
             if let Some(user) = users.get(&user_id) {
                 if user.role != UserRole::Admin {
-                    return ApiResponse::error(403, "Forbidden".to_string(), 0);
+                    return ApiResponse::localized_error(403, "error-forbidden");
                 }
             } else {
-                return ApiResponse::error(404, "User not found".to_string(), 0);
+                return ApiResponse::localized_error(404, "error-user-not-found");
             }
         } else {
-            return ApiResponse::error(401, "Unauthorized".to_string(), 0);
+            return ApiResponse::localized_error(401, "error-unauthorized");
         }
 
         let metrics = self.metrics.read().await;
         ApiResponse::success(serde_json::to_string(&*metrics).unwrap(), 0)
     }
 
-    async fn update_metrics(&self, response: &ApiResponse, processing_time: u64) {
-        let mut metrics = self.metrics.write().await;
-        metrics.total_requests += 1;
+    // POST /api/jobs
+    //
+    // Queues a background job on the task queue and returns 202 with its ID.
+    // Repeating a submission with the same Idempotency-Key returns the
+    // original job instead of queueing a duplicate.
+    async fn handle_job_submit(&self, request: &ApiRequest) -> ApiResponse {
+        let Some(user_id) = request.user_id else {
+            return ApiResponse::localized_error(401, "error-unauthorized");
+        };
+        if request.method != "POST" {
+            return ApiResponse::localized_error(405, "error-method-not-allowed");
+        }
 
-        if response.status_code < 400 {
-            metrics.successful_requests += 1;
+        let idempotency_key = request
+            .headers
+            .get("Idempotency-Key")
+            .map(|key| (user_id, key.clone()));
+        if let Some(key) = &idempotency_key {
+            if let Some(job_id) = self.idempotency_keys.read().await.get(key) {
+                info!("Idempotent replay of job {} for key {}", job_id, key.1);
+                return self.job_accepted(*job_id, true);
+            }
+        }
+
+        let job: JobRequest = match request
+            .body
+            .as_deref()
+            .map(serde_json::from_str)
+            .unwrap_or_else(|| Err(serde::de::Error::custom("missing request body")))
+        {
+            Ok(job) => job,
+            Err(e) => return ApiResponse::error(400, format!("Invalid job request: {}", e), 0),
+        };
+
+        let task = match build_job_task(&job) {
+            Ok(task) => task,
+            Err(e) => return ApiResponse::error(400, e, 0),
+        };
+        let priority = if job.high_priority {
+            TaskPriority::High
         } else {
-            metrics.failed_requests += 1;
+            TaskPriority::Normal
+        };
+
+        // Hold the key map while queueing so concurrent retries can't double-submit
+        let mut idempotency_keys = self.idempotency_keys.write().await;
+        if let Some(key) = &idempotency_key {
+            if let Some(job_id) = idempotency_keys.get(key) {
+                return self.job_accepted(*job_id, true);
+            }
+        }
+
+        let job_id = match self
+            .job_queue
+            .add_task(
+                priority,
+                task,
+                format!("{} job for {}", job.job_type, user_id),
+            )
+            .await
+        {
+            Ok(job_id) => job_id,
+            Err(e) => return ApiResponse::error(503, e, 0),
+        };
+
+        if let Some(key) = idempotency_key {
+            idempotency_keys.insert(key, job_id);
+        }
+        drop(idempotency_keys);
+
+        self.jobs.write().await.insert(
+            job_id,
+            JobRecord {
+                owner: user_id,
+                job_type: job.job_type,
+                submitted_at: Utc::now(),
+            },
+        );
+
+        self.job_accepted(job_id, false)
+    }
+
+    // GET /api/jobs/{id} returns status and result; DELETE /api/jobs/{id} cancels
+    async fn handle_job_request(&self, request: &ApiRequest) -> ApiResponse {
+        let Some(user_id) = request.user_id else {
+            return ApiResponse::localized_error(401, "error-unauthorized");
+        };
+        let Some(job_id) = request
+            .path
+            .strip_prefix("/api/jobs/")
+            .and_then(|id| id.parse::<u64>().ok())
+        else {
+            return ApiResponse::localized_error(400, "error-invalid-job-id");
+        };
+
+        // Jobs owned by other users are reported as missing
+        let Some(job) = self
+            .jobs
+            .read()
+            .await
+            .get(&job_id)
+            .filter(|job| job.owner == user_id)
+            .cloned()
+        else {
+            return ApiResponse::localized_error(404, "error-job-not-found");
+        };
+
+        match request.method.as_str() {
+            "GET" => match self.job_queue.get_task(job_id).await {
+                Some(record) => {
+                    let body = serde_json::json!({
+                        "job_id": job_id,
+                        "job_type": job.job_type,
+                        "submitted_at": job.submitted_at.to_rfc3339(),
+                        "status": record.status,
+                        "result": record.result,
+                        "error": record.error,
+                    });
+                    ApiResponse::success(body.to_string(), 0)
+                }
+                None => ApiResponse::localized_error(404, "error-job-not-found"),
+            },
+            "DELETE" => match self.job_queue.cancel_task(job_id).await {
+                Ok(()) => ApiResponse::success(
+                    serde_json::json!({ "job_id": job_id, "status": "cancelled" }).to_string(),
+                    0,
+                ),
+                Err(e) => ApiResponse::error(409, e, 0),
+            },
+            _ => ApiResponse::localized_error(405, "error-method-not-allowed"),
+        }
+    }
+
+    fn job_accepted(&self, job_id: u64, replayed: bool) -> ApiResponse {
+        let body = serde_json::json!({
+            "job_id": job_id,
+            "status_url": format!("/api/jobs/{}", job_id),
+            "idempotent_replay": replayed,
+        });
+        ApiResponse {
+            status_code: 202,
+            ..ApiResponse::success(body.to_string(), 0)
+        }
+    }
+
+    // POST /api/export
+    //
+    // Starts a data export on the task queue and returns 202 with its ID.
+    // The users dataset holds email addresses, so only admins may export it.
+    async fn handle_export_submit(&self, request: &ApiRequest) -> ApiResponse {
+        let Some(user_id) = request.user_id else {
+            return ApiResponse::localized_error(401, "error-unauthorized");
+        };
+        if request.method != "POST" {
+            return ApiResponse::localized_error(405, "error-method-not-allowed");
+        }
+
+        let export: ExportRequest = match request
+            .body
+            .as_deref()
+            .map(serde_json::from_str)
+            .unwrap_or_else(|| Err(serde::de::Error::custom("missing request body")))
+        {
+            Ok(export) => export,
+            Err(e) => return ApiResponse::error(400, format!("Invalid export request: {}", e), 0),
+        };
+
+        let users = if export.dataset == "users" {
+            let users = self.users.read().await;
+            if users.get(&user_id).map(|user| &user.role) != Some(&UserRole::Admin) {
+                return ApiResponse::localized_error(403, "error-forbidden");
+            }
+            users.values().cloned().collect()
+        } else {
+            Vec::new()
+        };
+
+        let file_name = format!(
+            "export-{}.{}",
+            Uuid::new_v4().simple(),
+            export.format.extension()
+        );
+        let Some(path) = self.export_config.path_for(&file_name) else {
+            return ApiResponse::localized_error(500, "error-invalid-export-file-name");
+        };
+        let task = match build_export_task(&export, users, path) {
+            Ok(task) => task,
+            Err(e) => return ApiResponse::error(400, e, 0),
+        };
+
+        // Exports are bulk work and yield to interactive jobs
+        let export_id = match self
+            .job_queue
+            .add_task(
+                TaskPriority::Low,
+                task,
+                format!("{} export for {}", export.dataset, user_id),
+            )
+            .await
+        {
+            Ok(export_id) => export_id,
+            Err(e) => return ApiResponse::error(503, e, 0),
+        };
+
+        self.exports.write().await.insert(
+            export_id,
+            ExportRecord {
+                owner: user_id,
+                dataset: export.dataset,
+                format: export.format,
+                file_name,
+                submitted_at: Utc::now(),
+            },
+        );
+
+        let body = serde_json::json!({
+            "export_id": export_id,
+            "status_url": format!("/api/export/{}", export_id),
+        });
+        ApiResponse {
+            status_code: 202,
+            ..ApiResponse::success(body.to_string(), 0)
+        }
+    }
+
+    // GET /api/export/{id}
+    //
+    // Reports an export's progress. Once the file is written the response
+    // carries a download link signed for this export, valid for the
+    // configured TTL; polling again issues a fresh link.
+    async fn handle_export_status(&self, request: &ApiRequest) -> ApiResponse {
+        let Some(user_id) = request.user_id else {
+            return ApiResponse::localized_error(401, "error-unauthorized");
+        };
+        if request.method != "GET" {
+            return ApiResponse::localized_error(405, "error-method-not-allowed");
+        }
+        let Some(export_id) = request
+            .path
+            .strip_prefix("/api/export/")
+            .and_then(|id| id.parse::<u64>().ok())
+        else {
+            return ApiResponse::localized_error(400, "error-invalid-export-id");
+        };
+
+        // Exports owned by other users are reported as missing
+        let Some(export) = self
+            .exports
+            .read()
+            .await
+            .get(&export_id)
+            .filter(|export| export.owner == user_id)
+            .cloned()
+        else {
+            return ApiResponse::localized_error(404, "error-export-not-found");
+        };
+        let Some(record) = self.job_queue.get_task(export_id).await else {
+            return ApiResponse::localized_error(404, "error-export-not-found");
+        };
+
+        let mut body = serde_json::json!({
+            "export_id": export_id,
+            "dataset": export.dataset,
+            "format": export.format,
+            "submitted_at": export.submitted_at.to_rfc3339(),
+            "status": record.status,
+            "result": record.result,
+            "error": record.error,
+        });
+        if record.status == TaskStatus::Completed {
+            let ttl = self.export_config.link_ttl;
+            let token = self.auth.service_key.issue(
+                &user_id.to_string(),
+                EXPORT_DOWNLOAD_AUDIENCE,
+                &[&format!("export:{}", export_id)],
+                ttl,
+            );
+            body["download_url"] = format!("/api/export/download/{}", token).into();
+            body["expires_at"] = (Utc::now() + ttl).to_rfc3339().into();
+        }
+        ApiResponse::success(body.to_string(), 0)
+    }
+
+    // GET /api/export/download/{token}
+    //
+    // Serves a finished export to whoever holds a valid link. The signed
+    // token rather than a session is the credential, so the link can be
+    // handed to a browser or a plain HTTP client until it expires.
+    async fn handle_export_download(&self, request: &ApiRequest) -> ApiResponse {
+        if request.method != "GET" {
+            return ApiResponse::localized_error(405, "error-method-not-allowed");
         }
+        let token = request
+            .path
+            .strip_prefix("/api/export/download/")
+            .unwrap_or_default();
+        let claims = match self
+            .auth
+            .service_key
+            .verify(token, EXPORT_DOWNLOAD_AUDIENCE)
+        {
+            Ok(claims) => claims,
+            Err(e) => {
+                warn!("Rejected export download link: {}", e);
+                return ApiResponse::error(401, format!("Invalid download link: {}", e), 0);
+            }
+        };
+        let Some(export_id) = claims
+            .scopes
+            .iter()
+            .find_map(|scope| scope.strip_prefix("export:"))
+            .and_then(|id| id.parse::<u64>().ok())
+        else {
+            return ApiResponse::localized_error(401, "error-invalid-download-link");
+        };
 
-        // Update average response time
-        let total_time = metrics.average_response_time_ms * (metrics.total_requests - 1) as f64
-            + processing_time as f64;
-        metrics.average_response_time_ms = total_time / metrics.total_requests as f64;
+        let Some(export) = self
+            .exports
+            .read()
+            .await
+            .get(&export_id)
+            .filter(|export| export.owner.to_string() == claims.subject)
+            .cloned()
+        else {
+            return ApiResponse::localized_error(404, "error-export-not-found");
+        };
+        let Some(path) = self.export_config.path_for(&export.file_name) else {
+            return ApiResponse::localized_error(404, "error-export-not-found");
+        };
+
+        // Response bodies are strings, so the file is read whole
+        let body = match tokio::fs::read_to_string(&path).await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Export file {} unavailable: {}", path.display(), e);
+                return ApiResponse::localized_error(410, "error-export-gone");
+            }
+        };
+        let mut response = ApiResponse::success(body, 0);
+        response.headers.insert(
+            "Content-Type".to_string(),
+            export.format.content_type().to_string(),
+        );
+        response.headers.insert(
+            "Content-Disposition".to_string(),
+            format!("attachment; filename=\"{}\"", export.file_name),
+        );
+        response
     }
 
     pub async fn get_metrics(&self) -> Metrics {
         self.metrics.read().await.clone()
     }
 
+    // Connection keepalive
+
+    // Registers a long-lived connection for a valid session
+    pub async fn open_connection(&self, session_id: Uuid) -> Result<Uuid, String> {
+        if self.validate_session(session_id).await.is_none() {
+            return Err("Invalid or expired session".to_string());
+        }
+
+        let connection_id = Uuid::new_v4();
+        let connection = PeerConnection {
+            session_id,
+            keepalive: Keepalive::new(self.keepalive, tokio::time::Instant::now()),
+        };
+        self.connections
+            .write()
+            .await
+            .insert(connection_id, connection);
+
+        info!(
+            "Opened connection {} for session {}",
+            connection_id, session_id
+        );
+        Ok(connection_id)
+    }
+
+    // Any frame from the peer, including a ping response, shows it is alive
+    pub async fn record_peer_message(&self, connection_id: Uuid) {
+        if let Some(connection) = self.connections.write().await.get_mut(&connection_id) {
+            connection
+                .keepalive
+                .record_activity(tokio::time::Instant::now());
+        }
+    }
+
+    // Drops a connection and ends the session bound to it
+    pub async fn close_connection(&self, connection_id: Uuid, reason: &DisconnectReason) {
+        let Some(connection) = self.connections.write().await.remove(&connection_id) else {
+            return;
+        };
+        self.end_session(connection.session_id).await;
+
+        match reason {
+            DisconnectReason::Closed => info!(
+                "Connection {} closed ({}); ended session {}",
+                connection_id, reason, connection.session_id
+            ),
+            DisconnectReason::KeepaliveTimeout { .. } => warn!(
+                "Connection {} is dead ({}); ended session {}",
+                connection_id, reason, connection.session_id
+            ),
+        }
+    }
+
+    // Pings quiet peers and closes the ones that stopped answering. Call it
+    // regularly; a tick of half the keepalive timeout is precise enough.
+    pub async fn check_connections(&self) -> KeepaliveSweep {
+        let now = tokio::time::Instant::now();
+        let mut sweep = KeepaliveSweep::default();
+
+        for (connection_id, connection) in self.connections.write().await.iter_mut() {
+            match connection.keepalive.poll(now) {
+                KeepaliveAction::Wait => {}
+                KeepaliveAction::Ping(ping) => sweep.pings.push((*connection_id, ping)),
+                KeepaliveAction::Disconnect(reason) => {
+                    sweep.disconnected.push((*connection_id, reason))
+                }
+            }
+        }
+
+        for (connection_id, reason) in &sweep.disconnected {
+            self.close_connection(*connection_id, reason).await;
+        }
+        sweep
+    }
+
+    async fn end_session(&self, session_id: Uuid) {
+        if self.sessions.write().await.remove(&session_id).is_some() {
+            self.csrf_tokens.write().await.remove(&session_id);
+
+            let mut metrics = self.metrics.write().await;
+            metrics.active_sessions = metrics.active_sessions.saturating_sub(1);
+        }
+    }
+
     pub async fn cleanup_expired_sessions(&self) {
         let mut sessions = self.sessions.write().await;
         let now = Utc::now();
         let initial_count = sessions.len();
 
         sessions.retain(|_, session| session.expires_at > now);
+        self.csrf_tokens
+            .write()
+            .await
+            .retain(|session_id, _| sessions.contains_key(session_id));
 
         let removed_count = initial_count - sessions.len();
         if removed_count > 0 {
@@ -454,12 +2034,802 @@ impl EnterpriseServer {
     }
 }
 
+// Trait implementation: Endpoint
+//
+// What the middleware chain wraps: routing and the per-route checks.
+impl Endpoint<ApiRequest, ApiResponse> for EnterpriseServer {
+    fn handle<'a>(&'a self, request: &'a mut ApiRequest) -> BoxFuture<'a, ApiResponse> {
+        Box::pin(self.process(request))
+    }
+}
+
+// Trait implementation: StatefulServer
+//
+// The job queue is snapshotted through its own StatefulServer implementation,
+// so job history survives a restore alongside users and sessions.
+impl StatefulServer for EnterpriseServer {
+    const STATE_NAME: &'static str = "enterprise_server";
+
+    async fn snapshot(&self) -> Result<serde_json::Value, StateError> {
+        let state = EnterpriseServerState {
+            users: self.users.read().await.values().cloned().collect(),
+            sessions: self.sessions.read().await.values().cloned().collect(),
+            metrics: self.metrics.read().await.clone(),
+            jobs: self.jobs.read().await.clone(),
+            idempotency_keys: self
+                .idempotency_keys
+                .read()
+                .await
+                .iter()
+                .map(|((user_id, key), job_id)| IdempotencyEntry {
+                    user_id: *user_id,
+                    key: key.clone(),
+                    job_id: *job_id,
+                })
+                .collect(),
+            csrf_tokens: self.csrf_tokens.read().await.clone(),
+            job_queue: self.job_queue.snapshot().await?,
+            exports: self.exports.read().await.clone(),
+        };
+        Ok(serde_json::to_value(state)?)
+    }
+
+    async fn restore(&mut self, state: serde_json::Value) -> Result<(), StateError> {
+        let state: EnterpriseServerState = serde_json::from_value(state)?;
+
+        self.job_queue.restore(state.job_queue).await?;
+        *self.users.write().await = state
+            .users
+            .into_iter()
+            .map(|user| (user.id, user))
+            .collect();
+        *self.sessions.write().await = state
+            .sessions
+            .into_iter()
+            .map(|session| (session.id, session))
+            .collect();
+        *self.metrics.write().await = state.metrics;
+        *self.jobs.write().await = state.jobs;
+        *self.idempotency_keys.write().await = state
+            .idempotency_keys
+            .into_iter()
+            .map(|entry| ((entry.user_id, entry.key), entry.job_id))
+            .collect();
+        *self.csrf_tokens.write().await = state.csrf_tokens;
+        *self.exports.write().await = state.exports;
+        Ok(())
+    }
+}
+
+// Function: build_job_task
+//
+// Turns a job request into a task closure for the queue.
+fn build_job_task(
+    job: &JobRequest,
+) -> Result<impl Fn(&Heartbeat) -> Result<String, String> + Send + Sync + 'static, String> {
+    let payload = job.payload.clone();
+
+    let run: fn(&serde_json::Value) -> Result<String, String> = match job.job_type.as_str() {
+        "word_count" => |payload| {
+            let text = payload
+                .get("text")
+                .and_then(|t| t.as_str())
+                .ok_or("word_count requires a 'text' field")?;
+            Ok(format!("{} words", text.split_whitespace().count()))
+        },
+        "generate_report" => |payload| {
+            let rows = payload.get("rows").and_then(|r| r.as_u64()).unwrap_or(100);
+            // Simulate report generation work
+            std::thread::sleep(std::time::Duration::from_millis(rows.min(1_000)));
+            Ok(format!("Report generated with {} rows", rows))
+        },
+        other => return Err(format!("Unknown job type: {}", other)),
+    };
+
+    // Jobs finish well within the queue's default stuck_after window, so
+    // they do not need to send heartbeats
+    Ok(move |_heartbeat: &Heartbeat| run(&payload))
+}
+
+// Function: build_export_task
+//
+// Turns an export request into a task that streams the dataset to a file row
+// by row. Rows go to a ".part" file that is renamed once complete, so a
+// download never sees a half-written export.
+//
+// Arguments:
+//     export: The export request
+//     users: The user directory at submission, for the users dataset
+//     path: Where the finished export is written
+//
+// Returns:
+//     The task, or an error for an unknown dataset or row count
+fn build_export_task(
+    export: &ExportRequest,
+    users: Vec<User>,
+    path: PathBuf,
+) -> Result<impl Fn(&Heartbeat) -> Result<String, String> + Send + Sync + 'static, String> {
+    type RowFn = Box<dyn Fn(u64) -> Vec<serde_json::Value> + Send + Sync>;
+
+    let (columns, rows, row): (&[&str], u64, RowFn) = match export.dataset.as_str() {
+        "users" => (
+            &["id", "username", "email", "role", "created_at"],
+            users.len() as u64,
+            Box::new(move |i| {
+                let user = &users[i as usize];
+                vec![
+                    user.id.to_string().into(),
+                    user.username.clone().into(),
+                    user.email.clone().into(),
+                    format!("{:?}", user.role).into(),
+                    user.created_at.to_rfc3339().into(),
+                ]
+            }),
+        ),
+        "activity" => {
+            let rows = export.rows.unwrap_or(1_000);
+            if rows == 0 || rows > MAX_EXPORT_ROWS {
+                return Err(format!("rows must be between 1 and {}", MAX_EXPORT_ROWS));
+            }
+            let start = Utc::now() - chrono::Duration::seconds(rows as i64);
+            (
+                &["row", "timestamp", "user", "action", "duration_ms"],
+                rows,
+                Box::new(move |i| {
+                    let actions = ["login", "view_report", "update_profile", "logout"];
+                    vec![
+                        (i + 1).into(),
+                        (start + chrono::Duration::seconds(i as i64))
+                            .to_rfc3339()
+                            .into(),
+                        format!("user{}", i % 25).into(),
+                        actions[(i % 4) as usize].into(),
+                        (20 + i * 37 % 480).into(),
+                    ]
+                }),
+            )
+        }
+        other => return Err(format!("Unknown dataset: {}", other)),
+    };
+    let format = export.format;
+    let dataset = export.dataset.clone();
+
+    Ok(move |heartbeat: &Heartbeat| {
+        let partial = path.with_extension("part");
+        let write = || -> std::io::Result<()> {
+            if let Some(directory) = path.parent() {
+                std::fs::create_dir_all(directory)?;
+            }
+            let mut out = std::io::BufWriter::new(std::fs::File::create(&partial)?);
+            if format == ExportFormat::Csv {
+                writeln!(out, "{}", columns.join(","))?;
+            }
+            for i in 0..rows {
+                let values = row(i);
+                match format {
+                    ExportFormat::Csv => {
+                        let fields: Vec<String> = values.iter().map(csv_field).collect();
+                        writeln!(out, "{}", fields.join(","))?;
+                    }
+                    ExportFormat::Jsonl => {
+                        let object: serde_json::Map<String, serde_json::Value> = columns
+                            .iter()
+                            .map(|column| column.to_string())
+                            .zip(values)
+                            .collect();
+                        writeln!(out, "{}", serde_json::Value::Object(object))?;
+                    }
+                }
+                if (i + 1) % 1_000 == 0 {
+                    heartbeat.beat();
+                    // Simulate reading from a slow source
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+            }
+            out.flush()?;
+            std::fs::rename(&partial, &path)
+        };
+
+        write().map_err(|e| {
+            let _ = std::fs::remove_file(&partial);
+            format!("Export failed: {}", e)
+        })?;
+        Ok(format!("Exported {} {} rows", rows, dataset))
+    })
+}
+
+// Function: csv_field
+//
+// Formats a value as a CSV field, quoting it when needed.
+fn csv_field(value: &serde_json::Value) -> String {
+    let text = match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+// Function: fetch_csrf_token
+//
+// Fetches the CSRF token for a session, as a browser client would on page load.
+async fn fetch_csrf_token(
+    server: &EnterpriseServer,
+    session: Uuid,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut req = ApiRequest::new("GET".to_string(), "/api/csrf-token".to_string());
+    req.headers
+        .insert("Authorization".to_string(), format!("Bearer {}", session));
+
+    let body: serde_json::Value = serde_json::from_str(&server.handle_request(req).await.body)?;
+    body["csrf_token"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "CSRF token missing from response".into())
+}
+
+// Function: demo_security_middleware
+//
+// Demonstrates CORS preflight handling, origin checks, CSRF validation and
+// the security headers added to every response.
+async fn demo_security_middleware(
+    server: &EnterpriseServer,
+    session: Uuid,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Security Middleware ===");
+
+    let preflight = |origin: &str, method: &str| {
+        let mut req = ApiRequest::new("OPTIONS".to_string(), "/api/jobs".to_string());
+        req.headers.insert("Origin".to_string(), origin.to_string());
+        req.headers.insert(
+            "Access-Control-Request-Method".to_string(),
+            method.to_string(),
+        );
+        req.headers.insert(
+            "Access-Control-Request-Headers".to_string(),
+            "Authorization, X-CSRF-Token".to_string(),
+        );
+        req
+    };
+
+    for (origin, method) in [
+        ("https://portal.company.com", "POST"),
+        ("https://portal.company.com", "PUT"),
+        ("https://evil.example", "POST"),
+    ] {
+        let response = server.handle_request(preflight(origin, method)).await;
+        info!(
+            "Preflight {} from {}: {} (allow-origin: {})",
+            method,
+            origin,
+            response.status_code,
+            response
+                .headers
+                .get("Access-Control-Allow-Origin")
+                .map(String::as_str)
+                .unwrap_or("-")
+        );
+    }
+
+    // A state-changing request without the CSRF token is rejected
+    let submit = || {
+        let mut req = ApiRequest::new("POST".to_string(), "/api/jobs".to_string());
+        req.headers
+            .insert("Authorization".to_string(), format!("Bearer {}", session));
+        req.headers.insert(
+            "Origin".to_string(),
+            "https://portal.company.com".to_string(),
+        );
+        req.body = Some(r#"{"job_type": "word_count", "payload": {"text": "hello"}}"#.to_string());
+        req
+    };
+    let response = server.handle_request(submit()).await;
+    info!(
+        "POST /api/jobs without CSRF token: {}",
+        response.status_code
+    );
+
+    let mut req = submit();
+    req.headers
+        .insert(CSRF_HEADER.to_string(), "forged-token".to_string());
+    let response = server.handle_request(req).await;
+    info!(
+        "POST /api/jobs with forged CSRF token: {}",
+        response.status_code
+    );
+
+    let mut req = submit();
+    req.headers.insert(
+        CSRF_HEADER.to_string(),
+        fetch_csrf_token(server, session).await?,
+    );
+    let response = server.handle_request(req).await;
+    info!("POST /api/jobs with CSRF token: {}", response.status_code);
+
+    for header in [
+        "Content-Security-Policy",
+        "Strict-Transport-Security",
+        "X-Content-Type-Options",
+        "Access-Control-Allow-Origin",
+    ] {
+        info!("  {}: {}", header, response.headers[header]);
+    }
+
+    Ok(())
+}
+
+// Function: demo_background_jobs
+//
+// Demonstrates submitting, polling and cancelling background jobs.
+async fn demo_background_jobs(
+    server: &EnterpriseServer,
+    session: Uuid,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Background Jobs ===");
+
+    let csrf_token = fetch_csrf_token(server, session).await?;
+    let authorized = |method: &str, path: &str| {
+        let mut req = ApiRequest::new(method.to_string(), path.to_string());
+        req.headers
+            .insert("Authorization".to_string(), format!("Bearer {}", session));
+        req.headers
+            .insert(CSRF_HEADER.to_string(), csrf_token.clone());
+        req
+    };
+
+    // Submit the same report twice with one idempotency key: one job is queued
+    let mut job_id = 0;
+    for attempt in 1..=2 {
+        let mut req = authorized("POST", "/api/jobs");
+        req.headers
+            .insert("Idempotency-Key".to_string(), "report-2024-q1".to_string());
+        req.body = Some(r#"{"job_type": "generate_report", "payload": {"rows": 200}}"#.to_string());
+
+        let response = server.handle_request(req).await;
+        info!(
+            "Submit attempt {}: {} {}",
+            attempt, response.status_code, response.body
+        );
+        let body: serde_json::Value = serde_json::from_str(&response.body)?;
+        job_id = body["job_id"].as_u64().unwrap_or_default();
+    }
+
+    // Queue a second job and cancel it before the worker gets to it
+    let mut req = authorized("POST", "/api/jobs");
+    req.body = Some(r#"{"job_type": "word_count", "payload": {"text": "a b c"}}"#.to_string());
+    let body: serde_json::Value = serde_json::from_str(&server.handle_request(req).await.body)?;
+    let cancel_id = body["job_id"].as_u64().unwrap_or_default();
+    let response = server
+        .handle_request(authorized("DELETE", &format!("/api/jobs/{}", cancel_id)))
+        .await;
+    info!(
+        "Cancel job {}: {} {}",
+        cancel_id, response.status_code, response.body
+    );
+
+    // Poll the report job until it finishes
+    for _ in 0..20 {
+        let response = server
+            .handle_request(authorized("GET", &format!("/api/jobs/{}", job_id)))
+            .await;
+        let body: serde_json::Value = serde_json::from_str(&response.body)?;
+        if body["status"] == "completed" || body["status"] == "failed" {
+            info!("Job {} finished: {}", job_id, response.body);
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+
+    Ok(())
+}
+
+// Function: demo_data_export
+//
+// Demonstrates the async export pattern: start an export, poll it until the
+// file is written, then fetch the file through the signed link it returns.
+async fn demo_data_export(
+    server: &EnterpriseServer,
+    admin_session: Uuid,
+    employee_session: Uuid,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Data Export ===");
+
+    let admin_csrf = fetch_csrf_token(server, admin_session).await?;
+    let employee_csrf = fetch_csrf_token(server, employee_session).await?;
+    let authorized = |session: Uuid, csrf_token: &str, method: &str, path: &str| {
+        let mut req = ApiRequest::new(method.to_string(), path.to_string());
+        req.headers
+            .insert("Authorization".to_string(), format!("Bearer {}", session));
+        req.headers
+            .insert(CSRF_HEADER.to_string(), csrf_token.to_string());
+        req
+    };
+
+    // Only admins may export the user directory
+    let mut req = authorized(employee_session, &employee_csrf, "POST", "/api/export");
+    req.body = Some(r#"{"dataset": "users", "format": "csv"}"#.to_string());
+    let response = server.handle_request(req).await;
+    info!("Employee exporting users: {}", response.status_code);
+
+    let mut req = authorized(admin_session, &admin_csrf, "POST", "/api/export");
+    req.body = Some(r#"{"dataset": "activity", "format": "csv", "rows": 5000}"#.to_string());
+    let response = server.handle_request(req).await;
+    info!("Start export: {} {}", response.status_code, response.body);
+    let body: serde_json::Value = serde_json::from_str(&response.body)?;
+    let export_id = body["export_id"].as_u64().unwrap_or_default();
+    let status_path = format!("/api/export/{}", export_id);
+
+    // Poll until the file is written and a download link comes back
+    let mut download_url = None;
+    for _ in 0..50 {
+        let response = server
+            .handle_request(authorized(admin_session, &admin_csrf, "GET", &status_path))
+            .await;
+        let body: serde_json::Value = serde_json::from_str(&response.body)?;
+        if let Some(url) = body["download_url"].as_str() {
+            info!(
+                "Export {} ready: {} (link expires {})",
+                export_id, body["result"], body["expires_at"]
+            );
+            download_url = Some(url.to_string());
+            break;
+        }
+        if body["status"] == "failed" {
+            warn!("Export {} failed: {}", export_id, body["error"]);
+            return Ok(());
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    let Some(download_url) = download_url else {
+        warn!("Export {} did not finish in time", export_id);
+        return Ok(());
+    };
+
+    // The link needs no session, so it can be handed to a browser
+    let response = server
+        .handle_request(ApiRequest::new("GET".to_string(), download_url.clone()))
+        .await;
+    info!(
+        "Download: {} {} ({} lines)",
+        response.status_code,
+        response.headers["Content-Type"],
+        response.body.lines().count()
+    );
+    for line in response.body.lines().take(3) {
+        info!("  {}", line);
+    }
+
+    // Other users can't see the export, and an altered link is refused
+    let response = server
+        .handle_request(authorized(
+            employee_session,
+            &employee_csrf,
+            "GET",
+            &status_path,
+        ))
+        .await;
+    info!(
+        "Employee polling the admin's export: {}",
+        response.status_code
+    );
+    let response = server
+        .handle_request(ApiRequest::new(
+            "GET".to_string(),
+            format!("{}x", download_url),
+        ))
+        .await;
+    info!("Download with an altered link: {}", response.status_code);
+
+    Ok(())
+}
+
+// Function: demo_rate_limiting
+//
+// Demonstrates per-user and per-IP throttling on a tightly limited route group.
+async fn demo_rate_limiting() -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Rate Limiting ===");
+
+    let server = EnterpriseServer::with_rate_limits(RateLimitConfig {
+        groups: vec![RouteGroupLimit::new(
+            "data",
+            "/api/data",
+            3,
+            3,
+            Duration::from_secs(60),
+        )],
+        ..RateLimitConfig::default()
+    });
+
+    let user_id = server
+        .create_user(
+            "jane_doe".to_string(),
+            "jane@company.com".to_string(),
+            UserRole::Employee,
+        )
+        .await?;
+    let session = server.create_session(user_id).await?;
+
+    // The user limit trips first, even when switching IPs
+    for (attempt, ip) in ["10.0.0.1", "10.0.0.1", "10.0.0.2", "10.0.0.2"]
+        .iter()
+        .enumerate()
+    {
+        let mut req = ApiRequest::new("GET".to_string(), "/api/data".to_string());
+        req.headers
+            .insert("Authorization".to_string(), format!("Bearer {}", session));
+        req.client_ip = ip.to_string();
+
+        let response = server.handle_request(req).await;
+        info!(
+            "User request {} from {}: {} (remaining: {})",
+            attempt + 1,
+            ip,
+            response.status_code,
+            response.headers["RateLimit-Remaining"]
+        );
+    }
+
+    // Anonymous requests from one IP hit the IP limit; health checks are a
+    // separate group and stay available
+    for path in ["/api/data", "/api/data", "/api/data", "/api/health"] {
+        let mut req = ApiRequest::new("GET".to_string(), path.to_string());
+        req.client_ip = "10.0.0.2".to_string();
+
+        let response = server.handle_request(req).await;
+        info!(
+            "Anonymous {} from 10.0.0.2: {} (Retry-After: {})",
+            path,
+            response.status_code,
+            response
+                .headers
+                .get("Retry-After")
+                .map(String::as_str)
+                .unwrap_or("-")
+        );
+    }
+
+    let metrics = server.get_metrics().await;
+    info!(
+        "Throttled requests: {} {:?}",
+        metrics.throttled_requests, metrics.throttled_by_group
+    );
+
+    server.rate_limiter.cleanup_expired().await;
+
+    Ok(())
+}
+
+// Function: demo_api_versioning
+//
+// Demonstrates v1 and v2 route groups, deprecation headers on v1, a version
+// that ships disabled, and a retired version.
+async fn demo_api_versioning() -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== API Versioning ===");
+
+    let server = EnterpriseServer::new();
+    let user_id = server
+        .create_user(
+            "versioned_client".to_string(),
+            "client@company.com".to_string(),
+            UserRole::Employee,
+        )
+        .await?;
+    let session = server.create_session(user_id).await?;
+
+    let get = |path: &str| {
+        let mut req = ApiRequest::new("GET".to_string(), path.to_string());
+        req.headers
+            .insert("Authorization".to_string(), format!("Bearer {}", session));
+        req
+    };
+
+    for path in [
+        "/api/users/profile",
+        "/api/v1/users/profile",
+        "/api/v2/users/profile",
+        "/api/v1/health",
+        "/api/v2/health",
+        "/api/v3/health",
+    ] {
+        let response = server.handle_request(get(path)).await;
+        info!(
+            "{} -> {} (Sunset: {}, Link: {}): {}",
+            path,
+            response.status_code,
+            response
+                .headers
+                .get("Sunset")
+                .map(String::as_str)
+                .unwrap_or("-"),
+            response
+                .headers
+                .get("Link")
+                .map(String::as_str)
+                .unwrap_or("-"),
+            response.body
+        );
+    }
+
+    let metrics = server.get_metrics().await;
+    info!(
+        "Deprecated route usage: {:?}",
+        metrics.deprecated_route_usage
+    );
+
+    // A v3 that is still behind its flag, and a v1 whose sunset has passed
+    let server = EnterpriseServer::new().with_api_versions(ApiVersionConfig {
+        groups: vec![
+            ApiVersionGroup::new("v1").deprecated(Utc::now() - chrono::Duration::days(1), "v2"),
+            ApiVersionGroup::new("v2"),
+            ApiVersionGroup::new("v3").disabled(),
+        ],
+        legacy_version: "v1".to_string(),
+    });
+    for path in ["/api/v1/health", "/api/v2/health", "/api/v3/health"] {
+        let response = server
+            .handle_request(ApiRequest::new("GET".to_string(), path.to_string()))
+            .await;
+        info!("{} -> {}: {}", path, response.status_code, response.body);
+    }
+
+    // Errors come back in the client's language where there is a
+    // translation, and in English otherwise
+    for accept_language in ["de-AT, en;q=0.5", "fr-CA", "ja"] {
+        let mut req = ApiRequest::new("GET".to_string(), "/api/v1/health".to_string());
+        req.headers
+            .insert("Accept-Language".to_string(), accept_language.to_string());
+        let response = server.handle_request(req).await;
+        info!(
+            "Accept-Language: {} -> {} ({}): {}",
+            accept_language,
+            response.status_code,
+            response
+                .headers
+                .get("Content-Language")
+                .map(String::as_str)
+                .unwrap_or("-"),
+            response.body
+        );
+    }
+
+    Ok(())
+}
+
+// Function: demo_keepalive
+//
+// Demonstrates keepalive on long-lived connections: the client that answers
+// pings keeps its session, the one that goes silent loses it.
+async fn demo_keepalive() -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Connection Keepalive ===");
+
+    let server = EnterpriseServer::new().with_keepalive(KeepaliveConfig::new(
+        Duration::from_millis(50),
+        Duration::from_millis(100),
+    ));
+    let mut peers = Vec::new();
+    for username in ["live_client", "silent_client"] {
+        let user_id = server
+            .create_user(
+                username.to_string(),
+                format!("{}@company.com", username),
+                UserRole::Employee,
+            )
+            .await?;
+        let session_id = server.create_session(user_id).await?;
+        peers.push((server.open_connection(session_id).await?, session_id));
+    }
+    let [(live, live_session), (_, silent_session)] = peers[..] else {
+        unreachable!()
+    };
+
+    // Only the live client answers its pings
+    for _ in 0..20 {
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        let sweep = server.check_connections().await;
+        for (connection_id, ping) in &sweep.pings {
+            info!("Sent {} to connection {}", ping.method, connection_id);
+            if *connection_id == live {
+                server.record_peer_message(live).await;
+            }
+        }
+        if !sweep.disconnected.is_empty() {
+            break;
+        }
+    }
+
+    info!(
+        "Live client session valid: {}",
+        server.validate_session(live_session).await.is_some()
+    );
+    info!(
+        "Silent client session valid: {}",
+        server.validate_session(silent_session).await.is_some()
+    );
+
+    server
+        .close_connection(live, &DisconnectReason::Closed)
+        .await;
+    info!(
+        "Active sessions after both disconnects: {}",
+        server.get_metrics().await.active_sessions
+    );
+
+    Ok(())
+}
+
+// Function: demo_service_tokens
+//
+// Demonstrates calls from other servers. The token here is signed locally
+// with the shared key; in a deployment the caller obtains it from the auth
+// service of example 13 with its client credentials.
+async fn demo_service_tokens(server: &EnterpriseServer) -> Result<(), Box<dyn std::error::Error>> {
+    info!("=== Service Tokens ===");
+    let key = ServiceTokenKey::from_env();
+    let ttl = chrono::Duration::minutes(5);
+
+    let cases = [
+        (
+            "metrics reader",
+            key.issue("monitoring", SERVICE_NAME, &[METRICS_READ_SCOPE], ttl),
+        ),
+        (
+            "without scope",
+            key.issue("gateway", SERVICE_NAME, &[], ttl),
+        ),
+        (
+            "other audience",
+            key.issue("gateway", "user-service", &[METRICS_READ_SCOPE], ttl),
+        ),
+        (
+            "forged",
+            ServiceTokenKey::new("not the shared key").issue(
+                "gateway",
+                SERVICE_NAME,
+                &[METRICS_READ_SCOPE],
+                ttl,
+            ),
+        ),
+    ];
+    for (case, token) in cases {
+        let mut request = ApiRequest::new("GET".to_string(), "/api/metrics".to_string());
+        request
+            .headers
+            .insert(SERVICE_TOKEN_HEADER.to_string(), token);
+        let response = server.handle_request(request).await;
+        info!(
+            "Service token ({}): {} {}",
+            case,
+            response.status_code,
+            if response.status_code == 200 {
+                ""
+            } else {
+                &response.body
+            }
+        );
+    }
+
+    Ok(())
+}
+
 // Function: demo_enterprise_server
 //
 // Demonstrates the enterprise server functionality.
-async fn demo_enterprise_server() -> Result<(), Box<dyn std::error::Error>> {
+async fn demo_enterprise_server(
+    state_command: &StateCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
     info!("=== Creating Enterprise Server ===");
-    let server = EnterpriseServer::new();
+    let mut server = EnterpriseServer::new();
+    state_command.restore(&mut server).await?;
+
+    // The demo creates fixed usernames, which a restored server already has
+    if state_command.restore_from.is_some() {
+        demo_restored_state(&server).await;
+        state_command.dump(&server).await?;
+        return Ok(());
+    }
 
     // Create users
     let admin_id = server
@@ -522,6 +2892,11 @@ async fn demo_enterprise_server() -> Result<(), Box<dyn std::error::Error>> {
         );
     }
 
+    demo_background_jobs(&server, employee_session).await?;
+    demo_data_export(&server, admin_session, employee_session).await?;
+    demo_security_middleware(&server, employee_session).await?;
+    demo_service_tokens(&server).await?;
+
     // Cleanup and show final metrics
     server.user_cache.cleanup_expired().await;
     server.cleanup_expired_sessions().await;
@@ -545,19 +2920,150 @@ async fn demo_enterprise_server() -> Result<(), Box<dyn std::error::Error>> {
             * 100.0
     );
 
+    state_command.dump(&server).await?;
+
     Ok(())
 }
 
+// Function: demo_restored_state
+//
+// Summarizes a server restored from a snapshot, including the restored jobs.
+async fn demo_restored_state(server: &EnterpriseServer) {
+    info!("=== Restored State ===");
+    info!("Users: {}", server.users.read().await.len());
+    info!("Sessions: {}", server.sessions.read().await.len());
+
+    let jobs = server.jobs.read().await;
+    for (job_id, job) in jobs.iter() {
+        if let Some(record) = server.job_queue.get_task(*job_id).await {
+            info!(
+                "Job {} ({}, submitted {}): {:?}",
+                job_id,
+                job.job_type,
+                job.submitted_at.to_rfc3339(),
+                record.status
+            );
+        }
+    }
+
+    let metrics = server.get_metrics().await;
+    info!(
+        "Metrics carried over: {} requests, {} throttled",
+        metrics.total_requests, metrics.throttled_requests
+    );
+}
+
 // Function: main
 //
 // Entry point demonstrating the enterprise server implementation.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt().with_env_filter("info").init();
+    let _telemetry = mcp_rust_examples::logging::init("info");
 
     info!("Starting Enterprise Server Example");
-    demo_enterprise_server().await?;
+    let state_command = StateCommand::from_env()?;
+    demo_enterprise_server(&state_command).await?;
+    demo_rate_limiting().await?;
+    demo_api_versioning().await?;
+    demo_keepalive().await?;
     info!("Enterprise Server Example completed successfully");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A server with one employee, and a request carrying their session
+    async fn server_with_session() -> (EnterpriseServer, impl Fn(&str, &str) -> ApiRequest) {
+        let server = EnterpriseServer::new();
+        let user_id = server
+            .create_user(
+                "jane_doe".to_string(),
+                "jane@company.com".to_string(),
+                UserRole::Employee,
+            )
+            .await
+            .unwrap();
+        let session = server.create_session(user_id).await.unwrap();
+        let request = move |method: &str, path: &str| {
+            let mut request = ApiRequest::new(method.to_string(), path.to_string());
+            request
+                .headers
+                .insert("Authorization".to_string(), format!("Bearer {}", session));
+            request
+        };
+        (server, request)
+    }
+
+    #[tokio::test]
+    async fn test_check_csrf_rejects_missing_and_forged_tokens() {
+        let (server, request) = server_with_session().await;
+        let response = server
+            .handle_request(request("GET", "/api/csrf-token"))
+            .await;
+        assert_eq!(response.status_code, 200);
+        let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        let token = body["csrf_token"].as_str().unwrap().to_string();
+
+        let missing = request("POST", "/api/jobs");
+        assert_eq!(server.handle_request(missing).await.status_code, 403);
+
+        let mut forged = request("POST", "/api/jobs");
+        forged
+            .headers
+            .insert(CSRF_HEADER.to_string(), Uuid::new_v4().simple().to_string());
+        assert_eq!(server.handle_request(forged).await.status_code, 403);
+
+        let mut genuine = request("POST", "/api/jobs");
+        genuine.headers.insert(CSRF_HEADER.to_string(), token);
+        assert!(server.check_csrf(&genuine).await.is_none());
+        assert_ne!(server.handle_request(genuine).await.status_code, 403);
+    }
+
+    #[tokio::test]
+    async fn test_requests_over_the_window_limit_get_429() {
+        let server = EnterpriseServer::with_rate_limits(RateLimitConfig {
+            groups: vec![RouteGroupLimit::new(
+                "data",
+                "/api/data",
+                2,
+                2,
+                Duration::from_secs(60),
+            )],
+            ..RateLimitConfig::default()
+        });
+        let request = || ApiRequest::new("GET".to_string(), "/api/data".to_string());
+
+        for remaining in ["1", "0"] {
+            let response = server.handle_request(request()).await;
+            assert_ne!(response.status_code, 429);
+            assert_eq!(response.headers["RateLimit-Remaining"], remaining);
+        }
+        let response = server.handle_request(request()).await;
+        assert_eq!(response.status_code, 429);
+        assert_eq!(response.headers["RateLimit-Limit"], "2");
+        assert_eq!(response.headers["RateLimit-Remaining"], "0");
+        assert_eq!(response.headers["RateLimit-Policy"], "2;w=60");
+        let reset: u64 = response.headers["RateLimit-Reset"].parse().unwrap();
+        assert!(reset <= 60);
+        assert!(response.headers.contains_key("Retry-After"));
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_evicts_keys_by_their_own_window() {
+        let limiter = RateLimiter::new();
+        let short = [("short:ip:1".to_string(), 5)];
+        let long = [("long:ip:1".to_string(), 5)];
+        limiter.check(&short, Duration::from_millis(20)).await;
+        limiter.check(&long, Duration::from_secs(60)).await;
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        limiter.cleanup_expired().await;
+
+        let windows = limiter.windows.read().await;
+        assert!(!windows.contains_key("short:ip:1"));
+        assert!(windows.contains_key("long:ip:1"));
+    }
+}
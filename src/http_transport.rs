@@ -0,0 +1,972 @@
+//! The MCP streamable HTTP transport, for servers deployed remotely.
+//!
+//! Everything goes through one endpoint, `/mcp` by default:
+//!
+//! - `POST` carries one JSON-RPC message or a batch. Requests are answered in
+//!   the response body; notifications, responses and requests cancelled with
+//!   `notifications/cancelled` get `202 Accepted`.
+//! - `GET` opens a Server-Sent Events stream on which the server sends its own
+//!   messages, such as `notifications/tools/list_changed`,
+//!   `notifications/resources/updated` and `notifications/message`. The
+//!   first stream of a session also carries the `elicitation/create`
+//!   requests of its tool calls, which wait until the client POSTs the answer.
+//! - `DELETE` ends the session.
+//!
+//! The `initialize` response carries an `Mcp-Session-Id` header that the
+//! client sends with every later request, and what tools keep through
+//! [`session::current`] lasts until the session ends. Browsers can be tricked into
+//! reaching a server on localhost, so requests with an `Origin` header are
+//! only accepted from localhost or the configured origins.
+//!
+//! Clients that negotiated [compression](crate::compression) in `initialize`
+//! get large responses with a `Content-Encoding` header, and may send
+//! compressed bodies the same way. Event streams are not compressed.
+//!
+//! ```bash
+//! curl -i -X POST http://127.0.0.1:8080/mcp -H 'Content-Type: application/json' \
+//!   -d '{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}'
+//! ```
+
+use crate::cancellation::InFlight;
+use crate::capabilities::{self, ClientCapabilities};
+use crate::compression::{self, Compression, Compressor};
+use crate::elicitation::{self, ElicitationSink};
+use crate::protocol::{JsonRpcError, JsonRpcRequest, JsonRpcResponse, LoggingMessageParams};
+use crate::resource_diff::ResourceUpdate;
+use crate::server::McpServer;
+use crate::session::{self, SessionStore};
+use crate::transport::{
+    log_message, next_log_message, next_tool_change, next_update, resource_updated,
+    tools_list_changed,
+};
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{self, HeaderValue};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, OwnedMutexGuard};
+use tokio::task::JoinHandle;
+
+/// Where the MCP endpoint is served unless configured otherwise.
+pub const DEFAULT_PATH: &str = "/mcp";
+
+/// The header that carries the session id.
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Larger request bodies are refused with `413 Payload Too Large`.
+pub const MAX_BODY_BYTES: usize = 4 * 1024 * 1024;
+
+// Proxies close idle connections, so quiet event streams send a comment now
+// and then. A failed write is also how a vanished client is noticed.
+const SSE_KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// Serves an [`McpServer`] over streamable HTTP.
+pub struct HttpTransport {
+    addr: SocketAddr,
+    path: String,
+    allowed_origins: Vec<String>,
+}
+
+impl HttpTransport {
+    /// Listens on `addr`. Bind to a loopback address unless the server is
+    /// meant to be reached from other machines.
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            path: DEFAULT_PATH.to_string(),
+            allowed_origins: Vec::new(),
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Accepts requests from browser pages on these origins, e.g.
+    /// `"https://app.example.com"`, besides localhost.
+    pub fn with_allowed_origins(mut self, origins: Vec<String>) -> Self {
+        self.allowed_origins = origins;
+        self
+    }
+
+    /// Serves `server` until the listener fails. The server's
+    /// [`self_check`](McpServer::self_check) runs first.
+    pub async fn serve(self, server: Arc<McpServer>) -> hyper::Result<()> {
+        let (_, running) = self.bind(server)?;
+        running.await
+    }
+
+    /// Serves `server` on a background task and returns the address it
+    /// listens on, which is useful when binding to port 0 in tests.
+    pub fn spawn(
+        self,
+        server: Arc<McpServer>,
+    ) -> hyper::Result<(SocketAddr, JoinHandle<hyper::Result<()>>)> {
+        let (addr, running) = self.bind(server)?;
+        Ok((addr, tokio::spawn(running)))
+    }
+
+    fn bind(
+        self,
+        server: Arc<McpServer>,
+    ) -> hyper::Result<(
+        SocketAddr,
+        impl std::future::Future<Output = hyper::Result<()>>,
+    )> {
+        let mcp_server = server.clone();
+        let state = Arc::new(Shared {
+            server,
+            path: self.path,
+            allowed_origins: self.allowed_origins,
+            sessions: Mutex::new(HashMap::new()),
+        });
+        let make_service = make_service_fn(move |_connection| {
+            let state = state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(state.handle(request).await) }
+                }))
+            }
+        });
+        let running = Server::try_bind(&self.addr)?.serve(make_service);
+        let addr = running.local_addr();
+
+        Ok((addr, async move {
+            tracing::info!(server = %mcp_server.info().name, %addr, "Serving MCP over HTTP");
+            mcp_server.self_check().await;
+            running.await
+        }))
+    }
+}
+
+struct Shared {
+    server: Arc<McpServer>,
+    path: String,
+    allowed_origins: Vec<String>,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+#[derive(Clone)]
+struct Session {
+    // Dropping the sender ends the session's event streams
+    ended: broadcast::Sender<()>,
+    // What the client declared in initialize, for the requests that follow
+    client: Arc<ClientCapabilities>,
+    // Requests being handled, which later POSTs may cancel
+    in_flight: InFlight,
+    // What tools keep for the session between requests
+    store: Arc<SessionStore>,
+    // How large responses are compressed, if the client accepts it
+    compressor: Option<Compressor>,
+    // Questions tools ask the user, and the stream that sends them
+    elicitations: ElicitationSink,
+    elicitation_requests: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<JsonRpcRequest>>>,
+}
+
+impl Shared {
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        if request.uri().path() != self.path {
+            return status(StatusCode::NOT_FOUND);
+        }
+        let origin = request.headers().get(header::ORIGIN);
+        if !origin_allowed(origin, &self.allowed_origins) {
+            tracing::warn!(?origin, "Rejected request from a foreign origin");
+            return status(StatusCode::FORBIDDEN);
+        }
+
+        match *request.method() {
+            Method::POST => self.post(request).await,
+            Method::GET => self.open_stream(&request),
+            Method::DELETE => self.end_session(&request),
+            _ => status(StatusCode::METHOD_NOT_ALLOWED),
+        }
+    }
+
+    async fn post(&self, request: Request<Body>) -> Response<Body> {
+        let too_large = request
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse::<usize>().ok())
+            .is_some_and(|length| length > MAX_BODY_BYTES);
+        if too_large {
+            return status(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        let session = session_id(&request);
+        let encoding = match content_encoding(&request) {
+            Ok(encoding) => encoding,
+            Err(rejection) => return status(rejection),
+        };
+        let body = match read_body(request.into_body()).await {
+            Ok(body) => body,
+            Err(rejection) => return status(rejection),
+        };
+        let body = match encoding {
+            Some(algorithm) => match algorithm.decompress(&body, MAX_BODY_BYTES) {
+                Ok(body) => Bytes::from(body),
+                Err(compression::CompressionError::TooLarge(_)) => {
+                    return status(StatusCode::PAYLOAD_TOO_LARGE)
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to decompress request body");
+                    return status(StatusCode::BAD_REQUEST);
+                }
+            },
+            None => body,
+        };
+        let message: Value = match serde_json::from_slice(&body) {
+            Ok(message) => message,
+            Err(e) => {
+                let error = JsonRpcResponse::failure(None, JsonRpcError::parse_error(e));
+                return json(StatusCode::BAD_REQUEST, &error);
+            }
+        };
+
+        // Only initialize may start without a session; it creates one
+        if message.get("method").and_then(Value::as_str) == Some("initialize") {
+            return self.initialize(&message).await;
+        }
+        let session = match self.session(session.as_deref()) {
+            Ok(session) => session,
+            Err(rejection) => return status(rejection),
+        };
+        let handled = session::scope(
+            session.store.clone(),
+            elicitation::scope(
+                session.elicitations.clone(),
+                self.handle_messages(message, &session),
+            ),
+        );
+        capabilities::scope(Some(session.client.clone()), handled).await
+    }
+
+    async fn handle_messages(&self, message: Value, session: &Session) -> Response<Body> {
+        match message {
+            Value::Array(batch) => {
+                let mut responses = Vec::new();
+                for message in batch {
+                    responses.extend(self.handle_message(&message.to_string(), session).await);
+                }
+                if responses.is_empty() {
+                    status(StatusCode::ACCEPTED)
+                } else {
+                    encoded_json(StatusCode::OK, &responses, session.compressor)
+                }
+            }
+            message => match self.handle_message(&message.to_string(), session).await {
+                Some(response) => encoded_json(StatusCode::OK, &response, session.compressor),
+                // Notifications, responses to requests the server sent, and
+                // cancelled requests
+                None => status(StatusCode::ACCEPTED),
+            },
+        }
+    }
+
+    async fn handle_message(&self, message: &str, session: &Session) -> Option<JsonRpcResponse> {
+        // Answers to elicitation requests go to the tool call that asked
+        if session.elicitations.resolve(message) || session.in_flight.cancel(message).is_some() {
+            return None;
+        }
+        session
+            .in_flight
+            .track(message, self.server.handle_message(message))
+            .await
+            .flatten()
+    }
+
+    async fn initialize(&self, message: &Value) -> Response<Body> {
+        let Some(response) = self.server.handle_message(&message.to_string()).await else {
+            return status(StatusCode::ACCEPTED);
+        };
+        let mut http_response = json(StatusCode::OK, &response);
+        if response.error.is_none() {
+            let session = uuid::Uuid::new_v4().to_string();
+            let client = ClientCapabilities::from_initialize(&message["params"]);
+            let compressor = self.server.compressor(Some(&client));
+            let (elicitations, elicitation_requests) = elicitation::channel();
+            self.sessions.lock().unwrap().insert(
+                session.clone(),
+                Session {
+                    ended: broadcast::channel(1).0,
+                    client: Arc::new(client),
+                    in_flight: InFlight::new(),
+                    store: Arc::new(SessionStore::with_limits(self.server.session_limits())),
+                    compressor,
+                    elicitations,
+                    elicitation_requests: Arc::new(tokio::sync::Mutex::new(elicitation_requests)),
+                },
+            );
+            tracing::info!(%session, "Session started");
+            if let Ok(value) = HeaderValue::from_str(&session) {
+                http_response.headers_mut().insert(SESSION_HEADER, value);
+            }
+        }
+        http_response
+    }
+
+    // The live session, or how to refuse a request without one: 400 without
+    // an id, 404 for an id that expired or never existed, which tells the
+    // client to start over.
+    fn session(&self, session: Option<&str>) -> Result<Session, StatusCode> {
+        let session = session.ok_or(StatusCode::BAD_REQUEST)?;
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session)
+            .cloned()
+            .ok_or(StatusCode::NOT_FOUND)
+    }
+
+    fn open_stream(&self, request: &Request<Body>) -> Response<Body> {
+        let accepts_events = request
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/event-stream"));
+        if !accepts_events {
+            return status(StatusCode::METHOD_NOT_ALLOWED);
+        }
+        let Some(session) = session_id(request) else {
+            return status(StatusCode::BAD_REQUEST);
+        };
+        let Some((ended, requests)) = self.sessions.lock().unwrap().get(&session).map(|session| {
+            (
+                session.ended.subscribe(),
+                session.elicitation_requests.clone(),
+            )
+        }) else {
+            return status(StatusCode::NOT_FOUND);
+        };
+
+        let (sender, body) = Body::channel();
+        // Subscribe now, so nothing is missed before the task first runs
+        let events = ServerEvents {
+            updates: self.server.resource_updates(),
+            tool_changes: self.server.tool_list_changes(),
+            log_messages: self.server.log_messages(),
+            // A second stream leaves them to the first
+            requests: requests.try_lock_owned().ok(),
+            server: self.server.clone(),
+            ended,
+        };
+        tokio::spawn(stream_events(events, sender));
+        let mut response = Response::new(body);
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        response
+    }
+
+    fn end_session(&self, request: &Request<Body>) -> Response<Body> {
+        let Some(session) = session_id(request) else {
+            return status(StatusCode::BAD_REQUEST);
+        };
+        match self.sessions.lock().unwrap().remove(&session) {
+            Some(_) => {
+                tracing::info!(%session, "Session ended");
+                status(StatusCode::OK)
+            }
+            None => status(StatusCode::NOT_FOUND),
+        }
+    }
+}
+
+// Whether a request with this Origin header may be served: requests from
+// outside a browser carry none, pages on localhost and the allowed origins may
+pub(crate) fn origin_allowed(origin: Option<&HeaderValue>, allowed_origins: &[String]) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    let Ok(origin) = origin.to_str() else {
+        return false;
+    };
+    if allowed_origins.iter().any(|allowed| allowed == origin) {
+        return true;
+    }
+    let host = origin.split_once("://").map_or(origin, |(_, rest)| rest);
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split([':', '/']).next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+struct ServerEvents {
+    updates: Option<broadcast::Receiver<ResourceUpdate>>,
+    tool_changes: Option<broadcast::Receiver<()>>,
+    log_messages: Option<broadcast::Receiver<LoggingMessageParams>>,
+    requests: Option<OwnedMutexGuard<mpsc::UnboundedReceiver<JsonRpcRequest>>>,
+    // Decides which log messages the client wants
+    server: Arc<McpServer>,
+    ended: broadcast::Receiver<()>,
+}
+
+// Sends the server's notifications as SSE events until the client goes away
+// or its session ends
+async fn stream_events(events: ServerEvents, mut sender: hyper::body::Sender) {
+    let ServerEvents {
+        mut updates,
+        mut tool_changes,
+        mut log_messages,
+        mut requests,
+        server,
+        mut ended,
+    } = events;
+    let start = tokio::time::Instant::now() + SSE_KEEPALIVE;
+    let mut keepalive = tokio::time::interval_at(start, SSE_KEEPALIVE);
+
+    loop {
+        // Notifications the server drops are skipped like missed ones
+        let deliver = |notification: JsonRpcRequest| {
+            server
+                .delivers_notification(&notification)
+                .then(|| sse_event(&notification))
+        };
+        let event = tokio::select! {
+            update = next_update(&mut updates) => update.map(resource_updated).and_then(deliver),
+            changed = next_tool_change(&mut tool_changes) => {
+                changed.then(tools_list_changed).and_then(deliver)
+            }
+            message = next_log_message(&mut log_messages, &server) => {
+                message.map(log_message).and_then(deliver)
+            }
+            request = next_request(&mut requests) => request.map(|request| sse_event(&request)),
+            _ = keepalive.tick() => Some(Bytes::from_static(b": keepalive\n\n")),
+            _ = ended.recv() => break,
+        };
+        let Some(event) = event else {
+            continue;
+        };
+        if sender.send_data(event).await.is_err() {
+            break;
+        }
+    }
+}
+
+// Waits for the next request the server sends the client. On a stream
+// without the session's requests this never returns.
+async fn next_request(
+    requests: &mut Option<OwnedMutexGuard<mpsc::UnboundedReceiver<JsonRpcRequest>>>,
+) -> Option<JsonRpcRequest> {
+    let Some(receiver) = requests.as_mut() else {
+        return std::future::pending().await;
+    };
+    let request = receiver.recv().await;
+    if request.is_none() {
+        *requests = None;
+    }
+    request
+}
+
+fn sse_event(message: &impl Serialize) -> Bytes {
+    let data = serde_json::to_string(message).unwrap_or_default();
+    Bytes::from(format!("event: message\ndata: {}\n\n", data))
+}
+
+fn session_id(request: &Request<Body>) -> Option<String> {
+    request
+        .headers()
+        .get(SESSION_HEADER)
+        .and_then(|session| session.to_str().ok())
+        .map(str::to_string)
+}
+
+// The algorithm a request body is compressed with, or 415 Unsupported Media
+// Type for an encoding this crate cannot read
+fn content_encoding(request: &Request<Body>) -> Result<Option<Compression>, StatusCode> {
+    let Some(encoding) = request.headers().get(header::CONTENT_ENCODING) else {
+        return Ok(None);
+    };
+    match encoding.to_str().map(str::trim) {
+        Ok("identity") => Ok(None),
+        Ok(encoding) => encoding
+            .parse()
+            .map(Some)
+            .map_err(|_| StatusCode::UNSUPPORTED_MEDIA_TYPE),
+        Err(_) => Err(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+    }
+}
+
+/// Reads a request body chunk by chunk, giving up with `413 Payload Too
+/// Large` as soon as it passes [`MAX_BODY_BYTES`]. A chunked body carries no
+/// `Content-Length`, so the size can only be checked while it arrives.
+async fn read_body(mut body: Body) -> Result<Bytes, StatusCode> {
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| {
+            tracing::warn!(error = %e, "Failed to read request body");
+            StatusCode::BAD_REQUEST
+        })?;
+        if buffer.len() + chunk.len() > MAX_BODY_BYTES {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(buffer))
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+}
+
+fn json(code: StatusCode, body: &impl Serialize) -> Response<Body> {
+    encoded_json(code, body, None)
+}
+
+// Like json, compressed with `compressor` if the body is large enough
+fn encoded_json(
+    code: StatusCode,
+    body: &impl Serialize,
+    compressor: Option<Compressor>,
+) -> Response<Body> {
+    let body = serde_json::to_vec(body).unwrap_or_default();
+    let compressed = compressor.and_then(|compressor| {
+        let compressed = compressor.compress(&body)?;
+        Some((compressor.algorithm, compressed))
+    });
+    let mut response = match compressed {
+        Some((algorithm, compressed)) => {
+            let mut response = Response::new(Body::from(compressed));
+            response.headers_mut().insert(
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static(algorithm.as_str()),
+            );
+            response
+        }
+        None => Response::new(Body::from(body)),
+    };
+    *response.status_mut() = code;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elicitation::{
+        Elicitation, ElicitationOutcome, ElicitationSchema, TransportElicitor, CREATE_METHOD,
+    };
+    use crate::error::McpError;
+    use crate::protocol::{RequestId, Tool};
+    use crate::registry::ToolRegistry;
+    use crate::server::{ToolHandler, ToolRouter};
+    use futures::future::BoxFuture;
+
+    struct Ping;
+
+    impl ToolHandler for Ping {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "ping".to_string(),
+                description: "Answer pong".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async { Ok(serde_json::json!("pong")) })
+        }
+    }
+
+    struct Hang;
+
+    impl ToolHandler for Hang {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "hang".to_string(),
+                description: "Never answer".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    struct Document;
+
+    impl ToolHandler for Document {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "document".to_string(),
+                description: "Answer a long document".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async { Ok(serde_json::json!("lorem ipsum ".repeat(1000))) })
+        }
+    }
+
+    struct Confirm;
+
+    impl ToolHandler for Confirm {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "confirm".to_string(),
+                description: "Ask the user to confirm".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async {
+                let elicitation =
+                    Elicitation::new(Arc::new(TransportElicitor), Duration::from_secs(5));
+                let schema = ElicitationSchema::new().boolean("confirm", "Go ahead?", true);
+                match elicitation.ask("Proceed?", schema).await {
+                    Ok(ElicitationOutcome::Accepted(content)) => Ok(content["confirm"].clone()),
+                    Ok(outcome) => Ok(Value::from(format!("{:?}", outcome))),
+                    Err(e) => Err(McpError::Unavailable(e.to_string())),
+                }
+            })
+        }
+    }
+
+    fn spawn(server: McpServer) -> String {
+        let transport = HttpTransport::new(([127, 0, 0, 1], 0).into());
+        let (addr, _) = transport.spawn(Arc::new(server)).unwrap();
+        format!("http://{}{}", addr, DEFAULT_PATH)
+    }
+
+    async fn post(
+        client: &reqwest::Client,
+        url: &str,
+        session: Option<&str>,
+        body: &str,
+    ) -> reqwest::Response {
+        let mut request = client
+            .post(url)
+            .header("content-type", "application/json")
+            .body(body.to_string());
+        if let Some(session) = session {
+            request = request.header(SESSION_HEADER, session);
+        }
+        request.send().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let url = spawn(McpServer::new(Arc::new(
+            ToolRouter::new("pinger").with_handler(Ping),
+        )));
+        let client = reqwest::Client::new();
+        let call = r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"ping"}}"#;
+
+        let response = post(&client, &url, None, call).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+        let response = post(&client, &url, None, initialize).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let session = response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let result: JsonRpcResponse = response.json().await.unwrap();
+        assert_eq!(result.result.unwrap()["serverInfo"]["name"], "pinger");
+
+        let response = post(&client, &url, Some(&session), call).await;
+        let result: JsonRpcResponse = response.json().await.unwrap();
+        assert_eq!(result.result.unwrap()["content"][0]["text"], r#""pong""#);
+
+        // Notifications are accepted without a body, batches answered together
+        let initialized = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+        let response = post(&client, &url, Some(&session), initialized).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let batch = format!("[{},{}]", call, initialized);
+        let response = post(&client, &url, Some(&session), &batch).await;
+        let results: Vec<JsonRpcResponse> = response.json().await.unwrap();
+        assert_eq!(results.len(), 1);
+
+        let response = post(&client, &url, Some(&session), "{not json").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = client
+            .delete(&url)
+            .header(SESSION_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = post(&client, &url, Some(&session), call).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_compresses_for_clients_that_accept_it() {
+        let url = spawn(McpServer::new(Arc::new(
+            ToolRouter::new("documents")
+                .with_handler(Ping)
+                .with_handler(Document),
+        )));
+        let client = reqwest::Client::new();
+        let initialize = |accepted: &str| {
+            format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"initialize","params":{{"capabilities":{{"experimental":{{"compression":{}}}}}}}}}"#,
+                accepted
+            )
+        };
+        let document =
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"document"}}"#;
+
+        let response = post(&client, &url, None, &initialize(r#"["gzip"]"#)).await;
+        let session = response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let result: JsonRpcResponse = response.json().await.unwrap();
+        assert_eq!(
+            result.result.unwrap()["capabilities"]["experimental"]["compression"]["algorithm"],
+            "gzip"
+        );
+
+        let response = post(&client, &url, Some(&session), document).await;
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        let body = response.bytes().await.unwrap();
+        let body = compression::decompress(&body, MAX_BODY_BYTES).unwrap();
+        let result: JsonRpcResponse = serde_json::from_slice(&body).unwrap();
+        assert!(result.result.unwrap()["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("lorem ipsum"));
+
+        // Small responses go out as they are
+        let ping = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"ping"}}"#;
+        let response = post(&client, &url, Some(&session), ping).await;
+        assert!(response.headers().get("content-encoding").is_none());
+
+        // Requests may be compressed with any supported algorithm
+        let response = client
+            .post(&url)
+            .header(SESSION_HEADER, &session)
+            .header("content-encoding", "zstd")
+            .body(Compression::Zstd.compress(ping.as_bytes()).unwrap())
+            .send()
+            .await
+            .unwrap();
+        let result: JsonRpcResponse = response.json().await.unwrap();
+        assert_eq!(result.result.unwrap()["content"][0]["text"], r#""pong""#);
+        let response = client
+            .post(&url)
+            .header(SESSION_HEADER, &session)
+            .header("content-encoding", "br")
+            .body(ping)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        // Clients that accept nothing this server offers get plain responses
+        let response = post(&client, &url, None, &initialize(r#"["brotli"]"#)).await;
+        let session = response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let result: JsonRpcResponse = response.json().await.unwrap();
+        assert!(result.result.unwrap()["capabilities"]["experimental"]
+            .get("compression")
+            .is_none());
+        let response = post(&client, &url, Some(&session), document).await;
+        assert!(response.headers().get("content-encoding").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancels_requests_of_the_session() {
+        let url = spawn(McpServer::new(Arc::new(
+            ToolRouter::new("pinger").with_handler(Hang),
+        )));
+        let client = reqwest::Client::new();
+        let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+        let response = post(&client, &url, None, initialize).await;
+        let session = response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let call =
+            r#"{"jsonrpc":"2.0","id":"slow","method":"tools/call","params":{"name":"hang"}}"#;
+        let pending = tokio::spawn({
+            let (client, url, session) = (client.clone(), url.clone(), session.clone());
+            async move { post(&client, &url, Some(&session), call).await.status() }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let cancel =
+            r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":"slow"}}"#;
+        let response = post(&client, &url, Some(&session), cancel).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let status = tokio::time::timeout(Duration::from_secs(5), pending)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(status, StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_sends_notifications() {
+        let registry = Arc::new(ToolRegistry::new("dynamic"));
+        let url = spawn(McpServer::new(registry.clone()));
+        let client = reqwest::Client::new();
+
+        let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+        let response = post(&client, &url, None, initialize).await;
+        let session = response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let mut events = client
+            .get(&url)
+            .header("accept", "text/event-stream")
+            .header(SESSION_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(events.headers()["content-type"], "text/event-stream");
+
+        registry.register(Ping);
+        let chunk = events.chunk().await.unwrap().unwrap();
+        let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+        assert!(chunk.starts_with("event: message\ndata: "));
+        assert!(chunk.contains("notifications/tools/list_changed"));
+
+        // Ending the session closes the stream
+        client
+            .delete(&url)
+            .header(SESSION_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+        assert!(events.chunk().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_elicitation_requests_go_out_on_the_event_stream() {
+        let server = McpServer::new(Arc::new(ToolRouter::new("asker").with_handler(Confirm)));
+        let url = spawn(server);
+        let client = reqwest::Client::new();
+
+        let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"capabilities":{"elicitation":{}}}}"#;
+        let response = post(&client, &url, None, initialize).await;
+        let session = response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let mut events = client
+            .get(&url)
+            .header("accept", "text/event-stream")
+            .header(SESSION_HEADER, &session)
+            .send()
+            .await
+            .unwrap();
+
+        let call = r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"confirm"}}"#;
+        let pending = tokio::spawn({
+            let (client, url, session) = (client.clone(), url.clone(), session.clone());
+            async move {
+                let response = post(&client, &url, Some(&session), call).await;
+                response.json::<JsonRpcResponse>().await.unwrap()
+            }
+        });
+
+        // The call waits while the client is asked over the event stream
+        let chunk = events.chunk().await.unwrap().unwrap();
+        let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+        let data = chunk
+            .strip_prefix("event: message\ndata: ")
+            .unwrap()
+            .trim_end();
+        let request: JsonRpcRequest = serde_json::from_str(data).unwrap();
+        assert_eq!(request.method, CREATE_METHOD);
+        assert_eq!(request.params["message"], "Proceed?");
+
+        let answer = JsonRpcResponse::success(
+            request.id,
+            serde_json::json!({ "action": "accept", "content": { "confirm": true } }),
+        );
+        let answer = serde_json::to_string(&answer).unwrap();
+        let response = post(&client, &url, Some(&session), &answer).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let result = tokio::time::timeout(Duration::from_secs(5), pending)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.id, Some(RequestId::Number(2)));
+        assert_eq!(result.result.unwrap()["content"][0]["text"], "true");
+    }
+
+    #[tokio::test]
+    async fn test_limits_chunked_bodies() {
+        let (mut sender, body) = Body::channel();
+        // Without a Content-Length the limit is only noticed while reading
+        tokio::spawn(async move {
+            let chunk = Bytes::from(vec![b' '; MAX_BODY_BYTES / 4]);
+            while sender.send_data(chunk.clone()).await.is_ok() {}
+        });
+        assert_eq!(read_body(body).await, Err(StatusCode::PAYLOAD_TOO_LARGE));
+
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from_static(b"{}")).await.unwrap();
+        });
+        assert_eq!(read_body(body).await, Ok(Bytes::from_static(b"{}")));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_foreign_origins() {
+        let transport = HttpTransport::new(([127, 0, 0, 1], 0).into())
+            .with_allowed_origins(vec!["https://app.example.com".to_string()]);
+        let server = McpServer::new(Arc::new(ToolRouter::new("pinger")));
+        let (addr, _) = transport.spawn(Arc::new(server)).unwrap();
+        let url = format!("http://{}{}", addr, DEFAULT_PATH);
+        let client = reqwest::Client::new();
+        let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+
+        for (origin, expected) in [
+            ("http://localhost:3000", StatusCode::OK),
+            ("http://[::1]:3000", StatusCode::OK),
+            ("https://app.example.com", StatusCode::OK),
+            ("https://evil.example", StatusCode::FORBIDDEN),
+            ("http://localhost.evil.example", StatusCode::FORBIDDEN),
+        ] {
+            let response = client
+                .post(&url)
+                .header("origin", origin)
+                .body(initialize)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), expected, "{}", origin);
+        }
+
+        let response = client
+            .get(format!("http://{}/other", addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,302 @@
+//! Messages in the caller's language.
+//!
+//! A [`Catalog`] holds [Fluent](https://projectfluent.org) messages for a
+//! set of locales. A caller asks for messages in the locales it prefers, most
+//! preferred first; [`Catalog::localizer`] negotiates those against the
+//! locales the catalog has into a fallback chain, and each message comes from
+//! the first locale in the chain that has it:
+//!
+//! ```
+//! # use mcp_rust_examples::i18n::{Catalog, FluentArgs};
+//! let mut catalog = Catalog::new("en").unwrap();
+//! catalog.add_messages("en", "hello = Hello, { $name }!\nbye = Goodbye").unwrap();
+//! catalog.add_messages("de", "hello = Hallo, { $name }!").unwrap();
+//!
+//! // de-AT falls back to de, and messages de lacks to the default, en
+//! let messages = catalog.localizer(&["de-AT"]);
+//! assert_eq!(messages.locale(), "de");
+//! let args = FluentArgs::from_iter([("name", "Ada")]);
+//! assert_eq!(messages.message("hello", Some(&args)), "Hallo, Ada!");
+//! assert_eq!(messages.message("bye", None), "Goodbye");
+//! ```
+//!
+//! The examples keep their catalogs in `examples/locales/<locale>/*.ftl` and
+//! embed them in the binary. Callers that only need to choose between their
+//! own translations, such as notification templates, use [`negotiate`]
+//! directly, and HTTP servers read the preferred locales from the
+//! `Accept-Language` header with [`parse_accept_language`].
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::FluentResource;
+use fluent_langneg::{negotiate_languages, NegotiationStrategy};
+use unic_langid::LanguageIdentifier;
+
+pub use fluent_bundle::{FluentArgs, FluentValue};
+
+/// Errors raised while building a [`Catalog`].
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum I18nError {
+    #[error("invalid locale '{0}'")]
+    InvalidLocale(String),
+
+    #[error("invalid messages for {locale}: {errors}")]
+    Syntax { locale: String, errors: String },
+}
+
+/// Fluent messages for a set of locales, with a default locale every
+/// fallback chain ends in.
+pub struct Catalog {
+    default_locale: LanguageIdentifier,
+    // In the order the locales were added
+    bundles: Vec<(LanguageIdentifier, FluentBundle<FluentResource>)>,
+}
+
+impl std::fmt::Debug for Catalog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Catalog")
+            .field("default_locale", &self.default_locale.to_string())
+            .field("locales", &self.locales())
+            .finish()
+    }
+}
+
+impl Catalog {
+    /// An empty catalog whose fallback chains end in `default_locale`.
+    pub fn new(default_locale: &str) -> Result<Self, I18nError> {
+        Ok(Self {
+            default_locale: parse_locale(default_locale)?,
+            bundles: Vec::new(),
+        })
+    }
+
+    /// Adds the messages in Fluent `source` to `locale`. A message that
+    /// `locale` already has is replaced, so later files override earlier
+    /// ones.
+    pub fn add_messages(&mut self, locale: &str, source: &str) -> Result<(), I18nError> {
+        let langid = parse_locale(locale)?;
+        let resource = FluentResource::try_new(source.to_string()).map_err(|(_, errors)| {
+            I18nError::Syntax {
+                locale: locale.to_string(),
+                errors: errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            }
+        })?;
+
+        let position = match self.bundles.iter().position(|(l, _)| *l == langid) {
+            Some(position) => position,
+            None => {
+                let mut bundle = FluentBundle::new_concurrent(vec![langid.clone()]);
+                // The messages end up in JSON and logs, not in a UI that
+                // needs bidi isolation marks around arguments
+                bundle.set_use_isolating(false);
+                self.bundles.push((langid, bundle));
+                self.bundles.len() - 1
+            }
+        };
+        self.bundles[position].1.add_resource_overriding(resource);
+        Ok(())
+    }
+
+    /// The locales that have messages, in the order they were added.
+    pub fn locales(&self) -> Vec<String> {
+        self.bundles.iter().map(|(l, _)| l.to_string()).collect()
+    }
+
+    /// The locale every fallback chain ends in.
+    pub fn default_locale(&self) -> String {
+        self.default_locale.to_string()
+    }
+
+    /// Messages for a caller preferring `requested`, most preferred first.
+    /// Tags that are not valid locales are ignored.
+    pub fn localizer<S: AsRef<str>>(&self, requested: &[S]) -> Localizer<'_> {
+        let available: Vec<&LanguageIdentifier> = self.bundles.iter().map(|(l, _)| l).collect();
+        let chain = negotiate_languages(
+            &parse_locales(requested),
+            &available,
+            Some(&&self.default_locale),
+            NegotiationStrategy::Filtering,
+        )
+        .into_iter()
+        .map(|locale| (*locale).clone())
+        .collect();
+        Localizer {
+            catalog: self,
+            chain,
+        }
+    }
+
+    fn bundle(&self, locale: &LanguageIdentifier) -> Option<&FluentBundle<FluentResource>> {
+        self.bundles
+            .iter()
+            .find(|(l, _)| l == locale)
+            .map(|(_, bundle)| bundle)
+    }
+}
+
+/// A [`Catalog`]'s messages for one caller, looked up along its fallback
+/// chain.
+#[derive(Debug)]
+pub struct Localizer<'a> {
+    catalog: &'a Catalog,
+    chain: Vec<LanguageIdentifier>,
+}
+
+impl Localizer<'_> {
+    /// The caller's most preferred locale that the catalog has, e.g. for a
+    /// `Content-Language` header.
+    pub fn locale(&self) -> String {
+        self.chain
+            .first()
+            .unwrap_or(&self.catalog.default_locale)
+            .to_string()
+    }
+
+    /// The locales messages are looked up in, in order.
+    pub fn chain(&self) -> Vec<String> {
+        self.chain.iter().map(|l| l.to_string()).collect()
+    }
+
+    /// Message `id` formatted with `args`, from the first locale in the
+    /// chain that has it, or `None` if none does.
+    pub fn try_message(&self, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+        self.chain.iter().find_map(|locale| {
+            let bundle = self.catalog.bundle(locale)?;
+            let pattern = bundle.get_message(id)?.value()?;
+            // A missing argument is left as {$name} in the text, which is
+            // more useful to a caller than no message at all
+            let mut errors = Vec::new();
+            Some(
+                bundle
+                    .format_pattern(pattern, args, &mut errors)
+                    .into_owned(),
+            )
+        })
+    }
+
+    /// Like [`try_message`](Self::try_message), but falls back to `id`
+    /// itself so a missing translation shows up rather than failing.
+    pub fn message(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        self.try_message(id, args).unwrap_or_else(|| id.to_string())
+    }
+}
+
+/// The fallback chain for a caller preferring `requested` among the
+/// `available` locales: every available locale that matches one of the
+/// requested ones, most preferred first, then `default`.
+///
+/// ```
+/// # use mcp_rust_examples::i18n::negotiate;
+/// assert_eq!(negotiate(&["fr-CA", "de"], &["en", "de", "fr"], "en"), ["fr", "de", "en"]);
+/// assert_eq!(negotiate(&["pt-BR"], &["en", "de"], "en"), ["en"]);
+/// ```
+pub fn negotiate<R: AsRef<str>, A: AsRef<str>>(
+    requested: &[R],
+    available: &[A],
+    default: &str,
+) -> Vec<String> {
+    let available = parse_locales(available);
+    let default = default.parse::<LanguageIdentifier>().ok();
+    negotiate_languages(
+        &parse_locales(requested),
+        &available,
+        default.as_ref(),
+        NegotiationStrategy::Filtering,
+    )
+    .into_iter()
+    .map(|locale| locale.to_string())
+    .collect()
+}
+
+/// The locales in an `Accept-Language` header, most preferred first. Tags
+/// with `q=0` and the `*` wildcard are left out.
+///
+/// ```
+/// # use mcp_rust_examples::i18n::parse_accept_language;
+/// let locales = parse_accept_language("en;q=0.5, de-AT, fr;q=0.8, *;q=0.1");
+/// assert_eq!(locales, ["de-AT", "fr", "en"]);
+/// ```
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut weighted: Vec<(f32, String)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let tag = parts.next().filter(|tag| !tag.is_empty() && *tag != "*")?;
+            let weight = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            let locale = tag.parse::<LanguageIdentifier>().ok()?;
+            (weight > 0.0).then(|| (weight, locale.to_string()))
+        })
+        .collect();
+    // Stable, so equally weighted tags keep the client's order
+    weighted.sort_by(|a, b| b.0.total_cmp(&a.0));
+    weighted.into_iter().map(|(_, locale)| locale).collect()
+}
+
+/// `locale` in canonical form, e.g. `es-MX` for `es_mx`.
+pub fn canonical_locale(locale: &str) -> Result<String, I18nError> {
+    parse_locale(locale).map(|locale| locale.to_string())
+}
+
+fn parse_locale(locale: &str) -> Result<LanguageIdentifier, I18nError> {
+    locale
+        .parse()
+        .map_err(|_| I18nError::InvalidLocale(locale.to_string()))
+}
+
+fn parse_locales<S: AsRef<str>>(locales: &[S]) -> Vec<LanguageIdentifier> {
+    locales
+        .iter()
+        .filter_map(|locale| locale.as_ref().parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_fall_back_along_the_chain() {
+        let mut catalog = Catalog::new("en").unwrap();
+        catalog
+            .add_messages("en", "greeting = Hello\nfarewell = Goodbye\n")
+            .unwrap();
+        catalog
+            .add_messages("es", "greeting = Hola\nfarewell = Adiós\n")
+            .unwrap();
+        catalog
+            .add_messages("es-MX", "greeting = Qué onda\n")
+            .unwrap();
+        // Later messages replace earlier ones
+        catalog
+            .add_messages("es", "farewell = Hasta luego\n")
+            .unwrap();
+
+        let messages = catalog.localizer(&["es-MX", "fr"]);
+        assert_eq!(messages.chain(), ["es-MX", "es", "en"]);
+        assert_eq!(messages.message("greeting", None), "Qué onda");
+        assert_eq!(messages.message("farewell", None), "Hasta luego");
+        assert_eq!(messages.try_message("missing", None), None);
+        assert_eq!(messages.message("missing", None), "missing");
+
+        let messages = catalog.localizer(&["not a locale!", "ja"]);
+        assert_eq!(
+            (messages.locale(), messages.message("greeting", None)),
+            ("en".to_string(), "Hello".to_string())
+        );
+
+        assert!(matches!(
+            catalog.add_messages("en", "= broken"),
+            Err(I18nError::Syntax { .. })
+        ));
+        assert_eq!(canonical_locale("es_mx").unwrap(), "es-MX");
+        assert_eq!(
+            Catalog::new("not a locale!").unwrap_err(),
+            I18nError::InvalidLocale("not a locale!".to_string())
+        );
+    }
+}
@@ -0,0 +1,36 @@
+//! Caller identities shared between examples.
+//!
+//! The auth service (example 13) issues and validates tokens. Other servers
+//! only need to know who is calling and with which role, so they accept an
+//! [`Identity`] built from a validated token with `AuthToken::identity()`.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// An authenticated caller.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Identity {
+    pub user_id: Uuid,
+    pub username: String,
+    /// One of the example 13 roles: `Admin`, `Moderator`, `User` or `Guest`.
+    pub role: String,
+}
+
+impl Identity {
+    pub fn new(user_id: Uuid, username: impl Into<String>, role: impl Into<String>) -> Self {
+        Self {
+            user_id,
+            username: username.into(),
+            role: role.into(),
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == "Admin"
+    }
+
+    /// Guests have read-only access everywhere.
+    pub fn is_guest(&self) -> bool {
+        self.role == "Guest"
+    }
+}
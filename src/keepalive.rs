@@ -0,0 +1,175 @@
+//! Detecting dead peers with MCP `ping` requests.
+//!
+//! A peer that hangs or loses its network link without closing the connection
+//! leaves nothing to read, so a server would wait on it forever and keep
+//! whatever it allocated for it. [`Keepalive`] pings a peer that has been quiet
+//! for a while and declares it dead if the ping goes unanswered. Any message
+//! from the peer counts as a sign of life, not just the ping response.
+//!
+//! The tracker does no I/O itself: callers [`poll`](Keepalive::poll) it when
+//! [`next_deadline`](Keepalive::next_deadline) passes and send the pings it
+//! asks for, so the same logic serves stdio and WebSocket peers.
+
+use crate::protocol::{JsonRpcRequest, RequestId};
+use serde_json::Value;
+use std::fmt;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How often a quiet peer is pinged and how long it has to answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl KeepaliveConfig {
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Self { interval, timeout }
+    }
+}
+
+/// Why a peer's connection ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The peer closed the connection.
+    Closed,
+    /// The peer did not answer a ping in time.
+    KeepaliveTimeout { timeout: Duration },
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => write!(f, "peer closed the connection"),
+            Self::KeepaliveTimeout { timeout } => {
+                write!(f, "no answer to ping within {}ms", timeout.as_millis())
+            }
+        }
+    }
+}
+
+/// What the caller should do after [`Keepalive::poll`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeepaliveAction {
+    Wait,
+    /// Send this ping to the peer.
+    Ping(JsonRpcRequest),
+    /// Give up on the peer.
+    Disconnect(DisconnectReason),
+}
+
+/// Keepalive state for one peer.
+#[derive(Debug, Clone)]
+pub struct Keepalive {
+    config: KeepaliveConfig,
+    last_seen: Instant,
+    ping_sent_at: Option<Instant>,
+    pings_sent: u64,
+}
+
+impl Keepalive {
+    pub fn new(config: KeepaliveConfig, now: Instant) -> Self {
+        Self {
+            config,
+            last_seen: now,
+            ping_sent_at: None,
+            pings_sent: 0,
+        }
+    }
+
+    pub fn config(&self) -> &KeepaliveConfig {
+        &self.config
+    }
+
+    /// Records a message from the peer, which also answers any pending ping.
+    pub fn record_activity(&mut self, now: Instant) {
+        self.last_seen = now;
+        self.ping_sent_at = None;
+    }
+
+    /// When [`poll`](Self::poll) next has something to do.
+    pub fn next_deadline(&self) -> Instant {
+        match self.ping_sent_at {
+            Some(sent_at) => sent_at + self.config.timeout,
+            None => self.last_seen + self.config.interval,
+        }
+    }
+
+    pub fn poll(&mut self, now: Instant) -> KeepaliveAction {
+        match self.ping_sent_at {
+            Some(sent_at) if now.duration_since(sent_at) >= self.config.timeout => {
+                KeepaliveAction::Disconnect(DisconnectReason::KeepaliveTimeout {
+                    timeout: self.config.timeout,
+                })
+            }
+            Some(_) => KeepaliveAction::Wait,
+            None if now.duration_since(self.last_seen) >= self.config.interval => {
+                self.pings_sent += 1;
+                self.ping_sent_at = Some(now);
+                KeepaliveAction::Ping(JsonRpcRequest::new(
+                    RequestId::String(format!("keepalive-{}", self.pings_sent)),
+                    "ping",
+                    Value::Null,
+                ))
+            }
+            None => KeepaliveAction::Wait,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_pings_quiet_peer_then_disconnects() {
+        let start = Instant::now();
+        let mut keepalive = Keepalive::new(KeepaliveConfig::new(10 * SECOND, 5 * SECOND), start);
+
+        assert_eq!(keepalive.poll(start + 9 * SECOND), KeepaliveAction::Wait);
+        assert_eq!(keepalive.next_deadline(), start + 10 * SECOND);
+
+        let KeepaliveAction::Ping(ping) = keepalive.poll(start + 10 * SECOND) else {
+            panic!("expected a ping");
+        };
+        assert_eq!(ping.method, "ping");
+        assert_eq!(keepalive.next_deadline(), start + 15 * SECOND);
+        assert_eq!(keepalive.poll(start + 14 * SECOND), KeepaliveAction::Wait);
+
+        let action = keepalive.poll(start + 15 * SECOND);
+        assert_eq!(
+            action,
+            KeepaliveAction::Disconnect(DisconnectReason::KeepaliveTimeout {
+                timeout: 5 * SECOND
+            })
+        );
+    }
+
+    #[test]
+    fn test_activity_answers_ping() {
+        let start = Instant::now();
+        let mut keepalive = Keepalive::new(KeepaliveConfig::new(10 * SECOND, 5 * SECOND), start);
+
+        assert!(matches!(
+            keepalive.poll(start + 10 * SECOND),
+            KeepaliveAction::Ping(_)
+        ));
+        keepalive.record_activity(start + 12 * SECOND);
+
+        // The peer is alive again, so the next ping waits a full interval
+        assert_eq!(keepalive.poll(start + 20 * SECOND), KeepaliveAction::Wait);
+        assert_eq!(keepalive.next_deadline(), start + 22 * SECOND);
+    }
+}
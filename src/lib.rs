@@ -0,0 +1,48 @@
+//! # MCP Rust Examples - Shared Support Code
+//!
+//! Small building blocks shared by several examples. Each example is still a
+//! standalone binary; this library only holds code that would otherwise be
+//! copied between them.
+
+// Lets code generated by #[mcp_tool] name this crate from inside it too
+extern crate self as mcp_rust_examples;
+
+pub mod cancellation;
+pub mod capabilities;
+pub mod chaos;
+pub mod checkpoint;
+pub mod codegen;
+pub mod compression;
+pub mod diagnostics;
+pub mod elicitation;
+pub mod error;
+pub mod http_transport;
+pub mod i18n;
+pub mod identity;
+pub mod keepalive;
+pub mod logging;
+pub mod manifest;
+pub mod metrics;
+pub mod middleware;
+pub mod mock_transport;
+pub mod output_format;
+pub mod path_policy;
+pub mod plugin;
+pub mod progress;
+pub mod protocol;
+pub mod rate_limit;
+pub mod registry;
+pub mod resource_diff;
+pub mod response;
+pub mod sampling;
+pub mod schema;
+pub mod server;
+pub mod service_token;
+pub mod session;
+pub mod state;
+pub mod stats;
+pub mod telemetry;
+pub mod tools;
+pub mod transport;
+pub mod websocket;
+pub mod yaml;
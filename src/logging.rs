@@ -0,0 +1,778 @@
+//! Structured logging with redaction of sensitive fields.
+//!
+//! All examples initialize logging through [`init`], which reads:
+//!
+//! - `RUST_LOG`: the usual filter directives (falls back to the example's default)
+//! - `MCP_LOG_FORMAT`: `text` (default) or `json`
+//! - `MCP_LOG_REDACT`: extra comma-separated keys to redact
+//! - `OTEL_EXPORTER_OTLP_ENDPOINT`: also export spans there, see [`crate::telemetry`]
+//!
+//! In JSON mode every event is one object per line. Span fields are flattened
+//! into the event, so a tool call logged with [`ToolCallLog`] always carries
+//! `request_id`, `tool`, `duration_ms` and `status` under the same names.
+//!
+//! Any field whose name contains a sensitive key as whole words (`password`,
+//! `access_token`, `Authorization`, ...) is replaced with `[REDACTED]`. Field
+//! values that are JSON objects, such as tool arguments, are redacted key by
+//! key, in both output formats.
+//!
+//! Code in the process can also read its own logs: [`subscribe`] receives
+//! every event at info level or above as the JSON object the JSON format
+//! would write, whatever the two settings above say.
+
+use crate::telemetry::{self, TelemetryConfig, TelemetryGuard};
+use chrono::Utc;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Instant;
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Instrument, Span, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormattedFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+use uuid::Uuid;
+
+/// Keys redacted by default. Names are split into words at `_`, `-`, `.`
+/// and camelCase boundaries, and a key matches names containing its words in
+/// order, so `password` catches `password_hash` and `api_key` catches
+/// `apiKey`, while `token` leaves `max_tokens` alone. Matching ignores case.
+pub const DEFAULT_REDACT_KEYS: &[&str] = &[
+    "password",
+    "token",
+    "authorization",
+    "secret",
+    "api_key",
+    "private_key",
+];
+
+/// What a redacted value is replaced with.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// How many events a [`subscribe`]r may fall behind by before it misses some.
+pub const LOG_TAP_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    pub filter: String,
+    pub redact_keys: Vec<String>,
+}
+
+impl LogConfig {
+    /// Builds the configuration from the environment, using `default_filter`
+    /// when `RUST_LOG` is not set.
+    pub fn from_env(default_filter: &str) -> Self {
+        let format = match std::env::var("MCP_LOG_FORMAT").as_deref() {
+            Ok("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        };
+
+        Self {
+            format,
+            filter: std::env::var("RUST_LOG").unwrap_or_else(|_| default_filter.to_string()),
+            redact_keys: redact_keys_from_env(),
+        }
+    }
+}
+
+/// [`DEFAULT_REDACT_KEYS`] plus any keys listed in `MCP_LOG_REDACT`.
+pub fn redact_keys_from_env() -> Vec<String> {
+    let mut redact_keys: Vec<String> = DEFAULT_REDACT_KEYS.iter().map(|k| k.to_string()).collect();
+    if let Ok(extra) = std::env::var("MCP_LOG_REDACT") {
+        redact_keys.extend(
+            extra
+                .split(',')
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .map(str::to_string),
+        );
+    }
+    redact_keys
+}
+
+/// Initializes the global subscriber from the environment, including trace
+/// export when it is configured. Keep the returned guard until exit.
+pub fn init(default_filter: &str) -> TelemetryGuard {
+    init_with(
+        LogConfig::from_env(default_filter),
+        TelemetryConfig::from_env(),
+    )
+}
+
+/// Initializes the global subscriber with an explicit configuration.
+pub fn init_with(config: LogConfig, telemetry: Option<TelemetryConfig>) -> TelemetryGuard {
+    let redactor = Redactor::new(&config.redact_keys);
+    let (otel_layer, guard, warning) = telemetry::layer(telemetry);
+    // `RUST_LOG` only quiets the console; tool call spans are info level and
+    // exported even where an example defaults to errors only
+    let registry = tracing_subscriber::registry()
+        .with(otel_layer.map(|l| l.with_filter(LevelFilter::INFO)))
+        .with(LogTap::new(redactor.clone()).with_filter(LevelFilter::INFO));
+    let filter = EnvFilter::new(&config.filter);
+    // stdout carries the JSON-RPC stream when serving over stdio, so logs
+    // always go to stderr
+    let fmt_layer = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+
+    match config.format {
+        LogFormat::Json => registry
+            .with(
+                fmt_layer
+                    .event_format(JsonFormat::new(redactor.clone()))
+                    .fmt_fields(JsonFields::new(redactor))
+                    .with_filter(filter),
+            )
+            .init(),
+        LogFormat::Text => registry
+            .with(
+                fmt_layer
+                    .fmt_fields(RedactingFields::new(redactor))
+                    .with_filter(filter),
+            )
+            .init(),
+    }
+
+    if let Some(warning) = warning {
+        tracing::warn!("{}", warning);
+    }
+    guard
+}
+
+/// Decides which fields are sensitive and masks them.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    keys: Vec<Vec<String>>,
+}
+
+impl Redactor {
+    pub fn new(keys: &[String]) -> Self {
+        Self {
+            keys: keys
+                .iter()
+                .map(|key| words(key))
+                .filter(|words| !words.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Whether the words of `key` include those of a sensitive key.
+    pub fn is_sensitive(&self, key: &str) -> bool {
+        let key = words(key);
+        self.keys.iter().any(|sensitive| {
+            key.windows(sensitive.len())
+                .any(|window| window == sensitive.as_slice())
+        })
+    }
+
+    /// Redacts sensitive keys anywhere inside a JSON value.
+    pub fn redact(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.is_sensitive(key) {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact(item)),
+            _ => {}
+        }
+    }
+
+    // Converts a recorded field to JSON. Values that parse as JSON objects or
+    // arrays (tool arguments, header maps) are kept structured and redacted
+    // key by key; everything else stays a string.
+    fn field_value(&self, key: &str, raw: String) -> Value {
+        if self.is_sensitive(key) {
+            return Value::String(REDACTED.to_string());
+        }
+        if key != "message" && raw.trim_start().starts_with(['{', '[']) {
+            if let Ok(mut value) = serde_json::from_str::<Value>(&raw) {
+                self.redact(&mut value);
+                return value;
+            }
+        }
+        Value::String(raw)
+    }
+}
+
+// The lowercase words of a field name: `x-api-key`, `X_API_KEY` and `xApiKey`
+// are all ["x", "api", "key"]
+fn words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut after_lowercase = false;
+    for c in name.chars() {
+        let boundary = !c.is_alphanumeric() || (c.is_uppercase() && after_lowercase);
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        if c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+        }
+        after_lowercase = c.is_lowercase() || c.is_ascii_digit();
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+// Collects an event's or span's fields into a JSON map, redacting as it goes.
+struct FieldCollector<'a> {
+    redactor: &'a Redactor,
+    fields: Map<String, Value>,
+}
+
+impl<'a> FieldCollector<'a> {
+    fn new(redactor: &'a Redactor) -> Self {
+        Self {
+            redactor,
+            fields: Map::new(),
+        }
+    }
+
+    fn insert(&mut self, field: &Field, value: Value) {
+        let value = if self.redactor.is_sensitive(field.name()) {
+            Value::String(REDACTED.to_string())
+        } else {
+            value
+        };
+        self.fields.insert(field.name().to_string(), value);
+    }
+}
+
+impl Visit for FieldCollector<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let value = self.redactor.field_value(field.name(), value.to_string());
+        self.fields.insert(field.name().to_string(), value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let value = self
+            .redactor
+            .field_value(field.name(), format!("{:?}", value));
+        self.fields.insert(field.name().to_string(), value);
+    }
+}
+
+/// Formats fields as `message key=value ...` with sensitive values masked.
+pub struct RedactingFields {
+    redactor: Redactor,
+}
+
+impl RedactingFields {
+    pub fn new(redactor: Redactor) -> Self {
+        Self { redactor }
+    }
+}
+
+impl<'writer> FormatFields<'writer> for RedactingFields {
+    fn format_fields<R: tracing_subscriber::field::RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut collector = FieldCollector::new(&self.redactor);
+        fields.record(&mut collector);
+
+        let mut separator = "";
+        if let Some(Value::String(message)) = collector.fields.remove("message") {
+            write!(writer, "{}", message)?;
+            separator = " ";
+        }
+        for (key, value) in collector.fields {
+            match value {
+                Value::String(text) => write!(writer, "{}{}={}", separator, key, text)?,
+                other => write!(writer, "{}{}={}", separator, key, other)?,
+            }
+            separator = " ";
+        }
+        Ok(())
+    }
+}
+
+/// Formats span fields as a JSON object so [`JsonFormat`] can merge them.
+pub struct JsonFields {
+    redactor: Redactor,
+}
+
+impl JsonFields {
+    pub fn new(redactor: Redactor) -> Self {
+        Self { redactor }
+    }
+}
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: tracing_subscriber::field::RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut collector = FieldCollector::new(&self.redactor);
+        fields.record(&mut collector);
+        write!(writer, "{}", Value::Object(collector.fields))
+    }
+
+    // Fields recorded later on a span (e.g. `status`) are merged into the
+    // existing object instead of appended as text.
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &tracing::span::Record<'_>,
+    ) -> fmt::Result {
+        let mut merged = match serde_json::from_str::<Value>(&current.fields) {
+            Ok(Value::Object(map)) => map,
+            _ => Map::new(),
+        };
+        let mut collector = FieldCollector::new(&self.redactor);
+        fields.record(&mut collector);
+        merged.extend(collector.fields);
+
+        current.fields = Value::Object(merged).to_string();
+        Ok(())
+    }
+}
+
+/// Writes each event as a single-line JSON object.
+pub struct JsonFormat {
+    redactor: Redactor,
+}
+
+impl JsonFormat {
+    pub fn new(redactor: Redactor) -> Self {
+        Self { redactor }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert("timestamp".to_string(), Utc::now().to_rfc3339().into());
+        object.insert("level".to_string(), metadata.level().as_str().into());
+        object.insert("target".to_string(), metadata.target().into());
+
+        // Outer spans first so inner spans win on conflicting names
+        if let Some(scope) = ctx.event_scope() {
+            let mut span_names = Vec::new();
+            for span in scope.from_root() {
+                span_names.push(span.name());
+                if let Some(fields) = span.extensions().get::<FormattedFields<N>>() {
+                    if let Ok(Value::Object(map)) = serde_json::from_str(&fields.fields) {
+                        object.extend(map);
+                    }
+                }
+            }
+            object.insert("span".to_string(), span_names.join(":").into());
+        }
+
+        let mut collector = FieldCollector::new(&self.redactor);
+        event.record(&mut collector);
+        object.extend(collector.fields);
+
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+/// Receives the events logged from now on as JSON objects, with the same
+/// fields and redaction as a line of the JSON format. Only events at info
+/// level or above are sent, and only once [`init`] has installed the tap.
+pub fn subscribe() -> broadcast::Receiver<Value> {
+    log_tap().subscribe()
+}
+
+fn log_tap() -> &'static broadcast::Sender<Value> {
+    static TAP: OnceLock<broadcast::Sender<Value>> = OnceLock::new();
+    TAP.get_or_init(|| broadcast::channel(LOG_TAP_CAPACITY).0)
+}
+
+// The fields of a span as the tap last saw them
+struct TapFields(Map<String, Value>);
+
+/// A layer that sends every event to the [`subscribe`]rs. [`init`] installs
+/// it; tests and custom subscribers can add it themselves. Events are only
+/// formatted while someone is subscribed.
+pub struct LogTap {
+    redactor: Redactor,
+    sender: broadcast::Sender<Value>,
+}
+
+impl LogTap {
+    pub fn new(redactor: Redactor) -> Self {
+        Self {
+            redactor,
+            sender: log_tap().clone(),
+        }
+    }
+}
+
+impl<S> Layer<S> for LogTap
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut collector = FieldCollector::new(&self.redactor);
+        attrs.record(&mut collector);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(TapFields(collector.fields));
+        }
+    }
+
+    fn on_record(
+        &self,
+        id: &tracing::span::Id,
+        values: &tracing::span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut collector = FieldCollector::new(&self.redactor);
+        values.record(&mut collector);
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<TapFields>() {
+                fields.0.extend(collector.fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert("timestamp".to_string(), Utc::now().to_rfc3339().into());
+        object.insert("level".to_string(), metadata.level().as_str().into());
+        object.insert("target".to_string(), metadata.target().into());
+
+        // Outer spans first so inner spans win on conflicting names, as in
+        // JsonFormat
+        if let Some(scope) = ctx.event_scope(event) {
+            let mut span_names = Vec::new();
+            for span in scope.from_root() {
+                span_names.push(span.name());
+                if let Some(fields) = span.extensions().get::<TapFields>() {
+                    object.extend(fields.0.clone());
+                }
+            }
+            object.insert("span".to_string(), span_names.join(":").into());
+        }
+
+        let mut collector = FieldCollector::new(&self.redactor);
+        event.record(&mut collector);
+        object.extend(collector.fields);
+
+        // Nobody may be listening any more, which is fine
+        let _ = self.sender.send(Value::Object(object));
+    }
+}
+
+/// Logs the start and end of one tool call with consistent fields.
+///
+/// The call runs inside a `tool_call` span carrying `request_id` and `tool`;
+/// the closing event adds `duration_ms` and `status`, which are then recorded
+/// on the span too, so an exported trace carries the outcome. Arguments are
+/// logged through the redacting formatters, so secrets in them never reach
+/// the log.
+pub struct ToolCallLog {
+    span: Span,
+    started: Instant,
+}
+
+impl ToolCallLog {
+    pub fn start(tool: &str, arguments: &Value) -> Self {
+        let span = tracing::info_span!(
+            "tool_call",
+            request_id = %Uuid::new_v4(),
+            tool = tool,
+            duration_ms = tracing::field::Empty,
+            status = tracing::field::Empty,
+            "otel.status_code" = tracing::field::Empty,
+            "otel.status_message" = tracing::field::Empty
+        );
+        span.in_scope(|| tracing::info!(arguments = %arguments, "tool call started"));
+
+        Self {
+            span,
+            started: Instant::now(),
+        }
+    }
+
+    /// The span of this call, for instrumenting async work done on its behalf.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Logs one call of `tool`, running `call` inside the call's span.
+    pub fn run<T, E: fmt::Display>(
+        tool: &str,
+        arguments: Value,
+        call: impl FnOnce(Value) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let log = Self::start(tool, &arguments);
+        let result = log.span.in_scope(|| call(arguments));
+        log.finish(&result);
+        result
+    }
+
+    /// Logs one call of `tool`, instrumenting the future `call` returns with
+    /// the call's span.
+    pub async fn run_async<T, E, F>(
+        tool: &str,
+        arguments: Value,
+        call: impl FnOnce(Value) -> F,
+    ) -> Result<T, E>
+    where
+        E: fmt::Display,
+        F: Future<Output = Result<T, E>>,
+    {
+        let log = Self::start(tool, &arguments);
+        let result = call(arguments).instrument(log.span.clone()).await;
+        log.finish(&result);
+        result
+    }
+
+    pub fn finish<T, E: fmt::Display>(self, result: &Result<T, E>) {
+        let duration_ms = self.started.elapsed().as_millis() as u64;
+        self.span.in_scope(|| match result {
+            Ok(_) => tracing::info!(duration_ms, status = "ok", "tool call finished"),
+            Err(e) => tracing::warn!(
+                duration_ms,
+                status = "error",
+                error = %e,
+                "tool call failed"
+            ),
+        });
+
+        // Recorded after the closing event, which already has these fields,
+        // so log lines don't repeat them
+        self.span.record("duration_ms", duration_ms);
+        match result {
+            Ok(_) => {
+                self.span.record("status", "ok");
+                self.span.record("otel.status_code", "OK");
+            }
+            Err(e) => {
+                self.span.record("status", "error");
+                self.span.record("otel.status_code", "ERROR");
+                self.span
+                    .record("otel.status_message", tracing::field::display(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn redactor() -> Redactor {
+        let keys: Vec<String> = DEFAULT_REDACT_KEYS.iter().map(|k| k.to_string()).collect();
+        Redactor::new(&keys)
+    }
+
+    #[test]
+    fn test_redacts_nested_keys() {
+        let mut value = serde_json::json!({
+            "username": "alice",
+            "password": "hunter2",
+            "headers": { "Authorization": "Bearer abc", "Accept": "*/*" },
+            "sessions": [{ "refresh_token": "xyz" }]
+        });
+        redactor().redact(&mut value);
+
+        assert_eq!(value["username"], "alice");
+        assert_eq!(value["password"], REDACTED);
+        assert_eq!(value["headers"]["Authorization"], REDACTED);
+        assert_eq!(value["headers"]["Accept"], "*/*");
+        assert_eq!(value["sessions"][0]["refresh_token"], REDACTED);
+    }
+
+    #[test]
+    fn test_redacts_by_whole_words() {
+        let redactor = redactor();
+        for key in [
+            "access_token",
+            "refreshToken",
+            "X-Api-Key",
+            "apiKey",
+            "DB_PASSWORD",
+            "password_hash",
+            "client.secret",
+            "secret_key",
+            "token_count",
+        ] {
+            assert!(redactor.is_sensitive(key), "{} should be redacted", key);
+        }
+        for key in [
+            "max_tokens",
+            "key_id",
+            "api_version",
+            "passwordless",
+            "secretary",
+        ] {
+            assert!(!redactor.is_sensitive(key), "{} should be kept", key);
+        }
+
+        let mut value = serde_json::json!({ "max_tokens": 256, "api_key": "sk-1" });
+        redactor.redact(&mut value);
+        assert_eq!(value["max_tokens"], 256);
+        assert_eq!(value["api_key"], REDACTED);
+    }
+
+    #[test]
+    fn test_json_tool_call_log() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .event_format(JsonFormat::new(redactor()))
+            .fmt_fields(JsonFields::new(redactor()))
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let log = ToolCallLog::start(
+                "login",
+                &serde_json::json!({ "username": "alice", "password": "hunter2" }),
+            );
+            log.finish::<(), String>(&Ok(()));
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["tool"], "login");
+        assert_eq!(lines[0]["arguments"]["username"], "alice");
+        assert_eq!(lines[0]["arguments"]["password"], REDACTED);
+        assert_eq!(lines[1]["status"], "ok");
+        assert_eq!(lines[0]["request_id"], lines[1]["request_id"]);
+        assert!(lines[1]["duration_ms"].is_u64());
+        assert!(!output.contains("hunter2"));
+    }
+
+    // Keeps the fields recorded on spans after they were created
+    #[derive(Clone, Default)]
+    struct RecordedFields(Arc<Mutex<Map<String, Value>>>);
+
+    impl<S: Subscriber> tracing_subscriber::Layer<S> for RecordedFields {
+        fn on_record(
+            &self,
+            _span: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let redactor = redactor();
+            let mut collector = FieldCollector::new(&redactor);
+            values.record(&mut collector);
+            self.0.lock().unwrap().extend(collector.fields);
+        }
+    }
+
+    #[test]
+    fn test_log_tap_sends_events_as_json() {
+        let sender = broadcast::channel(8).0;
+        let mut events = sender.subscribe();
+        let tap = LogTap {
+            redactor: redactor(),
+            sender,
+        };
+        let subscriber = tracing_subscriber::registry().with(tap);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let log = ToolCallLog::start("login", &serde_json::json!({ "password": "hunter2" }));
+            log.finish::<(), String>(&Err("bad password".to_string()));
+        });
+
+        let started = events.try_recv().unwrap();
+        assert_eq!(started["level"], "INFO");
+        assert_eq!(started["span"], "tool_call");
+        assert_eq!(started["tool"], "login");
+        assert_eq!(started["arguments"]["password"], REDACTED);
+        let failed = events.try_recv().unwrap();
+        assert_eq!(failed["level"], "WARN");
+        assert_eq!(failed["message"], "tool call failed");
+        assert_eq!(failed["status"], "error");
+        assert!(failed["duration_ms"].is_u64());
+        assert_eq!(started["request_id"], failed["request_id"]);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_tool_call_span_records_outcome() {
+        let recorded = RecordedFields::default();
+        let subscriber = tracing_subscriber::registry().with(recorded.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let log = ToolCallLog::start("delete_file", &serde_json::json!({}));
+            log.finish::<(), String>(&Err("permission denied".to_string()));
+        });
+
+        let fields = recorded.0.lock().unwrap();
+        assert!(fields["duration_ms"].is_u64());
+        assert_eq!(fields["status"], "error");
+        assert_eq!(fields["otel.status_code"], "ERROR");
+        assert_eq!(fields["otel.status_message"], "permission denied");
+    }
+}
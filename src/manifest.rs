@@ -0,0 +1,804 @@
+//! Servers described in a manifest file instead of code.
+//!
+//! A manifest names a server and lists its tools. Each tool maps its
+//! arguments onto one built-in action: an HTTP call, a SQL query, a file
+//! read or a rendered template. Arguments are referred to as `{{name}}`:
+//!
+//! ```yaml
+//! name: weather-desk
+//! tools:
+//!   - name: forecast
+//!     description: Fetches the forecast for a city
+//!     action:
+//!       type: http
+//!       url: https://wttr.in/{{city}}?format=j1
+//!   - name: find_user
+//!     description: Looks a user up by email
+//!     action:
+//!       type: sql
+//!       database: sqlite://data/example.db
+//!       query: SELECT id, name FROM users WHERE email = ?
+//!       params: ["{{email}}"]
+//! ```
+//!
+//! [`ServerManifest::build_server`] turns the manifest into an [`McpServer`]
+//! that any transport can serve. Tools without an `input_schema` get one
+//! that requires every argument their action refers to.
+//!
+//! Arguments never become code: SQL queries take them only as bound
+//! parameters, URLs percent-encode them, and file reads stay inside the
+//! configured root.
+
+use crate::error::McpError;
+use crate::protocol::{Tool, ToolAnnotations};
+use crate::server::{McpServer, ToolHandler, ToolRouter};
+use crate::yaml::{self, YamlError};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::{Column, Row, TypeInfo, ValueRef};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_HTTP_TIMEOUT_SECONDS: u64 = 30;
+const DEFAULT_MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Errors raised while loading or assembling a manifest.
+#[derive(Debug, thiserror::Error)]
+pub enum ManifestError {
+    #[error("failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("invalid manifest: {0}")]
+    Yaml(#[from] YamlError),
+
+    #[error("invalid manifest: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("tool '{tool}': {message}")]
+    Tool { tool: String, message: String },
+
+    #[error("duplicate tool '{0}'")]
+    DuplicateTool(String),
+}
+
+/// A server and its tools.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServerManifest {
+    pub name: String,
+    /// Reported in `initialize`; the crate's version if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub tools: Vec<ToolDefinition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<Value>,
+    /// Defaults to read-only for file reads, templates and HTTP GETs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ToolAnnotations>,
+    pub action: Action,
+}
+
+/// What a tool does when called. String fields marked as templates may
+/// refer to arguments as `{{name}}`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    /// Calls a URL and returns the status and body, parsed as JSON when
+    /// possible. Non-2xx statuses are tool errors.
+    Http {
+        #[serde(default = "default_http_method")]
+        method: String,
+        /// Template; arguments are percent-encoded.
+        url: String,
+        /// Values are templates.
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        /// Template.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        body: Option<String>,
+        #[serde(default = "default_http_timeout")]
+        timeout_seconds: u64,
+    },
+    /// Runs a query against a SQLite database. `SELECT`s return their rows,
+    /// other statements the number of rows they changed.
+    Sql {
+        /// e.g. `sqlite://data/example.db`
+        database: String,
+        /// Plain SQL with `?` placeholders, never a template.
+        query: String,
+        /// One template per `?`. A template that is a single `{{name}}`
+        /// binds the argument with its JSON type, others bind text.
+        #[serde(default)]
+        params: Vec<String>,
+    },
+    /// Reads a UTF-8 file below `root`.
+    FileRead {
+        root: PathBuf,
+        /// Template for the path relative to `root`.
+        path: String,
+        #[serde(default = "default_max_file_bytes")]
+        max_bytes: u64,
+    },
+    /// Returns the rendered template as the result.
+    Template { template: String },
+}
+
+fn default_http_method() -> String {
+    "GET".to_string()
+}
+
+fn default_http_timeout() -> u64 {
+    DEFAULT_HTTP_TIMEOUT_SECONDS
+}
+
+fn default_max_file_bytes() -> u64 {
+    DEFAULT_MAX_FILE_BYTES
+}
+
+impl ServerManifest {
+    /// Reads a manifest. Files ending in `.json` are read as JSON, anything
+    /// else as YAML.
+    pub fn load(path: &Path) -> Result<Self, ManifestError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ManifestError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            Ok(serde_json::from_str(&text)?)
+        } else {
+            Self::from_yaml(&text)
+        }
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self, ManifestError> {
+        Ok(yaml::from_str(text)?)
+    }
+
+    /// Builds a router with one handler per tool. Every template is checked
+    /// here, so a broken manifest fails at startup rather than on a call.
+    pub fn tool_router(&self) -> Result<ToolRouter, ManifestError> {
+        let mut router = ToolRouter::new(self.name.clone());
+        let mut names = BTreeSet::new();
+        let mut pools: HashMap<String, SqlitePool> = HashMap::new();
+        // One client for every HTTP tool, so connections are reused
+        let http = reqwest::Client::new();
+
+        for definition in &self.tools {
+            if !names.insert(definition.name.as_str()) {
+                return Err(ManifestError::DuplicateTool(definition.name.clone()));
+            }
+            let invalid = |message: String| ManifestError::Tool {
+                tool: definition.name.clone(),
+                message,
+            };
+            let runner = match &definition.action {
+                Action::Http {
+                    method,
+                    url,
+                    headers,
+                    body,
+                    timeout_seconds,
+                } => Runner::Http {
+                    client: http.clone(),
+                    method: reqwest::Method::from_bytes(method.to_uppercase().as_bytes())
+                        .map_err(|_| invalid(format!("invalid HTTP method '{}'", method)))?,
+                    url: Template::parse(url).map_err(invalid)?,
+                    headers: headers
+                        .iter()
+                        .map(|(name, value)| Ok((name.clone(), Template::parse(value)?)))
+                        .collect::<Result<_, String>>()
+                        .map_err(invalid)?,
+                    body: body
+                        .as_deref()
+                        .map(Template::parse)
+                        .transpose()
+                        .map_err(invalid)?,
+                    timeout: Duration::from_secs(*timeout_seconds),
+                },
+                Action::Sql {
+                    database,
+                    query,
+                    params,
+                } => {
+                    if !Template::parse(query)
+                        .map_err(invalid)?
+                        .arguments()
+                        .is_empty()
+                    {
+                        return Err(invalid(
+                            "queries take arguments through params, not {{...}}".to_string(),
+                        ));
+                    }
+                    let pool = match pools.get(database) {
+                        Some(pool) => pool.clone(),
+                        None => {
+                            // Connects on first use, so the server starts
+                            // even while the database is unavailable
+                            let pool = SqlitePoolOptions::new()
+                                .connect_lazy(database)
+                                .map_err(|e| invalid(format!("invalid database URL: {}", e)))?;
+                            pools.insert(database.clone(), pool.clone());
+                            pool
+                        }
+                    };
+                    Runner::Sql {
+                        pool,
+                        query: query.clone(),
+                        params: params
+                            .iter()
+                            .map(|param| Template::parse(param))
+                            .collect::<Result<_, _>>()
+                            .map_err(invalid)?,
+                    }
+                }
+                Action::FileRead {
+                    root,
+                    path,
+                    max_bytes,
+                } => Runner::FileRead {
+                    root: root.clone(),
+                    path: Template::parse(path).map_err(invalid)?,
+                    max_bytes: *max_bytes,
+                },
+                Action::Template { template } => {
+                    Runner::Template(Template::parse(template).map_err(invalid)?)
+                }
+            };
+            router = router.with_handler(ManifestTool {
+                tool: definition.describe(&runner),
+                runner,
+            });
+        }
+        Ok(router)
+    }
+
+    /// The whole server, ready for a transport.
+    pub fn build_server(&self) -> Result<McpServer, ManifestError> {
+        let server = McpServer::new(Arc::new(self.tool_router()?));
+        Ok(match &self.version {
+            Some(version) => server.with_version(version.clone()),
+            None => server,
+        })
+    }
+}
+
+impl ToolDefinition {
+    fn describe(&self, runner: &Runner) -> Tool {
+        let input_schema = self.input_schema.clone().unwrap_or_else(|| {
+            let arguments = runner.arguments();
+            let properties: Map<String, Value> = arguments
+                .iter()
+                .map(|name| (name.clone(), serde_json::json!({ "type": "string" })))
+                .collect();
+            serde_json::json!({
+                "type": "object",
+                "properties": properties,
+                "required": arguments
+            })
+        });
+        let annotations = self.annotations.clone().or_else(|| match &self.action {
+            Action::FileRead { .. } | Action::Template { .. } => Some(ToolAnnotations::read_only()),
+            Action::Http { method, .. } if method.eq_ignore_ascii_case("GET") => {
+                Some(ToolAnnotations::read_only().open_world())
+            }
+            _ => None,
+        });
+        Tool {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            input_schema,
+            annotations,
+        }
+    }
+}
+
+// A string with `{{name}}` placeholders
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Argument(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| format!("unclosed '{{{{' in '{}'", text))?;
+            let name = rest[start + 2..start + end].trim();
+            let valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid {
+                return Err(format!("invalid argument name '{}' in '{}'", name, text));
+            }
+            parts.push(Part::Argument(name.to_string()));
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    fn arguments(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for part in &self.parts {
+            if let Part::Argument(name) = part {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+        names
+    }
+
+    // The argument a template consists of, if that is all it is
+    fn single_argument(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [Part::Argument(name)] => Some(name),
+            _ => None,
+        }
+    }
+
+    fn render(&self, arguments: &Value) -> Result<String, McpError> {
+        self.render_with(arguments, |value| value.to_string())
+    }
+
+    fn render_with(
+        &self,
+        arguments: &Value,
+        encode: impl Fn(&str) -> String,
+    ) -> Result<String, McpError> {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Argument(name) => {
+                    let value = argument(arguments, name)?;
+                    let text = match value {
+                        Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    rendered.push_str(&encode(&text));
+                }
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+fn argument<'a>(arguments: &'a Value, name: &str) -> Result<&'a Value, McpError> {
+    arguments
+        .get(name)
+        .filter(|value| !value.is_null())
+        .ok_or_else(|| McpError::InvalidParams(format!("Missing required argument: {}", name)))
+}
+
+// Everything but RFC 3986's unreserved characters, so an argument cannot
+// add path segments or query parameters
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+// An action with its templates parsed and its clients set up
+enum Runner {
+    Http {
+        client: reqwest::Client,
+        method: reqwest::Method,
+        url: Template,
+        headers: Vec<(String, Template)>,
+        body: Option<Template>,
+        timeout: Duration,
+    },
+    Sql {
+        pool: SqlitePool,
+        query: String,
+        params: Vec<Template>,
+    },
+    FileRead {
+        root: PathBuf,
+        path: Template,
+        max_bytes: u64,
+    },
+    Template(Template),
+}
+
+impl Runner {
+    fn arguments(&self) -> Vec<String> {
+        let templates: Vec<&Template> = match self {
+            Self::Http {
+                url, headers, body, ..
+            } => std::iter::once(url)
+                .chain(headers.iter().map(|(_, value)| value))
+                .chain(body)
+                .collect(),
+            Self::Sql { params, .. } => params.iter().collect(),
+            Self::FileRead { path, .. } => vec![path],
+            Self::Template(template) => vec![template],
+        };
+        let mut names = Vec::new();
+        for name in templates.iter().flat_map(|template| template.arguments()) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    async fn run(&self, arguments: Value) -> Result<Value, McpError> {
+        match self {
+            Self::Http {
+                client,
+                method,
+                url,
+                headers,
+                body,
+                timeout,
+            } => {
+                let url = url.render_with(&arguments, percent_encode)?;
+                let mut request = client.request(method.clone(), &url).timeout(*timeout);
+                for (name, value) in headers {
+                    request = request.header(name, value.render(&arguments)?);
+                }
+                if let Some(body) = body {
+                    request = request.body(body.render(&arguments)?);
+                }
+                let response = request.send().await.map_err(|e| {
+                    McpError::Unavailable(format!("Request to {} failed: {}", url, e))
+                })?;
+                let status = response.status();
+                let text = response
+                    .text()
+                    .await
+                    .map_err(|e| format!("Failed to read the response: {}", e))?;
+                let body = serde_json::from_str(&text).unwrap_or(Value::String(text));
+                if !status.is_success() {
+                    return Err(http_error(
+                        status,
+                        format!("{} returned {}: {}", url, status, body),
+                    ));
+                }
+                Ok(serde_json::json!({ "status": status.as_u16(), "body": body }))
+            }
+            Self::Sql {
+                pool,
+                query,
+                params,
+            } => {
+                let mut statement = sqlx::query(query);
+                for param in params {
+                    statement = match param.single_argument() {
+                        Some(name) => match argument(&arguments, name)? {
+                            Value::Bool(value) => statement.bind(*value),
+                            Value::Number(number) => match number.as_i64() {
+                                Some(value) => statement.bind(value),
+                                None => statement.bind(number.as_f64()),
+                            },
+                            Value::String(value) => statement.bind(value.clone()),
+                            other => statement.bind(other.to_string()),
+                        },
+                        None => statement.bind(param.render(&arguments)?),
+                    };
+                }
+                let reads = query.split_whitespace().next().is_some_and(|keyword| {
+                    keyword.eq_ignore_ascii_case("select") || keyword.eq_ignore_ascii_case("with")
+                });
+                if reads {
+                    let rows = statement
+                        .fetch_all(pool)
+                        .await
+                        .map_err(|e| format!("Query failed: {}", e))?;
+                    let rows: Vec<Value> = rows.iter().map(row_to_json).collect();
+                    Ok(serde_json::json!({ "count": rows.len(), "rows": rows }))
+                } else {
+                    let result = statement
+                        .execute(pool)
+                        .await
+                        .map_err(|e| format!("Statement failed: {}", e))?;
+                    Ok(serde_json::json!({ "rows_affected": result.rows_affected() }))
+                }
+            }
+            Self::FileRead {
+                root,
+                path,
+                max_bytes,
+            } => {
+                let relative = PathBuf::from(path.render(&arguments)?);
+                let escapes = relative.components().any(|component| {
+                    !matches!(component, Component::Normal(_) | Component::CurDir)
+                });
+                if escapes {
+                    return Err(McpError::PermissionDenied(format!(
+                        "Path '{}' is outside the root",
+                        relative.display()
+                    )));
+                }
+                let root = tokio::fs::canonicalize(root)
+                    .await
+                    .map_err(|e| format!("Root {} is unavailable: {}", root.display(), e))?;
+                let full = tokio::fs::canonicalize(root.join(&relative))
+                    .await
+                    .map_err(|e| {
+                        McpError::NotFound(format!("Cannot read '{}': {}", relative.display(), e))
+                    })?;
+                // Symbolic links must not lead out of the root either
+                if !full.starts_with(&root) {
+                    return Err(McpError::PermissionDenied(format!(
+                        "Path '{}' is outside the root",
+                        relative.display()
+                    )));
+                }
+                let size = tokio::fs::metadata(&full)
+                    .await
+                    .map_err(|e| format!("Cannot read '{}': {}", relative.display(), e))?
+                    .len();
+                if size > *max_bytes {
+                    return Err(McpError::InvalidParams(format!(
+                        "'{}' is {} bytes, more than the limit of {}",
+                        relative.display(),
+                        size,
+                        max_bytes
+                    )));
+                }
+                let content = tokio::fs::read_to_string(&full)
+                    .await
+                    .map_err(|e| format!("Cannot read '{}': {}", relative.display(), e))?;
+                Ok(serde_json::json!({
+                    "path": relative,
+                    "size": size,
+                    "content": content
+                }))
+            }
+            Self::Template(template) => Ok(Value::String(template.render(&arguments)?)),
+        }
+    }
+}
+
+// The status says whose fault a failed request was
+fn http_error(status: reqwest::StatusCode, message: String) -> McpError {
+    match status.as_u16() {
+        400 | 422 => McpError::InvalidParams(message),
+        401 | 403 => McpError::PermissionDenied(message),
+        404 => McpError::NotFound(message),
+        409 => McpError::Conflict(message),
+        429 => McpError::RateLimited {
+            message,
+            retry_after: None,
+        },
+        500.. => McpError::Unavailable(message),
+        _ => McpError::Internal(message),
+    }
+}
+
+// SQLite values keep the type they were stored with, whatever the column
+// was declared as
+fn row_to_json(row: &SqliteRow) -> Value {
+    let mut object = Map::new();
+    for (index, column) in row.columns().iter().enumerate() {
+        let value = match row.try_get_raw(index) {
+            Ok(raw) if raw.is_null() => Value::Null,
+            Ok(raw) => match raw.type_info().name() {
+                "INTEGER" => row
+                    .try_get::<i64, _>(index)
+                    .map(Value::from)
+                    .unwrap_or_default(),
+                "REAL" => row
+                    .try_get::<f64, _>(index)
+                    .map(Value::from)
+                    .unwrap_or_default(),
+                "BLOB" => row
+                    .try_get::<Vec<u8>, _>(index)
+                    .map(|bytes| {
+                        use base64::Engine;
+                        Value::String(base64::engine::general_purpose::STANDARD.encode(bytes))
+                    })
+                    .unwrap_or_default(),
+                _ => row
+                    .try_get::<String, _>(index)
+                    .map(Value::from)
+                    .unwrap_or_default(),
+            },
+            Err(_) => Value::Null,
+        };
+        object.insert(column.name().to_string(), value);
+    }
+    Value::Object(object)
+}
+
+struct ManifestTool {
+    tool: Tool,
+    runner: Runner,
+}
+
+impl ToolHandler for ManifestTool {
+    fn tool(&self) -> Tool {
+        self.tool.clone()
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+        Box::pin(self.runner.run(arguments))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolServer;
+    use serde_json::json;
+
+    #[test]
+    fn test_templates() {
+        let template = Template::parse("/users/{{ id }}/posts?tag={{tag}}").unwrap();
+        assert_eq!(template.arguments(), ["id", "tag"]);
+        let arguments = json!({ "id": 7, "tag": "a&b/c" });
+        assert_eq!(
+            template.render_with(&arguments, percent_encode).unwrap(),
+            "/users/7/posts?tag=a%26b%2Fc"
+        );
+        assert!(template.render(&json!({ "id": 7 })).is_err());
+
+        assert!(Template::parse("{{unclosed").is_err());
+        assert!(Template::parse("{{two words}}").is_err());
+        assert_eq!(
+            Template::parse("{{x}}").unwrap().single_argument(),
+            Some("x")
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_manifests() {
+        let duplicate = ServerManifest::from_yaml(
+            "name: x\ntools:\n  - { name: a, description: A, action: { type: template, template: hi } }\n  - { name: a, description: B, action: { type: template, template: hi } }\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            duplicate.tool_router(),
+            Err(ManifestError::DuplicateTool(_))
+        ));
+
+        let interpolated = ServerManifest::from_yaml(
+            "name: x\ntools:\n  - name: q\n    description: Q\n    action:\n      type: sql\n      database: \"sqlite::memory:\"\n      query: SELECT * FROM t WHERE id = {{id}}\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            interpolated.tool_router(),
+            Err(ManifestError::Tool { .. })
+        ));
+
+        assert!(ServerManifest::from_yaml("name: x\ntools:\n  - name: a\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_built_in_actions() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "remember the milk").unwrap();
+        let database = dir.path().join("users.db");
+        let url = format!("sqlite://{}?mode=rwc", database.display());
+        let manifest = format!(
+            r#"
+name: assembled
+version: "2.0"
+tools:
+  - name: greet
+    description: Greets someone
+    action:
+      type: template
+      template: "Hello, {{{{name}}}}!"
+  - name: read_note
+    description: Reads a note
+    action:
+      type: file_read
+      root: {root}
+      path: "{{{{file}}}}"
+  - name: add_user
+    description: Adds a user
+    action:
+      type: sql
+      database: "{url}"
+      query: INSERT INTO users (name, age) VALUES (?, ?)
+      params: ["{{{{name}}}}", "{{{{age}}}}"]
+  - name: find_users
+    description: Finds users older than a given age
+    action:
+      type: sql
+      database: "{url}"
+      query: |
+        SELECT name, age FROM users
+        WHERE age > ? ORDER BY name
+      params: ["{{{{age}}}}"]
+"#,
+            root = dir.path().display(),
+            url = url
+        );
+        let manifest = ServerManifest::from_yaml(&manifest).unwrap();
+        let router = manifest.tool_router().unwrap();
+        let server = manifest.build_server().unwrap();
+        assert_eq!(server.info().name, "assembled");
+        assert_eq!(server.info().version, "2.0");
+
+        let tools = router.tool_descriptors();
+        assert_eq!(tools[0].input_schema["required"], json!(["name"]));
+        assert_eq!(tools[0].annotations, Some(ToolAnnotations::read_only()));
+        assert_eq!(tools[2].input_schema["required"], json!(["name", "age"]));
+        assert_eq!(tools[2].annotations, None);
+
+        let greeting = router.invoke_tool("greet", json!({ "name": "Ada" })).await;
+        assert_eq!(greeting, Ok(json!("Hello, Ada!")));
+
+        let note = router
+            .invoke_tool("read_note", json!({ "file": "notes.txt" }))
+            .await
+            .unwrap();
+        assert_eq!(note["content"], "remember the milk");
+        let escape = router
+            .invoke_tool("read_note", json!({ "file": "../secret" }))
+            .await;
+        assert!(
+            matches!(escape, Err(McpError::PermissionDenied(message)) if message.contains("outside the root"))
+        );
+
+        let pool = SqlitePool::connect(&url).await.unwrap();
+        sqlx::query("CREATE TABLE users (name TEXT, age INTEGER)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        for (name, age) in [("Grace", 85), ("Ada", 36), ("Linus", 25)] {
+            let added = router
+                .invoke_tool("add_user", json!({ "name": name, "age": age }))
+                .await
+                .unwrap();
+            assert_eq!(added["rows_affected"], 1);
+        }
+        // Text that would be SQL stays a value
+        let injection = json!({ "name": "x'); DROP TABLE users; --", "age": 1 });
+        router.invoke_tool("add_user", injection).await.unwrap();
+
+        let found = router
+            .invoke_tool("find_users", json!({ "age": 30 }))
+            .await
+            .unwrap();
+        assert_eq!(
+            found,
+            json!({
+                "count": 2,
+                "rows": [{ "name": "Ada", "age": 36 }, { "name": "Grace", "age": 85 }]
+            })
+        );
+    }
+}
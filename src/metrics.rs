@@ -0,0 +1,310 @@
+//! Counters and histograms shared by everything in a process.
+//!
+//! Metrics are identified by a name and a set of labels, in the style of
+//! Prometheus: `mcp_tool_calls_total{tool="search",status="ok"}`. The shared
+//! server runtime records every tool call here (see
+//! [`stats`](crate::stats)), and examples can add their own. Most code uses
+//! the process-wide [`global`] registry; tests build their own.
+//!
+//! ```no_run
+//! use mcp_rust_examples::metrics;
+//!
+//! metrics::global().increment("cache_hits_total", &[("cache", "users")], 1);
+//! metrics::global().observe("query_duration_seconds", &[], 0.012);
+//! ```
+//!
+//! [`MetricsRegistry::prometheus_text`] renders a registry in the Prometheus
+//! text exposition format, for servers that expose a `/metrics` endpoint.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Histogram bucket bounds, the Prometheus defaults. They suit durations in
+/// seconds.
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The registry shared by the whole process.
+pub fn global() -> &'static Arc<MetricsRegistry> {
+    static GLOBAL: OnceLock<Arc<MetricsRegistry>> = OnceLock::new();
+    GLOBAL.get_or_init(Default::default)
+}
+
+// A metric name and its labels, sorted by label name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct MetricKey {
+    name: String,
+    labels: Vec<(String, String)>,
+}
+
+impl MetricKey {
+    fn new(name: &str, labels: &[(&str, &str)]) -> Self {
+        let mut labels: Vec<_> = labels
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        labels.sort();
+        Self {
+            name: name.to_string(),
+            labels,
+        }
+    }
+}
+
+/// The observations of one histogram.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Histogram {
+    /// Upper bounds of the buckets, ascending.
+    pub bounds: Vec<f64>,
+    /// Observations at or below each bound, cumulative like Prometheus's.
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            buckets: vec![0; bounds.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, bucket) in self.bounds.iter().zip(&mut self.buckets) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+/// Named, labelled counters and histograms.
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    counters: Mutex<BTreeMap<MetricKey, u64>>,
+    histograms: Mutex<BTreeMap<MetricKey, Histogram>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `by` to a counter, creating it at zero.
+    pub fn increment(&self, name: &str, labels: &[(&str, &str)], by: u64) {
+        *self
+            .counters
+            .lock()
+            .unwrap()
+            .entry(MetricKey::new(name, labels))
+            .or_default() += by;
+    }
+
+    /// Records one observation in a histogram with the [`DEFAULT_BUCKETS`].
+    pub fn observe(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.observe_with_buckets(name, labels, value, DEFAULT_BUCKETS);
+    }
+
+    /// Records one observation in a histogram with its own bucket bounds,
+    /// ascending. The bounds are fixed by the first observation.
+    pub fn observe_with_buckets(
+        &self,
+        name: &str,
+        labels: &[(&str, &str)],
+        value: f64,
+        bounds: &[f64],
+    ) {
+        self.histograms
+            .lock()
+            .unwrap()
+            .entry(MetricKey::new(name, labels))
+            .or_insert_with(|| Histogram::new(bounds))
+            .observe(value);
+    }
+
+    /// A counter's value; zero if it was never incremented.
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        let key = MetricKey::new(name, labels);
+        self.counters
+            .lock()
+            .unwrap()
+            .get(&key)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn histogram(&self, name: &str, labels: &[(&str, &str)]) -> Option<Histogram> {
+        let key = MetricKey::new(name, labels);
+        self.histograms.lock().unwrap().get(&key).cloned()
+    }
+
+    /// Every metric as JSON, e.g. for a diagnostics report:
+    /// `{"counters": [{"name", "labels", "value"}], "histograms": [...]}`.
+    pub fn snapshot(&self) -> Value {
+        let labels =
+            |key: &MetricKey| -> BTreeMap<String, String> { key.labels.iter().cloned().collect() };
+        let counters: Vec<Value> = self
+            .counters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| {
+                serde_json::json!({ "name": key.name, "labels": labels(key), "value": value })
+            })
+            .collect();
+        let histograms: Vec<Value> = self
+            .histograms
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, histogram)| {
+                serde_json::json!({
+                    "name": key.name,
+                    "labels": labels(key),
+                    "histogram": histogram,
+                })
+            })
+            .collect();
+        serde_json::json!({ "counters": counters, "histograms": histograms })
+    }
+
+    /// Every metric in the Prometheus text exposition format, with `labels`
+    /// added to every series, e.g. to tell instances apart.
+    pub fn prometheus_text(&self, labels: &[(&str, &str)]) -> String {
+        let mut out = String::new();
+        let series_labels = |key: &MetricKey, extra: Option<(&str, &str)>| {
+            let mut all: Vec<(&str, &str)> = labels.to_vec();
+            all.extend(key.labels.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+            all.extend(extra);
+            prometheus_labels(&all)
+        };
+
+        let mut last_name = None;
+        for (key, value) in self.counters.lock().unwrap().iter() {
+            if last_name != Some(&key.name) {
+                let _ = writeln!(out, "# TYPE {} counter", key.name);
+                last_name = Some(&key.name);
+            }
+            let _ = writeln!(out, "{}{} {}", key.name, series_labels(key, None), value);
+        }
+
+        let mut last_name = None;
+        for (key, histogram) in self.histograms.lock().unwrap().iter() {
+            if last_name != Some(&key.name) {
+                let _ = writeln!(out, "# TYPE {} histogram", key.name);
+                last_name = Some(&key.name);
+            }
+            for (bound, count) in histogram.bounds.iter().zip(&histogram.buckets) {
+                let bound = bound.to_string();
+                let labels = series_labels(key, Some(("le", &bound)));
+                let _ = writeln!(out, "{}_bucket{} {}", key.name, labels, count);
+            }
+            let labels = series_labels(key, Some(("le", "+Inf")));
+            let _ = writeln!(out, "{}_bucket{} {}", key.name, labels, histogram.count);
+            let labels = series_labels(key, None);
+            let _ = writeln!(out, "{}_sum{} {}", key.name, labels, histogram.sum);
+            let _ = writeln!(out, "{}_count{} {}", key.name, labels, histogram.count);
+        }
+        out
+    }
+}
+
+/// A Prometheus label set such as `{tool="search",status="ok"}`, with values
+/// escaped; empty when there are no labels.
+pub fn prometheus_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// Whether `name` can be used as a Prometheus metric or label name.
+pub fn is_valid_prometheus_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_and_histograms() {
+        let registry = MetricsRegistry::new();
+        registry.increment("calls_total", &[("tool", "a"), ("status", "ok")], 1);
+        // Label order does not matter
+        registry.increment("calls_total", &[("status", "ok"), ("tool", "a")], 2);
+        assert_eq!(
+            registry.counter("calls_total", &[("tool", "a"), ("status", "ok")]),
+            3
+        );
+        assert_eq!(registry.counter("calls_total", &[("tool", "b")]), 0);
+
+        registry.observe("duration_seconds", &[], 0.02);
+        registry.observe("duration_seconds", &[], 3.0);
+        let histogram = registry.histogram("duration_seconds", &[]).unwrap();
+        assert_eq!(histogram.count, 2);
+        assert_eq!(histogram.buckets[1], 0);
+        assert_eq!(histogram.buckets[2], 1);
+        assert_eq!(*histogram.buckets.last().unwrap(), 2);
+
+        registry.observe_with_buckets("size_bytes", &[], 300.0, &[100.0, 1000.0]);
+        registry.observe_with_buckets("size_bytes", &[], 50.0, &[1.0]);
+        let histogram = registry.histogram("size_bytes", &[]).unwrap();
+        assert_eq!(histogram.bounds, [100.0, 1000.0]);
+        assert_eq!(histogram.buckets, [1, 2]);
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot["counters"][0]["labels"]["tool"], "a");
+        assert_eq!(snapshot["histograms"][0]["histogram"]["count"], 2);
+    }
+
+    #[test]
+    fn test_prometheus_text() {
+        let registry = MetricsRegistry::new();
+        registry.increment("calls_total", &[("tool", "a")], 2);
+        registry.increment("calls_total", &[("tool", "b\"")], 1);
+        registry.observe("duration_seconds", &[], 0.02);
+
+        let text = registry.prometheus_text(&[("instance", "web-1")]);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[..3],
+            [
+                "# TYPE calls_total counter",
+                "calls_total{instance=\"web-1\",tool=\"a\"} 2",
+                "calls_total{instance=\"web-1\",tool=\"b\\\"\"} 1",
+            ]
+        );
+        assert!(lines.contains(&"# TYPE duration_seconds histogram"));
+        assert!(lines.contains(&"duration_seconds_bucket{instance=\"web-1\",le=\"0.01\"} 0"));
+        assert!(lines.contains(&"duration_seconds_bucket{instance=\"web-1\",le=\"+Inf\"} 1"));
+        assert!(lines.contains(&"duration_seconds_count{instance=\"web-1\"} 1"));
+
+        assert!(is_valid_prometheus_name("mcp_tool_calls_total"));
+        assert!(!is_valid_prometheus_name("1st"));
+        assert!(!is_valid_prometheus_name("with-dash"));
+    }
+}
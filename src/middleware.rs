@@ -0,0 +1,211 @@
+//! Hooks that run around every request a server handles.
+//!
+//! A [`Middleware`] sees each request before it is handled and each response
+//! on its way out, so concerns such as authentication, logging, rate limiting
+//! and metrics stay out of the handlers. A [`MiddlewareChain`] wraps its
+//! layers around an [`Endpoint`], the code that answers requests, running
+//! them in the order they were added on the way in and in reverse on the way
+//! out:
+//!
+//! ```text
+//! on_request:  metrics -> auth -> endpoint
+//! on_response: metrics <- auth <- endpoint
+//! ```
+//!
+//! A layer may answer a request itself from `on_request`, e.g. to reject it.
+//! The endpoint and the layers after it are then skipped, while that layer
+//! and the ones before it still see the response, so an outer metrics layer
+//! counts rejected requests too.
+//!
+//! The request and response types are parameters: [`McpServer`] chains
+//! `Middleware<JsonRpcRequest, JsonRpcResponse>` layers with
+//! [`with_middleware`](McpServer::with_middleware), and a server with its own
+//! request types can chain those.
+//!
+//! [`McpServer`]: crate::server::McpServer
+
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// One layer of a [`MiddlewareChain`]. Both hooks do nothing by default.
+pub trait Middleware<Req, Res>: Send + Sync {
+    /// Runs before the request is handled, and may change it, e.g. to attach
+    /// the caller's identity. Returning a response answers the request
+    /// without running the endpoint or the layers after this one.
+    fn on_request<'a>(&'a self, _request: &'a mut Req) -> BoxFuture<'a, Option<Res>> {
+        Box::pin(async { None })
+    }
+
+    /// Runs once the request has been answered, and may change the response.
+    /// `elapsed` is the time since the chain received the request.
+    fn on_response<'a>(
+        &'a self,
+        _request: &'a Req,
+        _response: &'a mut Res,
+        _elapsed: Duration,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(async {})
+    }
+}
+
+/// Answers the requests every layer of a [`MiddlewareChain`] let through.
+pub trait Endpoint<Req, Res>: Send + Sync {
+    fn handle<'a>(&'a self, request: &'a mut Req) -> BoxFuture<'a, Res>;
+}
+
+/// Middleware layers run around an [`Endpoint`].
+pub struct MiddlewareChain<Req, Res> {
+    layers: Vec<Arc<dyn Middleware<Req, Res>>>,
+}
+
+impl<Req, Res> Default for MiddlewareChain<Req, Res> {
+    fn default() -> Self {
+        Self { layers: Vec::new() }
+    }
+}
+
+impl<Req, Res> Clone for MiddlewareChain<Req, Res> {
+    fn clone(&self) -> Self {
+        Self {
+            layers: self.layers.clone(),
+        }
+    }
+}
+
+impl<Req: Send + Sync, Res: Send> MiddlewareChain<Req, Res> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `layer` inside the layers added so far.
+    pub fn with(mut self, layer: Arc<dyn Middleware<Req, Res>>) -> Self {
+        self.push(layer);
+        self
+    }
+
+    pub fn push(&mut self, layer: Arc<dyn Middleware<Req, Res>>) {
+        self.layers.push(layer);
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Passes `request` through every layer to `endpoint` and the response
+    /// back out.
+    pub async fn run(&self, mut request: Req, endpoint: &impl Endpoint<Req, Res>) -> Res {
+        let started = Instant::now();
+        let mut entered = 0;
+        let mut answered = None;
+        for layer in &self.layers {
+            entered += 1;
+            answered = layer.on_request(&mut request).await;
+            if answered.is_some() {
+                break;
+            }
+        }
+
+        let mut response = match answered {
+            Some(response) => response,
+            None => endpoint.handle(&mut request).await,
+        };
+        let elapsed = started.elapsed();
+        for layer in self.layers[..entered].iter().rev() {
+            layer.on_response(&request, &mut response, elapsed).await;
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Records the hooks it sees; rejects requests equal to `reject`
+    struct Trace {
+        name: &'static str,
+        reject: Option<i32>,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware<i32, String> for Trace {
+        fn on_request<'a>(&'a self, request: &'a mut i32) -> BoxFuture<'a, Option<String>> {
+            Box::pin(async move {
+                self.log.lock().unwrap().push(format!("{} in", self.name));
+                if self.reject == Some(*request) {
+                    return Some(format!("rejected by {}", self.name));
+                }
+                *request += 1;
+                None
+            })
+        }
+
+        fn on_response<'a>(
+            &'a self,
+            _request: &'a i32,
+            response: &'a mut String,
+            _elapsed: Duration,
+        ) -> BoxFuture<'a, ()> {
+            Box::pin(async move {
+                self.log.lock().unwrap().push(format!("{} out", self.name));
+                response.push('!');
+            })
+        }
+    }
+
+    // Answers with the request it got
+    struct Echo;
+
+    impl Endpoint<i32, String> for Echo {
+        fn handle<'a>(&'a self, request: &'a mut i32) -> BoxFuture<'a, String> {
+            Box::pin(async move { format!("handled {}", request) })
+        }
+    }
+
+    fn chain(reject: Option<i32>, log: &Arc<Mutex<Vec<String>>>) -> MiddlewareChain<i32, String> {
+        MiddlewareChain::new()
+            .with(Arc::new(Trace {
+                name: "outer",
+                reject: None,
+                log: log.clone(),
+            }))
+            .with(Arc::new(Trace {
+                name: "inner",
+                reject,
+                log: log.clone(),
+            }))
+    }
+
+    #[tokio::test]
+    async fn test_layers_wrap_the_endpoint() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let response = chain(None, &log).run(0, &Echo).await;
+
+        // Each layer bumped the request on the way in and marked the response
+        // on the way out
+        assert_eq!(response, "handled 2!!");
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["outer in", "inner in", "inner out", "outer out"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_layer_can_answer_early() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let response = chain(Some(1), &log).run(0, &Echo).await;
+
+        // The endpoint never saw the request
+        assert_eq!(response, "rejected by inner!!");
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["outer in", "inner in", "inner out", "outer out"]
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! An in-memory transport for testing clients and servers together.
+//!
+//! [`MockTransport::pair`] connects a [`MockClient`] to a [`MockServer`]
+//! without spawning a process. The server end runs the same loop as
+//! [`StdioTransport`], so a test exercises the full JSON-RPC path of an
+//! example: framing, parse errors, notifications, keepalive and resource
+//! updates. Frames travel through channels on the way, where [`Faults`] can
+//! drop, delay or corrupt them.
+//!
+//! ```no_run
+//! # use mcp_rust_examples::mock_transport::{Fault, Faults, MockTransport};
+//! # use mcp_rust_examples::server::McpServer;
+//! # use std::sync::Arc;
+//! # async fn demo(server: Arc<McpServer>) {
+//! let (mut client, server_end) = MockTransport::new()
+//!     .with_request_faults(Faults::new().on_frame(0, Fault::Drop))
+//!     .pair();
+//! server_end.spawn(server);
+//! assert!(client.request("ping", serde_json::Value::Null).await.is_err());
+//! # }
+//! ```
+//!
+//! The pair spawns tasks to move frames, so it must be created inside a
+//! Tokio runtime.
+//!
+//! [`StdioTransport`]: crate::transport::StdioTransport
+
+use crate::keepalive::{DisconnectReason, KeepaliveConfig};
+use crate::protocol::{CallToolResult, JsonRpcError, JsonRpcRequest, JsonRpcResponse, RequestId};
+use crate::server::McpServer;
+use crate::transport::StdioTransport;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// How long [`MockClient`] waits for a message unless configured otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Room for a few large frames in flight between the pumps and the server
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// What can go wrong with a single frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The frame never arrives.
+    Drop,
+    /// The frame arrives late. Frames behind it wait too, as on a real stream.
+    Delay(Duration),
+    /// Only the first half of the frame arrives, which is never valid JSON.
+    Corrupt,
+}
+
+/// The faults to inject in one direction. Frames are numbered from 0 in the
+/// order they are sent.
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    latency: Duration,
+    frames: HashMap<usize, Fault>,
+}
+
+impl Faults {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delays every frame by `latency`.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn on_frame(mut self, index: usize, fault: Fault) -> Self {
+        self.frames.insert(index, fault);
+        self
+    }
+
+    async fn apply(&self, index: usize, frame: String) -> Option<String> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        match self.frames.get(&index) {
+            None => Some(frame),
+            Some(Fault::Drop) => {
+                tracing::debug!(index, "Dropping frame");
+                None
+            }
+            Some(Fault::Delay(delay)) => {
+                tokio::time::sleep(*delay).await;
+                Some(frame)
+            }
+            Some(Fault::Corrupt) => {
+                tracing::debug!(index, "Corrupting frame");
+                let half = frame.chars().count() / 2;
+                Some(frame.chars().take(half).collect())
+            }
+        }
+    }
+}
+
+/// Errors seen by a [`MockClient`].
+#[derive(Debug, thiserror::Error)]
+pub enum MockError {
+    #[error("no message within {}ms", .0.as_millis())]
+    Timeout(Duration),
+
+    #[error("the server end is closed")]
+    Closed,
+
+    #[error("malformed frame {frame:?}: {source}")]
+    Malformed {
+        frame: String,
+        source: serde_json::Error,
+    },
+
+    #[error("server returned error {}: {}", .0.code, .0.message)]
+    Rpc(JsonRpcError),
+
+    #[error("invalid message: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Builds a connected [`MockClient`] and [`MockServer`].
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    requests: Faults,
+    responses: Faults,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Faults on frames from the client to the server.
+    pub fn with_request_faults(mut self, faults: Faults) -> Self {
+        self.requests = faults;
+        self
+    }
+
+    /// Faults on frames from the server to the client.
+    pub fn with_response_faults(mut self, faults: Faults) -> Self {
+        self.responses = faults;
+        self
+    }
+
+    pub fn pair(self) -> (MockClient, MockServer) {
+        let (to_server_tx, mut to_server_rx) = mpsc::unbounded_channel::<String>();
+        let (to_client_tx, to_client_rx) = mpsc::unbounded_channel();
+        let (mut server_input, server_reader) = duplex(PIPE_CAPACITY);
+        let (server_writer, server_output) = duplex(PIPE_CAPACITY);
+
+        // Closing the client's sender ends the server's input
+        let requests = self.requests;
+        tokio::spawn(async move {
+            let mut index = 0;
+            while let Some(frame) = to_server_rx.recv().await {
+                if let Some(frame) = requests.apply(index, frame).await {
+                    let line = format!("{}\n", frame);
+                    if server_input.write_all(line.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+                index += 1;
+            }
+        });
+
+        let responses = self.responses;
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(server_output).lines();
+            let mut index = 0;
+            while let Ok(Some(frame)) = lines.next_line().await {
+                if let Some(frame) = responses.apply(index, frame).await {
+                    if to_client_tx.send(frame).is_err() {
+                        break;
+                    }
+                }
+                index += 1;
+            }
+        });
+
+        let client = MockClient {
+            outgoing: to_server_tx,
+            incoming: to_client_rx,
+            unsolicited: VecDeque::new(),
+            next_id: 0,
+            timeout: DEFAULT_TIMEOUT,
+        };
+        let server = MockServer {
+            transport: StdioTransport::with_io(BufReader::new(server_reader), server_writer),
+        };
+        (client, server)
+    }
+}
+
+/// The server end of a mock connection.
+pub struct MockServer {
+    transport: StdioTransport<BufReader<DuplexStream>, DuplexStream>,
+}
+
+impl MockServer {
+    /// See [`StdioTransport::with_keepalive`].
+    pub fn with_keepalive(mut self, config: KeepaliveConfig) -> Self {
+        self.transport = self.transport.with_keepalive(config);
+        self
+    }
+
+    /// Serves `server` until the client end is dropped or closed.
+    pub async fn serve(self, server: &McpServer) -> std::io::Result<DisconnectReason> {
+        self.transport.serve(server).await
+    }
+
+    /// Serves `server` on a background task.
+    pub fn spawn(self, server: Arc<McpServer>) -> JoinHandle<std::io::Result<DisconnectReason>> {
+        tokio::spawn(async move { self.serve(&server).await })
+    }
+}
+
+/// The client end of a mock connection. Dropping it disconnects the server.
+pub struct MockClient {
+    outgoing: mpsc::UnboundedSender<String>,
+    incoming: mpsc::UnboundedReceiver<String>,
+    // Messages that arrived while waiting for a response
+    unsolicited: VecDeque<Value>,
+    next_id: i64,
+    timeout: Duration,
+}
+
+impl MockClient {
+    /// How long to wait for each message before giving up.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends a frame exactly as given, e.g. deliberately malformed JSON.
+    pub fn send_raw(&self, frame: impl Into<String>) -> Result<(), MockError> {
+        self.outgoing
+            .send(frame.into())
+            .map_err(|_| MockError::Closed)
+    }
+
+    pub fn send(&self, message: &impl Serialize) -> Result<(), MockError> {
+        self.send_raw(serde_json::to_string(message)?)
+    }
+
+    /// Sends a notification, which gets no response.
+    pub fn notify(&self, method: &str, params: Value) -> Result<(), MockError> {
+        self.send(&JsonRpcRequest::notification(method, params))
+    }
+
+    /// Sends a request and waits for its response. Other messages that
+    /// arrive first, such as notifications, are kept for [`recv`](Self::recv).
+    pub async fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<JsonRpcResponse, MockError> {
+        self.next_id += 1;
+        let id = RequestId::Number(self.next_id);
+        self.send(&JsonRpcRequest::new(id.clone(), method, params))?;
+
+        let id = serde_json::json!(id);
+        loop {
+            let message = self.recv_frame().await?;
+            // A request the server could not parse is answered with a null id
+            let answers = message.get("method").is_none()
+                && matches!(message.get("id"), Some(found) if *found == id || found.is_null());
+            if answers {
+                return Ok(serde_json::from_value(message)?);
+            }
+            self.unsolicited.push_back(message);
+        }
+    }
+
+    /// Calls a tool. Protocol errors become [`MockError::Rpc`]; tool failures
+    /// are results with `is_error` set.
+    pub async fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: Value,
+    ) -> Result<CallToolResult, MockError> {
+        let params = serde_json::json!({ "name": name, "arguments": arguments });
+        let response = self.request("tools/call", params).await?;
+        if let Some(error) = response.error {
+            return Err(MockError::Rpc(error));
+        }
+        Ok(serde_json::from_value(response.result.unwrap_or_default())?)
+    }
+
+    /// The next message from the server, oldest first.
+    pub async fn recv(&mut self) -> Result<Value, MockError> {
+        match self.unsolicited.pop_front() {
+            Some(message) => Ok(message),
+            None => self.recv_frame().await,
+        }
+    }
+
+    /// The next frame from the server, unparsed.
+    pub async fn recv_raw(&mut self) -> Result<String, MockError> {
+        let deadline = Instant::now() + self.timeout;
+        match tokio::time::timeout_at(deadline, self.incoming.recv()).await {
+            Ok(Some(frame)) => Ok(frame),
+            Ok(None) => Err(MockError::Closed),
+            Err(_) => Err(MockError::Timeout(self.timeout)),
+        }
+    }
+
+    async fn recv_frame(&mut self) -> Result<Value, MockError> {
+        let frame = self.recv_raw().await?;
+        serde_json::from_str(&frame).map_err(|source| MockError::Malformed { frame, source })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::McpError;
+    use crate::protocol::{error_codes, Tool};
+    use crate::server::{ToolHandler, ToolRouter};
+    use futures::future::BoxFuture;
+
+    struct Echo;
+
+    impl ToolHandler for Echo {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "echo".to_string(),
+                description: "Echo the arguments back".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async move { Ok(arguments) })
+        }
+    }
+
+    fn connect(
+        transport: MockTransport,
+    ) -> (MockClient, JoinHandle<std::io::Result<DisconnectReason>>) {
+        let server = McpServer::new(Arc::new(ToolRouter::new("echo_server").with_handler(Echo)));
+        let (client, server_end) = transport.pair();
+        (client, server_end.spawn(Arc::new(server)))
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let (mut client, server) = connect(MockTransport::new());
+
+        let response = client.request("initialize", Value::Null).await.unwrap();
+        assert_eq!(
+            response.result.unwrap()["serverInfo"]["name"],
+            "echo_server"
+        );
+        client
+            .notify("notifications/initialized", Value::Null)
+            .unwrap();
+
+        let result = client
+            .call_tool("echo", serde_json::json!({ "word": "hi" }))
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+
+        let error = client.call_tool("missing", Value::Null).await.unwrap_err();
+        assert!(matches!(error, MockError::Rpc(e) if e.code == error_codes::INVALID_PARAMS));
+
+        drop(client);
+        assert_eq!(server.await.unwrap().unwrap(), DisconnectReason::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_and_delayed_frames() {
+        let delay = Duration::from_millis(50);
+        let faults = Faults::new()
+            .on_frame(0, Fault::Drop)
+            .on_frame(1, Fault::Delay(delay));
+        let (client, _server) = connect(MockTransport::new().with_request_faults(faults));
+        let mut client = client.with_timeout(Duration::from_millis(200));
+
+        let error = client.request("ping", Value::Null).await.unwrap_err();
+        assert!(matches!(error, MockError::Timeout(_)));
+
+        let started = Instant::now();
+        let response = client.request("ping", Value::Null).await.unwrap();
+        assert!(response.error.is_none());
+        assert!(started.elapsed() >= delay);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_frames() {
+        let (mut client, _server) = connect(
+            MockTransport::new()
+                .with_request_faults(Faults::new().on_frame(0, Fault::Corrupt))
+                .with_response_faults(Faults::new().on_frame(1, Fault::Corrupt)),
+        );
+
+        // The server answers a truncated request with a parse error
+        let response = client.request("ping", Value::Null).await.unwrap();
+        assert_eq!(response.error.unwrap().code, error_codes::PARSE_ERROR);
+
+        // and the client notices a truncated response
+        let error = client.request("ping", Value::Null).await.unwrap_err();
+        assert!(matches!(error, MockError::Malformed { .. }));
+
+        client.send_raw("not json").unwrap();
+        let response = client.recv().await.unwrap();
+        assert_eq!(response["error"]["code"], error_codes::PARSE_ERROR);
+    }
+}
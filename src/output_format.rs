@@ -0,0 +1,207 @@
+//! How tool results are written out as text.
+//!
+//! A tool returns a JSON value, which reaches the client as the text of the
+//! result's content. Models read any of it, but people and programs have
+//! preferences: someone at a terminal wants it indented, a log pipeline wants
+//! one record per line. Each connection picks one [`OutputFormat`]:
+//!
+//! - `compact`, the default: the value on one line.
+//! - `pretty`: the value indented over several lines.
+//! - `ndjson`: the items of a list result one per line, as newline-delimited
+//!   JSON. Each item is a content block of its own, so the text of the result
+//!   is the NDJSON document and a client can also take the items one by one.
+//!
+//! ```
+//! # use mcp_rust_examples::output_format::OutputFormat;
+//! # use serde_json::json;
+//! let result = json!({ "users": [{ "id": 1 }, { "id": 2 }], "count": 2 });
+//! let rendered = OutputFormat::Ndjson.render(&result);
+//! assert_eq!(rendered.blocks, [r#"{"id":1}"#, r#"{"id":2}"#]);
+//! // The fields around the list are not lost
+//! assert_eq!(rendered.meta.unwrap()["ndjson"]["fields"], json!({ "count": 2 }));
+//! ```
+//!
+//! A list result is an array, or an object with exactly one array field, like
+//! `{"users": [...], "count": 2}`; its other fields go to the result's
+//! `_meta`. Anything else is written as one compact line, which is an NDJSON
+//! document too.
+//!
+//! A server's default comes from [`McpServer::with_output_format`] or the
+//! [`OUTPUT_FORMAT_ENV`] environment variable. A client asks for another in
+//! `initialize`, as an experimental capability, and the server answers with
+//! the format the connection got:
+//!
+//! ```json
+//! "capabilities": { "experimental": { "outputFormat": "ndjson" } }
+//! ```
+//!
+//! [`McpServer::with_output_format`]: crate::server::McpServer::with_output_format
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// The default output format of servers that are not given one, e.g.
+/// `pretty`.
+pub const OUTPUT_FORMAT_ENV: &str = "MCP_OUTPUT_FORMAT";
+
+/// A name that is not an [`OutputFormat`].
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("unknown output format '{0}'; expected compact, pretty or ndjson")]
+pub struct UnknownOutputFormat(pub String);
+
+/// How a tool result's JSON value is written as text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Compact,
+    Pretty,
+    Ndjson,
+}
+
+/// A value written in an [`OutputFormat`]: the text of each content block,
+/// and what goes in the result's `_meta`, if anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    pub blocks: Vec<String>,
+    pub meta: Option<Value>,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 3] = [Self::Compact, Self::Pretty, Self::Ndjson];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Compact => "compact",
+            Self::Pretty => "pretty",
+            Self::Ndjson => "ndjson",
+        }
+    }
+
+    /// The format named by [`OUTPUT_FORMAT_ENV`], or `None` when it is unset
+    /// or invalid.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(OUTPUT_FORMAT_ENV).ok()?;
+        if value.trim().is_empty() {
+            return None;
+        }
+        match value.parse() {
+            Ok(format) => Some(format),
+            Err(e) => {
+                tracing::warn!("Ignoring invalid {}: {}", OUTPUT_FORMAT_ENV, e);
+                None
+            }
+        }
+    }
+
+    /// `value` written in this format.
+    pub fn render(&self, value: &Value) -> Rendered {
+        let text = |format: Self, value: &Value| match format {
+            Self::Pretty => serde_json::to_string_pretty(value).unwrap_or_default(),
+            _ => value.to_string(),
+        };
+        let Some((items, fields)) = list_items(value).filter(|_| *self == Self::Ndjson) else {
+            return Rendered {
+                blocks: vec![text(*self, value)],
+                meta: None,
+            };
+        };
+
+        Rendered {
+            blocks: items.iter().map(|item| text(*self, item)).collect(),
+            meta: fields.map(|(field, fields)| {
+                serde_json::json!({ "ndjson": { "itemsField": field, "fields": fields } })
+            }),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = UnknownOutputFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "compact" | "json" => Ok(Self::Compact),
+            "pretty" => Ok(Self::Pretty),
+            "ndjson" | "jsonl" | "jsonlines" => Ok(Self::Ndjson),
+            _ => Err(UnknownOutputFormat(s.to_string())),
+        }
+    }
+}
+
+type ListFields = (String, serde_json::Map<String, Value>);
+
+// The items of a list result, and for a list wrapped in an object, the name
+// of its field and the object's other fields
+fn list_items(value: &Value) -> Option<(&[Value], Option<ListFields>)> {
+    match value {
+        Value::Array(items) => Some((items, None)),
+        Value::Object(object) => {
+            let mut arrays = object.iter().filter(|(_, value)| value.is_array());
+            let (field, items) = arrays.next()?;
+            if arrays.next().is_some() {
+                return None;
+            }
+            let fields = object
+                .iter()
+                .filter(|(name, _)| *name != field)
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            Some((items.as_array()?, Some((field.clone(), fields))))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_formats_render_values_and_lists() {
+        let value = json!({ "name": "Ann", "tags": ["a", "b"], "age": 31 });
+        assert_eq!(
+            OutputFormat::Compact.render(&value).blocks,
+            [value.to_string()]
+        );
+        let pretty = OutputFormat::Pretty.render(&value);
+        assert!(pretty.blocks[0].contains("\n  \"name\": \"Ann\""));
+        assert_eq!(pretty.meta, None);
+
+        // A plain array has no fields to keep
+        let rendered = OutputFormat::Ndjson.render(&json!([1, { "a": 2 }]));
+        assert_eq!(rendered.blocks, ["1", r#"{"a":2}"#]);
+        assert_eq!(rendered.meta, None);
+        assert!(OutputFormat::Ndjson.render(&json!([])).blocks.is_empty());
+
+        // Objects with no or several arrays are not lists
+        let rendered = OutputFormat::Ndjson.render(&json!({ "a": [1], "b": [2] }));
+        assert_eq!(rendered.blocks, [r#"{"a":[1],"b":[2]}"#]);
+        assert_eq!(OutputFormat::Ndjson.render(&json!(7)).blocks, ["7"]);
+    }
+
+    #[test]
+    fn test_formats_parse_by_name() {
+        for format in OutputFormat::ALL {
+            assert_eq!(format.as_str().parse::<OutputFormat>(), Ok(format));
+            assert_eq!(
+                serde_json::to_value(format).unwrap(),
+                json!(format.to_string())
+            );
+        }
+        assert_eq!(" JSONL ".parse::<OutputFormat>(), Ok(OutputFormat::Ndjson));
+        assert_eq!(
+            "yaml".parse::<OutputFormat>(),
+            Err(UnknownOutputFormat("yaml".to_string()))
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Keeping the paths tools are given inside a set of allowed directories.
+//!
+//! A tool that reads or writes files must not be talked into going
+//! elsewhere, whether through `..`, a symlink, a name that only looks like
+//! an allowed one, or a different spelling of it. [`PathPolicy::resolve`]
+//! normalizes a requested path, canonicalizes it (or its parent, for a file
+//! that does not exist yet) and accepts it only inside one of the allowed
+//! directories. Case is ignored only where the volume itself ignores it:
+//!
+//! ```
+//! # use mcp_rust_examples::path_policy::{PathError, PathPolicy};
+//! let dir = tempfile::tempdir().unwrap();
+//! let allowed = [dir.path().to_path_buf()];
+//! let policy = PathPolicy::default();
+//! assert!(policy.resolve(&dir.path().join("new.txt"), &allowed).is_ok());
+//! assert!(matches!(
+//!     policy.resolve(&dir.path().join("../escape.txt"), &allowed),
+//!     Err(PathError::NotAllowed(_))
+//! ));
+//! ```
+
+use crate::error::McpError;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PathError {
+    /// The path is malformed, or its parent directory does not exist.
+    #[error("{0}")]
+    Invalid(String),
+    /// The path is outside every allowed directory.
+    #[error("Path '{}' is not in an allowed directory", .0.display())]
+    NotAllowed(PathBuf),
+}
+
+impl From<PathError> for McpError {
+    fn from(error: PathError) -> Self {
+        match error {
+            PathError::Invalid(message) => {
+                McpError::InvalidParams(format!("Invalid path: {}", message))
+            }
+            PathError::NotAllowed(_) => McpError::PermissionDenied(error.to_string()),
+        }
+    }
+}
+
+/// How requested paths are normalized and compared with the allowed
+/// directories.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PathPolicy {
+    /// Compose Unicode, so "e" followed by a combining accent matches "é".
+    #[serde(default = "default_true")]
+    pub unicode_nfc: bool,
+    /// Reject control and invisible formatting characters; NUL is always
+    /// rejected.
+    #[serde(default = "default_true")]
+    pub reject_control_characters: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for PathPolicy {
+    fn default() -> Self {
+        Self {
+            unicode_nfc: true,
+            reject_control_characters: true,
+        }
+    }
+}
+
+// Characters that change how a name is displayed without being visible,
+// e.g. a right-to-left override that makes "txt.exe" read as "exe.txt"
+fn is_invisible_format(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2069}' | '\u{FEFF}'
+    )
+}
+
+impl PathPolicy {
+    /// Checks a requested path and returns it in normal form.
+    pub fn normalize(&self, path: &str) -> Result<String, PathError> {
+        if path.contains('\0') {
+            return Err(PathError::Invalid(
+                "Path contains a NUL character".to_string(),
+            ));
+        }
+        if self.reject_control_characters {
+            if let Some(c) = path
+                .chars()
+                .find(|c| c.is_control() || is_invisible_format(*c))
+            {
+                return Err(PathError::Invalid(format!(
+                    "Path contains control character U+{:04X}",
+                    c as u32
+                )));
+            }
+        }
+
+        Ok(if self.unicode_nfc {
+            path.nfc().collect()
+        } else {
+            path.to_string()
+        })
+    }
+
+    /// The canonical form of `path`, if it is inside one of `allowed`.
+    /// `path` should already be [normalized](Self::normalize); it need not
+    /// exist, but its parent directory must.
+    pub fn resolve(&self, path: &Path, allowed: &[PathBuf]) -> Result<PathBuf, PathError> {
+        // Canonical paths have no `..` or symlinks left to escape through
+        let canonical_path = match path.canonicalize() {
+            Ok(path) => path,
+            // A dangling symlink would pass as a new file in its parent,
+            // while writing to it creates its target wherever that is
+            Err(_) if path.is_symlink() => {
+                return Err(PathError::Invalid(
+                    "Path is a symbolic link to nothing".to_string(),
+                ))
+            }
+            // The file might not exist yet, but its parent has to
+            Err(_) => match path.parent() {
+                Some(parent) if parent.exists() => parent
+                    .canonicalize()
+                    .map_err(|e| PathError::Invalid(e.to_string()))?
+                    .join(path.file_name().unwrap_or_default()),
+                Some(_) => {
+                    return Err(PathError::Invalid(
+                        "Parent directory does not exist".to_string(),
+                    ))
+                }
+                None => return Err(PathError::Invalid("Invalid path structure".to_string())),
+            },
+        };
+
+        let allowed = allowed.iter().any(|directory| {
+            directory.canonicalize().is_ok_and(|directory| {
+                within(&canonical_path, &directory, false)
+                    || (is_case_insensitive(&directory)
+                        && within(&canonical_path, &directory, true))
+            })
+        });
+        if allowed {
+            Ok(canonical_path)
+        } else {
+            Err(PathError::NotAllowed(canonical_path))
+        }
+    }
+
+    /// Whether `path` is `directory` or inside it, comparing whole
+    /// components byte for byte. Both should be canonical.
+    pub fn is_within(&self, path: &Path, directory: &Path) -> bool {
+        within(path, directory, false)
+    }
+}
+
+fn within(path: &Path, directory: &Path, ignore_case: bool) -> bool {
+    let mut components = path.components();
+    directory.components().all(|expected| {
+        components.next().is_some_and(|component| {
+            same_component(component.as_os_str(), expected.as_os_str(), ignore_case)
+        })
+    })
+}
+
+fn same_component(a: &OsStr, b: &OsStr, ignore_case: bool) -> bool {
+    if a == b {
+        return true;
+    }
+    // Names that are not UTF-8 can only be compared byte for byte
+    match (ignore_case, a.to_str(), b.to_str()) {
+        (true, Some(a), Some(b)) => a.to_lowercase() == b.to_lowercase(),
+        _ => false,
+    }
+}
+
+/// Whether the volume holding `directory` ignores case in names, found by
+/// looking up a name inside it (or, for an empty directory, a component of
+/// its canonical path) with the case swapped. Where that lookup finds
+/// nothing, or a different file, case matters.
+pub fn is_case_insensitive(directory: &Path) -> bool {
+    let Ok(directory) = directory.canonicalize() else {
+        return false;
+    };
+    let swap_case = |name: &str| -> String {
+        name.chars()
+            .map(|c| {
+                if c.is_ascii_lowercase() {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                }
+            })
+            .collect()
+    };
+    let probe = |path: &Path| -> Option<bool> {
+        let name = path.file_name()?.to_str()?;
+        let swapped = swap_case(name);
+        (swapped != name).then(|| is_same_file(path, &path.with_file_name(swapped)))
+    };
+
+    // Names inside the directory are on its volume; its own name may not be
+    let inside = std::fs::read_dir(&directory)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .find_map(|entry| probe(&entry.path()));
+    inside
+        .or_else(|| directory.ancestors().find_map(probe))
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    b.canonicalize().is_ok_and(|b| b == a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_resolve_only_inside_allowed_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = dir.path().join("inner");
+        std::fs::create_dir(&inner).unwrap();
+        let allowed = [inner.clone()];
+        let policy = PathPolicy::default();
+
+        let resolved = policy.resolve(&inner.join("a.db"), &allowed).unwrap();
+        assert_eq!(resolved, inner.canonicalize().unwrap().join("a.db"));
+        assert!(matches!(
+            policy.resolve(&inner.join("../a.db"), &allowed),
+            Err(PathError::NotAllowed(_))
+        ));
+        // A sibling whose name starts with the allowed one is still outside
+        std::fs::create_dir(dir.path().join("inner2")).unwrap();
+        assert!(matches!(
+            policy.resolve(&dir.path().join("inner2/a.db"), &allowed),
+            Err(PathError::NotAllowed(_))
+        ));
+        assert!(matches!(
+            policy.resolve(&inner.join("missing/a.db"), &allowed),
+            Err(PathError::Invalid(_))
+        ));
+        // Where a name differing only in case can be created, it is a
+        // different directory and case is never ignored
+        if std::fs::create_dir(dir.path().join("INNER")).is_ok() {
+            assert!(!is_case_insensitive(&inner));
+            assert!(matches!(
+                policy.resolve(&dir.path().join("INNER/a.db"), &allowed),
+                Err(PathError::NotAllowed(_))
+            ));
+        }
+
+        let error = McpError::from(policy.normalize("a\0b").unwrap_err());
+        assert_eq!(
+            error,
+            McpError::InvalidParams("Invalid path: Path contains a NUL character".to_string())
+        );
+        let error = McpError::from(PathError::NotAllowed(PathBuf::from("/etc/passwd")));
+        assert!(matches!(error, McpError::PermissionDenied(_)));
+    }
+}
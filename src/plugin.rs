@@ -0,0 +1,477 @@
+//! Tools loaded from WebAssembly plugins.
+//!
+//! A plugin is a WASM module that brings its own tools, so a server can be
+//! extended without recompiling this crate. The module exports:
+//!
+//! - `memory`, its linear memory;
+//! - `alloc(len: i32) -> i32`, returning a buffer of `len` bytes the host can
+//!   write into;
+//! - `tools() -> i64`, pointing at a JSON array of [`Tool`] descriptors;
+//! - `invoke(name_ptr: i32, name_len: i32, args_ptr: i32, args_len: i32) -> i64`,
+//!   calling a tool with its JSON arguments and pointing at either
+//!   `{"result": ...}` or `{"error": "..."}`.
+//!
+//! The `i64` results pack a pointer into the high 32 bits and a length into
+//! the low 32. Plugins get no imports, so they cannot reach files, the
+//! network or the clock; they only see the arguments they are given. Every
+//! call runs in a fresh instance with a fuel budget and a memory cap (see
+//! [`PluginLimits`]), so a plugin that loops or allocates without end fails
+//! that one call instead of stalling the server.
+
+use crate::error::McpError;
+use crate::protocol::Tool;
+use crate::registry::ToolRegistry;
+use crate::server::ToolHandler;
+use futures::future::BoxFuture;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use wasmi::core::TrapCode;
+use wasmi::{
+    Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+/// What a single plugin call may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PluginLimits {
+    /// Fuel for one call, roughly one unit per executed instruction.
+    pub fuel: u64,
+    /// The most linear memory an instance may grow to.
+    pub memory_bytes: usize,
+    /// The largest JSON document the host reads back out of the plugin.
+    pub max_output_bytes: usize,
+}
+
+impl Default for PluginLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 100_000_000,
+            memory_bytes: 16 * 1024 * 1024,
+            max_output_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Errors raised while loading a plugin or running one of its tools.
+#[derive(Debug, thiserror::Error)]
+pub enum PluginError {
+    #[error("failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("invalid plugin: {0}")]
+    Wasm(#[from] wasmi::Error),
+
+    #[error("plugin does not follow the tool interface: {0}")]
+    Abi(String),
+
+    #[error("plugin ran out of fuel")]
+    OutOfFuel,
+
+    #[error("plugin exceeded its memory limit")]
+    MemoryLimit,
+
+    #[error("plugin output is larger than {0} bytes")]
+    OutputTooLarge(usize),
+
+    #[error("{0}")]
+    Tool(String),
+}
+
+impl From<PluginError> for McpError {
+    fn from(error: PluginError) -> Self {
+        match error {
+            PluginError::OutOfFuel | PluginError::MemoryLimit => {
+                McpError::Unavailable(error.to_string())
+            }
+            PluginError::Tool(message) => McpError::Internal(message),
+            other => McpError::Internal(other.to_string()),
+        }
+    }
+}
+
+// Per-call store data: only the resource limits
+struct HostState {
+    limits: StoreLimits,
+}
+
+/// A compiled plugin and the tools it declared when it was loaded.
+pub struct Plugin {
+    name: String,
+    engine: Engine,
+    module: Module,
+    limits: PluginLimits,
+    tools: Vec<Tool>,
+}
+
+impl Plugin {
+    /// Compiles `bytes` (binary WASM) and asks the module for its tools.
+    pub fn from_bytes(
+        name: impl Into<String>,
+        bytes: &[u8],
+        limits: PluginLimits,
+    ) -> Result<Self, PluginError> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes)?;
+        let mut plugin = Self {
+            name: name.into(),
+            engine,
+            module,
+            limits,
+            tools: Vec::new(),
+        };
+        plugin.tools = plugin.describe()?;
+        Ok(plugin)
+    }
+
+    /// Loads the plugin at `path`, named after the file.
+    pub fn load(path: &Path, limits: PluginLimits) -> Result<Self, PluginError> {
+        let bytes = std::fs::read(path).map_err(|source| PluginError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::from_bytes(name, &bytes, limits)
+    }
+
+    /// Loads every `.wasm` file in `dir`, in file name order.
+    pub fn load_dir(dir: &Path, limits: PluginLimits) -> Result<Vec<Arc<Self>>, PluginError> {
+        let io_error = |source| PluginError::Io {
+            path: dir.to_path_buf(),
+            source,
+        };
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "wasm")
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| Self::load(path, limits).map(Arc::new))
+            .collect()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The tools the plugin declared.
+    pub fn tools(&self) -> &[Tool] {
+        &self.tools
+    }
+
+    /// One [`ToolHandler`] per declared tool.
+    pub fn handlers(self: &Arc<Self>) -> Vec<Box<dyn ToolHandler>> {
+        self.tools
+            .iter()
+            .map(|tool| {
+                Box::new(PluginTool {
+                    plugin: self.clone(),
+                    tool: tool.clone(),
+                }) as Box<dyn ToolHandler>
+            })
+            .collect()
+    }
+
+    /// Registers every declared tool on `registry`, replacing tools with the
+    /// same names.
+    pub fn register(self: &Arc<Self>, registry: &ToolRegistry) {
+        for tool in &self.tools {
+            registry.register(PluginTool {
+                plugin: self.clone(),
+                tool: tool.clone(),
+            });
+        }
+    }
+
+    /// Removes the plugin's tools from `registry`.
+    pub fn unregister(&self, registry: &ToolRegistry) {
+        for tool in &self.tools {
+            registry.unregister(&tool.name);
+        }
+    }
+
+    /// Runs the tool called `name`. This executes WASM on the calling thread;
+    /// async callers go through the [`ToolHandler`]s, which move it to a
+    /// blocking thread.
+    pub fn invoke(&self, name: &str, arguments: &Value) -> Result<Value, PluginError> {
+        let arguments = serde_json::to_vec(arguments)
+            .map_err(|e| PluginError::Abi(format!("unencodable arguments: {}", e)))?;
+        let mut call = self.instantiate()?;
+        let name = call.write(name.as_bytes())?;
+        let arguments = call.write(&arguments)?;
+        let invoke = call
+            .instance
+            .get_typed_func::<(i32, i32, i32, i32), i64>(&call.store, "invoke")
+            .map_err(|e| PluginError::Abi(format!("invoke: {}", e)))?;
+        let packed = invoke
+            .call(&mut call.store, (name.0, name.1, arguments.0, arguments.1))
+            .map_err(classify)?;
+
+        let mut response: Value = serde_json::from_slice(&call.read(packed)?)
+            .map_err(|e| PluginError::Abi(format!("invoke returned invalid JSON: {}", e)))?;
+        if let Some(message) = response.get("error") {
+            let message = match message {
+                Value::String(message) => message.clone(),
+                other => other.to_string(),
+            };
+            return Err(PluginError::Tool(message));
+        }
+        match response.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(PluginError::Abi(
+                "invoke must return {\"result\": ...} or {\"error\": ...}".to_string(),
+            )),
+        }
+    }
+
+    fn describe(&self) -> Result<Vec<Tool>, PluginError> {
+        let mut call = self.instantiate()?;
+        let tools = call
+            .instance
+            .get_typed_func::<(), i64>(&call.store, "tools")
+            .map_err(|e| PluginError::Abi(format!("tools: {}", e)))?;
+        let packed = tools.call(&mut call.store, ()).map_err(classify)?;
+        serde_json::from_slice(&call.read(packed)?)
+            .map_err(|e| PluginError::Abi(format!("tools returned invalid descriptors: {}", e)))
+    }
+
+    // A fresh, limited instance, so no call sees another's memory
+    fn instantiate(&self) -> Result<Call, PluginError> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.memory_bytes)
+            .instances(1)
+            .memories(1)
+            .tables(1)
+            .trap_on_grow_failure(true)
+            .build();
+        let mut store = Store::new(&self.engine, HostState { limits });
+        store.limiter(|state| &mut state.limits);
+        store
+            .set_fuel(self.limits.fuel)
+            .map_err(|e| PluginError::Abi(e.to_string()))?;
+
+        let instance = Linker::<HostState>::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .map_err(classify)?
+            .start(&mut store)
+            .map_err(classify)?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| PluginError::Abi("no exported memory".to_string()))?;
+        Ok(Call {
+            store,
+            instance,
+            memory,
+            max_output_bytes: self.limits.max_output_bytes,
+        })
+    }
+}
+
+// One instance of a plugin, alive for a single call
+struct Call {
+    store: Store<HostState>,
+    instance: Instance,
+    memory: Memory,
+    max_output_bytes: usize,
+}
+
+impl Call {
+    // Copies `bytes` into a buffer from the plugin's alloc; returns (ptr, len)
+    fn write(&mut self, bytes: &[u8]) -> Result<(i32, i32), PluginError> {
+        let len = i32::try_from(bytes.len())
+            .map_err(|_| PluginError::Abi("input too large".to_string()))?;
+        let alloc = self
+            .instance
+            .get_typed_func::<i32, i32>(&self.store, "alloc")
+            .map_err(|e| PluginError::Abi(format!("alloc: {}", e)))?;
+        let ptr = alloc.call(&mut self.store, len).map_err(classify)?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, bytes)
+            .map_err(|_| PluginError::Abi("alloc returned memory out of bounds".to_string()))?;
+        Ok((ptr, len))
+    }
+
+    // Reads the buffer a packed (ptr << 32 | len) result points at
+    fn read(&self, packed: i64) -> Result<Vec<u8>, PluginError> {
+        let packed = packed as u64;
+        let ptr = (packed >> 32) as usize;
+        let len = (packed & 0xffff_ffff) as usize;
+        if len > self.max_output_bytes {
+            return Err(PluginError::OutputTooLarge(self.max_output_bytes));
+        }
+        let mut buffer = vec![0; len];
+        self.memory
+            .read(&self.store, ptr, &mut buffer)
+            .map_err(|_| PluginError::Abi("result points out of bounds".to_string()))?;
+        Ok(buffer)
+    }
+}
+
+// Tells running out of fuel or memory apart from other traps
+fn classify(error: wasmi::Error) -> PluginError {
+    match error.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => PluginError::OutOfFuel,
+        Some(TrapCode::GrowthOperationLimited) => PluginError::MemoryLimit,
+        _ => PluginError::Wasm(error),
+    }
+}
+
+// One of a plugin's tools, as registered on a server
+struct PluginTool {
+    plugin: Arc<Plugin>,
+    tool: Tool,
+}
+
+impl ToolHandler for PluginTool {
+    fn tool(&self) -> Tool {
+        self.tool.clone()
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+        let plugin = self.plugin.clone();
+        let name = self.tool.name.clone();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || plugin.invoke(&name, &arguments))
+                .await
+                .map_err(|e| McpError::Internal(format!("Plugin call failed: {}", e)))?
+                .map_err(McpError::from)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolServer;
+    use serde_json::json;
+
+    const TOOLS: &str =
+        r#"[{"name":"echo","description":"Echoes its arguments","inputSchema":{"type":"object"}}]"#;
+
+    // A plugin whose `invoke` body is `invoke`. Its one page of memory holds
+    // the tool descriptors at 0, `{"result":` at 512 and allocations from 1024.
+    fn plugin(invoke: &str, limits: PluginLimits) -> Result<Plugin, PluginError> {
+        let escaped = TOOLS.replace('\\', "\\\\").replace('"', "\\\"");
+        let source = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 1024))
+                (data (i32.const 0) "{escaped}")
+                (data (i32.const 512) "{{\"result\":")
+                (func (export "alloc") (param $len i32) (result i32)
+                    (local $ptr i32)
+                    (local.set $ptr (global.get $next))
+                    (global.set $next (i32.add (global.get $next) (local.get $len)))
+                    (local.get $ptr))
+                (func (export "tools") (result i64)
+                    (i64.const {len}))
+                (func (export "invoke")
+                    (param $name i32) (param $name_len i32)
+                    (param $args i32) (param $args_len i32)
+                    (result i64)
+                    (local $out i32)
+                    {invoke}))"#,
+            len = TOOLS.len(),
+        );
+        Plugin::from_bytes("test", &wat::parse_str(source).unwrap(), limits)
+    }
+
+    // Copies {"result": from offset 512, then the arguments and a closing brace
+    const ECHO: &str = r#"
+        (local.set $out (i32.add (local.get $args) (local.get $args_len)))
+        (memory.copy (local.get $out) (i32.const 512) (i32.const 10))
+        (memory.copy
+            (i32.add (local.get $out) (i32.const 10))
+            (local.get $args)
+            (local.get $args_len))
+        (i32.store8
+            (i32.add (i32.add (local.get $out) (i32.const 10)) (local.get $args_len))
+            (i32.const 0x7d))
+        (i64.or
+            (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+            (i64.extend_i32_u (i32.add (local.get $args_len) (i32.const 11))))"#;
+
+    #[test]
+    fn test_loads_tools_and_invokes_them() {
+        let plugin = plugin(ECHO, PluginLimits::default()).unwrap();
+        assert_eq!(plugin.tools().len(), 1);
+        assert_eq!(plugin.tools()[0].name, "echo");
+        assert_eq!(plugin.tools()[0].input_schema, json!({ "type": "object" }));
+
+        let arguments = json!({ "text": "hi" });
+        assert_eq!(plugin.invoke("echo", &arguments).unwrap(), arguments);
+    }
+
+    #[test]
+    fn test_runaway_plugins_are_stopped() {
+        let limits = PluginLimits {
+            fuel: 100_000,
+            ..PluginLimits::default()
+        };
+        let spin = plugin("(loop $spin (br $spin)) (i64.const 0)", limits).unwrap();
+        let error = spin.invoke("echo", &json!({})).unwrap_err();
+        assert!(matches!(error, PluginError::OutOfFuel), "{}", error);
+        assert!(matches!(McpError::from(error), McpError::Unavailable(_)));
+
+        let limits = PluginLimits {
+            memory_bytes: 2 * 65536,
+            ..PluginLimits::default()
+        };
+        let hog = plugin("(drop (memory.grow (i32.const 8))) (i64.const 0)", limits).unwrap();
+        let error = hog.invoke("echo", &json!({})).unwrap_err();
+        assert!(matches!(error, PluginError::MemoryLimit), "{}", error);
+
+        let limits = PluginLimits {
+            max_output_bytes: 16,
+            ..PluginLimits::default()
+        };
+        let error = plugin(ECHO, limits).err().unwrap();
+        assert!(
+            matches!(error, PluginError::OutputTooLarge(16)),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_rejects_modules_without_the_interface() {
+        let bytes = wat::parse_str(r#"(module (memory (export "memory") 1))"#).unwrap();
+        let error = Plugin::from_bytes("empty", &bytes, PluginLimits::default())
+            .err()
+            .unwrap();
+        assert!(matches!(error, PluginError::Abi(_)), "{}", error);
+
+        let error = Plugin::from_bytes("garbage", b"not wasm", PluginLimits::default())
+            .err()
+            .unwrap();
+        assert!(matches!(error, PluginError::Wasm(_)), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_registers_tools_on_a_registry() {
+        let plugin = Arc::new(plugin(ECHO, PluginLimits::default()).unwrap());
+        let registry = ToolRegistry::new("plugins");
+        plugin.register(&registry);
+        assert!(registry.contains("echo"));
+
+        let result = registry.invoke_tool("echo", json!({ "n": 1 })).await;
+        assert_eq!(result, Ok(json!({ "n": 1 })));
+
+        plugin.unregister(&registry);
+        assert!(!registry.contains("echo"));
+    }
+}
@@ -0,0 +1,127 @@
+//! Telling the client how far a long-running request has got.
+//!
+//! A client that wants progress for a request puts a `progressToken` in the
+//! request's `_meta`, and the server may then send `notifications/progress`
+//! with that token until it responds. A tool reports its progress with
+//! [`report`], wherever it is in the handler:
+//!
+//! ```
+//! # async fn import(rows: &[&str]) {
+//! use mcp_rust_examples::progress;
+//!
+//! for (done, _row) in rows.iter().enumerate() {
+//!     // ... import the row ...
+//!     progress::report(done as f64 + 1.0, Some(rows.len() as f64), None);
+//! }
+//! # }
+//! ```
+//!
+//! Reports are dropped unless the client asked for them and the transport
+//! delivers them, so a tool reports the same way whoever calls it. The
+//! transport opens a [`channel`] per connection, runs each request in its
+//! [`scope`] and writes out the notifications the receiver yields;
+//! [`McpServer`](crate::server::McpServer) runs each `tools/call` under its
+//! token with [`track`].
+
+use crate::protocol::{JsonRpcRequest, ProgressParams};
+use serde_json::Value;
+use std::future::Future;
+use tokio::sync::mpsc;
+
+tokio::task_local! {
+    static SINK: ProgressSink;
+    static TOKEN: Value;
+}
+
+/// Where the progress notifications of a connection's requests go.
+#[derive(Debug, Clone)]
+pub struct ProgressSink(mpsc::UnboundedSender<JsonRpcRequest>);
+
+/// A sink for one connection, and the notifications sent to it in order.
+pub fn channel() -> (ProgressSink, mpsc::UnboundedReceiver<JsonRpcRequest>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (ProgressSink(sender), receiver)
+}
+
+/// Runs `future` with its progress going to `sink`. Transports wrap each
+/// request of a connection in this.
+pub async fn scope<F: Future>(sink: ProgressSink, future: F) -> F::Output {
+    SINK.scope(sink, future).await
+}
+
+/// Runs `future` with its progress reported under `token`, the
+/// `progressToken` of the request it handles, if it has one.
+pub async fn track<F: Future>(token: Option<Value>, future: F) -> F::Output {
+    match token {
+        Some(token) => TOKEN.scope(token, future).await,
+        None => future.await,
+    }
+}
+
+/// Whether a [`report`] would reach the client, for tools whose progress
+/// takes work to measure.
+pub fn is_requested() -> bool {
+    TOKEN.try_with(|_| ()).is_ok() && SINK.try_with(|_| ()).is_ok()
+}
+
+/// Sends `notifications/progress` for the request being handled: `progress`
+/// so far, out of `total` when it is known. `progress` has to increase from
+/// one report to the next.
+pub fn report(progress: f64, total: Option<f64>, message: Option<String>) {
+    let Ok(progress_token) = TOKEN.try_with(Value::clone) else {
+        return;
+    };
+    let params = ProgressParams {
+        progress_token,
+        progress,
+        total,
+        message,
+    };
+    let _ = SINK.try_with(|sink| {
+        // The connection may be gone; the request finds out soon enough
+        let _ = sink.0.send(JsonRpcRequest::notification(
+            "notifications/progress",
+            serde_json::json!(params),
+        ));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_reports_reach_the_sink_only_when_requested() {
+        let (sink, mut notifications) = channel();
+        scope(sink.clone(), async {
+            // No token: the client asked for no progress
+            track(None, async {
+                assert!(!is_requested());
+                report(1.0, None, None);
+            })
+            .await;
+            track(Some(json!("import-1")), async {
+                assert!(is_requested());
+                report(1000.0, None, Some("1000 rows".to_string()));
+                report(1500.0, Some(1500.0), None);
+            })
+            .await;
+        })
+        .await;
+        // Outside a transport's scope reports go nowhere
+        track(Some(json!(7)), async { report(1.0, None, None) }).await;
+        drop(sink);
+
+        let first = notifications.recv().await.unwrap();
+        assert_eq!(first.method, "notifications/progress");
+        assert_eq!(
+            first.params,
+            json!({ "progressToken": "import-1", "progress": 1000.0, "message": "1000 rows" })
+        );
+        let second: ProgressParams =
+            serde_json::from_value(notifications.recv().await.unwrap().params).unwrap();
+        assert_eq!((second.progress, second.total), (1500.0, Some(1500.0)));
+        assert!(notifications.recv().await.is_none());
+    }
+}
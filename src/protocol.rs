@@ -0,0 +1,585 @@
+//! JSON-RPC 2.0 messages and the MCP types carried in them.
+//!
+//! MCP runs on JSON-RPC 2.0: a client sends a [`JsonRpcRequest`] and gets a
+//! [`JsonRpcResponse`] with either a result or a [`JsonRpcError`]. Requests
+//! without an `id` are notifications and get no response.
+//!
+//! ```json
+//! {"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"greeting","arguments":{"name":"Ada"}}}
+//! {"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"..."}],"isError":false}}
+//! ```
+//!
+//! The examples used to define their own `Tool` struct each; they now all use
+//! [`Tool`] from here.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::McpError;
+use crate::output_format::OutputFormat;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// The MCP revision these examples implement.
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Standard JSON-RPC error codes, then the examples' own from the range
+/// JSON-RPC leaves to servers (see [`McpError`](crate::error::McpError)).
+pub mod error_codes {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+
+    pub const NOT_FOUND: i64 = -32002;
+    pub const PERMISSION_DENIED: i64 = -32003;
+    pub const RATE_LIMITED: i64 = -32004;
+    pub const CONFLICT: i64 = -32005;
+    pub const UNAVAILABLE: i64 = -32006;
+}
+
+/// A request ID, which JSON-RPC allows to be a number or a string.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(i64),
+    String(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<RequestId>,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+}
+
+impl JsonRpcRequest {
+    pub fn new(id: RequestId, method: &str, params: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: Some(id),
+            method: method.to_string(),
+            params,
+        }
+    }
+
+    pub fn notification(method: &str, params: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: None,
+            method: method.to_string(),
+            params,
+        }
+    }
+
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn parse_error(detail: impl std::fmt::Display) -> Self {
+        Self::new(error_codes::PARSE_ERROR, format!("Parse error: {}", detail))
+    }
+
+    pub fn invalid_request(detail: impl std::fmt::Display) -> Self {
+        Self::new(
+            error_codes::INVALID_REQUEST,
+            format!("Invalid request: {}", detail),
+        )
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(
+            error_codes::METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        )
+    }
+
+    pub fn invalid_params(detail: impl std::fmt::Display) -> Self {
+        Self::new(
+            error_codes::INVALID_PARAMS,
+            format!("Invalid params: {}", detail),
+        )
+    }
+}
+
+/// A response. `id` is null only when the request's ID could not be read.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: String,
+    pub id: Option<RequestId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    pub fn success(id: Option<RequestId>, result: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn failure(id: Option<RequestId>, error: JsonRpcError) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// A tool as advertised in `tools/list`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Tool {
+    pub name: String,
+    pub description: String,
+    #[serde(rename = "inputSchema", alias = "input_schema")]
+    pub input_schema: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ToolAnnotations>,
+}
+
+/// Hints about what a tool does, so clients can decide e.g. which calls need
+/// the user's confirmation. Unset hints take the MCP defaults: not read-only,
+/// destructive, not idempotent and open-world. They are only hints; a client
+/// should not trust them from a server it does not trust.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The tool does not modify its environment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    /// The tool may delete or overwrite data, rather than only add to it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    /// Repeating a call with the same arguments has no further effect.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    /// The tool talks to the outside world, e.g. the web.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
+}
+
+impl ToolAnnotations {
+    /// Only reads.
+    pub fn read_only() -> Self {
+        Self {
+            read_only_hint: Some(true),
+            ..Self::default()
+        }
+    }
+
+    /// Creates things but never deletes or overwrites them.
+    pub fn additive() -> Self {
+        Self {
+            read_only_hint: Some(false),
+            destructive_hint: Some(false),
+            ..Self::default()
+        }
+    }
+
+    /// May delete or overwrite data.
+    pub fn destructive() -> Self {
+        Self {
+            read_only_hint: Some(false),
+            destructive_hint: Some(true),
+            ..Self::default()
+        }
+    }
+
+    pub fn idempotent(mut self) -> Self {
+        self.idempotent_hint = Some(true);
+        self
+    }
+
+    pub fn open_world(mut self) -> Self {
+        self.open_world_hint = Some(true);
+        self
+    }
+
+    /// Whether a client should confirm a call with the user first: anything
+    /// that is not known to be read-only or purely additive.
+    pub fn needs_confirmation(&self) -> bool {
+        self.read_only_hint != Some(true) && self.destructive_hint != Some(false)
+    }
+}
+
+/// Parameters of list requests such as `tools/list`. The cursor is the
+/// `nextCursor` of the previous page; clients must treat it as opaque.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PaginatedParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// The result of `tools/list`. `nextCursor` is present while more pages remain.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ListToolsResult {
+    pub tools: Vec<Tool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// A resource as advertised in `resources/list`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Resource {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(
+        rename = "mimeType",
+        alias = "mime_type",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mime_type: Option<String>,
+}
+
+/// Parameters naming one resource: those of `resources/read`,
+/// `resources/subscribe` and `resources/unsubscribe`. Update notifications
+/// are built from a [`ResourceUpdate`](crate::resource_diff::ResourceUpdate).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResourceParams {
+    pub uri: String,
+}
+
+/// How severe a log message is, from `debug` up to `emergency` (the syslog
+/// levels), ordered so that more severe levels compare greater.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LoggingLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+/// Parameters of the `notifications/message` notification, a log message
+/// the server pushes to the client.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LoggingMessageParams {
+    pub level: LoggingLevel,
+    /// Which part of the server logged the message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logger: Option<String>,
+    pub data: Value,
+}
+
+/// Parameters of the `notifications/progress` notification: how far the
+/// request that asked for progress under `progress_token` has got.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressParams {
+    pub progress_token: Value,
+    /// Increases with every notification for the same token.
+    pub progress: f64,
+    /// What `progress` counts up to, when that is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Parameters of `logging/setLevel`: the least severe level the client
+/// wants to receive.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SetLevelParams {
+    pub level: LoggingLevel,
+}
+
+/// Parameters of `tools/call`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CallToolParams {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+/// One piece of a tool result. The examples only return text.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Content {
+    Text { text: String },
+}
+
+/// The result of `tools/call`. A tool that fails still produces a result,
+/// with `isError` set, so the model can see what went wrong; JSON-RPC errors
+/// are reserved for protocol problems such as an unknown method.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CallToolResult {
+    pub content: Vec<Content>,
+    #[serde(rename = "isError", default)]
+    pub is_error: bool,
+    /// For errors, the code and kind of the [`McpError`]; for list results
+    /// written as NDJSON, the fields around the list.
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Value>,
+}
+
+impl CallToolResult {
+    pub fn from_result(result: Result<Value, McpError>) -> Self {
+        Self::from_result_as(result, OutputFormat::Compact)
+    }
+
+    /// Like [`from_result`](Self::from_result), with the value written in
+    /// `format`. Errors are plain text in every format.
+    pub fn from_result_as(result: Result<Value, McpError>, format: OutputFormat) -> Self {
+        match result {
+            Ok(value) => {
+                let rendered = format.render(&value);
+                Self {
+                    content: rendered
+                        .blocks
+                        .into_iter()
+                        .map(|text| Content::Text { text })
+                        .collect(),
+                    is_error: false,
+                    meta: rendered.meta,
+                }
+            }
+            Err(error) => Self {
+                content: vec![Content::Text {
+                    text: error.to_string(),
+                }],
+                is_error: true,
+                meta: Some(error.meta()),
+            },
+        }
+    }
+
+    /// The text of the result, or the error it reports.
+    pub fn into_result(self) -> Result<String, McpError> {
+        let text = self
+            .content
+            .into_iter()
+            .map(|content| match content {
+                Content::Text { text } => text,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if self.is_error {
+            Err(McpError::from_meta(text, self.meta.as_ref()))
+        } else {
+            Ok(text)
+        }
+    }
+}
+
+/// At most this many values are returned by `completion/complete`.
+pub const MAX_COMPLETION_VALUES: usize = 100;
+
+/// What a `completion/complete` request completes an argument of.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum CompletionReference {
+    #[serde(rename = "ref/prompt")]
+    Prompt { name: String },
+    /// A resource template such as `document://{id}`.
+    #[serde(rename = "ref/resource")]
+    Resource { uri: String },
+    /// Not part of MCP, which only completes prompt and resource arguments;
+    /// the examples also complete tool arguments this way.
+    #[serde(rename = "ref/tool")]
+    Tool { name: String },
+}
+
+/// The argument being completed and what the user has typed so far.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompletionArgument {
+    pub name: String,
+    pub value: String,
+}
+
+/// Parameters of `completion/complete`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompleteParams {
+    #[serde(rename = "ref")]
+    pub reference: CompletionReference,
+    pub argument: CompletionArgument,
+    #[serde(default)]
+    pub context: CompletionContext,
+}
+
+impl CompleteParams {
+    /// The value of another, already filled in argument.
+    pub fn context_argument(&self, name: &str) -> Option<&str> {
+        self.context.arguments.get(name).map(String::as_str)
+    }
+}
+
+/// Arguments the user has already filled in, e.g. the collection when
+/// completing a document id within it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CompletionContext {
+    #[serde(default)]
+    pub arguments: std::collections::HashMap<String, String>,
+}
+
+/// The `completion` member of a `completion/complete` result.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Completion {
+    pub values: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<usize>,
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+impl Completion {
+    /// Keeps the first [`MAX_COMPLETION_VALUES`] values and reports how many
+    /// there were in total.
+    pub fn from_values(mut values: Vec<String>) -> Self {
+        let total = values.len();
+        values.truncate(MAX_COMPLETION_VALUES);
+        Self {
+            has_more: total > values.len(),
+            total: Some(total),
+            values,
+        }
+    }
+}
+
+/// Name and version reported in the `initialize` response.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServerInfo {
+    pub name: String,
+    pub version: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_round_trip() {
+        let request: JsonRpcRequest = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","id":"abc","method":"tools/call","params":{"name":"greeting"}}"#,
+        )
+        .unwrap();
+        assert_eq!(request.id, Some(RequestId::String("abc".to_string())));
+        assert!(!request.is_notification());
+
+        let params: CallToolParams = serde_json::from_value(request.params).unwrap();
+        assert_eq!(params.name, "greeting");
+        assert_eq!(params.arguments, Value::Null);
+
+        let notification = JsonRpcRequest::notification("notifications/initialized", Value::Null);
+        assert_eq!(
+            serde_json::to_string(&notification).unwrap(),
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#
+        );
+    }
+
+    #[test]
+    fn test_response_and_tool_shapes() {
+        let response = JsonRpcResponse::failure(None, JsonRpcError::method_not_found("nope"));
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["id"], Value::Null);
+        assert_eq!(json["error"]["code"], error_codes::METHOD_NOT_FOUND);
+        assert!(json.get("result").is_none());
+
+        // Tools use the MCP field name but still accept the old one
+        let tool: Tool = serde_json::from_value(serde_json::json!({
+            "name": "echo",
+            "description": "Echo",
+            "input_schema": { "type": "object" }
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&tool).unwrap()["inputSchema"]["type"],
+            "object"
+        );
+        assert!(tool.annotations.is_none());
+
+        let tool = Tool {
+            annotations: Some(ToolAnnotations::destructive().idempotent()),
+            ..tool
+        };
+        assert_eq!(
+            serde_json::to_value(&tool).unwrap()["annotations"],
+            serde_json::json!({
+                "readOnlyHint": false,
+                "destructiveHint": true,
+                "idempotentHint": true
+            })
+        );
+        assert!(tool.annotations.unwrap().needs_confirmation());
+        assert!(!ToolAnnotations::read_only().needs_confirmation());
+        assert!(!ToolAnnotations::additive().needs_confirmation());
+        assert!(ToolAnnotations::default().needs_confirmation());
+
+        let result = CallToolResult::from_result(Err(McpError::NotFound("boom".to_string())));
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            serde_json::json!({
+                "content": [{ "type": "text", "text": "boom" }],
+                "isError": true,
+                "_meta": { "error": { "code": error_codes::NOT_FOUND, "kind": "not_found" } }
+            })
+        );
+        assert_eq!(
+            result.into_result(),
+            Err(McpError::NotFound("boom".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_completion_shapes() {
+        let params: CompleteParams = serde_json::from_value(serde_json::json!({
+            "ref": { "type": "ref/resource", "uri": "document://{collection}/{id}" },
+            "argument": { "name": "id", "value": "do" },
+            "context": { "arguments": { "collection": "research" } }
+        }))
+        .unwrap();
+        assert_eq!(
+            params.reference,
+            CompletionReference::Resource {
+                uri: "document://{collection}/{id}".to_string()
+            }
+        );
+        assert_eq!(params.context_argument("collection"), Some("research"));
+
+        // Results are capped, with the real total reported
+        let completion = Completion::from_values((0..150).map(|i| i.to_string()).collect());
+        assert_eq!(completion.values.len(), MAX_COMPLETION_VALUES);
+        assert_eq!(completion.total, Some(150));
+        assert!(completion.has_more);
+    }
+}
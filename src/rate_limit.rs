@@ -0,0 +1,479 @@
+//! Token-bucket rate limiting for tool calls.
+//!
+//! [`RateLimiter`] is a [`Middleware`] for [`McpServer`]: every `tools/call`
+//! takes a token from the bucket of the calling client and the tool. Buckets
+//! hold up to `capacity` tokens and refill at `refill_per_second`, so a client
+//! can burst `capacity` calls and then keep up the refill rate. A call that
+//! finds its bucket empty is answered with an `isError` result carrying
+//! [`McpError::RateLimited`], without reaching the tool.
+//!
+//! Every `tools/call` result then reports the bucket in its `_meta`, like the
+//! `X-RateLimit-*` headers of an HTTP API:
+//!
+//! ```json
+//! {"_meta": {"rateLimit": {"limit": 10, "remaining": 7, "resetSeconds": 3}}}
+//! ```
+//!
+//! The limiter also offers a `rate_limit_status` tool reporting the caller's
+//! buckets. Clients are told apart by the name they give in `initialize`;
+//! calls from outside a session share one bucket per tool.
+//!
+//! [`McpServer`]: crate::server::McpServer
+
+use crate::capabilities;
+use crate::error::McpError;
+use crate::middleware::Middleware;
+use crate::protocol::{CallToolResult, JsonRpcRequest, JsonRpcResponse, Tool, ToolAnnotations};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+pub const TOOL_NAME: &str = "rate_limit_status";
+
+// The client name of calls made outside a session
+const ANONYMOUS_CLIENT: &str = "anonymous";
+
+// Past this many buckets, full ones are dropped: a full bucket is the same as
+// no bucket, and unknown tool names must not grow the map forever
+const MAX_BUCKETS: usize = 1024;
+
+/// Size and refill rate of one bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BucketConfig {
+    /// Calls that can be made in a burst.
+    pub capacity: u32,
+    pub refill_per_second: f64,
+}
+
+impl Default for BucketConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 60,
+            refill_per_second: 1.0,
+        }
+    }
+}
+
+/// Limits for every tool, with overrides for some.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub default: BucketConfig,
+    #[serde(default)]
+    pub tools: HashMap<String, BucketConfig>,
+}
+
+impl RateLimitConfig {
+    pub fn for_tool(&self, tool: &str) -> BucketConfig {
+        self.tools.get(tool).copied().unwrap_or(self.default)
+    }
+}
+
+/// The state of one bucket after a call, or when asked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Until the bucket is full again.
+    pub reset_after: Duration,
+    /// Until the next call would be allowed; zero if one is now.
+    pub retry_after: Duration,
+}
+
+impl RateLimitDecision {
+    /// The `_meta.rateLimit` object of a result.
+    pub fn meta(&self) -> Value {
+        json!({
+            "limit": self.limit,
+            "remaining": self.remaining,
+            "resetSeconds": self.reset_after.as_secs_f64().ceil() as u64,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, config: BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * config.refill_per_second).min(config.capacity as f64);
+        self.updated = now;
+    }
+
+    fn decision(&self, config: BucketConfig, allowed: bool) -> RateLimitDecision {
+        let seconds_until = |tokens: f64| {
+            if tokens <= self.tokens || config.refill_per_second <= 0.0 {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64((tokens - self.tokens) / config.refill_per_second)
+            }
+        };
+        RateLimitDecision {
+            allowed,
+            limit: config.capacity,
+            remaining: self.tokens.floor() as u32,
+            reset_after: seconds_until(config.capacity as f64),
+            retry_after: seconds_until(1.0),
+        }
+    }
+}
+
+/// Rate limits tool calls per client and tool. Without a configuration
+/// nothing is limited.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    config: RwLock<Option<RateLimitConfig>>,
+    buckets: Mutex<HashMap<(String, String), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: Option<RateLimitConfig>) -> Self {
+        Self {
+            config: RwLock::new(config),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Swaps in new limits. Buckets keep their tokens, up to the new capacity.
+    pub fn set_config(&self, config: Option<RateLimitConfig>) {
+        *self.config.write().unwrap() = config;
+    }
+
+    pub fn config(&self) -> Option<RateLimitConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Takes a token for a call of `tool` by `client`, if one is left.
+    /// `None` when nothing is limited.
+    pub fn check(&self, client: &str, tool: &str) -> Option<RateLimitDecision> {
+        self.bucket(client, tool, true)
+    }
+
+    /// The bucket of `client` and `tool`, without taking a token.
+    pub fn peek(&self, client: &str, tool: &str) -> Option<RateLimitDecision> {
+        self.bucket(client, tool, false)
+    }
+
+    fn bucket(&self, client: &str, tool: &str, take: bool) -> Option<RateLimitDecision> {
+        let config = self.config.read().unwrap().as_ref()?.for_tool(tool);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if take && buckets.len() >= MAX_BUCKETS {
+            let limits = self.config.read().unwrap().clone().unwrap_or_default();
+            buckets.retain(|(_, tool), bucket| {
+                let config = limits.for_tool(tool);
+                bucket.refill(config, now);
+                bucket.tokens < config.capacity as f64
+            });
+        }
+
+        let full = Bucket {
+            tokens: config.capacity as f64,
+            updated: now,
+        };
+        let key = (client.to_string(), tool.to_string());
+        if !take {
+            let mut bucket = buckets.get(&key).copied().unwrap_or(full);
+            bucket.refill(config, now);
+            return Some(bucket.decision(config, bucket.tokens >= 1.0));
+        }
+
+        let bucket = buckets.entry(key).or_insert(full);
+        bucket.refill(config, now);
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        Some(bucket.decision(config, allowed))
+    }
+
+    /// The `rate_limit_status` report for `client`: every tool it has called,
+    /// or just `tool`.
+    pub fn status(&self, client: &str, tool: Option<&str>) -> Value {
+        let Some(config) = self.config() else {
+            return json!({ "client": client, "limited": false });
+        };
+        let mut tools: Vec<String> = match tool {
+            Some(tool) => vec![tool.to_string()],
+            None => self
+                .buckets
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|(owner, _)| owner == client)
+                .map(|(_, tool)| tool.clone())
+                .collect(),
+        };
+        tools.sort();
+        let tools: Vec<Value> = tools
+            .iter()
+            .filter_map(|tool| {
+                let mut status = self.peek(client, tool)?.meta();
+                status["tool"] = json!(tool);
+                Some(status)
+            })
+            .collect();
+
+        json!({
+            "client": client,
+            "limited": true,
+            "default": config.default,
+            "overrides": config.tools,
+            "tools": tools,
+        })
+    }
+}
+
+/// The `rate_limit_status` tool's description.
+pub fn tool() -> Tool {
+    Tool {
+        name: TOOL_NAME.to_string(),
+        description: "Report your remaining tool call budget: limit, calls left and seconds \
+                      until the limit resets, per tool"
+            .to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "tool": {
+                    "type": "string",
+                    "description": "Only report this tool"
+                }
+            }
+        }),
+        annotations: Some(ToolAnnotations::read_only()),
+    }
+}
+
+// The connected client's name from `initialize`
+fn current_client() -> String {
+    capabilities::current()
+        .and_then(|client| client.client_name.clone())
+        .unwrap_or_else(|| ANONYMOUS_CLIENT.to_string())
+}
+
+// The tool a `tools/call` request is for
+fn called_tool(request: &JsonRpcRequest) -> Option<&str> {
+    (request.method == "tools/call")
+        .then(|| request.params["name"].as_str())
+        .flatten()
+}
+
+fn call_result(result: Result<Value, McpError>) -> Value {
+    serde_json::to_value(CallToolResult::from_result(result)).unwrap_or(Value::Null)
+}
+
+impl Middleware<JsonRpcRequest, JsonRpcResponse> for RateLimiter {
+    // Answers rate_limit_status itself, and empty buckets with an error
+    fn on_request<'a>(
+        &'a self,
+        request: &'a mut JsonRpcRequest,
+    ) -> BoxFuture<'a, Option<JsonRpcResponse>> {
+        Box::pin(async move {
+            let tool = called_tool(request)?;
+            let client = current_client();
+            if tool == TOOL_NAME {
+                let only = request.params["arguments"]["tool"].as_str();
+                let result = call_result(Ok(self.status(&client, only)));
+                return Some(JsonRpcResponse::success(request.id.clone(), result));
+            }
+
+            let decision = self.check(&client, tool)?;
+            if decision.allowed {
+                return None;
+            }
+            tracing::info!(client, tool, "Tool call rate limited");
+            let error = McpError::RateLimited {
+                message: format!(
+                    "Rate limit exceeded for {}: {} calls allowed, retry in {}s",
+                    tool,
+                    decision.limit,
+                    decision.retry_after.as_secs_f64().ceil()
+                ),
+                retry_after: Some(decision.retry_after),
+            };
+            Some(JsonRpcResponse::success(
+                request.id.clone(),
+                call_result(Err(error)),
+            ))
+        })
+    }
+
+    // Reports the bucket on tool call results, and lists rate_limit_status
+    // on the last page of tools/list
+    fn on_response<'a>(
+        &'a self,
+        request: &'a JsonRpcRequest,
+        response: &'a mut JsonRpcResponse,
+        _elapsed: Duration,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let Some(result) = response.result.as_mut() else {
+                return;
+            };
+            if request.method == "tools/list" && result.get("nextCursor").is_none() {
+                if let Some(tools) = result["tools"].as_array_mut() {
+                    if !tools.iter().any(|tool| tool["name"] == TOOL_NAME) {
+                        tools.push(json!(tool()));
+                    }
+                }
+                return;
+            }
+
+            let Some(tool) = called_tool(request).filter(|&tool| tool != TOOL_NAME) else {
+                return;
+            };
+            if let Some(decision) = self.peek(&current_client(), tool) {
+                result["_meta"]["rateLimit"] = decision.meta();
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(capacity: u32) -> RateLimiter {
+        let mut config = RateLimitConfig {
+            default: BucketConfig {
+                capacity,
+                refill_per_second: 0.001,
+            },
+            tools: HashMap::new(),
+        };
+        config.tools.insert(
+            "search".to_string(),
+            BucketConfig {
+                capacity: 1,
+                refill_per_second: 0.001,
+            },
+        );
+        RateLimiter::new(Some(config))
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = limiter(2);
+
+        let first = limiter.check("alice", "echo").unwrap();
+        assert!(first.allowed);
+        assert_eq!((first.limit, first.remaining), (2, 1));
+        assert!(limiter.check("alice", "echo").unwrap().allowed);
+        let denied = limiter.check("alice", "echo").unwrap();
+        assert!(!denied.allowed);
+        assert!(denied.retry_after > Duration::from_secs(900));
+
+        // Buckets are per client and per tool, with per-tool overrides
+        assert!(limiter.check("bob", "echo").unwrap().allowed);
+        assert!(limiter.check("alice", "search").unwrap().allowed);
+        assert!(!limiter.check("alice", "search").unwrap().allowed);
+
+        // Peeking takes nothing
+        assert_eq!(limiter.peek("carol", "echo").unwrap().remaining, 2);
+        assert_eq!(limiter.peek("carol", "echo").unwrap().remaining, 2);
+
+        limiter.set_config(None);
+        assert_eq!(limiter.check("alice", "echo"), None);
+    }
+
+    #[test]
+    fn test_status() {
+        let limiter = limiter(5);
+        limiter.check("alice", "echo");
+        limiter.check("bob", "search");
+
+        let status = limiter.status("alice", None);
+        assert_eq!(status["limited"], true);
+        assert_eq!(status["tools"].as_array().unwrap().len(), 1);
+        assert_eq!(status["tools"][0]["tool"], "echo");
+        assert_eq!(status["tools"][0]["remaining"], 4);
+
+        let status = limiter.status("alice", Some("search"));
+        assert_eq!(status["tools"][0]["remaining"], 1);
+
+        assert_eq!(
+            RateLimiter::default().status("alice", None)["limited"],
+            false
+        );
+    }
+
+    #[tokio::test]
+    async fn test_middleware() {
+        use crate::protocol::error_codes;
+        use crate::server::{McpServer, ToolHandler, ToolRouter};
+        use std::sync::Arc;
+
+        struct Ping;
+
+        impl ToolHandler for Ping {
+            fn tool(&self) -> Tool {
+                Tool {
+                    name: "ping".to_string(),
+                    description: "Answers pong".to_string(),
+                    input_schema: json!({ "type": "object" }),
+                    annotations: None,
+                }
+            }
+
+            fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+                Box::pin(async { Ok(json!("pong")) })
+            }
+        }
+
+        let server = McpServer::new(Arc::new(ToolRouter::new("limited").with_handler(Ping)))
+            .with_middleware(Arc::new(limiter(1)));
+        let call = |name: &str| {
+            format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{{"name":"{}"}}}}"#,
+                name
+            )
+        };
+
+        let response = server.handle_message(&call("ping")).await.unwrap();
+        let result = response.result.unwrap();
+        assert_eq!(result["isError"], false);
+        assert_eq!(result["_meta"]["rateLimit"]["limit"], 1);
+        assert_eq!(result["_meta"]["rateLimit"]["remaining"], 0);
+
+        let response = server.handle_message(&call("ping")).await.unwrap();
+        let result = response.result.unwrap();
+        assert_eq!(result["isError"], true);
+        assert_eq!(result["_meta"]["error"]["code"], error_codes::RATE_LIMITED);
+        assert!(
+            result["_meta"]["error"]["retryAfterSeconds"]
+                .as_u64()
+                .unwrap()
+                > 0
+        );
+        assert_eq!(result["_meta"]["rateLimit"]["remaining"], 0);
+
+        // The status tool is listed and never limited
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#)
+            .await
+            .unwrap();
+        let tools = response.result.unwrap()["tools"].clone();
+        assert!(tools
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|t| t["name"] == TOOL_NAME));
+        for _ in 0..3 {
+            let response = server.handle_message(&call(TOOL_NAME)).await.unwrap();
+            let result: CallToolResult = serde_json::from_value(response.result.unwrap()).unwrap();
+            let status: Value = serde_json::from_str(&result.into_result().unwrap()).unwrap();
+            assert_eq!(status["client"], ANONYMOUS_CLIENT);
+            assert_eq!(status["tools"][0]["tool"], "ping");
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! A tool set that changes while the server runs.
+//!
+//! [`ToolRouter`] fixes its tools when it is built. [`ToolRegistry`] lets a
+//! server register and unregister tools at any time, for example after its
+//! configuration is reloaded or a backend appears, and announces every change
+//! so the transport can send `notifications/tools/list_changed` and clients
+//! know to call `tools/list` again.
+//!
+//! [`ToolRouter`]: crate::server::ToolRouter
+
+use crate::error::McpError;
+use crate::logging::ToolCallLog;
+use crate::protocol::Tool;
+use crate::server::ToolHandler;
+use crate::tools::ToolServer;
+use futures::future::BoxFuture;
+use serde_json::Value;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+// Changes carry no data, so a small buffer is plenty: a client that lags
+// behind still learns that the list changed.
+const CHANGE_BUFFER: usize = 16;
+
+/// A [`ToolServer`] whose tools can be registered and unregistered at runtime.
+/// Calls are logged with [`ToolCallLog`], like [`ToolRouter`]'s.
+///
+/// [`ToolRouter`]: crate::server::ToolRouter
+pub struct ToolRegistry {
+    name: String,
+    handlers: RwLock<Vec<Arc<dyn ToolHandler>>>,
+    changes: broadcast::Sender<()>,
+}
+
+impl ToolRegistry {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            handlers: RwLock::new(Vec::new()),
+            changes: broadcast::channel(CHANGE_BUFFER).0,
+        }
+    }
+
+    /// Adds `handler`'s tool, replacing any tool with the same name. Returns
+    /// true if a tool was replaced.
+    pub fn register(&self, handler: impl ToolHandler + 'static) -> bool {
+        let tool = handler.tool();
+        let mut handlers = self.handlers.write().unwrap();
+        let existing = handlers
+            .iter()
+            .position(|handler| handler.tool().name == tool.name);
+        let changed = match existing {
+            Some(index) => {
+                let changed = handlers[index].tool() != tool;
+                handlers[index] = Arc::new(handler);
+                changed
+            }
+            None => {
+                handlers.push(Arc::new(handler));
+                true
+            }
+        };
+        drop(handlers);
+
+        if changed {
+            tracing::info!(registry = %self.name, tool = %tool.name, "Tool registered");
+            self.announce_change();
+        }
+        existing.is_some()
+    }
+
+    /// Removes the tool called `name`. Returns false if there was none.
+    pub fn unregister(&self, name: &str) -> bool {
+        let mut handlers = self.handlers.write().unwrap();
+        let count = handlers.len();
+        handlers.retain(|handler| handler.tool().name != name);
+        let removed = handlers.len() < count;
+        drop(handlers);
+
+        if removed {
+            tracing::info!(registry = %self.name, tool = %name, "Tool unregistered");
+            self.announce_change();
+        }
+        removed
+    }
+
+    /// Replaces every tool at once, e.g. after a configuration reload. The
+    /// change is announced only if the tool list actually differs.
+    pub fn replace_all(&self, handlers: impl IntoIterator<Item = Box<dyn ToolHandler>>) {
+        let handlers: Vec<Arc<dyn ToolHandler>> = handlers.into_iter().map(Arc::from).collect();
+        let tools: Vec<Tool> = handlers.iter().map(|handler| handler.tool()).collect();
+        let mut current = self.handlers.write().unwrap();
+        let changed = !current
+            .iter()
+            .map(|handler| handler.tool())
+            .eq(tools.iter().cloned());
+        *current = handlers;
+        drop(current);
+
+        if changed {
+            tracing::info!(registry = %self.name, tools = tools.len(), "Tools replaced");
+            self.announce_change();
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.handler(name).is_some()
+    }
+
+    fn handler(&self, name: &str) -> Option<Arc<dyn ToolHandler>> {
+        self.handlers
+            .read()
+            .unwrap()
+            .iter()
+            .find(|handler| handler.tool().name == name)
+            .cloned()
+    }
+
+    // Nobody listening is fine: clients connected later list the tools anyway
+    fn announce_change(&self) {
+        let _ = self.changes.send(());
+    }
+}
+
+impl ToolServer for ToolRegistry {
+    fn server_name(&self) -> &str {
+        &self.name
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.handlers
+            .read()
+            .unwrap()
+            .iter()
+            .map(|handler| handler.tool())
+            .collect()
+    }
+
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(ToolCallLog::run_async(
+            name,
+            arguments,
+            move |arguments| async move {
+                // The handler is cloned out so the lock is not held across the
+                // call, and a concurrent unregister does not cancel it
+                match self.handler(name) {
+                    Some(handler) => handler.call(arguments).await,
+                    None => Err(McpError::NotFound(format!("Unknown tool: {}", name))),
+                }
+            },
+        ))
+    }
+
+    fn tool_list_changes(&self) -> Option<broadcast::Receiver<()>> {
+        Some(self.changes.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    struct Constant {
+        name: &'static str,
+        description: &'static str,
+    }
+
+    impl ToolHandler for Constant {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: self.name.to_string(),
+                description: self.description.to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async move { Ok(serde_json::json!(self.description)) })
+        }
+    }
+
+    fn constant(name: &'static str, description: &'static str) -> Constant {
+        Constant { name, description }
+    }
+
+    #[tokio::test]
+    async fn test_register_and_unregister() {
+        let registry = ToolRegistry::new("dynamic");
+        let mut changes = registry.tool_list_changes().unwrap();
+
+        assert!(!registry.register(constant("a", "first")));
+        assert_eq!(changes.try_recv(), Ok(()));
+        let result = registry.invoke_tool("a", Value::Null).await;
+        assert_eq!(result, Ok(serde_json::json!("first")));
+
+        // Swapping the implementation behind an identical tool is no change
+        assert!(registry.register(constant("a", "first")));
+        assert_eq!(changes.try_recv(), Err(TryRecvError::Empty));
+        assert!(registry.register(constant("a", "second")));
+        assert_eq!(changes.try_recv(), Ok(()));
+        let result = registry.invoke_tool("a", Value::Null).await;
+        assert_eq!(result, Ok(serde_json::json!("second")));
+
+        assert!(registry.unregister("a"));
+        assert!(!registry.unregister("a"));
+        assert_eq!(changes.try_recv(), Ok(()));
+        assert_eq!(changes.try_recv(), Err(TryRecvError::Empty));
+        assert!(registry.tool_descriptors().is_empty());
+        assert!(registry.invoke_tool("a", Value::Null).await.is_err());
+    }
+
+    #[test]
+    fn test_replace_all_announces_only_real_changes() {
+        let registry = ToolRegistry::new("dynamic");
+        let mut changes = registry.tool_list_changes().unwrap();
+        let tools = || -> Vec<Box<dyn ToolHandler>> {
+            vec![
+                Box::new(constant("a", "first")),
+                Box::new(constant("b", "second")),
+            ]
+        };
+
+        registry.replace_all(tools());
+        assert_eq!(changes.try_recv(), Ok(()));
+        registry.replace_all(tools());
+        assert_eq!(changes.try_recv(), Err(TryRecvError::Empty));
+
+        registry.replace_all(vec![
+            Box::new(constant("b", "second")) as Box<dyn ToolHandler>
+        ]);
+        assert_eq!(changes.try_recv(), Ok(()));
+        assert!(!registry.contains("a"));
+        assert!(registry.contains("b"));
+    }
+}
@@ -0,0 +1,350 @@
+//! What changed in a resource, sent along with its update notification.
+//!
+//! A bare `notifications/resources/updated` only names the resource, so a
+//! subscriber re-reads all of it to find out what happened. Providers that
+//! know the old and new text attach a [`ResourceChange`] to the
+//! [`ResourceUpdate`] instead, and the transports send it in the
+//! notification's `_meta`:
+//!
+//! ```json
+//! {"jsonrpc":"2.0","method":"notifications/resources/updated",
+//!  "params":{"uri":"document://doc1",
+//!   "_meta":{"change":{"kind":"lines","line":3,"removed":1,"added":["New third line\n"]}}}}
+//! ```
+//!
+//! A `lines` change replaces `removed` lines, starting at the 1-based
+//! `line`, with the `added` ones (each keeping its line break), which
+//! [`ResourceChange::apply`] does for a client holding the old text. When
+//! that would take more than [`DiffConfig::max_bytes`], only the changed
+//! byte `range` is sent and the subscriber reads the resource as before.
+//!
+//! How much is sent is configured with [`DIFF_MODE_ENV`] (`lines`, the
+//! default, `range` or `off`) and [`DIFF_MAX_BYTES_ENV`].
+//!
+//! ```
+//! # use mcp_rust_examples::resource_diff::{DiffConfig, ResourceChange};
+//! let old = "one\ntwo\nthree\n";
+//! let new = "one\n2\nthree\n";
+//! let change = DiffConfig::default().change(old, new).unwrap();
+//! assert_eq!(change.apply(old).as_deref(), Some(new));
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// How resource changes are described: `lines`, `range` or `off`.
+pub const DIFF_MODE_ENV: &str = "MCP_RESOURCE_DIFF";
+
+/// The largest line diff, in bytes of JSON, sent with a notification.
+pub const DIFF_MAX_BYTES_ENV: &str = "MCP_RESOURCE_DIFF_MAX_BYTES";
+
+/// Used when [`DIFF_MAX_BYTES_ENV`] is unset.
+pub const DEFAULT_DIFF_MAX_BYTES: usize = 4096;
+
+/// How a resource's content changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ResourceChange {
+    /// `removed` lines starting at `line` were replaced by `added`.
+    Lines {
+        line: usize,
+        removed: usize,
+        added: Vec<String>,
+    },
+    /// Bytes `start..old_end` of the old content became `start..new_end` of
+    /// the new one; read the resource for the rest.
+    Range {
+        start: usize,
+        old_end: usize,
+        new_end: usize,
+    },
+    /// The resource no longer exists.
+    Deleted,
+}
+
+impl ResourceChange {
+    /// The single run of lines that differs between `old` and `new`.
+    pub fn lines(old: &str, new: &str) -> Self {
+        let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+        let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+        let prefix = common_length(old_lines.iter(), new_lines.iter());
+        let suffix = common_length(
+            old_lines[prefix..].iter().rev(),
+            new_lines[prefix..].iter().rev(),
+        );
+        Self::Lines {
+            line: prefix + 1,
+            removed: old_lines.len() - prefix - suffix,
+            added: new_lines[prefix..new_lines.len() - suffix]
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+        }
+    }
+
+    /// The bytes that differ between `old` and `new`, widened to whole
+    /// characters.
+    pub fn range(old: &str, new: &str) -> Self {
+        let (old_bytes, new_bytes) = (old.as_bytes(), new.as_bytes());
+        let mut start = common_length(old_bytes.iter(), new_bytes.iter());
+        while !old.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut suffix = common_length(
+            old_bytes[start..].iter().rev(),
+            new_bytes[start..].iter().rev(),
+        );
+        while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix)
+        {
+            suffix -= 1;
+        }
+        Self::Range {
+            start,
+            old_end: old.len() - suffix,
+            new_end: new.len() - suffix,
+        }
+    }
+
+    /// Applies a `lines` change to the content it was made from; `None` for
+    /// other changes, or content it does not fit.
+    pub fn apply(&self, old: &str) -> Option<String> {
+        let Self::Lines {
+            line,
+            removed,
+            added,
+        } = self
+        else {
+            return None;
+        };
+        let lines: Vec<&str> = old.split_inclusive('\n').collect();
+        let start = line.checked_sub(1)?;
+        let end = start.checked_add(*removed)?;
+        if end > lines.len() {
+            return None;
+        }
+        let mut new = lines[..start].concat();
+        new.extend(added.iter().map(String::as_str));
+        new.push_str(&lines[end..].concat());
+        Some(new)
+    }
+}
+
+fn common_length<T: PartialEq>(a: impl Iterator<Item = T>, b: impl Iterator<Item = T>) -> usize {
+    a.zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// How much of a change to describe.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffMode {
+    /// Changed lines, or the range when they are too large.
+    #[default]
+    Lines,
+    /// Only the changed byte range.
+    Range,
+    /// Nothing; subscribers re-read the resource.
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffConfig {
+    pub mode: DiffMode,
+    /// Line diffs larger than this, serialized, are sent as a range.
+    pub max_bytes: usize,
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        Self {
+            mode: DiffMode::default(),
+            max_bytes: DEFAULT_DIFF_MAX_BYTES,
+        }
+    }
+}
+
+impl DiffConfig {
+    /// Reads [`DIFF_MODE_ENV`] and [`DIFF_MAX_BYTES_ENV`]; values that don't
+    /// parse fall back to the defaults with a warning.
+    pub fn from_env() -> Self {
+        Self::from_vars(
+            std::env::var(DIFF_MODE_ENV).ok(),
+            std::env::var(DIFF_MAX_BYTES_ENV).ok(),
+        )
+    }
+
+    fn from_vars(mode: Option<String>, max_bytes: Option<String>) -> Self {
+        let mut config = Self::default();
+        if let Some(mode) = mode {
+            match serde_json::from_value(serde_json::Value::String(mode.trim().to_lowercase())) {
+                Ok(mode) => config.mode = mode,
+                Err(_) => tracing::warn!(
+                    "{} must be lines, range or off, not '{}'",
+                    DIFF_MODE_ENV,
+                    mode
+                ),
+            }
+        }
+        if let Some(max_bytes) = max_bytes {
+            match max_bytes.trim().parse() {
+                Ok(max_bytes) => config.max_bytes = max_bytes,
+                Err(_) => tracing::warn!(
+                    "{} must be a number of bytes, not '{}'",
+                    DIFF_MAX_BYTES_ENV,
+                    max_bytes
+                ),
+            }
+        }
+        config
+    }
+
+    /// Describes the change from `old` to `new`, as far as configured.
+    pub fn change(&self, old: &str, new: &str) -> Option<ResourceChange> {
+        match self.mode {
+            DiffMode::Off => None,
+            DiffMode::Range => Some(ResourceChange::range(old, new)),
+            DiffMode::Lines => {
+                let lines = ResourceChange::lines(old, new);
+                let size = serde_json::to_vec(&lines).map_or(usize::MAX, |json| json.len());
+                if size <= self.max_bytes {
+                    Some(lines)
+                } else {
+                    Some(ResourceChange::range(old, new))
+                }
+            }
+        }
+    }
+
+    /// Describes the removal of a resource, unless changes are not sent.
+    pub fn deleted(&self) -> Option<ResourceChange> {
+        (self.mode != DiffMode::Off).then_some(ResourceChange::Deleted)
+    }
+}
+
+/// A change to a subscribed resource, which a transport sends as
+/// `notifications/resources/updated`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceUpdate {
+    pub uri: String,
+    pub change: Option<ResourceChange>,
+}
+
+impl ResourceUpdate {
+    /// An update saying only that `uri` changed.
+    pub fn new(uri: impl Into<String>) -> Self {
+        Self {
+            uri: uri.into(),
+            change: None,
+        }
+    }
+
+    pub fn with_change(mut self, change: Option<ResourceChange>) -> Self {
+        self.change = change;
+        self
+    }
+
+    /// The notification's params: the URI, and the change in `_meta`.
+    pub fn params(&self) -> serde_json::Value {
+        match &self.change {
+            Some(change) => serde_json::json!({ "uri": self.uri, "_meta": { "change": change } }),
+            None => serde_json::json!({ "uri": self.uri }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_changes_apply_to_the_old_text() {
+        let cases = [
+            ("a\nb\nc\n", "a\nB\nc\n"),
+            ("a\nb\nc\n", "a\nc\n"),
+            ("a\nb", "a\nb\nc"),
+            ("", "new\n"),
+            ("old\n", ""),
+            ("same\n", "same\n"),
+        ];
+        for (old, new) in cases {
+            let change = ResourceChange::lines(old, new);
+            assert_eq!(change.apply(old).as_deref(), Some(new), "{:?}", change);
+        }
+
+        assert_eq!(
+            ResourceChange::lines("a\nb\nc\n", "a\nB\nc\n"),
+            ResourceChange::Lines {
+                line: 2,
+                removed: 1,
+                added: vec!["B\n".to_string()]
+            }
+        );
+        let change = ResourceChange::lines("a\nb\n", "a\nb\nc\n");
+        assert_eq!(change.apply("x\n"), None);
+    }
+
+    #[test]
+    fn test_ranges_cover_whole_characters() {
+        assert_eq!(
+            ResourceChange::range("hello world", "hello there world"),
+            ResourceChange::Range {
+                start: 6,
+                old_end: 6,
+                new_end: 12
+            }
+        );
+        // é and è share their first byte, which must not be split off
+        assert_eq!(
+            ResourceChange::range("café!", "cafè!"),
+            ResourceChange::Range {
+                start: 3,
+                old_end: 5,
+                new_end: 5
+            }
+        );
+        assert_eq!(
+            ResourceChange::range("aaa", "aa"),
+            ResourceChange::Range {
+                start: 2,
+                old_end: 3,
+                new_end: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_large_diffs_are_sent_as_ranges() {
+        let config = DiffConfig {
+            max_bytes: 64,
+            ..DiffConfig::default()
+        };
+        let old = "title\nbody\n";
+        assert!(matches!(
+            config.change(old, "title\nnew body\n"),
+            Some(ResourceChange::Lines { .. })
+        ));
+        let large = format!("title\n{}\n", "x".repeat(100));
+        assert_eq!(
+            config.change(old, &large),
+            Some(ResourceChange::range(old, &large))
+        );
+
+        let off = DiffConfig::from_vars(Some("OFF".to_string()), None);
+        assert_eq!(off.change(old, &large), None);
+        assert_eq!(off.deleted(), None);
+
+        let config = DiffConfig::from_vars(Some("range".to_string()), Some("10".to_string()));
+        assert_eq!((config.mode, config.max_bytes), (DiffMode::Range, 10));
+        assert_eq!(
+            DiffConfig::from_vars(Some("everything".to_string()), Some("lots".to_string())),
+            DiffConfig::default()
+        );
+    }
+
+    #[test]
+    fn test_update_params_carry_the_change_in_meta() {
+        let update = ResourceUpdate::new("note://todo");
+        assert_eq!(update.params(), serde_json::json!({ "uri": "note://todo" }));
+
+        let update = update.with_change(Some(ResourceChange::Deleted));
+        assert_eq!(update.params()["_meta"]["change"]["kind"], "deleted");
+    }
+}
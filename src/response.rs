@@ -0,0 +1,225 @@
+//! Size limits for tool responses.
+//!
+//! A tool that returns file contents, HTTP bodies or query results can easily
+//! produce more than a client wants to receive in one message. [`ResponseGuard`]
+//! cuts such results down to a byte budget and describes what was cut with a
+//! [`Truncation`], which tools merge into their JSON result:
+//!
+//! ```json
+//! { "content": "...", "truncated": true, "total_size": 183204, "next_cursor": 65536 }
+//! ```
+//!
+//! Truncation is deterministic: the same input and budget always produce the
+//! same output, so a client can resume from `next_cursor` and stitch the pieces
+//! back together. What the cursor counts depends on the tool (a byte offset for
+//! text, a row offset for query results); it is only present when the tool can
+//! actually resume.
+
+use crate::error::McpError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Errors raised when a resume cursor does not fit the content.
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ResponseGuardError {
+    #[error("offset {offset} is beyond the end of the content ({len} bytes)")]
+    OffsetOutOfRange { offset: usize, len: usize },
+
+    #[error("offset {0} is not on a UTF-8 character boundary")]
+    NotCharBoundary(usize),
+}
+
+// The cursor came from the caller, so it is their argument that is wrong
+impl From<ResponseGuardError> for McpError {
+    fn from(error: ResponseGuardError) -> Self {
+        McpError::InvalidParams(error.to_string())
+    }
+}
+
+/// Describes whether, and how much, a response was cut.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Truncation {
+    pub truncated: bool,
+    /// Size of the complete result in bytes.
+    pub total_size: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<usize>,
+}
+
+impl Truncation {
+    /// Adds the truncation fields to a JSON object result.
+    pub fn attach(&self, result: &mut Value) {
+        if let Value::Object(map) = result {
+            map.insert("truncated".to_string(), self.truncated.into());
+            map.insert("total_size".to_string(), self.total_size.into());
+            if let Some(cursor) = self.next_cursor {
+                map.insert("next_cursor".to_string(), cursor.into());
+            }
+        }
+    }
+}
+
+/// A possibly truncated piece of a result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limited<T> {
+    pub content: T,
+    pub truncation: Truncation,
+}
+
+/// Caps tool results at a fixed number of bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResponseGuard {
+    max_bytes: usize,
+}
+
+impl ResponseGuard {
+    pub fn new(max_bytes: usize) -> Self {
+        Self { max_bytes }
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Returns the part of `text` starting at byte `offset` that fits the
+    /// budget. The cut never splits a UTF-8 character, and `next_cursor` is
+    /// the byte offset to continue from.
+    pub fn page_text(
+        &self,
+        text: &str,
+        offset: usize,
+    ) -> Result<Limited<String>, ResponseGuardError> {
+        if offset > text.len() {
+            return Err(ResponseGuardError::OffsetOutOfRange {
+                offset,
+                len: text.len(),
+            });
+        }
+        if !text.is_char_boundary(offset) {
+            return Err(ResponseGuardError::NotCharBoundary(offset));
+        }
+
+        let mut end = (offset + self.max_bytes).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        // Always make progress, even if one character is larger than the budget
+        if end == offset && offset < text.len() {
+            end = offset + text[offset..].chars().next().map_or(0, char::len_utf8);
+        }
+
+        let truncated = end < text.len();
+        Ok(Limited {
+            content: text[offset..end].to_string(),
+            truncation: Truncation {
+                truncated,
+                total_size: text.len(),
+                next_cursor: truncated.then_some(end),
+            },
+        })
+    }
+
+    /// Cuts `text` to the budget for results that cannot be resumed, such as
+    /// an HTTP body that may differ if the request is sent again.
+    pub fn truncate_text(&self, text: &str) -> Limited<String> {
+        let mut limited = self
+            .page_text(text, 0)
+            .expect("offset 0 is always a valid cursor");
+        limited.truncation.next_cursor = None;
+        limited
+    }
+
+    /// Keeps the longest prefix of `items` whose serialized size fits the
+    /// budget. `offset` is the position of the first item in the full result
+    /// set, so `next_cursor` is the offset of the first item left out. At
+    /// least one item is kept so paging always makes progress.
+    pub fn limit_items<T: Serialize>(
+        &self,
+        items: Vec<T>,
+        offset: usize,
+    ) -> Result<Limited<Vec<T>>, serde_json::Error> {
+        let sizes = items
+            .iter()
+            .map(|item| serde_json::to_vec(item).map(|bytes| bytes.len()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let total_size: usize = sizes.iter().sum();
+
+        let mut used = 0;
+        let mut keep = 0;
+        for size in &sizes {
+            if keep > 0 && used + size > self.max_bytes {
+                break;
+            }
+            used += size;
+            keep += 1;
+        }
+
+        let truncated = keep < items.len();
+        let mut content = items;
+        content.truncate(keep);
+
+        Ok(Limited {
+            content,
+            truncation: Truncation {
+                truncated,
+                total_size,
+                next_cursor: truncated.then_some(offset + keep),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_text_resumes_on_char_boundaries() {
+        let guard = ResponseGuard::new(4);
+        let text = "héllo wörld";
+
+        let mut pieces = Vec::new();
+        let mut cursor = Some(0);
+        while let Some(offset) = cursor {
+            let page = guard.page_text(text, offset).unwrap();
+            assert!(page.content.len() <= 4);
+            assert_eq!(page.truncation.total_size, text.len());
+            pieces.push(page.content);
+            cursor = page.truncation.next_cursor;
+        }
+        assert_eq!(pieces.concat(), text);
+
+        assert_eq!(
+            guard.page_text(text, 2),
+            Err(ResponseGuardError::NotCharBoundary(2))
+        );
+    }
+
+    #[test]
+    fn test_truncate_text_has_no_cursor() {
+        let limited = ResponseGuard::new(5).truncate_text("hello world");
+        assert_eq!(limited.content, "hello");
+        assert!(limited.truncation.truncated);
+        assert_eq!(limited.truncation.next_cursor, None);
+
+        let mut result = serde_json::json!({ "body": limited.content });
+        limited.truncation.attach(&mut result);
+        assert_eq!(result["truncated"], true);
+        assert_eq!(result["total_size"], 11);
+        assert!(result.get("next_cursor").is_none());
+    }
+
+    #[test]
+    fn test_limit_items_keeps_prefix() {
+        let rows: Vec<Value> = (0..10).map(|i| serde_json::json!({ "id": i })).collect();
+        let row_size = serde_json::to_vec(&rows[0]).unwrap().len();
+
+        let limited = ResponseGuard::new(row_size * 3)
+            .limit_items(rows, 20)
+            .unwrap();
+        assert_eq!(limited.content.len(), 3);
+        assert!(limited.truncation.truncated);
+        assert_eq!(limited.truncation.total_size, row_size * 10);
+        assert_eq!(limited.truncation.next_cursor, Some(23));
+    }
+}
@@ -0,0 +1,217 @@
+//! Letting a server borrow the client's language model.
+//!
+//! MCP sampling runs the other way from tool calls: a server that needs a
+//! completion sends the client a `sampling/createMessage` request with a
+//! conversation and a token budget, and the client answers with the model's
+//! reply. The client stays in charge of which model runs, and may ask the
+//! user before sending anything.
+//!
+//! ```json
+//! {"method":"sampling/createMessage","params":{"messages":[{"role":"user","content":{"type":"text","text":"Summarize..."}}],"maxTokens":200}}
+//! {"role":"assistant","content":{"type":"text","text":"..."},"model":"claude-sonnet","stopReason":"endTurn"}
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The method a server calls to request a completion.
+pub const CREATE_MESSAGE_METHOD: &str = "sampling/createMessage";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// What a message says. Images and audio are base64 encoded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SamplingContent {
+    Text {
+        text: String,
+    },
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Audio {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+}
+
+impl SamplingContent {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text { text: text.into() }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text { text } => Some(text),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SamplingMessage {
+    pub role: Role,
+    pub content: SamplingContent,
+}
+
+impl SamplingMessage {
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: SamplingContent::text(text),
+        }
+    }
+}
+
+/// A model the server would like, matched by substring, e.g. `"sonnet"`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelHint {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// What the server cares about when the client picks a model. Priorities go
+/// from 0 to 1; the client is free to ignore all of it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPreferences {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<ModelHint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_priority: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed_priority: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intelligence_priority: Option<f64>,
+}
+
+/// Parameters of `sampling/createMessage`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageRequest {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_preferences: Option<ModelPreferences>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Which MCP context to add: `"none"`, `"thisServer"` or `"allServers"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_context: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    pub max_tokens: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+}
+
+impl CreateMessageRequest {
+    /// A single user message with a token budget.
+    pub fn new(prompt: impl Into<String>, max_tokens: u32) -> Self {
+        Self {
+            messages: vec![SamplingMessage::user(prompt)],
+            model_preferences: None,
+            system_prompt: None,
+            include_context: None,
+            temperature: None,
+            max_tokens,
+            stop_sequences: Vec::new(),
+            metadata: None,
+        }
+    }
+
+    /// Rejects requests no model could answer.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.messages.is_empty() {
+            return Err("messages must not be empty".to_string());
+        }
+        if self.max_tokens == 0 {
+            return Err("maxTokens must be positive".to_string());
+        }
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(format!("temperature {} is out of range", temperature));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The client's answer to a [`CreateMessageRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: Role,
+    pub content: SamplingContent,
+    /// The model that actually produced the reply.
+    pub model: String,
+    /// Usually `"endTurn"`, `"stopSequence"` or `"maxTokens"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+impl CreateMessageResult {
+    /// A complete text reply from the assistant.
+    pub fn text(model: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            content: SamplingContent::text(text),
+            model: model.into(),
+            stop_reason: Some("endTurn".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_uses_mcp_field_names() {
+        let request: CreateMessageRequest = serde_json::from_value(serde_json::json!({
+            "messages": [{ "role": "user", "content": { "type": "text", "text": "Hi" } }],
+            "modelPreferences": { "hints": [{ "name": "sonnet" }], "speedPriority": 0.8 },
+            "systemPrompt": "Be brief",
+            "maxTokens": 50
+        }))
+        .unwrap();
+        assert_eq!(request.messages[0].content.as_text(), Some("Hi"));
+        assert_eq!(request.model_preferences.unwrap().speed_priority, Some(0.8));
+        assert_eq!(request.max_tokens, 50);
+
+        let result =
+            serde_json::to_value(CreateMessageResult::text("test-model", "Hello")).unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!({
+                "role": "assistant",
+                "content": { "type": "text", "text": "Hello" },
+                "model": "test-model",
+                "stopReason": "endTurn"
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(CreateMessageRequest::new("Hi", 10).validate().is_ok());
+        assert!(CreateMessageRequest::new("Hi", 0).validate().is_err());
+
+        let mut request = CreateMessageRequest::new("Hi", 10);
+        request.messages.clear();
+        assert!(request.validate().is_err());
+
+        let mut request = CreateMessageRequest::new("Hi", 10);
+        request.temperature = Some(3.0);
+        assert!(request.validate().is_err());
+    }
+}
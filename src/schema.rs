@@ -0,0 +1,547 @@
+//! Tool descriptions generated from request structs.
+//!
+//! Writing `input_schema` JSON by hand means keeping it in step with the
+//! struct the arguments are parsed into, and the two drift apart. Instead,
+//! put `#[mcp_tool]` on the request struct:
+//!
+//! ```
+//! use mcp_rust_examples::schema::{mcp_tool, TypedTool};
+//! use serde::Deserialize;
+//!
+//! #[mcp_tool(name = "read_file", description = "Read a text file", read_only)]
+//! #[derive(Deserialize)]
+//! pub struct ReadFileRequest {
+//!     #[tool(description = "Path to the file to read")]
+//!     pub file_path: String,
+//!     #[tool(description = "Byte offset to resume from", default = 0)]
+//!     pub offset: Option<usize>,
+//! }
+//!
+//! let tool = ReadFileRequest::tool();
+//! assert_eq!(tool.input_schema["required"], serde_json::json!(["file_path"]));
+//! let request = ReadFileRequest::parse(serde_json::json!({ "file_path": "a.txt" })).unwrap();
+//! ```
+//!
+//! The attribute takes the tool's `name`, its `description` (otherwise the
+//! struct's doc comment) and the annotation flags `read_only`, `additive` or
+//! `destructive`, plus `idempotent` and `open_world`.
+//!
+//! Each field's schema comes from its type's [`JsonSchema`] impl: numbers,
+//! strings, booleans, `Vec`s and maps map to the matching JSON types, and
+//! `Option` fields are not required. Enums and other custom types implement
+//! [`JsonSchema`] themselves. `#[tool(...)]` on a field adds `description`
+//! (otherwise the field's doc comment), `default`, `minimum`, `maximum`,
+//! `exclusive_minimum`, `exclusive_maximum`, `min_items`, `max_items`,
+//! `min_length`, `max_length`, `pattern` and `enum_values`, or marks an
+//! `Option` field `required`. Serde's `rename`, `default` and `skip` on
+//! fields are honoured.
+//!
+//! Servers built from a [`ToolRouter`](crate::server::ToolRouter) register a
+//! typed handler with [`ToolRouter::with_typed`](crate::server::ToolRouter::with_typed),
+//! which parses the arguments before calling it.
+//!
+//! [`validate`] checks a value against a schema, e.g. a tool result against
+//! the shape a client expects.
+
+use crate::error::McpError;
+use crate::protocol::Tool;
+use crate::server::ToolHandler;
+use futures::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+pub use mcp_tool_macros::mcp_tool;
+
+// Used by the code #[mcp_tool] generates
+#[doc(hidden)]
+pub mod __private {
+    pub use serde_json::{json, Map, Value};
+}
+
+/// A type whose values can be described by a JSON Schema.
+pub trait JsonSchema {
+    /// Whether a struct field of this type may be left out.
+    const OPTIONAL: bool = false;
+
+    fn json_schema() -> Value;
+}
+
+/// A request struct that describes the tool it is the input of. Implemented
+/// by `#[mcp_tool]`.
+pub trait TypedTool: DeserializeOwned {
+    const NAME: &'static str;
+
+    fn tool() -> Tool;
+
+    /// Parses a call's arguments.
+    fn parse(arguments: Value) -> Result<Self, McpError> {
+        serde_json::from_value(arguments)
+            .map_err(|e| McpError::InvalidParams(format!("Failed to parse arguments: {}", e)))
+    }
+}
+
+/// An object schema with `properties`, listing `required` unless empty.
+pub fn object_schema(properties: Map<String, Value>, required: Vec<&str>) -> Value {
+    let mut schema = serde_json::json!({ "type": "object", "properties": properties });
+    if !required.is_empty() {
+        schema["required"] = serde_json::json!(required);
+    }
+    schema
+}
+
+/// Where a value breaks its schema, and how.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaViolation {
+    /// Dotted path to the value, e.g. `files.0.size`; empty for the root.
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path.as_str() {
+            "" => write!(f, "{}", self.message),
+            path => write!(f, "{}: {}", path, self.message),
+        }
+    }
+}
+
+/// Checks `value` against `schema` and returns every violation. Supports
+/// `type` (one or a list), `enum`, `properties`, `required`,
+/// `additionalProperties`, `items`, `minItems`, `maxItems`, `minimum`,
+/// `maximum`, `exclusiveMinimum`, `exclusiveMaximum`, `minLength`,
+/// `maxLength` and `pattern`; other keywords are ignored.
+pub fn validate(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    check(schema, value, "", &mut violations);
+    violations
+}
+
+fn check(schema: &Value, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    let mut violation = |message: String| {
+        violations.push(SchemaViolation {
+            path: path.to_string(),
+            message,
+        })
+    };
+    let child = |key: &dyn Display| match path {
+        "" => key.to_string(),
+        path => format!("{}.{}", path, key),
+    };
+
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            types => types.as_str().into_iter().collect(),
+        };
+        if !types.iter().any(|ty| has_type(value, ty)) {
+            violation(format!(
+                "expected {}, got {}",
+                types.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            violation(format!(
+                "{} is not one of {}",
+                value,
+                Value::from(allowed.clone())
+            ));
+        }
+    }
+
+    let keyword = |name: &str| schema.get(name).and_then(Value::as_f64);
+    match value {
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            let bounds = [
+                ("minimum", "at least"),
+                ("maximum", "at most"),
+                ("exclusiveMinimum", "more than"),
+                ("exclusiveMaximum", "less than"),
+            ];
+            for (name, relation) in bounds {
+                let Some(bound) = keyword(name) else {
+                    continue;
+                };
+                let within = match name {
+                    "minimum" => number >= bound,
+                    "maximum" => number <= bound,
+                    "exclusiveMinimum" => number > bound,
+                    _ => number < bound,
+                };
+                if !within {
+                    violation(format!("{} is not {} {}", number, relation, bound));
+                }
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count() as f64;
+            if keyword("minLength").is_some_and(|min| length < min) {
+                violation(format!("shorter than {} characters", schema["minLength"]));
+            }
+            if keyword("maxLength").is_some_and(|max| length > max) {
+                violation(format!("longer than {} characters", schema["maxLength"]));
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                match regex::Regex::new(pattern) {
+                    Ok(regex) if regex.is_match(text) => {}
+                    Ok(_) => violation(format!("does not match {}", pattern)),
+                    Err(e) => violation(format!("invalid pattern {}: {}", pattern, e)),
+                }
+            }
+        }
+        Value::Array(items) => {
+            let count = items.len() as f64;
+            if keyword("minItems").is_some_and(|min| count < min) {
+                violation(format!("fewer than {} items", schema["minItems"]));
+            }
+            if keyword("maxItems").is_some_and(|max| count > max) {
+                violation(format!("more than {} items", schema["maxItems"]));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(item_schema, item, &child(&index), violations);
+                }
+            }
+        }
+        Value::Object(fields) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !fields.contains_key(name) {
+                        violations.push(SchemaViolation {
+                            path: child(&name),
+                            message: "required field is missing".to_string(),
+                        });
+                    }
+                }
+            }
+            for (name, field) in fields {
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(field_schema) => check(field_schema, field, &child(name), violations),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => violations.push(SchemaViolation {
+                            path: child(name),
+                            message: "field is not in the schema".to_string(),
+                        }),
+                        Some(extra @ Value::Object(_)) => {
+                            check(extra, field, &child(name), violations)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "integer" => value.as_i64().is_some() || value.as_u64().is_some(),
+        "number" => value.is_number(),
+        ty => type_name(value) == ty,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+macro_rules! json_schema {
+    ($schema:tt => $($ty:ty),+) => {
+        $(
+            impl JsonSchema for $ty {
+                fn json_schema() -> Value {
+                    serde_json::json!($schema)
+                }
+            }
+        )+
+    };
+}
+
+json_schema!({ "type": "boolean" } => bool);
+json_schema!({ "type": "integer" } => i8, i16, i32, i64, isize);
+json_schema!({ "type": "integer", "minimum": 0 } => u8, u16, u32, u64, usize);
+json_schema!({ "type": "number" } => f32, f64);
+json_schema!({ "type": "string" } => String, str, char, PathBuf);
+// Any JSON value
+json_schema!({} => Value);
+
+impl<T: JsonSchema + ?Sized> JsonSchema for &T {
+    const OPTIONAL: bool = T::OPTIONAL;
+
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+}
+
+impl<T: JsonSchema + ?Sized> JsonSchema for Box<T> {
+    const OPTIONAL: bool = T::OPTIONAL;
+
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+}
+
+fn array_of<T: JsonSchema + ?Sized>() -> Value {
+    serde_json::json!({ "type": "array", "items": T::json_schema() })
+}
+
+impl<T: JsonSchema> JsonSchema for [T] {
+    fn json_schema() -> Value {
+        array_of::<T>()
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Vec<T> {
+    fn json_schema() -> Value {
+        array_of::<T>()
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for HashSet<T> {
+    fn json_schema() -> Value {
+        serde_json::json!({ "type": "array", "items": T::json_schema(), "uniqueItems": true })
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for BTreeSet<T> {
+    fn json_schema() -> Value {
+        HashSet::<T>::json_schema()
+    }
+}
+
+impl<V: JsonSchema> JsonSchema for HashMap<String, V> {
+    fn json_schema() -> Value {
+        serde_json::json!({ "type": "object", "additionalProperties": V::json_schema() })
+    }
+}
+
+impl<V: JsonSchema> JsonSchema for BTreeMap<String, V> {
+    fn json_schema() -> Value {
+        HashMap::<String, V>::json_schema()
+    }
+}
+
+/// A [`ToolHandler`] that parses its arguments into `T` before calling
+/// `handler`. See [`ToolRouter::with_typed`](crate::server::ToolRouter::with_typed).
+pub struct TypedHandler<T, F> {
+    handler: F,
+    input: PhantomData<fn() -> T>,
+}
+
+impl<T, F> TypedHandler<T, F> {
+    pub fn new(handler: F) -> Self {
+        Self {
+            handler,
+            input: PhantomData,
+        }
+    }
+}
+
+impl<T, F, Fut, R, E> ToolHandler for TypedHandler<T, F>
+where
+    T: TypedTool,
+    F: Fn(T) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<R, E>> + Send + 'static,
+    R: Serialize,
+    E: Into<McpError>,
+{
+    fn tool(&self) -> Tool {
+        T::tool()
+    }
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+        let call = T::parse(arguments).map(&self.handler);
+        Box::pin(async move {
+            let output = call?.await.map_err(Into::into)?;
+            serde_json::to_value(output)
+                .map_err(|e| McpError::Internal(format!("Failed to serialize result: {}", e)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{JsonRpcRequest, RequestId};
+    use crate::server::{McpServer, ToolRouter};
+    use serde::Deserialize;
+    use std::sync::Arc;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Unit {
+        Celsius,
+        Fahrenheit,
+    }
+
+    impl JsonSchema for Unit {
+        fn json_schema() -> Value {
+            serde_json::json!({ "type": "string", "enum": ["celsius", "fahrenheit"] })
+        }
+    }
+
+    /// Convert a temperature
+    #[mcp_tool(name = "convert", read_only, idempotent)]
+    #[derive(Debug, Deserialize)]
+    struct ConvertRequest {
+        /// Degrees to convert
+        degrees: f64,
+        #[tool(description = "Unit to convert to", default = "celsius")]
+        to: Option<Unit>,
+        #[serde(rename = "decimalPlaces", default)]
+        #[tool(minimum = 0, maximum = 6)]
+        decimals: u32,
+        #[tool(min_items = 1)]
+        tags: Vec<String>,
+        #[serde(skip)]
+        #[allow(dead_code)]
+        internal: bool,
+    }
+
+    #[test]
+    fn test_schema_from_struct() {
+        let tool = ConvertRequest::tool();
+        assert_eq!(ConvertRequest::NAME, "convert");
+        assert_eq!(tool.description, "Convert a temperature");
+        let annotations = tool.annotations.unwrap();
+        assert_eq!(annotations.read_only_hint, Some(true));
+        assert_eq!(annotations.idempotent_hint, Some(true));
+        assert_eq!(
+            tool.input_schema,
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "degrees": { "type": "number", "description": "Degrees to convert" },
+                    "to": {
+                        "type": "string",
+                        "enum": ["celsius", "fahrenheit"],
+                        "description": "Unit to convert to",
+                        "default": "celsius"
+                    },
+                    "decimalPlaces": { "type": "integer", "minimum": 0, "maximum": 6 },
+                    "tags": { "type": "array", "items": { "type": "string" }, "minItems": 1 }
+                },
+                "required": ["degrees", "tags"]
+            })
+        );
+
+        let request =
+            ConvertRequest::parse(serde_json::json!({ "degrees": 21.5, "tags": ["x"] })).unwrap();
+        assert_eq!(request.to, None);
+        assert_eq!(request.tags, ["x"]);
+        let error = ConvertRequest::parse(serde_json::json!({ "degrees": "hot" })).unwrap_err();
+        assert!(
+            matches!(error, McpError::InvalidParams(message) if message.starts_with("Failed to parse arguments"))
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let tool = ConvertRequest::tool();
+        let valid = serde_json::json!({ "degrees": 20, "decimalPlaces": 2, "tags": ["x"] });
+        assert_eq!(validate(&tool.input_schema, &valid), Vec::new());
+
+        let invalid = serde_json::json!({ "degrees": "hot", "to": "kelvin", "decimalPlaces": 7 });
+        let violations: Vec<String> = validate(&tool.input_schema, &invalid)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            [
+                "tags: required field is missing",
+                "decimalPlaces: 7 is not at most 6",
+                "degrees: expected number, got string",
+                r#"to: "kelvin" is not one of ["celsius","fahrenheit"]"#,
+            ]
+        );
+
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "files": { "type": "array", "items": { "type": "object", "properties": {
+                    "size": { "type": "integer" },
+                    "name": { "type": "string", "pattern": "^[a-z.]+$" }
+                }}},
+                "age": { "type": ["integer", "null"] }
+            },
+            "additionalProperties": false
+        });
+        let value = serde_json::json!({
+            "files": [{ "size": 1.5, "name": "A.txt" }],
+            "age": null,
+            "extra": true
+        });
+        let violations: Vec<String> = validate(&schema, &value)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            [
+                "extra: field is not in the schema",
+                "files.0.name: does not match ^[a-z.]+$",
+                "files.0.size: expected integer, got number",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_typed_handler() {
+        let router =
+            ToolRouter::new("thermometer").with_typed(|request: ConvertRequest| async move {
+                match request.to.unwrap_or(Unit::Celsius) {
+                    Unit::Celsius => Ok(request.degrees),
+                    Unit::Fahrenheit if request.decimals > 6 => Err("too precise"),
+                    Unit::Fahrenheit => Ok(request.degrees * 9.0 / 5.0 + 32.0),
+                }
+            });
+        let server = McpServer::new(Arc::new(router));
+        let call = |arguments: Value| {
+            server.handle_request(JsonRpcRequest::new(
+                RequestId::Number(1),
+                "tools/call",
+                serde_json::json!({ "name": "convert", "arguments": arguments }),
+            ))
+        };
+
+        let arguments = serde_json::json!({ "degrees": 100, "to": "fahrenheit", "tags": [] });
+        let result = call(arguments).await.unwrap().result.unwrap();
+        assert_eq!(result["content"][0]["text"], "212.0");
+
+        let result = call(serde_json::json!({ "to": "kelvin" }))
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(result["isError"], true);
+    }
+}
@@ -0,0 +1,1274 @@
+//! A minimal MCP server runtime shared by the examples.
+//!
+//! [`McpServer`] answers the JSON-RPC methods every example needs
+//! (`initialize`, `ping`, `tools/list` and `tools/call`) on top of any
+//! [`ToolServer`], so an example only has to provide its tools. Servers made
+//! of a few independent tools can skip writing their own dispatch and
+//! register one [`ToolHandler`] per tool on a [`ToolRouter`]. Servers that
+//! can suggest argument values also answer `completion/complete` through a
+//! [`CompletionProvider`], and servers with resources answer the
+//! `resources/*` methods through a [`ResourceProvider`]. Servers whose tools
+//! change at runtime use a [`ToolRegistry`](crate::registry::ToolRegistry)
+//! instead of a router. Every server also offers the
+//! [`self_diagnostics`](crate::diagnostics) tool, and the
+//! [`get_tool_stats`](crate::stats) tool, which reports the sizes and
+//! latencies the runtime measures for every tool call. Requests pass through
+//! any [`Middleware`] layers added with
+//! [`with_middleware`](McpServer::with_middleware) before they are answered.
+//! Servers that push log messages to the client publish them on a channel
+//! given to [`with_log_messages`](McpServer::with_log_messages). Tool results
+//! are written in the [`OutputFormat`] the client asked for in `initialize`,
+//! or the server's default. Clients that accept [compression](crate::compression)
+//! get large messages compressed on network transports. Any server
+//! started with [`MCP_CHAOS`](crate::chaos::CHAOS_ENV) set injects faults
+//! through [`Chaos`], for testing how clients cope.
+
+use crate::capabilities::{self, ClientCapabilities};
+use crate::chaos::Chaos;
+use crate::compression::{CompressionPolicy, Compressor};
+use crate::diagnostics::{self, DiagnosticsProvider};
+use crate::error::McpError;
+use crate::logging::ToolCallLog;
+use crate::metrics::{self, MetricsRegistry};
+use crate::middleware::{Endpoint, Middleware, MiddlewareChain};
+use crate::output_format::OutputFormat;
+use crate::progress;
+use crate::protocol::{
+    CallToolParams, CallToolResult, CompleteParams, Completion, JsonRpcError, JsonRpcRequest,
+    JsonRpcResponse, ListToolsResult, LoggingLevel, LoggingMessageParams, PaginatedParams,
+    Resource, ResourceParams, ServerInfo, SetLevelParams, Tool, JSONRPC_VERSION,
+};
+use crate::resource_diff::ResourceUpdate;
+use crate::schema::{TypedHandler, TypedTool};
+use crate::session::{self, SessionLimits};
+use crate::stats::{self, ToolCallMeasurement, ToolStats};
+use crate::tools::ToolServer;
+use futures::future::BoxFuture;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::broadcast;
+
+/// A single tool: its description and the code that runs it.
+pub trait ToolHandler: Send + Sync {
+    fn tool(&self) -> Tool;
+
+    fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>>;
+}
+
+/// Suggests values for an argument from what the user has typed so far.
+pub trait CompletionProvider: Send + Sync {
+    /// Returns matching values, best first. References and arguments the
+    /// provider knows nothing about complete to an empty list.
+    fn complete<'a>(
+        &'a self,
+        params: &'a CompleteParams,
+    ) -> BoxFuture<'a, Result<Vec<String>, String>>;
+}
+
+/// Serves resources and tells subscribers when one changes.
+pub trait ResourceProvider: Send + Sync {
+    fn list_resources(&self) -> BoxFuture<'_, Result<Vec<Resource>, McpError>>;
+
+    /// The `resources/read` result for `uri`, i.e. its `contents`. Unknown
+    /// resources are [`McpError::NotFound`].
+    fn read_resource<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Result<Value, McpError>>;
+
+    /// Starts announcing changes to `uri` through [`updates`](Self::updates).
+    fn subscribe(&self, uri: &str) -> Result<(), McpError>;
+
+    fn unsubscribe(&self, uri: &str) -> Result<(), McpError>;
+
+    /// Receives an update for every subscribed resource that changes, with
+    /// what changed if the provider knows.
+    fn updates(&self) -> broadcast::Receiver<ResourceUpdate>;
+}
+
+/// A [`ToolServer`] assembled from [`ToolHandler`]s. Calls are logged with
+/// [`ToolCallLog`], like the examples' own `call_tool` methods.
+pub struct ToolRouter {
+    name: String,
+    handlers: Vec<Box<dyn ToolHandler>>,
+}
+
+impl ToolRouter {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            handlers: Vec::new(),
+        }
+    }
+
+    pub fn with_handler(mut self, handler: impl ToolHandler + 'static) -> Self {
+        self.handlers.push(Box::new(handler));
+        self
+    }
+
+    /// Adds the tool described by the `#[mcp_tool]` struct `T`. Its
+    /// arguments are parsed into a `T` for `handler`, and what the handler
+    /// returns is serialized as the result.
+    pub fn with_typed<T, F>(self, handler: F) -> Self
+    where
+        T: TypedTool + 'static,
+        TypedHandler<T, F>: ToolHandler + 'static,
+    {
+        self.with_handler(TypedHandler::new(handler))
+    }
+
+    fn handler(&self, name: &str) -> Option<&dyn ToolHandler> {
+        self.handlers
+            .iter()
+            .find(|handler| handler.tool().name == name)
+            .map(|handler| handler.as_ref())
+    }
+}
+
+impl ToolServer for ToolRouter {
+    fn server_name(&self) -> &str {
+        &self.name
+    }
+
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        self.handlers.iter().map(|handler| handler.tool()).collect()
+    }
+
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>> {
+        Box::pin(ToolCallLog::run_async(
+            name,
+            arguments,
+            move |arguments| async move {
+                match self.handler(name) {
+                    Some(handler) => handler.call(arguments).await,
+                    None => Err(McpError::NotFound(format!("Unknown tool: {}", name))),
+                }
+            },
+        ))
+    }
+}
+
+/// How many tools `tools/list` returns per page unless configured otherwise.
+pub const DEFAULT_TOOLS_PAGE_SIZE: usize = 50;
+
+/// The least severe log message sent until the client picks a level.
+pub const DEFAULT_LOG_LEVEL: LoggingLevel = LoggingLevel::Info;
+
+/// What resource subscriptions count as in a session's
+/// [usage](crate::session::SessionStore::usage).
+pub const SUBSCRIPTIONS_KIND: &str = "subscriptions";
+
+/// Speaks the MCP JSON-RPC methods for a [`ToolServer`].
+pub struct McpServer {
+    info: ServerInfo,
+    tools: Arc<dyn ToolServer>,
+    completions: Option<Arc<dyn CompletionProvider>>,
+    resources: Option<Arc<dyn ResourceProvider>>,
+    diagnostics: Option<Arc<dyn DiagnosticsProvider>>,
+    page_size: usize,
+    started_at: Instant,
+    stats: ToolStats,
+    metrics: Arc<MetricsRegistry>,
+    middleware: MiddlewareChain<JsonRpcRequest, JsonRpcResponse>,
+    log_messages: Option<broadcast::Sender<LoggingMessageParams>>,
+    log_level: Mutex<LoggingLevel>,
+    output_format: OutputFormat,
+    compression: CompressionPolicy,
+    session_limits: SessionLimits,
+    chaos: Option<Arc<Chaos>>,
+}
+
+impl McpServer {
+    /// Serves `tools`, reporting the tool server's name and this crate's
+    /// version. Chaos is added when the environment enables it, and the
+    /// default output format, compression and session limits are read from
+    /// it.
+    pub fn new(tools: Arc<dyn ToolServer>) -> Self {
+        let server = Self {
+            info: ServerInfo {
+                name: tools.server_name().to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            tools,
+            completions: None,
+            resources: None,
+            diagnostics: None,
+            page_size: DEFAULT_TOOLS_PAGE_SIZE,
+            started_at: Instant::now(),
+            stats: ToolStats::new(),
+            metrics: metrics::global().clone(),
+            middleware: MiddlewareChain::new(),
+            log_messages: None,
+            log_level: Mutex::new(DEFAULT_LOG_LEVEL),
+            output_format: OutputFormat::from_env().unwrap_or_default(),
+            compression: CompressionPolicy::from_env(),
+            session_limits: SessionLimits::from_env(),
+            chaos: None,
+        };
+        match Chaos::from_env() {
+            Some(chaos) => server.with_chaos(chaos),
+            None => server,
+        }
+    }
+
+    /// Pages `tools/list` results `page_size` tools at a time.
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Answers `completion/complete` with `provider`, and advertises the
+    /// `completions` capability.
+    pub fn with_completions(mut self, provider: Arc<dyn CompletionProvider>) -> Self {
+        self.completions = Some(provider);
+        self
+    }
+
+    /// Answers `resources/list`, `resources/read`, `resources/subscribe` and
+    /// `resources/unsubscribe` with `provider`, and advertises the
+    /// `resources` capability.
+    pub fn with_resources(mut self, provider: Arc<dyn ResourceProvider>) -> Self {
+        self.resources = Some(provider);
+        self
+    }
+
+    /// Adds `provider`'s configuration, feature flags and dependency checks
+    /// to the `self_diagnostics` report.
+    pub fn with_diagnostics(mut self, provider: Arc<dyn DiagnosticsProvider>) -> Self {
+        self.diagnostics = Some(provider);
+        self
+    }
+
+    /// Records tool call metrics in `registry` instead of the
+    /// [global](metrics::global) one.
+    pub fn with_metrics(mut self, registry: Arc<MetricsRegistry>) -> Self {
+        self.metrics = registry;
+        self
+    }
+
+    /// Runs every request that expects a response through `layer`, inside
+    /// the layers added before it. Notifications bypass the middleware.
+    pub fn with_middleware(
+        mut self,
+        layer: Arc<dyn Middleware<JsonRpcRequest, JsonRpcResponse>>,
+    ) -> Self {
+        self.middleware.push(layer);
+        self
+    }
+
+    /// Sends the log messages published on `sender` to the client as
+    /// `notifications/message`, and advertises the `logging` capability so
+    /// the client can choose the least severe level it wants with
+    /// `logging/setLevel`.
+    pub fn with_log_messages(mut self, sender: broadcast::Sender<LoggingMessageParams>) -> Self {
+        self.log_messages = Some(sender);
+        self
+    }
+
+    /// Writes tool results in `format` for clients that do not ask for one.
+    pub fn with_output_format(mut self, format: OutputFormat) -> Self {
+        self.output_format = format;
+        self
+    }
+
+    /// The format tool results are written in for `client`, or for a caller
+    /// outside a session.
+    pub fn output_format(&self, client: Option<&ClientCapabilities>) -> OutputFormat {
+        client
+            .and_then(|client| client.output_format)
+            .unwrap_or(self.output_format)
+    }
+
+    /// Compresses large messages as `policy` says, for clients that accept
+    /// one of its algorithms.
+    pub fn with_compression(mut self, policy: CompressionPolicy) -> Self {
+        self.compression = policy;
+        self
+    }
+
+    /// How messages to `client` are compressed, or `None` when they are not,
+    /// e.g. for a caller outside a session.
+    pub fn compressor(&self, client: Option<&ClientCapabilities>) -> Option<Compressor> {
+        self.compression.negotiate(&client?.compression)
+    }
+
+    /// Limits the state kept for each connection to `limits`.
+    pub fn with_session_limits(mut self, limits: SessionLimits) -> Self {
+        self.session_limits = limits;
+        self
+    }
+
+    /// The limits transports give the session of each connection.
+    pub fn session_limits(&self) -> SessionLimits {
+        self.session_limits
+    }
+
+    /// Injects the faults of `chaos`'s policy into tool calls and
+    /// notifications, and offers the `set_chaos_policy` tool to change it.
+    /// Chaos runs outside the middleware added later.
+    pub fn with_chaos(mut self, chaos: Arc<Chaos>) -> Self {
+        self.middleware.push(chaos.clone());
+        self.chaos = Some(chaos);
+        self
+    }
+
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.info.version = version.into();
+        self
+    }
+
+    pub fn info(&self) -> &ServerInfo {
+        &self.info
+    }
+
+    pub fn tools(&self) -> &Arc<dyn ToolServer> {
+        &self.tools
+    }
+
+    /// Sizes and latencies of the tool calls answered so far.
+    pub fn tool_stats(&self) -> &ToolStats {
+        &self.stats
+    }
+
+    /// A receiver for changes to the tool list, which a transport turns into
+    /// `notifications/tools/list_changed`. `None` when the tools are fixed.
+    pub fn tool_list_changes(&self) -> Option<broadcast::Receiver<()>> {
+        self.tools.tool_list_changes()
+    }
+
+    /// A receiver for changes to subscribed resources, which a transport
+    /// turns into `notifications/resources/updated`. `None` when the server
+    /// has no resources.
+    pub fn resource_updates(&self) -> Option<broadcast::Receiver<ResourceUpdate>> {
+        self.resources.as_ref().map(|resources| resources.updates())
+    }
+
+    /// A receiver for log messages, which a transport turns into
+    /// `notifications/message` if [`wants_log`](Self::wants_log) says so.
+    /// `None` when the server sends no log messages.
+    pub fn log_messages(&self) -> Option<broadcast::Receiver<LoggingMessageParams>> {
+        self.log_messages.as_ref().map(broadcast::Sender::subscribe)
+    }
+
+    /// Whether the client wants messages of `level`, given the level it
+    /// last set with `logging/setLevel`.
+    pub fn wants_log(&self, level: LoggingLevel) -> bool {
+        level >= *self.log_level.lock().unwrap()
+    }
+
+    /// Whether a transport should send `notification`, or drop it to
+    /// simulate a lossy connection.
+    pub fn delivers_notification(&self, notification: &JsonRpcRequest) -> bool {
+        let dropped = self
+            .chaos
+            .as_ref()
+            .is_some_and(|chaos| chaos.drops_notification());
+        if dropped {
+            tracing::info!(method = %notification.method, "Chaos dropped a notification");
+        }
+        !dropped
+    }
+
+    /// The `self_diagnostics` report for this server.
+    pub async fn diagnostics_report(&self) -> Value {
+        diagnostics::report(
+            &self.info,
+            self.started_at.elapsed(),
+            self.diagnostics.as_deref(),
+        )
+        .await
+    }
+
+    /// Runs the diagnostics once and logs the outcome, warning about every
+    /// dependency that cannot be reached. Returns the report.
+    pub async fn self_check(&self) -> Value {
+        let report = self.diagnostics_report().await;
+        let checks = report["dependencies"]
+            .as_array()
+            .map_or(&[][..], Vec::as_slice);
+        for check in checks.iter().filter(|check| check["reachable"] != true) {
+            tracing::warn!(
+                server = %self.info.name,
+                dependency = %check["name"],
+                target = %check["target"],
+                detail = %check["detail"],
+                "Dependency unreachable"
+            );
+        }
+        tracing::info!(
+            server = %self.info.name,
+            version = %self.info.version,
+            status = %report["status"],
+            dependencies = checks.len(),
+            "Self-check finished"
+        );
+        report
+    }
+
+    /// Handles one raw JSON-RPC message. Returns `None` for notifications,
+    /// which get no response.
+    pub async fn handle_message(&self, message: &str) -> Option<JsonRpcResponse> {
+        let value: Value = match serde_json::from_str(message) {
+            Ok(value) => value,
+            Err(e) => return Some(JsonRpcResponse::failure(None, JsonRpcError::parse_error(e))),
+        };
+        let id = value
+            .get("id")
+            .and_then(|id| serde_json::from_value(id.clone()).ok());
+
+        match serde_json::from_value::<JsonRpcRequest>(value) {
+            Ok(request) => self.handle_request(request).await,
+            Err(e) => Some(JsonRpcResponse::failure(
+                id,
+                JsonRpcError::invalid_request(e),
+            )),
+        }
+    }
+
+    /// Handles a parsed request. Returns `None` for notifications.
+    pub async fn handle_request(&self, request: JsonRpcRequest) -> Option<JsonRpcResponse> {
+        if request.jsonrpc != JSONRPC_VERSION {
+            return Some(JsonRpcResponse::failure(
+                request.id,
+                JsonRpcError::invalid_request("jsonrpc must be \"2.0\""),
+            ));
+        }
+        // Notifications such as notifications/initialized need no answer
+        request.id.as_ref()?;
+
+        Some(self.middleware.run(request, &Dispatch(self)).await)
+    }
+
+    // Answers a request once the middleware has let it through. Middleware
+    // may look at the params afterwards, so they are cloned rather than taken.
+    async fn dispatch(&self, request: &JsonRpcRequest) -> JsonRpcResponse {
+        let id = request.id.clone();
+        let params = request.params.clone();
+        let result = match request.method.as_str() {
+            "initialize" => Ok(self.initialize_result(&params)),
+            "ping" => Ok(serde_json::json!({})),
+            "tools/list" => self.list_tools(params),
+            "tools/call" => self.call_tool(params).await,
+            "completion/complete" => self.complete(params).await,
+            method @ ("resources/list"
+            | "resources/read"
+            | "resources/subscribe"
+            | "resources/unsubscribe") => self.handle_resources(method, params).await,
+            "logging/setLevel" if self.log_messages.is_some() => self.set_log_level(params),
+            method => Err(JsonRpcError::method_not_found(method)),
+        };
+
+        match result {
+            Ok(result) => JsonRpcResponse::success(id, result),
+            Err(error) => JsonRpcResponse::failure(id, error),
+        }
+    }
+
+    // Older clients get the protocol version they asked for, if it is one
+    // this crate speaks
+    fn initialize_result(&self, params: &Value) -> Value {
+        let client = ClientCapabilities::from_initialize(params);
+        tracing::info!(
+            server = %self.info.name,
+            client = client.client_name.as_deref().unwrap_or("unknown"),
+            protocol_version = %client.protocol_version,
+            sampling = client.sampling,
+            elicitation = client.elicitation,
+            "Client initialized"
+        );
+        let list_changed = self.tools.tool_list_changes().is_some();
+        let mut capabilities = serde_json::json!({ "tools": { "listChanged": list_changed } });
+        if self.completions.is_some() {
+            capabilities["completions"] = serde_json::json!({});
+        }
+        if self.resources.is_some() {
+            capabilities["resources"] =
+                serde_json::json!({ "subscribe": true, "listChanged": false });
+        }
+        if self.log_messages.is_some() {
+            capabilities["logging"] = serde_json::json!({});
+        }
+        // Tells the client which format it got, whether it asked or not
+        capabilities["experimental"] =
+            serde_json::json!({ "outputFormat": self.output_format(Some(&client)) });
+        if let Some(compressor) = self.compressor(Some(&client)) {
+            capabilities["experimental"]["compression"] = compressor.to_capability();
+        }
+        serde_json::json!({
+            "protocolVersion": client.protocol_version,
+            "capabilities": capabilities,
+            "serverInfo": self.info,
+        })
+    }
+
+    fn set_log_level(&self, params: Value) -> Result<Value, JsonRpcError> {
+        let params: SetLevelParams =
+            serde_json::from_value(params).map_err(JsonRpcError::invalid_params)?;
+        *self.log_level.lock().unwrap() = params.level;
+        Ok(serde_json::json!({}))
+    }
+
+    // The cursor is the offset of the page's first tool. It stays valid as
+    // long as the tool set does; clients are expected to start over otherwise.
+    fn list_tools(&self, params: Value) -> Result<Value, JsonRpcError> {
+        let params: PaginatedParams = match params {
+            Value::Null => PaginatedParams::default(),
+            params => serde_json::from_value(params).map_err(JsonRpcError::invalid_params)?,
+        };
+        let tools = self.tool_descriptors();
+        let start = match params.cursor {
+            Some(cursor) => cursor
+                .parse::<usize>()
+                .ok()
+                .filter(|&start| start < tools.len())
+                .ok_or_else(|| {
+                    JsonRpcError::invalid_params(format!("Invalid cursor: {}", cursor))
+                })?,
+            None => 0,
+        };
+        let end = (start + self.page_size).min(tools.len());
+
+        let page = ListToolsResult {
+            next_cursor: (end < tools.len()).then(|| end.to_string()),
+            tools: tools[start..end].to_vec(),
+        };
+        serde_json::to_value(page).map_err(|e| {
+            JsonRpcError::new(crate::protocol::error_codes::INTERNAL_ERROR, e.to_string())
+        })
+    }
+
+    async fn complete(&self, params: Value) -> Result<Value, JsonRpcError> {
+        let Some(completions) = &self.completions else {
+            return Err(JsonRpcError::method_not_found("completion/complete"));
+        };
+        let params: CompleteParams =
+            serde_json::from_value(params).map_err(JsonRpcError::invalid_params)?;
+        let values = completions
+            .complete(&params)
+            .await
+            .map_err(JsonRpcError::invalid_params)?;
+
+        Ok(serde_json::json!({ "completion": Completion::from_values(values) }))
+    }
+
+    // The server's own tools, then self_diagnostics and get_tool_stats
+    // unless a tool already took that name.
+    fn tool_descriptors(&self) -> Vec<Tool> {
+        let mut tools = self.tools.tool_descriptors();
+        for builtin in [diagnostics::tool(), stats::tool()] {
+            if !tools.iter().any(|tool| tool.name == builtin.name) {
+                tools.push(builtin);
+            }
+        }
+        tools
+    }
+
+    async fn handle_resources(&self, method: &str, params: Value) -> Result<Value, JsonRpcError> {
+        let Some(resources) = &self.resources else {
+            return Err(JsonRpcError::method_not_found(method));
+        };
+        if method == "resources/list" {
+            let list = resources.list_resources().await?;
+            return Ok(serde_json::json!({ "resources": list }));
+        }
+
+        let ResourceParams { uri } =
+            serde_json::from_value(params).map_err(JsonRpcError::invalid_params)?;
+        // Subscriptions count towards the connection's session limits
+        let session = session::current();
+        match method {
+            "resources/read" => resources.read_resource(&uri).await,
+            "resources/subscribe" => session
+                .track(SUBSCRIPTIONS_KIND, &uri, uri.len())
+                .map_err(McpError::from)
+                .and_then(|()| {
+                    resources.subscribe(&uri).inspect_err(|_| {
+                        session.untrack(SUBSCRIPTIONS_KIND, &uri);
+                    })
+                })
+                .map(|()| serde_json::json!({})),
+            _ => {
+                session.untrack(SUBSCRIPTIONS_KIND, &uri);
+                resources.unsubscribe(&uri).map(|()| serde_json::json!({}))
+            }
+        }
+        .map_err(JsonRpcError::from)
+    }
+
+    async fn call_tool(&self, params: Value) -> Result<Value, JsonRpcError> {
+        let progress_token = capabilities::progress_token(&params).cloned();
+        let params: CallToolParams =
+            serde_json::from_value(params).map_err(JsonRpcError::invalid_params)?;
+        let is_own_tool = self
+            .tools
+            .tool_descriptors()
+            .iter()
+            .any(|tool| tool.name == params.name);
+        let is_builtin = [diagnostics::TOOL_NAME, stats::TOOL_NAME].contains(&params.name.as_str());
+        if !is_own_tool && !is_builtin {
+            return Err(JsonRpcError::invalid_params(format!(
+                "Unknown tool: {}",
+                params.name
+            )));
+        }
+
+        // Tools without arguments may be called with none at all
+        let arguments = match params.arguments {
+            Value::Null => Value::Object(serde_json::Map::new()),
+            arguments => arguments,
+        };
+        let bytes_in = json_len(&arguments);
+        let started = Instant::now();
+        let result = if is_own_tool {
+            progress::track(
+                progress_token,
+                self.tools.invoke_tool(&params.name, arguments),
+            )
+            .await
+        } else {
+            let name = params.name.as_str();
+            ToolCallLog::run_async(name, arguments, |arguments| async move {
+                if name == stats::TOOL_NAME {
+                    self.stats.report(&arguments)
+                } else {
+                    Ok(self.diagnostics_report().await)
+                }
+            })
+            .await
+        };
+        let latency = started.elapsed();
+
+        let format = self.output_format(capabilities::current().as_deref());
+        let result =
+            serde_json::to_value(CallToolResult::from_result_as(result, format)).map_err(|e| {
+                JsonRpcError::new(crate::protocol::error_codes::INTERNAL_ERROR, e.to_string())
+            })?;
+        let measurement = ToolCallMeasurement {
+            tool: &params.name,
+            bytes_in,
+            bytes_out: json_len(&result),
+            latency,
+            is_error: result["isError"] == true,
+        };
+        self.stats.record(&measurement);
+        measurement.record_metrics(&self.metrics, &self.info.name);
+        Ok(result)
+    }
+}
+
+// The endpoint the middleware wraps. Kept private so nothing outside the
+// server can answer requests past the middleware.
+struct Dispatch<'s>(&'s McpServer);
+
+impl Endpoint<JsonRpcRequest, JsonRpcResponse> for Dispatch<'_> {
+    fn handle<'a>(&'a self, request: &'a mut JsonRpcRequest) -> BoxFuture<'a, JsonRpcResponse> {
+        Box::pin(self.0.dispatch(request))
+    }
+}
+
+// How many bytes `value` takes on the wire
+fn json_len(value: &Value) -> usize {
+    serde_json::to_vec(value).map_or(0, |bytes| bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{error_codes, RequestId, ToolAnnotations, PROTOCOL_VERSION};
+    use crate::resource_diff::DiffConfig;
+
+    struct Echo;
+
+    impl ToolHandler for Echo {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "echo".to_string(),
+                description: "Echo the message back".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": { "message": { "type": "string" } },
+                    "required": ["message"]
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            }
+        }
+
+        fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async move {
+                let message = arguments["message"]
+                    .as_str()
+                    .ok_or("message must be a string")?;
+                Ok(serde_json::json!({ "echo": message }))
+            })
+        }
+    }
+
+    // Splits the message into words, for a result that is a list
+    struct Words;
+
+    impl ToolHandler for Words {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "words".to_string(),
+                description: "Split the message into words".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": { "message": { "type": "string" } }
+                }),
+                annotations: Some(ToolAnnotations::read_only()),
+            }
+        }
+
+        fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async move {
+                let words: Vec<&str> = arguments["message"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .collect();
+                Ok(serde_json::json!({ "words": words, "count": words.len() }))
+            })
+        }
+    }
+
+    struct Colors;
+
+    impl CompletionProvider for Colors {
+        fn complete<'a>(
+            &'a self,
+            params: &'a CompleteParams,
+        ) -> BoxFuture<'a, Result<Vec<String>, String>> {
+            Box::pin(async move {
+                Ok(["red", "green", "grey"]
+                    .into_iter()
+                    .filter(|color| color.starts_with(&params.argument.value))
+                    .map(String::from)
+                    .collect())
+            })
+        }
+    }
+
+    // One note whose content can be changed with `edit`
+    struct Notes {
+        content: std::sync::Mutex<String>,
+        subscribed: std::sync::Mutex<bool>,
+        updates: broadcast::Sender<ResourceUpdate>,
+    }
+
+    impl Notes {
+        const URI: &'static str = "note://todo";
+
+        fn new() -> Self {
+            Self {
+                content: std::sync::Mutex::new("buy milk".to_string()),
+                subscribed: std::sync::Mutex::new(false),
+                updates: broadcast::channel(8).0,
+            }
+        }
+
+        fn edit(&self, content: &str) {
+            let old = std::mem::replace(&mut *self.content.lock().unwrap(), content.to_string());
+            if *self.subscribed.lock().unwrap() {
+                let change = DiffConfig::default().change(&old, content);
+                let _ = self
+                    .updates
+                    .send(ResourceUpdate::new(Self::URI).with_change(change));
+            }
+        }
+
+        fn check_uri(uri: &str) -> Result<(), McpError> {
+            match uri {
+                Self::URI => Ok(()),
+                _ => Err(McpError::NotFound(format!("Unknown resource: {}", uri))),
+            }
+        }
+    }
+
+    impl ResourceProvider for Notes {
+        fn list_resources(&self) -> BoxFuture<'_, Result<Vec<Resource>, McpError>> {
+            Box::pin(async {
+                Ok(vec![Resource {
+                    uri: Self::URI.to_string(),
+                    name: Some("Todo".to_string()),
+                    description: None,
+                    mime_type: Some("text/plain".to_string()),
+                }])
+            })
+        }
+
+        fn read_resource<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Result<Value, McpError>> {
+            Box::pin(async move {
+                Self::check_uri(uri)?;
+                let text = self.content.lock().unwrap().clone();
+                Ok(serde_json::json!({ "contents": [{ "uri": uri, "text": text }] }))
+            })
+        }
+
+        fn subscribe(&self, uri: &str) -> Result<(), McpError> {
+            Self::check_uri(uri)?;
+            *self.subscribed.lock().unwrap() = true;
+            Ok(())
+        }
+
+        fn unsubscribe(&self, uri: &str) -> Result<(), McpError> {
+            Self::check_uri(uri)?;
+            *self.subscribed.lock().unwrap() = false;
+            Ok(())
+        }
+
+        fn updates(&self) -> broadcast::Receiver<ResourceUpdate> {
+            self.updates.subscribe()
+        }
+    }
+
+    fn server() -> McpServer {
+        McpServer::new(Arc::new(ToolRouter::new("echo_server").with_handler(Echo)))
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_list_tools() {
+        let server = server();
+
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#)
+            .await
+            .unwrap();
+        let result = response.result.unwrap();
+        assert_eq!(result["protocolVersion"], PROTOCOL_VERSION);
+        assert_eq!(result["serverInfo"]["name"], "echo_server");
+
+        // Older clients keep the version they speak
+        let response = server
+            .handle_message(
+                r#"{"jsonrpc":"2.0","id":2,"method":"initialize","params":{"protocolVersion":"2024-11-05"}}"#,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.result.unwrap()["protocolVersion"], "2024-11-05");
+
+        // The initialized notification gets no response
+        assert!(server
+            .handle_message(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#)
+            .await
+            .is_none());
+
+        let response = server
+            .handle_request(JsonRpcRequest::new(
+                RequestId::String("list".to_string()),
+                "tools/list",
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.id, Some(RequestId::String("list".to_string())));
+        assert_eq!(response.result.unwrap()["tools"][0]["name"], "echo");
+    }
+
+    #[tokio::test]
+    async fn test_call_tool() {
+        let server = server();
+        let call = |arguments: Value| {
+            server.handle_request(JsonRpcRequest::new(
+                RequestId::Number(7),
+                "tools/call",
+                serde_json::json!({ "name": "echo", "arguments": arguments }),
+            ))
+        };
+
+        let result = call(serde_json::json!({ "message": "hi" }))
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(result["isError"], false);
+        assert_eq!(result["content"][0]["text"], r#"{"echo":"hi"}"#);
+
+        // Tool failures are results, not protocol errors
+        let result = call(serde_json::json!({ "message": 1 }))
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(result["isError"], true);
+    }
+
+    #[tokio::test]
+    async fn test_output_format_follows_the_client() {
+        let server = McpServer::new(Arc::new(
+            ToolRouter::new("words_server")
+                .with_handler(Echo)
+                .with_handler(Words),
+        ))
+        .with_output_format(OutputFormat::Pretty);
+        let call = |name: &str| {
+            server.handle_request(JsonRpcRequest::new(
+                RequestId::Number(1),
+                "tools/call",
+                serde_json::json!({ "name": name, "arguments": { "message": "a b c" } }),
+            ))
+        };
+
+        // Clients that do not ask get the server's default
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#)
+            .await
+            .unwrap();
+        let capabilities = &response.result.unwrap()["capabilities"];
+        assert_eq!(capabilities["experimental"]["outputFormat"], "pretty");
+        let result = call("echo").await.unwrap().result.unwrap();
+        assert_eq!(result["content"][0]["text"], "{\n  \"echo\": \"a b c\"\n}");
+
+        // A client asking for NDJSON gets a list result one item per line
+        let initialize = serde_json::json!({
+            "capabilities": { "experimental": { "outputFormat": "ndjson" } }
+        });
+        let client = Arc::new(ClientCapabilities::from_initialize(&initialize));
+        let result = capabilities::scope(Some(client), call("words"))
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        let result: CallToolResult = serde_json::from_value(result).unwrap();
+        assert_eq!(
+            result.meta.as_ref().unwrap()["ndjson"]["fields"]["count"],
+            3
+        );
+        assert_eq!(result.into_result().unwrap(), "\"a\"\n\"b\"\n\"c\"");
+    }
+
+    #[tokio::test]
+    async fn test_protocol_errors() {
+        let server = server();
+
+        let response = server.handle_message("{not json").await.unwrap();
+        assert_eq!(response.id, None);
+        assert_eq!(response.error.unwrap().code, error_codes::PARSE_ERROR);
+
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"resources/list"}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.error.unwrap().code, error_codes::METHOD_NOT_FOUND);
+
+        let response = server
+            .handle_message(
+                r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"missing"}}"#,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.error.unwrap().code, error_codes::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_middleware() {
+        // Rejects tool calls; counts every response on its way out
+        #[derive(Default)]
+        struct NoTools {
+            responses: std::sync::atomic::AtomicUsize,
+        }
+
+        impl Middleware<JsonRpcRequest, JsonRpcResponse> for NoTools {
+            fn on_request<'a>(
+                &'a self,
+                request: &'a mut JsonRpcRequest,
+            ) -> BoxFuture<'a, Option<JsonRpcResponse>> {
+                Box::pin(async move {
+                    (request.method == "tools/call").then(|| {
+                        let error = McpError::PermissionDenied("Tools are disabled".to_string());
+                        JsonRpcResponse::failure(request.id.clone(), error.into())
+                    })
+                })
+            }
+
+            fn on_response<'a>(
+                &'a self,
+                _request: &'a JsonRpcRequest,
+                _response: &'a mut JsonRpcResponse,
+                _elapsed: std::time::Duration,
+            ) -> BoxFuture<'a, ()> {
+                self.responses
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                Box::pin(async {})
+            }
+        }
+
+        let layer = Arc::new(NoTools::default());
+        let server = server().with_middleware(layer.clone());
+
+        let response = server
+            .handle_message(
+                r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"echo"}}"#,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.id, Some(RequestId::Number(1)));
+        assert_eq!(response.error.unwrap().code, error_codes::PERMISSION_DENIED);
+        assert_eq!(
+            server.tool_stats().report(&Value::Null).unwrap()["tools"],
+            serde_json::json!([])
+        );
+
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#)
+            .await
+            .unwrap();
+        assert!(response.error.is_none());
+
+        // Notifications get no response, so they skip the middleware
+        assert!(server
+            .handle_message(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#)
+            .await
+            .is_none());
+        assert_eq!(
+            layer.responses.load(std::sync::atomic::Ordering::Relaxed),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_self_diagnostics_tool() {
+        let server = server();
+
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#)
+            .await
+            .unwrap();
+        let tools = response.result.unwrap()["tools"].clone();
+        assert_eq!(tools[1]["name"], diagnostics::TOOL_NAME);
+        assert_eq!(tools[1]["annotations"]["readOnlyHint"], true);
+
+        let result = server
+            .handle_message(
+                r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"self_diagnostics"}}"#,
+            )
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(result["isError"], false);
+        let report: Value =
+            serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(report["server"]["name"], "echo_server");
+        assert_eq!(report["status"], "ok");
+    }
+
+    #[tokio::test]
+    async fn test_tool_stats() {
+        let registry = Arc::new(MetricsRegistry::new());
+        let server = server().with_metrics(registry.clone());
+        let call = |arguments: Value| {
+            server.handle_request(JsonRpcRequest::new(
+                RequestId::Number(1),
+                "tools/call",
+                serde_json::json!({ "name": "echo", "arguments": arguments }),
+            ))
+        };
+        call(serde_json::json!({ "message": "hi" })).await.unwrap();
+        call(serde_json::json!({ "message": 1 })).await.unwrap();
+
+        let echo = server.tool_stats().summary("echo").unwrap();
+        assert_eq!(echo.calls, 2);
+        assert_eq!(echo.errors, 1);
+        assert_eq!(echo.bytes_in.max, r#"{"message":"hi"}"#.len() as u64);
+        assert!(echo.bytes_out.total > 0);
+        let labels = [("server", "echo_server"), ("tool", "echo")];
+        assert_eq!(
+            registry.counter(
+                "mcp_tool_calls_total",
+                &[labels[0], labels[1], ("status", "error")]
+            ),
+            1
+        );
+
+        let result = server
+            .handle_message(
+                r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"get_tool_stats","arguments":{"tool":"echo"}}}"#,
+            )
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        let report: Value =
+            serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(report["tools"][0]["tool"], "echo");
+        assert_eq!(report["tools"][0]["calls"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_list_tools_pages() {
+        struct Named(&'static str);
+
+        impl ToolHandler for Named {
+            fn tool(&self) -> Tool {
+                Tool {
+                    name: self.0.to_string(),
+                    description: String::new(),
+                    input_schema: serde_json::json!({ "type": "object" }),
+                    annotations: None,
+                }
+            }
+
+            fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+                Box::pin(async { Ok(Value::Null) })
+            }
+        }
+
+        let router = ["a", "b", "c"]
+            .into_iter()
+            .fold(ToolRouter::new("letters"), |router, name| {
+                router.with_handler(Named(name))
+            });
+        let server = McpServer::new(Arc::new(router)).with_page_size(2);
+        let list = |params: Value| {
+            server.handle_request(JsonRpcRequest::new(
+                RequestId::Number(1),
+                "tools/list",
+                params,
+            ))
+        };
+
+        let first = list(Value::Null).await.unwrap().result.unwrap();
+        assert_eq!(first["tools"].as_array().unwrap().len(), 2);
+        assert_eq!(first["nextCursor"], "2");
+
+        let second = list(serde_json::json!({ "cursor": first["nextCursor"] }))
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(second["tools"][0]["name"], "c");
+        assert_eq!(second["tools"][1]["name"], diagnostics::TOOL_NAME);
+
+        // The built-in tools come last
+        let last = list(serde_json::json!({ "cursor": second["nextCursor"] }))
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(last["tools"][0]["name"], stats::TOOL_NAME);
+        assert!(last.get("nextCursor").is_none());
+
+        let response = list(serde_json::json!({ "cursor": "bogus" }))
+            .await
+            .unwrap();
+        assert_eq!(response.error.unwrap().code, error_codes::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_completion() {
+        let complete = r#"{"jsonrpc":"2.0","id":4,"method":"completion/complete","params":{
+            "ref":{"type":"ref/prompt","name":"paint"},"argument":{"name":"color","value":"gr"}}}"#;
+
+        // Without a provider the method does not exist
+        let response = server().handle_message(complete).await.unwrap();
+        assert_eq!(response.error.unwrap().code, error_codes::METHOD_NOT_FOUND);
+
+        let server = server().with_completions(Arc::new(Colors));
+        let response = server
+            .handle_message(r#"{"jsonrpc":"2.0","id":1,"method":"initialize"}"#)
+            .await
+            .unwrap();
+        assert!(response.result.unwrap()["capabilities"]
+            .get("completions")
+            .is_some());
+
+        let result = server
+            .handle_message(complete)
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(
+            result["completion"],
+            serde_json::json!({ "values": ["green", "grey"], "total": 2, "hasMore": false })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resource_subscriptions() {
+        let notes = Arc::new(Notes::new());
+        let server = server().with_resources(notes.clone());
+        let mut updates = server.resource_updates().unwrap();
+        let request = |method: &str, params: Value| {
+            server.handle_request(JsonRpcRequest::new(RequestId::Number(1), method, params))
+        };
+
+        let result = request("initialize", Value::Null)
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(result["capabilities"]["resources"]["subscribe"], true);
+
+        let result = request("resources/list", Value::Null)
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(result["resources"][0]["mimeType"], "text/plain");
+
+        let response = request(
+            "resources/subscribe",
+            serde_json::json!({ "uri": "note://x" }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.error.unwrap().code, error_codes::NOT_FOUND);
+
+        // Changes are only announced while subscribed
+        notes.edit("buy eggs");
+        let uri = serde_json::json!({ "uri": Notes::URI });
+        assert!(request("resources/subscribe", uri.clone())
+            .await
+            .unwrap()
+            .result
+            .is_some());
+        notes.edit("buy bread");
+        let update = updates.try_recv().unwrap();
+        assert_eq!(update.uri, Notes::URI);
+        assert_eq!(
+            update.change.unwrap().apply("buy eggs").as_deref(),
+            Some("buy bread")
+        );
+
+        let result = request("resources/read", uri.clone())
+            .await
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(result["contents"][0]["text"], "buy bread");
+
+        request("resources/unsubscribe", uri).await.unwrap();
+        notes.edit("buy jam");
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_count_towards_session_limits() {
+        let server = server().with_resources(Arc::new(Notes::new()));
+        let limits = SessionLimits {
+            max_objects: 1,
+            max_bytes: 1024,
+        };
+        let store = Arc::new(session::SessionStore::with_limits(limits));
+        store.set("draft", "text".to_string()).unwrap();
+        let request = |method: &str| {
+            let params = serde_json::json!({ "uri": Notes::URI });
+            let request = JsonRpcRequest::new(RequestId::Number(1), method, params);
+            session::scope(store.clone(), server.handle_request(request))
+        };
+
+        // The session is full until the client lets go of its value
+        let response = request("resources/subscribe").await.unwrap();
+        assert_eq!(response.error.unwrap().code, error_codes::RATE_LIMITED);
+        store.remove("draft");
+        let response = request("resources/subscribe").await.unwrap();
+        assert!(response.result.is_some());
+        assert_eq!(store.usage().by_kind[SUBSCRIPTIONS_KIND].objects, 1);
+
+        request("resources/unsubscribe").await.unwrap();
+        assert_eq!(store.usage().objects, 0);
+    }
+}
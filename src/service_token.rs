@@ -0,0 +1,268 @@
+//! Signed, short-lived tokens for calls between example servers.
+//!
+//! User tokens from the auth service (example 13) are looked up in its own
+//! store, which other servers cannot reach. Machine-to-machine calls instead
+//! carry a self-contained service token: the auth service signs claims naming
+//! the calling service and the single service it may call (the audience),
+//! and the receiving server checks signature, audience and expiry with the
+//! same [`ServiceTokenKey`], without calling back.
+//!
+//! A token is `<claims>.<signature>`, both base64url without padding, where
+//! the claims are JSON and the signature is HMAC-SHA256 over the encoded
+//! claims. It travels in [`SERVICE_TOKEN_HEADER`], so a forwarded call can
+//! carry a user's own credentials in `Authorization` alongside it.
+//!
+//! ```
+//! # use mcp_rust_examples::service_token::ServiceTokenKey;
+//! let key = ServiceTokenKey::new("shared secret");
+//! let token = key.issue("gateway", "enterprise_server", &["jobs:submit"], chrono::Duration::minutes(5));
+//! let claims = key.verify(&token, "enterprise_server").unwrap();
+//! assert_eq!(claims.subject, "gateway");
+//! assert!(key.verify(&token, "monitoring").is_err());
+//! ```
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Once;
+use uuid::Uuid;
+
+/// Environment variable holding the secret shared by every server that
+/// issues or accepts service tokens.
+pub const SERVICE_TOKEN_KEY_ENV: &str = "MCP_SERVICE_TOKEN_KEY";
+
+/// The request header carrying a service token.
+pub const SERVICE_TOKEN_HEADER: &str = "X-Service-Token";
+
+/// Used when [`SERVICE_TOKEN_KEY_ENV`] is unset, so the examples work
+/// together out of the box. Never rely on it outside a demo.
+const DEVELOPMENT_KEY: &str = "mcp-examples-development-service-key";
+
+/// How far a token's issue time may lie in the future, for clocks that
+/// disagree slightly between servers.
+const MAX_CLOCK_SKEW_SECONDS: i64 = 30;
+
+/// What a service token asserts. Times are Unix seconds.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceClaims {
+    pub token_id: Uuid,
+    /// The calling service.
+    pub subject: String,
+    /// The only service that accepts the token.
+    pub audience: String,
+    /// What the caller may do there; the receiving server interprets them.
+    pub scopes: Vec<String>,
+    pub issued_at: i64,
+    pub expires_at: i64,
+}
+
+impl ServiceClaims {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+/// Why a service token was rejected.
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ServiceTokenError {
+    #[error("malformed service token")]
+    Malformed,
+
+    #[error("service token signature does not match")]
+    BadSignature,
+
+    #[error("service token is for '{actual}', not '{expected}'")]
+    WrongAudience { expected: String, actual: String },
+
+    #[error("service token expired")]
+    Expired,
+
+    #[error("service token is not valid yet")]
+    NotYetValid,
+}
+
+/// The shared secret that signs and verifies service tokens.
+#[derive(Clone)]
+pub struct ServiceTokenKey {
+    secret: Vec<u8>,
+}
+
+impl std::fmt::Debug for ServiceTokenKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ServiceTokenKey(..)")
+    }
+}
+
+impl ServiceTokenKey {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+        }
+    }
+
+    /// Reads the secret from [`SERVICE_TOKEN_KEY_ENV`], falling back to a
+    /// development key with a warning, given once per process.
+    pub fn from_env() -> Self {
+        static WARN_ONCE: Once = Once::new();
+        match std::env::var(SERVICE_TOKEN_KEY_ENV) {
+            Ok(secret) if !secret.is_empty() => Self::new(secret),
+            _ => {
+                WARN_ONCE.call_once(|| {
+                    tracing::warn!(
+                        "{} is not set; using the development service token key",
+                        SERVICE_TOKEN_KEY_ENV
+                    )
+                });
+                Self::new(DEVELOPMENT_KEY)
+            }
+        }
+    }
+
+    /// Signs a token for `subject` to call `audience`, valid for `ttl`.
+    pub fn issue(&self, subject: &str, audience: &str, scopes: &[&str], ttl: Duration) -> String {
+        let now = Utc::now().timestamp();
+        self.sign(&ServiceClaims {
+            token_id: Uuid::new_v4(),
+            subject: subject.to_string(),
+            audience: audience.to_string(),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+            issued_at: now,
+            expires_at: now + ttl.num_seconds(),
+        })
+    }
+
+    /// Encodes and signs arbitrary claims.
+    pub fn sign(&self, claims: &ServiceClaims) -> String {
+        let payload =
+            BASE64URL.encode(serde_json::to_vec(claims).expect("claims always serialize"));
+        let signature = BASE64URL.encode(self.mac(&payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Checks the signature, that the token is meant for `audience`, and that
+    /// it is within its validity period.
+    pub fn verify(&self, token: &str, audience: &str) -> Result<ServiceClaims, ServiceTokenError> {
+        let (payload, signature) = token.split_once('.').ok_or(ServiceTokenError::Malformed)?;
+        let signature = BASE64URL
+            .decode(signature)
+            .map_err(|_| ServiceTokenError::Malformed)?;
+        // verify_slice compares in constant time
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| ServiceTokenError::BadSignature)?;
+
+        let claims: ServiceClaims = BASE64URL
+            .decode(payload)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or(ServiceTokenError::Malformed)?;
+        if claims.audience != audience {
+            return Err(ServiceTokenError::WrongAudience {
+                expected: audience.to_string(),
+                actual: claims.audience,
+            });
+        }
+        let now = Utc::now().timestamp();
+        if now >= claims.expires_at {
+            return Err(ServiceTokenError::Expired);
+        }
+        if claims.issued_at > now + MAX_CLOCK_SKEW_SECONDS {
+            return Err(ServiceTokenError::NotYetValid);
+        }
+        Ok(claims)
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> ServiceTokenKey {
+        ServiceTokenKey::new("test secret")
+    }
+
+    #[test]
+    fn test_issued_token_verifies_for_its_audience() {
+        let token = key().issue(
+            "gateway",
+            "enterprise_server",
+            &["jobs:submit"],
+            Duration::minutes(5),
+        );
+        let claims = key().verify(&token, "enterprise_server").unwrap();
+        assert_eq!(claims.subject, "gateway");
+        assert!(claims.has_scope("jobs:submit"));
+        assert!(!claims.has_scope("admin"));
+        assert_eq!(claims.expires_at - claims.issued_at, 300);
+
+        assert_eq!(
+            key().verify(&token, "monitoring"),
+            Err(ServiceTokenError::WrongAudience {
+                expected: "monitoring".to_string(),
+                actual: "enterprise_server".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_tampered_and_foreign_tokens() {
+        let token = key().issue("gateway", "enterprise_server", &[], Duration::minutes(5));
+
+        let other_key = ServiceTokenKey::new("another secret");
+        assert_eq!(
+            other_key.verify(&token, "enterprise_server"),
+            Err(ServiceTokenError::BadSignature)
+        );
+
+        // Widening the claims invalidates the original signature
+        let mut claims = key().verify(&token, "enterprise_server").unwrap();
+        claims.scopes.push("admin".to_string());
+        let widened = key().sign(&claims);
+        let (payload, _) = widened.split_once('.').unwrap();
+        let (_, signature) = token.split_once('.').unwrap();
+        assert_eq!(
+            key().verify(&format!("{}.{}", payload, signature), "enterprise_server"),
+            Err(ServiceTokenError::BadSignature)
+        );
+
+        for malformed in ["", "no-dot", "abc.!!!"] {
+            assert_eq!(
+                key().verify(malformed, "enterprise_server"),
+                Err(ServiceTokenError::Malformed)
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_expired_and_future_tokens() {
+        let expired = key().issue("gateway", "enterprise_server", &[], Duration::zero());
+        assert_eq!(
+            key().verify(&expired, "enterprise_server"),
+            Err(ServiceTokenError::Expired)
+        );
+
+        let now = Utc::now().timestamp();
+        let future = key().sign(&ServiceClaims {
+            token_id: Uuid::new_v4(),
+            subject: "gateway".to_string(),
+            audience: "enterprise_server".to_string(),
+            scopes: Vec::new(),
+            issued_at: now + 3600,
+            expires_at: now + 7200,
+        });
+        assert_eq!(
+            key().verify(&future, "enterprise_server"),
+            Err(ServiceTokenError::NotYetValid)
+        );
+    }
+}
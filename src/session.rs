@@ -0,0 +1,467 @@
+//! State one client connection keeps across its tool calls.
+//!
+//! Each tool call is a request of its own, but many interactions take
+//! several: a calculator's variables, a file server's working directory, the
+//! transaction a database client has open. The transports give every
+//! connection its own [`SessionStore`] and make it available to the code
+//! handling that connection's requests through [`current`]:
+//!
+//! ```
+//! # use mcp_rust_examples::session;
+//! # use std::time::Duration;
+//! let session = session::current();
+//! session.set_with_ttl("last_result", 42.0_f64, Duration::from_secs(600))?;
+//! assert_eq!(session.get::<f64>("last_result"), Some(42.0));
+//! assert_eq!(session.get::<String>("last_result"), None);
+//! # Ok::<(), session::SessionLimitExceeded>(())
+//! ```
+//!
+//! Values keep their Rust type, and a lookup as another type finds nothing.
+//! A value set with a time to live disappears once it has passed; the rest
+//! lives until the connection closes or, over HTTP, until the
+//! `Mcp-Session-Id` session ends.
+//!
+//! A client that never lets go of anything would make a long-running server
+//! keep more and more for it, so each session has [`SessionLimits`]: how
+//! many objects it may hold and roughly how many bytes. Its values count,
+//! and state kept for the connection elsewhere, such as its resource
+//! subscriptions or the messages it has not acknowledged, is counted with
+//! [`SessionStore::track`] while it is kept. Going over a limit fails with
+//! [`SessionLimitExceeded`] until the client releases something.
+//!
+//! Outside a connection, e.g. when an example calls its own tools, all calls
+//! share one process-wide session, so a demo keeps its context from call to
+//! call. Tests that need a fresh session run under [`scope`].
+
+use crate::error::McpError;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// The most objects a session may hold, e.g. `1000`.
+pub const SESSION_MAX_OBJECTS_ENV: &str = "MCP_SESSION_MAX_OBJECTS";
+/// The most bytes a session's state may take, by estimate.
+pub const SESSION_MAX_BYTES_ENV: &str = "MCP_SESSION_MAX_BYTES";
+
+/// What the values of a session are counted as in its usage.
+pub const VALUES_KIND: &str = "values";
+
+tokio::task_local! {
+    static SESSION: Arc<SessionStore>;
+}
+
+/// How much state one session may keep on the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionLimits {
+    pub max_objects: usize,
+    /// Compared with estimates: a value counts its key and the size of its
+    /// type, not what it points to.
+    pub max_bytes: usize,
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        Self {
+            max_objects: 10_000,
+            max_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl SessionLimits {
+    /// The default limits, with those set in [`SESSION_MAX_OBJECTS_ENV`]
+    /// and [`SESSION_MAX_BYTES_ENV`] instead. Invalid settings are ignored.
+    pub fn from_env() -> Self {
+        let read = |variable: &str| {
+            let value = std::env::var(variable).ok()?;
+            match value.trim().parse() {
+                Ok(limit) => Some(limit),
+                Err(_) => {
+                    tracing::warn!("Ignoring invalid {}: '{}'", variable, value);
+                    None
+                }
+            }
+        };
+        let defaults = Self::default();
+        Self {
+            max_objects: read(SESSION_MAX_OBJECTS_ENV).unwrap_or(defaults.max_objects),
+            max_bytes: read(SESSION_MAX_BYTES_ENV).unwrap_or(defaults.max_bytes),
+        }
+    }
+}
+
+/// State a session could not keep without going over its limits.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error(
+    "This session keeps too much state on the server: {kind} would take it to {objects} \
+     objects and {bytes} bytes, over its limit of {} objects and {} bytes. Release some \
+     first, e.g. by unsubscribing or acknowledging messages",
+    .limits.max_objects,
+    .limits.max_bytes
+)]
+pub struct SessionLimitExceeded {
+    /// What was being kept, e.g. [`VALUES_KIND`] or `"subscriptions"`.
+    pub kind: String,
+    pub objects: usize,
+    pub bytes: usize,
+    pub limits: SessionLimits,
+}
+
+// A quota the client can free up again, so it is reported like one
+impl From<SessionLimitExceeded> for McpError {
+    fn from(error: SessionLimitExceeded) -> Self {
+        McpError::RateLimited {
+            message: error.to_string(),
+            retry_after: None,
+        }
+    }
+}
+
+/// How much state a session keeps, in all and by kind.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionUsage {
+    pub objects: usize,
+    pub bytes: usize,
+    pub limits: SessionLimits,
+    pub by_kind: BTreeMap<String, KindUsage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KindUsage {
+    pub objects: usize,
+    pub bytes: usize,
+}
+
+// The estimated size of every object a session keeps, by kind and id
+struct Usage {
+    limits: SessionLimits,
+    objects: HashMap<(String, String), usize>,
+    bytes: usize,
+}
+
+impl Usage {
+    fn track(&mut self, kind: &str, id: &str, bytes: usize) -> Result<(), SessionLimitExceeded> {
+        let key = (kind.to_string(), id.to_string());
+        // Tracking an object again replaces its size rather than adding one
+        let replaced = self.objects.get(&key).copied();
+        let objects = self.objects.len() + usize::from(replaced.is_none());
+        let total = self.bytes - replaced.unwrap_or(0) + bytes;
+        if objects > self.limits.max_objects || total > self.limits.max_bytes {
+            return Err(SessionLimitExceeded {
+                kind: kind.to_string(),
+                objects,
+                bytes: total,
+                limits: self.limits,
+            });
+        }
+        self.objects.insert(key, bytes);
+        self.bytes = total;
+        Ok(())
+    }
+
+    fn untrack(&mut self, kind: &str, id: &str) -> bool {
+        match self.objects.remove(&(kind.to_string(), id.to_string())) {
+            Some(bytes) => {
+                self.bytes -= bytes;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+struct Entry {
+    value: Box<dyn Any + Send + Sync>,
+    expires_at: Option<Instant>,
+}
+
+impl Entry {
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+/// The values one connection keeps between its requests.
+pub struct SessionStore {
+    id: Uuid,
+    values: Mutex<HashMap<String, Entry>>,
+    // Locked after values when both are
+    usage: Mutex<Usage>,
+}
+
+impl std::fmt::Debug for SessionStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionStore")
+            .field("id", &self.id)
+            .field("keys", &self.keys())
+            .finish()
+    }
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionStore {
+    /// A session with the default [`SessionLimits`].
+    pub fn new() -> Self {
+        Self::with_limits(SessionLimits::default())
+    }
+
+    pub fn with_limits(limits: SessionLimits) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            values: Mutex::new(HashMap::new()),
+            usage: Mutex::new(Usage {
+                limits,
+                objects: HashMap::new(),
+                bytes: 0,
+            }),
+        }
+    }
+
+    /// Identifies the session, e.g. to tie a resource to the connection that
+    /// created it.
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Stores `value` under `key` until the session ends, replacing whatever
+    /// was there, unless that would go over the session's limits.
+    pub fn set<T: Any + Send + Sync>(
+        &self,
+        key: impl Into<String>,
+        value: T,
+    ) -> Result<(), SessionLimitExceeded> {
+        self.insert(key.into(), value, None)
+    }
+
+    /// Stores `value` under `key` for `ttl`.
+    pub fn set_with_ttl<T: Any + Send + Sync>(
+        &self,
+        key: impl Into<String>,
+        value: T,
+        ttl: Duration,
+    ) -> Result<(), SessionLimitExceeded> {
+        self.insert(key.into(), value, Some(Instant::now() + ttl))
+    }
+
+    /// The value under `key`, unless it is missing, expired or of another
+    /// type.
+    pub fn get<T: Any + Clone>(&self, key: &str) -> Option<T> {
+        let values = self.values.lock().unwrap();
+        values
+            .get(key)
+            .filter(|entry| entry.is_live(Instant::now()))
+            .and_then(|entry| entry.value.downcast_ref::<T>())
+            .cloned()
+    }
+
+    /// Removes the value under `key`; false if there was none.
+    pub fn remove(&self, key: &str) -> bool {
+        let mut values = self.values.lock().unwrap();
+        let entry = values.remove(key);
+        self.usage.lock().unwrap().untrack(VALUES_KIND, key);
+        entry.is_some_and(|entry| entry.is_live(Instant::now()))
+    }
+
+    /// The keys with a live value, sorted.
+    pub fn keys(&self) -> Vec<String> {
+        let now = Instant::now();
+        let mut keys: Vec<String> = self
+            .values
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| entry.is_live(now))
+            .map(|(key, _)| key.clone())
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    /// Removes every value. State tracked with [`track`](Self::track) is
+    /// left to its owners.
+    pub fn clear(&self) {
+        let mut values = self.values.lock().unwrap();
+        let mut usage = self.usage.lock().unwrap();
+        for (key, _) in values.drain() {
+            usage.untrack(VALUES_KIND, &key);
+        }
+    }
+
+    /// Counts an object of `kind` the server keeps for this session,
+    /// estimated at `bytes`, against its limits until it is
+    /// [`untrack`](Self::untrack)ed. Tracking the same kind and id again
+    /// updates its size.
+    pub fn track(&self, kind: &str, id: &str, bytes: usize) -> Result<(), SessionLimitExceeded> {
+        self.usage.lock().unwrap().track(kind, id, bytes)
+    }
+
+    /// Stops counting an object; false if it was not tracked.
+    pub fn untrack(&self, kind: &str, id: &str) -> bool {
+        self.usage.lock().unwrap().untrack(kind, id)
+    }
+
+    /// How many more objects the session may hold.
+    pub fn remaining_objects(&self) -> usize {
+        let usage = self.usage.lock().unwrap();
+        usage.limits.max_objects.saturating_sub(usage.objects.len())
+    }
+
+    /// What the session keeps, by kind.
+    pub fn usage(&self) -> SessionUsage {
+        let usage = self.usage.lock().unwrap();
+        let mut by_kind = BTreeMap::<String, KindUsage>::new();
+        for ((kind, _), bytes) in &usage.objects {
+            let kind = by_kind.entry(kind.clone()).or_default();
+            kind.objects += 1;
+            kind.bytes += bytes;
+        }
+        SessionUsage {
+            objects: usage.objects.len(),
+            bytes: usage.bytes,
+            limits: usage.limits,
+            by_kind,
+        }
+    }
+
+    fn insert<T: Any + Send + Sync>(
+        &self,
+        key: String,
+        value: T,
+        expires_at: Option<Instant>,
+    ) -> Result<(), SessionLimitExceeded> {
+        let mut values = self.values.lock().unwrap();
+        let mut usage = self.usage.lock().unwrap();
+        // Expired values are only dropped here, which keeps them from piling up
+        let now = Instant::now();
+        values.retain(|key, entry| {
+            let live = entry.is_live(now);
+            if !live {
+                usage.untrack(VALUES_KIND, key);
+            }
+            live
+        });
+        usage.track(VALUES_KIND, &key, key.len() + std::mem::size_of::<T>())?;
+        values.insert(
+            key,
+            Entry {
+                value: Box::new(value),
+                expires_at,
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Runs `future` with `session` as the [`current`] session. Transports wrap
+/// each request of a connection in this.
+pub async fn scope<F: Future>(session: Arc<SessionStore>, future: F) -> F::Output {
+    SESSION.scope(session, future).await
+}
+
+/// The session of the connection whose request is being handled, or the
+/// process-wide one outside a connection.
+pub fn current() -> Arc<SessionStore> {
+    static DETACHED: OnceLock<Arc<SessionStore>> = OnceLock::new();
+    SESSION
+        .try_with(Arc::clone)
+        .unwrap_or_else(|_| DETACHED.get_or_init(Arc::default).clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_are_typed_and_expire() {
+        let session = SessionStore::new();
+        session.set("cwd", "/tmp".to_string()).unwrap();
+        session
+            .set_with_ttl("x", 2.5_f64, Duration::from_millis(20))
+            .unwrap();
+        assert_eq!(session.get::<String>("cwd").as_deref(), Some("/tmp"));
+        assert_eq!(session.get::<f64>("x"), Some(2.5));
+        assert_eq!(session.get::<String>("x"), None);
+        assert_eq!(session.keys(), ["cwd", "x"]);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(session.get::<f64>("x"), None);
+        assert_eq!(session.keys(), ["cwd"]);
+        assert!(!session.remove("x"));
+        assert!(session.remove("cwd"));
+        assert!(session.keys().is_empty());
+    }
+
+    #[test]
+    fn test_state_is_kept_within_the_limits() {
+        let session = SessionStore::with_limits(SessionLimits {
+            max_objects: 3,
+            max_bytes: 1000,
+        });
+        session.set("a", 1_u64).unwrap();
+        session.set("a", 2_u64).unwrap();
+        session.track("subscriptions", "file:///x", 100).unwrap();
+        session.track("subscriptions", "file:///x", 200).unwrap();
+        assert_eq!(session.remaining_objects(), 1);
+        let usage = session.usage();
+        assert_eq!((usage.objects, usage.bytes), (2, 1 + 8 + 200));
+        assert_eq!(usage.by_kind[VALUES_KIND].bytes, 9);
+
+        // Too many bytes, then too many objects
+        let error = session
+            .track("subscriptions", "file:///y", 900)
+            .unwrap_err();
+        assert_eq!((error.kind.as_str(), error.objects), ("subscriptions", 3));
+        session.set("b", 3_u64).unwrap();
+        let error = session.set("c", 4_u64).unwrap_err();
+        assert_eq!(error.objects, 4);
+        assert!(matches!(
+            McpError::from(error),
+            McpError::RateLimited {
+                retry_after: None,
+                ..
+            }
+        ));
+        assert_eq!(session.get::<u64>("c"), None);
+        // A value that fails to replace another leaves it in place
+        session.set("a", [0_u8; 2000]).unwrap_err();
+        assert_eq!(session.get::<u64>("a"), Some(2));
+
+        // Releasing state makes room again, and expired values release theirs
+        assert!(session.untrack("subscriptions", "file:///x"));
+        assert!(!session.untrack("subscriptions", "file:///x"));
+        session
+            .set_with_ttl("c", 4_u64, Duration::from_millis(10))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        session.set("d", 5_u64).unwrap();
+        assert_eq!(session.usage().objects, 3);
+        session.clear();
+        assert_eq!(session.usage().objects, 0);
+    }
+
+    #[tokio::test]
+    async fn test_each_scope_has_its_own_session() {
+        let first = Arc::new(SessionStore::new());
+        let second = Arc::new(SessionStore::new());
+        scope(first.clone(), async { current().set("n", 1_u32).unwrap() }).await;
+        scope(second.clone(), async { current().set("n", 2_u32).unwrap() }).await;
+
+        assert_eq!(first.get::<u32>("n"), Some(1));
+        assert_eq!(second.get::<u32>("n"), Some(2));
+        let id = scope(first.clone(), async { current().id() }).await;
+        assert_eq!(id, first.id());
+
+        // Outside a scope, every caller sees the same process-wide session
+        assert_eq!(current().id(), current().id());
+        assert_ne!(current().id(), first.id());
+    }
+}
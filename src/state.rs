@@ -0,0 +1,231 @@
+//! Snapshot and restore of in-memory server state.
+//!
+//! Stateful examples implement [`StatefulServer`] so their state can be dumped
+//! to a JSON file and loaded back later. This is handy for demos (start from a
+//! prepared state), tests (assert on a known state) and migrations (reshape a
+//! dump with any JSON tool before loading it).
+//!
+//! Every example that supports snapshots accepts the same flags:
+//!
+//! ```bash
+//! cargo run --bin example_13_auth_service -- --dump-state auth.json
+//! cargo run --bin example_13_auth_service -- --restore-state auth.json
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+/// Errors raised while taking, saving or loading a snapshot.
+#[derive(Debug, thiserror::Error)]
+pub enum StateError {
+    #[error("failed to access snapshot file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid snapshot data: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("snapshot belongs to server '{found}', expected '{expected}'")]
+    WrongServer { expected: String, found: String },
+
+    #[error("snapshot version {found} is not supported (expected {expected})")]
+    UnsupportedVersion { expected: u32, found: u32 },
+
+    #[error("invalid command line: {0}")]
+    InvalidArgs(String),
+}
+
+/// A server whose in-memory state can be captured and restored as JSON.
+///
+/// Only durable state belongs in a snapshot. Runtime plumbing such as channels,
+/// worker handles and caches is rebuilt by the server's constructor.
+pub trait StatefulServer {
+    /// Identifies the server in the snapshot envelope, e.g. `"auth_service"`.
+    const STATE_NAME: &'static str;
+
+    /// Bumped whenever the snapshot layout changes incompatibly.
+    const STATE_VERSION: u32 = 1;
+
+    /// Captures the current state.
+    fn snapshot(&self) -> impl Future<Output = Result<Value, StateError>> + Send;
+
+    /// Replaces the current state with a previously captured one.
+    fn restore(&mut self, state: Value) -> impl Future<Output = Result<(), StateError>> + Send;
+}
+
+/// The on-disk format: the server's state plus enough metadata to reject a
+/// snapshot taken from a different server or an incompatible version.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub server: String,
+    pub version: u32,
+    pub taken_at: DateTime<Utc>,
+    pub state: Value,
+}
+
+impl StateSnapshot {
+    pub async fn capture<S: StatefulServer>(server: &S) -> Result<Self, StateError> {
+        Ok(Self {
+            server: S::STATE_NAME.to_string(),
+            version: S::STATE_VERSION,
+            taken_at: Utc::now(),
+            state: server.snapshot().await?,
+        })
+    }
+
+    pub async fn apply<S: StatefulServer>(self, server: &mut S) -> Result<(), StateError> {
+        if self.server != S::STATE_NAME {
+            return Err(StateError::WrongServer {
+                expected: S::STATE_NAME.to_string(),
+                found: self.server,
+            });
+        }
+        if self.version != S::STATE_VERSION {
+            return Err(StateError::UnsupportedVersion {
+                expected: S::STATE_VERSION,
+                found: self.version,
+            });
+        }
+        server.restore(self.state).await
+    }
+}
+
+/// Writes a snapshot of `server` to `path` as pretty-printed JSON.
+pub async fn save_snapshot<S: StatefulServer>(server: &S, path: &Path) -> Result<(), StateError> {
+    let snapshot = StateSnapshot::capture(server).await?;
+    tokio::fs::write(path, serde_json::to_vec_pretty(&snapshot)?).await?;
+    Ok(())
+}
+
+/// Loads the snapshot at `path` into `server`.
+pub async fn load_snapshot<S: StatefulServer>(
+    server: &mut S,
+    path: &Path,
+) -> Result<(), StateError> {
+    let snapshot: StateSnapshot = serde_json::from_slice(&tokio::fs::read(path).await?)?;
+    snapshot.apply(server).await
+}
+
+/// The `--restore-state <path>` and `--dump-state <path>` command line flags.
+///
+/// Other arguments are ignored so examples can combine these flags with their
+/// own.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StateCommand {
+    pub restore_from: Option<PathBuf>,
+    pub dump_to: Option<PathBuf>,
+}
+
+impl StateCommand {
+    pub fn from_env() -> Result<Self, StateError> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, StateError> {
+        let mut command = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let target = match arg.as_str() {
+                "--restore-state" => &mut command.restore_from,
+                "--dump-state" => &mut command.dump_to,
+                _ => continue,
+            };
+            let path = args
+                .next()
+                .ok_or_else(|| StateError::InvalidArgs(format!("{} requires a path", arg)))?;
+            *target = Some(PathBuf::from(path));
+        }
+
+        Ok(command)
+    }
+
+    /// Restores `server` from the `--restore-state` file, if one was given.
+    pub async fn restore<S: StatefulServer>(&self, server: &mut S) -> Result<(), StateError> {
+        if let Some(path) = &self.restore_from {
+            load_snapshot(server, path).await?;
+            tracing::info!("Restored {} state from {}", S::STATE_NAME, path.display());
+        }
+        Ok(())
+    }
+
+    /// Dumps `server` to the `--dump-state` file, if one was given.
+    pub async fn dump<S: StatefulServer>(&self, server: &S) -> Result<(), StateError> {
+        if let Some(path) = &self.dump_to {
+            save_snapshot(server, path).await?;
+            tracing::info!("Dumped {} state to {}", S::STATE_NAME, path.display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct CounterServer {
+        counters: HashMap<String, u64>,
+    }
+
+    impl StatefulServer for CounterServer {
+        const STATE_NAME: &'static str = "counter";
+
+        async fn snapshot(&self) -> Result<Value, StateError> {
+            Ok(serde_json::to_value(&self.counters)?)
+        }
+
+        async fn restore(&mut self, state: Value) -> Result<(), StateError> {
+            self.counters = serde_json::from_value(state)?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        let mut server = CounterServer::default();
+        server.counters.insert("requests".to_string(), 42);
+        save_snapshot(&server, &path).await.unwrap();
+
+        let mut restored = CounterServer::default();
+        load_snapshot(&mut restored, &path).await.unwrap();
+        assert_eq!(restored.counters.get("requests"), Some(&42));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_foreign_snapshot() {
+        let snapshot = StateSnapshot {
+            server: "other".to_string(),
+            version: 1,
+            taken_at: Utc::now(),
+            state: serde_json::json!({}),
+        };
+
+        let mut server = CounterServer::default();
+        let result = snapshot.apply(&mut server).await;
+        assert!(matches!(result, Err(StateError::WrongServer { .. })));
+    }
+
+    #[test]
+    fn test_parse_state_command() {
+        let args = [
+            "--verbose",
+            "--restore-state",
+            "in.json",
+            "--dump-state",
+            "out.json",
+        ];
+        let command = StateCommand::parse(args.iter().map(|a| a.to_string())).unwrap();
+        assert_eq!(command.restore_from, Some(PathBuf::from("in.json")));
+        assert_eq!(command.dump_to, Some(PathBuf::from("out.json")));
+
+        let missing = StateCommand::parse(["--dump-state".to_string()]);
+        assert!(matches!(missing, Err(StateError::InvalidArgs(_))));
+    }
+}
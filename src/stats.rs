@@ -0,0 +1,285 @@
+//! The `get_tool_stats` tool every [`McpServer`] offers.
+//!
+//! The runtime measures each `tools/call` it dispatches: the size of the
+//! arguments it received, the size of the result it sent back, and how long
+//! the tool took. [`ToolStats`] aggregates these per tool so operators can ask
+//! a running server which tools are slow or return too much, and every call
+//! is also fed into a [`MetricsRegistry`] as
+//!
+//! - `mcp_tool_calls_total{server, tool, status}`, status `ok` or `error`
+//! - `mcp_tool_request_bytes_total{server, tool}`
+//! - `mcp_tool_response_bytes_total{server, tool}`
+//! - `mcp_tool_duration_seconds{server, tool}`, a histogram
+//!
+//! [`McpServer`]: crate::server::McpServer
+
+use crate::error::McpError;
+use crate::metrics::MetricsRegistry;
+use crate::protocol::{Tool, ToolAnnotations};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const TOOL_NAME: &str = "get_tool_stats";
+
+// Percentiles are computed over this many of each tool's latest calls
+const RECENT_CALLS: usize = 1000;
+
+/// One measured tool call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCallMeasurement<'a> {
+    pub tool: &'a str,
+    /// Size of the call's arguments as JSON.
+    pub bytes_in: usize,
+    /// Size of the `tools/call` result as JSON.
+    pub bytes_out: usize,
+    pub latency: Duration,
+    pub is_error: bool,
+}
+
+impl ToolCallMeasurement<'_> {
+    /// Records the call in `registry`, labelled with the server's name.
+    pub fn record_metrics(&self, registry: &MetricsRegistry, server: &str) {
+        let labels = [("server", server), ("tool", self.tool)];
+        let status = if self.is_error { "error" } else { "ok" };
+        registry.increment(
+            "mcp_tool_calls_total",
+            &[labels[0], labels[1], ("status", status)],
+            1,
+        );
+        registry.increment(
+            "mcp_tool_request_bytes_total",
+            &labels,
+            self.bytes_in as u64,
+        );
+        registry.increment(
+            "mcp_tool_response_bytes_total",
+            &labels,
+            self.bytes_out as u64,
+        );
+        registry.observe(
+            "mcp_tool_duration_seconds",
+            &labels,
+            self.latency.as_secs_f64(),
+        );
+    }
+}
+
+/// Bytes in or out of one tool.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ByteSummary {
+    pub total: u64,
+    pub mean: u64,
+    pub max: u64,
+}
+
+/// Latencies of one tool, in milliseconds. Percentiles cover recent calls.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+/// What [`ToolStats`] knows about one tool.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolSummary {
+    pub tool: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub bytes_in: ByteSummary,
+    pub bytes_out: ByteSummary,
+    pub latency: LatencySummary,
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    calls: u64,
+    errors: u64,
+    bytes_in: u64,
+    max_bytes_in: u64,
+    bytes_out: u64,
+    max_bytes_out: u64,
+    latency: Duration,
+    max_latency: Duration,
+    recent: VecDeque<Duration>,
+}
+
+impl Totals {
+    fn summary(&self, tool: &str) -> ToolSummary {
+        let mean = |total: u64| total / self.calls.max(1);
+        let millis = |latency: Duration| latency.as_secs_f64() * 1000.0;
+        let mut recent: Vec<Duration> = self.recent.iter().copied().collect();
+        recent.sort();
+        let percentile = |p: f64| {
+            let rank = ((recent.len() as f64 * p).ceil() as usize).saturating_sub(1);
+            recent.get(rank).copied().map_or(0.0, millis)
+        };
+
+        ToolSummary {
+            tool: tool.to_string(),
+            calls: self.calls,
+            errors: self.errors,
+            bytes_in: ByteSummary {
+                total: self.bytes_in,
+                mean: mean(self.bytes_in),
+                max: self.max_bytes_in,
+            },
+            bytes_out: ByteSummary {
+                total: self.bytes_out,
+                mean: mean(self.bytes_out),
+                max: self.max_bytes_out,
+            },
+            latency: LatencySummary {
+                mean_ms: millis(self.latency) / self.calls.max(1) as f64,
+                p50_ms: percentile(0.5),
+                p95_ms: percentile(0.95),
+                max_ms: millis(self.max_latency),
+            },
+        }
+    }
+}
+
+/// Per-tool totals of the calls a server has answered.
+#[derive(Debug, Default)]
+pub struct ToolStats {
+    tools: Mutex<BTreeMap<String, Totals>>,
+}
+
+impl ToolStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, call: &ToolCallMeasurement<'_>) {
+        let mut tools = self.tools.lock().unwrap();
+        let totals = tools.entry(call.tool.to_string()).or_default();
+        totals.calls += 1;
+        totals.errors += u64::from(call.is_error);
+        totals.bytes_in += call.bytes_in as u64;
+        totals.max_bytes_in = totals.max_bytes_in.max(call.bytes_in as u64);
+        totals.bytes_out += call.bytes_out as u64;
+        totals.max_bytes_out = totals.max_bytes_out.max(call.bytes_out as u64);
+        totals.latency += call.latency;
+        totals.max_latency = totals.max_latency.max(call.latency);
+        if totals.recent.len() == RECENT_CALLS {
+            totals.recent.pop_front();
+        }
+        totals.recent.push_back(call.latency);
+    }
+
+    /// Summaries of every tool called so far, by name.
+    pub fn summaries(&self) -> Vec<ToolSummary> {
+        self.tools
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(tool, totals)| totals.summary(tool))
+            .collect()
+    }
+
+    pub fn summary(&self, tool: &str) -> Option<ToolSummary> {
+        let tools = self.tools.lock().unwrap();
+        tools.get(tool).map(|totals| totals.summary(tool))
+    }
+
+    /// The `get_tool_stats` result: all tools, or only `arguments.tool`.
+    pub fn report(&self, arguments: &Value) -> Result<Value, McpError> {
+        let tools: Vec<ToolSummary> = match arguments.get("tool").and_then(Value::as_str) {
+            Some(tool) => self.summary(tool).into_iter().collect(),
+            None => self.summaries(),
+        };
+        let tools = serde_json::to_value(tools)
+            .map_err(|e| format!("Failed to serialize tool stats: {}", e))?;
+        Ok(serde_json::json!({ "tools": tools }))
+    }
+}
+
+/// The `get_tool_stats` tool as listed by `tools/list`.
+pub fn tool() -> Tool {
+    Tool {
+        name: TOOL_NAME.to_string(),
+        description: "Report call counts, errors, request and response sizes and latency \
+                      (mean, p50, p95, max) for each tool of this server"
+            .to_string(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "tool": {
+                    "type": "string",
+                    "description": "Only report this tool"
+                }
+            }
+        }),
+        annotations: Some(ToolAnnotations::read_only()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(tool: &str, millis: u64, is_error: bool) -> ToolCallMeasurement<'_> {
+        ToolCallMeasurement {
+            tool,
+            bytes_in: 10,
+            bytes_out: 100 * millis as usize,
+            latency: Duration::from_millis(millis),
+            is_error,
+        }
+    }
+
+    #[test]
+    fn test_aggregates_per_tool() {
+        let stats = ToolStats::new();
+        for millis in 1..=20 {
+            stats.record(&call("search", millis, millis == 20));
+        }
+        stats.record(&call("echo", 5, false));
+
+        let search = stats.summary("search").unwrap();
+        assert_eq!(search.calls, 20);
+        assert_eq!(search.errors, 1);
+        assert_eq!(search.bytes_in.total, 200);
+        assert_eq!(search.bytes_out.max, 2000);
+        assert_eq!(search.latency.p50_ms, 10.0);
+        assert_eq!(search.latency.p95_ms, 19.0);
+        assert_eq!(search.latency.max_ms, 20.0);
+
+        let report = stats
+            .report(&serde_json::json!({ "tool": "echo" }))
+            .unwrap();
+        assert_eq!(report["tools"].as_array().unwrap().len(), 1);
+        assert_eq!(report["tools"][0]["latency"]["mean_ms"], 5.0);
+        let report = stats.report(&Value::Null).unwrap();
+        assert_eq!(report["tools"][0]["tool"], "echo");
+    }
+
+    #[test]
+    fn test_feeds_metrics() {
+        let registry = MetricsRegistry::new();
+        call("search", 30, false).record_metrics(&registry, "docs");
+        call("search", 30, true).record_metrics(&registry, "docs");
+
+        let labels = [("server", "docs"), ("tool", "search")];
+        let calls = |status| {
+            registry.counter(
+                "mcp_tool_calls_total",
+                &[labels[0], labels[1], ("status", status)],
+            )
+        };
+        assert_eq!(calls("ok"), 1);
+        assert_eq!(calls("error"), 1);
+        assert_eq!(
+            registry.counter("mcp_tool_response_bytes_total", &labels),
+            6000
+        );
+        let histogram = registry
+            .histogram("mcp_tool_duration_seconds", &labels)
+            .unwrap();
+        assert_eq!(histogram.count, 2);
+    }
+}
@@ -0,0 +1,177 @@
+//! Opt-in OpenTelemetry trace export.
+//!
+//! Built with `--features telemetry` and run with
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` set (e.g. `http://localhost:4318`),
+//! [`crate::logging::init`] adds a layer that exports spans over OTLP/HTTP
+//! next to the usual log output. Every tool call runs in the `tool_call` span
+//! of [`crate::logging::ToolCallLog`], so each call arrives as one span with
+//! its `tool`, `request_id`, `duration_ms` and `status`, and failed calls are
+//! marked with the error status. Spans are exported from info level up
+//! whatever `RUST_LOG` says, so a quiet console still yields full traces.
+//!
+//! Spans are named after the server through `OTEL_SERVICE_NAME`, which
+//! defaults to the executable name. Without the feature the endpoint is
+//! ignored with a warning, and nothing extra is compiled in.
+//!
+//! Spans are sent in batches: keep the [`TelemetryGuard`] returned by
+//! [`crate::logging::init`] alive until `main` returns so the last batch is
+//! flushed.
+
+/// Where to send traces; export is off when unset.
+pub const ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// The `service.name` reported with every span.
+pub const SERVICE_NAME_ENV: &str = "OTEL_SERVICE_NAME";
+
+/// Used when neither [`SERVICE_NAME_ENV`] nor the executable name is known.
+const DEFAULT_SERVICE_NAME: &str = "mcp-server";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryConfig {
+    pub endpoint: String,
+    pub service_name: String,
+}
+
+impl TelemetryConfig {
+    /// Reads the configuration from the environment; `None` when no
+    /// endpoint is configured.
+    pub fn from_env() -> Option<Self> {
+        let executable = std::env::current_exe().ok().and_then(|path| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        });
+        Self::from_vars(
+            std::env::var(ENDPOINT_ENV).ok(),
+            std::env::var(SERVICE_NAME_ENV).ok(),
+            executable,
+        )
+    }
+
+    fn from_vars(
+        endpoint: Option<String>,
+        service_name: Option<String>,
+        executable: Option<String>,
+    ) -> Option<Self> {
+        let endpoint = endpoint.filter(|e| !e.trim().is_empty())?;
+        let service_name = service_name
+            .filter(|name| !name.trim().is_empty())
+            .or(executable)
+            .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+        Some(Self {
+            endpoint: endpoint.trim().to_string(),
+            service_name,
+        })
+    }
+}
+
+/// Flushes and shuts down trace export when dropped.
+#[must_use = "dropping the guard stops trace export"]
+#[derive(Debug, Default)]
+pub struct TelemetryGuard {
+    #[cfg(feature = "telemetry")]
+    active: bool,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "telemetry")]
+        if self.active {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// A layer exporting spans to the configured collector, its guard, and a
+/// warning to log once the subscriber is up when export could not start.
+#[cfg(feature = "telemetry")]
+pub(crate) fn layer<S>(
+    config: Option<TelemetryConfig>,
+) -> (
+    Option<tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>>,
+    TelemetryGuard,
+    Option<String>,
+)
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    use opentelemetry_otlp::WithExportConfig;
+
+    let Some(config) = config else {
+        return (None, TelemetryGuard::default(), None);
+    };
+    let resource = opentelemetry_sdk::Resource::new([opentelemetry::KeyValue::new(
+        "service.name",
+        config.service_name.clone(),
+    )]);
+    let installed = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(&config.endpoint),
+        )
+        .with_trace_config(opentelemetry_sdk::trace::config().with_resource(resource))
+        .install_batch(opentelemetry_sdk::runtime::Tokio);
+
+    match installed {
+        Ok(tracer) => (
+            Some(tracing_opentelemetry::layer().with_tracer(tracer)),
+            TelemetryGuard { active: true },
+            None,
+        ),
+        Err(e) => (
+            None,
+            TelemetryGuard::default(),
+            Some(format!(
+                "Failed to start trace export to {}: {}",
+                config.endpoint, e
+            )),
+        ),
+    }
+}
+
+#[cfg(not(feature = "telemetry"))]
+pub(crate) fn layer(
+    config: Option<TelemetryConfig>,
+) -> (
+    Option<tracing_subscriber::layer::Identity>,
+    TelemetryGuard,
+    Option<String>,
+) {
+    let warning = config.map(|_| {
+        format!(
+            "{} is set but trace export is not built in; rebuild with --features telemetry",
+            ENDPOINT_ENV
+        )
+    });
+    (None, TelemetryGuard::default(), warning)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(endpoint: Option<&str>, service_name: Option<&str>) -> Option<TelemetryConfig> {
+        TelemetryConfig::from_vars(
+            endpoint.map(str::to_string),
+            service_name.map(str::to_string),
+            Some("example_13_auth".to_string()),
+        )
+    }
+
+    #[test]
+    fn test_config_requires_endpoint() {
+        assert_eq!(vars(None, Some("auth")), None);
+        assert_eq!(vars(Some("  "), Some("auth")), None);
+
+        let config = vars(Some("http://localhost:4318 "), None).unwrap();
+        assert_eq!(config.endpoint, "http://localhost:4318");
+        assert_eq!(config.service_name, "example_13_auth");
+
+        let config = vars(Some("http://localhost:4318"), Some("auth")).unwrap();
+        assert_eq!(config.service_name, "auth");
+
+        let config = TelemetryConfig::from_vars(Some("http://collector".to_string()), None, None);
+        assert_eq!(config.unwrap().service_name, DEFAULT_SERVICE_NAME);
+    }
+}
@@ -0,0 +1,39 @@
+//! A common interface over the example servers' tools.
+//!
+//! Every server example exposes `list_tools`/`call_tool`, but some are
+//! synchronous and each dispatches in its own way. [`ToolServer`] papers over
+//! those differences so a client can discover and call tools on several
+//! servers at once, as an agent would, and so [`McpServer`] can serve any of
+//! them over JSON-RPC.
+//!
+//! [`McpServer`]: crate::server::McpServer
+
+use crate::error::McpError;
+use futures::future::BoxFuture;
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+/// A tool as advertised to clients.
+pub type ToolDescriptor = crate::protocol::Tool;
+
+/// A server whose tools can be discovered and called through a trait object.
+pub trait ToolServer: Send + Sync {
+    /// A short identifier for the server, e.g. `"file_operations"`.
+    fn server_name(&self) -> &str;
+
+    /// The tools this server currently offers.
+    fn tool_descriptors(&self) -> Vec<ToolDescriptor>;
+
+    /// Calls one of this server's tools.
+    fn invoke_tool<'a>(
+        &'a self,
+        name: &'a str,
+        arguments: Value,
+    ) -> BoxFuture<'a, Result<Value, McpError>>;
+
+    /// Receives a message whenever the tools change. Servers with a fixed set
+    /// of tools return `None`.
+    fn tool_list_changes(&self) -> Option<broadcast::Receiver<()>> {
+        None
+    }
+}
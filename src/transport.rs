@@ -0,0 +1,902 @@
+//! Newline-delimited JSON-RPC over stdin/stdout.
+//!
+//! This is how MCP clients such as Claude Desktop talk to a local server: they
+//! start the binary, write one JSON-RPC message per line to its stdin and read
+//! one response per line from its stdout. Anything else the server prints must
+//! go to stderr, which is why the examples use `eprintln!` and log to stderr.
+//!
+//! ```json
+//! {
+//!   "mcpServers": {
+//!     "calculator": {
+//!       "command": "/path/to/target/release/example_02_calculator"
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! With [`StdioTransport::with_keepalive`] the transport also pings a client
+//! that has gone quiet and stops serving one that no longer answers. Servers
+//! with resources get their changes sent to the client as
+//! `notifications/resources/updated`, with what changed when the provider
+//! knows (see [`resource_diff`](crate::resource_diff)), servers whose tools
+//! change at runtime send `notifications/tools/list_changed`, and log
+//! messages go out as `notifications/message`. What the client declared
+//! in `initialize` is available to the code handling its requests through
+//! [`capabilities::current`], and state kept for the connection through
+//! [`session::current`]. The transport keeps reading while a request is
+//! handled, so a `notifications/cancelled` from the client aborts it; see
+//! [`cancellation`]. Progress the request reports goes out meanwhile as
+//! `notifications/progress`; see [`progress`]. A tool that asks the user for
+//! input sends an `elicitation/create` request, and the client's answer is
+//! passed back to it; see [`elicitation`].
+
+use crate::cancellation::{self, InFlight};
+use crate::capabilities::{self, ClientCapabilities};
+use crate::elicitation::{self, ElicitationSink};
+use crate::keepalive::{DisconnectReason, Keepalive, KeepaliveAction, KeepaliveConfig};
+use crate::progress;
+use crate::protocol::{JsonRpcRequest, LoggingMessageParams};
+use crate::resource_diff::ResourceUpdate;
+use crate::server::McpServer;
+use crate::session::{self, SessionStore};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::io::{
+    stdin, stdout, AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, Lines,
+    Stdin, Stdout,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// The flag that makes the demo examples serve over stdio instead of running
+/// their demo.
+pub const STDIO_FLAG: &str = "--stdio";
+
+/// Returns true when the example was started with [`STDIO_FLAG`].
+pub fn stdio_requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == STDIO_FLAG)
+}
+
+/// Reads requests from `reader` and writes responses to `writer`, one JSON
+/// message per line, until the reader reaches end of input.
+pub struct StdioTransport<R, W> {
+    reader: R,
+    writer: W,
+    keepalive: Option<KeepaliveConfig>,
+}
+
+impl StdioTransport<BufReader<Stdin>, Stdout> {
+    /// A transport over the process's stdin and stdout.
+    pub fn new() -> Self {
+        Self::with_io(BufReader::new(stdin()), stdout())
+    }
+}
+
+impl Default for StdioTransport<BufReader<Stdin>, Stdout> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R, W> StdioTransport<R, W>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    /// A transport over arbitrary streams, e.g. in-memory buffers in tests.
+    pub fn with_io(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            keepalive: None,
+        }
+    }
+
+    /// Pings the client when it has been quiet for `config.interval`, and
+    /// stops serving it if a ping goes unanswered for `config.timeout`.
+    pub fn with_keepalive(mut self, config: KeepaliveConfig) -> Self {
+        self.keepalive = Some(config);
+        self
+    }
+
+    /// Serves `server` until the client goes away, and returns why. The
+    /// server's [`self_check`](McpServer::self_check) runs first. Malformed
+    /// lines are answered with a JSON-RPC error rather than ending the
+    /// session; only I/O failures do.
+    pub async fn serve(self, server: &McpServer) -> std::io::Result<DisconnectReason> {
+        tracing::info!(server = %server.info().name, "Serving MCP over stdio");
+        server.self_check().await;
+        let mut lines = self.reader.lines();
+        let mut writer = self.writer;
+        let mut keepalive = self
+            .keepalive
+            .map(|config| Keepalive::new(config, Instant::now()));
+        let mut updates = server.resource_updates();
+        let mut tool_changes = server.tool_list_changes();
+        let mut log_messages = server.log_messages();
+        let mut client = None;
+        let session = Arc::new(SessionStore::with_limits(server.session_limits()));
+        let in_flight = InFlight::new();
+        let (progress_sink, mut progress) = progress::channel();
+        let (elicitations, mut elicitation_requests) = elicitation::channel();
+        // Lines that arrived while a request was handled
+        let mut queued = VecDeque::new();
+        let mut closed = false;
+
+        let reason = loop {
+            let deadline = keepalive.as_ref().map(Keepalive::next_deadline);
+            // next_line is cancel safe, so a keepalive tick never loses input
+            let line = tokio::select! {
+                line = next_line(&mut lines, &mut queued, closed) => line?,
+                update = next_update(&mut updates) => {
+                    if let Some(update) = update {
+                        notify(&mut writer, server, resource_updated(update)).await?;
+                    }
+                    continue;
+                }
+                changed = next_tool_change(&mut tool_changes) => {
+                    if changed {
+                        notify(&mut writer, server, tools_list_changed()).await?;
+                    }
+                    continue;
+                }
+                message = next_log_message(&mut log_messages, server) => {
+                    if let Some(message) = message {
+                        notify(&mut writer, server, log_message(message)).await?;
+                    }
+                    continue;
+                }
+                _ = sleep_until(deadline) => {
+                    let Some(keepalive) = keepalive.as_mut() else { continue };
+                    match keepalive.poll(Instant::now()) {
+                        KeepaliveAction::Wait => {}
+                        KeepaliveAction::Ping(ping) => write_line(&mut writer, &ping).await?,
+                        KeepaliveAction::Disconnect(reason) => break reason,
+                    }
+                    continue;
+                }
+            };
+            let Some(line) = line else {
+                break DisconnectReason::Closed;
+            };
+
+            if let Some(keepalive) = keepalive.as_mut() {
+                keepalive.record_activity(Instant::now());
+            }
+            let message = line.trim();
+            // Other responses can only be answers to our pings, or to
+            // elicitation requests whose tool call gave up waiting
+            if message.is_empty() || elicitations.resolve(message) || is_response(message) {
+                continue;
+            }
+
+            // Remembered for the rest of the session
+            if let Some(declared) = ClientCapabilities::from_message(message) {
+                client = Some(Arc::new(declared));
+            }
+            if in_flight.cancel(message).is_some() {
+                continue;
+            }
+            let handled = in_flight.track(
+                message,
+                capabilities::scope(
+                    client.clone(),
+                    session::scope(
+                        session.clone(),
+                        elicitation::scope(
+                            elicitations.clone(),
+                            progress::scope(progress_sink.clone(), server.handle_message(message)),
+                        ),
+                    ),
+                ),
+            );
+            let reading = Reading {
+                lines: &mut lines,
+                queued: &mut queued,
+                closed: &mut closed,
+                in_flight: &in_flight,
+                progress: &mut progress,
+                elicitations: &elicitations,
+                elicitation_requests: &mut elicitation_requests,
+                writer: &mut writer,
+                server,
+            };
+            // Notifications and cancelled requests get no response
+            if let Some(response) = reading.until(handled).await?.flatten() {
+                write_line(&mut writer, &response).await?;
+            }
+        };
+
+        match &reason {
+            DisconnectReason::Closed => {
+                tracing::info!(server = %server.info().name, %reason, "Client disconnected")
+            }
+            DisconnectReason::KeepaliveTimeout { .. } => {
+                tracing::warn!(server = %server.info().name, %reason, "Client disconnected")
+            }
+        }
+        Ok(reason)
+    }
+}
+
+// The next line to handle: a queued one first, then one from the client
+async fn next_line<R: AsyncBufRead + Unpin>(
+    lines: &mut Lines<R>,
+    queued: &mut VecDeque<String>,
+    closed: bool,
+) -> std::io::Result<Option<String>> {
+    match queued.pop_front() {
+        Some(line) => Ok(Some(line)),
+        None if closed => Ok(None),
+        None => lines.next_line().await,
+    }
+}
+
+// Reads on while a request is handled, so a cancellation can reach it.
+// Everything else is queued for after the request, and a request cancelled
+// before its turn is dropped from the queue. The progress the request
+// reports is written as it comes, and so are the questions it asks the
+// user, whose answers go straight back to it.
+struct Reading<'a, R, W> {
+    lines: &'a mut Lines<R>,
+    queued: &'a mut VecDeque<String>,
+    closed: &'a mut bool,
+    in_flight: &'a InFlight,
+    progress: &'a mut mpsc::UnboundedReceiver<JsonRpcRequest>,
+    elicitations: &'a ElicitationSink,
+    elicitation_requests: &'a mut mpsc::UnboundedReceiver<JsonRpcRequest>,
+    writer: &'a mut W,
+    server: &'a McpServer,
+}
+
+impl<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin> Reading<'_, R, W> {
+    async fn until<F: Future>(self, handled: F) -> std::io::Result<F::Output> {
+        tokio::pin!(handled);
+        loop {
+            tokio::select! {
+                output = &mut handled => {
+                    // Progress goes out before the response it leads up to
+                    while let Ok(notification) = self.progress.try_recv() {
+                        notify(self.writer, self.server, notification).await?;
+                    }
+                    return Ok(output);
+                }
+                Some(notification) = self.progress.recv() => {
+                    notify(self.writer, self.server, notification).await?;
+                }
+                Some(request) = self.elicitation_requests.recv() => {
+                    write_line(self.writer, &request).await?;
+                }
+                line = self.lines.next_line(), if !*self.closed => match line? {
+                    Some(line) if self.elicitations.resolve(line.trim()) => {}
+                    Some(line) => match self.in_flight.cancel(line.trim()) {
+                        Some(id) => self.queued.retain(|queued| {
+                            cancellation::request_id(queued.trim()).as_ref() != Some(&id)
+                        }),
+                        None => self.queued.push_back(line),
+                    },
+                    // Finish the request before reporting the disconnect
+                    None => *self.closed = true,
+                },
+            }
+        }
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+// Waits for the next changed resource. Without resources this never returns.
+pub(crate) async fn next_update(
+    updates: &mut Option<broadcast::Receiver<ResourceUpdate>>,
+) -> Option<ResourceUpdate> {
+    let Some(receiver) = updates.as_mut() else {
+        return std::future::pending().await;
+    };
+    let received = receiver.recv().await;
+    match received {
+        Ok(update) => Some(update),
+        Err(RecvError::Lagged(missed)) => {
+            tracing::warn!(missed, "Dropped resource updates for a slow client");
+            None
+        }
+        Err(RecvError::Closed) => {
+            *updates = None;
+            None
+        }
+    }
+}
+
+// Waits for the next change to the tool list. Missed changes still mean the
+// list changed, so a lagging receiver reports one change for all of them.
+pub(crate) async fn next_tool_change(changes: &mut Option<broadcast::Receiver<()>>) -> bool {
+    let Some(receiver) = changes.as_mut() else {
+        return std::future::pending().await;
+    };
+    match receiver.recv().await {
+        Ok(()) | Err(RecvError::Lagged(_)) => true,
+        Err(RecvError::Closed) => {
+            *changes = None;
+            false
+        }
+    }
+}
+
+// Waits for the next log message the client wants. Without log messages
+// this never returns.
+pub(crate) async fn next_log_message(
+    messages: &mut Option<broadcast::Receiver<LoggingMessageParams>>,
+    server: &McpServer,
+) -> Option<LoggingMessageParams> {
+    let Some(receiver) = messages.as_mut() else {
+        return std::future::pending().await;
+    };
+    match receiver.recv().await {
+        Ok(message) => server.wants_log(message.level).then_some(message),
+        Err(RecvError::Lagged(missed)) => {
+            tracing::warn!(missed, "Dropped log messages for a slow client");
+            None
+        }
+        Err(RecvError::Closed) => {
+            *messages = None;
+            None
+        }
+    }
+}
+
+pub(crate) fn log_message(message: LoggingMessageParams) -> JsonRpcRequest {
+    JsonRpcRequest::notification("notifications/message", serde_json::json!(message))
+}
+
+pub(crate) fn tools_list_changed() -> JsonRpcRequest {
+    JsonRpcRequest::notification("notifications/tools/list_changed", Value::Null)
+}
+
+pub(crate) fn resource_updated(update: ResourceUpdate) -> JsonRpcRequest {
+    JsonRpcRequest::notification("notifications/resources/updated", update.params())
+}
+
+// Sends a notification, unless the server drops it
+async fn notify<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    server: &McpServer,
+    notification: JsonRpcRequest,
+) -> std::io::Result<()> {
+    if !server.delivers_notification(&notification) {
+        return Ok(());
+    }
+    write_line(writer, &notification).await
+}
+
+async fn write_line<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &impl Serialize,
+) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await
+}
+
+fn is_response(message: &str) -> bool {
+    serde_json::from_str::<Value>(message)
+        .is_ok_and(|value| value.get("method").is_none() && value.get("id").is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elicitation::{
+        Elicitation, ElicitationOutcome, ElicitationSchema, TransportElicitor, CREATE_METHOD,
+    };
+    use crate::error::McpError;
+    use crate::protocol::{JsonRpcResponse, LoggingLevel, RequestId, Resource, Tool};
+    use crate::registry::ToolRegistry;
+    use crate::resource_diff::ResourceChange;
+    use crate::server::{ResourceProvider, ToolHandler, ToolRouter};
+    use futures::future::BoxFuture;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{duplex, AsyncReadExt};
+
+    struct Ping;
+
+    impl ToolHandler for Ping {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "ping".to_string(),
+                description: "Answer pong".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async { Ok(serde_json::json!("pong")) })
+        }
+    }
+
+    // Never answers, until its call is cancelled
+    struct Hang;
+
+    impl ToolHandler for Hang {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "hang".to_string(),
+                description: "Never answer".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    // Counts to three, reporting each step as progress
+    struct Count;
+
+    impl ToolHandler for Count {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "count".to_string(),
+                description: "Count to three".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async {
+                for step in 1..=3 {
+                    tokio::task::yield_now().await;
+                    progress::report(step as f64, Some(3.0), None);
+                }
+                Ok(Value::from(3))
+            })
+        }
+    }
+
+    // Asks the user to confirm, through the client of the call
+    struct Confirm;
+
+    impl ToolHandler for Confirm {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "confirm".to_string(),
+                description: "Ask the user to confirm".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, _arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async {
+                let elicitation =
+                    Elicitation::new(Arc::new(TransportElicitor), Duration::from_secs(5));
+                let schema = ElicitationSchema::new().boolean("confirm", "Go ahead?", true);
+                match elicitation.ask("Proceed?", schema).await {
+                    Ok(ElicitationOutcome::Accepted(content)) => Ok(content["confirm"].clone()),
+                    Ok(outcome) => Ok(Value::from(format!("{:?}", outcome))),
+                    Err(e) => Err(McpError::Unavailable(e.to_string())),
+                }
+            })
+        }
+    }
+
+    // Resources whose changes the test announces itself
+    struct Feed {
+        updates: broadcast::Sender<ResourceUpdate>,
+    }
+
+    impl ResourceProvider for Feed {
+        fn list_resources(&self) -> BoxFuture<'_, Result<Vec<Resource>, McpError>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn read_resource<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Result<Value, McpError>> {
+            Box::pin(async move { Err(McpError::NotFound(format!("Unknown resource: {}", uri))) })
+        }
+
+        fn subscribe(&self, _uri: &str) -> Result<(), McpError> {
+            Ok(())
+        }
+
+        fn unsubscribe(&self, _uri: &str) -> Result<(), McpError> {
+            Ok(())
+        }
+
+        fn updates(&self) -> broadcast::Receiver<ResourceUpdate> {
+            self.updates.subscribe()
+        }
+    }
+
+    fn server() -> McpServer {
+        McpServer::new(Arc::new(ToolRouter::new("pinger").with_handler(Ping)))
+    }
+
+    #[tokio::test]
+    async fn test_serves_one_response_per_request_line() {
+        let server = server();
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            "\n\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"ping"}}"#,
+            "\n",
+            "not json\n",
+        );
+        let mut output = Vec::new();
+
+        let reason = StdioTransport::with_io(input.as_bytes(), &mut output)
+            .serve(&server)
+            .await
+            .unwrap();
+        assert_eq!(reason, DisconnectReason::Closed);
+
+        let responses: Vec<JsonRpcResponse> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(
+            responses[0].result.as_ref().unwrap()["serverInfo"]["name"],
+            "pinger"
+        );
+        assert_eq!(
+            responses[1].result.as_ref().unwrap()["content"][0]["text"],
+            r#""pong""#
+        );
+        assert!(responses[2].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_requests_get_no_response() {
+        let server = McpServer::new(Arc::new(
+            ToolRouter::new("pinger")
+                .with_handler(Ping)
+                .with_handler(Hang),
+        ));
+        // The ping is cancelled while it waits behind the hanging call
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"hang"}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"ping"}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":2}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":1,"reason":"timeout"}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"ping"}}"#,
+            "\n",
+        );
+        let mut output = Vec::new();
+
+        let served = StdioTransport::with_io(input.as_bytes(), &mut output).serve(&server);
+        tokio::time::timeout(Duration::from_secs(5), served)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let responses: Vec<JsonRpcResponse> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].id, Some(RequestId::Number(3)));
+    }
+
+    #[tokio::test]
+    async fn test_sends_progress_before_the_response() {
+        let server = McpServer::new(Arc::new(ToolRouter::new("counter").with_handler(Count)));
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"count","_meta":{"progressToken":"c1"}}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"count"}}"#,
+            "\n",
+        );
+        let mut output = Vec::new();
+
+        StdioTransport::with_io(input.as_bytes(), &mut output)
+            .serve(&server)
+            .await
+            .unwrap();
+
+        let messages: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let methods: Vec<&str> = messages
+            .iter()
+            .map(|message| message["method"].as_str().unwrap_or("response"))
+            .collect();
+        // The call without a token gets no progress
+        assert_eq!(
+            methods,
+            [
+                "notifications/progress",
+                "notifications/progress",
+                "notifications/progress",
+                "response",
+                "response"
+            ]
+        );
+        assert_eq!(
+            messages[2]["params"],
+            serde_json::json!({ "progressToken": "c1", "progress": 3.0, "total": 3.0 })
+        );
+        assert_eq!(messages[3]["id"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_disconnects_silent_client() {
+        let server = server();
+        let config = KeepaliveConfig::new(Duration::from_millis(20), Duration::from_millis(50));
+        // The client end stays open but never writes
+        let (_client_input, input) = duplex(1024);
+        let (output, mut client_output) = duplex(1024);
+
+        let reason = StdioTransport::with_io(BufReader::new(input), output)
+            .with_keepalive(config)
+            .serve(&server)
+            .await
+            .unwrap();
+        assert_eq!(
+            reason,
+            DisconnectReason::KeepaliveTimeout {
+                timeout: config.timeout
+            }
+        );
+
+        let mut written = String::new();
+        client_output.read_to_string(&mut written).await.unwrap();
+        let ping: JsonRpcRequest = serde_json::from_str(written.trim()).unwrap();
+        assert_eq!(ping.method, "ping");
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_accepts_ping_answers() {
+        let server = server();
+        let config = KeepaliveConfig::new(Duration::from_millis(20), Duration::from_millis(50));
+        let (mut client_input, input) = duplex(1024);
+        let (output, client_output) = duplex(1024);
+
+        // A well-behaved client answers every ping, then hangs up
+        let client = tokio::spawn(async move {
+            let mut pings = BufReader::new(client_output).lines();
+            for _ in 0..3 {
+                let ping: JsonRpcRequest =
+                    serde_json::from_str(&pings.next_line().await.unwrap().unwrap()).unwrap();
+                let pong = JsonRpcResponse::success(ping.id, serde_json::json!({}));
+                let line = format!("{}\n", serde_json::to_string(&pong).unwrap());
+                client_input.write_all(line.as_bytes()).await.unwrap();
+            }
+        });
+
+        let reason = StdioTransport::with_io(BufReader::new(input), output)
+            .with_keepalive(config)
+            .serve(&server)
+            .await
+            .unwrap();
+        client.await.unwrap();
+        assert_eq!(reason, DisconnectReason::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_elicitation_answers_reach_the_waiting_tool_call() {
+        let server = McpServer::new(Arc::new(ToolRouter::new("asker").with_handler(Confirm)));
+        let (mut client_input, input) = duplex(1024);
+        let (output, client_output) = duplex(1024);
+
+        let client = tokio::spawn(async move {
+            let mut lines = BufReader::new(client_output).lines();
+            let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"capabilities":{"elicitation":{}}}}"#;
+            client_input
+                .write_all(format!("{}\n", initialize).as_bytes())
+                .await
+                .unwrap();
+            lines.next_line().await.unwrap().unwrap();
+
+            for (call, answer, expected) in [
+                (
+                    2,
+                    serde_json::json!({ "action": "accept", "content": { "confirm": true } }),
+                    "true",
+                ),
+                (
+                    3,
+                    serde_json::json!({ "action": "decline" }),
+                    r#""Declined""#,
+                ),
+            ] {
+                let call = serde_json::json!({
+                    "jsonrpc": "2.0", "id": call, "method": "tools/call", "params": { "name": "confirm" }
+                });
+                client_input
+                    .write_all(format!("{}\n", call).as_bytes())
+                    .await
+                    .unwrap();
+
+                // The call waits while the client is asked
+                let request: JsonRpcRequest =
+                    serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+                assert_eq!(request.method, CREATE_METHOD);
+                assert_eq!(request.params["message"], "Proceed?");
+                let answer = JsonRpcResponse::success(request.id, answer);
+                client_input
+                    .write_all(format!("{}\n", serde_json::to_string(&answer).unwrap()).as_bytes())
+                    .await
+                    .unwrap();
+
+                let response: JsonRpcResponse =
+                    serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+                assert_eq!(
+                    response.id,
+                    Some(RequestId::Number(call["id"].as_i64().unwrap()))
+                );
+                assert_eq!(response.result.unwrap()["content"][0]["text"], expected);
+            }
+        });
+
+        let served = StdioTransport::with_io(BufReader::new(input), output).serve(&server);
+        let reason = tokio::time::timeout(Duration::from_secs(5), served)
+            .await
+            .unwrap()
+            .unwrap();
+        client.await.unwrap();
+        assert_eq!(reason, DisconnectReason::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_sends_resource_updates() {
+        let feed = Arc::new(Feed {
+            updates: broadcast::channel(8).0,
+        });
+        let server = server().with_resources(feed.clone());
+        let (mut client_input, input) = duplex(1024);
+        let (output, client_output) = duplex(1024);
+
+        let client = tokio::spawn(async move {
+            let mut lines = BufReader::new(client_output).lines();
+            let subscribe = r#"{"jsonrpc":"2.0","id":1,"method":"resources/subscribe","params":{"uri":"feed://news"}}"#;
+            client_input
+                .write_all(format!("{}\n", subscribe).as_bytes())
+                .await
+                .unwrap();
+            let response: JsonRpcResponse =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            assert!(response.result.is_some());
+
+            feed.updates
+                .send(ResourceUpdate::new("feed://news"))
+                .unwrap();
+            let notification: JsonRpcRequest =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            assert!(notification.is_notification());
+            assert_eq!(notification.method, "notifications/resources/updated");
+            assert_eq!(
+                notification.params,
+                serde_json::json!({ "uri": "feed://news" })
+            );
+
+            // What changed travels in _meta
+            let change = ResourceChange::lines("old\n", "new\n");
+            let update = ResourceUpdate::new("feed://news").with_change(Some(change.clone()));
+            feed.updates.send(update).unwrap();
+            let notification: JsonRpcRequest =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            let sent: ResourceChange =
+                serde_json::from_value(notification.params["_meta"]["change"].clone()).unwrap();
+            assert_eq!(sent, change);
+        });
+
+        let reason = StdioTransport::with_io(BufReader::new(input), output)
+            .serve(&server)
+            .await
+            .unwrap();
+        client.await.unwrap();
+        assert_eq!(reason, DisconnectReason::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_sends_tool_list_changes() {
+        let registry = Arc::new(ToolRegistry::new("dynamic"));
+        let server = McpServer::new(registry.clone());
+        let (mut client_input, input) = duplex(1024);
+        let (output, client_output) = duplex(1024);
+
+        let client = tokio::spawn(async move {
+            let mut lines = BufReader::new(client_output).lines();
+            let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+            client_input
+                .write_all(format!("{}\n", initialize).as_bytes())
+                .await
+                .unwrap();
+            let response: JsonRpcResponse =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            assert_eq!(
+                response.result.unwrap()["capabilities"]["tools"]["listChanged"],
+                true
+            );
+
+            registry.register(Ping);
+            let notification: JsonRpcRequest =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            assert!(notification.is_notification());
+            assert_eq!(notification.method, "notifications/tools/list_changed");
+        });
+
+        let reason = StdioTransport::with_io(BufReader::new(input), output)
+            .serve(&server)
+            .await
+            .unwrap();
+        client.await.unwrap();
+        assert_eq!(reason, DisconnectReason::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_sends_log_messages_at_the_chosen_level() {
+        let (log, _) = broadcast::channel(8);
+        let server = server().with_log_messages(log.clone());
+        let (mut client_input, input) = duplex(1024);
+        let (output, client_output) = duplex(1024);
+
+        let client = tokio::spawn(async move {
+            let mut lines = BufReader::new(client_output).lines();
+            let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+            client_input
+                .write_all(format!("{}\n", initialize).as_bytes())
+                .await
+                .unwrap();
+            let response: JsonRpcResponse =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            assert!(response.result.unwrap()["capabilities"]["logging"].is_object());
+            let set_level = r#"{"jsonrpc":"2.0","id":2,"method":"logging/setLevel","params":{"level":"warning"}}"#;
+            client_input
+                .write_all(format!("{}\n", set_level).as_bytes())
+                .await
+                .unwrap();
+            lines.next_line().await.unwrap().unwrap();
+
+            // Only the warning is at or above the level the client chose
+            for (level, text) in [
+                (LoggingLevel::Info, "quiet"),
+                (LoggingLevel::Warning, "loud"),
+            ] {
+                log.send(LoggingMessageParams {
+                    level,
+                    logger: Some("test".to_string()),
+                    data: serde_json::json!(text),
+                })
+                .unwrap();
+            }
+            let notification: JsonRpcRequest =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            assert_eq!(notification.method, "notifications/message");
+            assert_eq!(
+                notification.params,
+                serde_json::json!({ "level": "warning", "logger": "test", "data": "loud" })
+            );
+        });
+
+        StdioTransport::with_io(BufReader::new(input), output)
+            .serve(&server)
+            .await
+            .unwrap();
+        client.await.unwrap();
+    }
+}
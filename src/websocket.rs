@@ -0,0 +1,964 @@
+//! MCP over WebSocket, for servers and the clients that talk to them.
+//!
+//! Each JSON-RPC message travels as one text message. On the server side,
+//! [`WebSocketTransport`] accepts connections through an HTTP upgrade and runs
+//! the same loop as [`StdioTransport`] for each of them, so keepalive, resource
+//! updates, tool list changes and elicitation work as they do over stdio.
+//!
+//! [`WebSocketClient`] keeps a connection to such a server. When the
+//! connection drops it reconnects with exponential backoff, repeats the
+//! `initialize` request if one was sent, and sends every request that had not
+//! been answered yet again, so callers only notice a delay. Requests may
+//! therefore reach the server twice; tools that are not idempotent should be
+//! called with that in mind.
+//!
+//! ```no_run
+//! # use mcp_rust_examples::websocket::{ReconnectPolicy, WebSocketClient};
+//! # async fn demo() -> Result<(), mcp_rust_examples::websocket::WsError> {
+//! let client = WebSocketClient::connect("ws://127.0.0.1:8765/ws", ReconnectPolicy::default()).await?;
+//! let tools = client.request("tools/list", serde_json::Value::Null).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Once a connection has negotiated [compression](crate::compression) in
+//! `initialize`, large messages travel compressed as binary messages instead;
+//! a client opts in with [`WebSocketClient::with_compression`].
+//!
+//! Framing, pings and closing are left to tokio-tungstenite. Connections use
+//! plain `ws://`, since the crate has no TLS connector.
+//!
+//! [`StdioTransport`]: crate::transport::StdioTransport
+use crate::capabilities::ClientCapabilities;
+use crate::compression::{self, Compression, CompressionError, Compressor};
+use crate::http_transport::origin_allowed;
+use crate::keepalive::KeepaliveConfig;
+use crate::protocol::{JsonRpcError, JsonRpcRequest, JsonRpcResponse, RequestId};
+use crate::server::McpServer;
+use crate::transport::StdioTransport;
+use futures::{SinkExt, StreamExt};
+use hyper::header::{self, HeaderValue};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{
+    duplex, split, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream,
+};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::{Role, WebSocketConfig};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// Where WebSocket connections are accepted unless configured otherwise.
+pub const DEFAULT_PATH: &str = "/ws";
+
+/// Larger messages end the connection.
+pub const MAX_MESSAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// How long [`WebSocketClient::request`] waits for an answer unless
+/// configured otherwise, reconnects included.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Between the WebSocket bridge and the stdio loop it feeds
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Errors raised by the WebSocket client.
+#[derive(Debug, thiserror::Error)]
+pub enum WsError {
+    #[error("connection failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid WebSocket URL '{0}'")]
+    InvalidUrl(String),
+
+    #[error("WebSocket handshake failed: {0}")]
+    Handshake(String),
+
+    #[error("WebSocket protocol error: {0}")]
+    Protocol(String),
+
+    #[error("gave up reconnecting after {0} attempts")]
+    ReconnectFailed(u32),
+
+    #[error("no response within {}ms", .0.as_millis())]
+    Timeout(Duration),
+
+    #[error("the client is closed")]
+    Closed,
+
+    #[error("server returned error {}: {}", .0.code, .0.message)]
+    Rpc(JsonRpcError),
+
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("unreadable compressed message: {0}")]
+    Compression(#[from] CompressionError),
+}
+
+impl From<tungstenite::Error> for WsError {
+    fn from(error: tungstenite::Error) -> Self {
+        match error {
+            tungstenite::Error::Io(e) => Self::Io(e),
+            tungstenite::Error::Http(response) => {
+                Self::Handshake(format!("server answered {}", response.status()))
+            }
+            tungstenite::Error::Url(e) => Self::InvalidUrl(e.to_string()),
+            e => Self::Protocol(e.to_string()),
+        }
+    }
+}
+
+// Both sides refuse messages, and single frames, over the size limit
+fn config() -> WebSocketConfig {
+    WebSocketConfig::default()
+        .max_message_size(Some(MAX_MESSAGE_BYTES))
+        .max_frame_size(Some(MAX_MESSAGE_BYTES))
+}
+
+pub struct WebSocketTransport {
+    addr: SocketAddr,
+    path: String,
+    allowed_origins: Vec<String>,
+    keepalive: Option<KeepaliveConfig>,
+}
+
+impl WebSocketTransport {
+    /// Listens on `addr`. Bind to a loopback address unless the server is
+    /// meant to be reached from other machines.
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            path: DEFAULT_PATH.to_string(),
+            allowed_origins: Vec::new(),
+            keepalive: None,
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Accepts connections from browser pages on these origins besides
+    /// localhost.
+    pub fn with_allowed_origins(mut self, origins: Vec<String>) -> Self {
+        self.allowed_origins = origins;
+        self
+    }
+
+    /// Pings quiet clients and drops the ones that stop answering, as
+    /// [`StdioTransport::with_keepalive`] does.
+    pub fn with_keepalive(mut self, config: KeepaliveConfig) -> Self {
+        self.keepalive = Some(config);
+        self
+    }
+
+    /// Serves `server` until the listener fails.
+    pub async fn serve(self, server: Arc<McpServer>) -> hyper::Result<()> {
+        let (_, running) = self.bind(server)?;
+        running.await
+    }
+
+    /// Serves `server` on a background task and returns the address it
+    /// listens on, which is useful when binding to port 0 in tests.
+    pub fn spawn(
+        self,
+        server: Arc<McpServer>,
+    ) -> hyper::Result<(SocketAddr, JoinHandle<hyper::Result<()>>)> {
+        let (addr, running) = self.bind(server)?;
+        Ok((addr, tokio::spawn(running)))
+    }
+
+    fn bind(
+        self,
+        server: Arc<McpServer>,
+    ) -> hyper::Result<(
+        SocketAddr,
+        impl std::future::Future<Output = hyper::Result<()>>,
+    )> {
+        let name = server.info().name.clone();
+        let state = Arc::new(Upgrader {
+            server,
+            path: self.path,
+            allowed_origins: self.allowed_origins,
+            keepalive: self.keepalive,
+        });
+        let make_service = make_service_fn(move |_connection| {
+            let state = state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(state.upgrade(request)) }
+                }))
+            }
+        });
+        let running = Server::try_bind(&self.addr)?.serve(make_service);
+        let addr = running.local_addr();
+
+        Ok((addr, async move {
+            tracing::info!(server = %name, %addr, "Serving MCP over WebSocket");
+            running.await
+        }))
+    }
+}
+
+struct Upgrader {
+    server: Arc<McpServer>,
+    path: String,
+    allowed_origins: Vec<String>,
+    keepalive: Option<KeepaliveConfig>,
+}
+
+impl Upgrader {
+    fn upgrade(self: &Arc<Self>, request: Request<Body>) -> Response<Body> {
+        if request.uri().path() != self.path {
+            return status(StatusCode::NOT_FOUND);
+        }
+        let origin = request.headers().get(header::ORIGIN);
+        if !origin_allowed(origin, &self.allowed_origins) {
+            tracing::warn!(?origin, "Rejected WebSocket from a foreign origin");
+            return status(StatusCode::FORBIDDEN);
+        }
+        let headers = request.headers();
+        let has_token = |name: header::HeaderName, token: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| {
+                    value
+                        .split(',')
+                        .any(|part| part.trim().eq_ignore_ascii_case(token))
+                })
+        };
+        if request.method() != Method::GET
+            || !has_token(header::UPGRADE, "websocket")
+            || !has_token(header::CONNECTION, "upgrade")
+        {
+            return status(StatusCode::BAD_REQUEST);
+        }
+        if headers
+            .get(header::SEC_WEBSOCKET_VERSION)
+            .is_none_or(|version| version != "13")
+        {
+            let mut response = status(StatusCode::UPGRADE_REQUIRED);
+            response.headers_mut().insert(
+                header::SEC_WEBSOCKET_VERSION,
+                HeaderValue::from_static("13"),
+            );
+            return response;
+        }
+        let Some(accept) = headers
+            .get(header::SEC_WEBSOCKET_KEY)
+            .and_then(|key| HeaderValue::from_str(&derive_accept_key(key.as_bytes())).ok())
+        else {
+            return status(StatusCode::BAD_REQUEST);
+        };
+
+        let state = self.clone();
+        tokio::spawn(async move {
+            match hyper::upgrade::on(request).await {
+                Ok(upgraded) => {
+                    let socket =
+                        WebSocketStream::from_raw_socket(upgraded, Role::Server, Some(config()))
+                            .await;
+                    state.serve_connection(socket).await
+                }
+                Err(e) => tracing::warn!(error = %e, "WebSocket upgrade failed"),
+            }
+        });
+
+        let mut response = status(StatusCode::SWITCHING_PROTOCOLS);
+        let headers = response.headers_mut();
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+        headers.insert(header::SEC_WEBSOCKET_ACCEPT, accept);
+        response
+    }
+
+    // Runs the stdio loop over a pipe, with a bridge between the pipe's lines
+    // and the socket's messages
+    async fn serve_connection<S>(&self, socket: WebSocketStream<S>)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (pipe, server_end) = duplex(PIPE_CAPACITY);
+        let bridge = tokio::spawn(bridge(socket, pipe, self.server.clone()));
+
+        let (reader, writer) = split(server_end);
+        let mut transport = StdioTransport::with_io(BufReader::new(reader), writer);
+        if let Some(config) = self.keepalive {
+            transport = transport.with_keepalive(config);
+        }
+        match transport.serve(&self.server).await {
+            Ok(reason) => tracing::info!(%reason, "WebSocket session ended"),
+            Err(e) => tracing::warn!(error = %e, "WebSocket session failed"),
+        }
+        // Dropping the server's end closes the socket if the client has not
+        bridge.abort();
+    }
+}
+
+// Moves messages between the socket and the line-based pipe until either
+// side closes. Pings are answered by the socket itself. Large messages to
+// the client are compressed once its initialize has negotiated how.
+async fn bridge<S>(mut socket: WebSocketStream<S>, pipe: DuplexStream, server: Arc<McpServer>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (pipe_reader, mut pipe_writer) = split(pipe);
+    let mut lines = BufReader::new(pipe_reader).lines();
+    let mut initialized = false;
+    let mut compressor = None;
+
+    loop {
+        tokio::select! {
+            message = socket.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text.to_string(),
+                    Some(Ok(Message::Binary(bytes))) => match decompress_text(&bytes) {
+                        Ok(text) => text,
+                        Err(e) => {
+                            tracing::warn!(error = %e, "Ignoring binary WebSocket message");
+                            continue;
+                        }
+                    },
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        tracing::warn!(error = %e, "Dropping WebSocket client");
+                        return;
+                    }
+                };
+                if !initialized {
+                    if let Some(client) = ClientCapabilities::from_message(&text) {
+                        initialized = true;
+                        compressor = server.compressor(Some(&client));
+                    }
+                }
+                // Valid JSON only has line breaks between tokens, where any
+                // whitespace will do
+                let mut line = text.replace(['\r', '\n'], " ");
+                line.push('\n');
+                if pipe_writer.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => {
+                    if socket.send(frame(line, compressor)).await.is_err() {
+                        return;
+                    }
+                }
+                // The session ended, e.g. on a keepalive timeout
+                _ => break,
+            },
+        }
+    }
+    // Also sends the reply to a close the client started
+    let _ = socket.close(None).await;
+}
+
+// A binary message when compressing pays off, a text message otherwise
+fn frame(text: String, compressor: Option<Compressor>) -> Message {
+    match compressor.and_then(|compressor| compressor.compress(text.as_bytes())) {
+        Some(compressed) => Message::binary(compressed),
+        None => Message::text(text),
+    }
+}
+
+// The JSON-RPC message carried by a compressed binary message
+fn decompress_text(bytes: &[u8]) -> Result<String, WsError> {
+    let text = compression::decompress(bytes, MAX_MESSAGE_BYTES)?;
+    String::from_utf8(text)
+        .map_err(|_| WsError::Protocol("compressed message is not UTF-8".to_string()))
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+}
+
+/// How [`WebSocketClient`] retries a lost connection. The wait doubles after
+/// every failed attempt, up to `max_delay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Attempts per outage before pending requests fail.
+    pub max_attempts: u32,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            max_attempts: 10,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// How long to wait before attempt number `attempt`, counting from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay)
+    }
+}
+
+// TLS would need a connector this crate does not have
+fn check_url(url: &str) -> Result<(), WsError> {
+    let invalid = || WsError::InvalidUrl(url.to_string());
+    if !url.starts_with("ws://") {
+        return Err(invalid());
+    }
+    let request = url.into_client_request().map_err(|_| invalid())?;
+    if request.uri().host().is_none_or(str::is_empty) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// Opens a connection and performs the client side of the handshake
+async fn open(url: &str) -> Result<Socket, WsError> {
+    let (socket, _) =
+        tokio_tungstenite::connect_async_with_config(url, Some(config()), false).await?;
+    Ok(socket)
+}
+
+enum Command {
+    Request {
+        id: i64,
+        message: String,
+        reply: oneshot::Sender<Result<JsonRpcResponse, WsError>>,
+    },
+    Notify(String),
+}
+
+/// An MCP client connection over WebSocket that survives dropped
+/// connections. Dropping it closes the connection.
+pub struct WebSocketClient {
+    commands: mpsc::UnboundedSender<Command>,
+    notifications: Mutex<mpsc::UnboundedReceiver<JsonRpcRequest>>,
+    next_id: AtomicI64,
+    timeout: Duration,
+    compression: Vec<Compression>,
+}
+
+impl WebSocketClient {
+    /// Connects to `url`, e.g. `ws://127.0.0.1:8765/ws`. The first connection
+    /// is not retried, so a wrong address fails right away.
+    pub async fn connect(url: &str, policy: ReconnectPolicy) -> Result<Self, WsError> {
+        check_url(url)?;
+        let socket = open(url).await?;
+        let (commands, command_receiver) = mpsc::unbounded_channel();
+        let (notification_sender, notifications) = mpsc::unbounded_channel();
+
+        let connection = Connection {
+            url: url.to_string(),
+            policy,
+            pending: BTreeMap::new(),
+            initialize: None,
+            notifications: notification_sender,
+            compressor: None,
+        };
+        tokio::spawn(connection.run(socket, command_receiver));
+
+        Ok(Self {
+            commands,
+            notifications: Mutex::new(notifications),
+            next_id: AtomicI64::new(0),
+            timeout: DEFAULT_TIMEOUT,
+            compression: Vec::new(),
+        })
+    }
+
+    /// How long a request may take, reconnects included.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Accepts large messages compressed with `accepted`, preferred first.
+    /// `initialize` requests declare them unless their params already do.
+    pub fn with_compression(mut self, accepted: Vec<Compression>) -> Self {
+        self.compression = accepted;
+        self
+    }
+
+    /// Sends a request and returns its result. Error responses become
+    /// [`WsError::Rpc`].
+    pub async fn request(&self, method: &str, mut params: Value) -> Result<Value, WsError> {
+        let declared = params["capabilities"]["experimental"]
+            .get("compression")
+            .is_some();
+        if method == "initialize"
+            && !self.compression.is_empty()
+            && !declared
+            && (params.is_object() || params.is_null())
+        {
+            params["capabilities"]["experimental"]["compression"] =
+                serde_json::json!(self.compression);
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let request = JsonRpcRequest::new(RequestId::Number(id), method, params);
+        let (reply, response) = oneshot::channel();
+        self.commands
+            .send(Command::Request {
+                id,
+                message: serde_json::to_string(&request)?,
+                reply,
+            })
+            .map_err(|_| WsError::Closed)?;
+
+        let response = match tokio::time::timeout(self.timeout, response).await {
+            Ok(Ok(response)) => response?,
+            Ok(Err(_)) => return Err(WsError::Closed),
+            Err(_) => return Err(WsError::Timeout(self.timeout)),
+        };
+        match response.error {
+            Some(error) => Err(WsError::Rpc(error)),
+            None => Ok(response.result.unwrap_or_default()),
+        }
+    }
+
+    /// Sends a notification. Notifications sent while disconnected are lost.
+    pub fn notify(&self, method: &str, params: Value) -> Result<(), WsError> {
+        let message = serde_json::to_string(&JsonRpcRequest::notification(method, params))?;
+        self.commands
+            .send(Command::Notify(message))
+            .map_err(|_| WsError::Closed)
+    }
+
+    /// The next notification from the server, such as
+    /// `notifications/tools/list_changed`. `None` once the client has given up.
+    pub async fn next_notification(&self) -> Option<JsonRpcRequest> {
+        self.notifications.lock().await.recv().await
+    }
+}
+
+struct Pending {
+    message: String,
+    reply: oneshot::Sender<Result<JsonRpcResponse, WsError>>,
+}
+
+// The client's background task: owns the socket, matches responses to
+// requests, and reconnects when the socket goes away
+struct Connection {
+    url: String,
+    policy: ReconnectPolicy,
+    // By id, so a replay keeps the original order
+    pending: BTreeMap<i64, Pending>,
+    // Sent again first after every reconnect
+    initialize: Option<String>,
+    notifications: mpsc::UnboundedSender<JsonRpcRequest>,
+    // What the server agreed to in its answer to initialize
+    compressor: Option<Compressor>,
+}
+
+impl Connection {
+    async fn run(mut self, socket: Socket, mut commands: mpsc::UnboundedReceiver<Command>) {
+        let mut socket = Some(socket);
+        while let Some(mut current) = socket.take() {
+            // Both branches are cancel safe: the socket buffers partly
+            // read frames itself, and answers pings on its own
+            let closed_by_client = loop {
+                tokio::select! {
+                    command = commands.recv() => {
+                        let Some(command) = command else { break true };
+                        if self.send_command(&mut current, command).await.is_err() {
+                            break false;
+                        }
+                    }
+                    message = current.next() => {
+                        let text = match message {
+                            Some(Ok(Message::Text(text))) => text.to_string(),
+                            Some(Ok(Message::Binary(bytes))) => match decompress_text(&bytes) {
+                                Ok(text) => text,
+                                Err(e) => {
+                                    tracing::warn!(error = %e, "Ignoring message from the server");
+                                    continue;
+                                }
+                            },
+                            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break false,
+                            Some(Ok(_)) => continue,
+                        };
+                        if self.dispatch(&mut current, &text).await.is_err() {
+                            break false;
+                        }
+                    }
+                }
+            };
+
+            if closed_by_client {
+                let _ = current.close(None).await;
+                return;
+            }
+            tracing::warn!(pending = self.pending.len(), "WebSocket connection lost");
+            socket = self.reconnect().await;
+        }
+
+        let attempts = self.policy.max_attempts;
+        for (_, pending) in std::mem::take(&mut self.pending) {
+            let _ = pending.reply.send(Err(WsError::ReconnectFailed(attempts)));
+        }
+    }
+
+    async fn send_command(&mut self, socket: &mut Socket, command: Command) -> Result<(), WsError> {
+        match command {
+            Command::Request { id, message, reply } => {
+                if message.contains("\"method\":\"initialize\"") {
+                    self.initialize = Some(message.clone());
+                }
+                // Kept until answered, so a lost connection can replay it
+                let sent = socket.send(frame(message.clone(), self.compressor)).await;
+                self.pending.insert(id, Pending { message, reply });
+                Ok(sent?)
+            }
+            Command::Notify(message) => Ok(socket.send(frame(message, self.compressor)).await?),
+        }
+    }
+
+    async fn dispatch(&mut self, socket: &mut Socket, text: &str) -> Result<(), WsError> {
+        let Ok(message) = serde_json::from_str::<Value>(text) else {
+            tracing::warn!("Ignoring malformed message from the server");
+            return Ok(());
+        };
+
+        if message.get("method").is_some() {
+            let Ok(request) = serde_json::from_value::<JsonRpcRequest>(message) else {
+                return Ok(());
+            };
+            let Some(id) = request.id.clone() else {
+                let _ = self.notifications.send(request);
+                return Ok(());
+            };
+            // The server's keepalive pings are the only requests we answer
+            let response = match request.method.as_str() {
+                "ping" => JsonRpcResponse::success(Some(id), serde_json::json!({})),
+                method => {
+                    JsonRpcResponse::failure(Some(id), JsonRpcError::method_not_found(method))
+                }
+            };
+            let response = serde_json::to_string(&response)?;
+            return Ok(socket.send(Message::text(response)).await?);
+        }
+
+        let Ok(response) = serde_json::from_value::<JsonRpcResponse>(message) else {
+            return Ok(());
+        };
+        let negotiated = response.result.as_ref().and_then(|result| {
+            Compressor::from_capability(&result["capabilities"]["experimental"]["compression"])
+        });
+        if negotiated.is_some() {
+            self.compressor = negotiated;
+        }
+        // Answers to a replayed initialize have no caller waiting
+        if let Some(RequestId::Number(id)) = response.id {
+            if let Some(pending) = self.pending.remove(&id) {
+                let _ = pending.reply.send(Ok(response));
+            }
+        }
+        Ok(())
+    }
+
+    // Retries with backoff. On success, replays initialize and every request
+    // still waiting for an answer, uncompressed until the new connection has
+    // negotiated again.
+    async fn reconnect(&mut self) -> Option<Socket> {
+        self.compressor = None;
+        for attempt in 1..=self.policy.max_attempts {
+            tokio::time::sleep(self.policy.delay(attempt)).await;
+            // Callers that timed out no longer need their request
+            self.pending.retain(|_, pending| !pending.reply.is_closed());
+
+            let mut socket = match open(&self.url).await {
+                Ok(socket) => socket,
+                Err(e) => {
+                    tracing::warn!(attempt, error = %e, "Reconnect failed");
+                    continue;
+                }
+            };
+            let replay = self
+                .initialize
+                .iter()
+                .chain(self.pending.values().map(|pending| &pending.message));
+            let mut replayed = 0;
+            let mut failed = false;
+            for message in replay {
+                if socket.send(Message::text(message.clone())).await.is_err() {
+                    failed = true;
+                    break;
+                }
+                replayed += 1;
+            }
+            if failed {
+                continue;
+            }
+            tracing::info!(attempt, replayed, "Reconnected");
+            return Some(socket);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::McpError;
+    use crate::protocol::Tool;
+    use crate::registry::ToolRegistry;
+    use crate::server::ToolHandler;
+    use futures::future::BoxFuture;
+    use tokio::net::TcpListener;
+
+    struct Echo;
+
+    impl ToolHandler for Echo {
+        fn tool(&self) -> Tool {
+            Tool {
+                name: "echo".to_string(),
+                description: "Returns its arguments".to_string(),
+                input_schema: serde_json::json!({ "type": "object" }),
+                annotations: None,
+            }
+        }
+
+        fn call(&self, arguments: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+            Box::pin(async move { Ok(arguments) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_malformed_frames() {
+        // Client frames, masked with an all-zero key unless noted
+        let malformed: [(&str, Vec<u8>); 5] = [
+            ("fragmented ping", vec![0x09, 0x80, 0, 0, 0, 0]),
+            (
+                "oversized ping",
+                [vec![0x89, 0x80 | 126, 0, 126, 0, 0, 0, 0], vec![0; 126]].concat(),
+            ),
+            ("RSV1 set", vec![0xC1, 0x80 | 2, 0, 0, 0, 0, b'h', b'i']),
+            ("unmasked", vec![0x81, 2, b'h', b'i']),
+            (
+                "oversized message",
+                vec![0x81, 0x80 | 127, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+            ),
+        ];
+        for (name, frame) in malformed {
+            let (mut client, server) = duplex(1024);
+            let mut server =
+                WebSocketStream::from_raw_socket(server, Role::Server, Some(config())).await;
+            client
+                .write_all(&[0x81, 0x80 | 2, 0, 0, 0, 0, b'o', b'k'])
+                .await
+                .unwrap();
+            client.write_all(&frame).await.unwrap();
+
+            let message = server.next().await.unwrap().unwrap();
+            assert_eq!(message, Message::text("ok"), "{}", name);
+            assert!(
+                matches!(server.next().await, Some(Err(_))),
+                "{} was accepted",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_url_and_backoff() {
+        assert!(check_url("ws://127.0.0.1:8765/ws").is_ok());
+        assert!(check_url("ws://[::1]").is_ok());
+        assert!(check_url("wss://example.com/ws").is_err());
+        assert!(check_url("ws://:80/ws").is_err());
+
+        let policy = ReconnectPolicy::default();
+        let delays: Vec<u64> = (1..=8)
+            .map(|n| policy.delay(n).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1600, 3200, 5000, 5000]);
+    }
+    #[tokio::test]
+    async fn test_client_and_server() {
+        let registry = Arc::new(ToolRegistry::new("dynamic"));
+        let server = Arc::new(McpServer::new(registry.clone()));
+        let (addr, _running) = WebSocketTransport::new("127.0.0.1:0".parse().unwrap())
+            .spawn(server)
+            .unwrap();
+
+        let wrong_path = format!("ws://{}/other", addr);
+        let result = WebSocketClient::connect(&wrong_path, ReconnectPolicy::default()).await;
+        assert!(matches!(result, Err(WsError::Handshake(_))));
+
+        let url = format!("ws://{}/ws", addr);
+        let client = WebSocketClient::connect(&url, ReconnectPolicy::default())
+            .await
+            .unwrap()
+            .with_timeout(Duration::from_secs(5));
+        let initialized = client
+            .request(
+                "initialize",
+                serde_json::json!({ "protocolVersion": "2025-06-18" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(initialized["capabilities"]["tools"]["listChanged"], true);
+
+        registry.register(Echo);
+        let notification = client.next_notification().await.unwrap();
+        assert_eq!(notification.method, "notifications/tools/list_changed");
+
+        // Multi-line JSON is fine on the wire
+        let arguments = serde_json::json!({ "name": "echo", "arguments": { "text": "hi\nthere" } });
+        let result = client.request("tools/call", arguments).await.unwrap();
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("hi\\nthere"));
+
+        let error = client
+            .request("no/such/method", Value::Null)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, WsError::Rpc(ref error) if error.code == -32601));
+    }
+
+    #[tokio::test]
+    async fn test_compresses_large_messages_once_negotiated() {
+        let server = McpServer::new(Arc::new(
+            crate::server::ToolRouter::new("echo").with_handler(Echo),
+        ));
+        let (addr, _running) = WebSocketTransport::new("127.0.0.1:0".parse().unwrap())
+            .spawn(Arc::new(server))
+            .unwrap();
+        let url = format!("ws://{}/ws", addr);
+        let text = "all work and no play ".repeat(500);
+        let call = serde_json::json!({ "name": "echo", "arguments": { "text": text } });
+
+        // The client compresses its large requests and reads compressed answers
+        let client = WebSocketClient::connect(&url, ReconnectPolicy::default())
+            .await
+            .unwrap()
+            .with_timeout(Duration::from_secs(5))
+            .with_compression(vec![Compression::Gzip]);
+        let initialized = client.request("initialize", Value::Null).await.unwrap();
+        let negotiated = &initialized["capabilities"]["experimental"]["compression"];
+        assert_eq!(negotiated["algorithm"], "gzip");
+        let result = client.request("tools/call", call.clone()).await.unwrap();
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains(text.as_str()));
+
+        // On the wire, large answers are binary and small ones text
+        let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .unwrap();
+        let initialize = serde_json::json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": { "capabilities": { "experimental": { "compression": ["zstd"] } } }
+        });
+        socket
+            .send(Message::text(initialize.to_string()))
+            .await
+            .unwrap();
+        assert!(matches!(socket.next().await, Some(Ok(Message::Text(_)))));
+
+        let request = JsonRpcRequest::new(RequestId::Number(2), "tools/call", call);
+        let request = serde_json::to_vec(&request).unwrap();
+        let compressed = Compression::Zstd.compress(&request).unwrap();
+        socket.send(Message::binary(compressed)).await.unwrap();
+        let Some(Ok(Message::Binary(bytes))) = socket.next().await else {
+            panic!("expected a compressed answer");
+        };
+        assert_eq!(Compression::detect(&bytes), Some(Compression::Zstd));
+        let response: JsonRpcResponse =
+            serde_json::from_str(&decompress_text(&bytes).unwrap()).unwrap();
+        assert_eq!(response.id, Some(RequestId::Number(2)));
+    }
+
+    // Accepts a connection, reads one message from it, and answers it only
+    // if `answer` is set
+    async fn accept_one(listener: &TcpListener, answer: bool) -> Vec<String> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let mut received = Vec::new();
+        while let Some(Ok(Message::Text(text))) = socket.next().await {
+            received.push(text.to_string());
+            if !answer {
+                return received;
+            }
+            let request: JsonRpcRequest = serde_json::from_str(&text).unwrap();
+            let response = JsonRpcResponse::success(request.id, serde_json::json!(request.method));
+            let response = serde_json::to_string(&response).unwrap();
+            socket.send(Message::text(response)).await.unwrap();
+            if request.method == "tools/list" {
+                return received;
+            }
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_and_replays_pending_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+            max_attempts: 3,
+        };
+
+        let server = tokio::spawn(async move {
+            // The first connection drops the request, the second answers it
+            let first = accept_one(&listener, false).await;
+            let second = accept_one(&listener, true).await;
+            (first, second)
+        });
+        let client = WebSocketClient::connect(&url, policy)
+            .await
+            .unwrap()
+            .with_timeout(Duration::from_secs(5));
+        let result = client.request("tools/list", Value::Null).await.unwrap();
+        assert_eq!(result, "tools/list");
+
+        let (first, second) = server.await.unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(second, first);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(5),
+            max_delay: Duration::from_millis(5),
+            max_attempts: 2,
+        };
+        let server = tokio::spawn(async move {
+            accept_one(&listener, false).await;
+            // Nothing listens on the port any more
+        });
+        let client = WebSocketClient::connect(&url, policy).await.unwrap();
+        let error = client.request("tools/list", Value::Null).await.unwrap_err();
+        assert!(matches!(error, WsError::ReconnectFailed(2)));
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,212 @@
+//! YAML manifests and scripts, parsed with `yaml-rust2`.
+//!
+//! Documents are converted into a [`serde_json::Value`] and deserialized from
+//! there, so any type that reads JSON reads YAML too. Anchors and aliases,
+//! the core schema's tags (`!!str`, `!!int`, ...) and flow collections over
+//! several lines all work. A file holds one document, and mapping keys must be
+//! scalars, since JSON object keys are strings.
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value};
+use yaml_rust2::{ScanError, Yaml, YamlLoader};
+
+#[derive(Debug, thiserror::Error)]
+pub enum YamlError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+
+    #[error("{0}")]
+    Unsupported(String),
+
+    #[error("{0}")]
+    Invalid(#[from] serde_json::Error),
+}
+
+impl From<ScanError> for YamlError {
+    fn from(error: ScanError) -> Self {
+        Self::Syntax {
+            line: error.marker().line(),
+            message: error.info().to_string(),
+        }
+    }
+}
+
+/// Parses a document into a value.
+pub fn parse(text: &str) -> Result<Value, YamlError> {
+    let mut documents = YamlLoader::load_from_str(text)?;
+    if documents.len() > 1 {
+        return Err(YamlError::Unsupported(
+            "expected a single document".to_string(),
+        ));
+    }
+    match documents.pop() {
+        // A lone `---` is an empty document
+        None | Some(Yaml::BadValue) => Ok(Value::Null),
+        Some(document) => to_json(document),
+    }
+}
+
+/// Parses a document and deserializes it into `T`.
+pub fn from_str<T: DeserializeOwned>(text: &str) -> Result<T, YamlError> {
+    Ok(serde_json::from_value(parse(text)?)?)
+}
+
+fn to_json(node: Yaml) -> Result<Value, YamlError> {
+    Ok(match node {
+        Yaml::Null => Value::Null,
+        Yaml::Boolean(value) => Value::Bool(value),
+        Yaml::Integer(value) => Value::Number(value.into()),
+        // `.inf` and `.nan` have no JSON number
+        Yaml::Real(text) => match text.parse().ok().and_then(Number::from_f64) {
+            Some(number) => Value::Number(number),
+            None => Value::String(text),
+        },
+        Yaml::String(text) => Value::String(text),
+        Yaml::Array(items) => {
+            Value::Array(items.into_iter().map(to_json).collect::<Result<_, _>>()?)
+        }
+        Yaml::Hash(entries) => {
+            let mut map = Map::new();
+            for (key, value) in entries {
+                map.insert(key_string(key)?, to_json(value)?);
+            }
+            Value::Object(map)
+        }
+        // The loader resolves aliases itself and leaves a bad value where a
+        // tag does not fit its scalar, e.g. `!!int abc`
+        Yaml::Alias(_) | Yaml::BadValue => {
+            return Err(YamlError::Unsupported(
+                "a value does not match its tag or refers to an unknown anchor".to_string(),
+            ))
+        }
+    })
+}
+
+fn key_string(key: Yaml) -> Result<String, YamlError> {
+    match key {
+        Yaml::String(text) | Yaml::Real(text) => Ok(text),
+        Yaml::Integer(value) => Ok(value.to_string()),
+        Yaml::Boolean(value) => Ok(value.to_string()),
+        Yaml::Null => Ok("null".to_string()),
+        key => Err(YamlError::Unsupported(format!(
+            "mapping keys must be scalars, found {:?}",
+            key
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parses_manifest_shapes() {
+        let text = r#"
+---
+# A comment
+name: demo server   # trailing comment
+version: "1.0"
+port: 8080
+ratio: 0.5
+enabled: true
+nothing: ~
+url: http://localhost:8080/x#y
+tags: [a, 'b c', 3]
+limits: { max: 10, label: "x, y" }
+tools:
+  - name: greet
+    arguments:
+      - who
+      - - nested
+  - name: "quoted: key"
+    empty:
+steps:
+- one
+- two
+"#;
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({
+                "name": "demo server",
+                "version": "1.0",
+                "port": 8080,
+                "ratio": 0.5,
+                "enabled": true,
+                "nothing": null,
+                "url": "http://localhost:8080/x#y",
+                "tags": ["a", "b c", 3],
+                "limits": { "max": 10, "label": "x, y" },
+                "tools": [
+                    { "name": "greet", "arguments": ["who", ["nested"]] },
+                    { "name": "quoted: key", "empty": null }
+                ],
+                "steps": ["one", "two"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_block_scalars() {
+        let text = "literal: |\n  SELECT *\n    FROM t\n\n  WHERE x\nfolded: >-\n  one\n  two\n\n  three\nkept: |+\n  a\n\nlast: done\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({
+                "literal": "SELECT *\n  FROM t\n\nWHERE x\n",
+                "folded": "one two\nthree",
+                "kept": "a\n\n",
+                "last": "done"
+            })
+        );
+    }
+
+    #[test]
+    fn test_anchors_tags_and_flow_collections() {
+        let text = r#"
+defaults: &defaults
+  timeout: 30
+  retries: 2
+primary: *defaults
+port: !!str 8080
+count: !!int "3"
+hosts: [
+  alpha,
+  "beta",
+]
+labels: {
+  team: core,
+  tier: 1
+}
+"#;
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({
+                "defaults": { "timeout": 30, "retries": 2 },
+                "primary": { "timeout": 30, "retries": 2 },
+                "port": "8080",
+                "count": "3",
+                "hosts": ["alpha", "beta"],
+                "labels": { "team": "core", "tier": 1 }
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_what_it_cannot_read() {
+        for text in [
+            "a: 1\n  b: 2",
+            "a: [1, 2",
+            "a: 1\na: 2",
+            "- a\nb: 1",
+            "a: \"unterminated",
+            "a: !!int abc",
+            "a: *missing",
+            "? [1, 2]\n: x",
+            "a: 1\n---\nb: 2",
+        ] {
+            assert!(parse(text).is_err(), "{:?} should not parse", text);
+        }
+        let error = parse("a: 1\nb: c: d\n").unwrap_err();
+        assert!(error.to_string().starts_with("line 2:"), "{}", error);
+    }
+}
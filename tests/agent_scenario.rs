@@ -0,0 +1,256 @@
+// End-to-end scenario: the example 04 client acts as an AI agent.
+//
+// The agent is attached to three example servers (documents, files and
+// notifications), discovers their tools, and chains calls to research a topic,
+// write a summary to disk, and notify the user. The test then checks the side
+// effects each server should have produced. A second test checks that the
+// typed responses the client expects still match what the servers return.
+
+#[path = "../src/examples/example_04_simple_client.rs"]
+#[allow(dead_code)]
+mod client;
+
+#[path = "../src/examples/example_05_resource_provider.rs"]
+#[allow(dead_code)]
+mod documents;
+
+#[path = "../src/examples/example_07_file_operations.rs"]
+#[allow(dead_code)]
+mod files;
+
+#[path = "../src/examples/example_11_monitoring.rs"]
+#[allow(dead_code)]
+mod monitoring;
+
+#[path = "../src/examples/example_14_notification_service.rs"]
+#[allow(dead_code)]
+mod notifications;
+
+use client::{DirectoryListing, FileInfo, SimpleMcpClient, SystemMetrics, ToolCallRequest};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+const USER_ID: &str = "analyst";
+
+// Calls a tool and unwraps the result, failing the test with the server's error
+async fn call(client: &SimpleMcpClient, tool_name: &str, arguments: Value) -> Value {
+    let response = client
+        .call_tool(ToolCallRequest {
+            tool_name: tool_name.to_string(),
+            arguments,
+        })
+        .await
+        .unwrap();
+
+    assert!(
+        response.success,
+        "{} failed: {:?}",
+        tool_name, response.error
+    );
+    response.result.unwrap()
+}
+
+async fn notification_service() -> notifications::NotificationService {
+    let service = notifications::NotificationService::new();
+    service
+        .create_template(
+            "research_summary".to_string(),
+            "Summary ready: {{topic}}".to_string(),
+            "Your summary of {{topic}} is at {{path}}".to_string(),
+            vec![notifications::NotificationChannel::InApp],
+        )
+        .await;
+
+    let subscription = serde_json::from_value(json!({
+        "user_id": USER_ID,
+        "channel": "InApp",
+        "endpoint": format!("inbox://{}", USER_ID),
+        "is_active": true,
+        "preferences": {}
+    }))
+    .unwrap();
+    service
+        .subscribe_user(USER_ID.to_string(), subscription)
+        .await
+        .unwrap();
+
+    service
+}
+
+#[tokio::test]
+async fn test_agent_researches_summarizes_and_notifies() {
+    let workspace = tempfile::tempdir().unwrap();
+    let notes_path = workspace.path().join("rust_notes.txt");
+    let summary_path = workspace.path().join("rust_summary.md");
+    std::fs::write(
+        &notes_path,
+        "Team notes: adopt Tokio for the new ingestion service.",
+    )
+    .unwrap();
+
+    let file_server = files::FileOperationsServer::new(files::FileOperationsConfig {
+        allowed_directories: vec![workspace.path().to_path_buf()],
+        ..files::FileOperationsConfig::default()
+    });
+
+    let mut agent = SimpleMcpClient::new("in-process://agent");
+    agent.attach_server(Arc::new(documents::ResourceProviderServer::new()));
+    agent.attach_server(Arc::new(file_server));
+    agent.attach_server(Arc::new(notification_service().await));
+
+    // Discover tools across all three servers
+    let tools = agent.discover_tools().await.unwrap();
+    for (tool, server) in [
+        ("search_documents", "resource_provider"),
+        ("read_file", "file_operations"),
+        ("write_file", "file_operations"),
+        ("send_notification", "notification_service"),
+    ] {
+        assert!(
+            tools
+                .iter()
+                .any(|d| d.tool.name == tool && d.server == server),
+            "{} should be discovered on {}",
+            tool,
+            server
+        );
+    }
+
+    // Annotations travel with the tools, so the agent knows which calls to
+    // confirm with the user
+    let needs_confirmation = |name: &str| {
+        tools
+            .iter()
+            .find(|d| d.tool.name == name)
+            .and_then(|d| d.tool.annotations.as_ref())
+            .map(|annotations| annotations.needs_confirmation())
+    };
+    assert_eq!(needs_confirmation("read_file"), Some(false));
+    assert_eq!(needs_confirmation("write_file"), Some(true));
+
+    // 1. Search the document store
+    let search = call(
+        &agent,
+        "search_documents",
+        json!({ "query": "Rust", "limit": 5 }),
+    )
+    .await;
+    let titles: Vec<String> = search["matches"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["title"].as_str().unwrap().to_string())
+        .collect();
+    assert!(!titles.is_empty());
+
+    // 2. Read the team's local notes
+    let notes = call(
+        &agent,
+        "read_file",
+        json!({ "file_path": notes_path.to_string_lossy() }),
+    )
+    .await;
+    let notes = notes["content"].as_str().unwrap();
+
+    // 3. Write a summary combining both sources
+    let summary = format!(
+        "# Rust research\n\n## Sources\n{}\n\n## Notes\n{}\n",
+        titles
+            .iter()
+            .map(|t| format!("- {}", t))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        notes
+    );
+    call(
+        &agent,
+        "write_file",
+        json!({ "file_path": summary_path.to_string_lossy(), "content": summary }),
+    )
+    .await;
+
+    // 4. Notify the user
+    let sent = call(
+        &agent,
+        "send_notification",
+        json!({
+            "user_id": USER_ID,
+            "template_name": "research_summary",
+            "variables": { "topic": "Rust", "path": summary_path.to_string_lossy() }
+        }),
+    )
+    .await;
+    assert_eq!(sent["notifications_queued"], 1);
+
+    // Side effect: the summary is on disk with content from both sources
+    let written = std::fs::read_to_string(&summary_path).unwrap();
+    assert!(written.contains("Rust Programming Language Overview"));
+    assert!(written.contains("adopt Tokio"));
+
+    // Side effect: the notification lands in the user's inbox. Delivery runs
+    // on a background worker, so poll briefly.
+    // The inbox only exists once the first message is delivered.
+    let mut inbox = Value::Null;
+    for _ in 0..50 {
+        let response = agent
+            .call_tool(ToolCallRequest {
+                tool_name: "list_inbox_messages".to_string(),
+                arguments: json!({ "user_id": USER_ID }),
+            })
+            .await
+            .unwrap();
+        if let Some(result) = response.result {
+            inbox = result;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(inbox["count"], 1);
+    assert_eq!(inbox["messages"][0]["subject"], "Summary ready: Rust");
+    assert!(inbox["messages"][0]["body"]
+        .as_str()
+        .unwrap()
+        .contains(&*summary_path.to_string_lossy()));
+}
+
+#[tokio::test]
+async fn test_typed_responses_match_the_servers() {
+    let workspace = tempfile::tempdir().unwrap();
+    std::fs::write(workspace.path().join("notes.txt"), "hello").unwrap();
+    let file_server = files::FileOperationsServer::new(files::FileOperationsConfig {
+        allowed_directories: vec![workspace.path().to_path_buf()],
+        ..files::FileOperationsConfig::default()
+    });
+
+    let mut agent = SimpleMcpClient::new("in-process://agent").expect_known_responses();
+    agent.attach_server(Arc::new(file_server));
+    agent.attach_server(Arc::new(monitoring::MonitoringServer::new()));
+
+    let listing: DirectoryListing = agent
+        .call_typed(
+            "list_directory",
+            json!({ "directory_path": workspace.path().to_string_lossy() }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(listing.total_count, 1);
+    assert_eq!(listing.files[0].name, "notes.txt");
+
+    let info: FileInfo = agent
+        .call_typed(
+            "get_file_info",
+            json!({ "file_path": workspace.path().join("notes.txt").to_string_lossy() }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(info.size, 5);
+
+    let metrics: SystemMetrics = agent
+        .call_typed("get_current_metrics", json!({}))
+        .await
+        .unwrap();
+    assert!(metrics.cpu_usage_percent <= 100.0);
+
+    assert_eq!(agent.drift(), Vec::new());
+}